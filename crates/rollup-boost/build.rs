@@ -0,0 +1,10 @@
+fn main() {
+    // The protobuf types of the flashblocks are only generated for the `proto` feature,
+    // so that other builds do not need `protoc`
+    #[cfg(feature = "proto")]
+    {
+        println!("cargo:rerun-if-changed=proto/flashblocks.proto");
+        prost_build::compile_protos(&["proto/flashblocks.proto"], &["proto"])
+            .expect("failed to generate the flashblocks protobuf types");
+    }
+}
@@ -0,0 +1,602 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use alloy_primitives::B256;
+use alloy_rpc_types_engine::PayloadId;
+use clap::Parser;
+use flate2::{Compression, write::GzEncoder};
+use metrics::{counter, gauge};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tracing::warn;
+use url::Url;
+
+use crate::{FlashblocksPayloadV1, OpExecutionPayloadEnvelope};
+
+/// Number of sealed payloads waiting to be written before new ones are dropped.
+const QUEUE_SIZE: usize = 64;
+
+/// Extension of the files of the archived payloads.
+const ARCHIVE_EXTENSION: &str = "json";
+
+/// File of the archive listing the payloads already uploaded, one per line.
+const UPLOADED_FILE: &str = "uploaded.txt";
+
+#[derive(Parser, Clone, Debug)]
+pub struct ArchiveArgs {
+    /// Directory the payloads assembled from flashblocks are archived in once returned,
+    /// with their flashblocks. Payloads are not archived if not set
+    #[arg(long, env, value_name = "PATH")]
+    pub archive_path: Option<PathBuf>,
+
+    /// S3 bucket the archived payloads are uploaded to, not uploaded if not set. The
+    /// credentials are read from the `AWS_*` environment variables. Requires the `s3`
+    /// feature
+    #[arg(long, env)]
+    pub archive_s3_bucket: Option<String>,
+
+    /// Prefix of the keys of the uploaded payloads
+    #[arg(long, env, default_value = "rollup-boost")]
+    pub archive_s3_prefix: String,
+
+    /// Endpoint of an S3-compatible object store, such as MinIO or R2. AWS if not set
+    #[arg(long, env)]
+    pub archive_s3_endpoint: Option<Url>,
+
+    /// Region of the bucket
+    #[arg(long, env, default_value = "us-east-1")]
+    pub archive_s3_region: String,
+
+    /// Days the uploaded payloads are kept in the bucket, forever if not set
+    #[arg(long, env)]
+    pub archive_s3_retention_days: Option<u64>,
+
+    /// Interval in seconds at which the new archived payloads are uploaded
+    #[arg(long, env, default_value = "60")]
+    pub archive_upload_interval_secs: u64,
+
+    /// Compression of the uploaded payloads
+    #[arg(long, env, default_value = "gzip")]
+    pub archive_compression: ArchiveCompression,
+
+    /// Seconds the payloads are kept in the local archive, forever if not set
+    #[arg(long, env)]
+    pub archive_retention_secs: Option<u64>,
+
+    /// Number of most recent blocks whose payloads are kept in the local archive,
+    /// unlimited if not set
+    #[arg(long, env)]
+    pub archive_max_blocks: Option<u64>,
+
+    /// Maximum size in bytes of the local archive, the payloads of the oldest blocks are
+    /// deleted first. Unlimited if not set
+    #[arg(long, env)]
+    pub archive_max_bytes: Option<u64>,
+
+    /// Interval in seconds at which the payloads beyond the retention are deleted from the
+    /// local archive
+    #[arg(long, env, default_value = "300")]
+    pub archive_compaction_interval_secs: u64,
+}
+
+/// Limits on the payloads kept in the local archive, so that long-running nodes do not
+/// need an external job to prune it.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ArchiveRetention {
+    /// Age of the files of the payloads after which they are deleted
+    pub max_age: Option<Duration>,
+    /// Number of most recent blocks whose payloads are kept
+    pub max_blocks: Option<u64>,
+    /// Size of the archive beyond which the payloads of the oldest blocks are deleted
+    pub max_bytes: Option<u64>,
+}
+
+impl ArchiveRetention {
+    pub fn from_args(args: &ArchiveArgs) -> Self {
+        Self {
+            max_age: args.archive_retention_secs.map(Duration::from_secs),
+            max_blocks: args.archive_max_blocks,
+            max_bytes: args.archive_max_bytes,
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.max_age.is_none() && self.max_blocks.is_none() && self.max_bytes.is_none()
+    }
+}
+
+/// Compression of the archived payloads uploaded to the object store.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ArchiveCompression {
+    None,
+    #[default]
+    Gzip,
+}
+
+impl ArchiveCompression {
+    /// Suffix of the keys of the payloads compressed this way.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveCompression::None => "",
+            ArchiveCompression::Gzip => ".gz",
+        }
+    }
+
+    pub fn compress(&self, data: Vec<u8>) -> io::Result<Vec<u8>> {
+        match self {
+            ArchiveCompression::None => Ok(data),
+            ArchiveCompression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(&data)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// A payload returned by rollup-boost, with the flashblocks it was assembled from, as
+/// archived for audits of externally built blocks.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SealedPayload {
+    pub payload_id: PayloadId,
+    pub block_number: u64,
+    pub block_hash: B256,
+    /// Unix timestamp at which the payload was returned, in milliseconds
+    pub sealed_at: u64,
+    /// Version of the `engine_getPayload` call the payload was returned to
+    pub version: String,
+    /// The payload envelope, as returned by `engine_getPayload`
+    pub envelope: Value,
+    /// The flashblocks the payload was assembled from, in order, without their metadata
+    pub flashblocks: Vec<FlashblocksPayloadV1>,
+}
+
+impl SealedPayload {
+    pub fn new(
+        payload_id: PayloadId,
+        envelope: &OpExecutionPayloadEnvelope,
+        flashblocks: Vec<FlashblocksPayloadV1>,
+    ) -> serde_json::Result<Self> {
+        Ok(Self {
+            payload_id,
+            block_number: envelope.block_number(),
+            block_hash: envelope.block_hash(),
+            sealed_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            version: envelope.version().as_str().to_string(),
+            envelope: envelope.to_json()?,
+            flashblocks,
+        })
+    }
+
+    /// Name of the file of the payload in the archive, sorting by block number.
+    pub fn file_name(&self) -> String {
+        format!(
+            "{:020}-{}.{ARCHIVE_EXTENSION}",
+            self.block_number, self.payload_id
+        )
+    }
+}
+
+/// A local archive of the payloads assembled from flashblocks, one JSON file per payload,
+/// optionally uploaded to an S3-compatible object store.
+///
+/// Payloads are written in the background and never delay `engine_getPayload`. They are
+/// dropped if the disk cannot keep up.
+#[derive(Debug)]
+pub struct PayloadArchive {
+    dir: PathBuf,
+    sender: mpsc::Sender<SealedPayload>,
+}
+
+impl PayloadArchive {
+    /// Starts writing the payloads to `dir`. Must be called from a tokio runtime.
+    pub fn open(dir: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+        tokio::spawn(write_payloads(dir.clone(), receiver));
+        Ok(Self { dir, sender })
+    }
+
+    /// Opens the archive, and starts uploading and compacting it if they are enabled by
+    /// the arguments.
+    pub fn from_args(args: &ArchiveArgs) -> eyre::Result<Option<Self>> {
+        let Some(dir) = &args.archive_path else {
+            if args.archive_s3_bucket.is_some() {
+                eyre::bail!("--archive-s3-bucket requires --archive-path");
+            }
+            return Ok(None);
+        };
+        let archive = Self::open(dir.clone())?;
+
+        let retention = ArchiveRetention::from_args(args);
+        if !retention.is_unlimited() {
+            spawn_compaction(
+                dir.clone(),
+                retention,
+                Duration::from_secs(args.archive_compaction_interval_secs.max(1)),
+            );
+        }
+
+        if let Some(bucket) = &args.archive_s3_bucket {
+            #[cfg(feature = "s3")]
+            {
+                s3::ArchiveUploader::new(dir.clone(), bucket, args)?.spawn(Duration::from_secs(
+                    args.archive_upload_interval_secs.max(1),
+                ));
+            }
+
+            #[cfg(not(feature = "s3"))]
+            eyre::bail!(
+                "cannot upload the archive to {bucket}, rollup-boost was built without the s3 feature"
+            );
+        }
+        Ok(Some(archive))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The archived payload of `payload_id`, the most recent one if it was built more than
+    /// once.
+    pub fn load(&self, payload_id: PayloadId) -> io::Result<Option<SealedPayload>> {
+        let suffix = format!("-{payload_id}.{ARCHIVE_EXTENSION}");
+        let Some(path) = archived_files(&self.dir)?
+            .into_iter()
+            .rev()
+            .find(|path| path.to_str().is_some_and(|path| path.ends_with(&suffix)))
+        else {
+            return Ok(None);
+        };
+        Ok(Some(serde_json::from_slice(&fs::read(path)?)?))
+    }
+
+    /// Queues a payload to be archived.
+    pub fn record(&self, payload: SealedPayload) {
+        if self.sender.try_send(payload).is_err() {
+            counter!("archive.dropped").increment(1);
+        }
+    }
+}
+
+async fn write_payloads(dir: PathBuf, mut receiver: mpsc::Receiver<SealedPayload>) {
+    while let Some(payload) = receiver.recv().await {
+        match write_payload(&dir, &payload) {
+            Ok(()) => counter!("archive.payloads").increment(1),
+            Err(e) => {
+                counter!("archive.write_failed").increment(1);
+                warn!(message = "failed to archive payload", payload_id = %payload.payload_id, error = %e);
+            }
+        }
+    }
+}
+
+/// Writes the payload to a temporary file renamed once complete, so that readers of the
+/// archive never see a partial payload.
+fn write_payload(dir: &Path, payload: &SealedPayload) -> io::Result<()> {
+    let path = dir.join(payload.file_name());
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_vec(payload)?)?;
+    fs::rename(tmp, path)
+}
+
+/// The files of the payloads archived in `dir`, lowest block first.
+pub fn archived_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == ARCHIVE_EXTENSION) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Block number of an archived payload, from the name of its file.
+fn archived_block_number(path: &Path) -> Option<u64> {
+    path.file_stem()?.to_str()?.split_once('-')?.0.parse().ok()
+}
+
+fn spawn_compaction(dir: PathBuf, retention: ArchiveRetention, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = compact_archive(&dir, &retention) {
+                warn!(message = "failed to compact the payload archive", error = %e);
+            }
+        }
+    });
+}
+
+/// Deletes the archived payloads beyond the retention, oldest block first, and drops the
+/// deleted payloads from the list of the uploaded ones. Returns the number of payloads
+/// deleted.
+pub fn compact_archive(dir: &Path, retention: &ArchiveRetention) -> io::Result<usize> {
+    let mut files = vec![];
+    for path in archived_files(dir)? {
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            // Deleted since it was listed
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        files.push((path, metadata.len(), metadata.modified()?));
+    }
+
+    let latest_block = files
+        .iter()
+        .filter_map(|(path, _, _)| archived_block_number(path))
+        .max();
+    let expired_before = retention
+        .max_age
+        .and_then(|max_age| SystemTime::now().checked_sub(max_age));
+    let mut size: u64 = files.iter().map(|(_, len, _)| len).sum();
+    let mut deleted = 0;
+    let mut kept = HashSet::new();
+    for (path, len, modified) in files {
+        let too_old = expired_before.is_some_and(|before| modified < before);
+        let too_far = match (retention.max_blocks, latest_block) {
+            (Some(max_blocks), Some(latest)) => {
+                archived_block_number(&path).is_some_and(|block| block + max_blocks <= latest)
+            }
+            _ => false,
+        };
+        let too_large = retention
+            .max_bytes
+            .is_some_and(|max_bytes| size > max_bytes);
+        if !(too_old || too_far || too_large) {
+            kept.extend(
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .map(str::to_string),
+            );
+            continue;
+        }
+
+        let removed = fs::remove_file(&path);
+        if let Some(e) = removed
+            .err()
+            .filter(|e| e.kind() != io::ErrorKind::NotFound)
+        {
+            return Err(e);
+        }
+        size -= len;
+        deleted += 1;
+    }
+    counter!("archive.pruned").increment(deleted as u64);
+    gauge!("archive.size_bytes").set(size as f64);
+
+    if deleted > 0 {
+        compact_uploaded(dir, &kept)?;
+    }
+    Ok(deleted)
+}
+
+/// Rewrites the list of the uploaded payloads without those no longer archived. A payload
+/// uploaded while the list is rewritten may be listed out, and is uploaded again.
+fn compact_uploaded(dir: &Path, kept: &HashSet<String>) -> io::Result<()> {
+    let path = dir.join(UPLOADED_FILE);
+    let uploaded = match fs::read_to_string(&path) {
+        Ok(uploaded) => uploaded,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let compacted: String = uploaded
+        .lines()
+        .filter(|name| kept.contains(*name))
+        .map(|name| format!("{name}\n"))
+        .collect();
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, compacted)?;
+    fs::rename(tmp, path)
+}
+
+#[cfg(feature = "s3")]
+mod s3 {
+    use std::{
+        collections::HashSet,
+        fs::{self, OpenOptions},
+        io::{self, Write},
+        path::PathBuf,
+        sync::Arc,
+        time::{Duration, Instant, SystemTime},
+    };
+
+    use futures::TryStreamExt;
+    use metrics::counter;
+    use object_store::{ObjectStore, PutPayload, aws::AmazonS3Builder, path::Path as ObjectPath};
+    use tracing::{debug, warn};
+
+    use super::{ArchiveArgs, ArchiveCompression, UPLOADED_FILE, archived_files};
+
+    /// Interval at which the payloads past the retention are deleted from the bucket.
+    const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+    pub(super) struct ArchiveUploader {
+        store: Arc<dyn ObjectStore>,
+        dir: PathBuf,
+        prefix: String,
+        compression: ArchiveCompression,
+        retention: Option<Duration>,
+    }
+
+    impl ArchiveUploader {
+        pub(super) fn new(dir: PathBuf, bucket: &str, args: &ArchiveArgs) -> eyre::Result<Self> {
+            let mut builder = AmazonS3Builder::from_env()
+                .with_bucket_name(bucket)
+                .with_region(&args.archive_s3_region);
+            if let Some(endpoint) = &args.archive_s3_endpoint {
+                builder = builder
+                    .with_endpoint(endpoint.as_str())
+                    .with_allow_http(endpoint.scheme() == "http");
+            }
+
+            Ok(Self {
+                store: Arc::new(builder.build()?),
+                dir,
+                prefix: args.archive_s3_prefix.trim_matches('/').to_string(),
+                compression: args.archive_compression,
+                retention: args
+                    .archive_s3_retention_days
+                    .map(|days| Duration::from_secs(days * 24 * 3600)),
+            })
+        }
+
+        /// Uploads the new archived payloads every `interval`, and deletes the expired
+        /// ones from the bucket.
+        pub(super) fn spawn(self, interval: Duration) {
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(interval);
+                let mut last_pruned: Option<Instant> = None;
+                loop {
+                    ticker.tick().await;
+                    if let Err(e) = self.upload_pending().await {
+                        warn!(message = "failed to upload the archived payloads", error = %e);
+                    }
+
+                    let prune = self.retention.is_some()
+                        && last_pruned.is_none_or(|at| at.elapsed() >= PRUNE_INTERVAL);
+                    if prune {
+                        if let Err(e) = self.prune().await {
+                            warn!(message = "failed to delete the expired archived payloads", error = %e);
+                        }
+                        last_pruned = Some(Instant::now());
+                    }
+                }
+            });
+        }
+
+        async fn upload_pending(&self) -> eyre::Result<()> {
+            let uploaded_path = self.dir.join(UPLOADED_FILE);
+            let mut uploaded = match fs::read_to_string(&uploaded_path) {
+                Ok(uploaded) => uploaded.lines().map(str::to_string).collect(),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => HashSet::new(),
+                Err(e) => return Err(e.into()),
+            };
+
+            for path in archived_files(&self.dir)? {
+                let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                    continue;
+                };
+                if uploaded.contains(name) {
+                    continue;
+                }
+
+                let data = self.compression.compress(fs::read(&path)?)?;
+                let location = ObjectPath::from(format!(
+                    "{}/{name}{}",
+                    self.prefix,
+                    self.compression.extension()
+                ));
+                self.store.put(&location, PutPayload::from(data)).await?;
+
+                let mut file = OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open(&uploaded_path)?;
+                writeln!(file, "{name}")?;
+                uploaded.insert(name.to_string());
+                counter!("archive.uploaded").increment(1);
+                debug!(message = "uploaded archived payload", %location);
+            }
+            Ok(())
+        }
+
+        async fn prune(&self) -> eyre::Result<()> {
+            let Some(expired_before) = self
+                .retention
+                .and_then(|retention| SystemTime::now().checked_sub(retention))
+            else {
+                return Ok(());
+            };
+
+            let prefix = ObjectPath::from(self.prefix.as_str());
+            let expired = self
+                .store
+                .list(Some(&prefix))
+                .try_filter(|meta| {
+                    std::future::ready(SystemTime::from(meta.last_modified) < expired_before)
+                })
+                .map_ok(|meta| meta.location)
+                .try_collect::<Vec<_>>()
+                .await?;
+            for location in expired {
+                self.store.delete(&location).await?;
+                counter!("archive.expired").increment(1);
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_archive_compression() -> eyre::Result<()> {
+        let data = br#"{"payload_id":"0x0101010101010101"}"#.to_vec();
+        assert_eq!(ArchiveCompression::None.compress(data.clone())?, data);
+
+        let compressed = ArchiveCompression::Gzip.compress(data.clone())?;
+        let mut decompressed = vec![];
+        flate2::read::GzDecoder::new(compressed.as_slice()).read_to_end(&mut decompressed)?;
+        assert_eq!(decompressed, data);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact_archive() -> eyre::Result<()> {
+        let dir = std::env::temp_dir().join(format!("rollup-boost-archive-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        let names: Vec<_> = (1..=5)
+            .map(|block| format!("{block:020}-0x0101010101010101.json"))
+            .collect();
+        for name in &names {
+            fs::write(dir.join(name), [0; 100])?;
+        }
+        fs::write(
+            dir.join(UPLOADED_FILE),
+            format!("{}\n{}\n", names[0], names[4]),
+        )?;
+
+        // Only the payloads of the last 4 blocks are kept
+        let retention = ArchiveRetention {
+            max_blocks: Some(4),
+            ..Default::default()
+        };
+        assert_eq!(compact_archive(&dir, &retention)?, 1);
+        assert_eq!(archived_files(&dir)?.len(), 4);
+        assert_eq!(
+            fs::read_to_string(dir.join(UPLOADED_FILE))?,
+            format!("{}\n", names[4])
+        );
+
+        // The payloads of the oldest blocks are deleted until the archive fits
+        let retention = ArchiveRetention {
+            max_bytes: Some(250),
+            ..Default::default()
+        };
+        assert_eq!(compact_archive(&dir, &retention)?, 2);
+        assert_eq!(
+            archived_files(&dir)?,
+            [dir.join(&names[3]), dir.join(&names[4])]
+        );
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,302 @@
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use clap::Parser;
+use futures::{FutureExt as _, TryFutureExt as _};
+use http::{HeaderMap, header::AUTHORIZATION};
+use jsonrpsee::core::BoxError;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tower::{Layer, Service};
+use tracing::warn;
+
+use crate::{ExecutionMode, Request, Response, ws::unauthorized};
+
+/// Caller recorded for debug API requests when no auth tokens are configured.
+pub const UNAUTHENTICATED_CALLER: &str = "unauthenticated";
+
+#[derive(Parser, Clone, Debug)]
+pub struct AuditArgs {
+    /// Path to the append-only file execution mode changes are recorded in. Changes are
+    /// only kept in memory if not set
+    #[arg(long, env, value_name = "PATH")]
+    pub audit_log_path: Option<PathBuf>,
+
+    /// Path to a JSON file mapping the name of each debug API caller to its bearer
+    /// token. The debug API is not authenticated if not set
+    #[arg(long, env, value_name = "PATH")]
+    pub debug_auth_tokens_path: Option<PathBuf>,
+
+    /// Bearer token sent by the debug commands
+    #[arg(long, env)]
+    pub debug_auth_token: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum AuditError {
+    #[error("invalid audit log entry on line {line}: {source}")]
+    InvalidEntry {
+        line: usize,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// A change of the execution mode, as returned by `debug_getAuditLog`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AuditEntry {
+    /// Unix timestamp of the change, in seconds
+    pub timestamp: u64,
+    /// Name of the debug API caller that made the change
+    pub caller: String,
+    pub old_mode: ExecutionMode,
+    pub new_mode: ExecutionMode,
+}
+
+impl AuditEntry {
+    pub fn new(caller: String, old_mode: ExecutionMode, new_mode: ExecutionMode) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            caller,
+            old_mode,
+            new_mode,
+        }
+    }
+}
+
+/// Records every execution mode change made through the debug API. Flipping the mode of
+/// a sequencer is security sensitive, so entries are appended to a file, one JSON
+/// object per line, and synced before the change takes effect.
+#[derive(Debug, Default)]
+pub struct AuditLog {
+    path: Option<PathBuf>,
+    entries: Mutex<Vec<AuditEntry>>,
+}
+
+impl AuditLog {
+    /// Opens the audit log at `path`, loading the entries recorded by previous runs.
+    pub fn open(path: PathBuf) -> Result<Self, AuditError> {
+        let mut entries = vec![];
+        match fs::File::open(&path) {
+            Ok(file) => {
+                for (i, line) in BufReader::new(file).lines().enumerate() {
+                    let line = line?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let entry =
+                        serde_json::from_str(&line).map_err(|source| AuditError::InvalidEntry {
+                            line: i + 1,
+                            source,
+                        })?;
+                    entries.push(entry);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        Ok(Self {
+            path: Some(path),
+            entries: Mutex::new(entries),
+        })
+    }
+
+    pub fn from_args(args: &AuditArgs) -> Result<Self, AuditError> {
+        match &args.audit_log_path {
+            Some(path) => Self::open(path.clone()),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn record(&self, entry: AuditEntry) -> Result<(), AuditError> {
+        let mut entries = self.entries.lock();
+        if let Some(path) = &self.path {
+            let mut line = serde_json::to_vec(&entry)?;
+            line.push(b'\n');
+
+            let mut file = OpenOptions::new()
+                .append(true)
+                .create(true)
+                .mode(0o600)
+                .open(path)?;
+            file.write_all(&line)?;
+            file.sync_data()?;
+        }
+        entries.push(entry);
+        Ok(())
+    }
+
+    pub fn entries(&self) -> Vec<AuditEntry> {
+        self.entries.lock().clone()
+    }
+}
+
+/// Identity of the caller of a debug API request, inserted in the request extensions by
+/// the [`DebugAuthLayer`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Caller(pub String);
+
+/// Bearer tokens of the debug API callers.
+#[derive(Debug, Default)]
+pub struct DebugAuth {
+    callers: HashMap<String, String>,
+}
+
+impl DebugAuth {
+    /// Loads a JSON object mapping the name of each caller to its token.
+    pub fn load(path: &Path) -> Result<Self, AuditError> {
+        let tokens: HashMap<String, String> = serde_json::from_slice(&fs::read(path)?)?;
+        Ok(Self::new(tokens))
+    }
+
+    pub fn new(tokens: HashMap<String, String>) -> Self {
+        Self {
+            callers: tokens
+                .into_iter()
+                .map(|(caller, token)| (token, caller))
+                .collect(),
+        }
+    }
+
+    pub fn from_args(args: &AuditArgs) -> Result<Option<Self>, AuditError> {
+        args.debug_auth_tokens_path
+            .as_deref()
+            .map(Self::load)
+            .transpose()
+    }
+
+    /// The caller whose token is the bearer token of the request, if any.
+    pub fn caller(&self, headers: &HeaderMap) -> Option<String> {
+        let token = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))?;
+        self.callers.get(token).cloned()
+    }
+}
+
+/// A layer identifying the caller of each debug API request. If auth tokens are
+/// configured, requests without a known bearer token are rejected.
+#[derive(Clone, Debug)]
+pub struct DebugAuthLayer {
+    auth: Option<Arc<DebugAuth>>,
+}
+
+impl DebugAuthLayer {
+    pub fn new(auth: Option<Arc<DebugAuth>>) -> Self {
+        Self { auth }
+    }
+}
+
+impl<S> Layer<S> for DebugAuthLayer {
+    type Service = DebugAuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        DebugAuthService {
+            inner,
+            auth: self.auth.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DebugAuthService<S> {
+    inner: S,
+    auth: Option<Arc<DebugAuth>>,
+}
+
+impl<S> Service<Request> for DebugAuthService<S>
+where
+    S: Service<Request, Response = Response> + Send + Sync + Clone + 'static,
+    S::Response: 'static,
+    S::Error: Into<BoxError> + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = BoxError;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, mut request: Request) -> Self::Future {
+        let caller = match &self.auth {
+            Some(auth) => auth.caller(request.headers()),
+            None => Some(UNAUTHENTICATED_CALLER.to_string()),
+        };
+        let Some(caller) = caller else {
+            warn!(target: "rollup_boost::security", message = "rejecting unauthenticated debug API request");
+            return futures::future::ready(Ok(unauthorized())).boxed();
+        };
+
+        request.extensions_mut().insert(Caller(caller));
+        self.inner.call(request).map_err(Into::into).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+
+    #[test]
+    fn test_audit_log_persists() -> eyre::Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("rollup-boost-audit-{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let log = AuditLog::open(path.clone())?;
+        log.record(AuditEntry::new(
+            "alice".to_string(),
+            ExecutionMode::Enabled,
+            ExecutionMode::DryRun,
+        ))?;
+        log.record(AuditEntry::new(
+            "bob".to_string(),
+            ExecutionMode::DryRun,
+            ExecutionMode::Enabled,
+        ))?;
+
+        let reopened = AuditLog::open(path.clone())?;
+        assert_eq!(reopened.entries(), log.entries());
+        assert_eq!(reopened.entries()[0].caller, "alice");
+        assert_eq!(reopened.entries()[1].new_mode, ExecutionMode::Enabled);
+
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_auth_caller() {
+        let auth = DebugAuth::new(HashMap::from([("alice".to_string(), "secret".to_string())]));
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(auth.caller(&headers), None);
+
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+        assert_eq!(auth.caller(&headers), Some("alice".to_string()));
+
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer other"));
+        assert_eq!(auth.caller(&headers), None);
+    }
+}
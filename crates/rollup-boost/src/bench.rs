@@ -0,0 +1,381 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use alloy_primitives::{Address, B256};
+use alloy_rpc_types_engine::{ForkchoiceState, JwtSecret, PayloadAttributes};
+use alloy_rpc_types_eth::BlockNumberOrTag;
+use clap::Parser;
+use eyre::{OptionExt, eyre};
+use futures::StreamExt;
+use jsonrpsee::core::client::ClientT;
+use jsonrpsee::http_client::{HttpClient, HttpClientBuilder};
+use jsonrpsee::rpc_params;
+use op_alloy_rpc_types_engine::OpPayloadAttributes;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use url::Url;
+
+use crate::{AuthLayer, EngineApiClient, FlashblocksPayloadV1, OpExecutionPayloadEnvelope};
+
+#[derive(Parser, Clone, Debug)]
+pub struct BenchArgs {
+    /// URL of the Engine API of the rollup-boost instance
+    #[arg(long, default_value = "http://127.0.0.1:8081")]
+    pub rpc_url: String,
+
+    /// Hex encoded JWT secret authenticating the Engine API calls
+    #[arg(long, value_name = "HEX")]
+    pub jwt_token: Option<JwtSecret>,
+
+    /// Path to the JWT secret authenticating the Engine API calls
+    #[arg(long, value_name = "PATH")]
+    pub jwt_path: Option<PathBuf>,
+
+    /// Flashblocks websocket of the rollup-boost instance. The publish latency of the
+    /// flashblocks is only measured if set
+    #[arg(long)]
+    pub flashblocks_url: Option<Url>,
+
+    /// Unauthenticated RPC of the L2 client, to measure the overhead of proxying
+    /// `eth_getBlockByNumber` through rollup-boost
+    #[arg(long)]
+    pub l2_rpc_url: Option<String>,
+
+    /// Number of FCU, getPayload and newPayload cycles
+    #[arg(long, default_value = "100")]
+    pub cycles: usize,
+
+    /// Time in milliseconds the payload is built for between the FCU and getPayload
+    #[arg(long, default_value = "1000")]
+    pub build_time_ms: u64,
+
+    /// Request Isthmus payloads with `engine_getPayloadV4`
+    #[arg(long)]
+    pub isthmus: bool,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Latency percentiles of a set of samples, in milliseconds.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct LatencyStats {
+    pub count: usize,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl LatencyStats {
+    pub fn from_samples(samples: &[Duration]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        let mut samples = samples.to_vec();
+        samples.sort();
+
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let percentile = |p: usize| ms(samples[(samples.len() * p).div_ceil(100).max(1) - 1]);
+        Self {
+            count: samples.len(),
+            mean_ms: ms(samples.iter().sum::<Duration>() / samples.len() as u32),
+            p50_ms: percentile(50),
+            p90_ms: percentile(90),
+            p99_ms: percentile(99),
+            max_ms: ms(samples[samples.len() - 1]),
+        }
+    }
+}
+
+impl std::fmt::Display for LatencyStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:>6} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2}",
+            self.count, self.mean_ms, self.p50_ms, self.p90_ms, self.p99_ms, self.max_ms
+        )
+    }
+}
+
+/// Latencies measured by `rollup-boost bench`.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct BenchReport {
+    pub cycles: usize,
+    pub failures: usize,
+    /// `engine_forkchoiceUpdated` with payload attributes
+    pub fork_choice_updated: LatencyStats,
+    /// `engine_getPayload`, which assembles the payload from the flashblocks
+    pub get_payload: LatencyStats,
+    pub new_payload: LatencyStats,
+    /// From the FCU to the first flashblock of the payload published by rollup-boost
+    pub first_flashblock: Option<LatencyStats>,
+    /// Between two flashblocks published by rollup-boost for the same payload
+    pub flashblock_interval: Option<LatencyStats>,
+    /// `eth_getBlockByNumber` through rollup-boost minus the same call to the L2 client
+    pub proxy_overhead: Option<LatencyStats>,
+}
+
+impl std::fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "cycles: {} ({} failed)", self.cycles, self.failures)?;
+        writeln!(
+            f,
+            "{:<20} {:>6} {:>9} {:>9} {:>9} {:>9} {:>9}",
+            "latency (ms)", "count", "mean", "p50", "p90", "p99", "max"
+        )?;
+        let rows = [
+            ("fork_choice_updated", Some(&self.fork_choice_updated)),
+            ("get_payload", Some(&self.get_payload)),
+            ("new_payload", Some(&self.new_payload)),
+            ("first_flashblock", self.first_flashblock.as_ref()),
+            ("flashblock_interval", self.flashblock_interval.as_ref()),
+            ("proxy_overhead", self.proxy_overhead.as_ref()),
+        ];
+        for (name, stats) in rows {
+            if let Some(stats) = stats {
+                writeln!(f, "{name:<20} {stats}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Samples {
+    fork_choice_updated: Vec<Duration>,
+    get_payload: Vec<Duration>,
+    new_payload: Vec<Duration>,
+    first_flashblock: Vec<Duration>,
+    flashblock_interval: Vec<Duration>,
+    proxy_overhead: Vec<Duration>,
+}
+
+impl BenchArgs {
+    pub async fn run(self) -> eyre::Result<()> {
+        let report = self.bench().await?;
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            print!("{report}");
+        }
+        Ok(())
+    }
+
+    /// Drives the cycles against the rollup-boost instance and returns the latencies.
+    pub async fn bench(&self) -> eyre::Result<BenchReport> {
+        let secret = match (&self.jwt_token, &self.jwt_path) {
+            (Some(secret), _) => *secret,
+            (None, Some(path)) => JwtSecret::from_file(path)?,
+            // rollup-boost does not authenticate the calls on its HTTP endpoint
+            (None, None) => JwtSecret::random(),
+        };
+        let client = HttpClientBuilder::new()
+            .set_http_middleware(tower::ServiceBuilder::new().layer(AuthLayer::new(secret)))
+            .build(&self.rpc_url)?;
+        let l2_client = self
+            .l2_rpc_url
+            .as_ref()
+            .map(|url| HttpClientBuilder::new().build(url))
+            .transpose()?;
+
+        let (flashblocks_tx, mut flashblocks) = mpsc::unbounded_channel();
+        if let Some(url) = &self.flashblocks_url {
+            let (ws_stream, _) = connect_async(url.as_str()).await?;
+            tokio::spawn(record_flashblocks(ws_stream, flashblocks_tx));
+        }
+
+        let latest =
+            EngineApiClient::get_block_by_number(&client, BlockNumberOrTag::Latest, false).await?;
+        let mut head = latest.header.hash;
+        let mut timestamp = latest.header.timestamp;
+
+        let mut samples = Samples::default();
+        let mut failures = 0;
+        for _ in 0..self.cycles {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            timestamp = now.max(timestamp + 1);
+
+            // Only the flashblocks of this cycle are measured
+            while flashblocks.try_recv().is_ok() {}
+            let started = Instant::now();
+            match self.cycle(&client, head, timestamp, &mut samples).await {
+                Ok(block_hash) => head = block_hash,
+                Err(e) => {
+                    failures += 1;
+                    eprintln!("cycle failed: {e:#}");
+                }
+            }
+
+            let arrivals: Vec<_> = std::iter::from_fn(|| flashblocks.try_recv().ok())
+                .filter(|(_, received)| *received >= started)
+                .collect();
+            if let Some((_, received)) = arrivals.iter().find(|(index, _)| *index == 0) {
+                samples.first_flashblock.push(*received - started);
+            }
+            samples.flashblock_interval.extend(
+                arrivals
+                    .windows(2)
+                    .filter(|pair| pair[1].0 > pair[0].0)
+                    .map(|pair| pair[1].1 - pair[0].1),
+            );
+
+            if let Some(l2_client) = &l2_client {
+                match proxy_overhead(&client, l2_client).await {
+                    Ok(overhead) => samples.proxy_overhead.push(overhead),
+                    Err(e) => eprintln!("proxy overhead not measured: {e:#}"),
+                }
+            }
+        }
+
+        let flashblocks_enabled = self.flashblocks_url.is_some();
+        Ok(BenchReport {
+            cycles: self.cycles,
+            failures,
+            fork_choice_updated: LatencyStats::from_samples(&samples.fork_choice_updated),
+            get_payload: LatencyStats::from_samples(&samples.get_payload),
+            new_payload: LatencyStats::from_samples(&samples.new_payload),
+            first_flashblock: flashblocks_enabled
+                .then(|| LatencyStats::from_samples(&samples.first_flashblock)),
+            flashblock_interval: flashblocks_enabled
+                .then(|| LatencyStats::from_samples(&samples.flashblock_interval)),
+            proxy_overhead: l2_client
+                .is_some()
+                .then(|| LatencyStats::from_samples(&samples.proxy_overhead)),
+        })
+    }
+
+    /// Builds a block on `head`, returning its hash.
+    async fn cycle(
+        &self,
+        client: &HttpClient,
+        head: B256,
+        timestamp: u64,
+        samples: &mut Samples,
+    ) -> eyre::Result<B256> {
+        let parent_beacon_block_root = B256::random();
+        let attributes = OpPayloadAttributes {
+            payload_attributes: PayloadAttributes {
+                timestamp,
+                prev_randao: B256::random(),
+                suggested_fee_recipient: Address::ZERO,
+                withdrawals: Some(vec![]),
+                parent_beacon_block_root: Some(parent_beacon_block_root),
+            },
+            gas_limit: Some(30_000_000),
+            ..Default::default()
+        };
+        let state = ForkchoiceState {
+            head_block_hash: head,
+            safe_block_hash: head,
+            finalized_block_hash: head,
+        };
+
+        let started = Instant::now();
+        let updated = client
+            .fork_choice_updated_v3(state, Some(attributes))
+            .await?;
+        samples.fork_choice_updated.push(started.elapsed());
+        let payload_id = updated
+            .payload_id
+            .ok_or_eyre("the fork choice update returned no payload ID")?;
+
+        tokio::time::sleep(Duration::from_millis(self.build_time_ms)).await;
+
+        let started = Instant::now();
+        let payload = match self.isthmus {
+            true => OpExecutionPayloadEnvelope::V4(client.get_payload_v4(payload_id).await?),
+            false => OpExecutionPayloadEnvelope::V3(client.get_payload_v3(payload_id).await?),
+        };
+        samples.get_payload.push(started.elapsed());
+
+        let started = Instant::now();
+        let status = match &payload {
+            OpExecutionPayloadEnvelope::V4(v4) => {
+                client
+                    .new_payload_v4(
+                        v4.execution_payload.clone(),
+                        vec![],
+                        parent_beacon_block_root,
+                        vec![],
+                    )
+                    .await?
+            }
+            OpExecutionPayloadEnvelope::V3(v3) => {
+                client
+                    .new_payload_v3(
+                        v3.execution_payload.clone(),
+                        vec![],
+                        parent_beacon_block_root,
+                    )
+                    .await?
+            }
+            _ => return Err(eyre!("unexpected payload version")),
+        };
+        samples.new_payload.push(started.elapsed());
+
+        if !status.status.is_valid() {
+            return Err(eyre!("the payload was not accepted: {}", status.status));
+        }
+        Ok(payload.block_hash())
+    }
+}
+
+/// Records the index and arrival time of the flashblocks published by rollup-boost.
+async fn record_flashblocks<S>(mut ws_stream: S, arrivals: mpsc::UnboundedSender<(u64, Instant)>)
+where
+    S: futures::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    while let Some(Ok(message)) = ws_stream.next().await {
+        let received = Instant::now();
+        let Message::Text(text) = message else {
+            continue;
+        };
+        let Ok(flashblock) = serde_json::from_str::<FlashblocksPayloadV1>(&text) else {
+            continue;
+        };
+        if arrivals.send((flashblock.index, received)).is_err() {
+            return;
+        }
+    }
+}
+
+/// Latency added by rollup-boost to a call served by the L2 client.
+async fn proxy_overhead(client: &HttpClient, l2_client: &HttpClient) -> eyre::Result<Duration> {
+    let started = Instant::now();
+    EngineApiClient::get_block_by_number(client, BlockNumberOrTag::Latest, false).await?;
+    let proxied = started.elapsed();
+
+    let started = Instant::now();
+    let _: serde_json::Value = l2_client
+        .request("eth_getBlockByNumber", rpc_params!["latest", false])
+        .await?;
+    Ok(proxied.saturating_sub(started.elapsed()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_stats() {
+        assert_eq!(LatencyStats::from_samples(&[]), LatencyStats::default());
+
+        let samples: Vec<_> = (1..=100).rev().map(Duration::from_millis).collect();
+        let stats = LatencyStats::from_samples(&samples);
+        assert_eq!(stats.count, 100);
+        assert_eq!(stats.mean_ms, 50.5);
+        assert_eq!(stats.p50_ms, 50.0);
+        assert_eq!(stats.p90_ms, 90.0);
+        assert_eq!(stats.p99_ms, 99.0);
+        assert_eq!(stats.max_ms, 100.0);
+
+        let stats = LatencyStats::from_samples(&[Duration::from_millis(3)]);
+        assert_eq!((stats.p50_ms, stats.p99_ms), (3.0, 3.0));
+    }
+}
@@ -4,11 +4,22 @@ use rollup_boost::init_tracing;
 
 use dotenvy::dotenv;
 
-#[tokio::main]
-async fn main() -> eyre::Result<()> {
+#[cfg(feature = "profiling")]
+#[global_allocator]
+static ALLOC: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
+/// Samples the allocations every 512KiB on average, for the heap profiles.
+#[cfg(feature = "profiling")]
+#[allow(non_upper_case_globals)]
+#[unsafe(export_name = "malloc_conf")]
+pub static malloc_conf: &[u8] = b"prof:true,prof_active:true,lg_prof_sample:19\0";
+
+fn main() -> eyre::Result<()> {
     dotenv().ok();
 
     let args = Args::parse();
-    init_tracing(&args)?;
-    args.run().await
+    args.runtime.build_runtime()?.block_on(async move {
+        init_tracing(&args)?;
+        args.run().await
+    })
 }
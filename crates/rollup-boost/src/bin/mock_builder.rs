@@ -0,0 +1,12 @@
+use clap::Parser;
+use rollup_boost::MockBuilderArgs;
+use tracing_subscriber::EnvFilter;
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
+        .init();
+
+    MockBuilderArgs::parse().run().await
+}
@@ -0,0 +1,56 @@
+use std::net::{IpAddr, SocketAddr};
+
+use eyre::eyre;
+
+/// Parses the address a listener binds to from an IPv4 or IPv6 host, with or without
+/// brackets.
+///
+/// Binding to `::` accepts IPv4 connections as well where the OS allows dual-stack
+/// sockets, which is the default on Linux.
+pub fn bind_addr(host: &str, port: u16) -> eyre::Result<SocketAddr> {
+    let host = host.trim();
+    let ip = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+    let ip = ip
+        .parse::<IpAddr>()
+        .map_err(|_| eyre!("invalid bind address {host:?}, expected an IPv4 or IPv6 address"))?;
+    Ok(SocketAddr::new(ip, port))
+}
+
+/// Parses the addresses a service listens on from the hosts it is configured with, all on
+/// `port`. Duplicate addresses are only bound once.
+pub fn bind_addrs(hosts: &[String], port: u16) -> eyre::Result<Vec<SocketAddr>> {
+    let mut addrs = Vec::with_capacity(hosts.len());
+    for host in hosts {
+        let addr = bind_addr(host, port)?;
+        if !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    }
+    if addrs.is_empty() {
+        eyre::bail!("no bind address configured for port {port}");
+    }
+    Ok(addrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bind_addrs() -> eyre::Result<()> {
+        let hosts = ["0.0.0.0", "::", "[::1]", " 127.0.0.1 ", "127.0.0.1"].map(String::from);
+        let addrs = bind_addrs(&hosts, 8081)?;
+        assert_eq!(
+            addrs.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            ["0.0.0.0:8081", "[::]:8081", "[::1]:8081", "127.0.0.1:8081"]
+        );
+
+        assert!(bind_addr("localhost", 8081).is_err());
+        assert!(bind_addr("[127.0.0.1", 8081).is_err());
+        assert!(bind_addrs(&[], 8081).is_err());
+        Ok(())
+    }
+}
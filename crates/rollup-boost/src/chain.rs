@@ -0,0 +1,222 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use clap::Parser;
+use metrics::{counter, gauge};
+use thiserror::Error;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::{ClientResult, EngineApiExt, RpcClient, RpcClientError, payload::PayloadSource};
+
+#[derive(Parser, Clone, Debug)]
+pub struct ChainIdArgs {
+    /// Chain ID of the network. The builder and L2 clients are checked to be on this
+    /// chain at startup and periodically, they are not checked if not set
+    #[arg(long, env)]
+    pub chain_id: Option<u64>,
+
+    /// Duration in seconds between checks of the chain ID of the clients
+    #[arg(long, env, default_value = "60")]
+    pub chain_id_check_interval: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum ChainIdError {
+    #[error("{client} client is on chain {actual}, expected chain {expected}")]
+    Mismatch {
+        client: PayloadSource,
+        expected: u64,
+        actual: u64,
+    },
+    #[error("failed to query the chain id of the {client} client: {source}")]
+    Client {
+        client: PayloadSource,
+        #[source]
+        source: RpcClientError,
+    },
+}
+
+/// Checks that the builder and L2 clients are on the configured chain, so that a
+/// misconfigured builder never produces blocks for the wrong network.
+///
+/// Any mismatch is fatal at startup. Afterwards, a builder found on another chain is
+/// bypassed, and engine API calls are refused while the L2 client is on another chain.
+#[derive(Debug)]
+pub struct ChainIdGuard {
+    expected: u64,
+    interval: Duration,
+    builder_mismatch: AtomicBool,
+    l2_mismatch: AtomicBool,
+}
+
+impl ChainIdGuard {
+    pub fn new(expected: u64, interval: Duration) -> Self {
+        Self {
+            expected,
+            interval,
+            builder_mismatch: AtomicBool::new(false),
+            l2_mismatch: AtomicBool::new(false),
+        }
+    }
+
+    pub fn from_args(args: &ChainIdArgs) -> Option<Self> {
+        args.chain_id
+            .map(|chain_id| Self::new(chain_id, Duration::from_secs(args.chain_id_check_interval)))
+    }
+
+    pub fn expected(&self) -> u64 {
+        self.expected
+    }
+
+    /// Whether the builder was last found on another chain, and should be bypassed.
+    pub fn builder_mismatch(&self) -> bool {
+        self.builder_mismatch.load(Ordering::Relaxed)
+    }
+
+    /// Whether the L2 client was last found on another chain, and engine API calls
+    /// should be refused.
+    pub fn l2_mismatch(&self) -> bool {
+        self.l2_mismatch.load(Ordering::Relaxed)
+    }
+
+    /// Checks the chain ID of both clients before serving. The L2 client must be
+    /// reachable, while a builder that is down is only checked once it comes back.
+    pub async fn preflight(
+        &self,
+        builder_client: &dyn EngineApiExt,
+        l2_client: &RpcClient,
+    ) -> Result<(), ChainIdError> {
+        let (builder_chain_id, l2_chain_id) =
+            tokio::join!(builder_client.chain_id(), l2_client.chain_id());
+        let builder_chain_id = self.builder_chain_id(builder_chain_id);
+
+        let l2_chain_id = l2_chain_id.map_err(|source| ChainIdError::Client {
+            client: PayloadSource::L2,
+            source,
+        })?;
+        if !self.record(PayloadSource::L2, l2_chain_id) {
+            return Err(ChainIdError::Mismatch {
+                client: PayloadSource::L2,
+                expected: self.expected,
+                actual: l2_chain_id,
+            });
+        }
+
+        match builder_chain_id {
+            Ok(chain_id) if !self.record(PayloadSource::Builder, chain_id) => {
+                return Err(ChainIdError::Mismatch {
+                    client: PayloadSource::Builder,
+                    expected: self.expected,
+                    actual: chain_id,
+                });
+            }
+            Ok(_) => {}
+            Err(e) => {
+                warn!(message = "failed to query the chain id of the builder, checking it later", error = %e);
+            }
+        }
+
+        info!(
+            message = "builder and l2 clients are on the expected chain",
+            chain_id = self.expected
+        );
+        Ok(())
+    }
+
+    pub fn spawn(
+        self: Arc<Self>,
+        builder_client: Arc<dyn EngineApiExt>,
+        l2_client: Arc<RpcClient>,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            // The first tick completes immediately, right after the preflight
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let (builder_chain_id, l2_chain_id) =
+                    tokio::join!(builder_client.chain_id(), l2_client.chain_id());
+                for (client, result) in [
+                    (
+                        PayloadSource::Builder,
+                        self.builder_chain_id(builder_chain_id),
+                    ),
+                    (PayloadSource::L2, l2_chain_id),
+                ] {
+                    match result {
+                        Ok(chain_id) => {
+                            self.record(client, chain_id);
+                        }
+                        Err(e) => {
+                            warn!(message = "failed to query the chain id", %client, error = %e);
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// A registry of builders fails if its builders are not all on the same chain, in
+    /// which case the chain ID of a builder that is not on the expected chain is used.
+    fn builder_chain_id(&self, result: ClientResult<u64>) -> ClientResult<u64> {
+        match result {
+            Err(RpcClientError::ChainIdMismatch { expected, .. }) if expected != self.expected => {
+                Ok(expected)
+            }
+            Err(RpcClientError::ChainIdMismatch { actual, .. }) => Ok(actual),
+            result => result,
+        }
+    }
+
+    /// Records the chain ID reported by `client`. Returns whether it is the expected one.
+    fn record(&self, client: PayloadSource, chain_id: u64) -> bool {
+        let mismatch = chain_id != self.expected;
+        let flag = match client {
+            PayloadSource::Builder => &self.builder_mismatch,
+            PayloadSource::L2 => &self.l2_mismatch,
+        };
+        if flag.swap(mismatch, Ordering::Relaxed) == mismatch {
+            return !mismatch;
+        }
+
+        gauge!("chain_id.mismatch", "client" => client.to_string()).set(if mismatch {
+            1
+        } else {
+            0
+        });
+        if mismatch {
+            counter!("chain_id.mismatches", "client" => client.to_string()).increment(1);
+            error!(target: "rollup_boost::security", message = "client is on the wrong chain", %client, chain_id, expected = self.expected);
+        } else {
+            info!(message = "client is back on the expected chain", %client, chain_id);
+        }
+        !mismatch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_id_mismatch() {
+        let guard = ChainIdGuard::new(8453, Duration::from_secs(60));
+
+        assert!(guard.record(PayloadSource::L2, 8453));
+        assert!(guard.record(PayloadSource::Builder, 8453));
+        assert!(!guard.builder_mismatch());
+
+        assert!(!guard.record(PayloadSource::Builder, 10));
+        assert!(guard.builder_mismatch());
+        assert!(!guard.l2_mismatch());
+
+        assert!(guard.record(PayloadSource::Builder, 8453));
+        assert!(!guard.builder_mismatch());
+    }
+}
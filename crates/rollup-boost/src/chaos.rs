@@ -0,0 +1,275 @@
+use std::{sync::Arc, time::Duration};
+
+use alloy_primitives::{B64, B256, U64};
+use alloy_rpc_types_engine::{
+    ExecutionPayloadBodiesV1, ForkchoiceState, ForkchoiceUpdated, PayloadId, PayloadStatus,
+};
+use alloy_rpc_types_eth::{Block, BlockNumberOrTag};
+use clap::Parser;
+use jsonrpsee::core::async_trait;
+use jsonrpsee::types::ErrorObjectOwned;
+use metrics::counter;
+use op_alloy_rpc_types_engine::OpPayloadAttributes;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    BlockSource, ClientResult, EngineApiExt, NewPayload, OpExecutionPayloadEnvelope,
+    PayloadVersion, ReorgEvent, RpcClientError,
+};
+
+#[derive(Parser, Clone, Debug)]
+pub struct ChaosArgs {
+    /// Allow injecting faults in the builder RPCs and flashblocks through the debug API,
+    /// to rehearse failover. Never enable in production
+    #[arg(long, env, default_value = "false")]
+    pub chaos: bool,
+}
+
+/// A builder RPC faults can be injected in.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum BuilderRpc {
+    ForkChoiceUpdated,
+    NewPayload,
+    GetPayload,
+}
+
+impl BuilderRpc {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BuilderRpc::ForkChoiceUpdated => "fork_choice_updated",
+            BuilderRpc::NewPayload => "new_payload",
+            BuilderRpc::GetPayload => "get_payload",
+        }
+    }
+}
+
+/// JSON-RPC error returned instead of calling the builder.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InjectedError {
+    pub code: i32,
+    pub message: String,
+    /// RPCs failing with the error, all of them if empty
+    #[serde(default)]
+    pub rpcs: Vec<BuilderRpc>,
+}
+
+/// Faults injected in the builder RPCs and flashblocks, set through `debug_setFaults`.
+/// The default injects no fault.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct FaultConfig {
+    /// Latency added to every builder RPC, in milliseconds
+    #[serde(default)]
+    pub latency_ms: u64,
+    /// Percentage of the flashblocks received from the builder that are dropped
+    #[serde(default)]
+    pub drop_flashblocks_percent: u8,
+    #[serde(default)]
+    pub builder_error: Option<InjectedError>,
+}
+
+/// Holds the faults currently injected, shared by the builder client, the flashblocks
+/// service and the debug API.
+#[derive(Debug, Default)]
+pub struct FaultInjector {
+    config: Mutex<FaultConfig>,
+}
+
+impl FaultInjector {
+    pub fn from_args(args: &ChaosArgs) -> Option<Self> {
+        args.chaos.then(Self::default)
+    }
+
+    pub fn config(&self) -> FaultConfig {
+        self.config.lock().clone()
+    }
+
+    pub fn set_config(&self, config: FaultConfig) -> Result<(), String> {
+        if config.drop_flashblocks_percent > 100 {
+            return Err(format!(
+                "drop_flashblocks_percent must be at most 100, got {}",
+                config.drop_flashblocks_percent
+            ));
+        }
+        *self.config.lock() = config;
+        Ok(())
+    }
+
+    /// Whether to drop the flashblock just received from the builder.
+    pub fn drop_flashblock(&self) -> bool {
+        let percent = self.config.lock().drop_flashblocks_percent as u64;
+        let dropped = percent > 0 && u64::from_be_bytes(B64::random().0) % 100 < percent;
+        if dropped {
+            counter!("chaos.flashblocks_dropped").increment(1);
+        }
+        dropped
+    }
+
+    /// Waits for the injected latency, then returns the injected error of `rpc`, if any.
+    async fn before(&self, rpc: BuilderRpc) -> ClientResult<()> {
+        let (latency, error) = {
+            let config = self.config.lock();
+            let error = config
+                .builder_error
+                .clone()
+                .filter(|error| error.rpcs.is_empty() || error.rpcs.contains(&rpc));
+            (Duration::from_millis(config.latency_ms), error)
+        };
+
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+        match error {
+            Some(error) => {
+                counter!("chaos.builder_errors", "rpc" => rpc.as_str()).increment(1);
+                Err(RpcClientError::Jsonrpsee(
+                    ErrorObjectOwned::owned(error.code, error.message, None::<()>).into(),
+                ))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// A builder client injecting the faults of a [`FaultInjector`] in the calls made to the
+/// builder, before forwarding them.
+pub struct FaultInjectedClient {
+    inner: Arc<dyn EngineApiExt>,
+    faults: Arc<FaultInjector>,
+}
+
+impl FaultInjectedClient {
+    pub fn new(inner: Arc<dyn EngineApiExt>, faults: Arc<FaultInjector>) -> Self {
+        Self { inner, faults }
+    }
+}
+
+#[async_trait]
+impl EngineApiExt for FaultInjectedClient {
+    async fn fork_choice_updated_v3(
+        &self,
+        fork_choice_state: ForkchoiceState,
+        payload_attributes: Option<OpPayloadAttributes>,
+    ) -> ClientResult<ForkchoiceUpdated> {
+        self.faults.before(BuilderRpc::ForkChoiceUpdated).await?;
+        self.inner
+            .fork_choice_updated_v3(fork_choice_state, payload_attributes)
+            .await
+    }
+
+    async fn new_payload(&self, new_payload: NewPayload) -> ClientResult<PayloadStatus> {
+        self.faults.before(BuilderRpc::NewPayload).await?;
+        self.inner.new_payload(new_payload).await
+    }
+
+    async fn get_payload(
+        &self,
+        payload_id: PayloadId,
+        version: PayloadVersion,
+    ) -> ClientResult<OpExecutionPayloadEnvelope> {
+        self.faults.before(BuilderRpc::GetPayload).await?;
+        self.inner.get_payload(payload_id, version).await
+    }
+
+    fn last_payload_source(&self) -> BlockSource {
+        self.inner.last_payload_source()
+    }
+
+    fn last_payload_builder(&self) -> String {
+        self.inner.last_payload_builder()
+    }
+
+    fn last_payload_flashblocks(&self) -> usize {
+        self.inner.last_payload_flashblocks()
+    }
+
+    async fn get_payload_bodies_by_hash_v1(
+        &self,
+        block_hashes: Vec<B256>,
+    ) -> ClientResult<ExecutionPayloadBodiesV1> {
+        self.inner.get_payload_bodies_by_hash_v1(block_hashes).await
+    }
+
+    async fn get_payload_bodies_by_range_v1(
+        &self,
+        start: U64,
+        count: U64,
+    ) -> ClientResult<ExecutionPayloadBodiesV1> {
+        self.inner
+            .get_payload_bodies_by_range_v1(start, count)
+            .await
+    }
+
+    async fn exchange_capabilities(&self, capabilities: Vec<String>) -> ClientResult<Vec<String>> {
+        self.inner.exchange_capabilities(capabilities).await
+    }
+
+    async fn get_block_by_number(
+        &self,
+        number: BlockNumberOrTag,
+        full: bool,
+    ) -> ClientResult<Block> {
+        self.inner.get_block_by_number(number, full).await
+    }
+
+    async fn chain_id(&self) -> ClientResult<u64> {
+        self.inner.chain_id().await
+    }
+
+    async fn on_reorg(&self, event: &ReorgEvent) {
+        self.inner.on_reorg(event).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop_flashblocks() {
+        let faults = FaultInjector::default();
+        assert!(!(0..100).any(|_| faults.drop_flashblock()));
+
+        faults
+            .set_config(FaultConfig {
+                drop_flashblocks_percent: 100,
+                ..Default::default()
+            })
+            .unwrap();
+        assert!((0..100).all(|_| faults.drop_flashblock()));
+
+        assert!(
+            faults
+                .set_config(FaultConfig {
+                    drop_flashblocks_percent: 101,
+                    ..Default::default()
+                })
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_builder_error() {
+        let faults = FaultInjector::default();
+        faults
+            .set_config(FaultConfig {
+                builder_error: Some(InjectedError {
+                    code: -38001,
+                    message: "unknown payload".to_string(),
+                    rpcs: vec![BuilderRpc::GetPayload],
+                }),
+                ..Default::default()
+            })
+            .unwrap();
+
+        assert!(faults.before(BuilderRpc::NewPayload).await.is_ok());
+        match faults.before(BuilderRpc::GetPayload).await {
+            Err(RpcClientError::Jsonrpsee(jsonrpsee::core::client::Error::Call(e))) => {
+                assert_eq!(e.code(), -38001);
+                assert_eq!(e.message(), "unknown payload");
+            }
+            result => panic!("unexpected result {result:?}"),
+        }
+    }
+}
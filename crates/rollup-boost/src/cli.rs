@@ -1,25 +1,46 @@
-use alloy_rpc_types_engine::JwtSecret;
+use alloy_primitives::{B64, U256};
+use alloy_rpc_types_engine::PayloadId;
 use clap::{Parser, Subcommand};
 use eyre::bail;
-use jsonrpsee::{RpcModule, server::Server};
+use futures::future::select_all;
+use jsonrpsee::{
+    RpcModule,
+    server::{Server, ServerHandle},
+};
 use parking_lot::Mutex;
 use std::{
-    net::{IpAddr, SocketAddr},
-    path::PathBuf,
-    str::FromStr,
+    net::SocketAddr,
+    path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
+use tokio::net::TcpListener;
 use tokio::signal::unix::{SignalKind, signal as unix_signal};
-use tracing::{Level, info};
+use tracing::{Level, info, warn};
 
 use crate::{
-    BlockSelectionPolicy, DebugClient, EngineApiExt, Flashblocks, FlashblocksArgs, ProxyLayer,
-    RollupBoostServer, RpcClient,
+    ArchiveArgs, AttributesHookArgs, AttributesPolicy, AttributesPolicyArgs, AuditArgs, AuditLog,
+    BenchArgs, BlockSelectionPolicy, BlockValueThreshold, BuilderConfig, BuilderForwarding,
+    BuilderPromotion, BuilderQuarantine, BuilderRegistry, BuilderRpc, BuilderScores, BuilderWarmup,
+    ChainIdArgs, ChainIdGuard, ChainsConfig, ChaosArgs, ClickHouseArgs, ClickHouseSink, CorsArgs,
+    CorsPolicy, DEFAULT_BUILDER, Dashboard, DashboardArgs, DebugAuth, DebugClient,
+    DecodePayloadArgs, DivergenceArgs, DivergenceDetector, EngineApiExt, ExportParquetArgs,
+    FaultConfig, FaultInjectedClient, FaultInjector, Flashblocks, FlashblocksArgs,
+    FlashblocksGossip, FlashblocksLimits, FlashblocksPayloadV1, FlashblocksService, ForwardedArgs,
+    HeadLagArgs, HeadLagMonitor, InjectedError, IpcArgs, LeaderElection, LeaderElectionArgs,
+    MulticastEmitter, Outbox, PayloadArchive, PayloadValidator, PromotionArgs, ProxyLayer,
+    PublisherConfig, QuarantineArgs, QuarantinedClient, RateLimitArgs, RecentFlashblocks,
+    RelayArgs, RelayClient, RollupBoostServer, RpcClient, RpcLogArgs, RpcLogger, RpcRateLimiter,
+    RpcRouting, RpcRoutingArgs, RuntimeArgs, ScoringArgs, SharedJwtSecret, StateSync, StreamArgs,
+    SubscriberLimits, SyntheticFlashblocks, TransactionDenylist, TrustedProxies, TxIngress,
+    TxIngressArgs, ValidatorArgs, WarmupArgs, WasmPluginArgs, WebhookArgs, WebhookNotifier, WsArgs,
+    bind_addrs,
     client::rpc::{BuilderArgs, L2ClientArgs},
     debug_api::ExecutionMode,
     init_metrics,
     payload::PayloadSource,
     probe::ProbeLayer,
+    spawn_chain, spawn_ipc_server, spawn_profiling_server, spawn_tcp_server, spawn_ws_server,
 };
 
 #[derive(Clone, Parser, Debug)]
@@ -28,6 +49,11 @@ pub struct Args {
     #[command(subcommand)]
     pub command: Option<Commands>,
 
+    /// Path to a JSON file listing the chains served by this process, each with its own
+    /// arguments. The arguments of the process only configure logging and metrics if set
+    #[arg(long, env, value_name = "PATH")]
+    pub chains_config: Option<PathBuf>,
+
     #[clap(flatten)]
     pub builder: BuilderArgs,
 
@@ -42,9 +68,14 @@ pub struct Args {
     #[arg(long, env, default_value = "5")]
     pub max_unsafe_interval: u64,
 
-    /// Host to run the server on
-    #[arg(long, env, default_value = "127.0.0.1")]
-    pub rpc_host: String,
+    /// Number of payload cycles kept for `debug_getRecentPayloads`
+    #[arg(long, env, default_value = "256")]
+    pub payload_history_size: usize,
+
+    /// Hosts to run the server on, comma separated IPv4 or IPv6 addresses. `::` also
+    /// accepts IPv4 connections where the OS allows dual-stack sockets
+    #[arg(long, env, default_value = "127.0.0.1", value_delimiter = ',')]
+    pub rpc_host: Vec<String>,
 
     /// Port to run the server on
     #[arg(long, env, default_value = "8081")]
@@ -58,14 +89,24 @@ pub struct Args {
     #[arg(long, env, default_value = "false")]
     pub metrics: bool,
 
-    /// Host to run the metrics server on
-    #[arg(long, env, default_value = "127.0.0.1")]
-    pub metrics_host: String,
+    /// Hosts to run the metrics server on, comma separated IPv4 or IPv6 addresses
+    #[arg(long, env, default_value = "127.0.0.1", value_delimiter = ',')]
+    pub metrics_host: Vec<String>,
 
     /// Port to run the metrics server on
     #[arg(long, env, default_value = "9090")]
     pub metrics_port: u16,
 
+    /// Address to serve the tokio tasks to tokio-console on, disabled if not set. Requires
+    /// the `tokio-console` feature and building with `--cfg tokio_unstable`
+    #[arg(long, env)]
+    pub tokio_console_addr: Option<SocketAddr>,
+
+    /// Address to serve CPU and heap profiles in the pprof format on, disabled if not set.
+    /// Requires the `profiling` feature
+    #[arg(long, env)]
+    pub profiling_addr: Option<SocketAddr>,
+
     /// OTLP endpoint
     #[arg(long, env, default_value = "http://localhost:4317")]
     pub otlp_endpoint: String,
@@ -82,9 +123,10 @@ pub struct Args {
     #[arg(long, env)]
     pub log_file: Option<PathBuf>,
 
-    /// Host to run the debug server on
-    #[arg(long, env, default_value = "127.0.0.1")]
-    pub debug_host: String,
+    /// Hosts to run the debug server on, comma separated IPv4 or IPv6 addresses. The
+    /// debug subcommands connect to the first one
+    #[arg(long, env, default_value = "127.0.0.1", value_delimiter = ',')]
+    pub debug_host: Vec<String>,
 
     /// Debug server port
     #[arg(long, env, default_value = "5555")]
@@ -97,93 +139,738 @@ pub struct Args {
     #[arg(long, env)]
     pub block_selection_policy: Option<BlockSelectionPolicy>,
 
+    /// Minimum block value in wei for a builder block to be selected
+    #[arg(long, env)]
+    pub min_builder_block_value: Option<U256>,
+
+    /// Minimum amount in wei by which the builder block value must exceed the L2 block value
+    #[arg(long, env)]
+    pub min_builder_block_value_margin: Option<U256>,
+
+    /// Make selected builder blocks the head of the L2 client immediately, instead of
+    /// waiting for them to be received over p2p
+    #[arg(long, env, default_value = "false")]
+    pub import_builder_blocks: bool,
+
+    /// Milliseconds after which `get_payload` stops waiting for the builder payload and
+    /// returns the l2 client payload, e.g. 90% of the time left in the block once
+    /// `get_payload` is called. Waits for the builder for as long as it takes if not set
+    #[arg(long, env)]
+    pub get_payload_budget_ms: Option<u64>,
+
+    /// How forkchoice updates without payload attributes are forwarded to the builder to
+    /// keep its head in sync with the L2 client
+    #[arg(long, env, default_value = "async")]
+    pub builder_fcu_forwarding: BuilderForwarding,
+
+    /// How `engine_newPayload` calls are forwarded to the builder so that it imports the
+    /// canonical blocks. In sync mode, the status returned by the builder is checked
+    /// against the one of the L2 client
+    #[arg(long, env, default_value = "async")]
+    pub builder_new_payload_forwarding: BuilderForwarding,
+
+    /// File to persist the builders registered through the debug API to. Enables
+    /// registering, disabling and removing builders at runtime
+    #[arg(long, env, value_name = "PATH")]
+    pub builder_registry_path: Option<PathBuf>,
+
+    /// Priority of the builder in the builder registry, builders with a higher priority
+    /// are called first
+    #[arg(long, env, default_value = "0")]
+    pub builder_priority: u32,
+
+    /// Weight of the builder in the builder registry, breaks ties between payloads of the
+    /// same block value
+    #[arg(long, env, default_value = "1")]
+    pub builder_weight: u32,
+
     #[clap(flatten)]
     pub flashblocks: FlashblocksArgs,
+
+    #[clap(flatten)]
+    pub attributes_policy: AttributesPolicyArgs,
+
+    #[clap(flatten)]
+    pub attributes_hooks: AttributesHookArgs,
+
+    #[clap(flatten)]
+    pub quarantine: QuarantineArgs,
+
+    #[clap(flatten)]
+    pub scoring: ScoringArgs,
+
+    #[clap(flatten)]
+    pub head_lag: HeadLagArgs,
+
+    #[clap(flatten)]
+    pub divergence: DivergenceArgs,
+
+    #[clap(flatten)]
+    pub validator: ValidatorArgs,
+
+    #[clap(flatten)]
+    pub warmup: WarmupArgs,
+
+    #[clap(flatten)]
+    pub promotion: PromotionArgs,
+
+    #[clap(flatten)]
+    pub leader_election: LeaderElectionArgs,
+
+    #[clap(flatten)]
+    pub chain_id: ChainIdArgs,
+
+    #[clap(flatten)]
+    pub audit: AuditArgs,
+
+    #[clap(flatten)]
+    pub webhooks: WebhookArgs,
+
+    #[clap(flatten)]
+    pub rate_limit: RateLimitArgs,
+
+    #[clap(flatten)]
+    pub rpc_routing: RpcRoutingArgs,
+
+    #[clap(flatten)]
+    pub tx_ingress: TxIngressArgs,
+
+    #[clap(flatten)]
+    pub ws: WsArgs,
+
+    #[clap(flatten)]
+    pub ipc: IpcArgs,
+
+    #[clap(flatten)]
+    pub chaos: ChaosArgs,
+
+    #[clap(flatten)]
+    pub relay: RelayArgs,
+
+    #[clap(flatten)]
+    pub wasm_plugin: WasmPluginArgs,
+
+    #[clap(flatten)]
+    pub dashboard: DashboardArgs,
+
+    #[clap(flatten)]
+    pub rpc_log: RpcLogArgs,
+
+    #[clap(flatten)]
+    pub cors: CorsArgs,
+
+    #[clap(flatten)]
+    pub forwarded: ForwardedArgs,
+
+    #[clap(flatten)]
+    pub clickhouse: ClickHouseArgs,
+
+    #[clap(flatten)]
+    pub archive: ArchiveArgs,
+
+    #[clap(flatten)]
+    pub runtime: RuntimeArgs,
 }
 
 impl Args {
     pub async fn run(self) -> eyre::Result<()> {
         let _ = rustls::crypto::ring::default_provider().install_default();
 
-        let debug_addr = format!("{}:{}", self.debug_host, self.debug_server_port);
-
         // Handle commands if present
         if let Some(cmd) = self.command {
+            let debug_addr = bind_addrs(&self.debug_host, self.debug_server_port)?[0];
             let debug_addr = format!("http://{debug_addr}");
+            let debug_auth_token = self.audit.debug_auth_token.clone();
+            let debug_client =
+                || DebugClient::new_with_auth(debug_addr.as_str(), debug_auth_token.as_deref());
             return match cmd {
                 Commands::Debug { command } => match command {
                     DebugCommands::SetExecutionMode { execution_mode } => {
-                        let client = DebugClient::new(debug_addr.as_str())?;
+                        let client = debug_client()?;
                         let result = client.set_execution_mode(execution_mode).await?;
                         println!("Response: {:?}", result.execution_mode);
 
                         Ok(())
                     }
                     DebugCommands::ExecutionMode {} => {
-                        let client = DebugClient::new(debug_addr.as_str())?;
+                        let client = debug_client()?;
                         let result = client.get_execution_mode().await?;
                         println!("Execution mode: {:?}", result.execution_mode);
 
+                        Ok(())
+                    }
+                    DebugCommands::Status {} => {
+                        let client = debug_client()?;
+                        let result = client.get_status().await?;
+                        println!("Execution mode: {:?}", result.execution_mode);
+                        for (i, builder) in result.builders.iter().enumerate() {
+                            println!(
+                                "{}. {} {} priority={} weight={} enabled={}",
+                                i + 1,
+                                builder.name,
+                                builder.url,
+                                builder.priority,
+                                builder.weight,
+                                builder.enabled
+                            );
+                        }
+                        for builder in result.quarantined {
+                            println!(
+                                "Quarantined: {} for {}s",
+                                builder.name, builder.remaining_secs
+                            );
+                        }
+                        if let Some(leader) = result.leader {
+                            println!(
+                                "Leader: {} (this instance: {}, leading: {})",
+                                leader.leader.as_deref().unwrap_or("none"),
+                                leader.instance_id,
+                                leader.is_leader
+                            );
+                        }
+                        if let Some(divergence) = result.divergence {
+                            println!(
+                                "Heads: builder={} l2={} diverged={:?}",
+                                divergence.builder_number, divergence.l2_number, divergence.reason
+                            );
+                        }
+                        if result.drain.draining {
+                            println!(
+                                "Draining: {} payload cycles in flight, safe to stop: {}",
+                                result.drain.in_flight, result.drain.safe_to_stop
+                            );
+                        }
+
+                        Ok(())
+                    }
+                    DebugCommands::SetDrainMode { enabled, wait } => {
+                        let client = debug_client()?;
+                        let mut status = client.set_drain_mode(enabled).await?;
+                        while wait && enabled && !status.safe_to_stop {
+                            println!("Waiting for {} payload cycles", status.in_flight);
+                            tokio::time::sleep(Duration::from_millis(500)).await;
+                            status = client.get_status().await?.drain;
+                        }
+                        println!(
+                            "Draining: {} (in flight: {}, safe to stop: {})",
+                            status.draining, status.in_flight, status.safe_to_stop
+                        );
+
+                        Ok(())
+                    }
+                    DebugCommands::SetLogFilter { filter } => {
+                        let client = debug_client()?;
+                        let result = client.set_log_filter(filter).await?;
+                        println!("Log filter: {}", result.filter);
+
+                        Ok(())
+                    }
+                    DebugCommands::ReloadJwtSecrets {} => {
+                        let client = debug_client()?;
+                        let result = client.reload_jwt_secrets().await?;
+                        println!("Rotated JWT secrets: {:?}", result.rotated);
+
+                        Ok(())
+                    }
+                    DebugCommands::RegisterBuilder {
+                        name,
+                        url,
+                        jwt_token,
+                        jwt_path,
+                        priority,
+                        weight,
+                    } => {
+                        let client = debug_client()?;
+                        let config = BuilderConfig {
+                            name,
+                            url,
+                            jwt_secret: jwt_token,
+                            jwt_path,
+                            priority,
+                            weight,
+                            enabled: true,
+                        };
+                        let result = client.register_builder(config).await?;
+                        println!("Builders: {:#?}", result.builders);
+
+                        Ok(())
+                    }
+                    DebugCommands::SetBuilderEnabled { name, enabled } => {
+                        let client = debug_client()?;
+                        let result = client.set_builder_enabled(name, enabled).await?;
+                        println!("Builders: {:#?}", result.builders);
+
+                        Ok(())
+                    }
+                    DebugCommands::RemoveBuilder { name } => {
+                        let client = debug_client()?;
+                        let result = client.remove_builder(name).await?;
+                        println!("Builders: {:#?}", result.builders);
+
+                        Ok(())
+                    }
+                    DebugCommands::ListBuilders {} => {
+                        let client = debug_client()?;
+                        let result = client.list_builders().await?;
+                        println!("Builders: {:#?}", result.builders);
+
+                        Ok(())
+                    }
+                    DebugCommands::AuditLog {} => {
+                        let client = debug_client()?;
+                        let result = client.get_audit_log().await?;
+                        for entry in result.entries {
+                            println!(
+                                "{} {} {:?} -> {:?}",
+                                entry.timestamp, entry.caller, entry.old_mode, entry.new_mode
+                            );
+                        }
+
+                        Ok(())
+                    }
+                    DebugCommands::SetFaults {
+                        latency_ms,
+                        drop_flashblocks_percent,
+                        error_code,
+                        error_message,
+                        error_rpcs,
+                    } => {
+                        let client = debug_client()?;
+                        let builder_error =
+                            error_code
+                                .zip(error_message)
+                                .map(|(code, message)| InjectedError {
+                                    code,
+                                    message,
+                                    rpcs: error_rpcs,
+                                });
+                        let result = client
+                            .set_faults(FaultConfig {
+                                latency_ms,
+                                drop_flashblocks_percent,
+                                builder_error,
+                            })
+                            .await?;
+                        println!("Faults: {result:#?}");
+
+                        Ok(())
+                    }
+                    DebugCommands::Faults {} => {
+                        let client = debug_client()?;
+                        let result = client.get_faults().await?;
+                        println!("Faults: {result:#?}");
+
+                        Ok(())
+                    }
+                    DebugCommands::Subscribers {} => {
+                        let client = debug_client()?;
+                        let result = client.list_subscribers().await?;
+                        for subscriber in result.subscribers {
+                            println!(
+                                "{} age={}s delivered={} dropped={} queued={}",
+                                subscriber.peer,
+                                subscriber.age_secs,
+                                subscriber.delivered,
+                                subscriber.dropped,
+                                subscriber.queue_depth
+                            );
+                        }
+
+                        Ok(())
+                    }
+                    DebugCommands::Payloads { count } => {
+                        let client = debug_client()?;
+                        let result = client.get_recent_payloads(Some(count)).await?;
+                        for cycle in result.payloads {
+                            println!(
+                                "{} block={} hash={} source={} fallback={} flashblocks={} value={} l2={}ms builder={} total={}ms",
+                                cycle.payload_id,
+                                cycle.block_number,
+                                cycle.block_hash,
+                                cycle.source,
+                                cycle.fallback_reason.as_deref().unwrap_or("-"),
+                                cycle.flashblocks,
+                                cycle.block_value,
+                                cycle.l2_latency_ms,
+                                cycle
+                                    .builder_latency_ms
+                                    .map_or_else(|| "-".to_string(), |ms| format!("{ms}ms")),
+                                cycle.total_latency_ms
+                            );
+                        }
+
+                        Ok(())
+                    }
+                    DebugCommands::Flashblocks { payload_id } => {
+                        let client = debug_client()?;
+                        let result = client.get_flashblocks(PayloadId(payload_id)).await?;
+                        for flashblock in &result.flashblocks {
+                            println!("{}", serde_json::to_string(flashblock)?);
+                        }
+
+                        Ok(())
+                    }
+                    DebugCommands::Metrics { filter } => {
+                        let client = debug_client()?;
+                        let result = client.get_metrics(filter).await?;
+                        for (name, samples) in result.metrics {
+                            for sample in samples {
+                                let labels = sample
+                                    .labels
+                                    .iter()
+                                    .map(|(key, value)| format!("{key}={value:?}"))
+                                    .collect::<Vec<_>>();
+                                if labels.is_empty() {
+                                    println!("{name} {}", sample.value);
+                                } else {
+                                    println!("{name}{{{}}} {}", labels.join(","), sample.value);
+                                }
+                            }
+                        }
+
+                        Ok(())
+                    }
+                    DebugCommands::BuilderScores {} => {
+                        let client = debug_client()?;
+                        let result = client.get_builder_scores().await?;
+                        for builder in result.builders {
+                            println!(
+                                "{} score={:.3} valid={:.3} value={:.3} cadence={:.3} samples={}",
+                                builder.name,
+                                builder.score,
+                                builder.valid_rate,
+                                builder.value_rate,
+                                builder.cadence_rate,
+                                builder.samples
+                            );
+                        }
+
+                        Ok(())
+                    }
+                    DebugCommands::ValidateFlashblocks { path } => {
+                        let client = debug_client()?;
+                        let flashblocks = read_flashblocks(path.as_deref())?;
+                        let report = client.validate_flashblocks(flashblocks).await?;
+                        for flashblock in &report.flashblocks {
+                            match &flashblock.error {
+                                Some(error) => println!("Flashblock {}: {error}", flashblock.index),
+                                None => println!("Flashblock {}: ok", flashblock.index),
+                            }
+                        }
+                        for error in &report.payload_errors {
+                            println!("Payload: {error}");
+                        }
+                        if !report.valid {
+                            bail!("the flashblocks are invalid");
+                        }
+                        println!("Valid");
+
                         Ok(())
                     }
                 },
+                Commands::Bench(bench) => bench.run().await,
+                Commands::Stream(stream) => stream.run().await,
+                Commands::DecodePayload(decode) => decode.run().await,
+                Commands::ExportParquet(export) => export.run().await,
             };
         }
 
         init_metrics(&self)?;
+        spawn_profiling_server(&self).await?;
+        self.runtime.start_flashblocks_runtime()?;
+
+        // Serve every chain of the chains config from this process, or the single chain
+        // configured by the arguments otherwise
+        let handles = match &self.chains_config {
+            Some(path) => {
+                let mut handles = vec![];
+                for (name, args) in ChainsConfig::load(path)?.into_args()? {
+                    info!(message = "Starting chain", %name);
+                    let worker_threads = args.runtime.runtime_worker_threads;
+                    handles.extend(spawn_chain(&name, worker_threads, move || args.serve()).await?);
+                }
+                handles
+            }
+            None => self.serve().await?,
+        };
+
+        // Capture SIGINT and SIGTERM
+        let mut sigint = unix_signal(SignalKind::interrupt())?;
+        let mut sigterm = unix_signal(SignalKind::terminate())?;
+        let stopped = select_all(
+            handles
+                .iter()
+                .map(|handle| Box::pin(handle.clone().stopped())),
+        );
+
+        tokio::select! {
+            _ = stopped => {
+                // A server has already shut down by itself
+                info!("Server stopped");
+            }
+            _ = sigint.recv() => {
+                info!("Received SIGINT, shutting down gracefully...");
+            }
+            _ = sigterm.recv() => {
+                info!("Received SIGTERM, shutting down gracefully...");
+            }
+        }
+
+        for handle in handles {
+            let _ = handle.stop();
+        }
+
+        Ok(())
+    }
+
+    /// Starts the servers of the chain configured by the arguments.
+    async fn serve(self) -> eyre::Result<Vec<ServerHandle>> {
+        let debug_addrs = bind_addrs(&self.debug_host, self.debug_server_port)?;
+        let flashblocks_addrs = bind_addrs(
+            &self.flashblocks.flashblocks_host,
+            self.flashblocks.flashblocks_port,
+        )?;
+
+        let block_value_threshold = self.block_value_threshold();
+
+        // Behind a load balancer, the clients are identified by the forwarding headers
+        let trusted_proxies = TrustedProxies::from_args(&self.forwarded);
+        let publisher_config = PublisherConfig {
+            ipc: self.ipc.endpoint(self.ipc.flashblocks_ipc_path.as_ref()),
+            cors: CorsPolicy::from_args(&self.cors),
+            trusted_proxies: trusted_proxies.clone(),
+            limits: SubscriberLimits::from_args(&self.flashblocks),
+            registry: Arc::default(),
+            outbox: Outbox::from_args(&self.flashblocks)?.map(Arc::new),
+            gossip: None,
+            multicast: MulticastEmitter::from_args(&self.flashblocks)?.map(Arc::new),
+            jwt_secret: None,
+        };
+        let flashblocks_published =
+            self.flashblocks.flashblocks || self.flashblocks.flashblocks_synthetic;
+        let flashblocks_subscribers =
+            flashblocks_published.then(|| publisher_config.registry.clone());
 
         let l2_client_args = self.l2_client;
 
         let l2_auth_jwt = if let Some(secret) = l2_client_args.l2_jwt_token {
-            secret
+            SharedJwtSecret::new(secret)
         } else if let Some(path) = l2_client_args.l2_jwt_path.as_ref() {
-            JwtSecret::from_file(path)?
+            // Secrets loaded from a file can be rotated with `debug_reloadJwtSecrets`
+            SharedJwtSecret::from_file(path)?
         } else {
             bail!("Missing L2 Client JWT secret");
         };
 
         let l2_client = RpcClient::new(
             l2_client_args.l2_url.clone(),
-            l2_auth_jwt,
+            l2_auth_jwt.clone(),
             l2_client_args.l2_timeout,
             PayloadSource::L2,
         )?;
 
+        // Propagate the flashblocks between regions over gossip, alongside the WebSocket
+        let publisher_config = PublisherConfig {
+            gossip: FlashblocksGossip::from_args(&self.flashblocks.gossip, &l2_client)
+                .await?
+                .map(Arc::new),
+            ..publisher_config
+        };
+
         let builder_args = self.builder;
         let builder_auth_jwt = if let Some(secret) = builder_args.builder_jwt_token {
-            secret
+            SharedJwtSecret::new(secret)
         } else if let Some(path) = builder_args.builder_jwt_path.as_ref() {
-            JwtSecret::from_file(path)?
+            // Secrets loaded from a file can be rotated with `debug_reloadJwtSecrets`
+            SharedJwtSecret::from_file(path)?
         } else {
             bail!("Missing Builder JWT secret");
         };
 
-        let builder_client = RpcClient::new(
+        let builder_tls = builder_args.tls_config().client_config()?;
+        let l2_pool = l2_client_args.pool_config();
+        let builder_pool = builder_args.pool_config();
+        let builder_client = RpcClient::new_with_tls(
             builder_args.builder_url.clone(),
-            builder_auth_jwt,
+            builder_auth_jwt.clone(),
             builder_args.builder_timeout,
             PayloadSource::Builder,
+            builder_tls.clone(),
         )?;
 
         let (probe_layer, probes) = ProbeLayer::new();
+        let quarantine = BuilderQuarantine::from_args(&self.quarantine).map(Arc::new);
+        let builder_scores = BuilderScores::from_args(&self.scoring)?.map(Arc::new);
+        if let Some(scores) = &builder_scores {
+            scores.clone().spawn_persistence();
+        }
+
+        // Elect a leader among the instances of an active/standby deployment
+        let leader_election = LeaderElection::from_args(&self.leader_election).map(Arc::new);
+        if let Some(leader_election) = &leader_election {
+            leader_election.clone().spawn();
+        }
+
+        // Start fallback-only until the builder is promoted to the configured mode
+        let promotion = BuilderPromotion::from_args(
+            &self.promotion,
+            self.execution_mode,
+            Duration::from_secs(self.health_check_interval),
+        );
+        let execution_mode = Arc::new(Mutex::new(match promotion {
+            Some(_) => BuilderPromotion::BOOT_MODE,
+            None => self.execution_mode,
+        }));
+        let webhooks = WebhookNotifier::from_args(&self.webhooks)?.map(Arc::new);
+        let fault_injector = FaultInjector::from_args(&self.chaos).map(Arc::new);
+        if fault_injector.is_some() {
+            warn!("Chaos mode is enabled, faults can be injected through the debug API");
+        }
+        if self.flashblocks.flashblocks_extra_data_tag.is_some() {
+            warn!(
+                "--flashblocks-extra-data-tag is ignored: since Holocene the extra data encodes the EIP-1559 parameters and cannot be tagged"
+            );
+        }
+
+        // Custom payload policy of the chain, applied on top of the built-in policies
+        let policy_plugins = self.wasm_plugin.load()?;
+        let mut attributes_hooks = self.attributes_hooks.hooks();
+        attributes_hooks.extend(policy_plugins.attributes_hook.clone());
+
+        // Split the returned payloads into flashblocks on chains without a flashblocks
+        // builder
+        let synthetic_flashblocks = SyntheticFlashblocks::from_args(
+            &self.flashblocks,
+            &flashblocks_addrs,
+            publisher_config.clone(),
+        )?;
+
+        // Flashblocks received from the builder and payloads assembled from them, kept for
+        // debug_getFlashblocks
+        let (recent_flashblocks, payload_archive) = if self.flashblocks.flashblocks {
+            (
+                Some(Arc::new(RecentFlashblocks::new(
+                    self.flashblocks.flashblocks_recent_payloads,
+                ))),
+                PayloadArchive::from_args(&self.archive)?.map(Arc::new),
+            )
+        } else {
+            (None, None)
+        };
 
         let builder_client: Arc<dyn EngineApiExt> = if self.flashblocks.flashblocks {
+            let limits = FlashblocksLimits::from_args(&self.flashblocks);
+            let staleness_ttl = self.flashblocks.staleness_ttl();
             let inbound_url = self.flashblocks.flashblocks_builder_url;
-            let outbound_addr = SocketAddr::new(
-                IpAddr::from_str(&self.flashblocks.flashblocks_host)?,
-                self.flashblocks.flashblocks_port,
-            );
 
-            Arc::new(Flashblocks::run(
+            let denylist = TransactionDenylist::new(
+                self.flashblocks.flashblocks_denied_addresses,
+                self.flashblocks.flashblocks_denied_selectors,
+            )
+            .unwrap_or_default()
+            .with_filter(policy_plugins.transaction_filter.clone());
+
+            // Replicate the payload being built to the standby instance
+            let state_sync = StateSync::from_args(
+                &self.leader_election,
+                leader_election.clone(),
+                execution_mode.clone(),
+            )?
+            .map(Arc::new);
+
+            let service = FlashblocksService::new(
                 builder_client.clone(),
+                &flashblocks_addrs,
+                publisher_config,
+            )?
+            .with_denylist((!denylist.is_empty()).then_some(denylist))
+            .with_quarantine(quarantine.clone())
+            .with_builder_scores(builder_scores.clone())
+            .with_builder_streams(
+                self.flashblocks.flashblocks_builder_streams.clone(),
+                self.flashblocks.flashblocks_conflict_resolution,
+            )
+            .with_merge(
+                self.flashblocks
+                    .flashblocks_merge
+                    .then(|| l2_client.clone()),
+            )
+            .with_leader_election(leader_election.clone())
+            .with_state_sync(state_sync.clone())
+            .with_webhooks(webhooks.clone())
+            .with_limits(limits)
+            .with_staleness_ttl(staleness_ttl)
+            .with_payload_id_ttl(Some(self.flashblocks.payload_id_ttl()))
+            .with_clock_skew_check(self.flashblocks.clock_skew_check())
+            .with_fault_injector(fault_injector.clone())
+            .with_clickhouse(ClickHouseSink::from_args(&self.clickhouse)?.map(Arc::new))
+            .with_archive(payload_archive.clone())
+            .with_recent_flashblocks(recent_flashblocks.clone());
+            let service = Flashblocks::run(
+                service,
                 inbound_url,
-                outbound_addr,
-            )?)
+                self.flashblocks.flashblocks_ingest_queue_size,
+                self.flashblocks.flashblocks_ingest_overflow,
+            );
+            if let Some(state_sync) = state_sync {
+                state_sync.spawn(service.clone());
+            }
+            Arc::new(service)
+        } else if let Some(relay) = RelayClient::from_args(&self.relay)? {
+            info!("Sourcing the builder blocks from a relay");
+            Arc::new(relay)
         } else {
             Arc::new(builder_client)
         };
 
-        let execution_mode = Arc::new(Mutex::new(self.execution_mode));
+        // Inject the faults set through the debug API in the calls to the builder
+        let builder_client: Arc<dyn EngineApiExt> = match &fault_injector {
+            Some(faults) => Arc::new(FaultInjectedClient::new(builder_client, faults.clone())),
+            None => builder_client,
+        };
+
+        // Stop building blocks with the builder while it is quarantined
+        let builder_client: Arc<dyn EngineApiExt> = match &quarantine {
+            Some(quarantine) => Arc::new(QuarantinedClient::new(
+                DEFAULT_BUILDER.to_string(),
+                builder_client,
+                quarantine.clone(),
+            )),
+            None => builder_client,
+        };
+
+        // Manage the builders in a registry, so that more builders can be added at runtime
+        let builder_registry = self
+            .builder_registry_path
+            .map(|path| {
+                BuilderRegistry::new(
+                    builder_client.clone(),
+                    &builder_args.builder_url,
+                    builder_args.builder_timeout,
+                )
+                .with_default_priority(self.builder_priority, self.builder_weight)
+                .with_tls(builder_tls.clone())
+                .with_quarantine(quarantine.clone())
+                // The other half of the budget is left to validate the selected payload
+                .with_get_payload_budget(
+                    self.get_payload_budget_ms
+                        .map(|budget| Duration::from_millis(budget) / 2),
+                )
+                .with_persistence(path)
+                .map(Arc::new)
+            })
+            .transpose()?;
+        let builder_client: Arc<dyn EngineApiExt> = match &builder_registry {
+            Some(registry) => registry.clone(),
+            None => builder_client,
+        };
+
+        // Refuse to serve if the clients are not on the configured chain
+        let chain_id_guard = ChainIdGuard::from_args(&self.chain_id).map(Arc::new);
+        if let Some(guard) = &chain_id_guard {
+            guard.preflight(builder_client.as_ref(), &l2_client).await?;
+        }
+
         let rollup_boost = RollupBoostServer::new(
             l2_client,
             builder_client,
@@ -192,59 +879,155 @@ impl Args {
             probes.clone(),
             self.health_check_interval,
             self.max_unsafe_interval,
-        );
+        )
+        .with_import_builder_blocks(self.import_builder_blocks)
+        .with_get_payload_budget(self.get_payload_budget_ms.map(Duration::from_millis))
+        .with_fcu_forwarding(self.builder_fcu_forwarding)
+        .with_new_payload_forwarding(self.builder_new_payload_forwarding)
+        .with_attributes_policy(AttributesPolicy::from_args(&self.attributes_policy))
+        .with_attributes_hooks(attributes_hooks)
+        .with_payload_selector(policy_plugins.payload_selector)
+        .with_block_value_threshold(block_value_threshold)
+        .with_jwt_secrets(vec![
+            (PayloadSource::L2, l2_auth_jwt.clone()),
+            (PayloadSource::Builder, builder_auth_jwt.clone()),
+        ])
+        .with_builder_registry(builder_registry)
+        .with_builder_quarantine(quarantine)
+        .with_builder_scores(builder_scores)
+        .with_payload_validator(
+            PayloadValidator::from_args(&self.validator, &l2_auth_jwt)?.map(Arc::new),
+        )
+        .with_warmup(BuilderWarmup::from_args(&self.warmup).map(Arc::new))
+        .with_head_lag_monitor(HeadLagMonitor::from_args(&self.head_lag))
+        .with_divergence_detector(DivergenceDetector::from_args(&self.divergence))
+        .with_leader_election(leader_election)
+        .with_chain_id_guard(chain_id_guard)
+        .with_audit_log(AuditLog::from_args(&self.audit)?)
+        .with_debug_auth(DebugAuth::from_args(&self.audit)?)
+        .with_webhooks(webhooks)
+        .with_builder_promotion(promotion)
+        .with_fault_injector(fault_injector)
+        .with_synthetic_flashblocks(synthetic_flashblocks)
+        .with_flashblocks_subscribers(flashblocks_subscribers)
+        .with_payload_history_size(self.payload_history_size)
+        .with_flashblocks_stores(recent_flashblocks, payload_archive);
 
         // Spawn the debug server
-        rollup_boost.start_debug_server(debug_addr.as_str()).await?;
+        for debug_addr in &debug_addrs {
+            rollup_boost
+                .start_debug_server(&debug_addr.to_string())
+                .await?;
+        }
+        if let Some(endpoint) = self.ipc.endpoint(self.ipc.debug_ipc_path.as_ref()) {
+            rollup_boost.start_debug_ipc_server(&endpoint)?;
+        }
+
+        // Serve the dashboard of the flashblocks published by rollup-boost
+        Dashboard::new(probes.clone(), execution_mode.clone())
+            .with_cors(CorsPolicy::from_args(&self.cors))
+            .with_flashblocks_port(
+                flashblocks_published.then_some(self.flashblocks.flashblocks_port),
+            )
+            .serve(&self.dashboard)
+            .await?;
 
         let module: RpcModule<()> = rollup_boost.try_into()?;
 
         // Build and start the server
         info!("Starting server on :{}", self.rpc_port);
 
-        let http_middleware =
-            tower::ServiceBuilder::new()
-                .layer(probe_layer)
-                .layer(ProxyLayer::new(
-                    l2_client_args.l2_url,
-                    l2_auth_jwt,
-                    builder_args.builder_url,
-                    builder_auth_jwt,
-                    probes,
-                    execution_mode,
-                ));
-
-        let server = Server::builder()
-            .set_http_middleware(http_middleware)
-            .build(format!("{}:{}", self.rpc_host, self.rpc_port).parse::<SocketAddr>()?)
-            .await?;
-        let handle = server.start(module);
+        let http_middleware = tower::ServiceBuilder::new().layer(probe_layer).layer(
+            ProxyLayer::new(
+                l2_client_args.l2_url,
+                l2_auth_jwt.clone(),
+                builder_args.builder_url,
+                builder_auth_jwt,
+                probes,
+                execution_mode,
+            )
+            .with_routing(RpcRouting::from_args(&self.rpc_routing))
+            .with_tx_ingress(TxIngress::from_args(&self.tx_ingress))
+            .with_rate_limiter(RpcRateLimiter::from_args(&self.rate_limit))
+            .with_rpc_logger(RpcLogger::from_args(&self.rpc_log))
+            .with_trusted_proxies(trusted_proxies)
+            .with_builder_tls(builder_tls)
+            .with_connection_pools(l2_pool, builder_pool),
+        );
 
-        let stop_handle = handle.clone();
+        let mut handles = vec![];
 
-        // Capture SIGINT and SIGTERM
-        let mut sigint = unix_signal(SignalKind::interrupt())?;
-        let mut sigterm = unix_signal(SignalKind::terminate())?;
+        // Serve the Engine API proxy on a unix domain socket for colocated consensus clients
+        if let Some(endpoint) = self.ipc.endpoint(self.ipc.rpc_ipc_path.as_ref()) {
+            let builder = Server::builder()
+                .set_http_middleware(http_middleware.clone())
+                .to_service_builder();
+            handles.push(spawn_ipc_server(endpoint.bind()?, |stop_handle| {
+                builder.build(module.clone(), stop_handle)
+            }));
+        }
 
-        tokio::select! {
-            _ = handle.stopped() => {
-                // The server has already shut down by itself
-                info!("Server stopped");
-            }
-            _ = sigint.recv() => {
-                info!("Received SIGINT, shutting down gracefully...");
-                let _ = stop_handle.stop();
-            }
-            _ = sigterm.recv() => {
-                info!("Received SIGTERM, shutting down gracefully...");
-                let _ = stop_handle.stop();
+        // Serve the Engine API proxy with the address of the clients available to the rate
+        // limiter, on each of the rpc hosts
+        let builder = Server::builder()
+            .set_http_middleware(http_middleware)
+            .to_service_builder();
+        for addr in bind_addrs(&self.rpc_host, self.rpc_port)? {
+            let listener = TcpListener::bind(addr).await?;
+            info!("Engine API proxy listening on {}", listener.local_addr()?);
+            handles.push(spawn_tcp_server(listener, |stop_handle| {
+                builder.clone().build(module.clone(), stop_handle)
+            }));
+        }
+
+        // Serve the Engine API proxy over websocket, authenticating the connections with
+        // JWT, through the same middleware as the rpc hosts
+        if let Some(port) = self.ws.rpc_ws_port {
+            let secret = self.ws.jwt_secret(l2_auth_jwt.get())?;
+            info!("Starting websocket server on :{}", port);
+
+            for addr in bind_addrs(&self.rpc_host, port)? {
+                let listener = TcpListener::bind(addr).await?;
+                info!("Websocket server listening on {}", listener.local_addr()?);
+                handles.push(spawn_ws_server(listener, secret, |stop_handle| {
+                    builder.clone().build(module.clone(), stop_handle)
+                }));
             }
         }
 
-        Ok(())
+        Ok(handles)
+    }
+}
+
+impl Args {
+    fn block_value_threshold(&self) -> Option<BlockValueThreshold> {
+        if self.min_builder_block_value.is_none() && self.min_builder_block_value_margin.is_none() {
+            return None;
+        }
+
+        Some(BlockValueThreshold {
+            min_value: self.min_builder_block_value.unwrap_or_default(),
+            min_margin: self.min_builder_block_value_margin,
+        })
     }
 }
 
+/// Reads the flashblocks of a payload from `path`, or stdin, as a JSON array or JSON lines.
+fn read_flashblocks(path: Option<&Path>) -> eyre::Result<Vec<FlashblocksPayloadV1>> {
+    let input = match path {
+        Some(path) => std::fs::read_to_string(path)?,
+        None => std::io::read_to_string(std::io::stdin())?,
+    };
+    if input.trim_start().starts_with('[') {
+        return Ok(serde_json::from_str(&input)?);
+    }
+    input
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
+
 #[derive(Clone, Debug)]
 pub enum LogFormat {
     Json,
@@ -270,6 +1053,19 @@ pub enum Commands {
         #[command(subcommand)]
         command: DebugCommands,
     },
+
+    /// Drive FCU, getPayload and newPayload cycles against a running instance and
+    /// report their latencies
+    Bench(BenchArgs),
+
+    /// Tail a flashblocks websocket, checking the continuity of the stream
+    Stream(StreamArgs),
+
+    /// Decode a recorded payload envelope or flashblocks sequence and validate it offline
+    DecodePayload(DecodePayloadArgs),
+
+    /// Export the payload archive to Parquet files, one row per flashblock
+    ExportParquet(ExportParquetArgs),
 }
 
 #[derive(Clone, Subcommand, Debug)]
@@ -279,4 +1075,155 @@ pub enum DebugCommands {
 
     /// Get the execution mode
     ExecutionMode {},
+
+    /// Get the execution mode and the builders in fan-out order
+    Status {},
+
+    /// Start or stop draining: payload attributes are only sent to the l2 client, and the
+    /// payloads the builder already started building are still served
+    SetDrainMode {
+        #[arg(action = clap::ArgAction::Set)]
+        enabled: bool,
+
+        /// Wait until it is safe to stop rollup-boost
+        #[arg(long, default_value = "false")]
+        wait: bool,
+    },
+
+    /// Set the log filter (e.g. `info,rollup_boost::flashblocks=debug`)
+    SetLogFilter { filter: String },
+
+    /// Re-read the builder and L2 JWT secret files
+    ReloadJwtSecrets {},
+
+    /// Register a builder, requires `--builder-registry-path`
+    RegisterBuilder {
+        name: String,
+        url: String,
+
+        /// Hex encoded JWT secret of the builder
+        #[arg(long)]
+        jwt_token: Option<String>,
+
+        /// File to read the JWT secret of the builder from
+        #[arg(long)]
+        jwt_path: Option<PathBuf>,
+
+        #[arg(long, default_value = "0")]
+        priority: u32,
+
+        #[arg(long, default_value = "1")]
+        weight: u32,
+    },
+
+    /// Enable or disable a registered builder
+    SetBuilderEnabled {
+        name: String,
+        #[arg(action = clap::ArgAction::Set)]
+        enabled: bool,
+    },
+
+    /// Remove a builder registered at runtime
+    RemoveBuilder { name: String },
+
+    /// List the registered builders
+    ListBuilders {},
+
+    /// Print the execution mode changes recorded in the audit log
+    AuditLog {},
+
+    /// Inject faults in the builder RPCs and flashblocks, requires `--chaos`. Faults not
+    /// given are cleared
+    SetFaults {
+        /// Latency added to every builder RPC, in milliseconds
+        #[arg(long, default_value = "0")]
+        latency_ms: u64,
+
+        /// Percentage of the flashblocks from the builder to drop
+        #[arg(long, default_value = "0")]
+        drop_flashblocks_percent: u8,
+
+        /// JSON-RPC error code returned instead of calling the builder
+        #[arg(long, requires = "error_message", allow_negative_numbers = true)]
+        error_code: Option<i32>,
+
+        #[arg(long, requires = "error_code")]
+        error_message: Option<String>,
+
+        /// RPCs failing with the error, all of them if not set
+        #[arg(long, value_delimiter = ',')]
+        error_rpcs: Vec<BuilderRpc>,
+    },
+
+    /// Print the injected faults
+    Faults {},
+
+    /// List the subscribers of the flashblocks websocket, with the messages delivered to
+    /// and dropped for each of them
+    Subscribers {},
+
+    /// List the last payload cycles, with the source of each block and why the l2 client
+    /// block was returned if it was
+    Payloads {
+        /// Number of payload cycles listed
+        #[arg(long, default_value = "20")]
+        count: usize,
+    },
+
+    /// Print the flashblocks received for a payload as JSON lines, from memory or the
+    /// payload archive
+    Flashblocks { payload_id: B64 },
+
+    /// Print the metrics recorded by rollup-boost, without a Prometheus scrape
+    Metrics {
+        /// Only print the metrics whose name contains this string
+        #[arg(long)]
+        filter: Option<String>,
+    },
+
+    /// Print the rolling quality score of each builder, requires `--builder-scoring`
+    BuilderScores {},
+
+    /// Check recorded flashblocks against the rules applied by rollup-boost. Reads a JSON
+    /// array or JSON lines of the flashblocks of a payload, from stdin if no path is given
+    ValidateFlashblocks { path: Option<PathBuf> },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_flashblocks() -> eyre::Result<()> {
+        let path = std::env::temp_dir().join(format!(
+            "rollup-boost-flashblocks-{}.json",
+            std::process::id()
+        ));
+        let flashblock = |index| {
+            serde_json::to_string(&FlashblocksPayloadV1 {
+                index,
+                ..Default::default()
+            })
+        };
+        let indexes = |flashblocks: Vec<FlashblocksPayloadV1>| {
+            flashblocks
+                .iter()
+                .map(|flashblock| flashblock.index)
+                .collect::<Vec<_>>()
+        };
+
+        std::fs::write(&path, format!("[{},{}]", flashblock(0)?, flashblock(1)?))?;
+        assert_eq!(indexes(read_flashblocks(Some(path.as_path()))?), vec![0, 1]);
+
+        // JSON lines, blank lines being skipped
+        std::fs::write(&path, format!("{}\n\n{}\n", flashblock(0)?, flashblock(1)?))?;
+        assert_eq!(indexes(read_flashblocks(Some(path.as_path()))?), vec![0, 1]);
+
+        std::fs::write(&path, format!("{}\nnot a flashblock\n", flashblock(0)?))?;
+        assert!(read_flashblocks(Some(path.as_path())).is_err());
+
+        std::fs::remove_file(&path)?;
+        assert!(read_flashblocks(Some(path.as_path())).is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,287 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use alloy_rpc_types_engine::PayloadId;
+use clap::Parser;
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use url::Url;
+
+use crate::FlashblocksPayloadV1;
+
+#[derive(Parser, Clone, Debug)]
+pub struct ClickHouseArgs {
+    /// URL of the HTTP interface of the ClickHouse server a row is written to for each
+    /// accepted flashblock, disabled if not set. Requires the `clickhouse` feature
+    #[arg(long, env)]
+    pub clickhouse_url: Option<Url>,
+
+    /// Table the flashblock rows are inserted into
+    #[arg(long, env, default_value = "flashblocks")]
+    pub clickhouse_table: String,
+
+    /// User the rows are inserted as
+    #[arg(long, env, default_value = "default")]
+    pub clickhouse_user: String,
+
+    /// Password of the ClickHouse user
+    #[arg(long, env)]
+    pub clickhouse_password: Option<String>,
+
+    /// Maximum number of rows inserted at once
+    #[arg(long, env, default_value = "1000")]
+    pub clickhouse_batch_size: usize,
+
+    /// Interval in milliseconds at which the pending rows are inserted
+    #[arg(long, env, default_value = "1000")]
+    pub clickhouse_flush_interval_ms: u64,
+}
+
+/// A flashblock accepted from the builder, as inserted in ClickHouse.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FlashblockRow {
+    /// Unix timestamp at which the flashblock was received, in milliseconds
+    pub received_at: u64,
+    pub payload_id: PayloadId,
+    pub block_number: u64,
+    pub index: u64,
+    pub tx_count: u64,
+    /// Gas used by the block up to and including the flashblock
+    pub gas_used: u64,
+    /// Milliseconds between the forkchoice update that started the payload and the
+    /// flashblock, if it was received by this instance
+    pub fcu_latency_ms: Option<u64>,
+}
+
+impl FlashblockRow {
+    pub fn new(
+        payload: &FlashblocksPayloadV1,
+        block_number: u64,
+        fcu_latency: Option<Duration>,
+    ) -> Self {
+        Self {
+            received_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            payload_id: payload.payload_id,
+            block_number,
+            index: payload.index,
+            tx_count: payload.diff.transactions.len() as u64,
+            gas_used: payload.diff.gas_used,
+            fcu_latency_ms: fcu_latency.map(|latency| latency.as_millis() as u64),
+        }
+    }
+}
+
+/// Writes a row for each flashblock accepted from the builder to ClickHouse, so that
+/// preconfirmation latency can be analyzed without scraping the logs.
+///
+/// Rows are batched and inserted in the background in the `JSONEachRow` format, and
+/// never delay the flashblocks. They are dropped if ClickHouse cannot keep up, and failed
+/// inserts are not retried.
+#[derive(Debug)]
+pub struct ClickHouseSink {
+    sender: mpsc::Sender<FlashblockRow>,
+}
+
+impl ClickHouseSink {
+    /// Starts inserting the rows if the sink is enabled by the arguments. Must be called
+    /// from a tokio runtime.
+    pub fn from_args(args: &ClickHouseArgs) -> eyre::Result<Option<Self>> {
+        let Some(url) = &args.clickhouse_url else {
+            return Ok(None);
+        };
+
+        #[cfg(feature = "clickhouse")]
+        {
+            let sender = client::ClickHouseClient::new(url, args)?.spawn(
+                args.clickhouse_batch_size.max(1),
+                Duration::from_millis(args.clickhouse_flush_interval_ms),
+            );
+            Ok(Some(Self { sender }))
+        }
+
+        #[cfg(not(feature = "clickhouse"))]
+        eyre::bail!(
+            "cannot write flashblocks to {url}, rollup-boost was built without the clickhouse feature"
+        )
+    }
+
+    /// Queues a row to be inserted.
+    pub fn record(&self, row: FlashblockRow) {
+        if self.sender.try_send(row).is_err() {
+            counter!("clickhouse.dropped").increment(1);
+        }
+    }
+}
+
+#[cfg(feature = "clickhouse")]
+mod client {
+    use std::time::Duration;
+
+    use alloy_primitives::bytes::Bytes;
+    use http::{Method, Request, header::CONTENT_TYPE};
+    use http_body_util::Full;
+    use hyper_rustls::HttpsConnector;
+    use hyper_util::{
+        client::legacy::{Client, connect::HttpConnector},
+        rt::TokioExecutor,
+    };
+    use metrics::counter;
+    use tokio::sync::mpsc;
+    use tracing::{debug, warn};
+    use url::Url;
+
+    use super::{ClickHouseArgs, FlashblockRow};
+
+    /// Number of rows waiting to be written before new ones are dropped.
+    const QUEUE_SIZE: usize = 16384;
+
+    const USER_HEADER: &str = "x-clickhouse-user";
+    const KEY_HEADER: &str = "x-clickhouse-key";
+
+    pub(super) struct ClickHouseClient {
+        client: Client<HttpsConnector<HttpConnector>, Full<Bytes>>,
+        url: Url,
+        user: String,
+        password: Option<String>,
+    }
+
+    impl ClickHouseClient {
+        pub(super) fn new(url: &Url, args: &ClickHouseArgs) -> eyre::Result<Self> {
+            let connector = hyper_rustls::HttpsConnectorBuilder::new()
+                .with_native_roots()?
+                .https_or_http()
+                .enable_http1()
+                .build();
+
+            let mut url = url.clone();
+            url.query_pairs_mut().append_pair(
+                "query",
+                &format!("INSERT INTO {} FORMAT JSONEachRow", args.clickhouse_table),
+            );
+
+            Ok(Self {
+                client: Client::builder(TokioExecutor::new()).build(connector),
+                url,
+                user: args.clickhouse_user.clone(),
+                password: args.clickhouse_password.clone(),
+            })
+        }
+
+        /// Starts inserting the rows sent to the returned channel, once `batch_size` are
+        /// pending or every `flush_interval`.
+        pub(super) fn spawn(
+            self,
+            batch_size: usize,
+            flush_interval: Duration,
+        ) -> mpsc::Sender<FlashblockRow> {
+            let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+            tokio::spawn(self.run(receiver, batch_size, flush_interval));
+            sender
+        }
+
+        async fn run(
+            self,
+            mut receiver: mpsc::Receiver<FlashblockRow>,
+            batch_size: usize,
+            flush_interval: Duration,
+        ) {
+            let mut interval = tokio::time::interval(flush_interval);
+            let mut rows = Vec::with_capacity(batch_size);
+            loop {
+                tokio::select! {
+                    row = receiver.recv() => match row {
+                        Some(row) => {
+                            rows.push(row);
+                            if rows.len() >= batch_size {
+                                self.insert(&mut rows).await;
+                            }
+                        }
+                        None => {
+                            self.insert(&mut rows).await;
+                            return;
+                        }
+                    },
+                    _ = interval.tick() => self.insert(&mut rows).await,
+                }
+            }
+        }
+
+        async fn insert(&self, rows: &mut Vec<FlashblockRow>) {
+            if rows.is_empty() {
+                return;
+            }
+            let mut body = Vec::new();
+            for row in rows.iter() {
+                if serde_json::to_writer(&mut body, row).is_ok() {
+                    body.push(b'\n');
+                }
+            }
+            let count = rows.len() as u64;
+            rows.clear();
+
+            let mut request = Request::builder()
+                .method(Method::POST)
+                .uri(self.url.as_str())
+                .header(CONTENT_TYPE, "application/x-ndjson")
+                .header(USER_HEADER, &self.user);
+            if let Some(password) = &self.password {
+                request = request.header(KEY_HEADER, password);
+            }
+            let request = match request.body(Full::new(Bytes::from(body))) {
+                Ok(request) => request,
+                Err(e) => {
+                    warn!(message = "invalid ClickHouse request", error = %e);
+                    return;
+                }
+            };
+
+            let status = match self.client.request(request).await {
+                Ok(response) if response.status().is_success() => {
+                    debug!(message = "inserted flashblocks in ClickHouse", count);
+                    "ok"
+                }
+                Ok(response) => {
+                    warn!(message = "ClickHouse rejected the flashblocks", count, status = %response.status());
+                    "error"
+                }
+                Err(e) => {
+                    warn!(message = "failed to insert flashblocks in ClickHouse", count, error = %e);
+                    "error"
+                }
+            };
+            counter!("clickhouse.inserted_rows", "status" => status).increment(count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ExecutionPayloadFlashblockDeltaV1, FlashblocksPayloadV1};
+    use alloy_primitives::Bytes;
+
+    #[test]
+    fn test_flashblock_row() {
+        let payload = FlashblocksPayloadV1 {
+            payload_id: PayloadId::new([1; 8]),
+            index: 2,
+            base: None,
+            diff: ExecutionPayloadFlashblockDeltaV1 {
+                gas_used: 42_000,
+                transactions: vec![Bytes::from_static(&[1]), Bytes::from_static(&[2])],
+                ..Default::default()
+            },
+            metadata: serde_json::Value::Null,
+        };
+        let row = FlashblockRow::new(&payload, 100, Some(Duration::from_millis(450)));
+        let json = serde_json::to_value(&row).unwrap();
+        assert_eq!(json["payload_id"], "0x0101010101010101");
+        assert_eq!(json["block_number"], 100);
+        assert_eq!(json["tx_count"], 2);
+        assert_eq!(json["gas_used"], 42_000);
+        assert_eq!(json["fcu_latency_ms"], 450);
+    }
+}
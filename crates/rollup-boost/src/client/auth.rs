@@ -1,8 +1,11 @@
 // From reth_rpc_layer
-use alloy_rpc_types_engine::{Claims, JwtSecret};
+use alloy_rpc_types_engine::{Claims, JwtError, JwtSecret};
 use http::{HeaderValue, header::AUTHORIZATION};
+use parking_lot::RwLock;
 use std::{
     iter::once,
+    path::{Path, PathBuf},
+    sync::Arc,
     task::{Context, Poll},
     time::{SystemTime, UNIX_EPOCH},
 };
@@ -11,16 +14,70 @@ use tower_http::sensitive_headers::{SetSensitiveRequestHeaders, SetSensitiveRequ
 
 pub type Auth<S> = AuthService<SetSensitiveRequestHeaders<S>>;
 
+/// A JWT secret shared by the clients of an upstream. Secrets loaded from a file can be
+/// re-read to rotate them without restarting.
+#[derive(Clone, Debug)]
+pub struct SharedJwtSecret {
+    secret: Arc<RwLock<JwtSecret>>,
+    path: Option<PathBuf>,
+}
+
+impl SharedJwtSecret {
+    pub fn new(secret: JwtSecret) -> Self {
+        Self {
+            secret: Arc::new(RwLock::new(secret)),
+            path: None,
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, JwtError> {
+        Ok(Self {
+            secret: Arc::new(RwLock::new(JwtSecret::from_file(path)?)),
+            path: Some(path.to_path_buf()),
+        })
+    }
+
+    pub fn get(&self) -> JwtSecret {
+        *self.secret.read()
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Re-reads the secret from its file, returning whether it changed. Secrets that
+    /// were not loaded from a file are left untouched.
+    pub fn reload(&self) -> Result<bool, JwtError> {
+        let Some(path) = &self.path else {
+            return Ok(false);
+        };
+
+        let secret = JwtSecret::from_file(path)?;
+        let mut current = self.secret.write();
+        let changed = *current != secret;
+        *current = secret;
+        Ok(changed)
+    }
+}
+
+impl From<JwtSecret> for SharedJwtSecret {
+    fn from(secret: JwtSecret) -> Self {
+        Self::new(secret)
+    }
+}
+
 /// A layer that adds a new JWT token to every request using `AuthClientService`.
 #[derive(Clone, Debug)]
 pub struct AuthLayer {
-    secret: JwtSecret,
+    secret: SharedJwtSecret,
 }
 
 impl AuthLayer {
     /// Create a new `AuthClientLayer` with the given `secret`.
-    pub const fn new(secret: JwtSecret) -> Self {
-        Self { secret }
+    pub fn new(secret: impl Into<SharedJwtSecret>) -> Self {
+        Self {
+            secret: secret.into(),
+        }
     }
 }
 
@@ -29,19 +86,19 @@ impl<S> Layer<S> for AuthLayer {
 
     fn layer(&self, inner: S) -> Self::Service {
         let inner = SetSensitiveRequestHeadersLayer::new(once(AUTHORIZATION)).layer(inner);
-        AuthService::new(self.secret, inner)
+        AuthService::new(self.secret.clone(), inner)
     }
 }
 
 /// Automatically authenticates every client request with the given `secret`.
 #[derive(Debug, Clone)]
 pub struct AuthService<S> {
-    secret: JwtSecret,
+    secret: SharedJwtSecret,
     inner: S,
 }
 
 impl<S> AuthService<S> {
-    const fn new(secret: JwtSecret, inner: S) -> Self {
+    const fn new(secret: SharedJwtSecret, inner: S) -> Self {
         Self { secret, inner }
     }
 }
@@ -62,7 +119,7 @@ where
     fn call(&mut self, mut request: http::Request<B>) -> Self::Future {
         request
             .headers_mut()
-            .insert(AUTHORIZATION, secret_to_bearer_header(&self.secret));
+            .insert(AUTHORIZATION, secret_to_bearer_header(&self.secret.get()));
         self.inner.call(request)
     }
 }
@@ -86,3 +143,33 @@ pub fn secret_to_bearer_header(secret: &JwtSecret) -> HeaderValue {
     .parse()
     .expect("Failed to parse JWT Header")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::hex;
+
+    #[test]
+    fn test_reload_shared_secret() -> eyre::Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("rollup-boost-jwt-{}.hex", std::process::id()));
+        let first = JwtSecret::random();
+        std::fs::write(&path, hex::encode(first.as_bytes()))?;
+
+        let secret = SharedJwtSecret::from_file(&path)?;
+        let clone = secret.clone();
+        assert_eq!(secret.get(), first);
+        assert!(!secret.reload()?);
+
+        let second = JwtSecret::random();
+        std::fs::write(&path, hex::encode(second.as_bytes()))?;
+        assert!(secret.reload()?);
+        assert_eq!(clone.get(), second);
+
+        // Secrets not loaded from a file are never reloaded
+        assert!(!SharedJwtSecret::new(first).reload()?);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -1,60 +1,121 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
-use crate::client::auth::AuthLayer;
+use crate::client::auth::{AuthLayer, SharedJwtSecret};
+use crate::client::pool::ConnectionPoolConfig;
+use crate::correlation::{CorrelationIdLayer, CorrelationIdService};
 use crate::payload::PayloadSource;
 use alloy_primitives::bytes::Bytes;
-use alloy_rpc_types_engine::JwtSecret;
 use http::Uri;
 use http_body_util::{BodyExt, Full};
 use hyper::body::Body;
 use hyper_rustls::HttpsConnector;
 use hyper_util::client::legacy::Client;
 use hyper_util::client::legacy::connect::HttpConnector;
-use hyper_util::rt::TokioExecutor;
 use jsonrpsee::core::BoxError;
 use jsonrpsee::server::HttpBody;
+use metrics::counter;
 use opentelemetry::trace::SpanKind;
+use parking_lot::RwLock;
+use rustls::ClientConfig;
 use tower::{
     Service as _, ServiceBuilder, ServiceExt,
-    timeout::{Timeout, TimeoutLayer},
+    timeout::{Timeout, TimeoutLayer, error::Elapsed},
 };
 use tower_http::decompression::{Decompression, DecompressionLayer};
 use tracing::{debug, error, instrument};
 
 use super::auth::Auth;
 
-pub type HttpClientService =
-    Timeout<Decompression<Auth<Client<HttpsConnector<HttpConnector>, HttpBody>>>>;
+pub type HttpClientService = Timeout<
+    Decompression<Auth<CorrelationIdService<Client<HttpsConnector<HttpConnector>, HttpBody>>>>,
+>;
 
 #[derive(Clone, Debug)]
 pub struct HttpClient {
-    client: HttpClientService,
+    client: Arc<RwLock<HttpClientService>>,
     url: Uri,
     target: PayloadSource,
+    secret: SharedJwtSecret,
+    tls: Option<ClientConfig>,
+    pool: ConnectionPoolConfig,
 }
 
 impl HttpClient {
-    pub fn new(url: Uri, secret: JwtSecret, target: PayloadSource) -> Self {
-        let connector = hyper_rustls::HttpsConnectorBuilder::new()
-            .with_native_roots()
-            .expect("no native root CA certificates found")
+    pub fn new(url: Uri, secret: impl Into<SharedJwtSecret>, target: PayloadSource) -> Self {
+        Self::new_with_tls(url, secret, target, None)
+    }
+
+    /// Creates a client connecting with the given TLS configuration instead of the native
+    /// roots, e.g. to authenticate with a client certificate.
+    pub fn new_with_tls(
+        url: Uri,
+        secret: impl Into<SharedJwtSecret>,
+        target: PayloadSource,
+        tls: Option<ClientConfig>,
+    ) -> Self {
+        Self::new_with_pool(url, secret, target, tls, ConnectionPoolConfig::default())
+    }
+
+    /// Creates a client connecting with the given TLS configuration and connection pool
+    /// settings.
+    pub fn new_with_pool(
+        url: Uri,
+        secret: impl Into<SharedJwtSecret>,
+        target: PayloadSource,
+        tls: Option<ClientConfig>,
+        pool: ConnectionPoolConfig,
+    ) -> Self {
+        let secret = secret.into();
+        let client = Self::connect(&secret, tls.clone(), &pool);
+
+        Self {
+            client: Arc::new(RwLock::new(client)),
+            url,
+            target,
+            secret,
+            tls,
+            pool,
+        }
+    }
+
+    fn connect(
+        secret: &SharedJwtSecret,
+        tls: Option<ClientConfig>,
+        pool: &ConnectionPoolConfig,
+    ) -> HttpClientService {
+        let builder = hyper_rustls::HttpsConnectorBuilder::new();
+        let builder = match tls {
+            Some(tls) => builder.with_tls_config(tls),
+            None => builder
+                .with_native_roots()
+                .expect("no native root CA certificates found"),
+        };
+        let connector = builder
             .https_or_http()
             .enable_http1()
             .enable_http2()
-            .build();
+            .wrap_connector(pool.connector());
 
-        let client = Client::builder(TokioExecutor::new()).build(connector);
+        let client = pool.client_builder().build(connector);
 
-        let client = ServiceBuilder::new()
+        ServiceBuilder::new()
             .layer(TimeoutLayer::new(Duration::from_secs(1)))
             .layer(DecompressionLayer::new())
-            .layer(AuthLayer::new(secret))
-            .service(client);
+            .layer(AuthLayer::new(secret.clone()))
+            .layer(CorrelationIdLayer)
+            .service(client)
+    }
 
-        Self {
-            client,
-            url,
-            target,
+    /// Drops the pooled connections after a request failed to connect or timed out, so
+    /// that the next request resolves the host again instead of reusing connections to
+    /// an address that is no longer served, e.g. behind a load balancer.
+    fn reset_on_connection_error(&self, e: &BoxError) {
+        let connection_error = e.is::<Elapsed>()
+            || e.downcast_ref::<hyper_util::client::legacy::Error>()
+                .is_some_and(|e| e.is_connect());
+        if connection_error {
+            counter!("http_client.reconnects", "target" => self.target.to_string()).increment(1);
+            *self.client.write() = Self::connect(&self.secret, self.tls.clone(), &self.pool);
         }
     }
 
@@ -85,7 +146,14 @@ impl HttpClient {
 
         let req = req.map(HttpBody::new);
 
-        let res = self.client.ready().await?.call(req).await?;
+        let mut client = self.client.read().clone();
+        let res = match client.ready().await?.call(req).await {
+            Ok(res) => res,
+            Err(e) => {
+                self.reset_on_connection_error(&e);
+                return Err(e);
+            }
+        };
 
         let (parts, body) = res.into_parts();
         let body_bytes = body.collect().await?.to_bytes();
@@ -1,3 +1,5 @@
 pub mod auth;
 pub mod http;
+pub mod pool;
 pub mod rpc;
+pub mod tls;
@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use hyper_util::client::legacy::{Builder, Client, connect::HttpConnector};
+use hyper_util::rt::{TokioExecutor, TokioTimer};
+
+/// Connections idle for longer are closed, as in hyper.
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Delay before racing the other address family of a dual-stack host, as in hyper.
+const DEFAULT_HAPPY_EYEBALLS_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// Settings of the connection pool of the HTTP client of an upstream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnectionPoolConfig {
+    /// Idle connections kept per host, unlimited if not set
+    pub max_idle_per_host: Option<usize>,
+    /// Idle connections are closed after this long
+    pub idle_timeout: Duration,
+    /// Interval of the TCP keep-alive probes, and of the HTTP/2 pings, on open
+    /// connections
+    pub keep_alive: Option<Duration>,
+    /// Only connect over HTTP/2, with prior knowledge for plain-text urls
+    pub http2_only: bool,
+    /// Delay before racing the other address family of a dual-stack host, disabled if
+    /// not set
+    pub happy_eyeballs_timeout: Option<Duration>,
+}
+
+impl Default for ConnectionPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_idle_per_host: None,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            keep_alive: None,
+            http2_only: false,
+            happy_eyeballs_timeout: Some(DEFAULT_HAPPY_EYEBALLS_TIMEOUT),
+        }
+    }
+}
+
+impl ConnectionPoolConfig {
+    /// Connector resolving the host and opening the TCP connections of the pool. The
+    /// host is resolved again for every new connection.
+    pub fn connector(&self) -> HttpConnector {
+        let mut connector = HttpConnector::new();
+        connector.enforce_http(false);
+        connector.set_keepalive(self.keep_alive);
+        connector.set_happy_eyeballs_timeout(self.happy_eyeballs_timeout);
+        connector
+    }
+
+    pub fn client_builder(&self) -> Builder {
+        let mut builder = Client::builder(TokioExecutor::new());
+        builder
+            .pool_timer(TokioTimer::new())
+            .pool_idle_timeout(self.idle_timeout)
+            .http2_only(self.http2_only);
+        if let Some(max_idle) = self.max_idle_per_host {
+            builder.pool_max_idle_per_host(max_idle);
+        }
+        if let Some(keep_alive) = self.keep_alive {
+            builder
+                .timer(TokioTimer::new())
+                .http2_keep_alive_interval(keep_alive)
+                .http2_keep_alive_while_idle(true);
+        }
+        builder
+    }
+}
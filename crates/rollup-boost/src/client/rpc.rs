@@ -1,36 +1,49 @@
-use crate::EngineApiExt;
-use crate::client::auth::AuthLayer;
-use crate::payload::{NewPayload, OpExecutionPayloadEnvelope, PayloadSource, PayloadVersion};
+use crate::client::auth::{AuthLayer, SharedJwtSecret};
+use crate::client::pool::ConnectionPoolConfig;
+use crate::payload::{
+    BlockSource, NewPayload, OpExecutionPayloadEnvelope, PayloadSource, PayloadVersion,
+};
 use crate::server::EngineApiClient;
-use alloy_primitives::{B256, Bytes};
+use crate::{
+    CorrelationIdLayer, CorrelationIdService, EngineApiError, EngineApiExt, FlashblocksError,
+    RelayError, SERVER_ERROR_CODE, TlsConfig, TlsError,
+};
+use alloy_primitives::{B256, Bytes, U64};
 use alloy_rpc_types_engine::{
-    ExecutionPayload, ExecutionPayloadV3, ForkchoiceState, ForkchoiceUpdated, JwtError, JwtSecret,
-    PayloadId, PayloadStatus,
+    ExecutionPayload, ExecutionPayloadBodiesV1, ExecutionPayloadEnvelopeV2,
+    ExecutionPayloadInputV2, ExecutionPayloadV1, ExecutionPayloadV3, ForkchoiceState,
+    ForkchoiceUpdated, JwtError, JwtSecret, PayloadId, PayloadStatus,
 };
 use alloy_rpc_types_eth::{Block, BlockNumberOrTag};
 use clap::{Parser, arg};
 use http::Uri;
 use jsonrpsee::core::async_trait;
+use jsonrpsee::core::client::ClientT;
 use jsonrpsee::core::middleware::layer::RpcLogger;
 use jsonrpsee::http_client::transport::HttpBackend;
 use jsonrpsee::http_client::{HttpClient, HttpClientBuilder, RpcService};
+use jsonrpsee::rpc_params;
 use jsonrpsee::types::ErrorObjectOwned;
+use jsonrpsee::types::error::INTERNAL_ERROR_CODE;
+use metrics::counter;
 use op_alloy_rpc_types_engine::{
     OpExecutionPayloadEnvelopeV3, OpExecutionPayloadEnvelopeV4, OpExecutionPayloadV4,
     OpPayloadAttributes,
 };
 use opentelemetry::trace::SpanKind;
+use parking_lot::RwLock;
 use paste::paste;
+use rustls::ClientConfig;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 use thiserror::Error;
 use tracing::{error, info, instrument};
 
 use super::auth::Auth;
 
-pub type RpcClientService = HttpClient<RpcLogger<RpcService<Auth<HttpBackend>>>>;
-
-const INTERNAL_ERROR: i32 = 13;
+pub type RpcClientService =
+    HttpClient<RpcLogger<RpcService<Auth<CorrelationIdService<HttpBackend>>>>>;
 
 pub(crate) type ClientResult<T> = Result<T, RpcClientError>;
 
@@ -44,6 +57,58 @@ pub enum RpcClientError {
     Io(#[from] std::io::Error),
     #[error(transparent)]
     Jwt(#[from] JwtError),
+    #[error(transparent)]
+    Tls(#[from] TlsError),
+    #[error("no builder is enabled")]
+    NoBuilderEnabled,
+    #[error("builder {0} is quarantined")]
+    Quarantined(String),
+    #[error("builder {builder} is on chain {actual}, expected chain {expected}")]
+    ChainIdMismatch {
+        builder: String,
+        expected: u64,
+        actual: u64,
+    },
+    #[error("Invalid flashblocks payload: {0}")]
+    Flashblocks(#[from] FlashblocksError),
+    #[error(transparent)]
+    Relay(#[from] RelayError),
+}
+
+impl EngineApiError for RpcClientError {
+    fn code(&self) -> i32 {
+        match self {
+            // Errors of the upstream client are returned as is
+            RpcClientError::Jsonrpsee(jsonrpsee::core::client::Error::Call(e)) => e.code(),
+            RpcClientError::Jsonrpsee(_) => SERVER_ERROR_CODE,
+            RpcClientError::InvalidPayload(_) => SERVER_ERROR_CODE,
+            RpcClientError::Io(_) | RpcClientError::Jwt(_) | RpcClientError::Tls(_) => {
+                INTERNAL_ERROR_CODE
+            }
+            RpcClientError::NoBuilderEnabled
+            | RpcClientError::Quarantined(_)
+            | RpcClientError::ChainIdMismatch { .. } => SERVER_ERROR_CODE,
+            RpcClientError::Flashblocks(e) => e.code(),
+            RpcClientError::Relay(e) => e.code(),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            RpcClientError::Jsonrpsee(jsonrpsee::core::client::Error::Call(_)) => "upstream",
+            RpcClientError::Jsonrpsee(jsonrpsee::core::client::Error::RequestTimeout) => "timeout",
+            RpcClientError::Jsonrpsee(_) => "transport",
+            RpcClientError::InvalidPayload(_) => "invalid_payload_status",
+            RpcClientError::Io(_) => "io",
+            RpcClientError::Jwt(_) => "jwt",
+            RpcClientError::Tls(_) => "tls",
+            RpcClientError::NoBuilderEnabled => "no_builder_enabled",
+            RpcClientError::Quarantined(_) => "quarantined",
+            RpcClientError::ChainIdMismatch { .. } => "chain_id_mismatch",
+            RpcClientError::Flashblocks(e) => e.kind(),
+            RpcClientError::Relay(e) => e.kind(),
+        }
+    }
 }
 
 trait Code: Sized {
@@ -64,14 +129,9 @@ impl<T, E: Code> Code for Result<T, E> {
     }
 }
 
-/// TODO: Add more robust error code system
 impl Code for RpcClientError {
     fn code(&self) -> i32 {
-        match self {
-            RpcClientError::Jsonrpsee(e) => e.code(),
-            // Status code 13 == internal error
-            _ => INTERNAL_ERROR,
-        }
+        EngineApiError::code(self)
     }
 }
 
@@ -79,7 +139,7 @@ impl Code for jsonrpsee::core::client::Error {
     fn code(&self) -> i32 {
         match self {
             jsonrpsee::core::client::Error::Call(call) => call.code(),
-            _ => INTERNAL_ERROR,
+            _ => SERVER_ERROR_CODE,
         }
     }
 }
@@ -90,8 +150,7 @@ impl From<RpcClientError> for ErrorObjectOwned {
             RpcClientError::Jsonrpsee(jsonrpsee::core::ClientError::Call(error_object)) => {
                 error_object
             }
-            // Status code 13 == internal error
-            e => ErrorObjectOwned::owned(INTERNAL_ERROR, e.to_string(), Option::<()>::None),
+            e => e.to_error_object(),
         }
     }
 }
@@ -102,35 +161,96 @@ impl From<RpcClientError> for ErrorObjectOwned {
 ///
 #[derive(Clone)]
 pub struct RpcClient {
-    /// Handles requests to the authenticated Engine API (requires JWT authentication)
-    auth_client: RpcClientService,
+    /// Handles requests to the authenticated Engine API (requires JWT authentication),
+    /// replaced after connection errors
+    auth_client: Arc<RwLock<RpcClientService>>,
     /// Uri of the RPC server for authenticated Engine API calls
     auth_rpc: Uri,
     /// The source of the payload
     payload_source: PayloadSource,
+    secret: SharedJwtSecret,
+    timeout: u64,
+    tls: Option<ClientConfig>,
 }
 
 impl RpcClient {
     /// Initializes a new [ExecutionClient] with JWT auth for the Engine API and without auth for general execution layer APIs.
     pub fn new(
         auth_rpc: Uri,
-        auth_rpc_jwt_secret: JwtSecret,
+        auth_rpc_jwt_secret: impl Into<SharedJwtSecret>,
         timeout: u64,
         payload_source: PayloadSource,
     ) -> Result<Self, RpcClientError> {
-        let auth_layer = AuthLayer::new(auth_rpc_jwt_secret);
-        let auth_client = HttpClientBuilder::new()
-            .set_http_middleware(tower::ServiceBuilder::new().layer(auth_layer))
-            .request_timeout(Duration::from_millis(timeout))
-            .build(auth_rpc.to_string())?;
+        Self::new_with_tls(auth_rpc, auth_rpc_jwt_secret, timeout, payload_source, None)
+    }
+
+    /// Initializes a new [RpcClient] connecting with the given TLS configuration, e.g. to
+    /// authenticate with a client certificate alongside the JWT.
+    pub fn new_with_tls(
+        auth_rpc: Uri,
+        auth_rpc_jwt_secret: impl Into<SharedJwtSecret>,
+        timeout: u64,
+        payload_source: PayloadSource,
+        tls: Option<ClientConfig>,
+    ) -> Result<Self, RpcClientError> {
+        let secret = auth_rpc_jwt_secret.into();
+        let auth_client = Self::connect(&auth_rpc, &secret, timeout, tls.clone())?;
 
         Ok(Self {
-            auth_client,
+            auth_client: Arc::new(RwLock::new(auth_client)),
             auth_rpc,
             payload_source,
+            secret,
+            timeout,
+            tls,
         })
     }
 
+    fn connect(
+        auth_rpc: &Uri,
+        secret: &SharedJwtSecret,
+        timeout: u64,
+        tls: Option<ClientConfig>,
+    ) -> Result<RpcClientService, RpcClientError> {
+        let auth_layer = AuthLayer::new(secret.clone());
+        let mut builder = HttpClientBuilder::new()
+            .set_http_middleware(
+                tower::ServiceBuilder::new()
+                    .layer(auth_layer)
+                    .layer(CorrelationIdLayer),
+            )
+            .request_timeout(Duration::from_millis(timeout));
+        if let Some(tls) = tls {
+            builder = builder.with_custom_cert_store(tls);
+        }
+        Ok(builder.build(auth_rpc.to_string())?)
+    }
+
+    fn auth_client(&self) -> RpcClientService {
+        self.auth_client.read().clone()
+    }
+
+    /// Replaces the client after a transport error or timeout, dropping its pooled
+    /// connections so that the host is resolved again on the next call instead of
+    /// reusing connections to an address that is no longer served.
+    fn reset_on_connection_error(&self, e: &jsonrpsee::core::client::Error) {
+        if !matches!(
+            e,
+            jsonrpsee::core::client::Error::Transport(_)
+                | jsonrpsee::core::client::Error::RequestTimeout
+        ) {
+            return;
+        }
+        match Self::connect(&self.auth_rpc, &self.secret, self.timeout, self.tls.clone()) {
+            Ok(client) => {
+                counter!("rpc_client.reconnects", "target" => self.payload_source.to_string())
+                    .increment(1);
+                *self.auth_client.write() = client;
+            }
+            Err(e) => error!(message = "failed to reconnect", error = %e),
+        }
+    }
+
     #[instrument(
         skip_all,
         err,
@@ -150,9 +270,10 @@ impl RpcClient {
     ) -> ClientResult<ForkchoiceUpdated> {
         info!("Sending fork_choice_updated_v3 to {}", self.payload_source);
         let res = self
-            .auth_client
+            .auth_client()
             .fork_choice_updated_v3(fork_choice_state, payload_attributes.clone())
             .await
+            .inspect_err(|e| self.reset_on_connection_error(e))
             .set_code()?;
 
         if let Some(payload_id) = res.payload_id {
@@ -169,6 +290,111 @@ impl RpcClient {
         Ok(res)
     }
 
+    #[instrument(
+        skip(self),
+        err,
+        fields(
+            otel.kind = ?SpanKind::Client,
+            target = self.payload_source.to_string(),
+            url = %self.auth_rpc,
+            %payload_id,
+        )
+    )]
+    pub async fn get_payload_v1(&self, payload_id: PayloadId) -> ClientResult<ExecutionPayloadV1> {
+        info!("Sending get_payload_v1 to {}", self.payload_source);
+        Ok(self
+            .auth_client()
+            .get_payload_v1(payload_id)
+            .await
+            .inspect_err(|e| self.reset_on_connection_error(e))
+            .set_code()?)
+    }
+
+    #[instrument(
+        skip_all,
+        err,
+        fields(
+            otel.kind = ?SpanKind::Client,
+            target = self.payload_source.to_string(),
+            url = %self.auth_rpc,
+            block_hash,
+            code,
+        )
+    )]
+    pub async fn new_payload_v1(&self, payload: ExecutionPayloadV1) -> ClientResult<PayloadStatus> {
+        info!("Sending new_payload_v1 to {}", self.payload_source);
+        tracing::Span::current().record("block_hash", payload.block_hash.to_string());
+
+        let res = self
+            .auth_client()
+            .new_payload_v1(payload)
+            .await
+            .inspect_err(|e| self.reset_on_connection_error(e))
+            .set_code()?;
+
+        if res.is_invalid() {
+            return Err(RpcClientError::InvalidPayload(res.status.to_string()).set_code());
+        }
+
+        Ok(res)
+    }
+
+    #[instrument(
+        skip(self),
+        err,
+        fields(
+            otel.kind = ?SpanKind::Client,
+            target = self.payload_source.to_string(),
+            url = %self.auth_rpc,
+            %payload_id,
+        )
+    )]
+    pub async fn get_payload_v2(
+        &self,
+        payload_id: PayloadId,
+    ) -> ClientResult<ExecutionPayloadEnvelopeV2> {
+        info!("Sending get_payload_v2 to {}", self.payload_source);
+        Ok(self
+            .auth_client()
+            .get_payload_v2(payload_id)
+            .await
+            .inspect_err(|e| self.reset_on_connection_error(e))
+            .set_code()?)
+    }
+
+    #[instrument(
+        skip_all,
+        err,
+        fields(
+            otel.kind = ?SpanKind::Client,
+            target = self.payload_source.to_string(),
+            url = %self.auth_rpc,
+            block_hash,
+            code,
+        )
+    )]
+    pub async fn new_payload_v2(
+        &self,
+        payload: ExecutionPayloadInputV2,
+    ) -> ClientResult<PayloadStatus> {
+        info!("Sending new_payload_v2 to {}", self.payload_source);
+        let block_hash = payload.execution_payload.block_hash;
+        tracing::Span::current().record("block_hash", block_hash.to_string());
+
+        let res = self
+            .auth_client()
+            .new_payload_v2(payload)
+            .await
+            .inspect_err(|e| self.reset_on_connection_error(e))
+            .set_code()?;
+
+        if res.is_invalid() {
+            return Err(RpcClientError::InvalidPayload(res.status.to_string()).set_code());
+        }
+
+        Ok(res)
+    }
+
     #[instrument(
         skip(self),
         err,
@@ -185,9 +411,10 @@ impl RpcClient {
     ) -> ClientResult<OpExecutionPayloadEnvelopeV3> {
         info!("Sending get_payload_v3 to {}", self.payload_source);
         Ok(self
-            .auth_client
+            .auth_client()
             .get_payload_v3(payload_id)
             .await
+            .inspect_err(|e| self.reset_on_connection_error(e))
             .set_code()?)
     }
 
@@ -213,9 +440,10 @@ impl RpcClient {
         tracing::Span::current().record("block_hash", block_hash.to_string());
 
         let res = self
-            .auth_client
+            .auth_client()
             .new_payload_v3(payload, versioned_hashes, parent_beacon_block_root)
             .await
+            .inspect_err(|e| self.reset_on_connection_error(e))
             .set_code()?;
 
         if res.is_invalid() {
@@ -241,9 +469,10 @@ impl RpcClient {
     ) -> ClientResult<OpExecutionPayloadEnvelopeV4> {
         info!("Sending get_payload_v4 to {}", self.payload_source);
         Ok(self
-            .auth_client
+            .auth_client()
             .get_payload_v4(payload_id)
             .await
+            .inspect_err(|e| self.reset_on_connection_error(e))
             .set_code()?)
     }
 
@@ -253,11 +482,29 @@ impl RpcClient {
         version: PayloadVersion,
     ) -> ClientResult<OpExecutionPayloadEnvelope> {
         match version {
+            PayloadVersion::V1 => Ok(OpExecutionPayloadEnvelope::V1(
+                self.get_payload_v1(payload_id)
+                    .await
+                    .inspect_err(|e| self.reset_on_connection_error(e))
+                    .set_code()?,
+            )),
+            PayloadVersion::V2 => Ok(OpExecutionPayloadEnvelope::V2(
+                self.get_payload_v2(payload_id)
+                    .await
+                    .inspect_err(|e| self.reset_on_connection_error(e))
+                    .set_code()?,
+            )),
             PayloadVersion::V3 => Ok(OpExecutionPayloadEnvelope::V3(
-                self.get_payload_v3(payload_id).await.set_code()?,
+                self.get_payload_v3(payload_id)
+                    .await
+                    .inspect_err(|e| self.reset_on_connection_error(e))
+                    .set_code()?,
             )),
             PayloadVersion::V4 => Ok(OpExecutionPayloadEnvelope::V4(
-                self.get_payload_v4(payload_id).await.set_code()?,
+                self.get_payload_v4(payload_id)
+                    .await
+                    .inspect_err(|e| self.reset_on_connection_error(e))
+                    .set_code()?,
             )),
         }
     }
@@ -286,7 +533,7 @@ impl RpcClient {
         tracing::Span::current().record("block_hash", block_hash.to_string());
 
         let res = self
-            .auth_client
+            .auth_client()
             .new_payload_v4(
                 payload,
                 versioned_hashes,
@@ -294,6 +541,7 @@ impl RpcClient {
                 execution_requests,
             )
             .await
+            .inspect_err(|e| self.reset_on_connection_error(e))
             .set_code()?;
 
         if res.is_invalid() {
@@ -305,6 +553,8 @@ impl RpcClient {
 
     pub async fn new_payload(&self, new_payload: NewPayload) -> ClientResult<PayloadStatus> {
         match new_payload {
+            NewPayload::V1(payload) => self.new_payload_v1(payload).await,
+            NewPayload::V2(payload) => self.new_payload_v2(payload).await,
             NewPayload::V3(new_payload) => {
                 self.new_payload_v3(
                     new_payload.payload,
@@ -325,17 +575,106 @@ impl RpcClient {
         }
     }
 
+    #[instrument(
+        skip_all,
+        err,
+        fields(
+            otel.kind = ?SpanKind::Client,
+            target = self.payload_source.to_string(),
+            url = %self.auth_rpc,
+            code,
+        )
+    )]
+    pub async fn get_payload_bodies_by_hash_v1(
+        &self,
+        block_hashes: Vec<B256>,
+    ) -> ClientResult<ExecutionPayloadBodiesV1> {
+        info!(
+            "Sending get_payload_bodies_by_hash_v1 to {}",
+            self.payload_source
+        );
+        Ok(self
+            .auth_client()
+            .get_payload_bodies_by_hash_v1(block_hashes)
+            .await
+            .inspect_err(|e| self.reset_on_connection_error(e))
+            .set_code()?)
+    }
+
+    #[instrument(
+        skip_all,
+        err,
+        fields(
+            otel.kind = ?SpanKind::Client,
+            target = self.payload_source.to_string(),
+            url = %self.auth_rpc,
+            %start,
+            %count,
+            code,
+        )
+    )]
+    pub async fn get_payload_bodies_by_range_v1(
+        &self,
+        start: U64,
+        count: U64,
+    ) -> ClientResult<ExecutionPayloadBodiesV1> {
+        info!(
+            "Sending get_payload_bodies_by_range_v1 to {}",
+            self.payload_source
+        );
+        Ok(self
+            .auth_client()
+            .get_payload_bodies_by_range_v1(start, count)
+            .await
+            .inspect_err(|e| self.reset_on_connection_error(e))
+            .set_code()?)
+    }
+
+    #[instrument(
+        skip_all,
+        err,
+        fields(
+            otel.kind = ?SpanKind::Client,
+            target = self.payload_source.to_string(),
+            url = %self.auth_rpc,
+            code,
+        )
+    )]
+    pub async fn exchange_capabilities(
+        &self,
+        capabilities: Vec<String>,
+    ) -> ClientResult<Vec<String>> {
+        info!("Sending exchange_capabilities to {}", self.payload_source);
+        Ok(self
+            .auth_client()
+            .exchange_capabilities(capabilities)
+            .await
+            .inspect_err(|e| self.reset_on_connection_error(e))
+            .set_code()?)
+    }
+
     pub async fn get_block_by_number(
         &self,
         number: BlockNumberOrTag,
         full: bool,
     ) -> ClientResult<Block> {
         Ok(self
-            .auth_client
+            .auth_client()
             .get_block_by_number(number, full)
             .await
+            .inspect_err(|e| self.reset_on_connection_error(e))
             .set_code()?)
     }
+
+    pub async fn chain_id(&self) -> ClientResult<u64> {
+        let chain_id: U64 = self
+            .auth_client()
+            .request("eth_chainId", rpc_params![])
+            .await
+            .inspect_err(|e| self.reset_on_connection_error(e))
+            .set_code()?;
+        Ok(chain_id.to())
+    }
 }
 
 #[async_trait]
@@ -361,6 +700,14 @@ impl EngineApiExt for RpcClient {
         self.get_payload(payload_id, version).await
     }
 
+    fn last_payload_source(&self) -> BlockSource {
+        BlockSource::from(&self.payload_source)
+    }
+
+    async fn exchange_capabilities(&self, capabilities: Vec<String>) -> ClientResult<Vec<String>> {
+        self.exchange_capabilities(capabilities).await
+    }
+
     async fn get_block_by_number(
         &self,
         number: BlockNumberOrTag,
@@ -368,6 +715,10 @@ impl EngineApiExt for RpcClient {
     ) -> ClientResult<Block> {
         self.get_block_by_number(number, full).await
     }
+
+    async fn chain_id(&self) -> ClientResult<u64> {
+        self.chain_id().await
+    }
 }
 
 /// Generates Clap argument structs with a prefix to create a unique namespace when specifying RPC client config via the CLI.
@@ -392,6 +743,69 @@ macro_rules! define_rpc_args {
                     /// Timeout for http calls in milliseconds
                     #[arg(long, env, default_value_t = 1000)]
                     pub [<$prefix _timeout>]: u64,
+
+                    /// Path to the PEM client certificate presented for mutual TLS
+                    #[arg(long, env, value_name = "PATH", requires = stringify!([<$prefix _tls_key>]))]
+                    pub [<$prefix _tls_cert>]: Option<PathBuf>,
+
+                    /// Path to the PEM private key of the mutual TLS client certificate
+                    #[arg(long, env, value_name = "PATH", requires = stringify!([<$prefix _tls_cert>]))]
+                    pub [<$prefix _tls_key>]: Option<PathBuf>,
+
+                    /// Path to the PEM CA bundle trusted to verify the server certificate,
+                    /// instead of the native roots
+                    #[arg(long, env, value_name = "PATH")]
+                    pub [<$prefix _tls_ca>]: Option<PathBuf>,
+
+                    /// Server name the server certificate is verified against, instead of
+                    /// the host of the url
+                    #[arg(long, env)]
+                    pub [<$prefix _tls_server_name>]: Option<String>,
+
+                    /// Idle connections kept open to the server, unlimited if not set
+                    #[arg(long, env)]
+                    pub [<$prefix _pool_max_idle>]: Option<usize>,
+
+                    /// Idle connections to the server are closed after this many seconds
+                    #[arg(long, env, default_value_t = 90)]
+                    pub [<$prefix _pool_idle_timeout>]: u64,
+
+                    /// Interval in seconds of the TCP keep-alive probes and HTTP/2 pings on
+                    /// the connections to the server, disabled if not set
+                    #[arg(long, env)]
+                    pub [<$prefix _keep_alive>]: Option<u64>,
+
+                    /// Only connect to the server over HTTP/2
+                    #[arg(long, env)]
+                    pub [<$prefix _http2_only>]: bool,
+
+                    /// Delay in milliseconds before racing the IPv4 and IPv6 addresses of a
+                    /// dual-stack server, 0 disables happy eyeballs
+                    #[arg(long, env, default_value_t = 300)]
+                    pub [<$prefix _happy_eyeballs_timeout>]: u64,
+                }
+
+                impl $name {
+                    pub fn tls_config(&self) -> TlsConfig {
+                        TlsConfig {
+                            cert: self.[<$prefix _tls_cert>].clone(),
+                            key: self.[<$prefix _tls_key>].clone(),
+                            ca: self.[<$prefix _tls_ca>].clone(),
+                            server_name: self.[<$prefix _tls_server_name>].clone(),
+                        }
+                    }
+
+                    pub fn pool_config(&self) -> ConnectionPoolConfig {
+                        let happy_eyeballs_timeout = self.[<$prefix _happy_eyeballs_timeout>];
+                        ConnectionPoolConfig {
+                            max_idle_per_host: self.[<$prefix _pool_max_idle>],
+                            idle_timeout: Duration::from_secs(self.[<$prefix _pool_idle_timeout>]),
+                            keep_alive: self.[<$prefix _keep_alive>].map(Duration::from_secs),
+                            http2_only: self.[<$prefix _http2_only>],
+                            happy_eyeballs_timeout: (happy_eyeballs_timeout > 0)
+                                .then(|| Duration::from_millis(happy_eyeballs_timeout)),
+                        }
+                    }
                 }
             }
         )*
@@ -412,6 +826,7 @@ pub mod tests {
     use jsonrpsee::core::client::Error as ClientError;
     use jsonrpsee::server::{ServerBuilder, ServerHandle};
     use jsonrpsee::{RpcModule, rpc_params};
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
     use predicates::prelude::*;
     use std::collections::HashSet;
     use std::net::SocketAddr;
@@ -446,17 +861,91 @@ pub mod tests {
         ));
     }
 
+    #[test]
+    fn test_error_codes() {
+        let upstream = ErrorObjectOwned::owned(-38002, "invalid forkchoice state", None::<()>);
+        let error = ErrorObjectOwned::from(RpcClientError::Jsonrpsee(ClientError::Call(
+            upstream.clone(),
+        )));
+        assert_eq!(error, upstream);
+
+        let error = ErrorObjectOwned::from(RpcClientError::from(FlashblocksError::MissingPayload));
+        assert_eq!(error.code(), crate::UNKNOWN_PAYLOAD_CODE);
+        assert_eq!(
+            error.data().map(|data| data.get()),
+            Some(r#"{"kind":"unknown_payload"}"#)
+        );
+
+        let error = ErrorObjectOwned::from(RpcClientError::Jsonrpsee(ClientError::RequestTimeout));
+        assert_eq!(error.code(), SERVER_ERROR_CODE);
+        let error = ErrorObjectOwned::from(RpcClientError::Relay(RelayError::Unsupported(
+            "engine_getPayloadBodiesByHashV1",
+        )));
+        assert_eq!(error.code(), jsonrpsee::types::error::METHOD_NOT_FOUND_CODE);
+    }
+
     #[tokio::test]
     async fn valid_jwt() {
         let port = get_available_port();
         let secret = JwtSecret::from_hex(SECRET).unwrap();
         let auth_rpc = Uri::from_str(&format!("http://{}:{}", AUTH_ADDR, port)).unwrap();
         let client = RpcClient::new(auth_rpc, secret, 1000, PayloadSource::L2).unwrap();
-        let response = send_request(client.auth_client, port).await;
+        let response = send_request(client.auth_client(), port).await;
         assert!(response.is_ok());
         assert_eq!(response.unwrap(), "You are the dark lord");
     }
 
+    #[tokio::test]
+    async fn reconnect_on_connection_error() {
+        // The test runtime polls the client on this thread, which records to the recorder
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let _guard = metrics::set_default_local_recorder(&recorder);
+        let reconnects = || {
+            snapshotter
+                .snapshot()
+                .into_vec()
+                .into_iter()
+                .filter(|(key, ..)| key.key().name() == "rpc_client.reconnects")
+                .map(|(.., value)| match value {
+                    DebugValue::Counter(count) => count,
+                    _ => 0,
+                })
+                .sum::<u64>()
+        };
+
+        let port = get_available_port();
+        let secret = JwtSecret::from_hex(SECRET).unwrap();
+        let auth_rpc = Uri::from_str(&format!("http://{}:{}", AUTH_ADDR, port)).unwrap();
+        let client = RpcClient::new(auth_rpc, secret, 1000, PayloadSource::L2).unwrap();
+        let fork_choice_state = ForkchoiceState {
+            head_block_hash: B256::ZERO,
+            safe_block_hash: B256::ZERO,
+            finalized_block_hash: B256::ZERO,
+        };
+
+        // The upstream cannot be reached, so the client is replaced
+        let response = client.fork_choice_updated_v3(fork_choice_state, None).await;
+        assert!(matches!(
+            response,
+            Err(RpcClientError::Jsonrpsee(ClientError::Transport(_)))
+        ));
+        assert_eq!(reconnects(), 1);
+
+        // The new client reaches the upstream once it is up, and errors returned by the
+        // upstream keep the client
+        let server = spawn_server(port).await;
+        let response = client.fork_choice_updated_v3(fork_choice_state, None).await;
+        assert!(matches!(
+            response,
+            Err(RpcClientError::Jsonrpsee(ClientError::Call(_)))
+        ));
+        assert_eq!(reconnects(), 1);
+
+        server.stop().unwrap();
+        server.stopped().await;
+    }
+
     async fn send_request(client: RpcClientService, port: u16) -> Result<String, ClientError> {
         let server = spawn_server(port).await;
 
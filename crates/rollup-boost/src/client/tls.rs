@@ -0,0 +1,163 @@
+use std::{io, path::PathBuf, sync::Arc};
+
+use rustls::{
+    ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+    client::{
+        VerifierBuilderError, WebPkiServerVerifier,
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    },
+    pki_types::{
+        CertificateDer, InvalidDnsNameError, PrivateKeyDer, ServerName, UnixTime,
+        pem::{self, PemObject},
+    },
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TlsError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("invalid PEM file: {0}")]
+    Pem(#[from] pem::Error),
+    #[error(transparent)]
+    Rustls(#[from] rustls::Error),
+    #[error(transparent)]
+    Verifier(#[from] VerifierBuilderError),
+    #[error("invalid TLS server name: {0}")]
+    ServerName(#[from] InvalidDnsNameError),
+    #[error("both a client certificate and a client key are required for mutual TLS")]
+    IncompleteClientAuth,
+}
+
+/// TLS settings of an upstream connection: a client certificate for mutual TLS, the CA
+/// bundle trusted to verify the server, and the server name the certificate is verified
+/// against.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TlsConfig {
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+    pub ca: Option<PathBuf>,
+    pub server_name: Option<String>,
+}
+
+impl TlsConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.cert.is_some() || self.key.is_some() || self.ca.is_some() || self.server_name.is_some()
+    }
+
+    /// Builds the rustls client configuration, or `None` if no TLS setting is configured
+    /// and the defaults apply.
+    pub fn client_config(&self) -> Result<Option<ClientConfig>, TlsError> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+
+        let roots = Arc::new(self.root_store()?);
+        let builder = match &self.server_name {
+            Some(server_name) => ClientConfig::builder()
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(ServerNameOverride {
+                    inner: WebPkiServerVerifier::builder(roots).build()?,
+                    server_name: ServerName::try_from(server_name.clone())?,
+                })),
+            None => ClientConfig::builder().with_root_certificates(roots),
+        };
+
+        let config = match (&self.cert, &self.key) {
+            (Some(cert), Some(key)) => {
+                let certs = CertificateDer::pem_file_iter(cert)?.collect::<Result<Vec<_>, _>>()?;
+                builder.with_client_auth_cert(certs, PrivateKeyDer::from_pem_file(key)?)?
+            }
+            (None, None) => builder.with_no_client_auth(),
+            _ => return Err(TlsError::IncompleteClientAuth),
+        };
+
+        Ok(Some(config))
+    }
+
+    /// Trusts the configured CA bundle, or the native roots if none is configured.
+    fn root_store(&self) -> Result<RootCertStore, TlsError> {
+        let mut roots = RootCertStore::empty();
+        match &self.ca {
+            Some(ca) => {
+                for cert in CertificateDer::pem_file_iter(ca)? {
+                    roots.add(cert?)?;
+                }
+            }
+            None => {
+                roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+            }
+        }
+        Ok(roots)
+    }
+}
+
+/// Verifies the server certificate against a fixed server name instead of the host of
+/// the url, for upstreams reached through an IP address or a tunnel.
+#[derive(Debug)]
+struct ServerNameOverride {
+    inner: Arc<WebPkiServerVerifier>,
+    server_name: ServerName<'static>,
+}
+
+impl ServerCertVerifier for ServerNameOverride {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            &self.server_name,
+            ocsp_response,
+            now,
+        )
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_config() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
+        assert!(TlsConfig::default().client_config().unwrap().is_none());
+
+        let config = TlsConfig {
+            cert: Some(PathBuf::from("client.pem")),
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.client_config(),
+            Err(TlsError::IncompleteClientAuth)
+        ));
+    }
+}
@@ -0,0 +1,149 @@
+use alloy_primitives::B64;
+use http::{HeaderMap, HeaderValue};
+use std::{
+    fmt,
+    future::Future,
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+/// Header used to propagate the correlation id to the builder and L2 clients.
+pub const CORRELATION_ID_HEADER: &str = "x-correlation-id";
+
+/// Upper bound on the length of a correlation id accepted from an incoming request.
+const MAX_CORRELATION_ID_LEN: usize = 64;
+
+tokio::task_local! {
+    static CORRELATION_ID: CorrelationId;
+}
+
+/// Identifier attached to every request at the proxy ingress.
+///
+/// The id is recorded on the request span, forwarded to upstream clients via the
+/// [`CORRELATION_ID_HEADER`] header and attached to flashblocks events, so that logs
+/// for a single FCU -> flashblocks -> getPayload flow can be joined together.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CorrelationId(String);
+
+impl Default for CorrelationId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CorrelationId {
+    /// Generates a new random correlation id.
+    pub fn new() -> Self {
+        Self(format!("{:x}", B64::random()))
+    }
+
+    /// Reuses the correlation id provided by the caller, if present and well formed.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let value = headers.get(CORRELATION_ID_HEADER)?.to_str().ok()?;
+        if value.is_empty()
+            || value.len() > MAX_CORRELATION_ID_LEN
+            || !value
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+        {
+            return None;
+        }
+        Some(Self(value.to_string()))
+    }
+
+    /// Returns the correlation id of the request currently being handled, if any.
+    pub fn current() -> Option<Self> {
+        CORRELATION_ID.try_with(|id| id.clone()).ok()
+    }
+
+    /// Runs the future with this correlation id set as the current one.
+    pub async fn scope<F: Future>(self, f: F) -> F::Output {
+        CORRELATION_ID.scope(self, f).await
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn header_value(&self) -> Option<HeaderValue> {
+        HeaderValue::from_str(&self.0).ok()
+    }
+}
+
+impl fmt::Display for CorrelationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A layer that attaches the current [`CorrelationId`] to outgoing requests.
+#[derive(Clone, Debug, Default)]
+pub struct CorrelationIdLayer;
+
+impl<S> Layer<S> for CorrelationIdLayer {
+    type Service = CorrelationIdService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CorrelationIdService { inner }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CorrelationIdService<S> {
+    inner: S,
+}
+
+impl<S, B> Service<http::Request<B>> for CorrelationIdService<S>
+where
+    S: Service<http::Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<B>) -> Self::Future {
+        if let Some(value) = CorrelationId::current().and_then(|id| id.header_value()) {
+            request.headers_mut().insert(CORRELATION_ID_HEADER, value);
+        }
+        self.inner.call(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_headers_rejects_malformed_ids() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(CorrelationId::from_headers(&headers), None);
+
+        headers.insert(CORRELATION_ID_HEADER, HeaderValue::from_static("abc-123_x"));
+        assert_eq!(
+            CorrelationId::from_headers(&headers).map(|id| id.to_string()),
+            Some("abc-123_x".to_string())
+        );
+
+        headers.insert(CORRELATION_ID_HEADER, HeaderValue::from_static("a b"));
+        assert_eq!(CorrelationId::from_headers(&headers), None);
+
+        let long = "a".repeat(MAX_CORRELATION_ID_LEN + 1);
+        headers.insert(CORRELATION_ID_HEADER, HeaderValue::from_str(&long).unwrap());
+        assert_eq!(CorrelationId::from_headers(&headers), None);
+    }
+
+    #[tokio::test]
+    async fn current_is_scoped() {
+        assert_eq!(CorrelationId::current(), None);
+
+        let id = CorrelationId::new();
+        let inner = id.clone().scope(async { CorrelationId::current() }).await;
+        assert_eq!(inner, Some(id));
+
+        assert_eq!(CorrelationId::current(), None);
+    }
+}
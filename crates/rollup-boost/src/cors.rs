@@ -0,0 +1,156 @@
+use std::collections::HashSet;
+
+use clap::Parser;
+use http::{
+    HeaderMap, HeaderValue, Response, StatusCode,
+    header::{
+        ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+        ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS, ORIGIN, VARY,
+    },
+};
+
+/// Duration in seconds browsers may cache the response to a preflight request.
+const PREFLIGHT_MAX_AGE: &str = "3600";
+
+#[derive(Parser, Clone, Debug)]
+pub struct CorsArgs {
+    /// Origins browsers may reach the flashblocks websocket, the metrics server and the
+    /// dashboard from, comma separated, `*` for any origin. Cross-origin requests are
+    /// not allowed if not set, except on the websocket
+    #[arg(long, env, value_delimiter = ',')]
+    pub cors_allowed_origins: Vec<String>,
+}
+
+/// The origins allowed to reach the public endpoints from a browser.
+///
+/// HTTP responses to allowed origins carry the CORS headers. Since browsers do not apply
+/// CORS to websockets, websocket handshakes from other origins are rejected instead.
+/// Requests without an `Origin` header do not come from a browser and are always allowed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CorsPolicy {
+    /// Allowed origins, any origin if not set
+    origins: Option<HashSet<String>>,
+}
+
+impl CorsPolicy {
+    pub fn new(origins: impl IntoIterator<Item = String>) -> Self {
+        let origins = origins
+            .into_iter()
+            .map(|origin| origin.trim().trim_end_matches('/').to_string())
+            .collect::<HashSet<_>>();
+        Self {
+            origins: (!origins.contains("*")).then_some(origins),
+        }
+    }
+
+    pub fn from_args(args: &CorsArgs) -> Option<Self> {
+        (!args.cors_allowed_origins.is_empty())
+            .then(|| Self::new(args.cors_allowed_origins.iter().cloned()))
+    }
+
+    pub fn allows(&self, origin: &str) -> bool {
+        self.origins
+            .as_ref()
+            .is_none_or(|origins| origins.contains(origin))
+    }
+
+    /// Whether a request with `headers` is allowed: it comes from an allowed origin, or
+    /// not from a browser.
+    pub fn allows_request(&self, headers: &HeaderMap) -> bool {
+        match headers.get(ORIGIN) {
+            Some(origin) => origin.to_str().is_ok_and(|origin| self.allows(origin)),
+            None => true,
+        }
+    }
+
+    /// Adds the CORS headers to the response to a request with `headers`, if it comes
+    /// from an allowed origin.
+    pub fn apply<B>(&self, headers: &HeaderMap, response: &mut Response<B>) {
+        let Some(origin) = headers.get(ORIGIN) else {
+            return;
+        };
+        if !origin.to_str().is_ok_and(|value| self.allows(value)) {
+            return;
+        }
+
+        let response_headers = response.headers_mut();
+        match self.origins {
+            Some(_) => {
+                response_headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+                response_headers.append(VARY, HeaderValue::from_static("origin"));
+            }
+            None => {
+                response_headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+            }
+        }
+    }
+
+    /// Response to a preflight `OPTIONS` request with `headers`, allowing `GET` requests
+    /// from allowed origins.
+    pub fn preflight(&self, headers: &HeaderMap) -> Response<()> {
+        let mut response = Response::new(());
+        *response.status_mut() = StatusCode::NO_CONTENT;
+        if !self.allows_request(headers) {
+            *response.status_mut() = StatusCode::FORBIDDEN;
+            return response;
+        }
+
+        self.apply(headers, &mut response);
+        let response_headers = response.headers_mut();
+        response_headers.insert(
+            ACCESS_CONTROL_ALLOW_METHODS,
+            HeaderValue::from_static("GET, OPTIONS"),
+        );
+        response_headers.insert(
+            ACCESS_CONTROL_MAX_AGE,
+            HeaderValue::from_static(PREFLIGHT_MAX_AGE),
+        );
+        if let Some(requested) = headers.get(ACCESS_CONTROL_REQUEST_HEADERS) {
+            response_headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, requested.clone());
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origin(origin: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ORIGIN, origin.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_cors_policy() {
+        let policy = CorsPolicy::new(["https://explorer.example/".to_string()]);
+        assert!(policy.allows_request(&origin("https://explorer.example")));
+        assert!(!policy.allows_request(&origin("https://evil.example")));
+        assert!(policy.allows_request(&HeaderMap::new()));
+
+        let mut response = Response::new(());
+        policy.apply(&origin("https://explorer.example"), &mut response);
+        assert_eq!(
+            response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://explorer.example"
+        );
+
+        let mut response = Response::new(());
+        policy.apply(&origin("https://evil.example"), &mut response);
+        assert!(
+            response
+                .headers()
+                .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+                .is_none()
+        );
+
+        let preflight = policy.preflight(&origin("https://evil.example"));
+        assert_eq!(preflight.status(), StatusCode::FORBIDDEN);
+
+        let any = CorsPolicy::new(["*".to_string()]);
+        let preflight = any.preflight(&origin("https://evil.example"));
+        assert_eq!(preflight.status(), StatusCode::NO_CONTENT);
+        assert_eq!(preflight.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    }
+}
@@ -0,0 +1,190 @@
+use std::sync::Arc;
+
+use clap::Parser;
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::{CorsPolicy, ExecutionMode, Probes};
+
+#[derive(Parser, Clone, Debug)]
+pub struct DashboardArgs {
+    /// Port to serve the web dashboard of the live flashblocks on, disabled if not set.
+    /// Requires the `dashboard` feature
+    #[arg(long, env)]
+    pub dashboard_port: Option<u16>,
+
+    /// Host to serve the dashboard on, an IPv4 or IPv6 address
+    #[arg(long, env, default_value = "127.0.0.1")]
+    pub dashboard_host: String,
+}
+
+/// State of rollup-boost polled by the dashboard.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DashboardStatus {
+    pub execution_mode: ExecutionMode,
+    pub health: &'static str,
+    /// Port the flashblocks are published on, which the dashboard streams from
+    pub flashblocks_port: Option<u16>,
+}
+
+/// A web page visualizing the live flashblocks stream and the health of the builder,
+/// for operators.
+///
+/// The page streams the flashblocks from the websocket rollup-boost publishes them on,
+/// and polls the `/status` endpoint for the execution mode and builder health.
+#[derive(Debug, Clone)]
+pub struct Dashboard {
+    probes: Arc<Probes>,
+    execution_mode: Arc<Mutex<ExecutionMode>>,
+    flashblocks_port: Option<u16>,
+    cors: Option<Arc<CorsPolicy>>,
+}
+
+impl Dashboard {
+    pub fn new(probes: Arc<Probes>, execution_mode: Arc<Mutex<ExecutionMode>>) -> Self {
+        Self {
+            probes,
+            execution_mode,
+            flashblocks_port: None,
+            cors: None,
+        }
+    }
+
+    /// Allows browsers of other origins to poll the status of the dashboard.
+    pub fn with_cors(mut self, cors: Option<CorsPolicy>) -> Self {
+        self.cors = cors.map(Arc::new);
+        self
+    }
+
+    pub fn with_flashblocks_port(mut self, flashblocks_port: Option<u16>) -> Self {
+        self.flashblocks_port = flashblocks_port;
+        self
+    }
+
+    pub fn status(&self) -> DashboardStatus {
+        DashboardStatus {
+            execution_mode: *self.execution_mode.lock(),
+            health: self.probes.health().as_str(),
+            flashblocks_port: self.flashblocks_port,
+        }
+    }
+
+    /// Serves the dashboard if it is enabled by the arguments.
+    pub async fn serve(self, args: &DashboardArgs) -> eyre::Result<()> {
+        let Some(port) = args.dashboard_port else {
+            return Ok(());
+        };
+
+        #[cfg(feature = "dashboard")]
+        {
+            server::spawn(self, crate::bind_addr(&args.dashboard_host, port)?).await
+        }
+
+        #[cfg(not(feature = "dashboard"))]
+        eyre::bail!(
+            "cannot serve the dashboard on port {port}, rollup-boost was built without the dashboard feature"
+        )
+    }
+}
+
+#[cfg(feature = "dashboard")]
+mod server {
+    use std::net::SocketAddr;
+
+    use http::{Method, StatusCode, header::CONTENT_TYPE};
+    use hyper::{Request, Response, server::conn::http1, service::service_fn};
+    use hyper_util::rt::TokioIo;
+    use jsonrpsee::http_client::HttpBody;
+    use tokio::net::TcpListener;
+    use tracing::{error, info};
+
+    use super::Dashboard;
+
+    const PAGE: &str = include_str!("dashboard.html");
+
+    pub(super) async fn spawn(dashboard: Dashboard, addr: SocketAddr) -> eyre::Result<()> {
+        let listener = TcpListener::bind(addr).await?;
+        info!("Dashboard running on {}", listener.local_addr()?);
+
+        tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        error!(message = "Error accepting dashboard connection", error = %e);
+                        continue;
+                    }
+                };
+                let dashboard = dashboard.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |req: Request<hyper::body::Incoming>| {
+                        let response = dashboard.respond(&req);
+                        async { Ok::<_, hyper::Error>(response) }
+                    });
+                    if let Err(e) = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await
+                    {
+                        error!(message = "Error serving dashboard connection", error = %e);
+                    }
+                });
+            }
+        });
+        Ok(())
+    }
+
+    impl Dashboard {
+        fn respond<B>(&self, req: &Request<B>) -> Response<HttpBody> {
+            if let (Some(cors), &Method::OPTIONS) = (&self.cors, req.method()) {
+                return cors.preflight(req.headers()).map(|()| HttpBody::empty());
+            }
+
+            let (content_type, body) = match req.uri().path() {
+                "/" => ("text/html; charset=utf-8", PAGE.to_string()),
+                "/status" => (
+                    "application/json",
+                    serde_json::to_string(&self.status()).expect("status serializes to JSON"),
+                ),
+                _ => {
+                    return Response::builder()
+                        .status(StatusCode::NOT_FOUND)
+                        .body(HttpBody::empty())
+                        .expect("Failed to create not found response");
+                }
+            };
+            let mut response = Response::builder()
+                .header(CONTENT_TYPE, content_type)
+                .body(HttpBody::from(body))
+                .expect("Failed to create dashboard response");
+            if let Some(cors) = &self.cors {
+                cors.apply(req.headers(), &mut response);
+            }
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Health;
+
+    #[test]
+    fn test_dashboard_status() {
+        let probes = Arc::new(Probes::default());
+        let execution_mode = Arc::new(Mutex::new(ExecutionMode::Enabled));
+        let dashboard = Dashboard::new(probes.clone(), execution_mode.clone())
+            .with_flashblocks_port(Some(1111));
+
+        probes.set_health(Health::PartialContent);
+        *execution_mode.lock() = ExecutionMode::DryRun;
+        assert_eq!(
+            serde_json::to_value(dashboard.status()).unwrap(),
+            serde_json::json!({
+                "execution_mode": "dry_run",
+                "health": "partial_content",
+                "flashblocks_port": 1111,
+            })
+        );
+    }
+}
@@ -1,11 +1,29 @@
+use alloy_rpc_types_engine::PayloadId;
+use http::{HeaderMap, HeaderValue, header::AUTHORIZATION};
+use jsonrpsee::Extensions;
 use jsonrpsee::core::{RpcResult, async_trait};
 use jsonrpsee::http_client::HttpClient;
 use jsonrpsee::proc_macros::rpc;
-use jsonrpsee::server::Server;
+use jsonrpsee::server::{Server, ServerHandle};
+use jsonrpsee::types::ErrorObjectOwned;
+use jsonrpsee::types::error::{INTERNAL_ERROR_CODE, INVALID_PARAMS_CODE, INVALID_REQUEST_CODE};
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
 
+use crate::payload::PayloadSource;
+use crate::{
+    AuditEntry, AuditLog, BuilderConfig, BuilderInfo, BuilderQuarantine, BuilderRegistry,
+    BuilderRegistryError, BuilderScore, BuilderScores, Caller, DebugAuth, DebugAuthLayer,
+    DivergenceDetector, DivergenceStatus, DrainMode, DrainStatus, FaultConfig, FaultInjector,
+    FlashblocksPayloadV1, IpcEndpoint, LeaderElection, LeaderStatus, MetricSample, PayloadArchive,
+    PayloadCycle, PayloadHistory, QuarantinedBuilder, RecentFlashblocks, SharedJwtSecret,
+    SubscriberInfo, SubscriberRegistry, UNAUTHENTICATED_CALLER, ValidationReport, WebhookEvent,
+    WebhookNotifier, metrics_snapshot, set_log_filter, spawn_ipc_server,
+};
+
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, clap::ValueEnum)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecutionMode {
@@ -15,6 +33,9 @@ pub enum ExecutionMode {
     DryRun,
     // Not sending any requests
     Disabled,
+    // Sending all requests and comparing the builder payload against the
+    // l2 payload, but always returning the l2 payload
+    Shadow,
 }
 
 impl ExecutionMode {
@@ -22,6 +43,10 @@ impl ExecutionMode {
         matches!(self, ExecutionMode::DryRun)
     }
 
+    pub fn is_shadow(&self) -> bool {
+        matches!(self, ExecutionMode::Shadow)
+    }
+
     pub fn is_disabled(&self) -> bool {
         matches!(self, ExecutionMode::Disabled)
     }
@@ -42,9 +67,104 @@ pub struct GetExecutionModeResponse {
     pub execution_mode: ExecutionMode,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GetStatusResponse {
+    pub execution_mode: ExecutionMode,
+    /// The registered builders in the order requests are fanned out to them, empty if the
+    /// builder registry is not enabled
+    pub builders: Vec<BuilderInfo>,
+    /// Builders excluded from block building after returning too many invalid payloads
+    pub quarantined: Vec<QuarantinedBuilder>,
+    /// Last comparison of the builder and L2 heads, if the divergence check is enabled
+    pub divergence: Option<DivergenceStatus>,
+    /// Leadership of this instance, if leader election is enabled
+    pub leader: Option<LeaderStatus>,
+    pub drain: DrainStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetDrainModeRequest {
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetLogFilterRequest {
+    pub filter: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SetLogFilterResponse {
+    pub filter: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReloadJwtSecretsResponse {
+    /// Upstreams whose JWT secret changed after re-reading the secret files
+    pub rotated: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetBuilderEnabledRequest {
+    pub name: String,
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RemoveBuilderRequest {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ListBuildersResponse {
+    pub builders: Vec<BuilderInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GetAuditLogResponse {
+    /// Execution mode changes, oldest first
+    pub entries: Vec<AuditEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ListSubscribersResponse {
+    /// Subscribers of the flashblocks websocket, oldest first
+    pub subscribers: Vec<SubscriberInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GetRecentPayloadsResponse {
+    /// Payload cycles served, most recent first
+    pub payloads: Vec<PayloadCycle>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GetFlashblocksResponse {
+    pub payload_id: PayloadId,
+    /// Where the flashblocks were found: `memory` for the flashblocks received for the
+    /// last payloads, exactly as received, or `archive` for the flashblocks of an archived
+    /// payload, without their metadata
+    pub source: String,
+    pub flashblocks: Vec<FlashblocksPayloadV1>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GetMetricsResponse {
+    /// Samples of the metrics recorded, by metric name
+    pub metrics: BTreeMap<String, Vec<MetricSample>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GetBuilderScoresResponse {
+    /// Rolling quality scores of the builders observed, by name
+    pub builders: Vec<BuilderScore>,
+}
+
+/// Number of payload cycles returned by `debug_getRecentPayloads` if not set.
+const DEFAULT_RECENT_PAYLOADS: usize = 20;
+
 #[rpc(server, client, namespace = "debug")]
 trait DebugApi {
-    #[method(name = "setExecutionMode")]
+    #[method(name = "setExecutionMode", with_extensions)]
     async fn set_execution_mode(
         &self,
         request: SetExecutionModeRequest,
@@ -52,23 +172,207 @@ trait DebugApi {
 
     #[method(name = "getExecutionMode")]
     async fn get_execution_mode(&self) -> RpcResult<GetExecutionModeResponse>;
+
+    #[method(name = "getStatus")]
+    async fn get_status(&self) -> RpcResult<GetStatusResponse>;
+
+    #[method(name = "setDrainMode", with_extensions)]
+    async fn set_drain_mode(&self, request: SetDrainModeRequest) -> RpcResult<DrainStatus>;
+
+    #[method(name = "setLogFilter")]
+    async fn set_log_filter(&self, request: SetLogFilterRequest)
+    -> RpcResult<SetLogFilterResponse>;
+
+    #[method(name = "reloadJwtSecrets")]
+    async fn reload_jwt_secrets(&self) -> RpcResult<ReloadJwtSecretsResponse>;
+
+    #[method(name = "registerBuilder", with_extensions)]
+    async fn register_builder(&self, request: BuilderConfig) -> RpcResult<ListBuildersResponse>;
+
+    #[method(name = "setBuilderEnabled", with_extensions)]
+    async fn set_builder_enabled(
+        &self,
+        request: SetBuilderEnabledRequest,
+    ) -> RpcResult<ListBuildersResponse>;
+
+    #[method(name = "removeBuilder", with_extensions)]
+    async fn remove_builder(
+        &self,
+        request: RemoveBuilderRequest,
+    ) -> RpcResult<ListBuildersResponse>;
+
+    #[method(name = "listBuilders")]
+    async fn list_builders(&self) -> RpcResult<ListBuildersResponse>;
+
+    #[method(name = "getAuditLog")]
+    async fn get_audit_log(&self) -> RpcResult<GetAuditLogResponse>;
+
+    #[method(name = "validateFlashblocks")]
+    async fn validate_flashblocks(
+        &self,
+        flashblocks: Vec<FlashblocksPayloadV1>,
+    ) -> RpcResult<ValidationReport>;
+
+    #[method(name = "setFaults", with_extensions)]
+    async fn set_faults(&self, faults: FaultConfig) -> RpcResult<FaultConfig>;
+
+    #[method(name = "getFaults")]
+    async fn get_faults(&self) -> RpcResult<FaultConfig>;
+
+    #[method(name = "listSubscribers")]
+    async fn list_subscribers(&self) -> RpcResult<ListSubscribersResponse>;
+
+    #[method(name = "getRecentPayloads")]
+    async fn get_recent_payloads(
+        &self,
+        count: Option<usize>,
+    ) -> RpcResult<GetRecentPayloadsResponse>;
+
+    #[method(name = "getFlashblocks")]
+    async fn get_flashblocks(&self, payload_id: PayloadId) -> RpcResult<GetFlashblocksResponse>;
+
+    #[method(name = "getMetrics")]
+    async fn get_metrics(&self, filter: Option<String>) -> RpcResult<GetMetricsResponse>;
+
+    #[method(name = "getBuilderScores")]
+    async fn get_builder_scores(&self) -> RpcResult<GetBuilderScoresResponse>;
 }
 
 pub struct DebugServer {
     execution_mode: Arc<Mutex<ExecutionMode>>,
+    jwt_secrets: Vec<(PayloadSource, SharedJwtSecret)>,
+    builder_registry: Option<Arc<BuilderRegistry>>,
+    builder_quarantine: Option<Arc<BuilderQuarantine>>,
+    builder_scores: Option<Arc<BuilderScores>>,
+    divergence: Option<Arc<DivergenceDetector>>,
+    leader_election: Option<Arc<LeaderElection>>,
+    drain: Arc<DrainMode>,
+    audit_log: Arc<AuditLog>,
+    auth: Option<Arc<DebugAuth>>,
+    webhooks: Option<Arc<WebhookNotifier>>,
+    fault_injector: Option<Arc<FaultInjector>>,
+    flashblocks_subscribers: Option<Arc<SubscriberRegistry>>,
+    payload_history: Option<Arc<PayloadHistory>>,
+    recent_flashblocks: Option<Arc<RecentFlashblocks>>,
+    payload_archive: Option<Arc<PayloadArchive>>,
 }
 
 impl DebugServer {
     pub fn new(execution_mode: Arc<Mutex<ExecutionMode>>) -> Self {
-        Self { execution_mode }
+        Self {
+            execution_mode,
+            jwt_secrets: vec![],
+            builder_registry: None,
+            builder_quarantine: None,
+            builder_scores: None,
+            divergence: None,
+            leader_election: None,
+            drain: Arc::new(DrainMode::default()),
+            audit_log: Arc::new(AuditLog::default()),
+            auth: None,
+            webhooks: None,
+            fault_injector: None,
+            flashblocks_subscribers: None,
+            payload_history: None,
+            recent_flashblocks: None,
+            payload_archive: None,
+        }
     }
 
-    pub async fn run(self, debug_addr: &str) -> eyre::Result<()> {
-        let server = Server::builder().build(debug_addr).await?;
+    /// Sets the JWT secrets of the upstreams rotated by `debug_reloadJwtSecrets`.
+    pub fn with_jwt_secrets(mut self, jwt_secrets: Vec<(PayloadSource, SharedJwtSecret)>) -> Self {
+        self.jwt_secrets = jwt_secrets;
+        self
+    }
 
-        let handle = server.start(self.into_rpc());
+    /// Sets the registry managed by the `debug_*Builder*` methods.
+    pub fn with_builder_registry(mut self, builder_registry: Option<Arc<BuilderRegistry>>) -> Self {
+        self.builder_registry = builder_registry;
+        self
+    }
+
+    pub fn with_builder_quarantine(
+        mut self,
+        builder_quarantine: Option<Arc<BuilderQuarantine>>,
+    ) -> Self {
+        self.builder_quarantine = builder_quarantine;
+        self
+    }
+
+    pub fn with_builder_scores(mut self, builder_scores: Option<Arc<BuilderScores>>) -> Self {
+        self.builder_scores = builder_scores;
+        self
+    }
+
+    pub fn with_divergence_detector(mut self, divergence: Option<Arc<DivergenceDetector>>) -> Self {
+        self.divergence = divergence;
+        self
+    }
+
+    pub fn with_leader_election(mut self, leader_election: Option<Arc<LeaderElection>>) -> Self {
+        self.leader_election = leader_election;
+        self
+    }
+
+    /// Sets the drain mode managed by `debug_setDrainMode`.
+    pub fn with_drain_mode(mut self, drain: Arc<DrainMode>) -> Self {
+        self.drain = drain;
+        self
+    }
+
+    /// Sets the log execution mode changes are recorded in.
+    pub fn with_audit_log(mut self, audit_log: Arc<AuditLog>) -> Self {
+        self.audit_log = audit_log;
+        self
+    }
+
+    /// Only accepts requests carrying the bearer token of a known caller.
+    pub fn with_auth(mut self, auth: Option<Arc<DebugAuth>>) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    /// Notifies webhooks of execution mode changes.
+    pub fn with_webhooks(mut self, webhooks: Option<Arc<WebhookNotifier>>) -> Self {
+        self.webhooks = webhooks;
+        self
+    }
+
+    /// Sets the faults managed by `debug_setFaults`.
+    pub fn with_fault_injector(mut self, fault_injector: Option<Arc<FaultInjector>>) -> Self {
+        self.fault_injector = fault_injector;
+        self
+    }
+
+    /// Sets the subscribers listed by `debug_listSubscribers`.
+    pub fn with_flashblocks_subscribers(
+        mut self,
+        flashblocks_subscribers: Option<Arc<SubscriberRegistry>>,
+    ) -> Self {
+        self.flashblocks_subscribers = flashblocks_subscribers;
+        self
+    }
+
+    /// Sets the payload cycles returned by `debug_getRecentPayloads`.
+    pub fn with_payload_history(mut self, payload_history: Option<Arc<PayloadHistory>>) -> Self {
+        self.payload_history = payload_history;
+        self
+    }
 
-        tracing::info!("Debug server listening on addr {}", debug_addr);
+    /// Sets where `debug_getFlashblocks` looks the flashblocks up: in the flashblocks
+    /// received for the last payloads, then in the payload archive.
+    pub fn with_flashblocks_stores(
+        mut self,
+        recent_flashblocks: Option<Arc<RecentFlashblocks>>,
+        payload_archive: Option<Arc<PayloadArchive>>,
+    ) -> Self {
+        self.recent_flashblocks = recent_flashblocks;
+        self.payload_archive = payload_archive;
+        self
+    }
+
+    pub async fn run(self, debug_addr: &str) -> eyre::Result<()> {
+        let (handle, _) = self.start(debug_addr).await?;
 
         // In this example we don't care about doing shutdown so let's it run forever.
         // You may use the `ServerHandle` to shut it down or manage it yourself.
@@ -77,6 +381,43 @@ impl DebugServer {
         Ok(())
     }
 
+    /// Serves the debug API on `debug_addr`, returning the handle of the server and the
+    /// address it listens on.
+    pub async fn start(self, debug_addr: &str) -> eyre::Result<(ServerHandle, SocketAddr)> {
+        let server = Server::builder()
+            .set_http_middleware(
+                tower::ServiceBuilder::new().layer(DebugAuthLayer::new(self.auth.clone())),
+            )
+            .build(debug_addr)
+            .await?;
+        let local_addr = server.local_addr()?;
+
+        let handle = server.start(self.into_rpc());
+
+        tracing::info!("Debug server listening on addr {}", local_addr);
+
+        Ok((handle, local_addr))
+    }
+
+    /// Serves the debug API on a unix domain socket.
+    pub fn run_ipc(self, endpoint: &IpcEndpoint) -> eyre::Result<()> {
+        let listener = endpoint.bind()?;
+        let builder = Server::builder()
+            .set_http_middleware(
+                tower::ServiceBuilder::new().layer(DebugAuthLayer::new(self.auth.clone())),
+            )
+            .to_service_builder();
+        let handle = spawn_ipc_server(listener, |stop_handle| {
+            builder.build(self.into_rpc(), stop_handle)
+        });
+
+        tracing::info!("Debug server listening on {}", endpoint.path.display());
+
+        tokio::spawn(handle.stopped());
+
+        Ok(())
+    }
+
     pub fn execution_mode(&self) -> ExecutionMode {
         *self.execution_mode.lock()
     }
@@ -84,17 +425,136 @@ impl DebugServer {
     pub fn set_execution_mode(&self, mode: ExecutionMode) {
         *self.execution_mode.lock() = mode;
     }
+
+    fn builder_registry(&self) -> RpcResult<&BuilderRegistry> {
+        self.builder_registry.as_deref().ok_or_else(|| {
+            ErrorObjectOwned::owned(
+                INVALID_REQUEST_CODE,
+                "the builder registry is not enabled, set --builder-registry-path",
+                None::<()>,
+            )
+        })
+    }
+
+    /// The registry, along with the caller managing it. Since the builders are sent the
+    /// payload attributes, only the callers authenticated with `--debug-auth-tokens-path`
+    /// can manage them.
+    fn managed_builder_registry(&self, ext: &Extensions) -> RpcResult<(&BuilderRegistry, String)> {
+        if self.auth.is_none() {
+            return Err(ErrorObjectOwned::owned(
+                INVALID_REQUEST_CODE,
+                "managing builders requires an authenticated debug API, set --debug-auth-tokens-path",
+                None::<()>,
+            ));
+        }
+        let caller = ext
+            .get::<Caller>()
+            .map(|caller| caller.0.clone())
+            .unwrap_or_else(|| UNAUTHENTICATED_CALLER.to_string());
+        Ok((self.builder_registry()?, caller))
+    }
+
+    fn builder_scores(&self) -> RpcResult<&BuilderScores> {
+        self.builder_scores.as_deref().ok_or_else(|| {
+            ErrorObjectOwned::owned(
+                INVALID_REQUEST_CODE,
+                "builders are not scored, set --builder-scoring",
+                None::<()>,
+            )
+        })
+    }
+
+    fn fault_injector(&self) -> RpcResult<&FaultInjector> {
+        self.fault_injector.as_deref().ok_or_else(|| {
+            ErrorObjectOwned::owned(
+                INVALID_REQUEST_CODE,
+                "fault injection is not enabled, set --chaos",
+                None::<()>,
+            )
+        })
+    }
+
+    fn flashblocks_subscribers(&self) -> RpcResult<&SubscriberRegistry> {
+        self.flashblocks_subscribers.as_deref().ok_or_else(|| {
+            ErrorObjectOwned::owned(
+                INVALID_REQUEST_CODE,
+                "flashblocks are not published, set --flashblocks or --flashblocks-synthetic",
+                None::<()>,
+            )
+        })
+    }
+
+    fn payload_history(&self) -> RpcResult<&PayloadHistory> {
+        self.payload_history.as_deref().ok_or_else(|| {
+            ErrorObjectOwned::owned(
+                INVALID_REQUEST_CODE,
+                "payload history is not recorded by this server",
+                None::<()>,
+            )
+        })
+    }
+
+    fn list_builders_response(&self) -> RpcResult<ListBuildersResponse> {
+        Ok(ListBuildersResponse {
+            builders: self.builder_registry()?.builders(),
+        })
+    }
+}
+
+fn registry_error(e: BuilderRegistryError) -> ErrorObjectOwned {
+    let code = match e {
+        BuilderRegistryError::Io(_) => INTERNAL_ERROR_CODE,
+        _ => INVALID_PARAMS_CODE,
+    };
+    ErrorObjectOwned::owned(code, e.to_string(), None::<()>)
 }
 
 #[async_trait]
 impl DebugApiServer for DebugServer {
     async fn set_execution_mode(
         &self,
+        ext: &Extensions,
         request: SetExecutionModeRequest,
     ) -> RpcResult<SetExecutionModeResponse> {
-        self.set_execution_mode(request.execution_mode);
-
-        tracing::info!("Set execution mode to {:?}", request.execution_mode);
+        let caller = ext
+            .get::<Caller>()
+            .map(|caller| caller.0.clone())
+            .unwrap_or_else(|| UNAUTHENTICATED_CALLER.to_string());
+
+        // Record the change before it takes effect, so that no change goes unaudited
+        let mut execution_mode = self.execution_mode.lock();
+        let old_mode = *execution_mode;
+        self.audit_log
+            .record(AuditEntry::new(
+                caller.clone(),
+                old_mode,
+                request.execution_mode,
+            ))
+            .map_err(|e| {
+                ErrorObjectOwned::owned(
+                    INTERNAL_ERROR_CODE,
+                    format!("failed to record the execution mode change: {e}"),
+                    None::<()>,
+                )
+            })?;
+        *execution_mode = request.execution_mode;
+        drop(execution_mode);
+
+        tracing::info!(target: "rollup_boost::security", message = "set execution mode", ?old_mode, new_mode = ?request.execution_mode, %caller);
+        if let Some(webhooks) = &self.webhooks {
+            webhooks.notify(
+                WebhookEvent::ExecutionModeChanged,
+                format!(
+                    "execution mode changed from {old_mode:?} to {:?} by {caller}",
+                    request.execution_mode
+                ),
+                serde_json::json!({
+                    "caller": caller,
+                    "old_mode": old_mode,
+                    "new_mode": request.execution_mode,
+                }),
+            );
+        }
 
         Ok(SetExecutionModeResponse {
             execution_mode: request.execution_mode,
@@ -106,6 +566,216 @@ impl DebugApiServer for DebugServer {
             execution_mode: self.execution_mode(),
         })
     }
+
+    async fn get_status(&self) -> RpcResult<GetStatusResponse> {
+        Ok(GetStatusResponse {
+            execution_mode: self.execution_mode(),
+            builders: self
+                .builder_registry
+                .as_ref()
+                .map(|registry| registry.builders())
+                .unwrap_or_default(),
+            quarantined: self
+                .builder_quarantine
+                .as_ref()
+                .map(|quarantine| quarantine.quarantined())
+                .unwrap_or_default(),
+            divergence: self.divergence.as_ref().map(|d| d.status()),
+            leader: self.leader_election.as_ref().map(|l| l.status()),
+            drain: self.drain.status(),
+        })
+    }
+
+    async fn set_drain_mode(
+        &self,
+        ext: &Extensions,
+        request: SetDrainModeRequest,
+    ) -> RpcResult<DrainStatus> {
+        let caller = ext
+            .get::<Caller>()
+            .map(|caller| caller.0.clone())
+            .unwrap_or_else(|| UNAUTHENTICATED_CALLER.to_string());
+        tracing::info!(target: "rollup_boost::security", message = "set drain mode", enabled = request.enabled, %caller);
+        Ok(self.drain.set(request.enabled))
+    }
+
+    async fn set_log_filter(
+        &self,
+        request: SetLogFilterRequest,
+    ) -> RpcResult<SetLogFilterResponse> {
+        set_log_filter(&request.filter).map_err(|e| {
+            ErrorObjectOwned::owned(INVALID_PARAMS_CODE, format!("{e:#}"), None::<()>)
+        })?;
+
+        tracing::info!("Set log filter to {}", request.filter);
+
+        Ok(SetLogFilterResponse {
+            filter: request.filter,
+        })
+    }
+
+    async fn reload_jwt_secrets(&self) -> RpcResult<ReloadJwtSecretsResponse> {
+        let mut rotated = vec![];
+        for (source, secret) in &self.jwt_secrets {
+            let changed = secret.reload().map_err(|e| {
+                ErrorObjectOwned::owned(
+                    INTERNAL_ERROR_CODE,
+                    format!("failed to reload {source} JWT secret: {e}"),
+                    None::<()>,
+                )
+            })?;
+
+            if changed {
+                tracing::info!(target: "rollup_boost::security", message = "rotated JWT secret", %source, path = ?secret.path());
+                rotated.push(source.to_string());
+            }
+        }
+
+        Ok(ReloadJwtSecretsResponse { rotated })
+    }
+
+    async fn register_builder(
+        &self,
+        ext: &Extensions,
+        request: BuilderConfig,
+    ) -> RpcResult<ListBuildersResponse> {
+        let (registry, caller) = self.managed_builder_registry(ext)?;
+        let (name, url) = (request.name.clone(), request.url.clone());
+        registry.register(request).map_err(registry_error)?;
+
+        tracing::info!(target: "rollup_boost::security", message = "registered builder", %name, %url, %caller);
+        self.list_builders_response()
+    }
+
+    async fn set_builder_enabled(
+        &self,
+        ext: &Extensions,
+        request: SetBuilderEnabledRequest,
+    ) -> RpcResult<ListBuildersResponse> {
+        let (registry, caller) = self.managed_builder_registry(ext)?;
+        registry
+            .set_enabled(&request.name, request.enabled)
+            .map_err(registry_error)?;
+
+        tracing::info!(target: "rollup_boost::security", message = "updated builder", name = %request.name, enabled = request.enabled, %caller);
+        self.list_builders_response()
+    }
+
+    async fn remove_builder(
+        &self,
+        ext: &Extensions,
+        request: RemoveBuilderRequest,
+    ) -> RpcResult<ListBuildersResponse> {
+        let (registry, caller) = self.managed_builder_registry(ext)?;
+        registry.remove(&request.name).map_err(registry_error)?;
+
+        tracing::info!(target: "rollup_boost::security", message = "removed builder", name = %request.name, %caller);
+        self.list_builders_response()
+    }
+
+    async fn list_builders(&self) -> RpcResult<ListBuildersResponse> {
+        self.list_builders_response()
+    }
+
+    async fn get_audit_log(&self) -> RpcResult<GetAuditLogResponse> {
+        Ok(GetAuditLogResponse {
+            entries: self.audit_log.entries(),
+        })
+    }
+
+    async fn validate_flashblocks(
+        &self,
+        flashblocks: Vec<FlashblocksPayloadV1>,
+    ) -> RpcResult<ValidationReport> {
+        Ok(crate::validate_flashblocks(flashblocks))
+    }
+
+    async fn set_faults(&self, ext: &Extensions, faults: FaultConfig) -> RpcResult<FaultConfig> {
+        let caller = ext
+            .get::<Caller>()
+            .map(|caller| caller.0.clone())
+            .unwrap_or_else(|| UNAUTHENTICATED_CALLER.to_string());
+
+        self.fault_injector()?
+            .set_config(faults.clone())
+            .map_err(|e| ErrorObjectOwned::owned(INVALID_PARAMS_CODE, e, None::<()>))?;
+
+        tracing::warn!(target: "rollup_boost::security", message = "set injected faults", ?faults, %caller);
+        Ok(faults)
+    }
+
+    async fn get_faults(&self) -> RpcResult<FaultConfig> {
+        Ok(self.fault_injector()?.config())
+    }
+
+    async fn list_subscribers(&self) -> RpcResult<ListSubscribersResponse> {
+        Ok(ListSubscribersResponse {
+            subscribers: self.flashblocks_subscribers()?.subscribers(),
+        })
+    }
+
+    async fn get_recent_payloads(
+        &self,
+        count: Option<usize>,
+    ) -> RpcResult<GetRecentPayloadsResponse> {
+        Ok(GetRecentPayloadsResponse {
+            payloads: self
+                .payload_history()?
+                .recent(count.unwrap_or(DEFAULT_RECENT_PAYLOADS)),
+        })
+    }
+
+    async fn get_flashblocks(&self, payload_id: PayloadId) -> RpcResult<GetFlashblocksResponse> {
+        if self.recent_flashblocks.is_none() && self.payload_archive.is_none() {
+            return Err(ErrorObjectOwned::owned(
+                INVALID_REQUEST_CODE,
+                "flashblocks are not received, set --flashblocks",
+                None::<()>,
+            ));
+        }
+
+        if let Some(flashblocks) = self
+            .recent_flashblocks
+            .as_ref()
+            .and_then(|recent| recent.get(payload_id))
+        {
+            return Ok(GetFlashblocksResponse {
+                payload_id,
+                source: "memory".to_string(),
+                flashblocks,
+            });
+        }
+        if let Some(archive) = &self.payload_archive {
+            let sealed = archive.load(payload_id).map_err(|e| {
+                ErrorObjectOwned::owned(INTERNAL_ERROR_CODE, e.to_string(), None::<()>)
+            })?;
+            if let Some(sealed) = sealed {
+                return Ok(GetFlashblocksResponse {
+                    payload_id,
+                    source: "archive".to_string(),
+                    flashblocks: sealed.flashblocks,
+                });
+            }
+        }
+        Err(ErrorObjectOwned::owned(
+            INVALID_PARAMS_CODE,
+            format!("no flashblocks stored for payload {payload_id}"),
+            None::<()>,
+        ))
+    }
+
+    async fn get_metrics(&self, filter: Option<String>) -> RpcResult<GetMetricsResponse> {
+        let metrics = metrics_snapshot(filter.as_deref()).ok_or_else(|| {
+            ErrorObjectOwned::owned(INVALID_REQUEST_CODE, "metrics are not recorded", None::<()>)
+        })?;
+        Ok(GetMetricsResponse { metrics })
+    }
+
+    async fn get_builder_scores(&self) -> RpcResult<GetBuilderScoresResponse> {
+        Ok(GetBuilderScoresResponse {
+            builders: self.builder_scores()?.scores(),
+        })
+    }
 }
 
 pub struct DebugClient {
@@ -114,7 +784,19 @@ pub struct DebugClient {
 
 impl DebugClient {
     pub fn new(url: &str) -> eyre::Result<Self> {
-        let client = HttpClient::builder().build(url)?;
+        Self::new_with_auth(url, None)
+    }
+
+    /// Creates a client sending `token` as bearer token, if any.
+    pub fn new_with_auth(url: &str, token: Option<&str>) -> eyre::Result<Self> {
+        let mut headers = HeaderMap::new();
+        if let Some(token) = token {
+            headers.insert(
+                AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {token}"))?,
+            );
+        }
+        let client = HttpClient::builder().set_headers(headers).build(url)?;
 
         Ok(Self { client })
     }
@@ -132,11 +814,120 @@ impl DebugClient {
         let result = DebugApiClient::get_execution_mode(&self.client).await?;
         Ok(result)
     }
+
+    pub async fn get_status(&self) -> eyre::Result<GetStatusResponse> {
+        let result = DebugApiClient::get_status(&self.client).await?;
+        Ok(result)
+    }
+
+    pub async fn set_drain_mode(&self, enabled: bool) -> eyre::Result<DrainStatus> {
+        let request = SetDrainModeRequest { enabled };
+        let result = DebugApiClient::set_drain_mode(&self.client, request).await?;
+        Ok(result)
+    }
+
+    pub async fn set_log_filter(&self, filter: String) -> eyre::Result<SetLogFilterResponse> {
+        let request = SetLogFilterRequest { filter };
+        let result = DebugApiClient::set_log_filter(&self.client, request).await?;
+        Ok(result)
+    }
+
+    pub async fn reload_jwt_secrets(&self) -> eyre::Result<ReloadJwtSecretsResponse> {
+        let result = DebugApiClient::reload_jwt_secrets(&self.client).await?;
+        Ok(result)
+    }
+
+    pub async fn register_builder(
+        &self,
+        config: BuilderConfig,
+    ) -> eyre::Result<ListBuildersResponse> {
+        let result = DebugApiClient::register_builder(&self.client, config).await?;
+        Ok(result)
+    }
+
+    pub async fn set_builder_enabled(
+        &self,
+        name: String,
+        enabled: bool,
+    ) -> eyre::Result<ListBuildersResponse> {
+        let request = SetBuilderEnabledRequest { name, enabled };
+        let result = DebugApiClient::set_builder_enabled(&self.client, request).await?;
+        Ok(result)
+    }
+
+    pub async fn remove_builder(&self, name: String) -> eyre::Result<ListBuildersResponse> {
+        let request = RemoveBuilderRequest { name };
+        let result = DebugApiClient::remove_builder(&self.client, request).await?;
+        Ok(result)
+    }
+
+    pub async fn list_builders(&self) -> eyre::Result<ListBuildersResponse> {
+        let result = DebugApiClient::list_builders(&self.client).await?;
+        Ok(result)
+    }
+
+    pub async fn get_audit_log(&self) -> eyre::Result<GetAuditLogResponse> {
+        let result = DebugApiClient::get_audit_log(&self.client).await?;
+        Ok(result)
+    }
+
+    pub async fn validate_flashblocks(
+        &self,
+        flashblocks: Vec<FlashblocksPayloadV1>,
+    ) -> eyre::Result<ValidationReport> {
+        let result = DebugApiClient::validate_flashblocks(&self.client, flashblocks).await?;
+        Ok(result)
+    }
+
+    pub async fn set_faults(&self, faults: FaultConfig) -> eyre::Result<FaultConfig> {
+        let result = DebugApiClient::set_faults(&self.client, faults).await?;
+        Ok(result)
+    }
+
+    pub async fn get_faults(&self) -> eyre::Result<FaultConfig> {
+        let result = DebugApiClient::get_faults(&self.client).await?;
+        Ok(result)
+    }
+
+    pub async fn list_subscribers(&self) -> eyre::Result<ListSubscribersResponse> {
+        let result = DebugApiClient::list_subscribers(&self.client).await?;
+        Ok(result)
+    }
+
+    pub async fn get_recent_payloads(
+        &self,
+        count: Option<usize>,
+    ) -> eyre::Result<GetRecentPayloadsResponse> {
+        let result = DebugApiClient::get_recent_payloads(&self.client, count).await?;
+        Ok(result)
+    }
+
+    pub async fn get_flashblocks(
+        &self,
+        payload_id: PayloadId,
+    ) -> eyre::Result<GetFlashblocksResponse> {
+        let result = DebugApiClient::get_flashblocks(&self.client, payload_id).await?;
+        Ok(result)
+    }
+
+    pub async fn get_metrics(&self, filter: Option<String>) -> eyre::Result<GetMetricsResponse> {
+        let result = DebugApiClient::get_metrics(&self.client, filter).await?;
+        Ok(result)
+    }
+
+    pub async fn get_builder_scores(&self) -> eyre::Result<GetBuilderScoresResponse> {
+        let result = DebugApiClient::get_builder_scores(&self.client).await?;
+        Ok(result)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{DEFAULT_BUILDER, RpcClient};
+    use alloy_primitives::hex;
+    use alloy_rpc_types_engine::JwtSecret;
+    use std::collections::HashMap;
 
     const DEFAULT_ADDR: &str = "127.0.0.1:5555";
 
@@ -172,4 +963,53 @@ mod tests {
         let status = client.get_execution_mode().await.unwrap();
         assert_eq!(status.execution_mode, ExecutionMode::Enabled);
     }
+
+    #[tokio::test]
+    async fn test_builder_management_requires_auth() -> eyre::Result<()> {
+        let url = http::Uri::from_static("http://127.0.0.1:8551");
+        let client = RpcClient::new(
+            url.clone(),
+            JwtSecret::random(),
+            1000,
+            PayloadSource::Builder,
+        )?;
+        let registry = Arc::new(BuilderRegistry::new(Arc::new(client), &url, 1000));
+        let config = BuilderConfig {
+            name: "backup".to_string(),
+            url: "http://127.0.0.1:9551".to_string(),
+            jwt_secret: Some(hex::encode(JwtSecret::random().as_bytes())),
+            jwt_path: None,
+            priority: 0,
+            weight: 1,
+            enabled: true,
+        };
+        let execution_mode = Arc::new(Mutex::new(ExecutionMode::Enabled));
+
+        // Without authentication, the builders can be listed but not managed
+        let (handle, addr) = DebugServer::new(execution_mode.clone())
+            .with_builder_registry(Some(registry.clone()))
+            .start("127.0.0.1:0")
+            .await?;
+        let client = DebugClient::new(&format!("http://{addr}"))?;
+        assert!(client.register_builder(config.clone()).await.is_err());
+        assert!(
+            client
+                .remove_builder(DEFAULT_BUILDER.to_string())
+                .await
+                .is_err()
+        );
+        assert_eq!(client.list_builders().await?.builders.len(), 1);
+        handle.stop()?;
+
+        let auth = DebugAuth::new(HashMap::from([("alice".to_string(), "secret".to_string())]));
+        let (handle, addr) = DebugServer::new(execution_mode)
+            .with_builder_registry(Some(registry))
+            .with_auth(Some(Arc::new(auth)))
+            .start("127.0.0.1:0")
+            .await?;
+        let client = DebugClient::new_with_auth(&format!("http://{addr}"), Some("secret"))?;
+        assert_eq!(client.register_builder(config).await?.builders.len(), 2);
+        handle.stop()?;
+        Ok(())
+    }
 }
@@ -0,0 +1,366 @@
+use std::path::PathBuf;
+
+use alloy_consensus::Transaction;
+use alloy_consensus::transaction::SignerRecoverable;
+use alloy_eips::Decodable2718;
+use alloy_primitives::{Address, B256, Bytes, U256};
+use alloy_rpc_types_engine::ExecutionPayload;
+use clap::Parser;
+use eyre::bail;
+use op_alloy_consensus::OpTxEnvelope;
+use op_alloy_rpc_types_engine::{OpExecutionPayloadEnvelopeV3, OpExecutionPayloadEnvelopeV4};
+use serde::Serialize;
+
+use crate::{
+    FlashblocksPayloadV1, OpExecutionPayloadEnvelope, ValidationReport, assemble_flashblocks,
+};
+
+#[derive(Parser, Clone, Debug)]
+pub struct DecodePayloadArgs {
+    /// File holding the recorded payload, read from stdin if not set. Either a
+    /// `engine_getPayloadV3` or `engine_getPayloadV4` envelope, optionally wrapped in its
+    /// JSON-RPC response, or the flashblocks of a payload as a JSON array or JSON lines
+    pub path: Option<PathBuf>,
+
+    /// Print the decoded payload as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// A transaction of a decoded payload.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DecodedTransaction {
+    pub index: usize,
+    pub hash: B256,
+    pub tx_type: String,
+    pub from: Option<Address>,
+    pub to: Option<Address>,
+    pub nonce: u64,
+    pub gas_limit: u64,
+    pub size: usize,
+}
+
+/// A payload decoded offline by `rollup-boost decode-payload`, with the results of its
+/// validation.
+#[derive(Serialize, Debug, Clone)]
+pub struct DecodedPayload {
+    /// Version of the payload, `v3` for Ecotone and `v4` for Isthmus
+    pub version: &'static str,
+    pub block_number: u64,
+    pub block_hash: B256,
+    /// Hash of the header rebuilt from the payload, which should match `block_hash`
+    pub computed_block_hash: B256,
+    pub parent_hash: B256,
+    pub parent_beacon_block_root: Option<B256>,
+    pub timestamp: u64,
+    pub fee_recipient: Address,
+    pub state_root: B256,
+    pub receipts_root: B256,
+    pub transactions_root: B256,
+    pub withdrawals_root: Option<B256>,
+    pub gas_limit: u64,
+    pub gas_used: u64,
+    pub base_fee_per_gas: u64,
+    pub extra_data: Bytes,
+    pub block_value: U256,
+    pub transactions: Vec<DecodedTransaction>,
+    /// Report of the flashblocks the payload was assembled from, if it was
+    pub flashblocks: Option<ValidationReport>,
+    pub errors: Vec<String>,
+    pub valid: bool,
+}
+
+impl DecodedPayload {
+    pub fn new(
+        envelope: &OpExecutionPayloadEnvelope,
+        flashblocks: Option<ValidationReport>,
+    ) -> Self {
+        let header = envelope.header();
+        let computed_block_hash = header.hash_slow();
+        let block_hash = envelope.block_hash();
+
+        let mut errors = vec![];
+        if computed_block_hash != block_hash {
+            errors.push(format!(
+                "Block hash {block_hash} does not match the computed hash {computed_block_hash}"
+            ));
+        }
+
+        let execution_payload = ExecutionPayload::from(envelope.clone());
+        let mut transactions = vec![];
+        for (index, tx) in execution_payload.transactions().iter().enumerate() {
+            match OpTxEnvelope::decode_2718(&mut tx.as_ref()) {
+                Ok(decoded) => transactions.push(DecodedTransaction {
+                    index,
+                    hash: decoded.tx_hash(),
+                    tx_type: format!("{:?}", decoded.tx_type()),
+                    from: decoded.recover_signer().ok(),
+                    to: decoded.to(),
+                    nonce: decoded.nonce(),
+                    gas_limit: decoded.gas_limit(),
+                    size: tx.len(),
+                }),
+                Err(e) => errors.push(format!("Transaction {index} cannot be decoded: {e}")),
+            }
+        }
+        if let Some(report) = &flashblocks {
+            errors.extend(
+                report
+                    .flashblocks
+                    .iter()
+                    .filter_map(|flashblock| {
+                        let error = flashblock.error.as_ref()?;
+                        Some(format!("Flashblock {}: {error}", flashblock.index))
+                    })
+                    .chain(report.payload_errors.iter().cloned()),
+            );
+        }
+
+        Self {
+            version: envelope.version().as_str(),
+            block_number: header.number,
+            block_hash,
+            computed_block_hash,
+            parent_hash: header.parent_hash,
+            parent_beacon_block_root: header.parent_beacon_block_root,
+            timestamp: header.timestamp,
+            fee_recipient: header.beneficiary,
+            state_root: header.state_root,
+            receipts_root: header.receipts_root,
+            transactions_root: header.transactions_root,
+            withdrawals_root: header.withdrawals_root,
+            gas_limit: header.gas_limit,
+            gas_used: header.gas_used,
+            base_fee_per_gas: header.base_fee_per_gas.unwrap_or_default(),
+            extra_data: header.extra_data,
+            block_value: envelope.block_value(),
+            transactions,
+            flashblocks,
+            valid: errors.is_empty(),
+            errors,
+        }
+    }
+}
+
+impl std::fmt::Display for DecodedPayload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Block {} ({})", self.block_number, self.version)?;
+        writeln!(f, "  block hash:         {}", self.block_hash)?;
+        writeln!(f, "  computed hash:      {}", self.computed_block_hash)?;
+        writeln!(f, "  parent hash:        {}", self.parent_hash)?;
+        if let Some(root) = self.parent_beacon_block_root {
+            writeln!(f, "  parent beacon root: {root}")?;
+        }
+        writeln!(f, "  timestamp:          {}", self.timestamp)?;
+        writeln!(f, "  fee recipient:      {}", self.fee_recipient)?;
+        writeln!(f, "  state root:         {}", self.state_root)?;
+        writeln!(f, "  receipts root:      {}", self.receipts_root)?;
+        writeln!(f, "  transactions root:  {}", self.transactions_root)?;
+        if let Some(root) = self.withdrawals_root {
+            writeln!(f, "  withdrawals root:   {root}")?;
+        }
+        writeln!(
+            f,
+            "  gas:                {} / {}",
+            self.gas_used, self.gas_limit
+        )?;
+        writeln!(f, "  base fee:           {}", self.base_fee_per_gas)?;
+        writeln!(f, "  extra data:         {}", self.extra_data)?;
+        writeln!(f, "  block value:        {}", self.block_value)?;
+        if let Some(report) = &self.flashblocks {
+            writeln!(f, "  flashblocks:        {}", report.flashblocks.len())?;
+        }
+
+        writeln!(f, "Transactions ({})", self.transactions.len())?;
+        for tx in &self.transactions {
+            writeln!(
+                f,
+                "  {:>4} {} {} from={} to={} nonce={} gas_limit={} size={}",
+                tx.index,
+                tx.hash,
+                tx.tx_type,
+                tx.from.map(|from| from.to_string()).unwrap_or_default(),
+                tx.to.map(|to| to.to_string()).unwrap_or_default(),
+                tx.nonce,
+                tx.gas_limit,
+                tx.size
+            )?;
+        }
+
+        match self.valid {
+            true => writeln!(f, "Valid"),
+            false => {
+                writeln!(f, "Errors")?;
+                for error in &self.errors {
+                    writeln!(f, "  {error}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Decodes a recorded envelope or flashblocks sequence, see [`DecodePayloadArgs::path`].
+pub fn decode_payload(input: &str) -> eyre::Result<DecodedPayload> {
+    let values = match serde_json::from_str::<serde_json::Value>(input) {
+        Ok(serde_json::Value::Array(values)) => values,
+        Ok(value) => vec![value],
+        // JSON lines, as printed by `rollup-boost stream --json`
+        Err(_) => input
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?,
+    };
+    let mut values: Vec<_> = values
+        .into_iter()
+        .map(|mut value| match value.get_mut("result") {
+            Some(result) => result.take(),
+            None => value,
+        })
+        .collect();
+
+    if values.iter().all(|value| value.get("payload_id").is_some()) {
+        let flashblocks = values
+            .into_iter()
+            .map(serde_json::from_value::<FlashblocksPayloadV1>)
+            .collect::<Result<Vec<_>, _>>()?;
+        let (report, envelope) = assemble_flashblocks(flashblocks);
+        let Some(envelope) = envelope else {
+            bail!(
+                "the flashblocks cannot be assembled: {}",
+                report.payload_errors.join(", ")
+            );
+        };
+        return Ok(DecodedPayload::new(&envelope, Some(report)));
+    }
+
+    let [value] = values.as_mut_slice() else {
+        bail!("expected a single payload envelope, got {}", values.len());
+    };
+    let is_isthmus = value.pointer("/executionPayload/withdrawalsRoot").is_some();
+    let envelope = match is_isthmus {
+        true => OpExecutionPayloadEnvelope::V4(serde_json::from_value::<
+            OpExecutionPayloadEnvelopeV4,
+        >(value.take())?),
+        false => OpExecutionPayloadEnvelope::V3(serde_json::from_value::<
+            OpExecutionPayloadEnvelopeV3,
+        >(value.take())?),
+    };
+    Ok(DecodedPayload::new(&envelope, None))
+}
+
+impl DecodePayloadArgs {
+    pub async fn run(self) -> eyre::Result<()> {
+        let input = match &self.path {
+            Some(path) => std::fs::read_to_string(path)?,
+            None => std::io::read_to_string(std::io::stdin())?,
+        };
+        let decoded = decode_payload(&input)?;
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&decoded)?);
+        } else {
+            print!("{decoded}");
+        }
+        if !decoded.valid {
+            bail!("the payload is invalid");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::split_payload;
+    use alloy_rpc_types_engine::{
+        BlobsBundleV1, ExecutionPayloadV1, ExecutionPayloadV2, ExecutionPayloadV3, PayloadId,
+    };
+
+    fn envelope() -> OpExecutionPayloadEnvelopeV3 {
+        let mut envelope = OpExecutionPayloadEnvelopeV3 {
+            execution_payload: ExecutionPayloadV3 {
+                payload_inner: ExecutionPayloadV2 {
+                    payload_inner: ExecutionPayloadV1 {
+                        parent_hash: B256::random(),
+                        fee_recipient: Address::ZERO,
+                        state_root: B256::random(),
+                        receipts_root: B256::random(),
+                        logs_bloom: Default::default(),
+                        prev_randao: B256::random(),
+                        block_number: 1,
+                        gas_limit: 30_000_000,
+                        gas_used: 0,
+                        timestamp: 1,
+                        extra_data: Bytes::new(),
+                        base_fee_per_gas: U256::from(1),
+                        block_hash: B256::ZERO,
+                        transactions: vec![],
+                    },
+                    withdrawals: vec![],
+                },
+                blob_gas_used: 0,
+                excess_blob_gas: 0,
+            },
+            block_value: U256::ZERO,
+            blobs_bundle: BlobsBundleV1::default(),
+            should_override_builder: false,
+            parent_beacon_block_root: B256::random(),
+        };
+        let block_hash = OpExecutionPayloadEnvelope::V3(envelope.clone())
+            .header()
+            .hash_slow();
+        envelope
+            .execution_payload
+            .payload_inner
+            .payload_inner
+            .block_hash = block_hash;
+        envelope
+    }
+
+    #[test]
+    fn test_decode_envelope() {
+        let envelope = envelope();
+        let json = serde_json::to_string(&envelope).unwrap();
+        let decoded = decode_payload(&json).unwrap();
+        assert!(decoded.valid, "{:?}", decoded.errors);
+        assert_eq!(decoded.block_hash, decoded.computed_block_hash);
+
+        // Wrapped in a JSON-RPC response, with a corrupted block hash
+        let mut response = serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": envelope });
+        response["result"]["executionPayload"]["blockHash"] = serde_json::json!(B256::ZERO);
+        let decoded = decode_payload(&response.to_string()).unwrap();
+        assert!(!decoded.valid);
+        assert_eq!(decoded.errors.len(), 1);
+
+        // An undecodable transaction
+        let mut envelope = envelope;
+        envelope
+            .execution_payload
+            .payload_inner
+            .payload_inner
+            .transactions = vec![Bytes::from_static(&[0x02, 0x01])];
+        let decoded = decode_payload(&serde_json::to_string(&envelope).unwrap()).unwrap();
+        assert!(
+            decoded
+                .errors
+                .iter()
+                .any(|e| e.starts_with("Transaction 0"))
+        );
+    }
+
+    #[test]
+    fn test_decode_flashblocks() {
+        let envelope = OpExecutionPayloadEnvelope::V3(envelope());
+        let flashblocks = split_payload(PayloadId::new([1; 8]), &envelope, 1);
+        let lines: Vec<_> = flashblocks
+            .iter()
+            .map(|flashblock| serde_json::to_string(flashblock).unwrap())
+            .collect();
+
+        let decoded = decode_payload(&lines.join("\n")).unwrap();
+        assert!(decoded.valid, "{:?}", decoded.errors);
+        assert_eq!(decoded.block_hash, envelope.block_hash());
+        assert!(decoded.flashblocks.is_some());
+    }
+}
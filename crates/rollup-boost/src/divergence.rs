@@ -0,0 +1,207 @@
+use std::{sync::Arc, time::Duration};
+
+use alloy_primitives::B256;
+use alloy_rpc_types_eth::BlockNumberOrTag;
+use clap::Parser;
+use metrics::{counter, gauge};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::{ClientResult, EngineApiExt, RpcClient};
+
+#[derive(Parser, Clone, Debug)]
+pub struct DivergenceArgs {
+    /// Duration in seconds between comparisons of the builder and L2 client heads,
+    /// heads are not compared if not set
+    #[arg(long, env)]
+    pub divergence_check_interval: Option<u64>,
+
+    /// Maximum number of blocks between the builder and L2 client heads before the
+    /// builder is bypassed
+    #[arg(long, env, default_value = "10")]
+    pub max_head_divergence: u64,
+}
+
+/// Why the builder head was found to diverge from the L2 client head.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DivergenceReason {
+    /// The heads are more than `max_head_divergence` blocks apart
+    Distance,
+    /// The clients have a different block at the same height
+    Hash,
+}
+
+impl DivergenceReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DivergenceReason::Distance => "distance",
+            DivergenceReason::Hash => "hash",
+        }
+    }
+}
+
+/// Outcome of the last comparison of the builder and L2 client heads, as reported by
+/// `debug_getStatus`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DivergenceStatus {
+    pub builder_number: u64,
+    pub l2_number: u64,
+    /// Set while the builder is bypassed because its head diverged
+    pub reason: Option<DivergenceReason>,
+}
+
+impl DivergenceStatus {
+    pub fn is_diverged(&self) -> bool {
+        self.reason.is_some()
+    }
+}
+
+/// Periodically compares the head of the builder with the head of the L2 client. While
+/// they are too far apart, or disagree on the hash of a block, the builder is bypassed
+/// and blocks are built by the L2 client only.
+#[derive(Debug)]
+pub struct DivergenceDetector {
+    interval: Duration,
+    max_blocks: u64,
+    status: Mutex<DivergenceStatus>,
+}
+
+impl DivergenceDetector {
+    pub fn new(interval: Duration, max_blocks: u64) -> Self {
+        Self {
+            interval,
+            max_blocks,
+            status: Mutex::new(DivergenceStatus::default()),
+        }
+    }
+
+    pub fn from_args(args: &DivergenceArgs) -> Option<Self> {
+        args.divergence_check_interval
+            .map(|interval| Self::new(Duration::from_secs(interval), args.max_head_divergence))
+    }
+
+    pub fn status(&self) -> DivergenceStatus {
+        self.status.lock().clone()
+    }
+
+    pub fn is_diverged(&self) -> bool {
+        self.status.lock().is_diverged()
+    }
+
+    pub fn spawn(
+        self: Arc<Self>,
+        builder_client: Arc<dyn EngineApiExt>,
+        l2_client: Arc<RpcClient>,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.check(builder_client.as_ref(), &l2_client).await {
+                    warn!(message = "failed to compare the builder and l2 heads", error = %e);
+                }
+            }
+        })
+    }
+
+    async fn check(
+        &self,
+        builder_client: &dyn EngineApiExt,
+        l2_client: &RpcClient,
+    ) -> ClientResult<()> {
+        let (builder_head, l2_head) = tokio::try_join!(
+            builder_client.get_block_by_number(BlockNumberOrTag::Latest, false),
+            l2_client.get_block_by_number(BlockNumberOrTag::Latest, false),
+        )?;
+        let (builder_number, l2_number) = (builder_head.header.number, l2_head.header.number);
+
+        // Compare the hashes at the height of the lowest head
+        let (builder_hash, l2_hash) = match builder_number.cmp(&l2_number) {
+            std::cmp::Ordering::Equal => (builder_head.header.hash, l2_head.header.hash),
+            std::cmp::Ordering::Less => {
+                let l2_block = l2_client
+                    .get_block_by_number(builder_number.into(), false)
+                    .await?;
+                (builder_head.header.hash, l2_block.header.hash)
+            }
+            std::cmp::Ordering::Greater => {
+                let builder_block = builder_client
+                    .get_block_by_number(l2_number.into(), false)
+                    .await?;
+                (builder_block.header.hash, l2_head.header.hash)
+            }
+        };
+
+        self.update(builder_number, l2_number, builder_hash, l2_hash);
+        Ok(())
+    }
+
+    fn update(&self, builder_number: u64, l2_number: u64, builder_hash: B256, l2_hash: B256) {
+        gauge!("divergence.blocks").set(builder_number as f64 - l2_number as f64);
+
+        let reason = if builder_number.abs_diff(l2_number) > self.max_blocks {
+            Some(DivergenceReason::Distance)
+        } else if builder_hash != l2_hash {
+            Some(DivergenceReason::Hash)
+        } else {
+            None
+        };
+
+        let mut status = self.status.lock();
+        let was_diverged = status.is_diverged();
+        *status = DivergenceStatus {
+            builder_number,
+            l2_number,
+            reason,
+        };
+
+        match reason {
+            Some(reason) if !was_diverged => {
+                counter!("divergence.detected", "reason" => reason.as_str()).increment(1);
+                gauge!("divergence.active").set(1);
+                warn!(
+                    message = "builder head diverged from the l2 head, bypassing the builder",
+                    reason = reason.as_str(),
+                    builder_number,
+                    l2_number,
+                    %builder_hash,
+                    %l2_hash,
+                );
+            }
+            None if was_diverged => {
+                gauge!("divergence.active").set(0);
+                info!(
+                    message = "builder head converged with the l2 head, resuming the builder",
+                    builder_number, l2_number,
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_divergence() {
+        let detector = DivergenceDetector::new(Duration::from_secs(1), 5);
+        let (a, b) = (B256::repeat_byte(1), B256::repeat_byte(2));
+
+        detector.update(100, 98, a, a);
+        assert!(!detector.is_diverged());
+
+        detector.update(100, 100, a, b);
+        assert_eq!(detector.status().reason, Some(DivergenceReason::Hash));
+
+        detector.update(90, 100, a, a);
+        assert_eq!(detector.status().reason, Some(DivergenceReason::Distance));
+
+        detector.update(100, 100, a, a);
+        assert!(!detector.is_diverged());
+    }
+}
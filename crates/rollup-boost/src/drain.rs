@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use alloy_rpc_types_engine::PayloadId;
+use metrics::gauge;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// Time after which a payload the builder started building is no longer waited for, as
+/// its payload will not be requested, e.g. after a reorg.
+const ABANDONED_CYCLE_TIMEOUT: Duration = Duration::from_secs(12);
+
+/// Drain state reported by the debug API.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DrainStatus {
+    pub draining: bool,
+    /// Payloads the builder is building whose payload was not requested yet
+    pub in_flight: usize,
+    /// Whether the process can be stopped without missing a block built by the builder
+    pub safe_to_stop: bool,
+}
+
+/// Drains rollup-boost before it is stopped, e.g. during a rollout. While draining,
+/// payload attributes are only sent to the l2 client, so that no new builder payload
+/// cycle starts, and the payloads the builder already started building are still
+/// served. Once none remain, the process can be stopped without missing a block.
+#[derive(Debug, Default)]
+pub struct DrainMode {
+    draining: AtomicBool,
+    // Payloads the builder started building, by the time they were started at
+    in_flight: Mutex<HashMap<PayloadId, Instant>>,
+}
+
+impl DrainMode {
+    /// Starts or stops draining, returning the resulting status.
+    pub fn set(&self, draining: bool) -> DrainStatus {
+        if self.draining.swap(draining, Ordering::Relaxed) != draining {
+            info!(message = "drain mode changed", draining);
+        }
+        gauge!("drain.draining").set(draining as u8 as f64);
+        self.status()
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    pub fn status(&self) -> DrainStatus {
+        self.status_at(Instant::now())
+    }
+
+    fn status_at(&self, now: Instant) -> DrainStatus {
+        let mut in_flight = self.in_flight.lock();
+        in_flight.retain(|_, started| now.duration_since(*started) < ABANDONED_CYCLE_TIMEOUT);
+        gauge!("drain.in_flight_cycles").set(in_flight.len() as f64);
+        let draining = self.is_draining();
+        DrainStatus {
+            draining,
+            in_flight: in_flight.len(),
+            safe_to_stop: draining && in_flight.is_empty(),
+        }
+    }
+
+    /// Records that the builder started building `payload_id`.
+    pub(crate) fn start_cycle(&self, payload_id: PayloadId) {
+        let mut in_flight = self.in_flight.lock();
+        in_flight.insert(payload_id, Instant::now());
+        gauge!("drain.in_flight_cycles").set(in_flight.len() as f64);
+    }
+
+    /// Guard recording that the payload of `payload_id` was served, or failed to be, once
+    /// dropped, so that the cycle is waited for until the payload is returned.
+    pub(crate) fn serve_cycle(&self, payload_id: PayloadId) -> ServedCycle<'_> {
+        ServedCycle {
+            drain: self,
+            payload_id,
+        }
+    }
+
+    fn finish_cycle(&self, payload_id: PayloadId) {
+        let mut in_flight = self.in_flight.lock();
+        if in_flight.remove(&payload_id).is_some() {
+            gauge!("drain.in_flight_cycles").set(in_flight.len() as f64);
+        }
+    }
+}
+
+pub(crate) struct ServedCycle<'a> {
+    drain: &'a DrainMode,
+    payload_id: PayloadId,
+}
+
+impl Drop for ServedCycle<'_> {
+    fn drop(&mut self) {
+        self.drain.finish_cycle(self.payload_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drain_mode() {
+        let drain = DrainMode::default();
+        let payload_id = PayloadId::new([1; 8]);
+        drain.start_cycle(payload_id);
+        assert!(!drain.status().safe_to_stop);
+
+        // The cycle started before draining is waited for
+        let status = drain.set(true);
+        assert!(status.draining);
+        assert_eq!(status.in_flight, 1);
+        assert!(!status.safe_to_stop);
+
+        let cycle = drain.serve_cycle(payload_id);
+        assert!(!drain.status().safe_to_stop);
+        drop(cycle);
+        assert!(drain.status().safe_to_stop);
+
+        // A cycle whose payload is never requested is not waited for forever
+        drain.start_cycle(payload_id);
+        let status = drain.status_at(Instant::now() + ABANDONED_CYCLE_TIMEOUT);
+        assert!(status.safe_to_stop);
+
+        assert!(!drain.set(false).safe_to_stop);
+    }
+}
@@ -0,0 +1,58 @@
+use http::HeaderMap;
+use std::future::Future;
+
+/// Header forcing the engine API call it is sent with into dry run: on a forkchoice
+/// update starting a payload, the l2 client payload is returned for that payload even if
+/// the builder built a valid one. On `get_payload`, it only applies to that call.
+pub const DRY_RUN_HEADER: &str = "x-rollup-boost-dry-run";
+
+tokio::task_local! {
+    static DRY_RUN: bool;
+}
+
+/// A dry run requested for a single FCU/getPayload cycle with the [`DRY_RUN_HEADER`],
+/// to test a builder on a live system without changing the execution mode of rollup-boost.
+/// Like the global dry run mode, the builder still builds the payload, and the l2 client
+/// payload is returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DryRunOverride;
+
+impl DryRunOverride {
+    /// Whether the request asks for a dry run, with `true` or `1`.
+    pub fn from_headers(headers: &HeaderMap) -> bool {
+        headers
+            .get(DRY_RUN_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.eq_ignore_ascii_case("true") || value == "1")
+    }
+
+    /// Whether the request currently being handled asks for a dry run.
+    pub fn requested() -> bool {
+        DRY_RUN.try_with(|dry_run| *dry_run).unwrap_or_default()
+    }
+
+    /// Runs the future with the dry run requested or not for the current request.
+    pub async fn scope<F: Future>(requested: bool, f: F) -> F::Output {
+        DRY_RUN.scope(requested, f).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+
+    #[tokio::test]
+    async fn test_dry_run_override() {
+        let mut headers = HeaderMap::new();
+        assert!(!DryRunOverride::from_headers(&headers));
+        headers.insert(DRY_RUN_HEADER, HeaderValue::from_static("TRUE"));
+        assert!(DryRunOverride::from_headers(&headers));
+        headers.insert(DRY_RUN_HEADER, HeaderValue::from_static("no"));
+        assert!(!DryRunOverride::from_headers(&headers));
+
+        assert!(!DryRunOverride::requested());
+        assert!(DryRunOverride::scope(true, async { DryRunOverride::requested() }).await);
+        assert!(!DryRunOverride::requested());
+    }
+}
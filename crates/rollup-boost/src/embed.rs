@@ -0,0 +1,340 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use eyre::OptionExt;
+use futures::future::select_all;
+use http::Uri;
+use jsonrpsee::RpcModule;
+use jsonrpsee::server::{Server, ServerHandle};
+use parking_lot::Mutex;
+use tokio::task::JoinHandle;
+use url::Url;
+
+use crate::{
+    EngineApiExt, ExecutionMode, Flashblocks, FlashblocksLimits, FlashblocksService,
+    IngestOverflowPolicy, PayloadSource, ProxyLayer, PublisherConfig, RollupBoostServer, RpcClient,
+    SharedJwtSecret, probe::ProbeLayer,
+};
+
+/// Timeout of the calls to the L2 client and builder, in milliseconds.
+const DEFAULT_TIMEOUT: u64 = 1000;
+
+/// Number of flashblocks from the builder waiting to be accumulated.
+const FLASHBLOCKS_QUEUE_SIZE: usize = 100;
+
+/// An Engine API endpoint and the JWT secret authenticating the calls made to it.
+#[derive(Clone, Debug)]
+struct Endpoint {
+    url: Uri,
+    secret: SharedJwtSecret,
+}
+
+/// Builds a [`RollupBoost`], for binaries embedding rollup-boost rather than running it
+/// as a separate process.
+///
+/// ```ignore
+/// let rollup_boost = RollupBoost::builder()
+///     .with_l2_client(l2_url, l2_secret)
+///     .with_builder_client(builder_url, builder_secret)
+///     .with_flashblocks(builder_ws_url, "0.0.0.0:1111".parse()?)
+///     .with_debug_api("127.0.0.1:5555".parse()?)
+///     .start()
+///     .await?;
+///
+/// rollup_boost.stopped().await;
+/// ```
+#[derive(Debug)]
+pub struct RollupBoostBuilder {
+    l2: Option<Endpoint>,
+    builder: Option<Endpoint>,
+    timeout: u64,
+    rpc_addr: SocketAddr,
+    debug_addr: Option<SocketAddr>,
+    flashblocks: Option<(Url, SocketAddr)>,
+    flashblocks_limits: FlashblocksLimits,
+    execution_mode: ExecutionMode,
+}
+
+impl Default for RollupBoostBuilder {
+    fn default() -> Self {
+        Self {
+            l2: None,
+            builder: None,
+            timeout: DEFAULT_TIMEOUT,
+            rpc_addr: SocketAddr::from(([127, 0, 0, 1], 8081)),
+            debug_addr: None,
+            flashblocks: None,
+            flashblocks_limits: FlashblocksLimits::default(),
+            execution_mode: ExecutionMode::Enabled,
+        }
+    }
+}
+
+impl RollupBoostBuilder {
+    /// Engine API of the L2 client, required.
+    pub fn with_l2_client(mut self, url: Uri, secret: impl Into<SharedJwtSecret>) -> Self {
+        self.l2 = Some(Endpoint {
+            url,
+            secret: secret.into(),
+        });
+        self
+    }
+
+    /// Engine API of the builder, required.
+    pub fn with_builder_client(mut self, url: Uri, secret: impl Into<SharedJwtSecret>) -> Self {
+        self.builder = Some(Endpoint {
+            url,
+            secret: secret.into(),
+        });
+        self
+    }
+
+    /// Timeout of the calls to the L2 client and builder, in milliseconds.
+    pub fn with_timeout(mut self, timeout: u64) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Address the Engine API is served on, `127.0.0.1:8081` by default. Port 0 picks
+    /// an ephemeral port, see [`RollupBoost::rpc_addr`].
+    pub fn with_rpc_addr(mut self, addr: SocketAddr) -> Self {
+        self.rpc_addr = addr;
+        self
+    }
+
+    /// Serves the debug API on `addr`. The debug API is not served by default.
+    pub fn with_debug_api(mut self, addr: SocketAddr) -> Self {
+        self.debug_addr = Some(addr);
+        self
+    }
+
+    /// Builds the payloads from the flashblocks streamed by the builder at
+    /// `builder_url`, republishing them on `addr`.
+    pub fn with_flashblocks(mut self, builder_url: Url, addr: SocketAddr) -> Self {
+        self.flashblocks = Some((builder_url, addr));
+        self
+    }
+
+    pub fn with_flashblocks_limits(mut self, limits: FlashblocksLimits) -> Self {
+        self.flashblocks_limits = limits;
+        self
+    }
+
+    pub fn with_execution_mode(mut self, execution_mode: ExecutionMode) -> Self {
+        self.execution_mode = execution_mode;
+        self
+    }
+
+    /// Connects to the L2 client and builder, then spawns the servers and tasks of
+    /// rollup-boost.
+    pub async fn start(self) -> eyre::Result<RollupBoost> {
+        let l2 = self.l2.ok_or_eyre("the L2 client is not set")?;
+        let builder = self.builder.ok_or_eyre("the builder client is not set")?;
+
+        let l2_client = RpcClient::new(
+            l2.url.clone(),
+            l2.secret.clone(),
+            self.timeout,
+            PayloadSource::L2,
+        )?;
+        let builder_client = RpcClient::new(
+            builder.url.clone(),
+            builder.secret.clone(),
+            self.timeout,
+            PayloadSource::Builder,
+        )?;
+
+        let mut tasks = vec![];
+        let (builder_client, flashblocks_addr): (Arc<dyn EngineApiExt>, _) = match self.flashblocks
+        {
+            Some((builder_url, addr)) => {
+                let service =
+                    FlashblocksService::new(builder_client, &[addr], PublisherConfig::default())?
+                        .with_limits(self.flashblocks_limits);
+                let (service, handles) = Flashblocks::spawn(
+                    service,
+                    builder_url,
+                    FLASHBLOCKS_QUEUE_SIZE,
+                    IngestOverflowPolicy::Block,
+                );
+                tasks.extend(handles);
+                let outbound_addr = service.outbound_addr();
+                (Arc::new(service), Some(outbound_addr))
+            }
+            None => (Arc::new(builder_client), None),
+        };
+
+        let execution_mode = Arc::new(Mutex::new(self.execution_mode));
+        let (probe_layer, probes) = ProbeLayer::new();
+        let rollup_boost = RollupBoostServer::new(
+            l2_client,
+            builder_client,
+            execution_mode.clone(),
+            None,
+            probes.clone(),
+            60,
+            5,
+        );
+
+        let mut servers = vec![];
+        let debug_addr = match self.debug_addr {
+            Some(addr) => {
+                let (handle, addr) = rollup_boost.serve_debug_api(&addr.to_string()).await?;
+                servers.push(handle);
+                Some(addr)
+            }
+            None => None,
+        };
+
+        let module: RpcModule<()> = rollup_boost.try_into()?;
+        let http_middleware =
+            tower::ServiceBuilder::new()
+                .layer(probe_layer)
+                .layer(ProxyLayer::new(
+                    l2.url,
+                    l2.secret,
+                    builder.url,
+                    builder.secret,
+                    probes,
+                    execution_mode.clone(),
+                ));
+        let server = Server::builder()
+            .set_http_middleware(http_middleware)
+            .build(self.rpc_addr)
+            .await?;
+        let rpc_addr = server.local_addr()?;
+        servers.push(server.start(module));
+
+        Ok(RollupBoost {
+            servers,
+            tasks,
+            rpc_addr,
+            debug_addr,
+            flashblocks_addr,
+            execution_mode,
+        })
+    }
+}
+
+/// rollup-boost running in process, with the handles of the servers and tasks it
+/// spawned. Dropping it leaves them running, see [`RollupBoost::stop`].
+#[derive(Debug)]
+pub struct RollupBoost {
+    servers: Vec<ServerHandle>,
+    tasks: Vec<JoinHandle<()>>,
+    rpc_addr: SocketAddr,
+    debug_addr: Option<SocketAddr>,
+    flashblocks_addr: Option<SocketAddr>,
+    execution_mode: Arc<Mutex<ExecutionMode>>,
+}
+
+impl RollupBoost {
+    pub fn builder() -> RollupBoostBuilder {
+        RollupBoostBuilder::default()
+    }
+
+    /// Address the Engine API is served on.
+    pub fn rpc_addr(&self) -> SocketAddr {
+        self.rpc_addr
+    }
+
+    /// Address the debug API is served on, if it is.
+    pub fn debug_addr(&self) -> Option<SocketAddr> {
+        self.debug_addr
+    }
+
+    /// Address the flashblocks are republished on, if flashblocks are enabled.
+    pub fn flashblocks_addr(&self) -> Option<SocketAddr> {
+        self.flashblocks_addr
+    }
+
+    pub fn execution_mode(&self) -> ExecutionMode {
+        *self.execution_mode.lock()
+    }
+
+    /// Changes the execution mode, like `debug_setExecutionMode` without the audit log
+    /// and webhooks.
+    pub fn set_execution_mode(&self, execution_mode: ExecutionMode) {
+        *self.execution_mode.lock() = execution_mode;
+    }
+
+    /// Handles of the Engine API and debug servers.
+    pub fn servers(&self) -> &[ServerHandle] {
+        &self.servers
+    }
+
+    /// Handles of the flashblocks tasks.
+    pub fn tasks(&self) -> &[JoinHandle<()>] {
+        &self.tasks
+    }
+
+    /// Waits until one of the servers stops.
+    pub async fn stopped(&self) {
+        let stopped = self
+            .servers
+            .iter()
+            .map(|handle| Box::pin(handle.clone().stopped()));
+        select_all(stopped).await;
+    }
+
+    /// Stops the servers and tasks, waiting for the servers to shut down.
+    pub async fn stop(self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+        for server in self.servers {
+            if server.stop().is_ok() {
+                server.stopped().await;
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "mock-builder"))]
+mod tests {
+    use super::*;
+    use crate::{DebugClient, EngineApiClient, MockBuilder, MockBuilderConfig};
+    use alloy_rpc_types_engine::{ForkchoiceState, JwtSecret};
+    use alloy_rpc_types_eth::BlockNumberOrTag;
+    use jsonrpsee::http_client::HttpClientBuilder;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_embedded_rollup_boost() -> eyre::Result<()> {
+        let localhost: SocketAddr = "127.0.0.1:0".parse()?;
+        let secret = JwtSecret::random();
+        let (builder_server, builder_addr) = MockBuilder::new(MockBuilderConfig::default())
+            .start(localhost)
+            .await?;
+        let (l2_server, l2_addr) = MockBuilder::new(MockBuilderConfig::default())
+            .start(localhost)
+            .await?;
+
+        let rollup_boost = RollupBoost::builder()
+            .with_l2_client(Uri::from_str(&format!("http://{l2_addr}"))?, secret)
+            .with_builder_client(Uri::from_str(&format!("http://{builder_addr}"))?, secret)
+            .with_rpc_addr(localhost)
+            .with_debug_api(localhost)
+            .start()
+            .await?;
+        assert!(rollup_boost.flashblocks_addr().is_none());
+
+        let client =
+            HttpClientBuilder::new().build(format!("http://{}", rollup_boost.rpc_addr()))?;
+        client
+            .fork_choice_updated_v3(ForkchoiceState::default(), None)
+            .await?;
+        EngineApiClient::get_block_by_number(&client, BlockNumberOrTag::Latest, false).await?;
+
+        let debug_addr = rollup_boost.debug_addr().expect("debug API is served");
+        let debug_client = DebugClient::new(&format!("http://{debug_addr}"))?;
+        debug_client
+            .set_execution_mode(ExecutionMode::Disabled)
+            .await?;
+        assert_eq!(rollup_boost.execution_mode(), ExecutionMode::Disabled);
+
+        rollup_boost.stop().await;
+        builder_server.stop()?;
+        l2_server.stop()?;
+        Ok(())
+    }
+}
@@ -1,9 +1,15 @@
-use alloy_rpc_types_engine::{ForkchoiceState, ForkchoiceUpdated, PayloadId, PayloadStatus};
+use alloy_primitives::{B256, U64};
+use alloy_rpc_types_engine::{
+    ExecutionPayloadBodiesV1, ForkchoiceState, ForkchoiceUpdated, PayloadId, PayloadStatus,
+};
 use alloy_rpc_types_eth::{Block, BlockNumberOrTag};
 use jsonrpsee::core::async_trait;
 use op_alloy_rpc_types_engine::OpPayloadAttributes;
 
-use crate::{ClientResult, NewPayload, OpExecutionPayloadEnvelope, PayloadVersion};
+use crate::{
+    BlockSource, ClientResult, DEFAULT_BUILDER, NewPayload, OpExecutionPayloadEnvelope,
+    PayloadVersion, ReorgEvent,
+};
 
 #[async_trait]
 pub trait EngineApiExt: Send + Sync + 'static {
@@ -21,9 +27,43 @@ pub trait EngineApiExt: Send + Sync + 'static {
         version: PayloadVersion,
     ) -> ClientResult<OpExecutionPayloadEnvelope>;
 
+    /// The source of the payload returned by the last successful `get_payload` call.
+    fn last_payload_source(&self) -> BlockSource;
+
+    /// The name of the builder that built the payload returned by the last successful
+    /// `get_payload` call.
+    fn last_payload_builder(&self) -> String {
+        DEFAULT_BUILDER.to_string()
+    }
+
+    /// The number of flashblocks the payload returned by the last successful
+    /// `get_payload` call was assembled from, 0 if it was not assembled from flashblocks.
+    fn last_payload_flashblocks(&self) -> usize {
+        0
+    }
+
+    async fn get_payload_bodies_by_hash_v1(
+        &self,
+        block_hashes: Vec<B256>,
+    ) -> ClientResult<ExecutionPayloadBodiesV1>;
+
+    async fn get_payload_bodies_by_range_v1(
+        &self,
+        start: U64,
+        count: U64,
+    ) -> ClientResult<ExecutionPayloadBodiesV1>;
+
+    async fn exchange_capabilities(&self, capabilities: Vec<String>) -> ClientResult<Vec<String>>;
+
     async fn get_block_by_number(
         &self,
         number: BlockNumberOrTag,
         full: bool,
     ) -> ClientResult<Block>;
+
+    async fn chain_id(&self) -> ClientResult<u64>;
+
+    /// Called when the unsafe head is reorged, so that any state built on top of the
+    /// previous head can be discarded.
+    async fn on_reorg(&self, _event: &ReorgEvent) {}
 }
@@ -0,0 +1,40 @@
+use jsonrpsee::types::ErrorObjectOwned;
+use serde::Serialize;
+
+/// Generic server error, for failures that may succeed when retried.
+pub const SERVER_ERROR_CODE: i32 = -32000;
+
+/// Engine API error codes, see
+/// <https://github.com/ethereum/execution-apis/blob/main/src/engine/common.md#errors>.
+pub const UNKNOWN_PAYLOAD_CODE: i32 = -38001;
+pub const INVALID_FORKCHOICE_STATE_CODE: i32 = -38002;
+pub const INVALID_PAYLOAD_ATTRIBUTES_CODE: i32 = -38003;
+pub const TOO_LARGE_REQUEST_CODE: i32 = -38004;
+pub const UNSUPPORTED_FORK_CODE: i32 = -38005;
+
+/// An error returned to the consensus client as a specific Engine API error, so that its
+/// retry logic can tell transient failures from invalid requests.
+///
+/// Transient failures are returned as [`SERVER_ERROR_CODE`], the other failures with the
+/// code describing them best, such as the Engine API codes.
+pub trait EngineApiError: std::error::Error {
+    /// JSON-RPC error code returned for the error.
+    fn code(&self) -> i32;
+
+    /// Stable name of the error, returned in the data of the JSON-RPC error.
+    fn kind(&self) -> &'static str;
+
+    fn to_error_object(&self) -> ErrorObjectOwned {
+        ErrorObjectOwned::owned(
+            self.code(),
+            self.to_string(),
+            Some(ErrorData { kind: self.kind() }),
+        )
+    }
+}
+
+/// Data of the JSON-RPC errors returned for an [`EngineApiError`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorData {
+    pub kind: &'static str,
+}
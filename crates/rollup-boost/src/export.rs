@@ -0,0 +1,330 @@
+use std::path::PathBuf;
+
+use alloy_primitives::Bytes;
+use clap::Parser;
+
+use crate::SealedPayload;
+
+#[derive(Parser, Clone, Debug)]
+pub struct ExportParquetArgs {
+    /// Directory of the payload archive, as set with `--archive-path`
+    pub archive_path: PathBuf,
+
+    /// Directory the Parquet files are written to. Partitions exported before are
+    /// overwritten
+    #[arg(long, short)]
+    pub output: PathBuf,
+
+    /// How the flashblocks are partitioned into files
+    #[arg(long, default_value = "day")]
+    pub partition: ParquetPartition,
+
+    /// Number of blocks per file, with `--partition blocks`
+    #[arg(long, default_value = "10000")]
+    pub blocks_per_file: u64,
+}
+
+/// Partitioning of the exported flashblocks, one Parquet file per partition.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, clap::ValueEnum)]
+pub enum ParquetPartition {
+    /// By UTC day at which the payloads were returned
+    Day,
+    /// By range of block numbers
+    Blocks,
+}
+
+impl ParquetPartition {
+    /// Hive-style directory of the partition of `record`, e.g. `date=2025-06-01` or
+    /// `blocks=100000-109999`.
+    pub fn directory(&self, record: &FlashblockRecord, blocks_per_file: u64) -> String {
+        match self {
+            ParquetPartition::Day => format!("date={}", utc_date(record.sealed_at)),
+            ParquetPartition::Blocks => {
+                let blocks_per_file = blocks_per_file.max(1);
+                let start = record.block_number / blocks_per_file * blocks_per_file;
+                format!("blocks={start}-{}", start + blocks_per_file - 1)
+            }
+        }
+    }
+}
+
+/// A flashblock of an archived payload, as exported to Parquet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlashblockRecord {
+    pub block_number: u64,
+    pub payload_id: String,
+    /// Hash of the payload returned, after the extra data was tagged
+    pub block_hash: String,
+    /// Unix timestamp at which the payload was returned, in milliseconds
+    pub sealed_at: u64,
+    pub version: String,
+    pub index: u64,
+    pub tx_count: u64,
+    /// Gas used by the block up to and including the flashblock
+    pub gas_used: u64,
+    /// Hash of the block up to and including the flashblock
+    pub flashblock_block_hash: String,
+    pub state_root: String,
+    pub transactions: Vec<Bytes>,
+}
+
+impl FlashblockRecord {
+    /// The flashblocks of an archived payload, in order.
+    pub fn from_payload(payload: &SealedPayload) -> Vec<Self> {
+        payload
+            .flashblocks
+            .iter()
+            .map(|flashblock| Self {
+                block_number: payload.block_number,
+                payload_id: payload.payload_id.to_string(),
+                block_hash: payload.block_hash.to_string(),
+                sealed_at: payload.sealed_at,
+                version: payload.version.clone(),
+                index: flashblock.index,
+                tx_count: flashblock.diff.transactions.len() as u64,
+                gas_used: flashblock.diff.gas_used,
+                flashblock_block_hash: flashblock.diff.block_hash.to_string(),
+                state_root: flashblock.diff.state_root.to_string(),
+                transactions: flashblock.diff.transactions.clone(),
+            })
+            .collect()
+    }
+}
+
+impl ExportParquetArgs {
+    pub async fn run(self) -> eyre::Result<()> {
+        for file in self.export()? {
+            println!("{}", file.display());
+        }
+        Ok(())
+    }
+
+    /// Converts the payload archive to Parquet files, one row per flashblock, so that
+    /// the preconfirmation history can be loaded in a lakehouse. Returns the files
+    /// written.
+    pub fn export(&self) -> eyre::Result<Vec<PathBuf>> {
+        #[cfg(feature = "parquet")]
+        {
+            writer::export(self)
+        }
+
+        #[cfg(not(feature = "parquet"))]
+        eyre::bail!(
+            "cannot export {}, rollup-boost was built without the parquet feature",
+            self.archive_path.display()
+        )
+    }
+}
+
+/// The `YYYY-MM-DD` UTC date of a Unix timestamp in milliseconds.
+fn utc_date(timestamp_ms: u64) -> String {
+    // Converts days since the epoch to a civil date, see
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = (timestamp_ms / 86_400_000) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(feature = "parquet")]
+mod writer {
+    use std::{
+        collections::{BTreeMap, btree_map::Entry},
+        fs::{self, File},
+        path::PathBuf,
+        sync::Arc,
+    };
+
+    use arrow_array::{
+        ArrayRef, RecordBatch, StringArray, TimestampMillisecondArray, UInt64Array,
+        builder::{BinaryBuilder, ListBuilder},
+    };
+    use eyre::eyre;
+    use parquet::{
+        arrow::ArrowWriter,
+        basic::{Compression, ZstdLevel},
+        file::properties::WriterProperties,
+    };
+
+    use super::{ExportParquetArgs, FlashblockRecord};
+    use crate::{SealedPayload, archived_files};
+
+    /// Number of flashblocks buffered per partition before they are written.
+    const BATCH_SIZE: usize = 8192;
+
+    /// Name of the Parquet file of each partition.
+    const FILE_NAME: &str = "flashblocks.parquet";
+
+    struct Partition {
+        path: PathBuf,
+        writer: Option<ArrowWriter<File>>,
+        records: Vec<FlashblockRecord>,
+    }
+
+    impl Partition {
+        fn flush(&mut self) -> eyre::Result<()> {
+            if self.records.is_empty() {
+                return Ok(());
+            }
+            let batch = record_batch(&std::mem::take(&mut self.records))?;
+            if self.writer.is_none() {
+                let properties = WriterProperties::builder()
+                    .set_compression(Compression::ZSTD(ZstdLevel::default()))
+                    .build();
+                self.writer = Some(ArrowWriter::try_new(
+                    File::create(&self.path)?,
+                    batch.schema(),
+                    Some(properties),
+                )?);
+            }
+            if let Some(writer) = &mut self.writer {
+                writer.write(&batch)?;
+            }
+            Ok(())
+        }
+    }
+
+    pub(super) fn export(args: &ExportParquetArgs) -> eyre::Result<Vec<PathBuf>> {
+        let mut partitions = BTreeMap::new();
+        for path in archived_files(&args.archive_path)? {
+            let payload: SealedPayload = serde_json::from_slice(&fs::read(&path)?)
+                .map_err(|e| eyre!("invalid archived payload {}: {e}", path.display()))?;
+
+            for record in FlashblockRecord::from_payload(&payload) {
+                let partition = match partitions
+                    .entry(args.partition.directory(&record, args.blocks_per_file))
+                {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        let dir = args.output.join(entry.key());
+                        fs::create_dir_all(&dir)?;
+                        entry.insert(Partition {
+                            path: dir.join(FILE_NAME),
+                            writer: None,
+                            records: vec![],
+                        })
+                    }
+                };
+                partition.records.push(record);
+                if partition.records.len() >= BATCH_SIZE {
+                    partition.flush()?;
+                }
+            }
+        }
+
+        let mut files = vec![];
+        for mut partition in partitions.into_values() {
+            partition.flush()?;
+            if let Some(writer) = partition.writer {
+                writer.close()?;
+                files.push(partition.path);
+            }
+        }
+        Ok(files)
+    }
+
+    fn record_batch(records: &[FlashblockRecord]) -> eyre::Result<RecordBatch> {
+        let mut transactions = ListBuilder::new(BinaryBuilder::new());
+        for record in records {
+            for transaction in &record.transactions {
+                transactions.values().append_value(transaction);
+            }
+            transactions.append(true);
+        }
+
+        let u64s = |values: Vec<u64>| Arc::new(UInt64Array::from(values)) as ArrayRef;
+        let strings = |values: Vec<&str>| Arc::new(StringArray::from(values)) as ArrayRef;
+        Ok(RecordBatch::try_from_iter([
+            (
+                "block_number",
+                u64s(records.iter().map(|r| r.block_number).collect()),
+            ),
+            (
+                "payload_id",
+                strings(records.iter().map(|r| r.payload_id.as_str()).collect()),
+            ),
+            (
+                "block_hash",
+                strings(records.iter().map(|r| r.block_hash.as_str()).collect()),
+            ),
+            (
+                "sealed_at",
+                Arc::new(TimestampMillisecondArray::from_iter_values(
+                    records.iter().map(|r| r.sealed_at as i64),
+                )) as ArrayRef,
+            ),
+            (
+                "version",
+                strings(records.iter().map(|r| r.version.as_str()).collect()),
+            ),
+            ("index", u64s(records.iter().map(|r| r.index).collect())),
+            (
+                "tx_count",
+                u64s(records.iter().map(|r| r.tx_count).collect()),
+            ),
+            (
+                "gas_used",
+                u64s(records.iter().map(|r| r.gas_used).collect()),
+            ),
+            (
+                "flashblock_block_hash",
+                strings(
+                    records
+                        .iter()
+                        .map(|r| r.flashblock_block_hash.as_str())
+                        .collect(),
+                ),
+            ),
+            (
+                "state_root",
+                strings(records.iter().map(|r| r.state_root.as_str()).collect()),
+            ),
+            ("transactions", Arc::new(transactions.finish()) as ArrayRef),
+        ])?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parquet_partition() {
+        assert_eq!(utc_date(0), "1970-01-01");
+        assert_eq!(utc_date(951_782_400_000), "2000-02-29");
+        assert_eq!(utc_date(1_748_822_399_999), "2025-06-01");
+
+        let record = FlashblockRecord {
+            block_number: 123_456,
+            payload_id: "0x0101010101010101".to_string(),
+            block_hash: String::new(),
+            sealed_at: 1_748_736_000_000,
+            version: "v3".to_string(),
+            index: 0,
+            tx_count: 0,
+            gas_used: 0,
+            flashblock_block_hash: String::new(),
+            state_root: String::new(),
+            transactions: vec![],
+        };
+        assert_eq!(
+            ParquetPartition::Day.directory(&record, 10_000),
+            "date=2025-06-01"
+        );
+        assert_eq!(
+            ParquetPartition::Blocks.directory(&record, 10_000),
+            "blocks=120000-129999"
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use alloy_rpc_types_engine::{ForkchoiceState, ForkchoiceUpdated, PayloadId};
+use op_alloy_rpc_types_engine::OpPayloadAttributes;
+use parking_lot::Mutex;
+
+/// A forkchoice update with payload attributes, and the response returned to it.
+#[derive(Debug, Clone)]
+struct ForkchoiceUpdate {
+    state: ForkchoiceState,
+    attributes: OpPayloadAttributes,
+    response: ForkchoiceUpdated,
+}
+
+/// Remembers the response to the last forkchoice update that started building a payload.
+///
+/// The consensus client retries forkchoice updates it did not get a response to in time.
+/// Forwarding an identical update again would make the builder restart building the
+/// payload, so the response to the first one is returned instead, until another
+/// forkchoice update or `engine_getPayload` is received.
+#[derive(Debug, Default)]
+pub struct ForkchoiceUpdateCache {
+    last: Mutex<Option<ForkchoiceUpdate>>,
+}
+
+impl ForkchoiceUpdateCache {
+    /// The response to the last forkchoice update, if it had the same state and
+    /// attributes.
+    pub fn get(
+        &self,
+        state: &ForkchoiceState,
+        attributes: &OpPayloadAttributes,
+    ) -> Option<ForkchoiceUpdated> {
+        self.last
+            .lock()
+            .as_ref()
+            .filter(|last| last.state == *state && last.attributes == *attributes)
+            .map(|last| last.response.clone())
+    }
+
+    /// Records the response to a forkchoice update with payload attributes. Only responses
+    /// that started building a payload are remembered.
+    pub fn insert(
+        &self,
+        state: ForkchoiceState,
+        attributes: OpPayloadAttributes,
+        response: &ForkchoiceUpdated,
+    ) {
+        *self.last.lock() = response.payload_id.map(|_| ForkchoiceUpdate {
+            state,
+            attributes,
+            response: response.clone(),
+        });
+    }
+
+    /// Forgets the last forkchoice update, or only the one that started building
+    /// `payload_id` if given.
+    pub fn clear(&self, payload_id: Option<PayloadId>) {
+        let mut last = self.last.lock();
+        if payload_id.is_none()
+            || last
+                .as_ref()
+                .is_some_and(|last| last.response.payload_id == payload_id)
+        {
+            *last = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::B256;
+    use alloy_rpc_types_engine::{PayloadStatus, PayloadStatusEnum};
+
+    #[test]
+    fn test_forkchoice_update_cache() {
+        let cache = ForkchoiceUpdateCache::default();
+        let state = ForkchoiceState {
+            head_block_hash: B256::with_last_byte(1),
+            ..Default::default()
+        };
+        let attributes = OpPayloadAttributes::default();
+        let payload_id = PayloadId::new([1; 8]);
+        let response = ForkchoiceUpdated::new(PayloadStatus::from_status(PayloadStatusEnum::Valid))
+            .with_payload_id(payload_id);
+
+        assert!(cache.get(&state, &attributes).is_none());
+        cache.insert(state, attributes.clone(), &response);
+        assert_eq!(cache.get(&state, &attributes), Some(response.clone()));
+
+        // Updates with other attributes or state are not deduplicated
+        let other_attributes = OpPayloadAttributes {
+            gas_limit: Some(30_000_000),
+            ..Default::default()
+        };
+        assert!(cache.get(&state, &other_attributes).is_none());
+        let other_state = ForkchoiceState {
+            head_block_hash: B256::with_last_byte(2),
+            ..Default::default()
+        };
+        assert!(cache.get(&other_state, &attributes).is_none());
+
+        // Retrieving another payload keeps the cached update
+        cache.clear(Some(PayloadId::new([2; 8])));
+        assert!(cache.get(&state, &attributes).is_some());
+        cache.clear(Some(payload_id));
+        assert!(cache.get(&state, &attributes).is_none());
+    }
+}
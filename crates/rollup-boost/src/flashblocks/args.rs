@@ -1,4 +1,13 @@
+use super::conflict::{BuilderStream, ConflictResolution};
+use super::gossip::GossipArgs;
+use super::skew::ClockSkewCheck;
+use alloy_primitives::{Address, Selector};
 use clap::Parser;
+use std::{
+    net::{Ipv4Addr, SocketAddrV4},
+    path::PathBuf,
+    time::Duration,
+};
 use url::Url;
 
 #[derive(Parser, Clone, Debug)]
@@ -11,11 +20,235 @@ pub struct FlashblocksArgs {
     #[arg(long, env, default_value = "ws://127.0.0.1:1111")]
     pub flashblocks_builder_url: Url,
 
-    /// Flashblocks WebSocket host for outbound connections
-    #[arg(long, env, default_value = "127.0.0.1")]
-    pub flashblocks_host: String,
+    /// Flashblocks streams of other builders building the same payloads, comma separated
+    /// `name=url` pairs. The builder at `--flashblocks-builder-url` is named `default`
+    #[arg(long, env, value_delimiter = ',')]
+    pub flashblocks_builder_streams: Vec<BuilderStream>,
+
+    /// Which builder stream wins once several builders streamed the same index of a
+    /// payload
+    #[arg(long, env, default_value = "pinned-primary")]
+    pub flashblocks_conflict_resolution: ConflictResolution,
+
+    /// Experimental: assemble the block returned by `engine_getPayload` from the deltas of
+    /// the winning builder stream, interleaved with the non-conflicting deltas of the
+    /// other builder streams, and have the l2 client build it
+    #[arg(
+        long,
+        env,
+        default_value = "false",
+        requires = "flashblocks_builder_streams"
+    )]
+    pub flashblocks_merge: bool,
+
+    /// Flashblocks WebSocket hosts for outbound connections, comma separated IPv4 or IPv6
+    /// addresses
+    #[arg(long, env, default_value = "127.0.0.1", value_delimiter = ',')]
+    pub flashblocks_host: Vec<String>,
 
     /// Flashblocks WebSocket port for outbound connections
     #[arg(long, env, default_value = "1112")]
     pub flashblocks_port: u16,
+
+    /// Addresses whose transactions must not be included in payloads assembled from
+    /// flashblocks, as sender or recipient
+    #[arg(long, env, value_delimiter = ',')]
+    pub flashblocks_denied_addresses: Vec<Address>,
+
+    /// Function selectors that must not be called by transactions in payloads assembled
+    /// from flashblocks
+    #[arg(long, env, value_delimiter = ',')]
+    pub flashblocks_denied_selectors: Vec<Selector>,
+
+    /// Maximum number of flashblocks accepted for a single payload
+    #[arg(long, env, default_value = "1000")]
+    pub flashblocks_max_per_payload: usize,
+
+    /// Maximum number of transactions in a single flashblock delta
+    #[arg(long, env, default_value = "10000")]
+    pub flashblocks_max_delta_transactions: usize,
+
+    /// Maximum size in bytes of the transactions and withdrawals of a single flashblock
+    /// delta
+    #[arg(long, env, default_value = "33554432")]
+    pub flashblocks_max_delta_bytes: usize,
+
+    /// Maximum size in bytes of the transactions and withdrawals accumulated for a
+    /// single payload
+    #[arg(long, env, default_value = "134217728")]
+    pub flashblocks_max_payload_bytes: usize,
+
+    /// Expected block time in seconds. Flashblocks are discarded once the timestamp of
+    /// the block they build is older than the block time plus the grace period
+    #[arg(long, env, default_value = "2")]
+    pub flashblocks_block_time: u64,
+
+    /// Grace period in milliseconds before the flashblocks of a past block are discarded
+    #[arg(long, env, default_value = "1000")]
+    pub flashblocks_staleness_grace_ms: u64,
+
+    /// Milliseconds by which the timestamp of the blocks built by the builder may have
+    /// passed, or be due after the next block time, when their first flashblock is
+    /// received, before a clock skew is reported
+    #[arg(long, env, default_value = "500")]
+    pub flashblocks_clock_skew_tolerance_ms: u64,
+
+    /// Milliseconds after the forkchoice update that started a payload after which its ID
+    /// and flashblocks are dropped if `get_payload` was not called, the block time plus
+    /// the grace period if not set
+    #[arg(long, env)]
+    pub flashblocks_payload_id_ttl_ms: Option<u64>,
+
+    /// Deprecated and ignored. Since Holocene the extra data encodes the EIP-1559
+    /// parameters, so the blocks assembled from flashblocks cannot be tagged
+    #[arg(long, env, hide = true)]
+    pub flashblocks_extra_data_tag: Option<String>,
+
+    /// Number of payloads whose flashblocks are kept in memory for `debug_getFlashblocks`
+    #[arg(long, env, default_value = "64")]
+    pub flashblocks_recent_payloads: usize,
+
+    /// Maximum number of flashblocks received from the builder waiting to be accumulated
+    #[arg(long, env, default_value = "100")]
+    pub flashblocks_ingest_queue_size: usize,
+
+    /// What to do with the flashblocks received while the ingest queue is full
+    #[arg(long, env, default_value = "block")]
+    pub flashblocks_ingest_overflow: IngestOverflowPolicy,
+
+    /// What to do with the transactions streamed in more than one delta of the same
+    /// payload
+    #[arg(long, env, default_value = "reject")]
+    pub flashblocks_duplicate_transactions: DuplicateTransactionPolicy,
+
+    /// Minimum milliseconds between two deltas of a payload. Deltas received sooner are
+    /// rejected as possibly replayed or flooded, disabled if 0
+    #[arg(long, env, default_value = "0")]
+    pub flashblocks_min_delta_interval_ms: u64,
+
+    /// Milliseconds between two deltas of a payload above which the builder is reported
+    /// as slower than the target cadence, disabled if not set
+    #[arg(long, env)]
+    pub flashblocks_max_delta_interval_ms: Option<u64>,
+
+    /// Maximum number of subscribers of the flashblocks websocket, unlimited if not set
+    #[arg(long, env)]
+    pub flashblocks_max_subscribers: Option<usize>,
+
+    /// Maximum number of subscribers of the flashblocks websocket from the same address,
+    /// unlimited if not set
+    #[arg(long, env)]
+    pub flashblocks_max_subscribers_per_ip: Option<usize>,
+
+    /// Maximum number of messages per second a subscriber of the flashblocks websocket
+    /// may send before being disconnected, unlimited if not set
+    #[arg(long, env)]
+    pub flashblocks_max_subscriber_message_rate: Option<u32>,
+
+    /// Directory of the outbox the published messages are retained in, so that
+    /// subscribers reconnecting with `?since=<seq>` receive the messages they missed.
+    /// Messages are not retained if not set
+    #[arg(long, env, value_name = "PATH")]
+    pub flashblocks_outbox_path: Option<PathBuf>,
+
+    /// Seconds the messages are retained in the outbox
+    #[arg(long, env, default_value = "300")]
+    pub flashblocks_outbox_retention_secs: u64,
+
+    /// Maximum number of messages retained in the outbox
+    #[arg(long, env, default_value = "10000")]
+    pub flashblocks_outbox_capacity: usize,
+
+    /// UDP multicast group and port the published messages are also emitted to, for
+    /// consumers on the same LAN, disabled if not set
+    #[arg(long, env)]
+    pub flashblocks_multicast_addr: Option<SocketAddrV4>,
+
+    /// Address of the interface the multicast packets are sent from, any interface by
+    /// default
+    #[arg(long, env, default_value = "0.0.0.0")]
+    pub flashblocks_multicast_interface: Ipv4Addr,
+
+    /// Number of routers the multicast packets may cross, 1 keeps them on the LAN
+    #[arg(long, env, default_value = "1")]
+    pub flashblocks_multicast_ttl: u32,
+
+    /// Number of fragments of a message covered by each parity packet, from which a
+    /// single lost fragment is recovered. 0 disables forward error correction
+    #[arg(long, env, default_value = "4")]
+    pub flashblocks_multicast_fec_group: u8,
+
+    /// Publish synthetic flashblocks on the outbound address, split from the payloads
+    /// returned by rollup-boost, for chains without a flashblocks-capable builder
+    #[arg(long, env, default_value = "false", conflicts_with = "flashblocks")]
+    pub flashblocks_synthetic: bool,
+
+    /// Number of synthetic flashblocks each payload is split into
+    #[arg(long, env, default_value = "10")]
+    pub flashblocks_synthetic_count: usize,
+
+    /// Interval in milliseconds between two synthetic flashblocks of a payload
+    #[arg(long, env, default_value = "200")]
+    pub flashblocks_synthetic_interval_ms: u64,
+
+    #[clap(flatten)]
+    pub gossip: GossipArgs,
+}
+
+/// What to do with a flashblock received from the builder while the ingest queue is
+/// full.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum IngestOverflowPolicy {
+    /// Stop reading the stream of the builder until the queue has room
+    #[default]
+    Block,
+    /// Drop the flashblock. The following flashblocks of the payload are then out of
+    /// sequence, so the payload is fetched from the builder
+    Drop,
+}
+
+/// What to do with a flashblock delta repeating a transaction of the payload being
+/// built, which would otherwise only be found invalid by the proposer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum DuplicateTransactionPolicy {
+    /// Reject the flashblock. The following flashblocks of the payload are then out of
+    /// sequence, so the payload is fetched from the builder
+    #[default]
+    Reject,
+    /// Discard the flashblocks of the payload, which is then fetched from the builder.
+    /// Removing the repeated transactions from the delta would leave the block hash and
+    /// roots of the builder stale
+    Discard,
+}
+
+impl DuplicateTransactionPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DuplicateTransactionPolicy::Reject => "reject",
+            DuplicateTransactionPolicy::Discard => "discard",
+        }
+    }
+}
+
+impl FlashblocksArgs {
+    /// Age of the block being built after which its flashblocks are discarded.
+    pub fn staleness_ttl(&self) -> Duration {
+        Duration::from_secs(self.flashblocks_block_time)
+            + Duration::from_millis(self.flashblocks_staleness_grace_ms)
+    }
+
+    /// Expected timestamps of the blocks built by the builder.
+    pub fn clock_skew_check(&self) -> ClockSkewCheck {
+        ClockSkewCheck::new(
+            Duration::from_secs(self.flashblocks_block_time),
+            Duration::from_millis(self.flashblocks_clock_skew_tolerance_ms),
+        )
+    }
+
+    /// Time after the forkchoice update that started a payload after which it is dropped
+    /// if not taken.
+    pub fn payload_id_ttl(&self) -> Duration {
+        self.flashblocks_payload_id_ttl_ms
+            .map_or_else(|| self.staleness_ttl(), Duration::from_millis)
+    }
 }
@@ -0,0 +1,111 @@
+use std::str::FromStr;
+
+use url::Url;
+
+use super::gossip::GOSSIP_BUILDER;
+use crate::DEFAULT_BUILDER;
+
+/// Flashblocks stream of a builder other than the one at `--flashblocks-builder-url`,
+/// building the same payloads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuilderStream {
+    pub name: String,
+    pub url: Url,
+}
+
+impl FromStr for BuilderStream {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, url) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected name=url, got {s}"))?;
+        if name.is_empty() || name == DEFAULT_BUILDER || name == GOSSIP_BUILDER {
+            return Err(format!(
+                "the name of a builder stream must not be empty, {DEFAULT_BUILDER} or {GOSSIP_BUILDER}"
+            ));
+        }
+        let url = url.parse().map_err(|e| format!("invalid url {url}: {e}"))?;
+        Ok(Self {
+            name: name.to_string(),
+            url,
+        })
+    }
+}
+
+/// Which flashblocks are published and assembled when several builders stream deltas
+/// for the same payload and index. The deltas of a builder build on its previous ones,
+/// so the winner is always a whole stream rather than a single delta.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ConflictResolution {
+    /// The builder that streamed the first flashblock of the payload
+    FirstWins,
+    /// The builder whose deltas used the most gas at the conflicting index, as deltas do
+    /// not carry the value of the block
+    HighestGas,
+    /// The builder at `--flashblocks-builder-url`, the other builders are only used
+    /// while it streams nothing for the payload
+    #[default]
+    PinnedPrimary,
+}
+
+impl ConflictResolution {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConflictResolution::FirstWins => "first_wins",
+            ConflictResolution::HighestGas => "highest_gas",
+            ConflictResolution::PinnedPrimary => "pinned_primary",
+        }
+    }
+
+    /// Whether the stream of `challenger` replaces the one of `incumbent`, given the
+    /// cumulative gas used by both at the conflicting index.
+    pub fn prefers(
+        &self,
+        challenger: &str,
+        challenger_gas: u64,
+        incumbent: &str,
+        incumbent_gas: u64,
+    ) -> bool {
+        match self {
+            ConflictResolution::FirstWins => false,
+            ConflictResolution::HighestGas => challenger_gas > incumbent_gas,
+            ConflictResolution::PinnedPrimary => {
+                challenger == DEFAULT_BUILDER && incumbent != DEFAULT_BUILDER
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_stream() {
+        let stream: BuilderStream = "backup=ws://127.0.0.1:1113".parse().unwrap();
+        assert_eq!(stream.name, "backup");
+        assert_eq!(stream.url.as_str(), "ws://127.0.0.1:1113/");
+        assert!("ws://127.0.0.1:1113".parse::<BuilderStream>().is_err());
+        assert!(
+            "default=ws://127.0.0.1:1113"
+                .parse::<BuilderStream>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_conflict_resolution() {
+        let first_wins = ConflictResolution::FirstWins;
+        assert!(!first_wins.prefers("backup", 2, DEFAULT_BUILDER, 1));
+
+        let highest_gas = ConflictResolution::HighestGas;
+        assert!(highest_gas.prefers("backup", 2, DEFAULT_BUILDER, 1));
+        assert!(!highest_gas.prefers("backup", 1, DEFAULT_BUILDER, 1));
+
+        let pinned_primary = ConflictResolution::PinnedPrimary;
+        assert!(pinned_primary.prefers(DEFAULT_BUILDER, 1, "backup", 2));
+        assert!(!pinned_primary.prefers("backup", 2, DEFAULT_BUILDER, 1));
+        assert!(!pinned_primary.prefers("other", 2, "backup", 1));
+    }
+}
@@ -0,0 +1,275 @@
+use alloy_consensus::Transaction;
+use alloy_consensus::transaction::SignerRecoverable;
+use alloy_eips::Decodable2718;
+use alloy_primitives::{Address, B256, Bytes, Selector};
+use metrics::counter;
+use op_alloy_consensus::OpTxEnvelope;
+use parking_lot::Mutex;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    sync::Arc,
+};
+use thiserror::Error;
+use tracing::error;
+
+/// Number of recovered transaction senders cached between the flashblocks ingest and
+/// the payload checks.
+const MAX_CACHED_SENDERS: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum DeniedTransaction {
+    #[error("transaction {tx_hash} interacts with denied address {address}")]
+    Address { tx_hash: B256, address: Address },
+    #[error("transaction {tx_hash} calls denied selector {selector}")]
+    Selector { tx_hash: B256, selector: Selector },
+    #[error("transaction at index {index} could not be decoded")]
+    Undecodable { index: usize },
+    #[error("transaction at index {index} rejected by filter {filter}")]
+    Filtered { index: usize, filter: &'static str },
+    #[error("transactions could not be checked: {0}")]
+    Unchecked(String),
+}
+
+impl DeniedTransaction {
+    fn reason(&self) -> &'static str {
+        match self {
+            DeniedTransaction::Address { .. } => "address",
+            DeniedTransaction::Selector { .. } => "selector",
+            DeniedTransaction::Undecodable { .. } => "undecodable",
+            DeniedTransaction::Filtered { .. } => "filtered",
+            DeniedTransaction::Unchecked(_) => "unchecked",
+        }
+    }
+}
+
+/// Custom policy rejecting transactions of the payloads assembled from flashblocks,
+/// checked after the denylist.
+pub trait TransactionFilter: Debug + Send + Sync {
+    /// Name of the filter, used in the metrics and logs.
+    fn name(&self) -> &'static str;
+
+    /// Returns the index of the first encoded transaction of a payload that must not be
+    /// included in it, if any. Called once per payload, on the blocking thread pool.
+    fn first_rejected(&self, transactions: &[Bytes]) -> Option<usize>;
+}
+
+/// Addresses and function selectors that must not be included in a payload assembled
+/// from flashblocks.
+///
+/// A transaction is denied if its sender or recipient is a denied address, or if its
+/// calldata starts with a denied selector. Recovering the senders is the expensive part
+/// of the check, so they are recovered as the flashblocks are received and cached by
+/// transaction hash.
+#[derive(Debug, Clone, Default)]
+pub struct TransactionDenylist {
+    addresses: HashSet<Address>,
+    selectors: HashSet<Selector>,
+    filter: Option<Arc<dyn TransactionFilter>>,
+    senders: Arc<Mutex<HashMap<B256, Address>>>,
+}
+
+impl TransactionDenylist {
+    pub fn new(
+        addresses: impl IntoIterator<Item = Address>,
+        selectors: impl IntoIterator<Item = Selector>,
+    ) -> Option<Self> {
+        let denylist = Self {
+            addresses: addresses.into_iter().collect(),
+            selectors: selectors.into_iter().collect(),
+            filter: None,
+            senders: Arc::default(),
+        };
+        (!denylist.is_empty()).then_some(denylist)
+    }
+
+    /// Also rejects the transactions not allowed by `filter`.
+    pub fn with_filter(mut self, filter: Option<Arc<dyn TransactionFilter>>) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty() && self.selectors.is_empty() && self.filter.is_none()
+    }
+
+    /// Checks the encoded transactions of a payload, recording an alert for the first
+    /// denied transaction found.
+    pub fn check(&self, transactions: &[Bytes]) -> Result<(), DeniedTransaction> {
+        let result = transactions
+            .iter()
+            .enumerate()
+            .try_for_each(|(index, tx)| self.check_transaction(index, tx))
+            .and_then(|()| self.check_filter(transactions));
+        if let Err(denied) = &result {
+            record_denied(denied);
+        }
+        result
+    }
+
+    /// Checks the transactions on the blocking thread pool, so that a slow filter does
+    /// not stall the runtime.
+    pub async fn check_blocking(
+        self: &Arc<Self>,
+        transactions: Vec<Bytes>,
+    ) -> Result<(), DeniedTransaction> {
+        let denylist = self.clone();
+        tokio::task::spawn_blocking(move || denylist.check(&transactions))
+            .await
+            .unwrap_or_else(|e| {
+                // Fail closed on transactions that could not be checked
+                let denied = DeniedTransaction::Unchecked(e.to_string());
+                record_denied(&denied);
+                Err(denied)
+            })
+    }
+
+    /// Recovers and caches the senders of the transactions, ahead of the payload checks.
+    pub fn recover_senders(&self, transactions: &[Bytes]) {
+        if self.addresses.is_empty() {
+            return;
+        }
+        for encoded in transactions {
+            if let Ok(tx) = OpTxEnvelope::decode_2718(&mut encoded.as_ref()) {
+                let _ = self.sender(&tx);
+            }
+        }
+    }
+
+    fn sender(&self, tx: &OpTxEnvelope) -> Option<Address> {
+        let tx_hash = tx.tx_hash();
+        if let Some(sender) = self.senders.lock().get(&tx_hash) {
+            return Some(*sender);
+        }
+        let sender = tx.recover_signer().ok()?;
+        let mut senders = self.senders.lock();
+        if senders.len() >= MAX_CACHED_SENDERS {
+            senders.clear();
+        }
+        senders.insert(tx_hash, sender);
+        Some(sender)
+    }
+
+    fn check_filter(&self, transactions: &[Bytes]) -> Result<(), DeniedTransaction> {
+        match &self.filter {
+            Some(filter) => match filter.first_rejected(transactions) {
+                Some(index) => Err(DeniedTransaction::Filtered {
+                    index,
+                    filter: filter.name(),
+                }),
+                None => Ok(()),
+            },
+            None => Ok(()),
+        }
+    }
+
+    fn check_transaction(&self, index: usize, encoded: &Bytes) -> Result<(), DeniedTransaction> {
+        // Fail closed on transactions that cannot be inspected
+        let tx = OpTxEnvelope::decode_2718(&mut encoded.as_ref())
+            .map_err(|_| DeniedTransaction::Undecodable { index })?;
+        let tx_hash = tx.tx_hash();
+
+        if !self.addresses.is_empty() {
+            let sender = self
+                .sender(&tx)
+                .ok_or(DeniedTransaction::Undecodable { index })?;
+            if let Some(address) = [Some(sender), tx.to()]
+                .into_iter()
+                .flatten()
+                .find(|address| self.addresses.contains(address))
+            {
+                return Err(DeniedTransaction::Address { tx_hash, address });
+            }
+        }
+
+        if let Some(selector) = tx
+            .function_selector()
+            .filter(|selector| self.selectors.contains(*selector))
+        {
+            return Err(DeniedTransaction::Selector {
+                tx_hash,
+                selector: *selector,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+fn record_denied(denied: &DeniedTransaction) {
+    counter!("flashblocks.denied_transactions", "reason" => denied.reason()).increment(1);
+    error!(message = "denied transaction in flashblocks payload", %denied);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_consensus::Sealable;
+    use alloy_eips::Encodable2718;
+    use alloy_primitives::{TxKind, address, bytes};
+    use op_alloy_consensus::TxDeposit;
+
+    fn deposit(from: Address, to: Address, input: Bytes) -> Bytes {
+        let tx = OpTxEnvelope::Deposit(
+            TxDeposit {
+                from,
+                to: TxKind::Call(to),
+                input,
+                ..Default::default()
+            }
+            .seal_slow(),
+        );
+        tx.encoded_2718().into()
+    }
+
+    #[test]
+    fn test_denylist() {
+        let denied = address!("0x00000000000000000000000000000000000000aa");
+        let allowed = address!("0x00000000000000000000000000000000000000bb");
+        let denylist =
+            TransactionDenylist::new([denied], [Selector::from([0xa9, 0x05, 0x9c, 0xbb])]).unwrap();
+
+        assert_eq!(
+            denylist.check(&[deposit(allowed, allowed, bytes!("0x095ea7b3"))]),
+            Ok(())
+        );
+        assert!(matches!(
+            denylist.check(&[deposit(denied, allowed, Bytes::new())]),
+            Err(DeniedTransaction::Address { address, .. }) if address == denied
+        ));
+        assert!(matches!(
+            denylist.check(&[deposit(allowed, denied, Bytes::new())]),
+            Err(DeniedTransaction::Address { address, .. }) if address == denied
+        ));
+        assert!(matches!(
+            denylist.check(&[deposit(allowed, allowed, bytes!("0xa9059cbb0000"))]),
+            Err(DeniedTransaction::Selector { .. })
+        ));
+        assert_eq!(
+            denylist.check(&[Bytes::from_static(&[0x7f])]),
+            Err(DeniedTransaction::Undecodable { index: 0 })
+        );
+        assert!(TransactionDenylist::new([], []).is_none());
+    }
+
+    #[test]
+    fn test_recover_senders() {
+        let denied = address!("0x00000000000000000000000000000000000000aa");
+        let allowed = address!("0x00000000000000000000000000000000000000bb");
+        let denylist = TransactionDenylist::new([denied], []).unwrap();
+
+        let transactions = [
+            deposit(allowed, allowed, Bytes::new()),
+            deposit(denied, allowed, Bytes::new()),
+            Bytes::from_static(&[0x7f]),
+        ];
+        denylist.recover_senders(&transactions);
+        assert_eq!(denylist.senders.lock().len(), 2);
+
+        // The cached senders are used by the payload check
+        assert!(matches!(
+            denylist.check(&transactions),
+            Err(DeniedTransaction::Address { address, .. }) if address == denied
+        ));
+    }
+}
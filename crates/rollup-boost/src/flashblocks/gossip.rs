@@ -0,0 +1,274 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use metrics::counter;
+use parking_lot::Mutex;
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use super::primitives::FlashblocksPayloadV1;
+use crate::RpcClient;
+
+/// Name of the builder stream of the flashblocks received over gossip.
+pub const GOSSIP_BUILDER: &str = "gossip";
+
+#[derive(Parser, Clone, Debug)]
+pub struct GossipArgs {
+    /// Publish the flashblocks on a libp2p gossipsub network, and receive the flashblocks
+    /// of its peers as the `gossip` builder stream. Requires the `p2p` feature
+    #[arg(long, env, default_value = "false")]
+    pub flashblocks_gossip: bool,
+
+    /// Multiaddress the gossip network is listened on
+    #[arg(long, env, default_value = "/ip4/0.0.0.0/tcp/9222")]
+    pub flashblocks_gossip_listen_addr: String,
+
+    /// Multiaddresses of the gossip peers to dial on startup, comma separated
+    #[arg(long, env, value_delimiter = ',')]
+    pub flashblocks_gossip_peers: Vec<String>,
+
+    /// File holding the ed25519 key the gossiped messages are signed with, generated if
+    /// it does not exist. A new key is used on every start if not set
+    #[arg(long, env)]
+    pub flashblocks_gossip_key_path: Option<PathBuf>,
+
+    /// Peer IDs whose messages are accepted, comma separated. Any validly signed message
+    /// is accepted if not set
+    #[arg(long, env, value_delimiter = ',')]
+    pub flashblocks_gossip_trusted_peers: Vec<String>,
+
+    /// Chain ID of the gossip topic, that of the l2 client if not set
+    #[arg(long, env)]
+    pub flashblocks_gossip_chain_id: Option<u64>,
+}
+
+/// Gossip topic the flashblocks of `chain_id` are published on, so that the peers of
+/// several chains can share a network.
+pub fn gossip_topic(chain_id: u64) -> String {
+    format!("/rollup-boost/flashblocks/{chain_id}")
+}
+
+/// Publishes the flashblocks on a libp2p gossipsub network, so that they propagate
+/// between regions without a central WebSocket endpoint, and receives the flashblocks
+/// published by the peers. The messages are signed with the key of the node, and
+/// messages with an invalid signature are rejected.
+#[derive(Debug)]
+pub struct FlashblocksGossip {
+    outbound: mpsc::Sender<Vec<u8>>,
+    // Flashblocks received from the peers, taken by the flashblocks launcher
+    inbound: Mutex<Option<mpsc::Receiver<FlashblocksPayloadV1>>>,
+}
+
+impl FlashblocksGossip {
+    /// Joins the gossip network if it is enabled, on the topic of the chain of the l2
+    /// client unless the chain ID is set.
+    pub async fn from_args(args: &GossipArgs, l2_client: &RpcClient) -> eyre::Result<Option<Self>> {
+        if !args.flashblocks_gossip {
+            return Ok(None);
+        }
+        let chain_id = match args.flashblocks_gossip_chain_id {
+            Some(chain_id) => chain_id,
+            None => l2_client.chain_id().await?,
+        };
+        Self::spawn(args, gossip_topic(chain_id)).map(Some)
+    }
+
+    /// Joins the gossip network on `topic`. Must be called from a tokio runtime.
+    pub fn spawn(args: &GossipArgs, topic: String) -> eyre::Result<Self> {
+        #[cfg(feature = "p2p")]
+        {
+            p2p::spawn(args, &topic)
+        }
+
+        #[cfg(not(feature = "p2p"))]
+        {
+            let _ = args;
+            eyre::bail!(
+                "cannot gossip the flashblocks on {topic}, rollup-boost was built without the p2p feature"
+            )
+        }
+    }
+
+    /// Queues a serialized message to be gossiped, dropping it if the queue is full.
+    pub fn publish(&self, message: &str) {
+        match self.outbound.try_send(message.as_bytes().to_vec()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                counter!("flashblocks.gossip_dropped").increment(1);
+            }
+            Err(TrySendError::Closed(_)) => {}
+        }
+    }
+
+    /// The flashblocks received from the peers. Returns `None` once taken.
+    pub(crate) fn take_inbound(&self) -> Option<mpsc::Receiver<FlashblocksPayloadV1>> {
+        self.inbound.lock().take()
+    }
+}
+
+#[cfg(feature = "p2p")]
+mod p2p {
+    use std::{collections::HashSet, fs, io, path::Path};
+
+    use alloy_primitives::keccak256;
+    use libp2p::{
+        Multiaddr, PeerId, Swarm, SwarmBuilder, futures::StreamExt, gossipsub, identity::Keypair,
+        noise, swarm::SwarmEvent, tcp, yamux,
+    };
+    use metrics::counter;
+    use tokio::sync::mpsc;
+    use tracing::{debug, info, warn};
+
+    use parking_lot::Mutex;
+
+    use super::{FlashblocksGossip, FlashblocksPayloadV1, GossipArgs};
+    use crate::spawn_flashblocks_task;
+
+    /// Number of messages waiting to be gossiped, or to be accumulated once received,
+    /// before new ones are dropped.
+    const QUEUE_SIZE: usize = 1024;
+
+    /// Largest message gossiped, as flashblocks can carry many transactions.
+    const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+    pub(super) fn spawn(args: &GossipArgs, topic: &str) -> eyre::Result<FlashblocksGossip> {
+        let keypair = load_keypair(args.flashblocks_gossip_key_path.as_deref())?;
+        let peer_id = keypair.public().to_peer_id();
+        let trusted = args
+            .flashblocks_gossip_trusted_peers
+            .iter()
+            .map(|peer| peer.parse::<PeerId>())
+            .collect::<Result<HashSet<_>, _>>()?;
+
+        // Identical flashblocks republished by several peers are only delivered once
+        let config = gossipsub::ConfigBuilder::default()
+            .validation_mode(gossipsub::ValidationMode::Strict)
+            .message_id_fn(|message: &gossipsub::Message| {
+                gossipsub::MessageId::from(keccak256(&message.data).to_vec())
+            })
+            .max_transmit_size(MAX_MESSAGE_SIZE)
+            .build()
+            .map_err(|e| eyre::eyre!("invalid gossipsub config: {e}"))?;
+        let mut behaviour = gossipsub::Behaviour::new(
+            gossipsub::MessageAuthenticity::Signed(keypair.clone()),
+            config,
+        )
+        .map_err(|e| eyre::eyre!("invalid gossipsub behaviour: {e}"))?;
+        let topic = gossipsub::IdentTopic::new(topic);
+        behaviour.subscribe(&topic)?;
+
+        let mut swarm = SwarmBuilder::with_existing_identity(keypair)
+            .with_tokio()
+            .with_tcp(
+                tcp::Config::default(),
+                noise::Config::new,
+                yamux::Config::default,
+            )?
+            .with_behaviour(|_| behaviour)?
+            .build();
+        swarm.listen_on(args.flashblocks_gossip_listen_addr.parse::<Multiaddr>()?)?;
+        for peer in &args.flashblocks_gossip_peers {
+            swarm.dial(peer.parse::<Multiaddr>()?)?;
+        }
+
+        let (outbound, outbound_rx) = mpsc::channel(QUEUE_SIZE);
+        let (inbound_tx, inbound) = mpsc::channel(QUEUE_SIZE);
+        spawn_flashblocks_task(
+            "flashblocks_gossip",
+            run(swarm, topic.clone(), trusted, outbound_rx, inbound_tx),
+        );
+        info!(message = "Joined the flashblocks gossip network", %peer_id, topic = %topic);
+        Ok(FlashblocksGossip {
+            outbound,
+            inbound: Mutex::new(Some(inbound)),
+        })
+    }
+
+    /// The key stored at `path`, generating and storing one if the file does not exist.
+    fn load_keypair(path: Option<&Path>) -> eyre::Result<Keypair> {
+        let Some(path) = path else {
+            return Ok(Keypair::generate_ed25519());
+        };
+        match fs::read(path) {
+            Ok(encoded) => Ok(Keypair::from_protobuf_encoding(&encoded)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let keypair = Keypair::generate_ed25519();
+                fs::write(path, keypair.to_protobuf_encoding()?)?;
+                info!(message = "Generated the flashblocks gossip key", path = %path.display());
+                Ok(keypair)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn run(
+        mut swarm: Swarm<gossipsub::Behaviour>,
+        topic: gossipsub::IdentTopic,
+        trusted: HashSet<PeerId>,
+        mut outbound: mpsc::Receiver<Vec<u8>>,
+        inbound: mpsc::Sender<FlashblocksPayloadV1>,
+    ) {
+        loop {
+            tokio::select! {
+                Some(message) = outbound.recv() => {
+                    match swarm.behaviour_mut().publish(topic.clone(), message) {
+                        Ok(_) => counter!("flashblocks.gossip_published").increment(1),
+                        // Already gossiped by a peer
+                        Err(gossipsub::PublishError::Duplicate) => {}
+                        Err(e) => {
+                            counter!("flashblocks.gossip_publish_failed").increment(1);
+                            debug!(message = "Failed to gossip flashblocks message", error = %e);
+                        }
+                    }
+                }
+                event = swarm.select_next_some() => match event {
+                    SwarmEvent::Behaviour(gossipsub::Event::Message { message, .. }) => {
+                        let outcome = on_message(&trusted, &inbound, message);
+                        counter!("flashblocks.gossip_received", "outcome" => outcome).increment(1);
+                    }
+                    SwarmEvent::NewListenAddr { address, .. } => {
+                        info!(message = "Listening for flashblocks gossip", %address);
+                    }
+                    SwarmEvent::OutgoingConnectionError { peer_id, error, .. } => {
+                        warn!(message = "Failed to connect to gossip peer", ?peer_id, %error);
+                    }
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    /// Queues the flashblock of a gossiped message to be accumulated, returning the
+    /// outcome it is counted with.
+    fn on_message(
+        trusted: &HashSet<PeerId>,
+        inbound: &mpsc::Sender<FlashblocksPayloadV1>,
+        message: gossipsub::Message,
+    ) -> &'static str {
+        // The signature of the message was checked against its source
+        let trusted_source = message
+            .source
+            .is_some_and(|source| trusted.is_empty() || trusted.contains(&source));
+        if !trusted_source {
+            return "untrusted";
+        }
+        // Control messages, such as reorgs, are not flashblocks
+        let Ok(payload) = serde_json::from_slice::<FlashblocksPayloadV1>(&message.data) else {
+            return "ignored";
+        };
+        match inbound.try_send(payload) {
+            Ok(()) => "accepted",
+            Err(_) => "dropped",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gossip_topic() {
+        assert_eq!(gossip_topic(8453), "/rollup-boost/flashblocks/8453");
+        assert_ne!(gossip_topic(8453), gossip_topic(84532));
+    }
+}
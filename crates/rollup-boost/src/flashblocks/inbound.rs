@@ -1,24 +1,82 @@
+use super::IngestOverflowPolicy;
 use super::primitives::FlashblocksPayloadV1;
+use super::service::{AtomicPayloadId, BuilderFlashblock};
+use crate::{DEFAULT_BUILDER, WebhookEvent, WebhookNotifier};
 use futures::StreamExt;
-use tokio::sync::mpsc;
+use metrics::{counter, gauge};
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+use tokio::sync::mpsc::{self, error::TrySendError};
 use tokio_tungstenite::{connect_async, tungstenite::Message};
-use tracing::{error, info};
+use tracing::{debug, error, info, warn};
 use url::Url;
 
+/// Scheme of the urls of the builders streaming their flashblocks over QUIC.
+pub const QUIC_SCHEME: &str = "quic";
+
 pub struct FlashblocksReceiverService {
     url: Url,
-    sender: mpsc::Sender<FlashblocksPayloadV1>,
+    // Name of the builder streaming the flashblocks
+    builder: Arc<str>,
+    sender: mpsc::Sender<BuilderFlashblock>,
+    overflow: IngestOverflowPolicy,
+    webhooks: Option<Arc<WebhookNotifier>>,
+    // Flashblocks of other payloads are dropped before being queued
+    current_payload_id: Option<Arc<AtomicPayloadId>>,
+    // Whether the stream is connected, so that its loss is only notified once
+    connected: AtomicBool,
 }
 
 impl FlashblocksReceiverService {
-    pub fn new(url: Url, sender: mpsc::Sender<FlashblocksPayloadV1>) -> Self {
-        Self { url, sender }
+    pub fn new(url: Url, sender: mpsc::Sender<BuilderFlashblock>) -> Self {
+        Self {
+            url,
+            builder: DEFAULT_BUILDER.into(),
+            sender,
+            overflow: IngestOverflowPolicy::default(),
+            webhooks: None,
+            current_payload_id: None,
+            connected: AtomicBool::new(true),
+        }
+    }
+
+    pub fn with_builder(mut self, builder: &str) -> Self {
+        self.builder = builder.into();
+        self
+    }
+
+    pub fn with_webhooks(mut self, webhooks: Option<Arc<WebhookNotifier>>) -> Self {
+        self.webhooks = webhooks;
+        self
+    }
+
+    pub fn with_overflow(mut self, overflow: IngestOverflowPolicy) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    pub(crate) fn with_current_payload_id(
+        mut self,
+        current_payload_id: Arc<AtomicPayloadId>,
+    ) -> Self {
+        self.current_payload_id = Some(current_payload_id);
+        self
     }
 
     pub async fn run(self) {
         loop {
             if let Err(e) = self.connect_and_handle().await {
                 error!("Flashblocks receiver connection error, retrying in 5 seconds: {e}");
+                let was_connected = self.connected.swap(false, Ordering::Relaxed);
+                if let Some(webhooks) = self.webhooks.as_ref().filter(|_| was_connected) {
+                    webhooks.notify(
+                        WebhookEvent::FlashblocksStreamLost,
+                        format!("lost the flashblocks stream of the builder: {e}"),
+                        serde_json::json!({ "url": self.url, "builder": self.builder }),
+                    );
+                }
                 tokio::time::sleep(std::time::Duration::from_secs(5)).await;
             } else {
                 break;
@@ -27,19 +85,335 @@ impl FlashblocksReceiverService {
     }
 
     async fn connect_and_handle(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self.url.scheme() == QUIC_SCHEME {
+            return self.connect_and_handle_quic().await;
+        }
+
         let (ws_stream, _) = connect_async(self.url.as_str()).await?;
         let (_, mut read) = ws_stream.split();
 
-        info!("Connected to Flashblocks receiver at {}", self.url);
+        info!(
+            "Connected to Flashblocks receiver of {} at {}",
+            self.builder, self.url
+        );
+        self.connected.store(true, Ordering::Relaxed);
 
         while let Some(msg) = read.next().await {
             if let Message::Text(text) = msg? {
-                if let Ok(flashblocks_msg) = serde_json::from_str::<FlashblocksPayloadV1>(&text) {
-                    self.sender.send(flashblocks_msg).await?;
+                self.on_message(&text).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Receives the flashblocks over a raw QUIC connection, not HTTP/3.
+    #[cfg(feature = "quic")]
+    async fn connect_and_handle_quic(
+        &self,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let connection = quic::connect(&self.url).await?;
+
+        info!(
+            "Connected to Flashblocks receiver of {} at {} over QUIC",
+            self.builder, self.url
+        );
+        self.connected.store(true, Ordering::Relaxed);
+
+        self.handle_quic(connection).await
+    }
+
+    /// Reads each message on its own unidirectional stream opened by the builder. The
+    /// streams are read concurrently, so that a lost packet only delays the message it
+    /// carries, and messages are queued in the order they complete, out of order
+    /// flashblocks being rejected by the index validation.
+    #[cfg(feature = "quic")]
+    async fn handle_quic(
+        &self,
+        connection: quinn::Connection,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut reads = futures::stream::FuturesUnordered::new();
+        loop {
+            tokio::select! {
+                stream = connection.accept_uni() => {
+                    let mut stream = stream?;
+                    reads.push(async move { stream.read_to_end(quic::MAX_MESSAGE_SIZE).await });
+                }
+                Some(message) = reads.next() => {
+                    self.on_message(std::str::from_utf8(&message?)?).await?;
                 }
             }
         }
+    }
+
+    #[cfg(not(feature = "quic"))]
+    async fn connect_and_handle_quic(
+        &self,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Err(format!(
+            "cannot receive the flashblocks at {} over QUIC, rollup-boost was built without the quic feature",
+            self.url
+        )
+        .into())
+    }
 
+    /// Queues the flashblock of a message of the builder, if it is one for the current
+    /// payload.
+    async fn on_message(&self, text: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let Ok(flashblocks_msg) = serde_json::from_str::<FlashblocksPayloadV1>(text) else {
+            return Ok(());
+        };
+        if self
+            .current_payload_id
+            .as_ref()
+            .is_some_and(|current| current.load() != flashblocks_msg.payload_id)
+        {
+            debug!(message = "Flashblock for another payload, dropping", payload_id = %flashblocks_msg.payload_id);
+            counter!("flashblocks.payload_id_mismatch").increment(1);
+            return Ok(());
+        }
+        self.enqueue(flashblocks_msg).await
+    }
+
+    /// Queues a flashblock to be accumulated, applying the overflow policy if the queue
+    /// is full.
+    async fn enqueue(
+        &self,
+        payload: FlashblocksPayloadV1,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let payload = BuilderFlashblock {
+            builder: self.builder.clone(),
+            payload,
+        };
+        if self.sender.capacity() == 0 {
+            counter!("flashblocks.ingest_full").increment(1);
+        }
+
+        match self.overflow {
+            IngestOverflowPolicy::Block => self.sender.send(payload).await?,
+            IngestOverflowPolicy::Drop => match self.sender.try_send(payload) {
+                Ok(()) => {}
+                Err(TrySendError::Full(BuilderFlashblock { builder, payload })) => {
+                    counter!("flashblocks.ingest_dropped").increment(1);
+                    warn!(
+                        message = "Flashblocks ingest queue is full, dropping flashblock",
+                        %builder,
+                        payload_id = %payload.payload_id,
+                        index = payload.index
+                    );
+                }
+                Err(e @ TrySendError::Closed(_)) => return Err(e.into()),
+            },
+        }
+
+        gauge!("flashblocks.ingest_queued")
+            .set((self.sender.max_capacity() - self.sender.capacity()) as f64);
         Ok(())
     }
 }
+
+#[cfg(feature = "quic")]
+mod quic {
+    use std::{error::Error, net::SocketAddr, sync::Arc};
+
+    use quinn::{ClientConfig, Connection, Endpoint, crypto::rustls::QuicClientConfig};
+    use rustls::RootCertStore;
+    use url::Url;
+
+    /// Protocol negotiated with the builder over QUIC.
+    pub(super) const ALPN: &[u8] = b"flashblocks";
+
+    /// Port of the QUIC url of a builder if it has none.
+    const DEFAULT_PORT: u16 = 443;
+
+    /// Largest message received from the builder, as flashblocks can carry many
+    /// transactions.
+    pub(super) const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+    /// Connects to the builder at `url`, verifying its certificate against the native
+    /// roots.
+    pub(super) async fn connect(url: &Url) -> Result<Connection, Box<dyn Error + Send + Sync>> {
+        let mut roots = RootCertStore::empty();
+        roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+        connect_with_roots(url, roots).await
+    }
+
+    /// Connects to the builder at `url`, verifying its certificate against `roots`.
+    pub(super) async fn connect_with_roots(
+        url: &Url,
+        roots: RootCertStore,
+    ) -> Result<Connection, Box<dyn Error + Send + Sync>> {
+        let host = url
+            .host_str()
+            .ok_or("the QUIC url of the builder has no host")?;
+        let addr = tokio::net::lookup_host((host, url.port().unwrap_or(DEFAULT_PORT)))
+            .await?
+            .next()
+            .ok_or_else(|| format!("cannot resolve {host}"))?;
+
+        let mut crypto = rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        crypto.alpn_protocols = vec![ALPN.to_vec()];
+
+        let bind_addr: SocketAddr = if addr.is_ipv6() {
+            "[::]:0".parse()?
+        } else {
+            "0.0.0.0:0".parse()?
+        };
+        let mut endpoint = Endpoint::client(bind_addr)?;
+        endpoint.set_default_client_config(ClientConfig::new(Arc::new(
+            QuicClientConfig::try_from(crypto)?,
+        )));
+        Ok(endpoint.connect(addr, host)?.await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_rpc_types_engine::PayloadId;
+    use std::time::Duration;
+
+    fn flashblock(payload_id: PayloadId, index: u64) -> FlashblocksPayloadV1 {
+        FlashblocksPayloadV1 {
+            payload_id,
+            index,
+            ..Default::default()
+        }
+    }
+
+    fn new_receiver(sender: mpsc::Sender<BuilderFlashblock>) -> FlashblocksReceiverService {
+        FlashblocksReceiverService::new(Url::parse("ws://127.0.0.1:1111").unwrap(), sender)
+    }
+
+    async fn recv(rx: &mut mpsc::Receiver<BuilderFlashblock>) -> BuilderFlashblock {
+        tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_drop_flashblocks_of_other_payloads() {
+        let (tx, mut rx) = mpsc::channel(16);
+        let current_payload_id = Arc::new(AtomicPayloadId::default());
+        current_payload_id.store(PayloadId::new([1; 8]));
+        let receiver = new_receiver(tx).with_current_payload_id(current_payload_id.clone());
+
+        let message = |payload_id, index| serde_json::to_string(&flashblock(payload_id, index));
+        receiver
+            .on_message(&message(PayloadId::new([2; 8]), 0).unwrap())
+            .await
+            .unwrap();
+        receiver
+            .on_message(&message(PayloadId::new([1; 8]), 1).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(recv(&mut rx).await.payload.index, 1);
+        assert!(rx.try_recv().is_err());
+
+        // The payload ID set by the flashblocks service is read on the next message
+        assert_eq!(current_payload_id.take(), PayloadId::new([1; 8]));
+        receiver
+            .on_message(&message(PayloadId::new([1; 8]), 2).unwrap())
+            .await
+            .unwrap();
+        assert!(rx.try_recv().is_err());
+
+        // Messages that are not flashblocks are skipped
+        receiver.on_message("not a flashblock").await.unwrap();
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ingest_overflow() {
+        let payload_id = PayloadId::default();
+
+        // A full queue drops the flashblocks with the drop policy
+        let (tx, mut rx) = mpsc::channel(1);
+        let receiver = new_receiver(tx).with_overflow(IngestOverflowPolicy::Drop);
+        receiver.enqueue(flashblock(payload_id, 0)).await.unwrap();
+        receiver.enqueue(flashblock(payload_id, 1)).await.unwrap();
+        assert_eq!(recv(&mut rx).await.payload.index, 0);
+        assert!(rx.try_recv().is_err());
+
+        // and holds up the stream with the block policy, until the queue has room
+        let (tx, mut rx) = mpsc::channel(1);
+        let receiver = new_receiver(tx).with_overflow(IngestOverflowPolicy::Block);
+        receiver.enqueue(flashblock(payload_id, 0)).await.unwrap();
+        let blocked = receiver.enqueue(flashblock(payload_id, 1));
+        tokio::pin!(blocked);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), &mut blocked)
+                .await
+                .is_err()
+        );
+        assert_eq!(recv(&mut rx).await.payload.index, 0);
+        blocked.await.unwrap();
+        assert_eq!(recv(&mut rx).await.payload.index, 1);
+
+        // A closed queue fails the stream, whatever the policy
+        drop(rx);
+        assert!(receiver.enqueue(flashblock(payload_id, 2)).await.is_err());
+    }
+
+    #[cfg(feature = "quic")]
+    fn message(index: u64) -> Vec<u8> {
+        serde_json::to_vec(&flashblock(PayloadId::default(), index)).unwrap()
+    }
+
+    #[cfg(feature = "quic")]
+    #[tokio::test]
+    async fn test_quic_streams_read_concurrently() {
+        use quinn::{Endpoint, ServerConfig, crypto::rustls::QuicServerConfig};
+        use rustls::RootCertStore;
+        use rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
+        use std::net::SocketAddr;
+
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
+        let certified = rcgen::generate_simple_self_signed(vec!["127.0.0.1".to_string()]).unwrap();
+        let cert = certified.cert.der().clone();
+        let key =
+            PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der()));
+        let mut crypto = rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert.clone()], key)
+            .unwrap();
+        crypto.alpn_protocols = vec![quic::ALPN.to_vec()];
+        let server_config =
+            ServerConfig::with_crypto(Arc::new(QuicServerConfig::try_from(crypto).unwrap()));
+        let endpoint =
+            Endpoint::server(server_config, "127.0.0.1:0".parse::<SocketAddr>().unwrap()).unwrap();
+        let url = Url::parse(&format!(
+            "quic://127.0.0.1:{}",
+            endpoint.local_addr().unwrap().port()
+        ))
+        .unwrap();
+
+        let mut roots = RootCertStore::empty();
+        roots.add(cert).unwrap();
+        let (client, server) = tokio::join!(quic::connect_with_roots(&url, roots), async {
+            endpoint.accept().await.unwrap().await.unwrap()
+        });
+        let (tx, mut rx) = mpsc::channel(16);
+        let receiver = FlashblocksReceiverService::new(url, tx);
+        tokio::spawn(async move { receiver.handle_quic(client.unwrap()).await });
+
+        // The first message stalls halfway, as if a packet was lost
+        let first = message(0);
+        let mut stalled = server.open_uni().await.unwrap();
+        stalled.write_all(&first[..first.len() / 2]).await.unwrap();
+
+        // The next message is not held up by it
+        let mut stream = server.open_uni().await.unwrap();
+        stream.write_all(&message(1)).await.unwrap();
+        stream.finish().unwrap();
+        assert_eq!(recv(&mut rx).await.payload.index, 1);
+
+        stalled.write_all(&first[first.len() / 2..]).await.unwrap();
+        stalled.finish().unwrap();
+        assert_eq!(recv(&mut rx).await.payload.index, 0);
+    }
+}
@@ -1,30 +1,76 @@
 use crate::flashblocks::inbound::FlashblocksReceiverService;
-use crate::{FlashblocksService, RpcClient};
-use core::net::SocketAddr;
+use crate::{
+    BuilderFlashblock, FlashblocksService, GOSSIP_BUILDER, IngestOverflowPolicy,
+    spawn_flashblocks_task,
+};
 use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
 use url::Url;
 
 pub struct Flashblocks {}
 
 impl Flashblocks {
+    /// Connects `service` to the flashblocks stream of the builder at `flashblocks_url`.
+    /// At most `queue_size` flashblocks wait to be accumulated, the next ones are handled
+    /// according to `overflow`.
     pub fn run(
-        builder_url: RpcClient,
+        service: FlashblocksService,
         flashblocks_url: Url,
-        outbound_addr: SocketAddr,
-    ) -> eyre::Result<FlashblocksService> {
-        let (tx, rx) = mpsc::channel(100);
+        queue_size: usize,
+        overflow: IngestOverflowPolicy,
+    ) -> FlashblocksService {
+        Self::spawn(service, flashblocks_url, queue_size, overflow).0
+    }
+
+    /// Like [`Self::run`], also returning the handles of the receiver and accumulator
+    /// tasks. The streams of the other builders set on `service`, and the flashblocks
+    /// gossiped by its peers, are received alongside.
+    pub fn spawn(
+        service: FlashblocksService,
+        flashblocks_url: Url,
+        queue_size: usize,
+        overflow: IngestOverflowPolicy,
+    ) -> (FlashblocksService, Vec<JoinHandle<()>>) {
+        let (tx, rx) = mpsc::channel(queue_size.max(1));
 
-        let receiver = FlashblocksReceiverService::new(flashblocks_url, tx);
-        tokio::spawn(async move {
+        let receiver = FlashblocksReceiverService::new(flashblocks_url, tx.clone())
+            .with_overflow(overflow)
+            .with_webhooks(service.webhooks())
+            .with_current_payload_id(service.current_payload_id());
+        let mut handles = vec![spawn_flashblocks_task("flashblocks_receiver", async move {
             let _ = receiver.run().await;
-        });
+        })];
+        for stream in service.builder_streams() {
+            let receiver = FlashblocksReceiverService::new(stream.url.clone(), tx.clone())
+                .with_builder(&stream.name)
+                .with_overflow(overflow)
+                .with_webhooks(service.webhooks())
+                .with_current_payload_id(service.current_payload_id());
+            handles.push(spawn_flashblocks_task("flashblocks_receiver", async move {
+                let _ = receiver.run().await;
+            }));
+        }
+        if let Some(mut gossiped) = service.gossip().and_then(|gossip| gossip.take_inbound()) {
+            let tx = tx.clone();
+            handles.push(spawn_flashblocks_task("flashblocks_receiver", async move {
+                while let Some(payload) = gossiped.recv().await {
+                    let flashblock = BuilderFlashblock {
+                        builder: GOSSIP_BUILDER.into(),
+                        payload,
+                    };
+                    if tx.send(flashblock).await.is_err() {
+                        break;
+                    }
+                }
+            }));
+        }
 
-        let service = FlashblocksService::new(builder_url, outbound_addr)?;
-        let mut service_handle = service.clone();
-        tokio::spawn(async move {
+        let service_handle = service.clone();
+        let accumulator = spawn_flashblocks_task("flashblocks_accumulator", async move {
             service_handle.run(rx).await;
         });
 
-        Ok(service)
+        handles.push(accumulator);
+        (service, handles)
     }
 }
@@ -0,0 +1,214 @@
+use std::collections::{HashMap, HashSet};
+
+use alloy_consensus::Transaction;
+use alloy_consensus::transaction::SignerRecoverable;
+use alloy_eips::Decodable2718;
+use alloy_primitives::{Address, B256, Bytes};
+use op_alloy_consensus::OpTxEnvelope;
+
+use super::primitives::ExecutionPayloadFlashblockDeltaV1;
+
+/// Transactions of a block assembled from the deltas of several builders.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MergedTransactions {
+    pub transactions: Vec<Bytes>,
+    /// Number of transactions taken from the deltas of the other builders
+    pub merged: usize,
+    /// Number of builders whose deltas were left out from a conflicting delta onwards
+    pub conflicts: usize,
+}
+
+/// A decoded transaction of a delta.
+struct DecodedTransaction {
+    hash: B256,
+    sender: Address,
+    nonce: u64,
+    deposit: bool,
+}
+
+fn decode(encoded: &Bytes) -> Option<DecodedTransaction> {
+    let tx = OpTxEnvelope::decode_2718(&mut encoded.as_ref()).ok()?;
+    Some(DecodedTransaction {
+        hash: tx.tx_hash(),
+        sender: tx.recover_signer().ok()?,
+        nonce: tx.nonce(),
+        deposit: tx.is_deposit(),
+    })
+}
+
+/// Assembles the transactions of a block from the deltas of the best builder, interleaved
+/// at each index with the deltas of the other builders that do not conflict with them.
+///
+/// The transactions of the best builder are always included. A transaction of another
+/// builder that is already included is skipped, as both builders saw it. A delta of
+/// another builder conflicts if one of its transactions cannot be decoded, is a deposit
+/// that is not already included, reuses the nonce of a sender, or would be ordered before
+/// a transaction of the best builder from the same sender, or if it does not fit in
+/// `gas_limit`. Since its later deltas build on it, the other builder is left out from
+/// its first conflicting delta onwards.
+pub fn merge_deltas(
+    best: &[ExecutionPayloadFlashblockDeltaV1],
+    competing: &[&[ExecutionPayloadFlashblockDeltaV1]],
+    gas_limit: u64,
+) -> MergedTransactions {
+    merge(best, competing, gas_limit, decode)
+}
+
+fn merge(
+    best: &[ExecutionPayloadFlashblockDeltaV1],
+    competing: &[&[ExecutionPayloadFlashblockDeltaV1]],
+    gas_limit: u64,
+    decode: impl Fn(&Bytes) -> Option<DecodedTransaction>,
+) -> MergedTransactions {
+    // Delta index of the last transaction of each sender of the best builder
+    let mut last_index = HashMap::new();
+    let mut hashes = HashSet::new();
+    let mut nonces = HashSet::new();
+    for (index, diff) in best.iter().enumerate() {
+        for tx in diff.transactions.iter().filter_map(&decode) {
+            hashes.insert(tx.hash);
+            if !tx.deposit {
+                nonces.insert((tx.sender, tx.nonce));
+                last_index.insert(tx.sender, index);
+            }
+        }
+    }
+
+    let mut merged = MergedTransactions::default();
+    let mut excluded = vec![false; competing.len()];
+    let mut gas_used = best.last().map_or(0, |diff| diff.gas_used);
+    let len = competing
+        .iter()
+        .map(|deltas| deltas.len())
+        .chain([best.len()])
+        .max()
+        .unwrap_or_default();
+
+    for index in 0..len {
+        if let Some(diff) = best.get(index) {
+            merged
+                .transactions
+                .extend(diff.transactions.iter().cloned());
+        }
+
+        for (deltas, excluded) in competing.iter().zip(excluded.iter_mut()) {
+            let Some(diff) = deltas.get(index).filter(|_| !*excluded) else {
+                continue;
+            };
+            let previous = index
+                .checked_sub(1)
+                .and_then(|index| deltas.get(index))
+                .map_or(0, |diff| diff.gas_used);
+            let delta_gas = diff.gas_used.saturating_sub(previous);
+
+            let mut transactions = vec![];
+            let mut delta_nonces = vec![];
+            let mut conflict = gas_used.saturating_add(delta_gas) > gas_limit;
+            for encoded in &diff.transactions {
+                if conflict {
+                    break;
+                }
+                let Some(tx) = decode(encoded) else {
+                    conflict = true;
+                    break;
+                };
+                if hashes.contains(&tx.hash) {
+                    continue;
+                }
+                conflict = tx.deposit
+                    || nonces.contains(&(tx.sender, tx.nonce))
+                    || delta_nonces.contains(&(tx.sender, tx.nonce))
+                    || last_index.get(&tx.sender).is_some_and(|last| *last > index);
+                delta_nonces.push((tx.sender, tx.nonce));
+                transactions.push((tx.hash, encoded.clone()));
+            }
+
+            if conflict {
+                *excluded = true;
+                merged.conflicts += 1;
+                continue;
+            }
+            gas_used += delta_gas;
+            nonces.extend(delta_nonces);
+            merged.merged += transactions.len();
+            for (hash, encoded) in transactions {
+                hashes.insert(hash);
+                merged.transactions.push(encoded);
+            }
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Transactions of the tests, encoded as their sender then their nonce.
+    fn transaction(sender: u8, nonce: u8) -> Bytes {
+        Bytes::from(vec![sender, nonce])
+    }
+
+    fn decode(encoded: &Bytes) -> Option<DecodedTransaction> {
+        Some(DecodedTransaction {
+            hash: alloy_primitives::keccak256(encoded),
+            sender: Address::with_last_byte(encoded[0]),
+            nonce: encoded[1] as u64,
+            deposit: false,
+        })
+    }
+
+    fn delta(transactions: Vec<Bytes>, gas_used: u64) -> ExecutionPayloadFlashblockDeltaV1 {
+        ExecutionPayloadFlashblockDeltaV1 {
+            transactions,
+            gas_used,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_merge_deltas() {
+        let (alice, bob, carol) = (1, 2, 3);
+        let best = [
+            delta(vec![transaction(alice, 0)], 21_000),
+            delta(vec![transaction(alice, 1)], 42_000),
+            delta(vec![transaction(alice, 2)], 63_000),
+        ];
+
+        // Bob's transactions are interleaved, and the transaction of Alice both builders
+        // saw is only included once
+        let other = [
+            delta(vec![transaction(alice, 0), transaction(bob, 0)], 42_000),
+            delta(vec![transaction(bob, 1)], 63_000),
+        ];
+        let merged = merge(&best, &[&other], 1_000_000, decode);
+        assert_eq!(merged.merged, 2);
+        assert_eq!(merged.conflicts, 0);
+        assert_eq!(
+            merged.transactions,
+            [
+                transaction(alice, 0),
+                transaction(bob, 0),
+                transaction(alice, 1),
+                transaction(bob, 1),
+                transaction(alice, 2),
+            ]
+        );
+
+        // A builder ordering a transaction of Alice before the best builder's is left out
+        // from then on
+        let conflicting = [
+            delta(vec![transaction(carol, 0)], 21_000),
+            delta(vec![transaction(alice, 3), transaction(carol, 1)], 42_000),
+            delta(vec![transaction(carol, 2)], 63_000),
+        ];
+        let merged = merge(&best, &[&conflicting], 1_000_000, decode);
+        assert_eq!(merged.merged, 1);
+        assert_eq!(merged.conflicts, 1);
+
+        // So is a builder whose deltas do not fit in the gas limit
+        let merged = merge(&best, &[&other], 50_000, decode);
+        assert_eq!(merged.merged, 0);
+        assert_eq!(merged.conflicts, 1);
+    }
+}
@@ -10,6 +10,50 @@ pub use service::*;
 
 mod inbound;
 mod outbound;
+pub use outbound::PublisherConfig;
+
+mod quota;
+pub(crate) use outbound::WebSocketPublisher;
+pub use quota::{QuotaExceeded, SubscriberLimits};
+
+mod outbox;
+pub use outbox::{Outbox, OutboxError};
+
+mod subscribers;
+pub use subscribers::{SubscriberInfo, SubscriberRegistry};
+
+mod recent;
+pub use recent::RecentFlashblocks;
+
+mod skew;
+pub use skew::*;
+
+mod conflict;
+pub use conflict::*;
+
+mod merge;
+pub use merge::*;
+
+mod gossip;
+pub use gossip::*;
+
+mod multicast;
+pub use multicast::*;
+
+mod schema;
+pub use schema::*;
+
+#[cfg(feature = "proto")]
+pub mod proto;
 
 mod args;
 pub use args::*;
+
+mod denylist;
+pub use denylist::*;
+
+mod sync;
+pub use sync::*;
+
+mod synthetic;
+pub use synthetic::*;
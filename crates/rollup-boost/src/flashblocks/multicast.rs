@@ -0,0 +1,455 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket},
+};
+
+use metrics::counter;
+use thiserror::Error;
+
+use super::args::FlashblocksArgs;
+
+/// Identifies the packets of the flashblocks multicast protocol, and its version.
+const MAGIC: u16 = 0xFB01;
+
+/// Size of the header of every packet.
+pub const MULTICAST_HEADER_SIZE: usize = 20;
+
+/// Size of the payload of a packet, so that packets fit in a 1500 bytes MTU.
+pub const MULTICAST_FRAGMENT_SIZE: usize = 1200;
+
+/// Number of messages being reassembled by a receiver before the oldest ones are dropped.
+const MAX_PENDING_MESSAGES: usize = 64;
+
+const KIND_DATA: u8 = 0;
+const KIND_PARITY: u8 = 1;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MulticastError {
+    #[error("packet of {0} bytes is too short")]
+    TooShort(usize),
+    #[error("unknown protocol {0:#06x}")]
+    UnknownProtocol(u16),
+    #[error("unknown packet kind {0}")]
+    UnknownKind(u8),
+    #[error("fragment {fragment} out of the {fragments} fragments of the message")]
+    InvalidFragment { fragment: u16, fragments: u16 },
+    #[error("message of {0} bytes does not fit in {max} fragments", max = u16::MAX)]
+    TooLarge(usize),
+    #[error("packet does not match the previous packets of message {0}")]
+    InconsistentPacket(u64),
+}
+
+/// Header of a packet, in network byte order:
+///
+/// | Field       | Size | Description                                                 |
+/// |-------------|------|-------------------------------------------------------------|
+/// | `magic`     | 2    | `0xFB01`                                                    |
+/// | `kind`      | 1    | `0` for a fragment of the message, `1` for a parity packet  |
+/// | `fec_group` | 1    | Number of fragments covered by each parity packet, 0 if none |
+/// | `seq`       | 8    | Sequence number of the message                              |
+/// | `len`       | 4    | Length of the whole message                                 |
+/// | `index`     | 2    | Index of the fragment, or of the group of a parity packet   |
+/// | `fragments` | 2    | Number of fragments of the message                          |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    kind: u8,
+    fec_group: u8,
+    seq: u64,
+    len: u32,
+    index: u16,
+    fragments: u16,
+}
+
+impl Header {
+    fn write(&self, packet: &mut Vec<u8>) {
+        packet.extend_from_slice(&MAGIC.to_be_bytes());
+        packet.push(self.kind);
+        packet.push(self.fec_group);
+        packet.extend_from_slice(&self.seq.to_be_bytes());
+        packet.extend_from_slice(&self.len.to_be_bytes());
+        packet.extend_from_slice(&self.index.to_be_bytes());
+        packet.extend_from_slice(&self.fragments.to_be_bytes());
+    }
+
+    fn read(packet: &[u8]) -> Result<Self, MulticastError> {
+        if packet.len() < MULTICAST_HEADER_SIZE {
+            return Err(MulticastError::TooShort(packet.len()));
+        }
+        let magic = u16::from_be_bytes([packet[0], packet[1]]);
+        if magic != MAGIC {
+            return Err(MulticastError::UnknownProtocol(magic));
+        }
+        let header = Self {
+            kind: packet[2],
+            fec_group: packet[3],
+            seq: u64::from_be_bytes(packet[4..12].try_into().unwrap_or_default()),
+            len: u32::from_be_bytes(packet[12..16].try_into().unwrap_or_default()),
+            index: u16::from_be_bytes([packet[16], packet[17]]),
+            fragments: u16::from_be_bytes([packet[18], packet[19]]),
+        };
+        let groups = match header.fec_group {
+            0 => 0,
+            group => header.fragments.div_ceil(group as u16),
+        };
+        let last = match header.kind {
+            KIND_DATA => header.fragments,
+            KIND_PARITY => groups,
+            kind => return Err(MulticastError::UnknownKind(kind)),
+        };
+        if header.index >= last {
+            return Err(MulticastError::InvalidFragment {
+                fragment: header.index,
+                fragments: header.fragments,
+            });
+        }
+        Ok(header)
+    }
+}
+
+/// Splits the message numbered `seq` into packets of at most
+/// [`MULTICAST_FRAGMENT_SIZE`] bytes of payload. With a non-zero `fec_group`, each group
+/// of `fec_group` fragments is followed by a parity packet, the XOR of the fragments of
+/// the group, from which any single lost fragment of the group is recovered. Fails if
+/// the message needs more fragments than the header can number.
+pub fn encode_multicast(
+    seq: u64,
+    message: &[u8],
+    fec_group: u8,
+) -> Result<Vec<Vec<u8>>, MulticastError> {
+    let chunks = message.chunks(MULTICAST_FRAGMENT_SIZE).collect::<Vec<_>>();
+    let chunks = if chunks.is_empty() {
+        vec![&[][..]]
+    } else {
+        chunks
+    };
+    let fragments =
+        u16::try_from(chunks.len()).map_err(|_| MulticastError::TooLarge(message.len()))?;
+    let header = Header {
+        kind: KIND_DATA,
+        fec_group,
+        seq,
+        len: message.len() as u32,
+        index: 0,
+        fragments,
+    };
+
+    let mut packets = vec![];
+    let mut parity = vec![0u8; MULTICAST_FRAGMENT_SIZE];
+    for (index, chunk) in chunks.iter().enumerate() {
+        let mut packet = Vec::with_capacity(MULTICAST_HEADER_SIZE + chunk.len());
+        Header {
+            index: index as u16,
+            ..header
+        }
+        .write(&mut packet);
+        packet.extend_from_slice(chunk);
+        packets.push(packet);
+
+        if fec_group == 0 {
+            continue;
+        }
+        for (parity, byte) in parity.iter_mut().zip(chunk.iter()) {
+            *parity ^= byte;
+        }
+        if (index + 1) % fec_group as usize == 0 || index + 1 == chunks.len() {
+            let mut packet = Vec::with_capacity(MULTICAST_HEADER_SIZE + parity.len());
+            Header {
+                kind: KIND_PARITY,
+                index: (index / fec_group as usize) as u16,
+                ..header
+            }
+            .write(&mut packet);
+            packet.extend_from_slice(&parity);
+            packets.push(packet);
+            parity.fill(0);
+        }
+    }
+    Ok(packets)
+}
+
+/// A message being reassembled from its packets.
+#[derive(Debug)]
+struct PendingMessage {
+    header: Header,
+    fragments: HashMap<u16, Vec<u8>>,
+    parities: HashMap<u16, Vec<u8>>,
+}
+
+impl PendingMessage {
+    /// Recovers the single missing fragment of `group` from its parity packet, if any.
+    fn recover(&mut self, group: u16) {
+        let fec_group = self.header.fec_group as u16;
+        let Some(parity) = self.parities.get(&group) else {
+            return;
+        };
+        let first = group * fec_group;
+        let last = first.saturating_add(fec_group).min(self.header.fragments);
+        let missing = (first..last)
+            .filter(|index| !self.fragments.contains_key(index))
+            .collect::<Vec<_>>();
+        let [missing] = missing[..] else {
+            return;
+        };
+
+        let mut fragment = parity.clone();
+        for fragment_of_group in (first..last).filter_map(|index| self.fragments.get(&index)) {
+            for (byte, other) in fragment.iter_mut().zip(fragment_of_group) {
+                *byte ^= other;
+            }
+        }
+        // Only the last fragment of the message is shorter than a full fragment
+        let len = if missing + 1 == self.header.fragments {
+            (self.header.len as usize).saturating_sub(missing as usize * MULTICAST_FRAGMENT_SIZE)
+        } else {
+            MULTICAST_FRAGMENT_SIZE
+        };
+        fragment.truncate(len);
+        self.fragments.insert(missing, fragment);
+        counter!("flashblocks.multicast_recovered").increment(1);
+    }
+
+    fn is_complete(&self) -> bool {
+        self.fragments.len() == self.header.fragments as usize
+    }
+
+    fn into_message(self) -> Vec<u8> {
+        let mut fragments = self.fragments.into_iter().collect::<Vec<_>>();
+        fragments.sort_by_key(|(index, _)| *index);
+        fragments.into_iter().flat_map(|(_, data)| data).collect()
+    }
+}
+
+/// Reassembles the messages of the packets received from the multicast group, for the
+/// consumers of the multicast stream. Messages whose lost fragments cannot be recovered
+/// are dropped once newer messages are pending, and the gaps are visible in the sequence
+/// numbers.
+#[derive(Debug, Default)]
+pub struct MulticastAssembler {
+    pending: BTreeMap<u64, PendingMessage>,
+    // Sequence number below which messages were completed or dropped
+    completed_below: u64,
+}
+
+impl MulticastAssembler {
+    /// Processes a packet, returning the sequence number and the content of the message
+    /// it completes, if any.
+    pub fn push(&mut self, packet: &[u8]) -> Result<Option<(u64, Vec<u8>)>, MulticastError> {
+        let header = Header::read(packet)?;
+        if header.seq < self.completed_below && !self.pending.contains_key(&header.seq) {
+            return Ok(None);
+        }
+        let pending = self
+            .pending
+            .entry(header.seq)
+            .or_insert_with(|| PendingMessage {
+                header,
+                fragments: HashMap::new(),
+                parities: HashMap::new(),
+            });
+        // The packets of a message must describe the same message as its first packet
+        if (header.fec_group, header.len, header.fragments)
+            != (
+                pending.header.fec_group,
+                pending.header.len,
+                pending.header.fragments,
+            )
+        {
+            counter!("flashblocks.multicast_inconsistent").increment(1);
+            return Err(MulticastError::InconsistentPacket(header.seq));
+        }
+        let data = packet[MULTICAST_HEADER_SIZE..].to_vec();
+        let group = match header.kind {
+            KIND_DATA => {
+                pending.fragments.insert(header.index, data);
+                header.index / header.fec_group.max(1) as u16
+            }
+            _ => {
+                pending.parities.insert(header.index, data);
+                header.index
+            }
+        };
+        if header.fec_group > 0 {
+            pending.recover(group);
+        }
+
+        if pending.is_complete() {
+            let message = self
+                .pending
+                .remove(&header.seq)
+                .map(PendingMessage::into_message);
+            self.completed_below = self.completed_below.max(header.seq + 1);
+            return Ok(message.map(|message| (header.seq, message)));
+        }
+        while self.pending.len() > MAX_PENDING_MESSAGES {
+            self.pending.pop_first();
+            counter!("flashblocks.multicast_incomplete").increment(1);
+        }
+        Ok(None)
+    }
+}
+
+/// Emits the messages published on the flashblocks websocket to a UDP multicast group,
+/// for consumers on the same LAN to whom the latency jitter of TCP matters. Datagrams are
+/// sent without waiting: they are dropped if the socket buffer is full, and the
+/// consumers detect the loss from the sequence numbers.
+#[derive(Debug)]
+pub struct MulticastEmitter {
+    socket: UdpSocket,
+    group: SocketAddr,
+    fec_group: u8,
+}
+
+impl MulticastEmitter {
+    /// Sends to the multicast `group` from the interface with the address `interface`,
+    /// any interface if unspecified, through at most `ttl` routers.
+    pub fn bind(
+        group: SocketAddrV4,
+        interface: Ipv4Addr,
+        ttl: u32,
+        fec_group: u8,
+    ) -> io::Result<Self> {
+        if !group.ip().is_multicast() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a multicast address", group.ip()),
+            ));
+        }
+        let socket = UdpSocket::bind(SocketAddrV4::new(interface, 0))?;
+        socket.set_multicast_ttl_v4(ttl)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            group: group.into(),
+            fec_group,
+        })
+    }
+
+    pub fn from_args(args: &FlashblocksArgs) -> io::Result<Option<Self>> {
+        args.flashblocks_multicast_addr
+            .map(|group| {
+                Self::bind(
+                    group,
+                    args.flashblocks_multicast_interface,
+                    args.flashblocks_multicast_ttl,
+                    args.flashblocks_multicast_fec_group,
+                )
+            })
+            .transpose()
+    }
+
+    /// Sends the message numbered `seq` to the multicast group.
+    pub fn emit(&self, seq: u64, message: &[u8]) {
+        let packets = match encode_multicast(seq, message, self.fec_group) {
+            Ok(packets) => packets,
+            Err(e) => {
+                counter!("flashblocks.multicast_dropped").increment(1);
+                tracing::warn!(message = "Failed to encode multicast message", seq, error = %e);
+                return;
+            }
+        };
+        for packet in packets {
+            if let Err(e) = self.socket.send_to(&packet, self.group) {
+                counter!("flashblocks.multicast_dropped").increment(1);
+                tracing::debug!(message = "Failed to send multicast packet", seq, error = %e);
+                return;
+            }
+        }
+        counter!("flashblocks.multicast_sent").increment(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multicast_roundtrip() -> Result<(), MulticastError> {
+        let message = (0..3000u32).map(|i| i as u8).collect::<Vec<_>>();
+        let packets = encode_multicast(7, &message, 2)?;
+        // 3 fragments, a parity packet after the 2nd fragment and after the last one
+        assert_eq!(packets.len(), 5);
+
+        let mut assembler = MulticastAssembler::default();
+        let mut received = None;
+        for packet in &packets {
+            received = assembler.push(packet)?.or(received);
+        }
+        assert_eq!(received, Some((7, message.clone())));
+
+        // The lost last fragment is recovered from its parity packet
+        let mut assembler = MulticastAssembler::default();
+        let mut received = None;
+        for (index, packet) in packets.iter().enumerate() {
+            if index != 3 {
+                received = assembler.push(packet)?.or(received);
+            }
+        }
+        assert_eq!(received, Some((7, message.clone())));
+
+        // Two lost fragments of a group cannot be recovered
+        let mut assembler = MulticastAssembler::default();
+        for packet in &packets[2..] {
+            assert_eq!(assembler.push(packet)?, None);
+        }
+
+        // Nor can a lost fragment without forward error correction
+        let packets = encode_multicast(8, &message, 0)?;
+        assert_eq!(packets.len(), 3);
+        let mut assembler = MulticastAssembler::default();
+        for packet in &packets[1..] {
+            assert_eq!(assembler.push(packet)?, None);
+        }
+
+        assert_eq!(
+            assembler.push(&packets[0][..10]),
+            Err(MulticastError::TooShort(10))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_multicast_too_large() {
+        let message = vec![0u8; u16::MAX as usize * MULTICAST_FRAGMENT_SIZE];
+        assert_eq!(
+            encode_multicast(1, &message, 0).map(|p| p.len()),
+            Ok(u16::MAX as usize)
+        );
+
+        let message = vec![0u8; u16::MAX as usize * MULTICAST_FRAGMENT_SIZE + 1];
+        assert_eq!(
+            encode_multicast(1, &message, 0),
+            Err(MulticastError::TooLarge(message.len()))
+        );
+    }
+
+    #[test]
+    fn test_multicast_inconsistent_packets() -> Result<(), MulticastError> {
+        let message = vec![1u8; 3000];
+        let packets = encode_multicast(7, &message, 2)?;
+
+        // A packet of the same sequence number from a message of another size
+        let other = encode_multicast(7, &message[..2000], 2)?;
+        let mut assembler = MulticastAssembler::default();
+        assembler.push(&packets[0])?;
+        assert_eq!(
+            assembler.push(&other[1]),
+            Err(MulticastError::InconsistentPacket(7))
+        );
+
+        // Or with another forward error correction group
+        let other = encode_multicast(7, &message, 3)?;
+        assert_eq!(
+            assembler.push(&other[1]),
+            Err(MulticastError::InconsistentPacket(7))
+        );
+
+        // The message is still reassembled from its own packets
+        let mut received = None;
+        for packet in &packets[1..] {
+            received = assembler.push(packet)?.or(received);
+        }
+        assert_eq!(received, Some((7, message)));
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 use super::primitives::FlashblocksPayloadV1;
+use alloy_rpc_types_engine::JwtSecret;
 use core::{
     fmt::{Debug, Formatter},
     net::SocketAddr,
@@ -6,67 +7,232 @@ use core::{
     sync::atomic::{AtomicUsize, Ordering},
     task::{Context, Poll},
 };
-use futures::{Sink, SinkExt};
+use futures::{Sink, SinkExt, StreamExt, future::select_all};
+use metrics::counter;
+use parking_lot::Mutex;
+use serde::Serialize;
 use std::{io, net::TcpListener, sync::Arc};
 use tokio::{
-    net::TcpStream,
+    io::{AsyncRead, AsyncWrite},
+    net::{UnixListener, UnixStream},
     sync::{
         broadcast::{self, Receiver, error::RecvError},
+        mpsc::{self, error::TrySendError},
         watch,
     },
 };
 use tokio_tungstenite::WebSocketStream;
 use tokio_tungstenite::tungstenite::Utf8Bytes;
-use tokio_tungstenite::{accept_async, tungstenite::Message};
+use tokio_tungstenite::{
+    accept_hdr_async,
+    tungstenite::{
+        Message,
+        handshake::server::{
+            ErrorResponse, Request as HandshakeRequest, Response as HandshakeResponse,
+        },
+        http::StatusCode,
+    },
+};
+
+use super::gossip::FlashblocksGossip;
+use super::multicast::MulticastEmitter;
+use super::outbox::Outbox;
+use super::quota::{QuotaExceeded, SubscriberLimits, SubscriberQuotas};
+use super::subscribers::{SubscriberHandle, SubscriberRegistry};
+use crate::{
+    CorsPolicy, IpcEndpoint, RateLimit, TrustedProxies, ratelimit::TokenBucket,
+    spawn_flashblocks_task, ws::validate_bearer,
+};
+
+/// Number of messages waiting to be broadcast before new ones are dropped.
+const PUBLISH_QUEUE_SIZE: usize = 1024;
+
+/// Options of the listeners of a [`WebSocketPublisher`].
+#[derive(Clone, Debug, Default)]
+pub struct PublisherConfig {
+    /// Unix domain socket subscribers can connect to, in addition to TCP
+    pub ipc: Option<IpcEndpoint>,
+    /// Origins browsers may subscribe from, any origin if not set
+    pub cors: Option<CorsPolicy>,
+    /// Proxies whose forwarding headers identify the subscribers behind them
+    pub trusted_proxies: TrustedProxies,
+    /// Quotas of the subscribers
+    pub limits: SubscriberLimits,
+    /// Registry the connected subscribers and their delivery statistics are listed in
+    pub registry: Arc<SubscriberRegistry>,
+    /// Outbox the published messages are retained in for the subscribers resuming from a
+    /// sequence number, not retained if not set
+    pub outbox: Option<Arc<Outbox>>,
+    /// Gossip network the published messages are also propagated on, not gossiped if
+    /// not set
+    pub gossip: Option<Arc<FlashblocksGossip>>,
+    /// Multicast group the published messages are also emitted to, not emitted if not set
+    pub multicast: Option<Arc<MulticastEmitter>>,
+    /// JWT secret the subscribers authenticate the handshake with, not authenticated if
+    /// not set
+    pub jwt_secret: Option<JwtSecret>,
+}
+
+impl PublisherConfig {
+    pub fn with_ipc(mut self, ipc: Option<IpcEndpoint>) -> Self {
+        self.ipc = ipc;
+        self
+    }
+}
 
 /// A WebSockets publisher that accepts connections from client websockets and broadcasts to them
 /// updates about new flashblocks. It maintains a count of sent messages and active subscriptions.
 ///
 /// This is modelled as a `futures::Sink` that can be used to send `FlashblocksPayloadV1` messages.
+///
+/// Subscribers connect over TCP, or over a unix domain socket if an `IpcEndpoint` is given.
+///
+/// Publishing only queues the serialized message: a dedicated task broadcasts it, and each
+/// subscriber is written to by its own task, so slow subscribers never delay the caller.
+///
+/// With an [`Outbox`], every message carries its sequence number in a top-level `seq`
+/// field, and subscribers connecting with `?since=<seq>` first receive the retained
+/// messages published after it.
 pub struct WebSocketPublisher {
     sent: Arc<AtomicUsize>,
     subs: Arc<AtomicUsize>,
     term: watch::Sender<bool>,
-    pipe: broadcast::Sender<Utf8Bytes>,
+    queue: mpsc::Sender<Published>,
+    /// Sequence number of the next published message
+    sequence: Mutex<u64>,
+    outbox: Option<Arc<Outbox>>,
+    local_addrs: Vec<SocketAddr>,
+    registry: Arc<SubscriberRegistry>,
 }
 
 impl WebSocketPublisher {
-    pub fn new(addr: SocketAddr) -> io::Result<Self> {
+    pub fn new(addr: SocketAddr, ipc: Option<IpcEndpoint>) -> io::Result<Self> {
+        Self::bind(&[addr], PublisherConfig::default().with_ipc(ipc))
+    }
+
+    /// Starts a publisher accepting subscribers on all of `addrs`, such as an IPv4 and an
+    /// IPv6 address.
+    pub fn bind(addrs: &[SocketAddr], config: PublisherConfig) -> io::Result<Self> {
+        if addrs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no address to publish on",
+            ));
+        }
         let (pipe, _) = broadcast::channel(100);
+        let (queue, queued) = mpsc::channel(PUBLISH_QUEUE_SIZE);
         let (term, _) = watch::channel(false);
 
         let sent = Arc::new(AtomicUsize::new(0));
         let subs = Arc::new(AtomicUsize::new(0));
-        let listener = TcpListener::bind(addr)?;
+        let listeners = addrs
+            .iter()
+            .map(TcpListener::bind)
+            .collect::<io::Result<Vec<_>>>()?;
+        let local_addrs = listeners
+            .iter()
+            .map(TcpListener::local_addr)
+            .collect::<io::Result<Vec<_>>>()?;
+        let ipc_listener = config.ipc.as_ref().map(IpcEndpoint::bind).transpose()?;
+        let registry = config.registry.clone();
+        let outbox = config.outbox.clone();
+        let gossip = config.gossip.clone();
+        let multicast = config.multicast.clone();
+        let sequence = outbox.as_ref().map(|outbox| outbox.next_seq());
+
+        let subscribers = Subscribers {
+            quotas: SubscriberQuotas::new(config.limits),
+            config,
+            sent: Arc::clone(&sent),
+            subs: Arc::clone(&subs),
+        };
 
         tokio::spawn(listener_loop(
-            listener,
+            listeners,
+            ipc_listener,
+            Arc::new(subscribers),
             pipe.subscribe(),
             term.subscribe(),
-            Arc::clone(&sent),
-            Arc::clone(&subs),
         ));
+        spawn_flashblocks_task(
+            "flashblocks_publisher",
+            dispatch_loop(queued, pipe, outbox.clone(), gossip, multicast),
+        );
 
         Ok(Self {
             sent,
             subs,
             term,
-            pipe,
+            queue,
+            sequence: Mutex::new(sequence.unwrap_or_default()),
+            outbox,
+            local_addrs,
+            registry,
         })
     }
 
+    /// Address subscribers connect to over TCP, resolved if an ephemeral port was requested.
+    /// The first one if the publisher listens on several addresses.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addrs[0]
+    }
+
+    /// All the addresses subscribers connect to over TCP.
+    pub fn local_addrs(&self) -> &[SocketAddr] {
+        &self.local_addrs
+    }
+
+    /// The connected subscribers and their delivery statistics.
+    pub fn subscribers(&self) -> Arc<SubscriberRegistry> {
+        self.registry.clone()
+    }
+
     pub fn publish(&self, payload: &FlashblocksPayloadV1) -> io::Result<()> {
+        self.publish_message(payload)
+    }
+
+    /// Broadcasts any serializable message, such as control messages, to all subscribers.
+    pub fn publish_message<T: Serialize>(&self, message: &T) -> io::Result<()> {
+        // Numbering and queueing under the same lock keeps the messages in sequence
+        let mut sequence = self.sequence.lock();
+        let seq = *sequence;
+
         // Serialize the payload to a UTF-8 string
         // serialize only once, then just copy around only a pointer
         // to the serialized data for each subscription.
-        let serialized = serde_json::to_string(payload)?;
-        let utf8_bytes = Utf8Bytes::from(serialized);
+        let serialized = match &self.outbox {
+            Some(_) => {
+                let mut value = serde_json::to_value(message)?;
+                if let Some(object) = value.as_object_mut() {
+                    object.insert("seq".to_string(), seq.into());
+                }
+                serde_json::to_string(&value)?
+            }
+            None => serde_json::to_string(message)?,
+        };
+        let message = Published {
+            seq,
+            message: Utf8Bytes::from(serialized),
+        };
 
-        // Send the serialized payload to all subscribers
-        self.pipe
-            .send(utf8_bytes)
-            .map_err(|e| io::Error::new(io::ErrorKind::ConnectionAborted, e))?;
-        Ok(())
+        // Queue the serialized payload to be sent to all subscribers
+        match self.queue.try_send(message) {
+            Ok(()) => {
+                *sequence += 1;
+                Ok(())
+            }
+            Err(TrySendError::Full(_)) => {
+                counter!("flashblocks.publish_dropped").increment(1);
+                Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    "publish queue is full",
+                ))
+            }
+            Err(TrySendError::Closed(_)) => Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "publisher is closed",
+            )),
+        }
     }
 }
 
@@ -78,30 +244,85 @@ impl Drop for WebSocketPublisher {
     }
 }
 
-async fn listener_loop(
-    listener: TcpListener,
-    receiver: Receiver<Utf8Bytes>,
-    term: watch::Receiver<bool>,
+/// A published message with its sequence number.
+#[derive(Clone, Debug)]
+struct Published {
+    seq: u64,
+    message: Utf8Bytes,
+}
+
+/// Broadcasts the queued messages to the broadcast loops of the subscribers, until the
+/// `WebSocketPublisher` is dropped.
+///
+/// Messages are appended to the outbox before they are broadcast, so that a subscriber
+/// resuming from the outbox receives them either from the outbox or from the broadcast.
+/// They are also gossiped and emitted to the multicast group, if set.
+async fn dispatch_loop(
+    mut queue: mpsc::Receiver<Published>,
+    pipe: broadcast::Sender<Published>,
+    outbox: Option<Arc<Outbox>>,
+    gossip: Option<Arc<FlashblocksGossip>>,
+    multicast: Option<Arc<MulticastEmitter>>,
+) {
+    while let Some(message) = queue.recv().await {
+        let retained = outbox
+            .as_ref()
+            .map(|outbox| outbox.append(message.seq, message.message.clone()));
+        if let Some(Err(e)) = retained {
+            counter!("flashblocks.outbox_write_failed").increment(1);
+            tracing::warn!(
+                "Failed to retain flashblocks message {} in the outbox: {e}",
+                message.seq
+            );
+        }
+        if let Some(gossip) = &gossip {
+            gossip.publish(message.message.as_str());
+        }
+        if let Some(multicast) = &multicast {
+            multicast.emit(message.seq, message.message.as_bytes());
+        }
+        // Sending only fails if there are no subscribers
+        let _ = pipe.send(message);
+    }
+}
+
+/// State shared by the listener loop and the tasks of the subscribers.
+#[derive(Debug)]
+struct Subscribers {
+    config: PublisherConfig,
+    quotas: SubscriberQuotas,
     sent: Arc<AtomicUsize>,
     subs: Arc<AtomicUsize>,
+}
+
+async fn listener_loop(
+    listeners: Vec<TcpListener>,
+    ipc_listener: Option<UnixListener>,
+    subscribers: Arc<Subscribers>,
+    receiver: Receiver<Published>,
+    term: watch::Receiver<bool>,
 ) {
-    listener
-        .set_nonblocking(true)
-        .expect("Failed to set TcpListener socket to non-blocking");
+    let listeners = listeners
+        .into_iter()
+        .map(|listener| {
+            listener
+                .set_nonblocking(true)
+                .expect("Failed to set TcpListener socket to non-blocking");
 
-    let listener = tokio::net::TcpListener::from_std(listener)
-        .expect("Failed to convert TcpListener to tokio TcpListener");
+            let listener = tokio::net::TcpListener::from_std(listener)
+                .expect("Failed to convert TcpListener to tokio TcpListener");
 
-    let listen_addr = listener
-        .local_addr()
-        .expect("Failed to get local address of listener");
-    tracing::info!("Flashblocks WebSocketPublisher listening on {listen_addr}");
+            let listen_addr = listener
+                .local_addr()
+                .expect("Failed to get local address of listener");
+            tracing::info!("Flashblocks WebSocketPublisher listening on {listen_addr}");
+            listener
+        })
+        .collect::<Vec<_>>();
 
     let mut term = term;
 
     loop {
-        let subs = Arc::clone(&subs);
-
         tokio::select! {
             // drop this connection if the `WebSocketPublisher` is dropped
             _ = term.changed() => {
@@ -113,50 +334,211 @@ async fn listener_loop(
             // Accept new connections on the websocket listener
             // when a new connection is established, spawn a dedicated task to handle
             // the connection and broadcast with that connection.
-            Ok((connection, peer_addr)) = listener.accept() => {
-                let sent = Arc::clone(&sent);
-                let term = term.clone();
-                let receiver_clone = receiver.resubscribe();
-
-                match accept_async(connection).await {
-                    Ok(stream) => {
-                        tokio::spawn(async move {
-                            subs.fetch_add(1, Ordering::Relaxed);
-                            tracing::debug!("WebSocket connection established with {}", peer_addr);
-
-                            // Handle the WebSocket connection in a dedicated task
-                            broadcast_loop(stream, term, receiver_clone, sent).await;
-
-                            subs.fetch_sub(1, Ordering::Relaxed);
-                            tracing::debug!("WebSocket connection closed for {}", peer_addr);
-                        });
-                    }
-                    Err(e) => {
-                        tracing::warn!("Failed to accept WebSocket connection from {peer_addr}: {e}");
-                    }
-                }
+            Ok((connection, peer_addr)) = accept_tcp(&listeners) => {
+                accept_connection(
+                    connection,
+                    Some(peer_addr),
+                    Arc::clone(&subscribers),
+                    term.clone(),
+                    receiver.resubscribe(),
+                );
+            }
+
+            // Accept new connections on the unix domain socket, if any
+            Some(connection) = accept_ipc(ipc_listener.as_ref()) => {
+                accept_connection(
+                    connection,
+                    None,
+                    Arc::clone(&subscribers),
+                    term.clone(),
+                    receiver.resubscribe(),
+                );
             }
         }
     }
 }
 
+/// Accepts the next connection on any of the TCP listeners.
+async fn accept_tcp(
+    listeners: &[tokio::net::TcpListener],
+) -> io::Result<(tokio::net::TcpStream, SocketAddr)> {
+    let accepts = listeners.iter().map(|listener| Box::pin(listener.accept()));
+    select_all(accepts).await.0
+}
+
+async fn accept_ipc(listener: Option<&UnixListener>) -> Option<UnixStream> {
+    match listener {
+        Some(listener) => listener.accept().await.ok().map(|(stream, _)| stream),
+        None => std::future::pending().await,
+    }
+}
+
+/// Spawns a dedicated task performing the websocket handshake and running the broadcast
+/// loop of the new subscriber, so that a slow handshake never delays other connections.
+///
+/// Handshakes without a valid JWT bearer token, if the publisher has a JWT secret, and
+/// from browsers of origins not allowed by the CORS policy are rejected. The
+/// subscriber is identified by the forwarding headers of the handshake when `peer_addr` is a
+/// trusted proxy, and as `ipc` on the unix domain socket. Subscribers over the connection
+/// quotas are closed right after the handshake, with the exceeded quota in the close frame.
+///
+/// Subscribers connecting with `?since=<seq>` are replayed the messages retained in the
+/// outbox after `seq`. `receiver` subscribed to the broadcast before the outbox is read,
+/// so that no message is missed in between.
+fn accept_connection<S>(
+    connection: S,
+    peer_addr: Option<SocketAddr>,
+    subscribers: Arc<Subscribers>,
+    term: watch::Receiver<bool>,
+    receiver: Receiver<Published>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let config = &subscribers.config;
+        let mut peer = peer_addr.map_or_else(|| "ipc".to_string(), |addr| addr.to_string());
+        let mut client = peer_addr.map(|addr| addr.ip().to_canonical());
+        let mut since = None;
+        let handshake = |request: &HandshakeRequest, response: HandshakeResponse| {
+            since = request.uri().query().and_then(since_param);
+            if let Some(addr) = peer_addr {
+                let ip = config
+                    .trusted_proxies
+                    .client_ip(addr.ip(), request.headers());
+                if ip != addr.ip().to_canonical() {
+                    peer = format!("{ip} (via {addr})");
+                }
+                client = Some(ip);
+            }
+            if let Some(secret) = &config.jwt_secret {
+                if let Err(e) = validate_bearer(secret, request.headers()) {
+                    counter!("flashblocks.subscriber_rejected", "reason" => "unauthorized")
+                        .increment(1);
+                    tracing::warn!("Rejecting unauthenticated flashblocks subscriber {peer}: {e}");
+                    let mut rejection = ErrorResponse::new(Some("Unauthorized".to_string()));
+                    *rejection.status_mut() = StatusCode::UNAUTHORIZED;
+                    return Err(rejection);
+                }
+            }
+            match &config.cors {
+                Some(cors) if !cors.allows_request(request.headers()) => {
+                    counter!("flashblocks.subscriber_rejected", "reason" => "origin").increment(1);
+                    let mut rejection = ErrorResponse::new(Some("origin not allowed".to_string()));
+                    *rejection.status_mut() = StatusCode::FORBIDDEN;
+                    Err(rejection)
+                }
+                _ => Ok(response),
+            }
+        };
+        let mut stream = match accept_hdr_async(connection, handshake).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                tracing::warn!("Failed to accept WebSocket connection from {peer}: {e}");
+                return;
+            }
+        };
+
+        let _permit = match subscribers.quotas.admit(client) {
+            Ok(permit) => permit,
+            Err(e) => {
+                counter!("flashblocks.subscriber_rejected", "reason" => e.kind()).increment(1);
+                tracing::info!("Rejecting flashblocks subscriber {peer}: {e}");
+                let _ = stream.close(Some(e.close_frame())).await;
+                return;
+            }
+        };
+
+        subscribers.subs.fetch_add(1, Ordering::Relaxed);
+        tracing::debug!("WebSocket connection established with {}", peer);
+
+        let stats = subscribers.config.registry.register(peer.clone(), client);
+        let replay = match (since, &config.outbox) {
+            (Some(since), Some(outbox)) => {
+                let replay = outbox.since(since);
+                if replay.gap {
+                    counter!("flashblocks.outbox_replay_gaps").increment(1);
+                    tracing::warn!(
+                        "Flashblocks subscriber {peer} resumed from {since}, past the retention of the outbox"
+                    );
+                }
+                replay
+                    .messages
+                    .into_iter()
+                    .map(|message| Published {
+                        seq: message.seq,
+                        message: message.message,
+                    })
+                    .collect()
+            }
+            _ => vec![],
+        };
+        broadcast_loop(
+            stream,
+            &peer,
+            term,
+            receiver,
+            replay,
+            subscribers.sent.clone(),
+            &stats,
+            subscribers.quotas.limits().max_message_rate,
+        )
+        .await;
+        drop(stats);
+
+        subscribers.subs.fetch_sub(1, Ordering::Relaxed);
+        tracing::debug!("WebSocket connection closed for {}", peer);
+    });
+}
+
+/// Sequence number of the `since` parameter of the query of a handshake.
+fn since_param(query: &str) -> Option<u64> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(name, _)| *name == "since")
+        .and_then(|(_, value)| value.parse().ok())
+}
+
 /// An instance of this loop is spawned for each connected WebSocket client.
 /// It listens for broadcast updates about new flashblocks and sends them to the client.
 /// It also handles termination signals to gracefully close the connection.
 /// Any connectivity errors will terminate the loop, which will in turn
 /// decrement the subscription count in the `WebSocketPublisher`.
-async fn broadcast_loop(
-    stream: WebSocketStream<TcpStream>,
+///
+/// The messages of the client are read to answer its pings and notice when it closes the
+/// connection. A client sending more than `max_message_rate` messages per second is
+/// disconnected.
+///
+/// The messages delivered to the client, dropped because it lagged behind, and still
+/// queued for it are recorded in its `stats`.
+///
+/// The `replay` messages are sent first, and the broadcast messages they include skipped.
+async fn broadcast_loop<S>(
+    stream: WebSocketStream<S>,
+    peer_addr: &str,
     term: watch::Receiver<bool>,
-    blocks: broadcast::Receiver<Utf8Bytes>,
+    blocks: broadcast::Receiver<Published>,
+    replay: Vec<Published>,
     sent: Arc<AtomicUsize>,
-) {
+    stats: &SubscriberHandle,
+    max_message_rate: Option<u32>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
     let mut term = term;
     let mut blocks = blocks;
-    let mut stream = stream;
-    let Ok(peer_addr) = stream.get_ref().peer_addr() else {
-        return;
-    };
+    let mut message_rate =
+        max_message_rate.map(|rate| (rate, TokenBucket::new(RateLimit::new(rate, None))));
+    let (mut sink, mut messages) = stream.split();
+
+    let replayed_until = replay.last().map(|message| message.seq);
+    for message in replay {
+        if let Err(e) = sink.send(Message::Text(message.message)).await {
+            tracing::debug!("Closing flashblocks subscription for {peer_addr}: {e}");
+            return;
+        }
+        stats.record_delivered();
+    }
 
     loop {
         tokio::select! {
@@ -170,25 +552,42 @@ async fn broadcast_loop(
 
             // Receive payloads from the broadcast channel
             payload = blocks.recv() => match payload {
+                Ok(payload) if replayed_until.is_some_and(|seq| payload.seq <= seq) => {}
                 Ok(payload) => {
-                    // Here you would typically send the payload to the WebSocket clients.
-                    // For this example, we just increment the sent counter.
                     sent.fetch_add(1, Ordering::Relaxed);
+                    stats.set_queue_depth(blocks.len());
 
-                    tracing::info!("Broadcasted payload: {:?}", payload);
-                    if let Err(e) = stream.send(Message::Text(payload)).await {
+                    tracing::trace!("Broadcasted payload: {:?}", payload.message);
+                    if let Err(e) = sink.send(Message::Text(payload.message)).await {
                         tracing::debug!("Closing flashblocks subscription for {peer_addr}: {e}");
                         break; // Exit the loop if sending fails
                     }
+                    stats.record_delivered();
                 }
                 Err(RecvError::Closed) => {
                     tracing::debug!("Broadcast channel closed, exiting broadcast loop");
                     return;
                 }
-                Err(RecvError::Lagged(_)) => {
-                    tracing::warn!("Broadcast channel lagged, some messages were dropped");
+                Err(RecvError::Lagged(count)) => {
+                    stats.record_dropped(count);
+                    tracing::warn!("Flashblocks subscriber {peer_addr} lagged, {count} messages were dropped");
                 }
             },
+
+            // Read the messages of the client, enforcing its message rate
+            message = messages.next() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => match message_rate.as_mut() {
+                    Some((limit, bucket)) if !bucket.try_acquire() => {
+                        let e = QuotaExceeded::MessageRate(*limit);
+                        counter!("flashblocks.subscriber_rejected", "reason" => e.kind()).increment(1);
+                        tracing::info!("Closing flashblocks subscription for {peer_addr}: {e}");
+                        let _ = sink.send(Message::Close(Some(e.close_frame()))).await;
+                        break;
+                    }
+                    _ => {}
+                },
+            },
         }
     }
 }
@@ -225,3 +624,77 @@ impl Sink<&FlashblocksPayloadV1> for WebSocketPublisher {
         Poll::Ready(Ok(()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flashblocks::primitives::ExecutionPayloadFlashblockDeltaV1;
+    use alloy_primitives::Bytes;
+    use std::time::Duration;
+    use tokio_tungstenite::connect_async;
+
+    async fn wait_for_subscribers(publisher: &WebSocketPublisher, count: usize) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while publisher.subs.load(Ordering::Relaxed) < count {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stalled_subscriber_does_not_delay_others() -> eyre::Result<()> {
+        let publisher = WebSocketPublisher::new("127.0.0.1:0".parse()?, None)?;
+        let url = format!("ws://{}", publisher.local_addr());
+        let (_stalled, _) = connect_async(url.as_str()).await?;
+        let (mut subscriber, _) = connect_async(url.as_str()).await?;
+        wait_for_subscribers(&publisher, 2).await;
+
+        // Messages large enough to fill the socket buffers of the subscriber that never
+        // reads them
+        let payload = FlashblocksPayloadV1 {
+            diff: ExecutionPayloadFlashblockDeltaV1 {
+                transactions: vec![Bytes::from(vec![0; 256 * 1024])],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        for index in 0..64 {
+            publisher.publish(&FlashblocksPayloadV1 {
+                index,
+                ..payload.clone()
+            })?;
+        }
+
+        for index in 0..64 {
+            let message = tokio::time::timeout(Duration::from_secs(5), subscriber.next())
+                .await?
+                .expect("subscription closed")?;
+            let received: FlashblocksPayloadV1 = serde_json::from_str(message.to_text()?)?;
+            assert_eq!(received.index, index);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_publish_queue_full() -> eyre::Result<()> {
+        let publisher = WebSocketPublisher::new("127.0.0.1:0".parse()?, None)?;
+
+        // The queue is only drained once the test yields to the dispatch task
+        for index in 0..PUBLISH_QUEUE_SIZE as u64 {
+            publisher.publish(&FlashblocksPayloadV1 {
+                index,
+                ..Default::default()
+            })?;
+        }
+        let e = publisher
+            .publish(&FlashblocksPayloadV1::default())
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        publisher.publish(&FlashblocksPayloadV1::default())?;
+        Ok(())
+    }
+}
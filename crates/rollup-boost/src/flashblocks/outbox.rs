@@ -0,0 +1,292 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use metrics::counter;
+use parking_lot::Mutex;
+use thiserror::Error;
+use tokio_tungstenite::tungstenite::Utf8Bytes;
+
+use super::args::FlashblocksArgs;
+
+/// Number of messages written to a segment of the outbox before a new one is started.
+/// Expired messages are removed from disk a whole segment at a time.
+const SEGMENT_MESSAGES: usize = 1024;
+
+/// Extension of the segment files of the outbox.
+const SEGMENT_EXTENSION: &str = "log";
+
+#[derive(Debug, Error)]
+pub enum OutboxError {
+    #[error("invalid outbox entry in {path} on line {line}")]
+    InvalidEntry { path: PathBuf, line: usize },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// A message published on the flashblocks websocket, with its sequence number.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct OutboxMessage {
+    pub(crate) seq: u64,
+    /// Unix timestamp of the message, in milliseconds
+    pub(crate) timestamp: u64,
+    pub(crate) message: Utf8Bytes,
+}
+
+/// The messages retained for a subscriber resuming from a sequence number.
+#[derive(Debug, Default)]
+pub(crate) struct Replay {
+    pub(crate) messages: Vec<OutboxMessage>,
+    /// Whether messages after the requested sequence number expired before the
+    /// subscriber came back
+    pub(crate) gap: bool,
+}
+
+#[derive(Debug)]
+struct Segment {
+    first_seq: u64,
+    path: PathBuf,
+}
+
+#[derive(Debug)]
+struct OutboxState {
+    next_seq: u64,
+    messages: VecDeque<OutboxMessage>,
+    /// Segments on disk, oldest first. The last one is written to.
+    segments: VecDeque<Segment>,
+    writer: Option<(BufWriter<File>, usize)>,
+}
+
+/// A disk-backed ring of the messages published on the flashblocks websocket, so that
+/// subscribers reconnecting within the retention window can resume from the last sequence
+/// number they received, for at-least-once delivery.
+///
+/// Messages are appended to segment files named after their first sequence number, one
+/// message per line, and mirrored in memory to serve replays. Messages older than the
+/// retention or beyond the capacity are dropped, and their segments deleted once all
+/// their messages are. Sequence numbers carry on across restarts.
+#[derive(Debug)]
+pub struct Outbox {
+    dir: PathBuf,
+    retention: Duration,
+    capacity: usize,
+    state: Mutex<OutboxState>,
+}
+
+impl Outbox {
+    /// Opens the outbox in `dir`, loading the messages retained by previous runs.
+    pub fn open(dir: PathBuf, retention: Duration, capacity: usize) -> Result<Self, OutboxError> {
+        fs::create_dir_all(&dir)?;
+
+        let mut segments = vec![];
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != SEGMENT_EXTENSION) {
+                continue;
+            }
+            let first_seq = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<u64>().ok());
+            if let Some(first_seq) = first_seq {
+                segments.push(Segment { first_seq, path });
+            }
+        }
+        segments.sort_by_key(|segment| segment.first_seq);
+
+        let mut messages = VecDeque::new();
+        for segment in &segments {
+            messages.extend(read_segment(&segment.path)?);
+        }
+        let next_seq = messages
+            .back()
+            .map(|message| message.seq + 1)
+            .or_else(|| segments.last().map(|segment| segment.first_seq))
+            .unwrap_or_default();
+
+        let outbox = Self {
+            dir,
+            retention,
+            capacity: capacity.max(1),
+            state: Mutex::new(OutboxState {
+                next_seq,
+                messages,
+                segments: segments.into(),
+                writer: None,
+            }),
+        };
+        outbox.prune(&mut outbox.state.lock())?;
+        Ok(outbox)
+    }
+
+    pub fn from_args(args: &FlashblocksArgs) -> Result<Option<Self>, OutboxError> {
+        args.flashblocks_outbox_path
+            .as_ref()
+            .map(|dir| {
+                Self::open(
+                    dir.clone(),
+                    Duration::from_secs(args.flashblocks_outbox_retention_secs),
+                    args.flashblocks_outbox_capacity,
+                )
+            })
+            .transpose()
+    }
+
+    /// Sequence number of the next message appended to the outbox.
+    pub fn next_seq(&self) -> u64 {
+        self.state.lock().next_seq
+    }
+
+    /// Appends the message numbered `seq` and drops the expired messages.
+    pub(crate) fn append(&self, seq: u64, message: Utf8Bytes) -> Result<(), OutboxError> {
+        let mut state = self.state.lock();
+        let timestamp = unix_millis(SystemTime::now());
+
+        if state
+            .writer
+            .as_ref()
+            .is_none_or(|(_, len)| *len >= SEGMENT_MESSAGES)
+        {
+            let path = self.dir.join(format!("{seq:020}.{SEGMENT_EXTENSION}"));
+            let file = OpenOptions::new().create(true).append(true).open(&path)?;
+            state.writer = Some((BufWriter::new(file), 0));
+            state.segments.push_back(Segment {
+                first_seq: seq,
+                path,
+            });
+        }
+        if let Some((writer, len)) = state.writer.as_mut() {
+            writeln!(writer, "{seq} {timestamp} {}", message.as_str())?;
+            writer.flush()?;
+            *len += 1;
+        }
+
+        state.next_seq = seq + 1;
+        state.messages.push_back(OutboxMessage {
+            seq,
+            timestamp,
+            message,
+        });
+        self.prune(&mut state)
+    }
+
+    /// The retained messages after `seq`, for a subscriber resuming from it.
+    pub(crate) fn since(&self, seq: u64) -> Replay {
+        let state = self.state.lock();
+        let oldest = state
+            .messages
+            .front()
+            .map_or(state.next_seq, |message| message.seq);
+        Replay {
+            messages: state
+                .messages
+                .iter()
+                .filter(|message| message.seq > seq)
+                .cloned()
+                .collect(),
+            gap: seq.saturating_add(1) < oldest,
+        }
+    }
+
+    fn prune(&self, state: &mut OutboxState) -> Result<(), OutboxError> {
+        let expired_before = SystemTime::now()
+            .checked_sub(self.retention)
+            .map_or(0, unix_millis);
+        while state.messages.front().is_some_and(|message| {
+            state.messages.len() > self.capacity || message.timestamp < expired_before
+        }) {
+            state.messages.pop_front();
+            counter!("flashblocks.outbox_expired").increment(1);
+        }
+
+        // A segment only holds expired messages once the next one starts at or before
+        // the oldest retained message. The segment being written to is always kept.
+        let oldest = state
+            .messages
+            .front()
+            .map_or(state.next_seq, |message| message.seq);
+        while state.segments.len() > 1 && state.segments[1].first_seq <= oldest {
+            if let Some(segment) = state.segments.pop_front() {
+                remove_segment(&segment.path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_segment(path: &Path) -> Result<Vec<OutboxMessage>, OutboxError> {
+    let mut messages = vec![];
+    for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = || OutboxError::InvalidEntry {
+            path: path.to_path_buf(),
+            line: i + 1,
+        };
+        let mut parts = line.splitn(3, ' ');
+        let (Some(seq), Some(timestamp), Some(message)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        messages.push(OutboxMessage {
+            seq: seq.parse().map_err(|_| invalid())?,
+            timestamp: timestamp.parse().map_err(|_| invalid())?,
+            message: message.to_string().into(),
+        });
+    }
+    Ok(messages)
+}
+
+fn remove_segment(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outbox_replay() -> eyre::Result<()> {
+        let dir = std::env::temp_dir().join(format!("rollup-boost-outbox-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let outbox = Outbox::open(dir.clone(), Duration::from_secs(60), 3)?;
+        for seq in 0..5 {
+            outbox.append(seq, format!("{{\"seq\":{seq}}}").into())?;
+        }
+
+        // Only the last 3 messages are retained
+        let replay = outbox.since(2);
+        assert!(!replay.gap);
+        assert_eq!(
+            replay.messages.iter().map(|m| m.seq).collect::<Vec<_>>(),
+            [3, 4]
+        );
+        assert!(outbox.since(0).gap);
+        assert!(outbox.since(4).messages.is_empty());
+
+        // Sequence numbers carry on after a restart
+        drop(outbox);
+        let outbox = Outbox::open(dir.clone(), Duration::from_secs(60), 3)?;
+        assert_eq!(outbox.next_seq(), 5);
+        assert_eq!(outbox.since(3).messages[0].message.as_str(), r#"{"seq":4}"#);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
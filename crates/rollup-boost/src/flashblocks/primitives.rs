@@ -1,4 +1,4 @@
-use alloy_primitives::{Address, B256, Bloom, Bytes, U256};
+use alloy_primitives::{Address, B64, B256, Bloom, Bytes, U256};
 use alloy_rpc_types_engine::PayloadId;
 use alloy_rpc_types_eth::Withdrawal;
 use serde::{Deserialize, Serialize};
@@ -57,6 +57,19 @@ pub struct ExecutionPayloadBaseV1 {
     pub extra_data: Bytes,
     /// The base fee per gas of the block.
     pub base_fee_per_gas: U256,
+    /// Holocene dynamic EIP-1559 parameters of the block: the denominator and the
+    /// elasticity, as big endian u32s. They are also encoded in the extra data.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eip_1559_params: Option<B64>,
+}
+
+impl ExecutionPayloadBaseV1 {
+    /// The EIP-1559 parameters encoded in the extra data since Holocene, after a zero
+    /// version byte.
+    pub fn extra_data_eip_1559_params(&self) -> Option<B64> {
+        (self.extra_data.len() == 9 && self.extra_data[0] == 0)
+            .then(|| B64::from_slice(&self.extra_data[1..]))
+    }
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
@@ -73,3 +86,35 @@ pub struct FlashblocksPayloadV1 {
     /// Additional metadata associated with the flashblock
     pub metadata: Value,
 }
+
+/// Control message sent to flashblocks subscribers when the unsafe head is reorged.
+/// Any preconfirmations streamed for `payload_id` should be considered invalid.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct FlashblocksReorgV1 {
+    /// The payload id of the invalidated flashblocks
+    pub payload_id: PayloadId,
+    /// The unsafe head before the reorg
+    pub old_head: B256,
+    /// The unsafe head after the reorg
+    pub new_head: B256,
+}
+
+/// Control message sent to flashblocks subscribers when the flashblocks published for a
+/// payload are replaced by those of another builder. The flashblocks received for
+/// `payload_id` should be discarded, the payload is published again from index 0.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct FlashblocksResetV1 {
+    /// The payload id of the replaced flashblocks
+    pub payload_id: PayloadId,
+}
+
+/// Control messages published to the flashblocks subscribers alongside the flashblocks,
+/// tagged by a `type` field, which flashblocks do not have.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FlashblocksControlV1 {
+    /// The unsafe head was reorged
+    Reorg(FlashblocksReorgV1),
+    /// The flashblocks of the payload are published again from the first one
+    Reset(FlashblocksResetV1),
+}
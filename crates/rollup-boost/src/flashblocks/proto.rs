@@ -0,0 +1,247 @@
+//! Protobuf encoding of the flashblocks primitives, generated from
+//! `proto/flashblocks.proto` by the build script, with the conversions from and to the types published as
+//! JSON to the flashblocks subscribers.
+
+use alloy_primitives::{Address, Bloom, Bytes, FixedBytes, U256};
+use alloy_rpc_types_engine::PayloadId;
+use prost::Message;
+use serde_json::Value;
+use thiserror::Error;
+
+use super::primitives;
+
+include!(concat!(env!("OUT_DIR"), "/flashblocks.v1.rs"));
+
+#[derive(Debug, Error)]
+pub enum ProtoError {
+    #[error("{field} is {len} bytes long, expected {expected}")]
+    InvalidLength {
+        field: &'static str,
+        len: usize,
+        expected: usize,
+    },
+    #[error("missing {0}")]
+    Missing(&'static str),
+    #[error("invalid metadata: {0}")]
+    Metadata(#[from] serde_json::Error),
+    #[error(transparent)]
+    Decode(#[from] prost::DecodeError),
+}
+
+/// Encodes a flashblock as protobuf.
+pub fn encode_flashblock(payload: &primitives::FlashblocksPayloadV1) -> Vec<u8> {
+    FlashblocksPayloadV1::from(payload.clone()).encode_to_vec()
+}
+
+/// Decodes a flashblock encoded as protobuf.
+pub fn decode_flashblock(bytes: &[u8]) -> Result<primitives::FlashblocksPayloadV1, ProtoError> {
+    FlashblocksPayloadV1::decode(bytes)?.try_into()
+}
+
+fn fixed<const N: usize>(field: &'static str, bytes: &[u8]) -> Result<[u8; N], ProtoError> {
+    FixedBytes::<N>::try_from(bytes)
+        .map(|fixed| fixed.0)
+        .map_err(|_| ProtoError::InvalidLength {
+            field,
+            len: bytes.len(),
+            expected: N,
+        })
+}
+
+impl From<primitives::FlashblocksPayloadV1> for FlashblocksPayloadV1 {
+    fn from(payload: primitives::FlashblocksPayloadV1) -> Self {
+        Self {
+            payload_id: payload.payload_id.0.to_vec(),
+            index: payload.index,
+            base: payload.base.map(Into::into),
+            diff: Some(payload.diff.into()),
+            metadata: payload.metadata.to_string(),
+        }
+    }
+}
+
+impl TryFrom<FlashblocksPayloadV1> for primitives::FlashblocksPayloadV1 {
+    type Error = ProtoError;
+
+    fn try_from(payload: FlashblocksPayloadV1) -> Result<Self, Self::Error> {
+        // proto3 strings default to empty when the field is not set
+        let metadata = if payload.metadata.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_str(&payload.metadata)?
+        };
+        Ok(Self {
+            payload_id: PayloadId::new(fixed("payload_id", &payload.payload_id)?),
+            index: payload.index,
+            base: payload.base.map(TryInto::try_into).transpose()?,
+            diff: payload
+                .diff
+                .ok_or(ProtoError::Missing("diff"))?
+                .try_into()?,
+            metadata,
+        })
+    }
+}
+
+impl From<primitives::ExecutionPayloadBaseV1> for ExecutionPayloadBaseV1 {
+    fn from(base: primitives::ExecutionPayloadBaseV1) -> Self {
+        Self {
+            parent_beacon_block_root: base.parent_beacon_block_root.to_vec(),
+            parent_hash: base.parent_hash.to_vec(),
+            fee_recipient: base.fee_recipient.to_vec(),
+            prev_randao: base.prev_randao.to_vec(),
+            block_number: base.block_number,
+            gas_limit: base.gas_limit,
+            timestamp: base.timestamp,
+            extra_data: base.extra_data.to_vec(),
+            base_fee_per_gas: base.base_fee_per_gas.to_be_bytes::<32>().to_vec(),
+            eip_1559_params: base.eip_1559_params.map(|params| params.to_vec()),
+        }
+    }
+}
+
+impl TryFrom<ExecutionPayloadBaseV1> for primitives::ExecutionPayloadBaseV1 {
+    type Error = ProtoError;
+
+    fn try_from(base: ExecutionPayloadBaseV1) -> Result<Self, Self::Error> {
+        Ok(Self {
+            parent_beacon_block_root: fixed(
+                "parent_beacon_block_root",
+                &base.parent_beacon_block_root,
+            )?
+            .into(),
+            parent_hash: fixed("parent_hash", &base.parent_hash)?.into(),
+            fee_recipient: Address::new(fixed("fee_recipient", &base.fee_recipient)?),
+            prev_randao: fixed("prev_randao", &base.prev_randao)?.into(),
+            block_number: base.block_number,
+            gas_limit: base.gas_limit,
+            timestamp: base.timestamp,
+            extra_data: Bytes::from(base.extra_data),
+            base_fee_per_gas: U256::from_be_bytes(fixed::<32>(
+                "base_fee_per_gas",
+                &base.base_fee_per_gas,
+            )?),
+            eip_1559_params: base
+                .eip_1559_params
+                .map(|params| fixed("eip_1559_params", &params).map(Into::into))
+                .transpose()?,
+        })
+    }
+}
+
+impl From<primitives::ExecutionPayloadFlashblockDeltaV1> for ExecutionPayloadFlashblockDeltaV1 {
+    fn from(diff: primitives::ExecutionPayloadFlashblockDeltaV1) -> Self {
+        Self {
+            state_root: diff.state_root.to_vec(),
+            receipts_root: diff.receipts_root.to_vec(),
+            logs_bloom: diff.logs_bloom.to_vec(),
+            gas_used: diff.gas_used,
+            block_hash: diff.block_hash.to_vec(),
+            transactions: diff
+                .transactions
+                .into_iter()
+                .map(|tx| tx.to_vec())
+                .collect(),
+            withdrawals: diff.withdrawals.into_iter().map(Into::into).collect(),
+            withdrawals_root: diff.withdrawals_root.to_vec(),
+        }
+    }
+}
+
+impl TryFrom<ExecutionPayloadFlashblockDeltaV1> for primitives::ExecutionPayloadFlashblockDeltaV1 {
+    type Error = ProtoError;
+
+    fn try_from(diff: ExecutionPayloadFlashblockDeltaV1) -> Result<Self, Self::Error> {
+        Ok(Self {
+            state_root: fixed("state_root", &diff.state_root)?.into(),
+            receipts_root: fixed("receipts_root", &diff.receipts_root)?.into(),
+            logs_bloom: Bloom::new(fixed("logs_bloom", &diff.logs_bloom)?),
+            gas_used: diff.gas_used,
+            block_hash: fixed("block_hash", &diff.block_hash)?.into(),
+            transactions: diff.transactions.into_iter().map(Bytes::from).collect(),
+            withdrawals: diff
+                .withdrawals
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
+            withdrawals_root: fixed("withdrawals_root", &diff.withdrawals_root)?.into(),
+        })
+    }
+}
+
+impl From<alloy_rpc_types_eth::Withdrawal> for Withdrawal {
+    fn from(withdrawal: alloy_rpc_types_eth::Withdrawal) -> Self {
+        Self {
+            index: withdrawal.index,
+            validator_index: withdrawal.validator_index,
+            address: withdrawal.address.to_vec(),
+            amount: withdrawal.amount,
+        }
+    }
+}
+
+impl TryFrom<Withdrawal> for alloy_rpc_types_eth::Withdrawal {
+    type Error = ProtoError;
+
+    fn try_from(withdrawal: Withdrawal) -> Result<Self, Self::Error> {
+        Ok(Self {
+            index: withdrawal.index,
+            validator_index: withdrawal.validator_index,
+            address: Address::new(fixed("withdrawal address", &withdrawal.address)?),
+            amount: withdrawal.amount,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_primitives::{B64, B256};
+
+    use super::*;
+
+    #[test]
+    fn test_flashblock_roundtrip() -> eyre::Result<()> {
+        let payload = primitives::FlashblocksPayloadV1 {
+            payload_id: PayloadId::new([1; 8]),
+            index: 0,
+            base: Some(primitives::ExecutionPayloadBaseV1 {
+                fee_recipient: Address::with_last_byte(1),
+                block_number: 10,
+                gas_limit: 30_000_000,
+                base_fee_per_gas: U256::from(1_000_000_000u64),
+                eip_1559_params: Some(B64::from([0, 0, 0, 250, 0, 0, 0, 6])),
+                ..Default::default()
+            }),
+            diff: primitives::ExecutionPayloadFlashblockDeltaV1 {
+                state_root: B256::repeat_byte(2),
+                gas_used: 21_000,
+                transactions: vec![Bytes::from_static(&[0x02, 0x01])],
+                withdrawals: vec![alloy_rpc_types_eth::Withdrawal {
+                    index: 1,
+                    validator_index: 2,
+                    address: Address::with_last_byte(3),
+                    amount: 4,
+                }],
+                ..Default::default()
+            },
+            metadata: serde_json::json!({ "block_number": 10 }),
+        };
+        let decoded = decode_flashblock(&encode_flashblock(&payload))?;
+        assert_eq!(
+            serde_json::to_value(&decoded)?,
+            serde_json::to_value(&payload)?
+        );
+
+        let mut invalid = FlashblocksPayloadV1::from(payload);
+        invalid.payload_id.pop();
+        assert!(matches!(
+            decode_flashblock(&invalid.encode_to_vec()),
+            Err(ProtoError::InvalidLength {
+                field: "payload_id",
+                len: 7,
+                expected: 8,
+            })
+        ));
+        Ok(())
+    }
+}
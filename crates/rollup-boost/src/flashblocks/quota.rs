@@ -0,0 +1,186 @@
+use std::{collections::HashMap, net::IpAddr, sync::Arc};
+
+use parking_lot::Mutex;
+use serde_json::json;
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, frame::coding::CloseCode};
+
+use super::args::FlashblocksArgs;
+
+/// Limits on the subscribers of the flashblocks websocket, keeping the stream stable
+/// when a consumer misbehaves.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SubscriberLimits {
+    /// Maximum number of subscribers, unlimited if not set
+    pub max_subscribers: Option<usize>,
+    /// Maximum number of subscribers from the same address, unlimited if not set
+    pub max_subscribers_per_ip: Option<usize>,
+    /// Maximum number of messages per second a subscriber may send, unlimited if not set
+    pub max_message_rate: Option<u32>,
+}
+
+impl SubscriberLimits {
+    pub fn from_args(args: &FlashblocksArgs) -> Self {
+        Self {
+            max_subscribers: args.flashblocks_max_subscribers,
+            max_subscribers_per_ip: args.flashblocks_max_subscribers_per_ip,
+            max_message_rate: args.flashblocks_max_subscriber_message_rate,
+        }
+    }
+}
+
+/// Why a subscriber was disconnected, sent to the subscriber in the reason of the close
+/// frame as JSON, e.g. `{"error":"max_subscribers","limit":100}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum QuotaExceeded {
+    #[error("too many subscribers, the limit is {0}")]
+    Subscribers(usize),
+    #[error("too many subscribers from the same address, the limit is {0}")]
+    SubscribersPerIp(usize),
+    #[error("too many messages, the limit is {0} per second")]
+    MessageRate(u32),
+}
+
+impl QuotaExceeded {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            QuotaExceeded::Subscribers(_) => "max_subscribers",
+            QuotaExceeded::SubscribersPerIp(_) => "max_subscribers_per_ip",
+            QuotaExceeded::MessageRate(_) => "message_rate",
+        }
+    }
+
+    /// Close frame sent to the subscriber. Subscribers over the connection quotas may
+    /// retry later, subscribers over the message rate violated the policy.
+    pub fn close_frame(&self) -> CloseFrame {
+        let (code, limit) = match *self {
+            QuotaExceeded::Subscribers(limit) | QuotaExceeded::SubscribersPerIp(limit) => {
+                (CloseCode::Again, limit as u64)
+            }
+            QuotaExceeded::MessageRate(limit) => (CloseCode::Policy, limit as u64),
+        };
+        CloseFrame {
+            code,
+            reason: json!({"error": self.kind(), "limit": limit})
+                .to_string()
+                .into(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Connections {
+    total: usize,
+    per_ip: HashMap<IpAddr, usize>,
+}
+
+/// Counts the subscribers of the publisher, admitting new ones while they are within the
+/// limits.
+#[derive(Debug, Default)]
+pub(crate) struct SubscriberQuotas {
+    limits: SubscriberLimits,
+    connections: Arc<Mutex<Connections>>,
+}
+
+impl SubscriberQuotas {
+    pub(crate) fn new(limits: SubscriberLimits) -> Self {
+        Self {
+            limits,
+            connections: Arc::default(),
+        }
+    }
+
+    pub(crate) fn limits(&self) -> &SubscriberLimits {
+        &self.limits
+    }
+
+    /// Admits a new subscriber from `ip`, or from the unix domain socket if not set. The
+    /// subscriber is counted until the returned permit is dropped.
+    pub(crate) fn admit(&self, ip: Option<IpAddr>) -> Result<SubscriberPermit, QuotaExceeded> {
+        let mut connections = self.connections.lock();
+        let total = connections.total;
+        if let Some(max) = self.limits.max_subscribers.filter(|max| total >= *max) {
+            return Err(QuotaExceeded::Subscribers(max));
+        }
+        let from_ip = ip
+            .and_then(|ip| connections.per_ip.get(&ip))
+            .copied()
+            .unwrap_or_default();
+        if let Some(max) = ip
+            .and(self.limits.max_subscribers_per_ip)
+            .filter(|max| from_ip >= *max)
+        {
+            return Err(QuotaExceeded::SubscribersPerIp(max));
+        }
+
+        connections.total += 1;
+        if let Some(ip) = ip {
+            *connections.per_ip.entry(ip).or_default() += 1;
+        }
+        Ok(SubscriberPermit {
+            ip,
+            connections: self.connections.clone(),
+        })
+    }
+}
+
+/// A subscriber counted by the [`SubscriberQuotas`] until dropped.
+#[derive(Debug)]
+pub(crate) struct SubscriberPermit {
+    ip: Option<IpAddr>,
+    connections: Arc<Mutex<Connections>>,
+}
+
+impl Drop for SubscriberPermit {
+    fn drop(&mut self) {
+        let mut connections = self.connections.lock();
+        connections.total -= 1;
+        let Some(ip) = self.ip else {
+            return;
+        };
+        if let Some(count) = connections.per_ip.get_mut(&ip) {
+            *count -= 1;
+            if *count == 0 {
+                connections.per_ip.remove(&ip);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscriber_quotas() {
+        let quotas = SubscriberQuotas::new(SubscriberLimits {
+            max_subscribers: Some(3),
+            max_subscribers_per_ip: Some(2),
+            max_message_rate: None,
+        });
+        let a: IpAddr = "203.0.113.1".parse().unwrap();
+        let b: IpAddr = "203.0.113.2".parse().unwrap();
+
+        let first = quotas.admit(Some(a)).unwrap();
+        let _second = quotas.admit(Some(a)).unwrap();
+        assert_eq!(
+            quotas.admit(Some(a)).unwrap_err(),
+            QuotaExceeded::SubscribersPerIp(2)
+        );
+        let _third = quotas.admit(Some(b)).unwrap();
+        assert_eq!(
+            quotas.admit(None).unwrap_err(),
+            QuotaExceeded::Subscribers(3)
+        );
+
+        // Disconnected subscribers free their slot
+        drop(first);
+        let _fourth = quotas.admit(Some(a)).unwrap();
+
+        let frame = QuotaExceeded::SubscribersPerIp(2).close_frame();
+        assert_eq!(frame.code, CloseCode::Again);
+        assert_eq!(
+            frame.reason.as_str(),
+            r#"{"error":"max_subscribers_per_ip","limit":2}"#
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::VecDeque;
+
+use alloy_rpc_types_engine::PayloadId;
+use parking_lot::Mutex;
+
+use super::primitives::FlashblocksPayloadV1;
+
+/// The flashblocks received from the builder for the last payloads, exactly as received,
+/// so that builder developers can inspect them with `debug_getFlashblocks`.
+///
+/// Flashblocks are kept whether or not they were accepted.
+#[derive(Debug)]
+pub struct RecentFlashblocks {
+    capacity: usize,
+    payloads: Mutex<VecDeque<(PayloadId, Vec<FlashblocksPayloadV1>)>>,
+}
+
+impl RecentFlashblocks {
+    /// Keeps the flashblocks of the last `capacity` payloads.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            payloads: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Records a flashblock received from the builder, dropping the flashblocks of the
+    /// oldest payload once `capacity` payloads are kept.
+    pub(crate) fn record(&self, payload: &FlashblocksPayloadV1) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut payloads = self.payloads.lock();
+        match payloads.back_mut() {
+            Some((payload_id, flashblocks)) if *payload_id == payload.payload_id => {
+                flashblocks.push(payload.clone());
+            }
+            _ => {
+                if payloads.len() >= self.capacity {
+                    payloads.pop_front();
+                }
+                payloads.push_back((payload.payload_id, vec![payload.clone()]));
+            }
+        }
+    }
+
+    /// The flashblocks received for `payload_id`, in the order they were received.
+    pub fn get(&self, payload_id: PayloadId) -> Option<Vec<FlashblocksPayloadV1>> {
+        self.payloads
+            .lock()
+            .iter()
+            .rev()
+            .find(|(id, _)| *id == payload_id)
+            .map(|(_, flashblocks)| flashblocks.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flashblock(payload_id: PayloadId, index: u64) -> FlashblocksPayloadV1 {
+        FlashblocksPayloadV1 {
+            payload_id,
+            index,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_recent_flashblocks() {
+        let recent = RecentFlashblocks::new(2);
+        let ids = [1, 2, 3].map(|i| PayloadId::new([i; 8]));
+        for id in ids {
+            recent.record(&flashblock(id, 0));
+            recent.record(&flashblock(id, 1));
+        }
+
+        // Only the flashblocks of the last 2 payloads are kept
+        assert!(recent.get(ids[0]).is_none());
+        let flashblocks = recent.get(ids[2]).unwrap();
+        assert_eq!(
+            flashblocks.iter().map(|f| f.index).collect::<Vec<_>>(),
+            [0, 1]
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use serde_json::{Value, json};
+
+/// Path the JSON Schemas of the flashblocks messages are served on.
+pub const FLASHBLOCKS_SCHEMA_PATH: &str = "/.well-known/flashblocks-schemas.json";
+
+fn hex(bytes: usize, description: &str) -> Value {
+    json!({
+        "type": "string",
+        "pattern": format!("^0x[0-9a-fA-F]{{{}}}$", bytes * 2),
+        "description": description,
+    })
+}
+
+fn quantity(description: &str) -> Value {
+    json!({
+        "type": "string",
+        "pattern": "^0x(0|[1-9a-fA-F][0-9a-fA-F]*)$",
+        "description": description,
+    })
+}
+
+fn data(description: &str) -> Value {
+    json!({
+        "type": "string",
+        "pattern": "^0x([0-9a-fA-F]{2})*$",
+        "description": description,
+    })
+}
+
+/// JSON Schema (draft 2020-12) of the messages published to the flashblocks subscribers,
+/// so that consumers in other languages can generate their clients from it. A message is
+/// either a flashblock or a control message tagged by its `type`, with its sequence
+/// number when the outbox is enabled. Every message type is also defined in `$defs`, by
+/// its name.
+pub fn flashblocks_json_schemas() -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "Flashblocks messages",
+        "oneOf": [
+            { "$ref": "#/$defs/FlashblocksPayloadV1" },
+            { "$ref": "#/$defs/FlashblocksReorgV1" },
+            { "$ref": "#/$defs/FlashblocksResetV1" },
+        ],
+        "$defs": {
+            "FlashblocksPayloadV1": payload_schema(),
+            "ExecutionPayloadBaseV1": base_schema(),
+            "ExecutionPayloadFlashblockDeltaV1": delta_schema(),
+            "Withdrawal": withdrawal_schema(),
+            "FlashblocksReorgV1": reorg_schema(),
+            "FlashblocksResetV1": reset_schema(),
+            "Seq": {
+                "type": "integer",
+                "minimum": 0,
+                "description": "Sequence number of the message, only set when the outbox is enabled",
+            },
+        },
+    })
+}
+
+fn payload_schema() -> Value {
+    json!({
+        "description": "A flashblock: the delta of the block being built at an index, with its base at index 0",
+        "type": "object",
+        "required": ["payload_id", "index", "diff", "metadata"],
+        "properties": {
+            "payload_id": hex(8, "Payload ID of the block being built"),
+            "index": {
+                "type": "integer",
+                "minimum": 0,
+                "description": "Index of the flashblock in the block",
+            },
+            "base": { "$ref": "#/$defs/ExecutionPayloadBaseV1" },
+            "diff": { "$ref": "#/$defs/ExecutionPayloadFlashblockDeltaV1" },
+            "metadata": { "description": "Metadata of the builder, of any shape" },
+            "seq": { "$ref": "#/$defs/Seq" },
+        },
+    })
+}
+
+fn base_schema() -> Value {
+    json!({
+        "description": "Fields of the block that remain constant throughout its construction",
+        "type": "object",
+        "required": [
+            "parent_beacon_block_root",
+            "parent_hash",
+            "fee_recipient",
+            "prev_randao",
+            "block_number",
+            "gas_limit",
+            "timestamp",
+            "extra_data",
+            "base_fee_per_gas",
+        ],
+        "properties": {
+            "parent_beacon_block_root": hex(32, "Ecotone parent beacon block root"),
+            "parent_hash": hex(32, "Parent hash of the block"),
+            "fee_recipient": hex(20, "Fee recipient of the block"),
+            "prev_randao": hex(32, "Previous randao of the block"),
+            "block_number": quantity("Number of the block"),
+            "gas_limit": quantity("Gas limit of the block"),
+            "timestamp": quantity("Timestamp of the block, in seconds"),
+            "extra_data": data("Extra data of the block"),
+            "base_fee_per_gas": quantity("Base fee per gas of the block"),
+            "eip_1559_params": hex(8, "Holocene EIP-1559 denominator and elasticity, as big endian u32s"),
+        },
+    })
+}
+
+fn delta_schema() -> Value {
+    json!({
+        "description": "Fields of the block updated by a flashblock, with the transactions and withdrawals it adds",
+        "type": "object",
+        "required": [
+            "state_root",
+            "receipts_root",
+            "logs_bloom",
+            "gas_used",
+            "block_hash",
+            "transactions",
+            "withdrawals",
+            "withdrawals_root",
+        ],
+        "properties": {
+            "state_root": hex(32, "State root of the block up to the flashblock"),
+            "receipts_root": hex(32, "Receipts root of the block up to the flashblock"),
+            "logs_bloom": hex(256, "Logs bloom of the block up to the flashblock"),
+            "gas_used": quantity("Gas used by the block up to the flashblock"),
+            "block_hash": hex(32, "Hash of the block up to the flashblock"),
+            "transactions": {
+                "type": "array",
+                "items": data("EIP-2718 encoded transaction"),
+                "description": "Transactions added by the flashblock",
+            },
+            "withdrawals": {
+                "type": "array",
+                "items": { "$ref": "#/$defs/Withdrawal" },
+                "description": "Withdrawals added by the flashblock",
+            },
+            "withdrawals_root": hex(32, "Withdrawals root of the block, the L2 to L1 message passer storage root since Isthmus"),
+        },
+    })
+}
+
+fn withdrawal_schema() -> Value {
+    json!({
+        "type": "object",
+        "required": ["index", "validatorIndex", "address", "amount"],
+        "properties": {
+            "index": quantity("Monotonically increasing identifier of the withdrawal"),
+            "validatorIndex": quantity("Index of the validator of the withdrawal"),
+            "address": hex(20, "Recipient of the withdrawn ether"),
+            "amount": quantity("Value of the withdrawal, in gwei"),
+        },
+    })
+}
+
+fn reorg_schema() -> Value {
+    json!({
+        "description": "Control message sent when the unsafe head is reorged: the flashblocks of the payload are invalidated",
+        "type": "object",
+        "required": ["type", "payload_id", "old_head", "new_head"],
+        "properties": {
+            "type": { "const": "reorg" },
+            "payload_id": hex(8, "Payload ID of the invalidated flashblocks"),
+            "old_head": hex(32, "Unsafe head before the reorg"),
+            "new_head": hex(32, "Unsafe head after the reorg"),
+            "seq": { "$ref": "#/$defs/Seq" },
+        },
+    })
+}
+
+fn reset_schema() -> Value {
+    json!({
+        "description": "Control message sent when the flashblocks of the payload are replaced by those of another builder: the flashblocks received are invalidated and the payload is published again from index 0",
+        "type": "object",
+        "required": ["type", "payload_id"],
+        "properties": {
+            "type": { "const": "reset" },
+            "payload_id": hex(8, "Payload ID of the invalidated flashblocks"),
+            "seq": { "$ref": "#/$defs/Seq" },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ExecutionPayloadBaseV1, FlashblocksControlV1, FlashblocksPayloadV1, FlashblocksReorgV1,
+        FlashblocksResetV1,
+    };
+
+    /// Checks that every field of `message` is described by the schema of its type.
+    fn assert_described(schemas: &Value, name: &str, message: Value) {
+        let schema = &schemas["$defs"][name];
+        let properties = schema["properties"].as_object().unwrap();
+        for field in message.as_object().unwrap().keys() {
+            assert!(
+                properties.contains_key(field),
+                "{name}.{field} is not described"
+            );
+        }
+        for required in schema["required"].as_array().unwrap() {
+            assert!(message.get(required.as_str().unwrap()).is_some());
+        }
+    }
+
+    #[test]
+    fn test_flashblocks_json_schemas() -> eyre::Result<()> {
+        let schemas = flashblocks_json_schemas();
+        let payload = FlashblocksPayloadV1 {
+            base: Some(ExecutionPayloadBaseV1::default()),
+            ..Default::default()
+        };
+        let message = serde_json::to_value(&payload)?;
+        assert_described(&schemas, "FlashblocksPayloadV1", message.clone());
+        assert_described(&schemas, "ExecutionPayloadBaseV1", message["base"].clone());
+        assert_described(
+            &schemas,
+            "ExecutionPayloadFlashblockDeltaV1",
+            message["diff"].clone(),
+        );
+        let reorg =
+            serde_json::to_value(FlashblocksControlV1::Reorg(FlashblocksReorgV1::default()))?;
+        assert_eq!(reorg["type"], "reorg");
+        assert_described(&schemas, "FlashblocksReorgV1", reorg.clone());
+        let reset =
+            serde_json::to_value(FlashblocksControlV1::Reset(FlashblocksResetV1::default()))?;
+        assert_eq!(reset["type"], "reset");
+        assert_described(&schemas, "FlashblocksResetV1", reset);
+
+        // Control messages are not mistaken for flashblocks
+        assert!(serde_json::from_value::<FlashblocksPayloadV1>(reorg).is_err());
+        Ok(())
+    }
+}
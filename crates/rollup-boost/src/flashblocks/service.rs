@@ -1,29 +1,54 @@
-use super::outbound::WebSocketPublisher;
+use super::args::{DuplicateTransactionPolicy, FlashblocksArgs};
+use super::conflict::{BuilderStream, ConflictResolution};
+use super::denylist::{DeniedTransaction, TransactionDenylist};
+use super::gossip::FlashblocksGossip;
+use super::merge::{MergedTransactions, merge_deltas};
+use super::outbound::{PublisherConfig, WebSocketPublisher};
 use super::primitives::{
-    ExecutionPayloadBaseV1, ExecutionPayloadFlashblockDeltaV1, FlashblocksPayloadV1,
+    ExecutionPayloadBaseV1, ExecutionPayloadFlashblockDeltaV1, FlashblocksControlV1,
+    FlashblocksPayloadV1, FlashblocksReorgV1, FlashblocksResetV1,
 };
-use crate::RpcClientError;
+use super::recent::RecentFlashblocks;
+use super::skew::{ClockSkew, ClockSkewCheck};
+use super::sync::{StateSync, StateSyncMessage};
 use crate::{
-    ClientResult, EngineApiExt, NewPayload, OpExecutionPayloadEnvelope, PayloadVersion, RpcClient,
+    BlockSource, ClientResult, EngineApiExt, NewPayload, OpExecutionPayloadEnvelope,
+    PayloadVersion, ReorgEvent, RpcClient,
 };
-use alloy_primitives::U256;
+use crate::{
+    BuilderQuarantine, BuilderScores, ClickHouseSink, CorrelationId, DEFAULT_BUILDER,
+    EngineApiError, FaultInjector, FlashblockRow, LeaderElection, PayloadArchive,
+    SERVER_ERROR_CODE, ScoreObservation, SealedPayload, UNKNOWN_PAYLOAD_CODE, ValidationFailure,
+    WebhookNotifier,
+};
+use alloy_consensus::proofs;
+use alloy_primitives::{B64, B256, Bytes, U64, U256, keccak256};
 use alloy_rpc_types_engine::{
-    BlobsBundleV1, ExecutionPayloadV1, ExecutionPayloadV2, ExecutionPayloadV3,
+    BlobsBundleV1, ExecutionPayloadBodiesV1, ExecutionPayloadEnvelopeV2, ExecutionPayloadFieldV2,
+    ExecutionPayloadV1, ExecutionPayloadV2, ExecutionPayloadV3,
 };
 use alloy_rpc_types_engine::{ForkchoiceState, ForkchoiceUpdated, PayloadId, PayloadStatus};
-use alloy_rpc_types_eth::{Block, BlockNumberOrTag};
+use alloy_rpc_types_eth::{Block, BlockNumberOrTag, Withdrawal};
 use core::net::SocketAddr;
 use jsonrpsee::core::async_trait;
+use metrics::{counter, gauge, histogram};
 use op_alloy_rpc_types_engine::{
     OpExecutionPayloadEnvelopeV3, OpExecutionPayloadEnvelopeV4, OpExecutionPayloadV4,
     OpPayloadAttributes,
 };
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
-use tokio::sync::RwLock;
-use tokio::sync::mpsc;
-use tracing::error;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{error, info, info_span, warn};
+
+/// Default age of the block being built after which its flashblocks are discarded: a 2s
+/// block time and a 1s grace period.
+pub const DEFAULT_STALENESS_TTL: Duration = Duration::from_secs(3);
 
 #[derive(Debug, Error)]
 pub enum FlashblocksError {
@@ -37,11 +62,233 @@ pub enum FlashblocksError {
     InvalidIndex,
     #[error("Missing payload")]
     MissingPayload,
+    #[error("Too many flashblocks for payload, at most {max} are accepted")]
+    TooManyFlashblocks { max: usize },
+    #[error("Too many transactions in flashblock delta: {count}, at most {max} are accepted")]
+    TooManyTransactions { count: usize, max: usize },
+    #[error("Flashblock delta too large: {size} bytes, at most {max} are accepted")]
+    DeltaTooLarge { size: usize, max: usize },
+    #[error("Payload too large: {size} bytes, at most {max} are accepted")]
+    PayloadTooLarge { size: usize, max: usize },
+    #[error("Extra data {actual} does not encode the eip1559 params {expected}")]
+    Eip1559ParamsMismatch { expected: B64, actual: Bytes },
+    #[error("Transaction {hash} was already streamed for this payload")]
+    DuplicateTransaction { hash: B256 },
+    #[error("Gas used {gas_used} is lower than the {previous} of the previous delta")]
+    GasUsedDecreased { gas_used: u64, previous: u64 },
+    #[error("Gas used {gas_used} exceeds the gas limit {gas_limit} of the block")]
+    GasLimitExceeded { gas_used: u64, gas_limit: u64 },
+    #[error("Missing withdrawals root in the last flashblock delta")]
+    MissingWithdrawalsRoot,
+    #[error("State root {state_root} changed from {previous} without any transaction")]
+    StateRootChanged { state_root: B256, previous: B256 },
+    #[error("State root {state_root} is unchanged by the transactions of the delta")]
+    StateRootUnchanged { state_root: B256 },
+    #[error("Missing state root in the last flashblock delta, although earlier deltas had one")]
+    MissingStateRoot,
+    #[error(
+        "Flashblock received {interval_ms}ms after the previous one, at least {min_ms}ms are expected"
+    )]
+    DeltaTooFast { interval_ms: u64, min_ms: u64 },
+    #[error("Withdrawals root {actual} does not match the root {expected} of the withdrawals")]
+    WithdrawalsRootMismatch { expected: B256, actual: B256 },
+    #[error(transparent)]
+    DeniedTransaction(#[from] DeniedTransaction),
+}
+
+impl FlashblocksError {
+    /// The limit exceeded by a rejected flashblock, if any.
+    fn limit(&self) -> Option<&'static str> {
+        match self {
+            FlashblocksError::TooManyFlashblocks { .. } => Some("flashblocks_per_payload"),
+            FlashblocksError::TooManyTransactions { .. } => Some("delta_transactions"),
+            FlashblocksError::DeltaTooLarge { .. } => Some("delta_bytes"),
+            FlashblocksError::PayloadTooLarge { .. } => Some("payload_bytes"),
+            FlashblocksError::DeltaTooFast { .. } => Some("min_delta_interval"),
+            _ => None,
+        }
+    }
+
+    /// The failure recorded against the builder for a rejected flashblock.
+    fn validation_failure(&self) -> ValidationFailure {
+        match self {
+            FlashblocksError::Eip1559ParamsMismatch { .. } => ValidationFailure::Eip1559Params,
+            FlashblocksError::DuplicateTransaction { .. } => {
+                ValidationFailure::DuplicateTransaction
+            }
+            FlashblocksError::GasUsedDecreased { .. }
+            | FlashblocksError::GasLimitExceeded { .. } => ValidationFailure::GasUsed,
+            FlashblocksError::StateRootChanged { .. }
+            | FlashblocksError::StateRootUnchanged { .. }
+            | FlashblocksError::MissingStateRoot => ValidationFailure::StateRoot,
+            FlashblocksError::DeltaTooFast { .. } => ValidationFailure::Cadence,
+            e if e.limit().is_some() => ValidationFailure::LimitExceeded,
+            _ => ValidationFailure::IndexSequence,
+        }
+    }
+}
+
+/// Checks that the extra data of `base` encodes its Holocene EIP-1559 params, if any,
+/// and the ones of the payload attributes. Zero params in the attributes mean the chain
+/// defaults are used, which are not known here.
+fn validate_eip_1559_params(
+    base: &ExecutionPayloadBaseV1,
+    expected: Option<B64>,
+) -> Result<(), FlashblocksError> {
+    let actual = base.extra_data_eip_1559_params();
+    for expected in [
+        base.eip_1559_params,
+        expected.filter(|params| !params.is_zero()),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        if actual != Some(expected) {
+            return Err(FlashblocksError::Eip1559ParamsMismatch {
+                expected,
+                actual: base.extra_data.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Checks the withdrawals root of an Isthmus payload against its withdrawals. A block
+/// without withdrawals commits to the storage of the L2 to L1 message passer instead,
+/// which cannot be computed here, so its root must only be set.
+fn validate_withdrawals_root(
+    withdrawals: &[Withdrawal],
+    withdrawals_root: B256,
+) -> Result<(), FlashblocksError> {
+    if withdrawals.is_empty() {
+        return match withdrawals_root.is_zero() {
+            true => Err(FlashblocksError::MissingWithdrawalsRoot),
+            false => Ok(()),
+        };
+    }
+
+    let expected = proofs::calculate_withdrawals_root(withdrawals);
+    if expected != withdrawals_root {
+        return Err(FlashblocksError::WithdrawalsRootMismatch {
+            expected,
+            actual: withdrawals_root,
+        });
+    }
+    Ok(())
+}
+
+/// Outcome of a flashblock checked by [`validate_flashblocks`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FlashblockOutcome {
+    pub index: u64,
+    /// Why the flashblock was rejected, if it was
+    pub error: Option<String>,
+}
+
+/// Result of checking a stream of flashblocks against the rules applied by rollup-boost,
+/// returned by `debug_validateFlashblocks`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Whether every flashblock was accepted and the assembled payload is consistent
+    pub valid: bool,
+    /// Outcome of each flashblock, in the order they were given
+    pub flashblocks: Vec<FlashblockOutcome>,
+    /// Errors of the payload assembled from the accepted flashblocks
+    pub payload_errors: Vec<String>,
+}
+
+/// Runs the checks applied to the flashblocks of a payload, in order, without touching
+/// the state of any service, so that builder developers can test their streams: the
+/// payload ID, indices and base rules, the EIP-1559 params, the default limits, duplicate
+/// transactions, the gas used and the state root of each flashblock, then the state root,
+/// withdrawals root and block hash of the assembled payload.
+///
+/// The payload is assembled as an Isthmus payload if the last delta has a withdrawals
+/// root, and as an Ecotone payload otherwise.
+pub fn validate_flashblocks(flashblocks: Vec<FlashblocksPayloadV1>) -> ValidationReport {
+    assemble_flashblocks(flashblocks).0
+}
+
+/// Like [`validate_flashblocks`], also returning the payload assembled from the accepted
+/// flashblocks, if it could be.
+pub fn assemble_flashblocks(
+    flashblocks: Vec<FlashblocksPayloadV1>,
+) -> (ValidationReport, Option<OpExecutionPayloadEnvelope>) {
+    let Some(payload_id) = flashblocks.first().map(|flashblock| flashblock.payload_id) else {
+        let report = ValidationReport {
+            payload_errors: vec![FlashblocksError::MissingPayload.to_string()],
+            ..Default::default()
+        };
+        return (report, None);
+    };
+
+    let limits = FlashblocksLimits::default();
+    let mut builder = FlashblockBuilder::new();
+    let mut outcomes = Vec::with_capacity(flashblocks.len());
+    for flashblock in flashblocks {
+        let index = flashblock.index;
+        let error = if flashblock.payload_id != payload_id {
+            Some(format!(
+                "Payload ID {} does not match the payload ID {payload_id} of the first flashblock",
+                flashblock.payload_id
+            ))
+        } else {
+            flashblock
+                .base
+                .as_ref()
+                .map_or(Ok(()), |base| validate_eip_1559_params(base, None))
+                .and_then(|()| builder.extend(flashblock, DEFAULT_BUILDER, &limits))
+                .err()
+                .map(|e| e.to_string())
+        };
+        outcomes.push(FlashblockOutcome { index, error });
+    }
+
+    let version = match builder.flashblocks.last() {
+        Some(diff) if !diff.withdrawals_root.is_zero() => PayloadVersion::V4,
+        _ => PayloadVersion::V3,
+    };
+    let mut payload_errors = vec![];
+    let envelope = match builder.into_envelope(version) {
+        Ok(envelope) => {
+            let block_hash = envelope.header().hash_slow();
+            if block_hash != envelope.block_hash() {
+                payload_errors.push(format!(
+                    "Block hash {} does not match the hash {block_hash} of the assembled payload",
+                    envelope.block_hash()
+                ));
+            }
+            Some(envelope)
+        }
+        Err(e) => {
+            payload_errors.push(e.to_string());
+            None
+        }
+    };
+
+    let report = ValidationReport {
+        valid: payload_errors.is_empty() && outcomes.iter().all(|o| o.error.is_none()),
+        flashblocks: outcomes,
+        payload_errors,
+    };
+    (report, envelope)
 }
 
-impl From<FlashblocksError> for RpcClientError {
-    fn from(err: FlashblocksError) -> Self {
-        RpcClientError::InvalidPayload(err.to_string())
+impl EngineApiError for FlashblocksError {
+    fn code(&self) -> i32 {
+        match self {
+            FlashblocksError::MissingPayload => UNKNOWN_PAYLOAD_CODE,
+            _ => SERVER_ERROR_CODE,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            FlashblocksError::MissingPayload => "unknown_payload",
+            FlashblocksError::DeniedTransaction(_) => "denied_transaction",
+            e if e.limit().is_some() => "flashblocks_limit_exceeded",
+            _ => "invalid_flashblocks",
+        }
     }
 }
 
@@ -53,16 +300,106 @@ struct FlashbotsMessage {
     id: Option<u64>,
 }
 
-// Simplify actor messages to just handle shutdown
+/// Number of messages queued for the payload state task before senders wait.
+const MESSAGE_QUEUE_SIZE: usize = 100;
+
+/// A flashblock received on the stream of `builder`.
+#[derive(Debug, Clone)]
+pub struct BuilderFlashblock {
+    pub builder: Arc<str>,
+    pub payload: FlashblocksPayloadV1,
+}
+
+// Messages handled by the task owning the payload being built
 #[derive(Debug)]
 enum FlashblocksEngineMessage {
-    FlashblocksPayloadV1(FlashblocksPayloadV1),
+    // Flashblock streamed by a builder
+    Flashblock(BuilderFlashblock),
+    // Flashblock replicated from the leader, accumulated without being published
+    SyncedFlashblock(FlashblocksPayloadV1),
+    SetPayloadId {
+        payload_id: PayloadId,
+        correlation_id: Option<CorrelationId>,
+        eip_1559_params: Option<B64>,
+    },
+    ClearPayloadId,
+    // Merges the deltas of the other builders into the ones of the best payload, without
+    // resetting the builder
+    MergeTransactions {
+        reply: oneshot::Sender<Option<MergedTransactions>>,
+    },
+    // Takes the payload assembled from the flashblocks and resets the builder
+    TakeBestPayload {
+        version: PayloadVersion,
+        reply: oneshot::Sender<Result<Option<OpExecutionPayloadEnvelope>, FlashblocksError>>,
+    },
+    // Drops the accumulated flashblocks and the current payload ID, replying with it
+    Reset {
+        reply: oneshot::Sender<PayloadId>,
+    },
+}
+
+/// Limits on the flashblocks accepted from the builder, so that a misbehaving builder
+/// cannot make the payload being built grow unboundedly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlashblocksLimits {
+    pub max_per_payload: usize,
+    pub max_delta_transactions: usize,
+    pub max_delta_bytes: usize,
+    pub max_payload_bytes: usize,
+    pub duplicate_transactions: DuplicateTransactionPolicy,
+    /// Deltas received sooner than this after the previous one are rejected
+    pub min_delta_interval: Duration,
+    /// Deltas received later than this after the previous one are reported as too slow
+    pub max_delta_interval: Option<Duration>,
+}
+
+impl Default for FlashblocksLimits {
+    fn default() -> Self {
+        Self {
+            max_per_payload: 1000,
+            max_delta_transactions: 10_000,
+            max_delta_bytes: 32 * 1024 * 1024,
+            max_payload_bytes: 128 * 1024 * 1024,
+            duplicate_transactions: DuplicateTransactionPolicy::default(),
+            min_delta_interval: Duration::ZERO,
+            max_delta_interval: None,
+        }
+    }
+}
+
+impl FlashblocksLimits {
+    pub fn from_args(args: &FlashblocksArgs) -> Self {
+        Self {
+            max_per_payload: args.flashblocks_max_per_payload,
+            max_delta_transactions: args.flashblocks_max_delta_transactions,
+            max_delta_bytes: args.flashblocks_max_delta_bytes,
+            max_payload_bytes: args.flashblocks_max_payload_bytes,
+            duplicate_transactions: args.flashblocks_duplicate_transactions,
+            min_delta_interval: Duration::from_millis(args.flashblocks_min_delta_interval_ms),
+            max_delta_interval: args
+                .flashblocks_max_delta_interval_ms
+                .map(Duration::from_millis),
+        }
+    }
+}
+
+/// Size in bytes of the transactions and withdrawals of a delta.
+fn delta_size(diff: &ExecutionPayloadFlashblockDeltaV1) -> usize {
+    diff.transactions.iter().map(|tx| tx.len()).sum::<usize>()
+        + diff.withdrawals.len() * std::mem::size_of::<Withdrawal>()
 }
 
 #[derive(Debug, Default)]
 struct FlashblockBuilder {
     base: Option<ExecutionPayloadBaseV1>,
     flashblocks: Vec<ExecutionPayloadFlashblockDeltaV1>,
+    // Arrival time of the last accepted flashblock
+    last_received_at: Option<Instant>,
+    // Size of the transactions and withdrawals of the accepted flashblocks
+    size: usize,
+    // Hashes of the transactions of the accepted flashblocks
+    tx_hashes: HashSet<B256>,
 }
 
 impl FlashblockBuilder {
@@ -70,20 +407,93 @@ impl FlashblockBuilder {
         Self {
             base: None,
             flashblocks: Vec::new(),
+            last_received_at: None,
+            size: 0,
+            tx_hashes: HashSet::new(),
         }
     }
 
-    pub fn extend(&mut self, payload: FlashblocksPayloadV1) -> Result<(), FlashblocksError> {
-        tracing::debug!(message = "Extending payload", payload_id = %payload.payload_id, index = payload.index, has_base=payload.base.is_some());
+    /// Validates and accumulates a flashblock streamed by `builder`.
+    pub fn extend(
+        &mut self,
+        payload: FlashblocksPayloadV1,
+        builder: &str,
+        limits: &FlashblocksLimits,
+    ) -> Result<(), FlashblocksError> {
+        let delta_size = self.validate(&payload, builder, limits)?;
+        self.push(payload, delta_size, builder, limits);
+        Ok(())
+    }
+
+    /// The accumulated flashblocks of `payload_id` in order, without their metadata.
+    fn sequence(&self, payload_id: PayloadId) -> Vec<FlashblocksPayloadV1> {
+        self.flashblocks
+            .iter()
+            .enumerate()
+            .map(|(index, diff)| FlashblocksPayloadV1 {
+                payload_id,
+                index: index as u64,
+                base: self.base.clone().filter(|_| index == 0),
+                diff: diff.clone(),
+                metadata: serde_json::Value::Null,
+            })
+            .collect()
+    }
+
+    /// Checks that `payload`, streamed by `builder`, can extend the payload being built,
+    /// without accumulating it. Returns the size of its delta.
+    fn validate(
+        &self,
+        payload: &FlashblocksPayloadV1,
+        builder: &str,
+        limits: &FlashblocksLimits,
+    ) -> Result<usize, FlashblocksError> {
+        // Reject anything beyond the limits before accumulating it
+        if self.flashblocks.len() >= limits.max_per_payload {
+            return Err(FlashblocksError::TooManyFlashblocks {
+                max: limits.max_per_payload,
+            });
+        }
+        let count = payload.diff.transactions.len();
+        if count > limits.max_delta_transactions {
+            return Err(FlashblocksError::TooManyTransactions {
+                count,
+                max: limits.max_delta_transactions,
+            });
+        }
+        let delta_size = delta_size(&payload.diff);
+        if delta_size > limits.max_delta_bytes {
+            return Err(FlashblocksError::DeltaTooLarge {
+                size: delta_size,
+                max: limits.max_delta_bytes,
+            });
+        }
+        if self.size + delta_size > limits.max_payload_bytes {
+            return Err(FlashblocksError::PayloadTooLarge {
+                size: self.size + delta_size,
+                max: limits.max_payload_bytes,
+            });
+        }
+
+        // Reject deltas streamed implausibly fast, which may be replayed or flooded
+        if let Some(interval) = self
+            .last_received_at
+            .map(|at| at.elapsed())
+            .filter(|interval| *interval < limits.min_delta_interval)
+        {
+            return Err(FlashblocksError::DeltaTooFast {
+                interval_ms: interval.as_millis() as u64,
+                min_ms: limits.min_delta_interval.as_millis() as u64,
+            });
+        }
 
         // Check base payload rules
-        match (payload.index, payload.base) {
+        match (payload.index, &payload.base) {
             // First payload must have a base
             (0, None) => return Err(FlashblocksError::MissingBasePayload),
-            (0, Some(base)) => self.base = Some(base),
             // Subsequent payloads must have no base
-            (_, Some(_)) => return Err(FlashblocksError::UnexpectedBasePayload),
-            (_, None) => {} // Non-zero index without base is fine
+            (1.., Some(_)) => return Err(FlashblocksError::UnexpectedBasePayload),
+            _ => {}
         }
 
         // Validate the index is contiguous
@@ -91,10 +501,118 @@ impl FlashblockBuilder {
             return Err(FlashblocksError::InvalidIndex);
         }
 
+        if let Some(hash) = self.duplicate_transactions(payload).next() {
+            counter!("flashblocks.duplicate_transactions", "builder" => builder.to_string(), "policy" => limits.duplicate_transactions.as_str())
+                .increment(1);
+            return Err(FlashblocksError::DuplicateTransaction { hash });
+        }
+
+        // The gas used is cumulative over the deltas, and bounded by the gas limit of the
+        // block, so that an inconsistent delta is rejected here rather than by the proposer
+        let gas_used = payload.diff.gas_used;
+        let previous = self.flashblocks.last().map_or(0, |diff| diff.gas_used);
+        if gas_used < previous {
+            return Err(FlashblocksError::GasUsedDecreased { gas_used, previous });
+        }
+        let gas_limit = payload
+            .base
+            .as_ref()
+            .or(self.base.as_ref())
+            .map_or(u64::MAX, |base| base.gas_limit);
+        if gas_used > gas_limit {
+            return Err(FlashblocksError::GasLimitExceeded {
+                gas_used,
+                gas_limit,
+            });
+        }
+
+        // Builders may only compute the state root of the last delta. When both deltas
+        // have one, the state only changes if transactions were added, as every
+        // transaction at least bumps the nonce of its sender
+        let state_root = payload.diff.state_root;
+        let previous = self
+            .flashblocks
+            .last()
+            .map_or(B256::ZERO, |diff| diff.state_root);
+        if !state_root.is_zero() && !previous.is_zero() {
+            let added = !payload.diff.transactions.is_empty();
+            if added && state_root == previous {
+                return Err(FlashblocksError::StateRootUnchanged { state_root });
+            }
+            if !added && state_root != previous {
+                return Err(FlashblocksError::StateRootChanged {
+                    state_root,
+                    previous,
+                });
+            }
+        }
+
+        Ok(delta_size)
+    }
+
+    /// Hashes of the transactions of `payload` that are already part of the payload
+    /// being built, or repeated within its delta.
+    fn duplicate_transactions(&self, payload: &FlashblocksPayloadV1) -> impl Iterator<Item = B256> {
+        let mut seen = HashSet::new();
+        payload
+            .diff
+            .transactions
+            .iter()
+            .map(keccak256)
+            .filter(move |hash| self.tx_hashes.contains(hash) || !seen.insert(*hash))
+    }
+
+    /// Accumulates a flashblock of `builder` that passed [`Self::validate`]. Returns
+    /// whether it was received within the maximum interval of the previous one.
+    fn push(
+        &mut self,
+        payload: FlashblocksPayloadV1,
+        delta_size: usize,
+        builder: &str,
+        limits: &FlashblocksLimits,
+    ) -> bool {
+        tracing::debug!(message = "Extending payload", payload_id = %payload.payload_id, index = payload.index, has_base=payload.base.is_some());
+        let (payload_id, index) = (payload.payload_id, payload.index);
+
+        if let Some(base) = payload.base {
+            self.base = Some(base);
+        }
+
         // Update latest diff and accumulate transactions and withdrawals
+        self.tx_hashes
+            .extend(payload.diff.transactions.iter().map(keccak256));
         self.flashblocks.push(payload.diff);
+        self.size += delta_size;
 
-        Ok(())
+        // Track the cadence at which the builder is streaming deltas
+        let now = Instant::now();
+        let mut on_time = true;
+        if let Some(last_received_at) = self.last_received_at {
+            let interval = now - last_received_at;
+            histogram!("flashblocks.delta_interval").record(interval);
+            if limits.max_delta_interval.is_some_and(|max| interval > max) {
+                counter!("flashblocks.cadence_violations", "builder" => builder.to_string(), "kind" => "too_slow")
+                    .increment(1);
+                warn!(message = "Builder is streaming flashblocks slower than the target cadence", %builder, %payload_id, index, interval_ms = interval.as_millis() as u64);
+                on_time = false;
+            }
+        }
+        self.last_received_at = Some(now);
+        on_time
+    }
+
+    /// Whether the block being built is older than `ttl` at `now`, the duration since the
+    /// Unix epoch.
+    fn is_stale(&self, now: Duration, ttl: Duration) -> bool {
+        self.base
+            .as_ref()
+            .is_some_and(|base| now > Duration::from_secs(base.timestamp) + ttl)
+    }
+
+    fn transactions(&self) -> impl Iterator<Item = &Bytes> {
+        self.flashblocks
+            .iter()
+            .flat_map(|diff| diff.transactions.iter())
     }
 
     pub fn into_envelope(
@@ -103,51 +621,85 @@ impl FlashblockBuilder {
     ) -> Result<OpExecutionPayloadEnvelope, FlashblocksError> {
         let base = self.base.ok_or(FlashblocksError::MissingPayload)?;
 
-        // There must be at least one delta
+        // There must be at least one delta. The roots and hashes of the block are those
+        // of the last one
         let diff = self
             .flashblocks
             .last()
             .ok_or(FlashblocksError::MissingDelta)?;
+        let (state_root, receipts_root, logs_bloom, gas_used, block_hash, withdrawals_root) = (
+            diff.state_root,
+            diff.receipts_root,
+            diff.logs_bloom,
+            diff.gas_used,
+            diff.block_hash,
+            diff.withdrawals_root,
+        );
 
-        let transactions = self
+        // The state root of the block is the one of the last delta, which must not be
+        // left out by a builder streaming intermediate state roots
+        if state_root.is_zero()
+            && self
+                .flashblocks
+                .iter()
+                .any(|diff| !diff.state_root.is_zero())
+        {
+            return Err(FlashblocksError::MissingStateRoot);
+        }
+
+        // Move the transactions and withdrawals out of the deltas instead of copying them
+        let tx_count = self
             .flashblocks
             .iter()
-            .flat_map(|diff| diff.transactions.clone())
-            .collect();
-
-        let withdrawals = self
+            .map(|diff| diff.transactions.len())
+            .sum();
+        let withdrawal_count = self
             .flashblocks
             .iter()
-            .flat_map(|diff| diff.withdrawals.clone())
-            .collect();
-
-        let withdrawals_root = diff.withdrawals_root;
+            .map(|diff| diff.withdrawals.len())
+            .sum();
+        let mut transactions = Vec::with_capacity(tx_count);
+        let mut withdrawals = Vec::with_capacity(withdrawal_count);
+        for diff in self.flashblocks {
+            transactions.extend(diff.transactions);
+            withdrawals.extend(diff.withdrawals);
+        }
 
+        let payload_inner = ExecutionPayloadV1 {
+            parent_hash: base.parent_hash,
+            fee_recipient: base.fee_recipient,
+            state_root,
+            receipts_root,
+            logs_bloom,
+            prev_randao: base.prev_randao,
+            block_number: base.block_number,
+            gas_limit: base.gas_limit,
+            gas_used,
+            timestamp: base.timestamp,
+            extra_data: base.extra_data,
+            base_fee_per_gas: base.base_fee_per_gas,
+            block_hash,
+            transactions,
+        };
+        let payload_inner = ExecutionPayloadV2 {
+            withdrawals,
+            payload_inner,
+        };
         let execution_payload = ExecutionPayloadV3 {
             blob_gas_used: 0,
             excess_blob_gas: 0,
-            payload_inner: ExecutionPayloadV2 {
-                withdrawals,
-                payload_inner: ExecutionPayloadV1 {
-                    parent_hash: base.parent_hash,
-                    fee_recipient: base.fee_recipient,
-                    state_root: diff.state_root,
-                    receipts_root: diff.receipts_root,
-                    logs_bloom: diff.logs_bloom,
-                    prev_randao: base.prev_randao,
-                    block_number: base.block_number,
-                    gas_limit: base.gas_limit,
-                    gas_used: diff.gas_used,
-                    timestamp: base.timestamp,
-                    extra_data: base.extra_data,
-                    base_fee_per_gas: base.base_fee_per_gas,
-                    block_hash: diff.block_hash,
-                    transactions,
-                },
-            },
+            payload_inner,
         };
 
         match version {
+            // Blocks before Canyon have no withdrawals
+            PayloadVersion::V1 => Ok(OpExecutionPayloadEnvelope::V1(
+                execution_payload.payload_inner.payload_inner,
+            )),
+            PayloadVersion::V2 => Ok(OpExecutionPayloadEnvelope::V2(ExecutionPayloadEnvelopeV2 {
+                execution_payload: ExecutionPayloadFieldV2::V2(execution_payload.payload_inner),
+                block_value: U256::ZERO,
+            })),
             PayloadVersion::V3 => Ok(OpExecutionPayloadEnvelope::V3(
                 OpExecutionPayloadEnvelopeV3 {
                     parent_beacon_block_root: base.parent_beacon_block_root,
@@ -157,20 +709,579 @@ impl FlashblockBuilder {
                     execution_payload,
                 },
             )),
-            PayloadVersion::V4 => Ok(OpExecutionPayloadEnvelope::V4(
-                OpExecutionPayloadEnvelopeV4 {
-                    parent_beacon_block_root: base.parent_beacon_block_root,
-                    block_value: U256::ZERO,
-                    blobs_bundle: BlobsBundleV1::default(),
-                    should_override_builder: false,
-                    execution_payload: OpExecutionPayloadV4 {
-                        withdrawals_root,
-                        payload_inner: execution_payload,
+            PayloadVersion::V4 => {
+                // Never forward a block whose header cannot match its body
+                validate_withdrawals_root(
+                    &execution_payload.payload_inner.withdrawals,
+                    withdrawals_root,
+                )?;
+                Ok(OpExecutionPayloadEnvelope::V4(
+                    OpExecutionPayloadEnvelopeV4 {
+                        parent_beacon_block_root: base.parent_beacon_block_root,
+                        block_value: U256::ZERO,
+                        blobs_bundle: BlobsBundleV1::default(),
+                        should_override_builder: false,
+                        execution_payload: OpExecutionPayloadV4 {
+                            withdrawals_root,
+                            payload_inner: execution_payload,
+                        },
+                        execution_requests: vec![],
                     },
-                    execution_requests: vec![],
-                },
-            )),
+                ))
+            }
+        }
+    }
+}
+
+/// The current payload ID, read without locking by the flashblocks ingest path while
+/// the forkchoice updates change it.
+#[derive(Debug, Default)]
+pub(crate) struct AtomicPayloadId(AtomicU64);
+
+impl AtomicPayloadId {
+    pub(crate) fn load(&self) -> PayloadId {
+        PayloadId::new(self.0.load(Ordering::Acquire).to_be_bytes())
+    }
+
+    pub(crate) fn store(&self, payload_id: PayloadId) {
+        self.0
+            .store(u64::from_be_bytes(payload_id.0.0), Ordering::Release);
+    }
+
+    /// Sets the payload ID to the default one, returning the previous one.
+    pub(crate) fn take(&self) -> PayloadId {
+        PayloadId::new(self.0.swap(0, Ordering::AcqRel).to_be_bytes())
+    }
+}
+
+/// State of the payload being built, owned by the task started by
+/// [`FlashblocksService::run`]. The payload ID and the accumulated flashblocks are only
+/// ever changed by this task, in the order the messages were sent, so that taking the
+/// best payload and resetting the builder is atomic with respect to the flashblocks
+/// ingest and the forkchoice updates.
+struct FlashblocksActor {
+    // Current payload ID we're processing (set from external notification), shared
+    // with the flashblocks receiver so that it drops flashblocks of other payloads
+    current_payload_id: Arc<AtomicPayloadId>,
+
+    // Correlation id of the FCU request that started building the current payload
+    current_correlation_id: Option<CorrelationId>,
+
+    // Holocene eip1559 params of the attributes of the current payload
+    eip_1559_params: Option<B64>,
+
+    // When the FCU that started building the current payload was received
+    payload_started_at: Option<Instant>,
+
+    // When the current payload ID and its flashblocks are dropped if the payload is not
+    // taken by then
+    payload_expires_at: Option<Instant>,
+    payload_id_ttl: Option<Duration>,
+
+    // flashblocks payload being constructed
+    best_payload: FlashblockBuilder,
+
+    // Whether the flashblocks of the current payload were discarded, in which case the
+    // payload is fetched from the builder
+    payload_discarded: bool,
+
+    // Builder whose flashblocks are accumulated in the best payload, and the flashblocks
+    // streamed by the other builders for the same payload
+    best_builder: Option<Arc<str>>,
+    competing: HashMap<Arc<str>, FlashblockBuilder>,
+    conflict_resolution: ConflictResolution,
+
+    // Number of flashblocks the last payload taken was assembled from
+    last_payload_flashblocks: Arc<AtomicUsize>,
+
+    ws_pub: Arc<WebSocketPublisher>,
+    denylist: Option<Arc<TransactionDenylist>>,
+    quarantine: Option<Arc<BuilderQuarantine>>,
+    builder_scores: Option<Arc<BuilderScores>>,
+    leader_election: Option<Arc<LeaderElection>>,
+    state_sync: Option<Arc<StateSync>>,
+    limits: FlashblocksLimits,
+    staleness_ttl: Duration,
+    clock_skew: ClockSkewCheck,
+    fault_injector: Option<Arc<FaultInjector>>,
+    clickhouse: Option<Arc<ClickHouseSink>>,
+    archive: Option<Arc<PayloadArchive>>,
+    recent_flashblocks: Option<Arc<RecentFlashblocks>>,
+}
+
+impl FlashblocksActor {
+    async fn run(
+        mut self,
+        mut messages: mpsc::Receiver<FlashblocksEngineMessage>,
+        mut stream: mpsc::Receiver<BuilderFlashblock>,
+    ) {
+        loop {
+            let expires_at = self.payload_expires_at;
+            let expiry = tokio::time::Instant::from_std(expires_at.unwrap_or_else(Instant::now));
+            tokio::select! {
+                // Engine API calls go first so that a burst of flashblocks never delays
+                // a get_payload
+                biased;
+                Some(message) = messages.recv() => self.on_message(message).await,
+                () = tokio::time::sleep_until(expiry), if expires_at.is_some() => {
+                    self.expire_payload_id()
+                }
+                Some(flashblock) = stream.recv() => {
+                    self.on_message(FlashblocksEngineMessage::Flashblock(flashblock)).await
+                }
+                else => break,
+            }
+        }
+    }
+
+    async fn on_message(&mut self, message: FlashblocksEngineMessage) {
+        match message {
+            FlashblocksEngineMessage::Flashblock(BuilderFlashblock { builder, payload }) => {
+                if self
+                    .fault_injector
+                    .as_ref()
+                    .is_some_and(|faults| faults.drop_flashblock())
+                {
+                    return;
+                }
+
+                // Attach the correlation id of the originating FCU so flashblocks events
+                // can be joined with the engine API calls for the same payload
+                let correlation_id = self
+                    .current_correlation_id
+                    .as_ref()
+                    .map(ToString::to_string)
+                    .unwrap_or_default();
+                let span = info_span!("flashblock", %correlation_id);
+                let _enter = span.enter();
+
+                self.on_flashblock(builder, payload);
+            }
+            FlashblocksEngineMessage::SyncedFlashblock(payload) => {
+                self.apply_synced_flashblock(payload);
+            }
+            FlashblocksEngineMessage::SetPayloadId {
+                payload_id,
+                correlation_id,
+                eip_1559_params,
+            } => self.set_current_payload_id(payload_id, correlation_id, eip_1559_params),
+            FlashblocksEngineMessage::ClearPayloadId => self.clear_current_payload_id(),
+            FlashblocksEngineMessage::MergeTransactions { reply } => {
+                let _ = reply.send(self.merge_transactions().await);
+            }
+            FlashblocksEngineMessage::TakeBestPayload { version, reply } => {
+                let _ = reply.send(self.take_best_payload(version).await);
+            }
+            FlashblocksEngineMessage::Reset { reply } => {
+                let payload_id = self.current_payload_id.take();
+                self.payload_expires_at = None;
+                self.reset_payload();
+                let _ = reply.send(payload_id);
+            }
+        }
+    }
+
+    fn on_flashblock(&mut self, builder: Arc<str>, payload: FlashblocksPayloadV1) {
+        tracing::debug!(
+            message = "Received flashblock payload",
+            %builder,
+            payload_id = %payload.payload_id,
+            index = payload.index
+        );
+
+        if self
+            .leader_election
+            .as_ref()
+            .is_some_and(|leader| !leader.is_leader())
+        {
+            tracing::debug!(message = "Standing by, ignoring flashblock");
+            return;
+        }
+
+        // make sure the payload id matches the current payload id
+        if self.current_payload_id.load() != payload.payload_id {
+            error!(message = "Payload ID mismatch",);
+            return;
+        }
+        if self.payload_discarded {
+            tracing::debug!(message = "Payload discarded, ignoring flashblock");
+            return;
+        }
+        if let Some(recent_flashblocks) = &self.recent_flashblocks {
+            recent_flashblocks.record(&payload);
+        }
+        if self
+            .best_builder
+            .as_ref()
+            .is_some_and(|best| *best != builder)
+        {
+            self.on_competing_flashblock(builder, payload);
+            return;
+        }
+        if let Some(base) = &payload.base {
+            self.check_clock_skew(base);
+        }
+
+        self.discard_if_stale();
+        let result = payload
+            .base
+            .as_ref()
+            .map_or(Ok(()), |base| {
+                validate_eip_1559_params(base, self.eip_1559_params)
+            })
+            .and_then(|()| self.best_payload.validate(&payload, &builder, &self.limits));
+        match result {
+            Ok(delta_size) => {
+                if let Some(clickhouse) = &self.clickhouse {
+                    let block_number = payload
+                        .base
+                        .as_ref()
+                        .or(self.best_payload.base.as_ref())
+                        .map_or(0, |base| base.block_number);
+                    let fcu_latency = self.payload_started_at.map(|at| at.elapsed());
+                    clickhouse.record(FlashblockRow::new(&payload, block_number, fcu_latency));
+                }
+
+                // Broadcast the valid message before accumulating it, so that
+                // the flashblock is never copied
+                if let Err(e) = self.ws_pub.publish(&payload) {
+                    error!(message = "Failed to broadcast payload", error = %e);
+                }
+                if let Some(state_sync) = &self.state_sync {
+                    state_sync.publish_flashblock(&payload);
+                }
+                // Recover the senders off the actor, so that the denylist check of the
+                // best payload does not have to
+                if let Some(denylist) = &self.denylist {
+                    let denylist = denylist.clone();
+                    let transactions = payload.diff.transactions.clone();
+                    tokio::task::spawn_blocking(move || denylist.recover_senders(&transactions));
+                }
+                let index = payload.index as usize;
+                let on_time = self
+                    .best_payload
+                    .push(payload, delta_size, &builder, &self.limits);
+                if let Some(scores) = &self.builder_scores {
+                    scores.record(&builder, ScoreObservation::Cadence(on_time));
+                }
+                self.best_builder = Some(builder);
+                self.resolve_conflict(index);
+            }
+            Err(e) => {
+                error!(message = "Failed to extend payload", error = %e);
+                if let Some(limit) = e.limit() {
+                    counter!("flashblocks.rejected", "limit" => limit).increment(1);
+                }
+                if matches!(e, FlashblocksError::DeltaTooFast { .. }) {
+                    counter!("flashblocks.cadence_violations", "builder" => builder.to_string(), "kind" => "too_fast")
+                        .increment(1);
+                    if let Some(scores) = &self.builder_scores {
+                        scores.record(&builder, ScoreObservation::Cadence(false));
+                    }
+                }
+                if let Some(quarantine) = &self.quarantine {
+                    quarantine.record_failure(&builder, e.validation_failure());
+                }
+                if matches!(e, FlashblocksError::DuplicateTransaction { .. })
+                    && self.limits.duplicate_transactions == DuplicateTransactionPolicy::Discard
+                {
+                    self.discard_payload();
+                }
+            }
+        }
+    }
+
+    /// Accumulates a flashblock of a builder other than the one whose flashblocks are
+    /// published for the current payload, without publishing it, until both builders
+    /// streamed the same index.
+    fn on_competing_flashblock(&mut self, builder: Arc<str>, payload: FlashblocksPayloadV1) {
+        let index = payload.index as usize;
+        let competing = self.competing.entry(builder.clone()).or_default();
+        let result = payload
+            .base
+            .as_ref()
+            .map_or(Ok(()), |base| {
+                validate_eip_1559_params(base, self.eip_1559_params)
+            })
+            .and_then(|()| competing.extend(payload, &builder, &self.limits));
+        if let Err(e) = result {
+            error!(message = "Failed to extend competing payload", %builder, error = %e);
+            if let Some(quarantine) = &self.quarantine {
+                quarantine.record_failure(&builder, e.validation_failure());
+            }
+            return;
+        }
+        self.resolve_conflict(index);
+    }
+
+    /// Once several builders streamed the flashblock at `index`, makes the flashblocks of
+    /// the builder preferred by the conflict resolution policy the best payload. Since
+    /// the subscribers were sent the flashblocks of the previous best builder, they are
+    /// told to discard them before the flashblocks of the new one are published again
+    /// from the first one.
+    fn resolve_conflict(&mut self, index: usize) {
+        let Some(best_builder) = self.best_builder.clone() else {
+            return;
+        };
+        let Some(best_gas) = self
+            .best_payload
+            .flashblocks
+            .get(index)
+            .map(|diff| diff.gas_used)
+        else {
+            return;
+        };
+
+        let policy = self.conflict_resolution;
+        let mut winner: Option<(Arc<str>, u64)> = None;
+        for (builder, competing) in &self.competing {
+            let Some(gas_used) = competing.flashblocks.get(index).map(|diff| diff.gas_used) else {
+                continue;
+            };
+            let (incumbent, incumbent_gas) = winner
+                .as_ref()
+                .map_or((&best_builder, best_gas), |(builder, gas)| (builder, *gas));
+            let outcome = if policy.prefers(builder, gas_used, incumbent, incumbent_gas) {
+                winner = Some((builder.clone(), gas_used));
+                "switched"
+            } else {
+                "kept"
+            };
+            counter!("flashblocks.stream_conflicts", "policy" => policy.as_str(), "outcome" => outcome)
+                .increment(1);
+        }
+        let Some((winner, _)) = winner else {
+            return;
+        };
+
+        let payload_id = self.current_payload_id.load();
+        info!(message = "Switching the flashblocks stream of the payload", %payload_id, index, from = %best_builder, to = %winner, policy = policy.as_str());
+        let best_payload = self.competing.remove(&winner).unwrap_or_default();
+        let previous = std::mem::replace(&mut self.best_payload, best_payload);
+        self.competing.insert(best_builder, previous);
+        self.best_builder = Some(winner);
+
+        let reset = FlashblocksControlV1::Reset(FlashblocksResetV1 { payload_id });
+        if let Err(e) = self.ws_pub.publish_message(&reset) {
+            error!(message = "Failed to broadcast reset", error = %e);
+        }
+        for flashblock in self.best_payload.sequence(payload_id) {
+            if let Err(e) = self.ws_pub.publish(&flashblock) {
+                error!(message = "Failed to broadcast payload", error = %e);
+            }
+        }
+    }
+
+    /// Drops the flashblocks accumulated for the current payload.
+    fn reset_payload(&mut self) {
+        self.best_payload = FlashblockBuilder::new();
+        self.best_builder = None;
+        self.competing.clear();
+        self.payload_discarded = false;
+    }
+
+    /// Drops the flashblocks of the current payload and ignores its next ones, so that
+    /// the payload is fetched from the builder.
+    fn discard_payload(&mut self) {
+        counter!("flashblocks.payloads_discarded").increment(1);
+        warn!(
+            message = "Discarding the flashblocks of the payload",
+            payload_id = %self.current_payload_id.load(),
+            count = self.best_payload.flashblocks.len()
+        );
+        self.reset_payload();
+        self.payload_discarded = true;
+    }
+
+    fn apply_synced_flashblock(&mut self, payload: FlashblocksPayloadV1) {
+        if self.current_payload_id.load() != payload.payload_id {
+            tracing::debug!(message = "Synced flashblock for another payload, ignoring", payload_id = %payload.payload_id);
+            return;
+        }
+
+        // Replicated flashblocks may arrive in bursts, their cadence was checked by the leader.
+        // The builder that streamed them to the leader is not replicated
+        let limits = FlashblocksLimits {
+            min_delta_interval: Duration::ZERO,
+            max_delta_interval: None,
+            ..self.limits
+        };
+        if let Err(e) = self.best_payload.extend(payload, DEFAULT_BUILDER, &limits) {
+            error!(message = "Failed to extend payload with synced flashblock", error = %e);
+        }
+    }
+
+    fn set_current_payload_id(
+        &mut self,
+        payload_id: PayloadId,
+        correlation_id: Option<CorrelationId>,
+        eip_1559_params: Option<B64>,
+    ) {
+        tracing::debug!(message = "Setting current payload ID", payload_id = %payload_id);
+        if self.current_payload_id.load() != payload_id {
+            // drop any flashblocks left over from a payload that was never retrieved
+            self.reset_payload();
+            let now = Instant::now();
+            self.payload_started_at = Some(now);
+            self.payload_expires_at = self.payload_id_ttl.map(|ttl| now + ttl);
+        }
+        self.current_payload_id.store(payload_id);
+        self.current_correlation_id = correlation_id;
+        self.eip_1559_params = eip_1559_params;
+
+        if let Some(state_sync) = &self.state_sync {
+            state_sync.publish(&StateSyncMessage::PayloadId { payload_id });
+        }
+    }
+
+    fn clear_current_payload_id(&mut self) {
+        tracing::debug!(message = "Clearing current payload ID");
+        self.current_payload_id.store(PayloadId::default());
+        self.current_correlation_id = None;
+        self.eip_1559_params = None;
+        self.payload_started_at = None;
+        self.payload_expires_at = None;
+        self.reset_payload();
+
+        if let Some(state_sync) = &self.state_sync {
+            state_sync.publish(&StateSyncMessage::PayloadId {
+                payload_id: PayloadId::default(),
+            });
+        }
+    }
+
+    /// Compares the timestamp of the block started by `base` to the local time, as a
+    /// builder building for a timestamp that has already passed fails silently otherwise.
+    fn check_clock_skew(&self, base: &ExecutionPayloadBaseV1) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let (skew_ms, skew) = self.clock_skew.check(base.timestamp, now);
+        gauge!("flashblocks.clock_skew_ms").set(skew_ms as f64);
+        if skew == ClockSkew::InTime {
+            return;
+        }
+
+        counter!("flashblocks.clock_skew_detected", "direction" => skew.as_str()).increment(1);
+        match skew {
+            ClockSkew::Past => warn!(
+                message = "Builder is building for a timestamp that has already passed",
+                block_number = base.block_number,
+                timestamp = base.timestamp,
+                skew_ms
+            ),
+            _ => warn!(
+                message = "Builder is building for a timestamp too far in the future",
+                block_number = base.block_number,
+                timestamp = base.timestamp,
+                skew_ms
+            ),
+        }
+    }
+
+    /// Drops the current payload ID and its flashblocks once `get_payload` was not called
+    /// in time, so that the next payload does not build on stale state.
+    fn expire_payload_id(&mut self) {
+        counter!("flashblocks.payload_id_expired").increment(1);
+        warn!(
+            message = "Payload ID expired before get_payload, dropping its flashblocks",
+            payload_id = %self.current_payload_id.load(),
+            count = self.best_payload.flashblocks.len()
+        );
+        self.clear_current_payload_id();
+    }
+
+    /// Transactions of the best payload interleaved with the non-conflicting ones of the
+    /// other builders, if other builders streamed flashblocks for the payload.
+    async fn merge_transactions(&self) -> Option<MergedTransactions> {
+        let base = self.best_payload.base.as_ref()?;
+        if self.competing.is_empty() {
+            return None;
+        }
+
+        // Merge the other builders in a stable order
+        let mut competing = self.competing.iter().collect::<Vec<_>>();
+        competing.sort_by(|a, b| a.0.cmp(b.0));
+        let competing = competing
+            .into_iter()
+            .map(|(_, builder)| builder.flashblocks.as_slice())
+            .collect::<Vec<_>>();
+        let merged = merge_deltas(&self.best_payload.flashblocks, &competing, base.gas_limit);
+        counter!("flashblocks.merge_conflicts").increment(merged.conflicts as u64);
+
+        let denied = match &self.denylist {
+            Some(denylist) => denylist.check_blocking(merged.transactions.clone()).await,
+            None => Ok(()),
+        };
+        if let Err(e) = denied {
+            warn!(message = "Not merging the flashblocks of the other builders", error = %e);
+            return None;
+        }
+        Some(merged)
+    }
+
+    /// Consumes the best payload and resets the builder.
+    async fn take_best_payload(
+        &mut self,
+        version: PayloadVersion,
+    ) -> Result<Option<OpExecutionPayloadEnvelope>, FlashblocksError> {
+        self.payload_expires_at = None;
+        if self.payload_discarded {
+            self.reset_payload();
+            return Ok(None);
         }
+        if self.discard_if_stale() {
+            return Ok(None);
+        }
+        let builder = std::mem::take(&mut self.best_payload);
+        self.reset_payload();
+        let payload_id = self.current_payload_id.load();
+        let sequence = self.archive.as_ref().map(|_| builder.sequence(payload_id));
+        histogram!("flashblocks.count_per_block").record(builder.flashblocks.len() as f64);
+        self.last_payload_flashblocks
+            .store(builder.flashblocks.len(), Ordering::Relaxed);
+        if let Some(denylist) = &self.denylist {
+            denylist
+                .check_blocking(builder.transactions().cloned().collect())
+                .await?;
+        }
+        let envelope = builder.into_envelope(version).inspect_err(|e| match e {
+            FlashblocksError::MissingWithdrawalsRoot
+            | FlashblocksError::WithdrawalsRootMismatch { .. } => {
+                counter!("flashblocks.invalid_withdrawals_root").increment(1);
+            }
+            FlashblocksError::MissingStateRoot => {
+                counter!("flashblocks.missing_state_root").increment(1);
+            }
+            _ => {}
+        })?;
+
+        if let (Some(archive), Some(sequence)) = (&self.archive, sequence) {
+            match SealedPayload::new(payload_id, &envelope, sequence) {
+                Ok(sealed) => archive.record(sealed),
+                Err(e) => warn!(message = "Failed to archive payload", error = %e),
+            }
+        }
+        Ok(Some(envelope))
+    }
+
+    /// Resets the builder if the block it is building is stale. Returns whether it was.
+    fn discard_if_stale(&mut self) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        if !self.best_payload.is_stale(now, self.staleness_ttl) {
+            return false;
+        }
+
+        counter!("flashblocks.stale_discarded").increment(1);
+        warn!(
+            message = "Discarding stale flashblocks",
+            timestamp = self.best_payload.base.as_ref().map(|base| base.timestamp),
+            count = self.best_payload.flashblocks.len()
+        );
+        self.reset_payload();
+        true
     }
 }
 
@@ -178,79 +1289,393 @@ impl FlashblockBuilder {
 pub struct FlashblocksService {
     client: RpcClient,
 
-    // Current payload ID we're processing (set from external notification)
-    current_payload_id: Arc<RwLock<PayloadId>>,
+    // Sends messages to the task owning the payload being built
+    messages: mpsc::Sender<FlashblocksEngineMessage>,
 
-    // flashblocks payload being constructed
-    best_payload: Arc<RwLock<FlashblockBuilder>>,
+    // Current payload ID, only changed by the task owning the payload being built
+    current_payload_id: Arc<AtomicPayloadId>,
+
+    // Receiving end of `messages`, taken by `run`
+    message_receiver: Arc<Mutex<Option<mpsc::Receiver<FlashblocksEngineMessage>>>>,
 
     // websocket publisher for sending valid preconfirmations to clients
     ws_pub: Arc<WebSocketPublisher>,
+
+    // Whether the last payload returned came from flashblocks or the builder client
+    last_payload_source: Arc<Mutex<BlockSource>>,
+
+    // Number of flashblocks the last payload taken from the builder was assembled from
+    last_payload_flashblocks: Arc<AtomicUsize>,
+
+    // Transactions that must not be included in payloads assembled from flashblocks
+    denylist: Option<Arc<TransactionDenylist>>,
+
+    // Invalid flashblock sequences count towards quarantining the builder
+    quarantine: Option<Arc<BuilderQuarantine>>,
+
+    // The cadence of the flashblocks counts towards the quality score of the builder
+    builder_scores: Option<Arc<BuilderScores>>,
+
+    // Flashblocks streams of the other builders, competing with the default builder
+    builder_streams: Vec<BuilderStream>,
+    conflict_resolution: ConflictResolution,
+
+    // Builds the blocks merging the deltas of several builders, and the forkchoice state
+    // and attributes of the current payload they are built with
+    merge_client: Option<RpcClient>,
+    merge_attributes: Arc<Mutex<Option<(PayloadId, ForkchoiceState, OpPayloadAttributes)>>>,
+
+    // Gossip network the flashblocks are published on, whose flashblocks are received as
+    // a builder stream
+    gossip: Option<Arc<FlashblocksGossip>>,
+
+    // Flashblocks are only published by the leader of an active/standby deployment
+    leader_election: Option<Arc<LeaderElection>>,
+
+    // Replicates the payload being built to the standby instance
+    state_sync: Option<Arc<StateSync>>,
+
+    // Notified when the flashblocks stream of the builder is lost
+    webhooks: Option<Arc<WebhookNotifier>>,
+
+    // Caps on the flashblocks accumulated for a payload
+    limits: FlashblocksLimits,
+
+    // Age of the block being built after which its flashblocks are discarded
+    staleness_ttl: Duration,
+
+    // Time after the FCU after which a payload not taken is dropped
+    payload_id_ttl: Option<Duration>,
+
+    // Expected timestamps of the blocks built, to detect a builder clock drift
+    clock_skew: ClockSkewCheck,
+
+    // Drops flashblocks from the builder when chaos mode is enabled
+    fault_injector: Option<Arc<FaultInjector>>,
+
+    // Receives a row for each accepted flashblock, for latency analysis
+    clickhouse: Option<Arc<ClickHouseSink>>,
+
+    // Archives the payloads assembled from flashblocks, for audits
+    archive: Option<Arc<PayloadArchive>>,
+
+    // Keeps the flashblocks received for the last payloads, for debugging
+    recent_flashblocks: Option<Arc<RecentFlashblocks>>,
 }
 
 impl FlashblocksService {
-    pub fn new(client: RpcClient, outbound_addr: SocketAddr) -> eyre::Result<Self> {
-        let ws_pub = WebSocketPublisher::new(outbound_addr)?.into();
+    pub fn new(
+        client: RpcClient,
+        outbound_addrs: &[SocketAddr],
+        outbound_config: PublisherConfig,
+    ) -> eyre::Result<Self> {
+        let gossip = outbound_config.gossip.clone();
+        let ws_pub = WebSocketPublisher::bind(outbound_addrs, outbound_config)?.into();
+        let (messages, message_receiver) = mpsc::channel(MESSAGE_QUEUE_SIZE);
 
         Ok(Self {
             client,
-            current_payload_id: Arc::new(RwLock::new(PayloadId::default())),
-            best_payload: Arc::new(RwLock::new(FlashblockBuilder::new())),
+            messages,
+            current_payload_id: Arc::new(AtomicPayloadId::default()),
+            message_receiver: Arc::new(Mutex::new(Some(message_receiver))),
             ws_pub,
+            last_payload_source: Arc::new(Mutex::new(BlockSource::Builder)),
+            last_payload_flashblocks: Arc::default(),
+            denylist: None,
+            quarantine: None,
+            builder_scores: None,
+            builder_streams: vec![],
+            conflict_resolution: ConflictResolution::default(),
+            merge_client: None,
+            merge_attributes: Arc::default(),
+            gossip,
+            leader_election: None,
+            state_sync: None,
+            webhooks: None,
+            limits: FlashblocksLimits::default(),
+            staleness_ttl: DEFAULT_STALENESS_TTL,
+            payload_id_ttl: None,
+            clock_skew: ClockSkewCheck::default(),
+            fault_injector: None,
+            clickhouse: None,
+            archive: None,
+            recent_flashblocks: None,
         })
     }
 
+    pub fn with_denylist(mut self, denylist: Option<TransactionDenylist>) -> Self {
+        self.denylist = denylist.map(Arc::new);
+        self
+    }
+
+    pub fn with_quarantine(mut self, quarantine: Option<Arc<BuilderQuarantine>>) -> Self {
+        self.quarantine = quarantine;
+        self
+    }
+
+    pub fn with_builder_scores(mut self, builder_scores: Option<Arc<BuilderScores>>) -> Self {
+        self.builder_scores = builder_scores;
+        self
+    }
+
+    /// Receives the flashblocks of other builders building the same payloads, resolving
+    /// the conflicts between the builders with `conflict_resolution`.
+    pub fn with_builder_streams(
+        mut self,
+        builder_streams: Vec<BuilderStream>,
+        conflict_resolution: ConflictResolution,
+    ) -> Self {
+        self.builder_streams = builder_streams;
+        self.conflict_resolution = conflict_resolution;
+        self
+    }
+
+    /// Has `l2_client` build the block returned by `get_payload` from the transactions of
+    /// the best builder stream interleaved with the non-conflicting transactions of the
+    /// other builder streams, as rollup-boost cannot compute the state root of the block.
+    pub fn with_merge(mut self, l2_client: Option<RpcClient>) -> Self {
+        self.merge_client = l2_client;
+        self
+    }
+
+    pub fn with_leader_election(mut self, leader_election: Option<Arc<LeaderElection>>) -> Self {
+        self.leader_election = leader_election;
+        self
+    }
+
+    pub fn with_state_sync(mut self, state_sync: Option<Arc<StateSync>>) -> Self {
+        self.state_sync = state_sync;
+        self
+    }
+
+    pub fn with_webhooks(mut self, webhooks: Option<Arc<WebhookNotifier>>) -> Self {
+        self.webhooks = webhooks;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: FlashblocksLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Discards the accumulated flashblocks once the timestamp of the block being built
+    /// is older than `staleness_ttl`, so that a stalled builder never makes `get_payload`
+    /// return a stale block for a new slot.
+    pub fn with_staleness_ttl(mut self, staleness_ttl: Duration) -> Self {
+        self.staleness_ttl = staleness_ttl;
+        self
+    }
+
+    /// Drops the current payload ID and its flashblocks `ttl` after the forkchoice update
+    /// that started the payload, if `get_payload` was not called by then, so that a missed
+    /// `get_payload` does not leave state behind for the next payload to collide with.
+    pub fn with_payload_id_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.payload_id_ttl = ttl;
+        self
+    }
+
+    /// Warns when the builder builds blocks whose timestamp is not due within the next
+    /// block time, within a tolerance.
+    pub fn with_clock_skew_check(mut self, clock_skew: ClockSkewCheck) -> Self {
+        self.clock_skew = clock_skew;
+        self
+    }
+
+    pub fn with_fault_injector(mut self, fault_injector: Option<Arc<FaultInjector>>) -> Self {
+        self.fault_injector = fault_injector;
+        self
+    }
+
+    /// Writes a row for each accepted flashblock to ClickHouse.
+    pub fn with_clickhouse(mut self, clickhouse: Option<Arc<ClickHouseSink>>) -> Self {
+        self.clickhouse = clickhouse;
+        self
+    }
+
+    /// Archives the payloads assembled from flashblocks once returned, with their
+    /// flashblocks.
+    pub fn with_archive(mut self, archive: Option<Arc<PayloadArchive>>) -> Self {
+        self.archive = archive;
+        self
+    }
+
+    /// Keeps the flashblocks received for the last payloads in `recent_flashblocks`.
+    pub fn with_recent_flashblocks(
+        mut self,
+        recent_flashblocks: Option<Arc<RecentFlashblocks>>,
+    ) -> Self {
+        self.recent_flashblocks = recent_flashblocks;
+        self
+    }
+
+    pub(crate) fn webhooks(&self) -> Option<Arc<WebhookNotifier>> {
+        self.webhooks.clone()
+    }
+
+    pub(crate) fn builder_streams(&self) -> &[BuilderStream] {
+        &self.builder_streams
+    }
+
+    pub(crate) fn gossip(&self) -> Option<Arc<FlashblocksGossip>> {
+        self.gossip.clone()
+    }
+
+    pub(crate) fn current_payload_id(&self) -> Arc<AtomicPayloadId> {
+        self.current_payload_id.clone()
+    }
+
+    /// Address the flashblocks are republished on over TCP, the first one if they are
+    /// republished on several addresses.
+    pub fn outbound_addr(&self) -> SocketAddr {
+        self.ws_pub.local_addr()
+    }
+
+    /// All the addresses the flashblocks are republished on over TCP.
+    pub fn outbound_addrs(&self) -> &[SocketAddr] {
+        self.ws_pub.local_addrs()
+    }
+
+    async fn send(&self, message: FlashblocksEngineMessage) {
+        if self.messages.send(message).await.is_err() {
+            error!(message = "Flashblocks service is not running");
+        }
+    }
+
     pub async fn get_best_payload(
         &self,
         version: PayloadVersion,
     ) -> Result<Option<OpExecutionPayloadEnvelope>, FlashblocksError> {
-        // consume the best payload and reset the builder
-        let payload = {
-            let mut builder = self.best_payload.write().await;
-            std::mem::take(&mut *builder).into_envelope(version)?
+        let (reply, response) = oneshot::channel();
+        self.send(FlashblocksEngineMessage::TakeBestPayload { version, reply })
+            .await;
+        response.await.unwrap_or(Ok(None))
+    }
+
+    /// Builds the block of `payload_id` with the l2 client from the transactions merged
+    /// from the deltas of all the builders, if merging is enabled and any transaction of
+    /// the other builders could be merged. Must be called before the best payload is taken.
+    async fn get_merged_payload(
+        &self,
+        payload_id: PayloadId,
+        version: PayloadVersion,
+    ) -> Option<OpExecutionPayloadEnvelope> {
+        let client = self.merge_client.as_ref()?;
+        let (reply, response) = oneshot::channel();
+        self.send(FlashblocksEngineMessage::MergeTransactions { reply })
+            .await;
+        let merged = response.await.ok().flatten()?;
+        if merged.merged == 0 {
+            return None;
+        }
+
+        match self
+            .build_merged_payload(client, payload_id, merged.transactions, version)
+            .await
+        {
+            Ok(payload) => {
+                counter!("flashblocks.merged_payloads", "outcome" => "built").increment(1);
+                counter!("flashblocks.merged_transactions").increment(merged.merged as u64);
+                info!(message = "Built merged payload", %payload_id, merged = merged.merged, conflicts = merged.conflicts);
+                Some(payload)
+            }
+            Err(e) => {
+                counter!("flashblocks.merged_payloads", "outcome" => "failed").increment(1);
+                warn!(message = "Failed to build merged payload", %payload_id, error = %e);
+                None
+            }
+        }
+    }
+
+    async fn build_merged_payload(
+        &self,
+        client: &RpcClient,
+        payload_id: PayloadId,
+        transactions: Vec<Bytes>,
+        version: PayloadVersion,
+    ) -> eyre::Result<OpExecutionPayloadEnvelope> {
+        let (fork_choice_state, mut attributes) = match self.merge_attributes.lock().clone() {
+            Some((id, fork_choice_state, attributes)) if id == payload_id => {
+                (fork_choice_state, attributes)
+            }
+            _ => eyre::bail!("no payload attributes for payload {payload_id}"),
         };
-        *self.best_payload.write().await = FlashblockBuilder::new();
 
-        Ok(Some(payload))
+        // The transactions of the attributes are the deposits, which the best builder
+        // included first
+        attributes.transactions = Some(transactions);
+        attributes.no_tx_pool = Some(true);
+        let updated = client
+            .fork_choice_updated_v3(fork_choice_state, Some(attributes))
+            .await?;
+        let merged_payload_id = updated
+            .payload_id
+            .ok_or_else(|| eyre::eyre!("no payload ID for the merged block"))?;
+        Ok(client.get_payload(merged_payload_id, version).await?)
     }
 
     pub async fn set_current_payload_id(&self, payload_id: PayloadId) {
-        tracing::debug!(message = "Setting current payload ID", payload_id = %payload_id);
-        *self.current_payload_id.write().await = payload_id;
+        self.set_current_payload(payload_id, None).await;
     }
 
-    async fn on_event(&mut self, event: FlashblocksEngineMessage) {
-        match event {
-            FlashblocksEngineMessage::FlashblocksPayloadV1(payload) => {
-                tracing::debug!(
-                    message = "Received flashblock payload",
-                    payload_id = %payload.payload_id,
-                    index = payload.index
-                );
+    /// Sets the current payload ID, along with the Holocene eip1559 params of its
+    /// attributes which the flashblocks must be built with.
+    pub async fn set_current_payload(&self, payload_id: PayloadId, eip_1559_params: Option<B64>) {
+        self.send(FlashblocksEngineMessage::SetPayloadId {
+            payload_id,
+            correlation_id: CorrelationId::current(),
+            eip_1559_params,
+        })
+        .await;
+    }
 
-                // make sure the payload id matches the current payload id
-                if *self.current_payload_id.read().await != payload.payload_id {
-                    error!(message = "Payload ID mismatch",);
-                    return;
-                }
+    /// Accumulates a flashblock replicated from the leader, without publishing it.
+    pub async fn apply_synced_flashblock(&self, payload: FlashblocksPayloadV1) {
+        self.send(FlashblocksEngineMessage::SyncedFlashblock(payload))
+            .await;
+    }
 
-                if let Err(e) = self.best_payload.write().await.extend(payload.clone()) {
-                    error!(message = "Failed to extend payload", error = %e);
-                } else {
-                    // Broadcast the valid message
-                    if let Err(e) = self.ws_pub.publish(&payload) {
-                        error!(message = "Failed to broadcast payload", error = %e);
-                    }
-                }
-            }
-        }
+    /// Stops accepting flashblocks until the next payload ID is set.
+    pub async fn clear_current_payload_id(&self) {
+        self.send(FlashblocksEngineMessage::ClearPayloadId).await;
     }
 
-    pub async fn run(&mut self, mut stream: mpsc::Receiver<FlashblocksPayloadV1>) {
-        while let Some(event) = stream.recv().await {
-            self.on_event(FlashblocksEngineMessage::FlashblocksPayloadV1(event))
-                .await;
-        }
+    /// Owns the payload being built, accumulating the flashblocks received on `stream`
+    /// and handling the engine API calls until the service is dropped. Must only be
+    /// called once.
+    pub async fn run(&self, stream: mpsc::Receiver<BuilderFlashblock>) {
+        let Some(messages) = self.message_receiver.lock().take() else {
+            error!(message = "Flashblocks service is already running");
+            return;
+        };
+
+        let actor = FlashblocksActor {
+            current_payload_id: self.current_payload_id.clone(),
+            current_correlation_id: None,
+            eip_1559_params: None,
+            payload_started_at: None,
+            payload_expires_at: None,
+            payload_id_ttl: self.payload_id_ttl,
+            best_payload: FlashblockBuilder::new(),
+            payload_discarded: false,
+            best_builder: None,
+            competing: HashMap::new(),
+            conflict_resolution: self.conflict_resolution,
+            last_payload_flashblocks: self.last_payload_flashblocks.clone(),
+            ws_pub: self.ws_pub.clone(),
+            denylist: self.denylist.clone(),
+            quarantine: self.quarantine.clone(),
+            builder_scores: self.builder_scores.clone(),
+            leader_election: self.leader_election.clone(),
+            state_sync: self.state_sync.clone(),
+            limits: self.limits,
+            staleness_ttl: self.staleness_ttl,
+            clock_skew: self.clock_skew,
+            fault_injector: self.fault_injector.clone(),
+            clickhouse: self.clickhouse.clone(),
+            archive: self.archive.clone(),
+            recent_flashblocks: self.recent_flashblocks.clone(),
+        };
+        actor.run(messages, stream).await;
     }
 }
 
@@ -261,14 +1686,34 @@ impl EngineApiExt for FlashblocksService {
         fork_choice_state: ForkchoiceState,
         payload_attributes: Option<OpPayloadAttributes>,
     ) -> ClientResult<ForkchoiceUpdated> {
+        // Deposit-only blocks must be built without the tx pool, so flashblocks
+        // streamed by the builder for them must never be used
+        let no_tx_pool = payload_attributes
+            .as_ref()
+            .is_some_and(|attrs| attrs.no_tx_pool.unwrap_or_default());
+        let eip_1559_params = payload_attributes
+            .as_ref()
+            .and_then(|attrs| attrs.eip_1559_params);
+        let merge_attributes = self
+            .merge_client
+            .as_ref()
+            .and_then(|_| payload_attributes.clone())
+            .filter(|_| !no_tx_pool);
+
         let result = self
             .client
             .fork_choice_updated_v3(fork_choice_state, payload_attributes)
             .await?;
+        if let (Some(attributes), Some(payload_id)) = (merge_attributes, result.payload_id) {
+            *self.merge_attributes.lock() = Some((payload_id, fork_choice_state, attributes));
+        }
 
-        if let Some(payload_id) = result.payload_id {
+        if no_tx_pool {
+            tracing::debug!(message = "Forkchoice updated with no_tx_pool, ignoring flashblocks");
+            self.clear_current_payload_id().await;
+        } else if let Some(payload_id) = result.payload_id {
             tracing::debug!(message = "Forkchoice updated", payload_id = %payload_id);
-            self.set_current_payload_id(payload_id).await;
+            self.set_current_payload(payload_id, eip_1559_params).await;
         } else {
             tracing::debug!(message = "Forkchoice updated with no payload ID");
         }
@@ -284,17 +1729,76 @@ impl EngineApiExt for FlashblocksService {
         payload_id: PayloadId,
         version: PayloadVersion,
     ) -> ClientResult<OpExecutionPayloadEnvelope> {
+        let merged_payload = self.get_merged_payload(payload_id, version).await;
         let fb_payload = self.get_best_payload(version).await?;
+        if let (Some(payload), Some(_)) = (merged_payload, &fb_payload) {
+            tracing::info!(message = "Returning merged fb payload", payload_id = %payload_id);
+            *self.last_payload_source.lock() = BlockSource::Flashblocks;
+            return Ok(payload);
+        }
         if let Some(payload) = fb_payload {
             tracing::info!(message = "Returning fb payload", payload_id = %payload_id);
+            *self.last_payload_source.lock() = BlockSource::Flashblocks;
             return Ok(payload);
         }
 
         tracing::info!(message = "No flashblocks payload available, fetching from client", payload_id = %payload_id);
         let result = self.client.get_payload(payload_id, version).await?;
+        *self.last_payload_source.lock() = BlockSource::Builder;
+        self.last_payload_flashblocks.store(0, Ordering::Relaxed);
         Ok(result)
     }
 
+    fn last_payload_source(&self) -> BlockSource {
+        *self.last_payload_source.lock()
+    }
+
+    fn last_payload_flashblocks(&self) -> usize {
+        self.last_payload_flashblocks.load(Ordering::Relaxed)
+    }
+
+    async fn on_reorg(&self, event: &ReorgEvent) {
+        // Drop the flashblocks accumulated on top of the reorged head
+        let (reply, response) = oneshot::channel();
+        self.send(FlashblocksEngineMessage::Reset { reply }).await;
+        let payload_id = response.await.unwrap_or_default();
+
+        tracing::info!(message = "Reset flashblocks builder after reorg", payload_id = %payload_id);
+
+        // Notify subscribers so they can invalidate their preconfirmations
+        let message = FlashblocksControlV1::Reorg(FlashblocksReorgV1 {
+            payload_id,
+            old_head: event.old_head,
+            new_head: event.new_head,
+        });
+        if let Err(e) = self.ws_pub.publish_message(&message) {
+            error!(message = "Failed to broadcast reorg", error = %e);
+        }
+    }
+
+    async fn get_payload_bodies_by_hash_v1(
+        &self,
+        block_hashes: Vec<B256>,
+    ) -> ClientResult<ExecutionPayloadBodiesV1> {
+        self.client
+            .get_payload_bodies_by_hash_v1(block_hashes)
+            .await
+    }
+
+    async fn get_payload_bodies_by_range_v1(
+        &self,
+        start: U64,
+        count: U64,
+    ) -> ClientResult<ExecutionPayloadBodiesV1> {
+        self.client
+            .get_payload_bodies_by_range_v1(start, count)
+            .await
+    }
+
+    async fn exchange_capabilities(&self, capabilities: Vec<String>) -> ClientResult<Vec<String>> {
+        self.client.exchange_capabilities(capabilities).await
+    }
+
     async fn get_block_by_number(
         &self,
         number: BlockNumberOrTag,
@@ -302,4 +1806,635 @@ impl EngineApiExt for FlashblocksService {
     ) -> ClientResult<Block> {
         self.client.get_block_by_number(number, full).await
     }
+
+    async fn chain_id(&self) -> ClientResult<u64> {
+        self.client.chain_id().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+    fn flashblock(index: u64, transactions: Vec<Bytes>) -> FlashblocksPayloadV1 {
+        FlashblocksPayloadV1 {
+            index,
+            base: (index == 0).then(ExecutionPayloadBaseV1::default),
+            diff: ExecutionPayloadFlashblockDeltaV1 {
+                transactions,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_flashblock_limits() {
+        let limits = FlashblocksLimits {
+            max_per_payload: 3,
+            max_delta_transactions: 2,
+            max_delta_bytes: 8,
+            max_payload_bytes: 12,
+            ..Default::default()
+        };
+        let tx = |len: usize| Bytes::from(vec![0; len]);
+        let mut builder = FlashblockBuilder::new();
+
+        builder
+            .extend(flashblock(0, vec![tx(4)]), DEFAULT_BUILDER, &limits)
+            .unwrap();
+        assert!(matches!(
+            builder.extend(
+                flashblock(1, vec![tx(1), tx(1), tx(1)]),
+                DEFAULT_BUILDER,
+                &limits
+            ),
+            Err(FlashblocksError::TooManyTransactions { count: 3, max: 2 })
+        ));
+        assert!(matches!(
+            builder.extend(flashblock(1, vec![tx(9)]), DEFAULT_BUILDER, &limits),
+            Err(FlashblocksError::DeltaTooLarge { size: 9, max: 8 })
+        ));
+        builder
+            .extend(flashblock(1, vec![tx(8)]), DEFAULT_BUILDER, &limits)
+            .unwrap();
+        assert!(matches!(
+            builder.extend(flashblock(2, vec![tx(1)]), DEFAULT_BUILDER, &limits),
+            Err(FlashblocksError::PayloadTooLarge { size: 13, max: 12 })
+        ));
+        builder
+            .extend(flashblock(2, vec![]), DEFAULT_BUILDER, &limits)
+            .unwrap();
+        assert!(matches!(
+            builder.extend(flashblock(3, vec![]), DEFAULT_BUILDER, &limits),
+            Err(FlashblocksError::TooManyFlashblocks { max: 3 })
+        ));
+
+        // Rejected flashblocks are not accumulated
+        assert_eq!(builder.flashblocks.len(), 3);
+        assert_eq!(builder.transactions().count(), 2);
+    }
+
+    #[test]
+    fn test_flashblock_cadence() {
+        let limits = FlashblocksLimits {
+            min_delta_interval: Duration::from_secs(3600),
+            ..Default::default()
+        };
+        let mut builder = FlashblockBuilder::new();
+
+        builder
+            .extend(flashblock(0, vec![]), DEFAULT_BUILDER, &limits)
+            .unwrap();
+        assert!(matches!(
+            builder.extend(flashblock(1, vec![]), DEFAULT_BUILDER, &limits),
+            Err(FlashblocksError::DeltaTooFast {
+                min_ms: 3_600_000,
+                ..
+            })
+        ));
+        assert_eq!(builder.flashblocks.len(), 1);
+    }
+
+    #[test]
+    fn test_stale_flashblocks() {
+        let mut builder = FlashblockBuilder::new();
+        let ttl = Duration::from_secs(3);
+        assert!(!builder.is_stale(Duration::from_secs(1_000), ttl));
+
+        let mut payload = flashblock(0, vec![]);
+        if let Some(base) = &mut payload.base {
+            base.timestamp = 1_000;
+        }
+        builder
+            .extend(payload, DEFAULT_BUILDER, &FlashblocksLimits::default())
+            .unwrap();
+        assert!(!builder.is_stale(Duration::from_secs(1_003), ttl));
+        assert!(builder.is_stale(Duration::from_millis(1_003_001), ttl));
+    }
+
+    fn actor() -> eyre::Result<FlashblocksActor> {
+        Ok(FlashblocksActor {
+            current_payload_id: Arc::new(AtomicPayloadId::default()),
+            current_correlation_id: None,
+            eip_1559_params: None,
+            payload_started_at: None,
+            payload_expires_at: None,
+            payload_id_ttl: None,
+            best_payload: FlashblockBuilder::new(),
+            payload_discarded: false,
+            best_builder: None,
+            competing: HashMap::new(),
+            conflict_resolution: ConflictResolution::default(),
+            last_payload_flashblocks: Arc::default(),
+            ws_pub: Arc::new(WebSocketPublisher::new("127.0.0.1:0".parse()?, None)?),
+            denylist: None,
+            quarantine: None,
+            builder_scores: None,
+            leader_election: None,
+            state_sync: None,
+            limits: FlashblocksLimits::default(),
+            staleness_ttl: DEFAULT_STALENESS_TTL,
+            clock_skew: ClockSkewCheck::default(),
+            fault_injector: None,
+            clickhouse: None,
+            archive: None,
+            recent_flashblocks: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_take_best_payload() -> eyre::Result<()> {
+        let mut actor = actor()?;
+        let payload_id = PayloadId::new([1; 8]);
+        actor.set_current_payload_id(payload_id, None, None);
+
+        let mut payload = flashblock(0, vec![Bytes::from_static(&[1])]);
+        payload.payload_id = payload_id;
+        if let Some(base) = &mut payload.base {
+            base.timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        }
+        actor.on_flashblock(DEFAULT_BUILDER.into(), payload);
+
+        // Flashblocks of another payload are ignored
+        let mut payload = flashblock(1, vec![Bytes::from_static(&[2])]);
+        payload.payload_id = PayloadId::new([2; 8]);
+        actor.on_flashblock(DEFAULT_BUILDER.into(), payload);
+        assert_eq!(actor.best_payload.flashblocks.len(), 1);
+
+        // The receiver reads the payload ID set by the actor
+        assert_eq!(actor.current_payload_id.load(), payload_id);
+
+        // The best payload is taken once, leaving an empty builder behind
+        assert!(actor.take_best_payload(PayloadVersion::V3).await?.is_some());
+        assert!(matches!(
+            actor.take_best_payload(PayloadVersion::V3).await,
+            Err(FlashblocksError::MissingPayload)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_flashblocks_histograms() -> eyre::Result<()> {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let _guard = metrics::set_default_local_recorder(&recorder);
+        let histogram = |name: &str| -> Vec<f64> {
+            snapshotter
+                .snapshot()
+                .into_vec()
+                .into_iter()
+                .filter(|(key, ..)| key.key().name() == name)
+                .flat_map(|(.., value)| match value {
+                    DebugValue::Histogram(values) => values,
+                    _ => vec![],
+                })
+                .map(|value| value.into_inner())
+                .collect()
+        };
+
+        // An interval is recorded between consecutive accepted deltas, but not for a
+        // rejected one
+        let limits = FlashblocksLimits::default();
+        let mut builder = FlashblockBuilder::new();
+        builder.extend(flashblock(0, vec![]), DEFAULT_BUILDER, &limits)?;
+        assert!(
+            builder
+                .extend(flashblock(2, vec![]), DEFAULT_BUILDER, &limits)
+                .is_err()
+        );
+        builder.extend(flashblock(1, vec![]), DEFAULT_BUILDER, &limits)?;
+        assert_eq!(histogram("flashblocks.delta_interval").len(), 1);
+
+        // The flashblocks of a payload are counted when it is taken
+        let mut actor = actor()?;
+        let payload_id = PayloadId::new([1; 8]);
+        actor.set_current_payload_id(payload_id, None, None);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        for index in 0..3 {
+            let mut payload = flashblock(index, vec![Bytes::from(vec![index as u8])]);
+            payload.payload_id = payload_id;
+            if let Some(base) = &mut payload.base {
+                base.timestamp = now;
+            }
+            actor.on_flashblock(DEFAULT_BUILDER.into(), payload);
+        }
+        assert!(actor.take_best_payload(PayloadVersion::V3).await?.is_some());
+        assert_eq!(histogram("flashblocks.count_per_block"), vec![3.0]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_competing_streams() -> eyre::Result<()> {
+        let mut actor = actor()?;
+        let payload_id = PayloadId::new([1; 8]);
+        actor.set_current_payload_id(payload_id, None, None);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let at = |index: u64, tx: u8| {
+            let mut payload = flashblock(index, vec![Bytes::from(vec![tx])]);
+            payload.payload_id = payload_id;
+            if let Some(base) = &mut payload.base {
+                base.timestamp = now;
+            }
+            payload
+        };
+
+        let (mut subscriber, _) =
+            tokio_tungstenite::connect_async(format!("ws://{}", actor.ws_pub.local_addr())).await?;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // The flashblocks of the first builder streaming are the best payload
+        let backup: Arc<str> = "backup".into();
+        actor.on_flashblock(backup.clone(), at(0, 1));
+        actor.on_flashblock(backup.clone(), at(1, 2));
+        assert_eq!(actor.best_builder, Some(backup.clone()));
+
+        // The pinned primary builder takes over once it streamed the same index
+        actor.on_flashblock(DEFAULT_BUILDER.into(), at(0, 3));
+        assert_eq!(actor.best_builder.as_deref(), Some(DEFAULT_BUILDER));
+        assert_eq!(actor.best_payload.flashblocks.len(), 1);
+        assert_eq!(actor.competing[&backup].flashblocks.len(), 2);
+
+        // The other builder keeps competing without replacing it
+        actor.on_flashblock(backup.clone(), at(2, 4));
+        assert_eq!(actor.best_builder.as_deref(), Some(DEFAULT_BUILDER));
+        assert_eq!(actor.competing[&backup].flashblocks.len(), 3);
+
+        // Subscribers are told to discard the flashblocks of the previous builder before
+        // the payload is published again from index 0
+        let mut messages = vec![];
+        for _ in 0..4 {
+            let message = tokio::time::timeout(Duration::from_secs(1), subscriber.next())
+                .await?
+                .expect("the subscriber is connected")?;
+            messages.push(serde_json::from_str::<serde_json::Value>(
+                message.to_text()?,
+            )?);
+        }
+        assert_eq!(messages[0]["index"], 0);
+        assert_eq!(messages[1]["index"], 1);
+        assert_eq!(messages[2]["type"], "reset");
+        assert_eq!(messages[2]["payload_id"], serde_json::json!(payload_id));
+        assert_eq!(messages[3]["index"], 0);
+        assert!(messages[3].get("type").is_none());
+
+        // The flashblocks of every builder are dropped with the payload
+        assert!(actor.take_best_payload(PayloadVersion::V3).await?.is_some());
+        assert!(actor.best_builder.is_none());
+        assert!(actor.competing.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_payload_id_expiry() -> eyre::Result<()> {
+        let mut actor = actor()?;
+        actor.payload_id_ttl = Some(Duration::from_millis(50));
+        let current_payload_id = actor.current_payload_id.clone();
+
+        let (messages_tx, messages) = mpsc::channel(1);
+        let (_stream_tx, stream) = mpsc::channel(1);
+        tokio::spawn(actor.run(messages, stream));
+
+        let payload_id = PayloadId::new([1; 8]);
+        messages_tx
+            .send(FlashblocksEngineMessage::SetPayloadId {
+                payload_id,
+                correlation_id: None,
+                eip_1559_params: None,
+            })
+            .await?;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(current_payload_id.load(), payload_id);
+
+        // The payload ID is dropped once get_payload was not called in time
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(current_payload_id.load(), PayloadId::default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_eip_1559_params() {
+        let params = B64::from([0, 0, 0, 250, 0, 0, 0, 6]);
+        let mut base = ExecutionPayloadBaseV1 {
+            extra_data: Bytes::from_static(&[0, 0, 0, 0, 250, 0, 0, 0, 6]),
+            eip_1559_params: Some(params),
+            ..Default::default()
+        };
+        assert!(validate_eip_1559_params(&base, Some(params)).is_ok());
+        assert!(validate_eip_1559_params(&base, Some(B64::ZERO)).is_ok());
+        assert!(matches!(
+            validate_eip_1559_params(&base, Some(B64::from([0, 0, 0, 8, 0, 0, 0, 2]))),
+            Err(FlashblocksError::Eip1559ParamsMismatch { .. })
+        ));
+
+        // The params of the base must match its extra data
+        base.eip_1559_params = Some(B64::from([0, 0, 0, 8, 0, 0, 0, 2]));
+        assert!(validate_eip_1559_params(&base, None).is_err());
+
+        // Before Holocene, there are no params to check
+        let base = ExecutionPayloadBaseV1::default();
+        assert!(validate_eip_1559_params(&base, None).is_ok());
+    }
+
+    #[test]
+    fn test_envelope_versions() -> eyre::Result<()> {
+        let envelope = |version| {
+            let mut payload = flashblock(0, vec![Bytes::from_static(&[1])]);
+            payload.diff.withdrawals_root = B256::with_last_byte(1);
+            let mut builder = FlashblockBuilder::new();
+            builder.extend(payload, DEFAULT_BUILDER, &FlashblocksLimits::default())?;
+            builder.into_envelope(version)
+        };
+
+        assert!(matches!(
+            envelope(PayloadVersion::V1)?,
+            OpExecutionPayloadEnvelope::V1(payload) if payload.transactions.len() == 1
+        ));
+        for version in [PayloadVersion::V2, PayloadVersion::V3, PayloadVersion::V4] {
+            let envelope = envelope(version)?;
+            assert_eq!(envelope.version().as_str(), version.as_str());
+            assert_eq!(envelope.tx_count(), 1);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_envelope_moves_deltas() -> eyre::Result<()> {
+        let tx = |byte: u8| Bytes::from(vec![byte]);
+        let withdrawal = |index: u64| Withdrawal {
+            index,
+            ..Default::default()
+        };
+        let mut builder = FlashblockBuilder::new();
+        for (index, transactions) in [vec![tx(1)], vec![tx(2), tx(3)], vec![]]
+            .into_iter()
+            .enumerate()
+        {
+            let mut payload = flashblock(index as u64, transactions);
+            payload.diff.withdrawals = vec![withdrawal(index as u64)];
+            payload.diff.block_hash = B256::with_last_byte(index as u8);
+            builder.extend(payload, DEFAULT_BUILDER, &FlashblocksLimits::default())?;
+        }
+
+        // The transactions and withdrawals of every delta are accumulated in order, and
+        // the block hash is the one of the last delta
+        let OpExecutionPayloadEnvelope::V3(envelope) = builder.into_envelope(PayloadVersion::V3)?
+        else {
+            panic!("expected a V3 envelope");
+        };
+        let payload = envelope.execution_payload.payload_inner;
+        assert_eq!(
+            payload.payload_inner.transactions,
+            vec![tx(1), tx(2), tx(3)]
+        );
+        assert_eq!(
+            payload.withdrawals,
+            vec![withdrawal(0), withdrawal(1), withdrawal(2)]
+        );
+        assert_eq!(payload.payload_inner.block_hash, B256::with_last_byte(2));
+
+        // A block cannot be built without any flashblock
+        assert!(matches!(
+            FlashblockBuilder::new().into_envelope(PayloadVersion::V3),
+            Err(FlashblocksError::MissingPayload)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_withdrawals_root() {
+        let withdrawals = vec![Withdrawal {
+            index: 1,
+            amount: 100,
+            ..Default::default()
+        }];
+        let root = proofs::calculate_withdrawals_root(&withdrawals);
+
+        assert!(validate_withdrawals_root(&withdrawals, root).is_ok());
+        assert!(matches!(
+            validate_withdrawals_root(&withdrawals, B256::with_last_byte(1)),
+            Err(FlashblocksError::WithdrawalsRootMismatch { expected, .. }) if expected == root
+        ));
+
+        // Without withdrawals, the root is the storage root of the message passer
+        assert!(validate_withdrawals_root(&[], B256::with_last_byte(1)).is_ok());
+        assert!(matches!(
+            validate_withdrawals_root(&[], B256::ZERO),
+            Err(FlashblocksError::MissingWithdrawalsRoot)
+        ));
+    }
+
+    #[test]
+    fn test_gas_used_monotonicity() {
+        let limits = FlashblocksLimits::default();
+        let with_gas = |index, gas_used| {
+            let mut payload = flashblock(index, vec![]);
+            payload.diff.gas_used = gas_used;
+            if let Some(base) = &mut payload.base {
+                base.gas_limit = 30_000;
+            }
+            payload
+        };
+        let mut builder = FlashblockBuilder::new();
+
+        builder
+            .extend(with_gas(0, 10_000), DEFAULT_BUILDER, &limits)
+            .unwrap();
+        assert!(matches!(
+            builder.extend(with_gas(1, 9_000), DEFAULT_BUILDER, &limits),
+            Err(FlashblocksError::GasUsedDecreased {
+                gas_used: 9_000,
+                previous: 10_000
+            })
+        ));
+        assert!(matches!(
+            builder.extend(with_gas(1, 30_001), DEFAULT_BUILDER, &limits),
+            Err(FlashblocksError::GasLimitExceeded {
+                gas_used: 30_001,
+                gas_limit: 30_000
+            })
+        ));
+        builder
+            .extend(with_gas(1, 10_000), DEFAULT_BUILDER, &limits)
+            .unwrap();
+        builder
+            .extend(with_gas(2, 30_000), DEFAULT_BUILDER, &limits)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_state_root_continuity() {
+        let limits = FlashblocksLimits::default();
+        let with_root = |index, root: u8, transactions: Vec<Bytes>| {
+            let mut payload = flashblock(index, transactions);
+            payload.diff.state_root = B256::repeat_byte(root);
+            payload
+        };
+        let mut builder = FlashblockBuilder::new();
+
+        builder
+            .extend(
+                with_root(0, 1, vec![Bytes::from_static(&[1])]),
+                DEFAULT_BUILDER,
+                &limits,
+            )
+            .unwrap();
+        assert!(matches!(
+            builder.extend(with_root(1, 2, vec![]), DEFAULT_BUILDER, &limits),
+            Err(FlashblocksError::StateRootChanged { .. })
+        ));
+        assert!(matches!(
+            builder.extend(
+                with_root(1, 1, vec![Bytes::from_static(&[2])]),
+                DEFAULT_BUILDER,
+                &limits
+            ),
+            Err(FlashblocksError::StateRootUnchanged { .. })
+        ));
+        builder
+            .extend(with_root(1, 1, vec![]), DEFAULT_BUILDER, &limits)
+            .unwrap();
+        builder
+            .extend(
+                with_root(2, 2, vec![Bytes::from_static(&[2])]),
+                DEFAULT_BUILDER,
+                &limits,
+            )
+            .unwrap();
+
+        // Deltas without a state root are not checked, but the last one must have one
+        builder
+            .extend(
+                with_root(3, 0, vec![Bytes::from_static(&[3])]),
+                DEFAULT_BUILDER,
+                &limits,
+            )
+            .unwrap();
+        assert!(matches!(
+            builder.into_envelope(PayloadVersion::V3),
+            Err(FlashblocksError::MissingStateRoot)
+        ));
+    }
+
+    #[test]
+    fn test_duplicate_transactions() {
+        let tx = |byte: u8| Bytes::from(vec![byte]);
+        let mut limits = FlashblocksLimits::default();
+        let mut builder = FlashblockBuilder::new();
+
+        builder
+            .extend(flashblock(0, vec![tx(1)]), DEFAULT_BUILDER, &limits)
+            .unwrap();
+        assert!(matches!(
+            builder.extend(flashblock(1, vec![tx(2), tx(1)]), DEFAULT_BUILDER, &limits),
+            Err(FlashblocksError::DuplicateTransaction { hash }) if hash == keccak256(tx(1))
+        ));
+        assert!(matches!(
+            builder.extend(flashblock(1, vec![tx(2), tx(2)]), DEFAULT_BUILDER, &limits),
+            Err(FlashblocksError::DuplicateTransaction { .. })
+        ));
+
+        // The duplicates are rejected whatever the policy, the actor discards the payload
+        limits.duplicate_transactions = DuplicateTransactionPolicy::Discard;
+        assert!(matches!(
+            builder.extend(flashblock(1, vec![tx(2), tx(1)]), DEFAULT_BUILDER, &limits),
+            Err(FlashblocksError::DuplicateTransaction { .. })
+        ));
+        assert_eq!(
+            builder.transactions().cloned().collect::<Vec<_>>(),
+            vec![tx(1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_discard_payload_on_duplicate_transactions() -> eyre::Result<()> {
+        let tx = |byte: u8| Bytes::from(vec![byte]);
+        let mut actor = actor()?;
+        actor.limits.duplicate_transactions = DuplicateTransactionPolicy::Discard;
+        let payload_id = PayloadId::new([1; 8]);
+        actor.set_current_payload_id(payload_id, None, None);
+
+        let at = |index, txs| {
+            let mut payload = flashblock(index, txs);
+            payload.payload_id = payload_id;
+            if let Some(base) = &mut payload.base {
+                base.timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+            }
+            payload
+        };
+        actor.on_flashblock(DEFAULT_BUILDER.into(), at(0, vec![tx(1)]));
+        actor.on_flashblock(DEFAULT_BUILDER.into(), at(1, vec![tx(1)]));
+        assert!(actor.best_payload.flashblocks.is_empty());
+
+        // The next flashblocks of the payload are ignored, the payload is fetched from
+        // the builder
+        actor.on_flashblock(DEFAULT_BUILDER.into(), at(2, vec![tx(2)]));
+        assert!(actor.best_payload.flashblocks.is_empty());
+        assert!(actor.take_best_payload(PayloadVersion::V3).await?.is_none());
+        assert!(!actor.payload_discarded);
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_transactions_builder_label() {
+        let tx = |byte: u8| Bytes::from(vec![byte]);
+        let limits = FlashblocksLimits::default();
+        let mut builder = FlashblockBuilder::new();
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            builder
+                .extend(flashblock(0, vec![tx(1)]), "backup", &limits)
+                .unwrap();
+            assert!(
+                builder
+                    .extend(flashblock(1, vec![tx(1)]), "backup", &limits)
+                    .is_err()
+            );
+        });
+
+        let labels: Vec<_> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .filter(|(key, ..)| key.key().name() == "flashblocks.duplicate_transactions")
+            .flat_map(|(key, ..)| {
+                key.key()
+                    .labels()
+                    .map(|label| (label.key().to_string(), label.value().to_string()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert!(labels.contains(&("builder".to_string(), "backup".to_string())));
+        assert!(!labels.contains(&("builder".to_string(), DEFAULT_BUILDER.to_string())));
+    }
+
+    #[test]
+    fn test_validate_flashblocks() {
+        let tx = |byte: u8| Bytes::from(vec![byte]);
+        let report = validate_flashblocks(vec![
+            flashblock(0, vec![tx(1)]),
+            flashblock(2, vec![tx(2)]),
+            flashblock(1, vec![tx(1)]),
+            flashblock(1, vec![tx(2)]),
+        ]);
+
+        assert!(!report.valid);
+        let errors: Vec<_> = report
+            .flashblocks
+            .iter()
+            .map(|o| o.error.is_some())
+            .collect();
+        assert_eq!(errors, vec![false, true, true, false]);
+        // The roots of the default deltas do not hash to their block hash
+        assert_eq!(report.payload_errors.len(), 1);
+
+        assert!(!validate_flashblocks(vec![]).valid);
+    }
 }
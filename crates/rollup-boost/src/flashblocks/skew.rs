@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+/// Tolerance on the timestamp of the blocks built by the builder by default.
+pub const DEFAULT_CLOCK_SKEW_TOLERANCE: Duration = Duration::from_millis(500);
+
+/// How the timestamp of a block compares to the time its first flashblock was received.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClockSkew {
+    /// The block is due within the next block time
+    InTime,
+    /// The timestamp of the block has already passed: the builder clock is behind, or it
+    /// is building for a slot that is over
+    Past,
+    /// The block is due after the next block time: the builder clock is ahead
+    Ahead,
+}
+
+impl ClockSkew {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClockSkew::InTime => "in_time",
+            ClockSkew::Past => "past",
+            ClockSkew::Ahead => "ahead",
+        }
+    }
+}
+
+/// Detects a builder whose clock drifted from the local one, from the timestamp of the
+/// blocks it builds compared to when their first flashblock is received. A block is
+/// expected to be due within the next block time.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ClockSkewCheck {
+    block_time: Duration,
+    tolerance: Duration,
+}
+
+impl Default for ClockSkewCheck {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(2), DEFAULT_CLOCK_SKEW_TOLERANCE)
+    }
+}
+
+impl ClockSkewCheck {
+    pub fn new(block_time: Duration, tolerance: Duration) -> Self {
+        Self {
+            block_time,
+            tolerance,
+        }
+    }
+
+    /// Milliseconds from `now` to the block `timestamp` in seconds, negative once it has
+    /// passed, and whether it is within the tolerance.
+    pub fn check(&self, timestamp: u64, now: Duration) -> (i64, ClockSkew) {
+        let skew_ms = (timestamp as i64 * 1000).saturating_sub(now.as_millis() as i64);
+        let skew = if skew_ms < -(self.tolerance.as_millis() as i64) {
+            ClockSkew::Past
+        } else if skew_ms > (self.block_time + self.tolerance).as_millis() as i64 {
+            ClockSkew::Ahead
+        } else {
+            ClockSkew::InTime
+        };
+        (skew_ms, skew)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_skew() {
+        let check = ClockSkewCheck::default();
+        let now = Duration::from_millis(1_000_250);
+        assert_eq!(check.check(1_002, now), (1750, ClockSkew::InTime));
+        assert_eq!(check.check(1_000, now), (-250, ClockSkew::InTime));
+        assert_eq!(check.check(999, now), (-1250, ClockSkew::Past));
+        assert_eq!(check.check(1_003, now), (2750, ClockSkew::Ahead));
+    }
+}
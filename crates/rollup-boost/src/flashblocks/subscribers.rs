@@ -0,0 +1,203 @@
+use std::{
+    collections::BTreeMap,
+    net::IpAddr,
+    ops::Deref,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use metrics::{counter, gauge, histogram};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+/// Label of the metrics of the subscribers connected over the unix domain socket.
+const IPC_LABEL: &str = "ipc";
+
+/// Snapshot of a subscriber of the flashblocks websocket, returned by
+/// `debug_listSubscribers`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SubscriberInfo {
+    /// Identifier of the connection, unique for the lifetime of the publisher
+    pub id: u64,
+    /// Address of the connection, or `ipc` for the unix domain socket
+    pub peer: String,
+    /// Client resolved from the forwarding headers of the trusted proxies, if connected
+    /// over TCP
+    pub client: Option<IpAddr>,
+    /// Unix timestamp in seconds of the connection
+    pub connected_at: u64,
+    /// Seconds since the connection
+    pub age_secs: u64,
+    /// Messages written to the subscriber
+    pub delivered: u64,
+    /// Messages dropped because the subscriber fell too far behind
+    pub dropped: u64,
+    /// Messages waiting to be written to the subscriber
+    pub queue_depth: usize,
+}
+
+/// Delivery statistics of a connected subscriber.
+///
+/// The metrics are labelled by the client address rather than the connection, so that
+/// reconnecting subscribers do not grow the number of series.
+#[derive(Debug)]
+pub(crate) struct SubscriberStats {
+    id: u64,
+    peer: String,
+    client: Option<IpAddr>,
+    label: String,
+    connected_at: SystemTime,
+    connected: Instant,
+    delivered: AtomicU64,
+    dropped: AtomicU64,
+    queue_depth: AtomicUsize,
+}
+
+impl SubscriberStats {
+    pub(crate) fn record_delivered(&self) {
+        self.delivered.fetch_add(1, Ordering::Relaxed);
+        counter!("flashblocks.subscriber_messages_delivered", "subscriber" => self.label.clone())
+            .increment(1);
+    }
+
+    pub(crate) fn record_dropped(&self, count: u64) {
+        self.dropped.fetch_add(count, Ordering::Relaxed);
+        counter!("flashblocks.subscriber_messages_dropped", "subscriber" => self.label.clone())
+            .increment(count);
+    }
+
+    /// Updates the queue depth of the subscriber. The gauge is shared by the subscribers of
+    /// the same client, so it is moved by the change of this subscriber only.
+    pub(crate) fn set_queue_depth(&self, depth: usize) {
+        let previous = self.queue_depth.swap(depth, Ordering::Relaxed);
+        let gauge =
+            gauge!("flashblocks.subscriber_queue_depth", "subscriber" => self.label.clone());
+        if depth > previous {
+            gauge.increment((depth - previous) as f64);
+        } else if depth < previous {
+            gauge.decrement((previous - depth) as f64);
+        }
+    }
+
+    fn info(&self) -> SubscriberInfo {
+        SubscriberInfo {
+            id: self.id,
+            peer: self.peer.clone(),
+            client: self.client,
+            connected_at: self
+                .connected_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            age_secs: self.connected.elapsed().as_secs(),
+            delivered: self.delivered.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            queue_depth: self.queue_depth.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The subscribers connected to a flashblocks publisher, to find which consumer is
+/// lagging.
+#[derive(Debug, Default)]
+pub struct SubscriberRegistry {
+    next_id: AtomicU64,
+    subscribers: Mutex<BTreeMap<u64, Arc<SubscriberStats>>>,
+}
+
+impl SubscriberRegistry {
+    /// Registers a subscriber connected from `peer`, listed until the returned handle is
+    /// dropped.
+    pub(crate) fn register(
+        self: &Arc<Self>,
+        peer: String,
+        client: Option<IpAddr>,
+    ) -> SubscriberHandle {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let stats = Arc::new(SubscriberStats {
+            id,
+            peer,
+            client,
+            label: client.map_or_else(|| IPC_LABEL.to_string(), |ip| ip.to_string()),
+            connected_at: SystemTime::now(),
+            connected: Instant::now(),
+            delivered: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            queue_depth: AtomicUsize::new(0),
+        });
+        self.subscribers.lock().insert(id, stats.clone());
+        SubscriberHandle {
+            stats,
+            registry: self.clone(),
+        }
+    }
+
+    /// The connected subscribers, oldest first.
+    pub fn subscribers(&self) -> Vec<SubscriberInfo> {
+        self.subscribers
+            .lock()
+            .values()
+            .map(|stats| stats.info())
+            .collect()
+    }
+}
+
+/// A subscriber listed in the [`SubscriberRegistry`] until dropped.
+#[derive(Debug)]
+pub(crate) struct SubscriberHandle {
+    stats: Arc<SubscriberStats>,
+    registry: Arc<SubscriberRegistry>,
+}
+
+impl Deref for SubscriberHandle {
+    type Target = SubscriberStats;
+
+    fn deref(&self) -> &Self::Target {
+        &self.stats
+    }
+}
+
+impl Drop for SubscriberHandle {
+    fn drop(&mut self) {
+        self.registry.subscribers.lock().remove(&self.stats.id);
+        self.stats.set_queue_depth(0);
+        let label = self.stats.label.clone();
+        histogram!("flashblocks.subscriber_connection_duration", "subscriber" => label)
+            .record(self.stats.connected.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscriber_registry() {
+        let registry = Arc::new(SubscriberRegistry::default());
+        let client: IpAddr = "203.0.113.1".parse().unwrap();
+
+        let first = registry.register("203.0.113.1:4711".to_string(), Some(client));
+        let second = registry.register("ipc".to_string(), None);
+        first.record_delivered();
+        first.record_delivered();
+        second.record_dropped(3);
+        second.set_queue_depth(5);
+
+        let subscribers = registry.subscribers();
+        assert_eq!(subscribers.len(), 2);
+        assert_eq!(subscribers[0].peer, "203.0.113.1:4711");
+        assert_eq!(subscribers[0].client, Some(client));
+        assert_eq!(subscribers[0].delivered, 2);
+        assert_eq!(subscribers[1].dropped, 3);
+        assert_eq!(subscribers[1].queue_depth, 5);
+
+        // Disconnected subscribers are no longer listed
+        drop(first);
+        let subscribers = registry.subscribers();
+        assert_eq!(subscribers.len(), 1);
+        assert_eq!(subscribers[0].peer, "ipc");
+    }
+}
@@ -0,0 +1,378 @@
+use super::outbound::{PublisherConfig, WebSocketPublisher};
+use super::primitives::FlashblocksPayloadV1;
+use crate::{
+    ExecutionMode, FlashblocksService, LeaderElection, LeaderElectionArgs, secret_to_bearer_header,
+};
+use alloy_rpc_types_engine::{JwtSecret, PayloadId};
+use core::net::SocketAddr;
+use futures::StreamExt;
+use http::header::AUTHORIZATION;
+use metrics::counter;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{io, sync::Arc, time::Duration};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{Message, client::IntoClientRequest},
+};
+use tracing::{debug, error, info, warn};
+use url::Url;
+
+/// Interval at which the leader republishes its execution mode, so that a standby
+/// instance connecting at any time converges on it.
+const EXECUTION_MODE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// State replicated from the leader to the standby instance of an active/standby
+/// deployment.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StateSyncMessage {
+    /// The leader started building a new payload
+    PayloadId { payload_id: PayloadId },
+    /// The leader accepted a flashblock for the current payload
+    Flashblock { payload: FlashblocksPayloadV1 },
+    /// The current execution mode of the leader
+    ExecutionMode { execution_mode: ExecutionMode },
+}
+
+/// Borrowed form of [`StateSyncMessage::Flashblock`], published without copying the
+/// flashblock.
+#[derive(Serialize)]
+#[serde(tag = "type", rename = "flashblock")]
+struct FlashblockMessage<'a> {
+    payload: &'a FlashblocksPayloadV1,
+}
+
+/// A message as published on the stream, along with the instance ID of the publisher in
+/// the lease.
+#[derive(Serialize)]
+struct PublishedMessage<'a, T> {
+    instance_id: &'a str,
+    #[serde(flatten)]
+    message: &'a T,
+}
+
+/// A message as received from the stream of the peer.
+#[derive(Deserialize)]
+struct ReceivedMessage {
+    instance_id: String,
+    #[serde(flatten)]
+    message: StateSyncMessage,
+}
+
+impl StateSyncMessage {
+    fn as_str(&self) -> &'static str {
+        match self {
+            StateSyncMessage::PayloadId { .. } => "payload_id",
+            StateSyncMessage::Flashblock { .. } => "flashblock",
+            StateSyncMessage::ExecutionMode { .. } => "execution_mode",
+        }
+    }
+}
+
+/// Streams the current payload ID, the accumulated flashblocks and the execution mode
+/// from the leader to the standby instance. If the leader fails in the middle of a
+/// block, the standby instance takes over with the flashblocks accumulated so far, and
+/// can still return the flashblocks payload once the builder is asked for it.
+///
+/// Both instances publish on their own address and subscribe to the address of their
+/// peer: only the leader publishes, and only the standby instance applies what it
+/// receives, from the instance holding the lease only.
+///
+/// The subscriptions are authenticated with a JWT secret shared by both instances.
+pub struct StateSync {
+    publisher: Option<WebSocketPublisher>,
+    peer_url: Option<Url>,
+    leader_election: Arc<LeaderElection>,
+    execution_mode: Arc<Mutex<ExecutionMode>>,
+    jwt_secret: JwtSecret,
+}
+
+impl StateSync {
+    pub fn new(
+        addr: Option<SocketAddr>,
+        peer_url: Option<Url>,
+        leader_election: Arc<LeaderElection>,
+        execution_mode: Arc<Mutex<ExecutionMode>>,
+        jwt_secret: JwtSecret,
+    ) -> io::Result<Self> {
+        let publisher = addr
+            .map(|addr| {
+                let config = PublisherConfig {
+                    jwt_secret: Some(jwt_secret),
+                    ..Default::default()
+                };
+                WebSocketPublisher::bind(&[addr], config)
+            })
+            .transpose()?;
+        Ok(Self {
+            publisher,
+            peer_url,
+            leader_election,
+            execution_mode,
+            jwt_secret,
+        })
+    }
+
+    pub fn from_args(
+        args: &LeaderElectionArgs,
+        leader_election: Option<Arc<LeaderElection>>,
+        execution_mode: Arc<Mutex<ExecutionMode>>,
+    ) -> io::Result<Option<Self>> {
+        let Some(leader_election) = leader_election else {
+            return Ok(None);
+        };
+        if args.ha_sync_addr.is_none() && args.ha_sync_peer_url.is_none() {
+            return Ok(None);
+        }
+        let Some(jwt_path) = &args.ha_sync_jwt_path else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--ha-sync-jwt-path is required to authenticate the state sync stream",
+            ));
+        };
+        let jwt_secret = JwtSecret::from_file(jwt_path).map_err(io::Error::other)?;
+        Self::new(
+            args.ha_sync_addr,
+            args.ha_sync_peer_url.clone(),
+            leader_election,
+            execution_mode,
+            jwt_secret,
+        )
+        .map(Some)
+    }
+
+    /// Publishes `message` to the standby instance, if this instance is the leader.
+    pub fn publish(&self, message: &StateSyncMessage) {
+        self.publish_message(message);
+    }
+
+    /// Publishes a flashblock accepted for the current payload to the standby instance,
+    /// if this instance is the leader.
+    pub fn publish_flashblock(&self, payload: &FlashblocksPayloadV1) {
+        self.publish_message(&FlashblockMessage { payload });
+    }
+
+    fn publish_message<T: Serialize>(&self, message: &T) {
+        let Some(publisher) = &self.publisher else {
+            return;
+        };
+        if !self.leader_election.is_leader() {
+            return;
+        }
+        let message = PublishedMessage {
+            instance_id: self.leader_election.instance_id(),
+            message,
+        };
+        if let Err(e) = publisher.publish_message(&message) {
+            error!(message = "Failed to publish state sync message", error = %e);
+        }
+    }
+
+    pub fn spawn(self: Arc<Self>, service: FlashblocksService) -> Vec<JoinHandle<()>> {
+        let mut handles = Vec::new();
+
+        if self.publisher.is_some() {
+            let sync = self.clone();
+            handles.push(tokio::spawn(async move {
+                let mut interval = tokio::time::interval(EXECUTION_MODE_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    let execution_mode = *sync.execution_mode.lock();
+                    sync.publish(&StateSyncMessage::ExecutionMode { execution_mode });
+                }
+            }));
+        }
+
+        if let Some(peer_url) = self.peer_url.clone() {
+            handles.push(tokio::spawn(async move {
+                loop {
+                    if let Err(e) = self.connect_and_apply(&peer_url, &service).await {
+                        warn!(message = "State sync connection error, retrying in 5 seconds", url = %peer_url, error = %e);
+                    }
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+            }));
+        }
+
+        handles
+    }
+
+    async fn connect_and_apply(
+        &self,
+        peer_url: &Url,
+        service: &FlashblocksService,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut request = peer_url.as_str().into_client_request()?;
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, secret_to_bearer_header(&self.jwt_secret));
+        let (ws_stream, _) = connect_async(request).await?;
+        let (_, mut read) = ws_stream.split();
+
+        info!(message = "Connected to the state sync stream of the peer", url = %peer_url);
+
+        while let Some(msg) = read.next().await {
+            if let Message::Text(text) = msg? {
+                match serde_json::from_str::<ReceivedMessage>(&text) {
+                    Ok(message) => {
+                        self.apply(&message.instance_id, message.message, service)
+                            .await
+                    }
+                    Err(e) => warn!(message = "Invalid state sync message", error = %e),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn apply(
+        &self,
+        instance_id: &str,
+        message: StateSyncMessage,
+        service: &FlashblocksService,
+    ) {
+        // The stream of a former leader is stale once this instance took the lease over
+        if self.leader_election.is_leader() {
+            debug!(message = "Leading, ignoring state sync message");
+            return;
+        }
+        // Only the holder of the lease streams its state, any other instance is a former
+        // leader that did not notice it lost the lease yet
+        if self.leader_election.leader().as_deref() != Some(instance_id) {
+            counter!("ha.sync_messages_rejected").increment(1);
+            debug!(message = "Ignoring state sync message of an instance not holding the lease", %instance_id);
+            return;
+        }
+        counter!("ha.sync_messages_received", "type" => message.as_str()).increment(1);
+
+        match message {
+            StateSyncMessage::PayloadId { payload_id } => {
+                service.set_current_payload_id(payload_id).await;
+            }
+            StateSyncMessage::Flashblock { payload } => {
+                service.apply_synced_flashblock(payload).await;
+            }
+            StateSyncMessage::ExecutionMode { execution_mode } => {
+                let mut current = self.execution_mode.lock();
+                if *current != execution_mode {
+                    info!(
+                        message = "Execution mode synced from the leader",
+                        ?execution_mode
+                    );
+                    *current = execution_mode;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PayloadSource, RpcClient};
+
+    #[test]
+    fn test_message_format() -> eyre::Result<()> {
+        let message = StateSyncMessage::ExecutionMode {
+            execution_mode: ExecutionMode::DryRun,
+        };
+        assert_eq!(
+            serde_json::to_value(&message)?,
+            serde_json::json!({"type": "execution_mode", "execution_mode": "dry_run"})
+        );
+
+        let message = StateSyncMessage::Flashblock {
+            payload: FlashblocksPayloadV1 {
+                payload_id: PayloadId::new([1; 8]),
+                index: 1,
+                ..Default::default()
+            },
+        };
+        let StateSyncMessage::Flashblock { payload } = &message else {
+            unreachable!();
+        };
+        assert_eq!(
+            serde_json::to_value(FlashblockMessage { payload })?,
+            serde_json::to_value(&message)?
+        );
+
+        // The published messages carry the instance ID of the publisher
+        let published = PublishedMessage {
+            instance_id: "a",
+            message: &message,
+        };
+        let decoded: ReceivedMessage = serde_json::from_str(&serde_json::to_string(&published)?)?;
+        assert_eq!(decoded.instance_id, "a");
+        let StateSyncMessage::Flashblock { payload } = decoded.message else {
+            panic!("expected a flashblock");
+        };
+        assert_eq!(payload.payload_id, PayloadId::new([1; 8]));
+        assert_eq!(payload.index, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_from_leader() -> eyre::Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("rollup-boost-sync-{}.json", std::process::id()));
+        let ttl = Duration::from_secs(10);
+        let leader = Arc::new(LeaderElection::new("a".to_string(), path.clone(), ttl));
+        leader.set_leader(Some("a".to_string()));
+        let standby = Arc::new(LeaderElection::new("b".to_string(), path, ttl));
+        standby.set_leader(Some("a".to_string()));
+
+        let jwt_secret = JwtSecret::random();
+        let leader_sync = Arc::new(StateSync::new(
+            Some("127.0.0.1:0".parse()?),
+            None,
+            leader,
+            Arc::new(Mutex::new(ExecutionMode::DryRun)),
+            jwt_secret,
+        )?);
+        let addr = leader_sync.publisher.as_ref().unwrap().local_addr();
+        let url = Url::parse(&format!("ws://{addr}"))?;
+
+        // Subscribers without the shared JWT secret are rejected
+        assert!(connect_async(url.as_str()).await.is_err());
+
+        let standby_mode = Arc::new(Mutex::new(ExecutionMode::Enabled));
+        let standby_sync = Arc::new(StateSync::new(
+            None,
+            Some(url),
+            standby,
+            standby_mode.clone(),
+            jwt_secret,
+        )?);
+        let service = FlashblocksService::new(
+            RpcClient::new(
+                "http://127.0.0.1:1".parse()?,
+                jwt_secret,
+                1000,
+                PayloadSource::Builder,
+            )?,
+            &["127.0.0.1:0".parse()?],
+            PublisherConfig::default(),
+        )?;
+        leader_sync.spawn(service.clone());
+        standby_sync.clone().spawn(service.clone());
+
+        // The standby instance adopts the execution mode of the leader
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while *standby_mode.lock() != ExecutionMode::DryRun {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await?;
+
+        // The messages of an instance not holding the lease are dropped
+        let message = StateSyncMessage::ExecutionMode {
+            execution_mode: ExecutionMode::Disabled,
+        };
+        standby_sync.apply("c", message, &service).await;
+        assert_eq!(*standby_mode.lock(), ExecutionMode::DryRun);
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+use super::outbound::{PublisherConfig, WebSocketPublisher};
+use super::primitives::{
+    ExecutionPayloadBaseV1, ExecutionPayloadFlashblockDeltaV1, FlashblocksPayloadV1,
+};
+use crate::{FlashblocksArgs, OpExecutionPayloadEnvelope};
+use alloy_primitives::{B256, U256};
+use alloy_rpc_types_engine::{ExecutionPayload, PayloadId};
+use core::net::SocketAddr;
+use metrics::counter;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+/// Publishes synthetic flashblocks for the payloads returned by rollup-boost, on chains
+/// without a flashblocks-capable builder, so that flashblocks consumers can be tested.
+///
+/// The transactions of each returned payload are split into timed deltas, streamed once
+/// the payload is returned. Only the last delta carries the roots, logs bloom and block
+/// hash of the payload: the intermediate ones have zero roots, and a gas used
+/// proportional to their share of the transactions, since their state was never built.
+#[derive(Clone, Debug)]
+pub struct SyntheticFlashblocks {
+    ws_pub: Arc<WebSocketPublisher>,
+    count: usize,
+    interval: Duration,
+}
+
+impl SyntheticFlashblocks {
+    pub fn new(
+        outbound_addrs: &[SocketAddr],
+        outbound_config: PublisherConfig,
+        count: usize,
+        interval: Duration,
+    ) -> eyre::Result<Self> {
+        Ok(Self {
+            ws_pub: Arc::new(WebSocketPublisher::bind(outbound_addrs, outbound_config)?),
+            count: count.max(1),
+            interval,
+        })
+    }
+
+    /// Starts the publisher on the outbound addresses of the flashblocks arguments, if
+    /// synthetic flashblocks are enabled.
+    pub fn from_args(
+        args: &FlashblocksArgs,
+        outbound_addrs: &[SocketAddr],
+        outbound_config: PublisherConfig,
+    ) -> eyre::Result<Option<Self>> {
+        if !args.flashblocks_synthetic {
+            return Ok(None);
+        }
+        Self::new(
+            outbound_addrs,
+            outbound_config,
+            args.flashblocks_synthetic_count,
+            Duration::from_millis(args.flashblocks_synthetic_interval_ms),
+        )
+        .map(Some)
+    }
+
+    /// Streams the flashblocks of `payload`, one every interval.
+    pub fn publish(&self, payload_id: PayloadId, payload: &OpExecutionPayloadEnvelope) {
+        let flashblocks = split_payload(payload_id, payload, self.count);
+        let ws_pub = self.ws_pub.clone();
+        let mut interval = tokio::time::interval(self.interval);
+
+        tokio::spawn(async move {
+            for flashblock in flashblocks {
+                interval.tick().await;
+                match ws_pub.publish(&flashblock) {
+                    Ok(()) => counter!("flashblocks.synthetic_published").increment(1),
+                    Err(e) => warn!(message = "Failed to publish synthetic flashblock", error = %e),
+                }
+            }
+        });
+    }
+}
+
+/// Splits the transactions of `payload` into at most `count` flashblocks, the last of
+/// which completes the payload.
+pub fn split_payload(
+    payload_id: PayloadId,
+    payload: &OpExecutionPayloadEnvelope,
+    count: usize,
+) -> Vec<FlashblocksPayloadV1> {
+    let header = payload.header();
+    let block_hash = payload.block_hash();
+    let execution_payload = ExecutionPayload::from(payload.clone());
+    let transactions = execution_payload.transactions();
+    let withdrawals = execution_payload.withdrawals().cloned().unwrap_or_default();
+    // Only Isthmus payloads commit to a withdrawals root of their own
+    let withdrawals_root = match payload {
+        OpExecutionPayloadEnvelope::V4(v4) => v4.execution_payload.withdrawals_root,
+        _ => B256::ZERO,
+    };
+
+    let base = ExecutionPayloadBaseV1 {
+        parent_beacon_block_root: header.parent_beacon_block_root.unwrap_or_default(),
+        parent_hash: header.parent_hash,
+        fee_recipient: header.beneficiary,
+        prev_randao: header.mix_hash,
+        block_number: header.number,
+        gas_limit: header.gas_limit,
+        timestamp: header.timestamp,
+        extra_data: header.extra_data.clone(),
+        base_fee_per_gas: U256::from(header.base_fee_per_gas.unwrap_or_default()),
+        eip_1559_params: None,
+    };
+    let eip_1559_params = base.extra_data_eip_1559_params();
+
+    let chunk_size = transactions.len().div_ceil(count.max(1)).max(1);
+    let mut chunks: Vec<_> = transactions.chunks(chunk_size).collect();
+    if chunks.is_empty() {
+        chunks.push(&[]);
+    }
+
+    let last = chunks.len() - 1;
+    let mut included = 0;
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            included += chunk.len();
+            let diff = match index == last {
+                true => ExecutionPayloadFlashblockDeltaV1 {
+                    state_root: header.state_root,
+                    receipts_root: header.receipts_root,
+                    logs_bloom: header.logs_bloom,
+                    gas_used: header.gas_used,
+                    block_hash,
+                    transactions: chunk.to_vec(),
+                    withdrawals: withdrawals.clone(),
+                    withdrawals_root,
+                },
+                false => ExecutionPayloadFlashblockDeltaV1 {
+                    state_root: B256::ZERO,
+                    receipts_root: B256::ZERO,
+                    gas_used: header.gas_used * included as u64 / transactions.len() as u64,
+                    block_hash: B256::ZERO,
+                    transactions: chunk.to_vec(),
+                    ..Default::default()
+                },
+            };
+
+            FlashblocksPayloadV1 {
+                payload_id,
+                index: index as u64,
+                base: (index == 0).then(|| ExecutionPayloadBaseV1 {
+                    eip_1559_params,
+                    ..base.clone()
+                }),
+                diff,
+                metadata: serde_json::json!({ "synthetic": true }),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate_flashblocks;
+    use alloy_primitives::Bytes;
+    use alloy_rpc_types_engine::{
+        BlobsBundleV1, ExecutionPayloadV1, ExecutionPayloadV2, ExecutionPayloadV3,
+    };
+    use op_alloy_rpc_types_engine::OpExecutionPayloadEnvelopeV3;
+
+    fn payload(tx_count: u8) -> OpExecutionPayloadEnvelope {
+        let mut payload = OpExecutionPayloadEnvelope::V3(OpExecutionPayloadEnvelopeV3 {
+            execution_payload: ExecutionPayloadV3 {
+                payload_inner: ExecutionPayloadV2 {
+                    payload_inner: ExecutionPayloadV1 {
+                        parent_hash: B256::random(),
+                        fee_recipient: Default::default(),
+                        state_root: B256::random(),
+                        receipts_root: B256::random(),
+                        logs_bloom: Default::default(),
+                        prev_randao: B256::random(),
+                        block_number: 1,
+                        gas_limit: 30_000_000,
+                        gas_used: 21_000 * tx_count as u64,
+                        timestamp: 1,
+                        extra_data: Bytes::new(),
+                        base_fee_per_gas: U256::from(1),
+                        block_hash: B256::ZERO,
+                        transactions: (0..tx_count).map(|i| Bytes::from(vec![i])).collect(),
+                    },
+                    withdrawals: vec![],
+                },
+                blob_gas_used: 0,
+                excess_blob_gas: 0,
+            },
+            block_value: U256::ZERO,
+            blobs_bundle: BlobsBundleV1::default(),
+            should_override_builder: false,
+            parent_beacon_block_root: B256::random(),
+        });
+        let block_hash = payload.header().hash_slow();
+        if let OpExecutionPayloadEnvelope::V3(v3) = &mut payload {
+            v3.execution_payload.payload_inner.payload_inner.block_hash = block_hash;
+        }
+        payload
+    }
+
+    #[test]
+    fn test_split_payload() {
+        let payload_id = PayloadId::new([1; 8]);
+
+        let flashblocks = split_payload(payload_id, &payload(10), 4);
+        assert_eq!(flashblocks.len(), 4);
+        assert_eq!(
+            flashblocks
+                .iter()
+                .map(|flashblock| flashblock.diff.transactions.len())
+                .collect::<Vec<_>>(),
+            vec![3, 3, 3, 1]
+        );
+        assert_eq!(flashblocks[1].diff.gas_used, 6 * 21_000);
+
+        // The flashblocks assemble back into the payload
+        let report = validate_flashblocks(flashblocks);
+        assert!(report.valid, "{report:?}");
+
+        // Fewer transactions than flashblocks, or none
+        assert_eq!(split_payload(payload_id, &payload(2), 4).len(), 2);
+        let flashblocks = split_payload(payload_id, &payload(0), 4);
+        assert_eq!(flashblocks.len(), 1);
+        assert!(validate_flashblocks(flashblocks).valid);
+    }
+}
@@ -0,0 +1,169 @@
+use std::net::{IpAddr, SocketAddr};
+
+use clap::Parser;
+use http::{HeaderMap, header::FORWARDED};
+use ipnet::IpNet;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+#[derive(Parser, Clone, Debug)]
+pub struct ForwardedArgs {
+    /// Addresses or CIDR ranges of the load balancers and reverse proxies in front of
+    /// rollup-boost. The client of a connection from a trusted proxy is taken from the
+    /// `Forwarded` or `X-Forwarded-For` headers, for rate limiting and logging
+    #[arg(long, env, value_delimiter = ',', value_parser = parse_trusted_proxy)]
+    pub trusted_proxies: Vec<IpNet>,
+}
+
+fn parse_trusted_proxy(s: &str) -> Result<IpNet, String> {
+    s.parse::<IpNet>()
+        .or_else(|_| s.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| format!("invalid trusted proxy {s}, expected an IP address or CIDR range"))
+}
+
+/// Address of the client of a request, resolved from the forwarding headers of the
+/// trusted proxies, inserted in the request extensions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClientAddr(pub IpAddr);
+
+/// The reverse proxies whose forwarding headers are trusted to identify the clients of
+/// the connections they forward.
+///
+/// Headers sent by any other peer are ignored, since clients can set them to anything.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TrustedProxies {
+    proxies: Vec<IpNet>,
+}
+
+impl TrustedProxies {
+    pub fn new(proxies: Vec<IpNet>) -> Self {
+        Self { proxies }
+    }
+
+    pub fn from_args(args: &ForwardedArgs) -> Self {
+        Self::new(args.trusted_proxies.clone())
+    }
+
+    pub fn trusts(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.proxies.iter().any(|proxy| proxy.contains(&ip))
+    }
+
+    /// Resolves the client of a connection from `peer`. The forwarding chain is walked
+    /// from the closest hop, and the first hop that is not a trusted proxy is the client.
+    /// `Forwarded` takes precedence over `X-Forwarded-For`.
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let mut client = peer.to_canonical();
+        if !self.trusts(client) {
+            return client;
+        }
+
+        for hop in forwarded_hops(headers).into_iter().rev() {
+            // An obfuscated or unknown hop cannot be checked, the last known hop is the
+            // closest to the client we can trust
+            let Some(hop) = hop else {
+                break;
+            };
+            client = hop.to_canonical();
+            if !self.trusts(client) {
+                break;
+            }
+        }
+        client
+    }
+}
+
+/// Hops of the forwarding headers of a request, from the original client to the closest
+/// proxy.
+fn forwarded_hops(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let forwarded = headers.get_all(FORWARDED).iter().collect::<Vec<_>>();
+    if !forwarded.is_empty() {
+        return forwarded
+            .into_iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|element| {
+                element
+                    .split(';')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(name, _)| name.trim().eq_ignore_ascii_case("for"))
+                    .and_then(|(_, node)| parse_node(node))
+            })
+            .collect();
+    }
+
+    headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(parse_node)
+        .collect()
+}
+
+/// Parses the address of a node of a forwarding header, such as `192.0.2.60`,
+/// `"192.0.2.60:4711"` or `"[2001:db8::17]:4711"`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    node.parse::<IpAddr>()
+        .ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+        .or_else(|| {
+            node.strip_prefix('[')
+                .and_then(|node| node.strip_suffix(']'))
+                .and_then(|node| node.parse().ok())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(name: &str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            http::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+            value.parse().unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_client_ip() {
+        let proxies = TrustedProxies::new(vec![
+            parse_trusted_proxy("10.0.0.0/8").unwrap(),
+            parse_trusted_proxy("fd00::1").unwrap(),
+        ]);
+        let proxy: IpAddr = "10.0.0.2".parse().unwrap();
+        let client: IpAddr = "203.0.113.7".parse().unwrap();
+
+        // Headers of untrusted peers are ignored
+        let spoofed = headers(X_FORWARDED_FOR, "198.51.100.1");
+        assert_eq!(proxies.client_ip(client, &spoofed), client);
+
+        let chain = headers(X_FORWARDED_FOR, "198.51.100.1, 203.0.113.7, 10.0.0.3");
+        assert_eq!(proxies.client_ip(proxy, &chain), client);
+
+        let forwarded = headers(
+            "forwarded",
+            r#"for=198.51.100.1, for="[2001:db8::17]:4711";proto=https, for=10.0.0.3"#,
+        );
+        assert_eq!(
+            proxies.client_ip("fd00::1".parse().unwrap(), &forwarded),
+            "2001:db8::17".parse::<IpAddr>().unwrap()
+        );
+
+        // The last known hop is used when a hop is obfuscated
+        let obfuscated = headers("forwarded", "for=_hidden, for=10.0.0.3");
+        assert_eq!(
+            proxies.client_ip(proxy, &obfuscated),
+            "10.0.0.3".parse::<IpAddr>().unwrap()
+        );
+
+        // IPv4 peers of dual-stack listeners are mapped to IPv6
+        assert_eq!(
+            proxies.client_ip("::ffff:10.0.0.2".parse().unwrap(), &chain),
+            client
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::VecDeque;
+
+use alloy_primitives::{B256, U256};
+use alloy_rpc_types_engine::PayloadId;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+/// Number of payload cycles kept by default.
+pub const DEFAULT_PAYLOAD_HISTORY_SIZE: usize = 256;
+
+/// A `get_payload` call served by rollup-boost, returned by `debug_getRecentPayloads`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PayloadCycle {
+    pub payload_id: PayloadId,
+    pub block_number: u64,
+    pub block_hash: B256,
+    pub block_value: U256,
+    /// Where the returned block came from: `flashblocks`, `builder` or `l2`
+    pub source: String,
+    /// Why the block of the l2 client was returned, if it was: `disabled`, `dry_run`,
+    /// `shadow`, `builder_error`, `builder_no_payload`, `latency_budget`, `builder_score`,
+    /// `block_value_threshold`, `selector` or `selection_policy`
+    pub fallback_reason: Option<String>,
+    /// Number of flashblocks the block was assembled from
+    pub flashblocks: usize,
+    /// Unix timestamp at which the call was received, in milliseconds
+    pub requested_at: u64,
+    /// Milliseconds the l2 client took to return its payload
+    pub l2_latency_ms: u64,
+    /// Milliseconds the builder took to return a validated payload, if it was called
+    pub builder_latency_ms: Option<u64>,
+    /// Milliseconds the call took
+    pub total_latency_ms: u64,
+}
+
+/// The last payload cycles served, to explain after the fact why a block came from the
+/// builder or the l2 client.
+#[derive(Debug)]
+pub struct PayloadHistory {
+    capacity: usize,
+    cycles: Mutex<VecDeque<PayloadCycle>>,
+}
+
+impl Default for PayloadHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_PAYLOAD_HISTORY_SIZE)
+    }
+}
+
+impl PayloadHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            cycles: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub fn record(&self, cycle: PayloadCycle) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut cycles = self.cycles.lock();
+        if cycles.len() >= self.capacity {
+            cycles.pop_front();
+        }
+        cycles.push_back(cycle);
+    }
+
+    /// The last `count` payload cycles, most recent first.
+    pub fn recent(&self, count: usize) -> Vec<PayloadCycle> {
+        self.cycles
+            .lock()
+            .iter()
+            .rev()
+            .take(count)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cycle(block_number: u64) -> PayloadCycle {
+        PayloadCycle {
+            payload_id: PayloadId::new([1; 8]),
+            block_number,
+            block_hash: B256::ZERO,
+            block_value: U256::ZERO,
+            source: "l2".to_string(),
+            fallback_reason: Some("builder_no_payload".to_string()),
+            flashblocks: 0,
+            requested_at: 0,
+            l2_latency_ms: 10,
+            builder_latency_ms: Some(20),
+            total_latency_ms: 20,
+        }
+    }
+
+    #[test]
+    fn test_payload_history() {
+        let history = PayloadHistory::new(3);
+        for block_number in 1..=5 {
+            history.record(cycle(block_number));
+        }
+
+        let recent = history.recent(10);
+        assert_eq!(
+            recent.iter().map(|c| c.block_number).collect::<Vec<_>>(),
+            [5, 4, 3]
+        );
+        assert_eq!(history.recent(1)[0].block_number, 5);
+    }
+}
@@ -0,0 +1,138 @@
+use std::{fmt::Debug, sync::Arc};
+
+use clap::Parser;
+use metrics::counter;
+use op_alloy_rpc_types_engine::OpPayloadAttributes;
+use tracing::debug;
+
+#[derive(Parser, Clone, Debug, Default)]
+pub struct AttributesHookArgs {
+    /// Gas limit set in the payload attributes forwarded to the builder. The L2 client
+    /// always builds with the gas limit of the original attributes
+    #[arg(long, env)]
+    pub builder_gas_limit: Option<u64>,
+
+    /// Minimum gas limit of the payload attributes forwarded to the builder, a lower gas
+    /// limit is raised to it
+    #[arg(long, env)]
+    pub builder_min_gas_limit: Option<u64>,
+
+    /// Maximum gas limit of the payload attributes forwarded to the builder, a higher gas
+    /// limit is lowered to it
+    #[arg(long, env)]
+    pub builder_max_gas_limit: Option<u64>,
+}
+
+impl AttributesHookArgs {
+    /// The built-in hooks enabled by the arguments.
+    pub fn hooks(&self) -> Vec<Arc<dyn AttributesHook>> {
+        let mut hooks: Vec<Arc<dyn AttributesHook>> = vec![];
+        if self.builder_gas_limit.is_some()
+            || self.builder_min_gas_limit.is_some()
+            || self.builder_max_gas_limit.is_some()
+        {
+            hooks.push(Arc::new(GasLimitHook::new(
+                self.builder_gas_limit,
+                self.builder_min_gas_limit,
+                self.builder_max_gas_limit,
+            )));
+        }
+        hooks
+    }
+}
+
+/// A hook modifying the payload attributes before they are forwarded to the builder, for
+/// chain specific attribute policies.
+///
+/// Hooks only apply to the attributes sent to the builder: the L2 client always builds
+/// the fallback block with the original attributes, and the builder payload is validated
+/// against the modified ones.
+pub trait AttributesHook: Debug + Send + Sync {
+    /// Name of the hook, used in the metrics and logs.
+    fn name(&self) -> &'static str;
+
+    fn apply(&self, attrs: &mut OpPayloadAttributes);
+}
+
+/// Applies `hooks` in order to the attributes forwarded to the builder.
+pub fn apply_attributes_hooks(
+    hooks: &[Arc<dyn AttributesHook>],
+    mut attrs: OpPayloadAttributes,
+) -> OpPayloadAttributes {
+    for hook in hooks {
+        let original = attrs.clone();
+        hook.apply(&mut attrs);
+        if attrs != original {
+            counter!("attributes.hook_modified", "hook" => hook.name()).increment(1);
+            debug!(message = "payload attributes modified", hook = hook.name());
+        }
+    }
+    attrs
+}
+
+/// Sets the gas limit of the attributes forwarded to the builder, and keeps it within
+/// bounds.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GasLimitHook {
+    gas_limit: Option<u64>,
+    min: Option<u64>,
+    max: Option<u64>,
+}
+
+impl GasLimitHook {
+    pub fn new(gas_limit: Option<u64>, min: Option<u64>, max: Option<u64>) -> Self {
+        Self {
+            gas_limit,
+            min,
+            max,
+        }
+    }
+}
+
+impl AttributesHook for GasLimitHook {
+    fn name(&self) -> &'static str {
+        "gas_limit"
+    }
+
+    fn apply(&self, attrs: &mut OpPayloadAttributes) {
+        let Some(mut gas_limit) = self.gas_limit.or(attrs.gas_limit) else {
+            return;
+        };
+        if let Some(min) = self.min {
+            gas_limit = gas_limit.max(min);
+        }
+        if let Some(max) = self.max {
+            gas_limit = gas_limit.min(max);
+        }
+        attrs.gas_limit = Some(gas_limit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gas_limit_hook() {
+        let attrs = OpPayloadAttributes {
+            gas_limit: Some(60_000_000),
+            ..Default::default()
+        };
+
+        let hooks: Vec<Arc<dyn AttributesHook>> =
+            vec![Arc::new(GasLimitHook::new(None, None, Some(30_000_000)))];
+        let modified = apply_attributes_hooks(&hooks, attrs.clone());
+        assert_eq!(modified.gas_limit, Some(30_000_000));
+        assert_eq!(modified.payload_attributes, attrs.payload_attributes);
+
+        let hooks: Vec<Arc<dyn AttributesHook>> = vec![Arc::new(GasLimitHook::new(
+            Some(10_000_000),
+            Some(20_000_000),
+            None,
+        ))];
+        assert_eq!(
+            apply_attributes_hooks(&hooks, attrs).gas_limit,
+            Some(20_000_000)
+        );
+    }
+}
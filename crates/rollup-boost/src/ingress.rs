@@ -0,0 +1,201 @@
+use alloy_primitives::{B256, Bytes, keccak256};
+use clap::Parser;
+use metrics::counter;
+use moka::future::Cache;
+use parking_lot::Mutex;
+use std::time::Duration;
+
+use crate::{RateLimit, ratelimit::TokenBucket};
+
+/// Maximum number of transaction hashes remembered for deduplication
+const MAX_SEEN_TRANSACTIONS: u64 = 100_000;
+
+#[derive(Parser, Clone, Debug)]
+pub struct TxIngressArgs {
+    /// Duration in seconds a raw transaction is remembered to drop duplicate submissions
+    #[arg(long, env, default_value = "60")]
+    pub tx_dedup_ttl: u64,
+
+    /// Maximum number of raw transactions forwarded per second, unlimited if not set
+    #[arg(long, env)]
+    pub tx_rate_limit: Option<u32>,
+
+    /// Only forward raw transactions to the builder, without sending them to the L2 client
+    #[arg(long, env, default_value = "false")]
+    pub tx_builder_only: bool,
+}
+
+/// A raw transaction submitted to the proxy, with the conditions of
+/// `eth_sendRawTransactionConditional` if any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawTransaction {
+    /// Hash of the transaction, returned for duplicate submissions
+    pub hash: B256,
+    /// Key the submission is deduplicated by, the same transaction being a new
+    /// submission when sent with other conditions
+    key: B256,
+}
+
+impl RawTransaction {
+    pub fn new(raw_tx: &Bytes, conditions: Option<&serde_json::Value>) -> Self {
+        let hash = keccak256(raw_tx);
+        let key = match conditions {
+            Some(conditions) => {
+                keccak256([hash.as_slice(), conditions.to_string().as_bytes()].concat())
+            }
+            None => hash,
+        };
+        Self { hash, key }
+    }
+}
+
+/// Outcome of a raw transaction submitted to the proxy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IngressDecision {
+    Forward,
+    Duplicate(B256),
+    RateLimited,
+}
+
+/// Admission control for the raw transactions forwarded to the builder and the L2
+/// client: duplicate submissions within the dedup window are answered with the
+/// transaction hash without being forwarded again, and submissions above the rate
+/// limit are rejected. Only the submissions accepted upstream are remembered, so that
+/// a failed submission can be retried.
+#[derive(Debug)]
+pub struct TxIngress {
+    seen: Cache<B256, ()>,
+    rate_limiter: Option<Mutex<TokenBucket>>,
+    builder_only: bool,
+}
+
+impl Default for TxIngress {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(60), None, false)
+    }
+}
+
+impl TxIngress {
+    pub fn new(dedup_ttl: Duration, rate_limit: Option<u32>, builder_only: bool) -> Self {
+        Self {
+            seen: Cache::builder()
+                .max_capacity(MAX_SEEN_TRANSACTIONS)
+                .time_to_live(dedup_ttl)
+                .build(),
+            rate_limiter: rate_limit
+                .map(|rate| Mutex::new(TokenBucket::new(RateLimit::new(rate, None)))),
+            builder_only,
+        }
+    }
+
+    pub fn from_args(args: &TxIngressArgs) -> Self {
+        Self::new(
+            Duration::from_secs(args.tx_dedup_ttl),
+            args.tx_rate_limit,
+            args.tx_builder_only,
+        )
+    }
+
+    /// Whether raw transactions are only sent to the builder.
+    pub fn builder_only(&self) -> bool {
+        self.builder_only
+    }
+
+    pub async fn admit(&self, tx: &RawTransaction) -> IngressDecision {
+        if self.seen.contains_key(&tx.key) {
+            counter!("tx_ingress.transactions", "decision" => "duplicate").increment(1);
+            return IngressDecision::Duplicate(tx.hash);
+        }
+
+        if let Some(rate_limiter) = &self.rate_limiter {
+            if !rate_limiter.lock().try_acquire() {
+                counter!("tx_ingress.transactions", "decision" => "rate_limited").increment(1);
+                return IngressDecision::RateLimited;
+            }
+        }
+
+        counter!("tx_ingress.transactions", "decision" => "forward").increment(1);
+        IngressDecision::Forward
+    }
+
+    /// Remembers a transaction accepted upstream, to drop its duplicate submissions.
+    pub async fn accepted(&self, tx: &RawTransaction) {
+        self.seen.insert(tx.key, ()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_tx(bytes: &'static [u8]) -> RawTransaction {
+        RawTransaction::new(&Bytes::from_static(bytes), None)
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_transactions() {
+        let ingress = TxIngress::default();
+        let tx = raw_tx(&[1, 2, 3]);
+
+        assert_eq!(ingress.admit(&tx).await, IngressDecision::Forward);
+        ingress.accepted(&tx).await;
+        assert_eq!(
+            ingress.admit(&tx).await,
+            IngressDecision::Duplicate(keccak256([1, 2, 3]))
+        );
+        assert_eq!(ingress.admit(&raw_tx(&[4])).await, IngressDecision::Forward);
+    }
+
+    #[tokio::test]
+    async fn test_failed_submissions_are_retried() {
+        let ingress = TxIngress::default();
+        let tx = raw_tx(&[1, 2, 3]);
+
+        // The transaction was not accepted upstream, it is forwarded again
+        assert_eq!(ingress.admit(&tx).await, IngressDecision::Forward);
+        assert_eq!(ingress.admit(&tx).await, IngressDecision::Forward);
+    }
+
+    #[tokio::test]
+    async fn test_conditional_transactions() {
+        let ingress = TxIngress::default();
+        let raw = Bytes::from_static(&[1, 2, 3]);
+        let conditions = serde_json::json!({ "blockNumberMax": "0x10" });
+        let tx = RawTransaction::new(&raw, Some(&conditions));
+        assert_eq!(tx.hash, keccak256(&raw));
+        ingress.accepted(&tx).await;
+
+        assert_eq!(
+            ingress.admit(&tx).await,
+            IngressDecision::Duplicate(keccak256(&raw))
+        );
+        // The same transaction with other conditions, or none, is a new submission
+        let other_conditions = serde_json::json!({ "blockNumberMax": "0x20" });
+        assert_eq!(
+            ingress
+                .admit(&RawTransaction::new(&raw, Some(&other_conditions)))
+                .await,
+            IngressDecision::Forward
+        );
+        assert_eq!(
+            ingress.admit(&RawTransaction::new(&raw, None)).await,
+            IngressDecision::Forward
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let ingress = TxIngress::new(Duration::from_secs(60), Some(2), false);
+
+        assert_eq!(ingress.admit(&raw_tx(&[1])).await, IngressDecision::Forward);
+        assert_eq!(ingress.admit(&raw_tx(&[2])).await, IngressDecision::Forward);
+        assert_eq!(
+            ingress.admit(&raw_tx(&[3])).await,
+            IngressDecision::RateLimited
+        );
+
+        // Rate limited transactions are not remembered and can be resubmitted
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert_eq!(ingress.admit(&raw_tx(&[3])).await, IngressDecision::Forward);
+    }
+}
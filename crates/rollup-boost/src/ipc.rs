@@ -0,0 +1,211 @@
+use std::{
+    fs, io,
+    net::SocketAddr,
+    os::unix::fs::{FileTypeExt, PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+use alloy_primitives::bytes::Bytes;
+use clap::Parser;
+use hyper::body::{Body, Incoming};
+use jsonrpsee::{
+    core::BoxError,
+    server::{ServerHandle, StopHandle, serve_with_graceful_shutdown, stop_channel},
+};
+use tokio::net::{TcpListener, UnixListener};
+use tower::{Service, ServiceExt as _};
+use tracing::{debug, info, warn};
+
+#[derive(Parser, Clone, Debug)]
+pub struct IpcArgs {
+    /// Unix domain socket to serve the Engine API proxy on, in addition to the rpc port
+    #[arg(long, env, value_name = "PATH")]
+    pub rpc_ipc_path: Option<PathBuf>,
+
+    /// Unix domain socket to serve the debug API on, in addition to the debug server port
+    #[arg(long, env, value_name = "PATH")]
+    pub debug_ipc_path: Option<PathBuf>,
+
+    /// Unix domain socket to publish flashblocks on, in addition to the flashblocks port
+    #[arg(long, env, value_name = "PATH")]
+    pub flashblocks_ipc_path: Option<PathBuf>,
+
+    /// Permissions of the unix domain sockets, in octal
+    #[arg(long, env, default_value = "600", value_parser = parse_permissions)]
+    pub ipc_permissions: u32,
+}
+
+impl IpcArgs {
+    pub fn endpoint(&self, path: Option<&PathBuf>) -> Option<IpcEndpoint> {
+        path.map(|path| IpcEndpoint::new(path.clone(), self.ipc_permissions))
+    }
+}
+
+fn parse_permissions(s: &str) -> Result<u32, String> {
+    u32::from_str_radix(s.trim_start_matches("0o"), 8)
+        .ok()
+        .filter(|mode| *mode <= 0o777)
+        .ok_or_else(|| format!("invalid permissions {s}, expected an octal mode such as 660"))
+}
+
+/// A unix domain socket with the permissions it is created with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IpcEndpoint {
+    pub path: PathBuf,
+    pub permissions: u32,
+}
+
+impl IpcEndpoint {
+    pub fn new(path: PathBuf, permissions: u32) -> Self {
+        Self { path, permissions }
+    }
+
+    /// Binds the socket, replacing a stale socket left at the path by a previous run.
+    pub fn bind(&self) -> io::Result<UnixListener> {
+        remove_stale_socket(&self.path)?;
+
+        let listener = UnixListener::bind(&self.path)?;
+        fs::set_permissions(&self.path, fs::Permissions::from_mode(self.permissions))?;
+
+        info!(message = "listening on unix domain socket", path = %self.path.display());
+        Ok(listener)
+    }
+}
+
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(path),
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        )),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Serves a jsonrpsee service over HTTP and websocket on a unix domain socket, until the
+/// returned handle is stopped.
+pub fn spawn_ipc_server<S, B>(
+    listener: UnixListener,
+    make_service: impl FnOnce(StopHandle) -> S,
+) -> ServerHandle
+where
+    S: Service<http::Request<Incoming>, Response = http::Response<B>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    B: Body<Data = Bytes> + Send + 'static,
+    B::Error: Into<BoxError>,
+{
+    let (stop_handle, server_handle) = stop_channel();
+    let service = make_service(stop_handle.clone());
+
+    tokio::spawn(async move {
+        loop {
+            let stream = tokio::select! {
+                res = listener.accept() => match res {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        warn!(message = "failed to accept unix domain socket connection", %e);
+                        continue;
+                    }
+                },
+                _ = stop_handle.clone().shutdown() => break,
+            };
+
+            let service = service.clone();
+            let stop_handle = stop_handle.clone();
+            tokio::spawn(async move {
+                if let Err(e) =
+                    serve_with_graceful_shutdown(stream, service, stop_handle.shutdown()).await
+                {
+                    debug!(message = "unix domain socket connection closed", %e);
+                }
+            });
+        }
+    });
+
+    server_handle
+}
+
+/// Address of the client of a request served by [`spawn_tcp_server`], inserted in the
+/// request extensions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RemoteAddr(pub SocketAddr);
+
+/// Serves a jsonrpsee service over HTTP and websocket on a TCP listener, until the
+/// returned handle is stopped. Unlike `Server::build`, the address of the client is
+/// available to the HTTP middleware as a [`RemoteAddr`] extension.
+pub fn spawn_tcp_server<S, B>(
+    listener: TcpListener,
+    make_service: impl FnOnce(StopHandle) -> S,
+) -> ServerHandle
+where
+    S: Service<http::Request<Incoming>, Response = http::Response<B>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    B: Body<Data = Bytes> + Send + 'static,
+    B::Error: Into<BoxError>,
+{
+    let (stop_handle, server_handle) = stop_channel();
+    let service = make_service(stop_handle.clone());
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, remote_addr) = tokio::select! {
+                res = listener.accept() => match res {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        warn!(message = "failed to accept tcp connection", %e);
+                        continue;
+                    }
+                },
+                _ = stop_handle.clone().shutdown() => break,
+            };
+
+            let service =
+                service
+                    .clone()
+                    .map_request(move |mut request: http::Request<Incoming>| {
+                        request.extensions_mut().insert(RemoteAddr(remote_addr));
+                        request
+                    });
+            let stop_handle = stop_handle.clone();
+            tokio::spawn(async move {
+                if let Err(e) =
+                    serve_with_graceful_shutdown(stream, service, stop_handle.shutdown()).await
+                {
+                    debug!(message = "tcp connection closed", %remote_addr, %e);
+                }
+            });
+        }
+    });
+
+    server_handle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_permissions() {
+        assert_eq!(parse_permissions("600"), Ok(0o600));
+        assert_eq!(parse_permissions("0o660"), Ok(0o660));
+        assert!(parse_permissions("1777").is_err());
+        assert!(parse_permissions("rw").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bind_replaces_stale_socket() -> eyre::Result<()> {
+        let path = std::env::temp_dir().join(format!("rollup-boost-{}.ipc", std::process::id()));
+        let endpoint = IpcEndpoint::new(path.clone(), 0o660);
+
+        drop(endpoint.bind()?);
+        let _listener = endpoint.bind()?;
+        assert_eq!(fs::metadata(&path)?.permissions().mode() & 0o777, 0o660);
+
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+}
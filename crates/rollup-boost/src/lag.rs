@@ -0,0 +1,167 @@
+use alloy_rpc_types_eth::BlockNumberOrTag;
+use clap::Parser;
+use metrics::{counter, gauge};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::{ClientResult, RpcClient};
+
+#[derive(Parser, Clone, Debug)]
+pub struct HeadLagArgs {
+    /// Maximum number of blocks between the unsafe and the safe head before the builder
+    /// is bypassed, unlimited if not set
+    #[arg(long, env)]
+    pub max_safe_head_lag: Option<u64>,
+
+    /// Maximum number of blocks between the unsafe and the finalized head before the
+    /// builder is bypassed, unlimited if not set
+    #[arg(long, env)]
+    pub max_finalized_head_lag: Option<u64>,
+}
+
+/// Block numbers of the heads of the last `ForkchoiceState`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HeadNumbers {
+    pub unsafe_number: u64,
+    pub safe_number: u64,
+    pub finalized_number: u64,
+}
+
+impl HeadNumbers {
+    pub fn safe_lag(&self) -> u64 {
+        self.unsafe_number.saturating_sub(self.safe_number)
+    }
+
+    pub fn finalized_lag(&self) -> u64 {
+        self.unsafe_number.saturating_sub(self.finalized_number)
+    }
+}
+
+#[derive(Debug, Default)]
+struct LagState {
+    heads: HeadNumbers,
+    exceeded: bool,
+}
+
+/// Tracks how far the safe and finalized heads trail the unsafe head. A growing gap
+/// means the derivation pipeline is not keeping up with the sequencer, in which case
+/// blocks are built by the l2 client only until the gap closes again.
+#[derive(Debug, Default)]
+pub struct HeadLagMonitor {
+    max_safe_lag: Option<u64>,
+    max_finalized_lag: Option<u64>,
+    state: Mutex<LagState>,
+}
+
+impl HeadLagMonitor {
+    pub fn new(max_safe_lag: Option<u64>, max_finalized_lag: Option<u64>) -> Self {
+        Self {
+            max_safe_lag,
+            max_finalized_lag,
+            state: Mutex::new(LagState::default()),
+        }
+    }
+
+    pub fn from_args(args: &HeadLagArgs) -> Self {
+        Self::new(args.max_safe_head_lag, args.max_finalized_head_lag)
+    }
+
+    pub fn heads(&self) -> HeadNumbers {
+        self.state.lock().heads
+    }
+
+    /// Whether the lag exceeds one of the thresholds, and the builder should be bypassed.
+    pub fn lag_exceeded(&self) -> bool {
+        self.state.lock().exceeded
+    }
+
+    /// Reads the block numbers of the heads from the l2 client, which tracks them from
+    /// the same fork choice updates.
+    pub async fn refresh(&self, l2_client: &RpcClient) -> ClientResult<()> {
+        let (unsafe_head, safe_head, finalized_head) = tokio::try_join!(
+            l2_client.get_block_by_number(BlockNumberOrTag::Latest, false),
+            l2_client.get_block_by_number(BlockNumberOrTag::Safe, false),
+            l2_client.get_block_by_number(BlockNumberOrTag::Finalized, false),
+        )?;
+
+        self.update(HeadNumbers {
+            unsafe_number: unsafe_head.header.number,
+            safe_number: safe_head.header.number,
+            finalized_number: finalized_head.header.number,
+        });
+        Ok(())
+    }
+
+    pub fn update(&self, heads: HeadNumbers) {
+        let (safe_lag, finalized_lag) = (heads.safe_lag(), heads.finalized_lag());
+        gauge!("head_lag.safe").set(safe_lag as f64);
+        gauge!("head_lag.finalized").set(finalized_lag as f64);
+        debug!(message = "head lag", safe_lag, finalized_lag);
+
+        let exceeded = self.max_safe_lag.is_some_and(|max| safe_lag > max)
+            || self
+                .max_finalized_lag
+                .is_some_and(|max| finalized_lag > max);
+
+        let mut state = self.state.lock();
+        state.heads = heads;
+        if exceeded == state.exceeded {
+            return;
+        }
+        state.exceeded = exceeded;
+
+        gauge!("head_lag.exceeded").set(if exceeded { 1 } else { 0 });
+        if exceeded {
+            counter!("head_lag.builder_bypassed").increment(1);
+            warn!(
+                message = "safe or finalized head lag exceeded, bypassing the builder",
+                safe_lag, finalized_lag
+            );
+        } else {
+            info!(
+                message = "safe and finalized head lag recovered, resuming the builder",
+                safe_lag, finalized_lag
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heads(unsafe_number: u64, safe_number: u64, finalized_number: u64) -> HeadNumbers {
+        HeadNumbers {
+            unsafe_number,
+            safe_number,
+            finalized_number,
+        }
+    }
+
+    #[test]
+    fn test_lag_thresholds() {
+        let monitor = HeadLagMonitor::new(Some(10), Some(100));
+
+        monitor.update(heads(100, 95, 0));
+        assert_eq!(monitor.heads().safe_lag(), 5);
+        assert!(!monitor.lag_exceeded());
+
+        monitor.update(heads(120, 95, 50));
+        assert!(monitor.lag_exceeded());
+
+        monitor.update(heads(200, 195, 50));
+        assert!(monitor.lag_exceeded());
+
+        monitor.update(heads(200, 195, 150));
+        assert!(!monitor.lag_exceeded());
+    }
+
+    #[test]
+    fn test_no_thresholds() {
+        let monitor = HeadLagMonitor::default();
+        monitor.update(heads(1000, 0, 0));
+        assert!(!monitor.lag_exceeded());
+        assert_eq!(monitor.heads().finalized_lag(), 1000);
+    }
+}
@@ -0,0 +1,258 @@
+use core::net::SocketAddr;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use clap::Parser;
+use metrics::{counter, gauge};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use url::Url;
+
+#[derive(Parser, Clone, Debug)]
+pub struct LeaderElectionArgs {
+    /// Lease file shared by the rollup-boost instances of an active/standby deployment.
+    /// Only the instance holding the lease forwards payload attributes to the builder
+    #[arg(long, env, value_name = "PATH")]
+    pub ha_lease_path: Option<PathBuf>,
+
+    /// Identifier of this instance in the lease, defaults to the hostname and process id
+    #[arg(long, env)]
+    pub ha_instance_id: Option<String>,
+
+    /// Duration in seconds the lease is held without being renewed
+    #[arg(long, env, default_value = "10")]
+    pub ha_lease_ttl: u64,
+
+    /// Address on which the leader streams the payload being built and the execution
+    /// mode to the standby instance, requires flashblocks
+    #[arg(long, env)]
+    pub ha_sync_addr: Option<SocketAddr>,
+
+    /// WebSocket URL of the state sync stream of the other instance
+    #[arg(long, env)]
+    pub ha_sync_peer_url: Option<Url>,
+
+    /// Path to the JWT secret shared by the instances, authenticating the state sync
+    /// stream. Required with `--ha-sync-addr` or `--ha-sync-peer-url`
+    #[arg(long, env, value_name = "PATH")]
+    pub ha_sync_jwt_path: Option<PathBuf>,
+}
+
+/// Contents of the lease file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct Lease {
+    holder: String,
+    expires_at_ms: u64,
+}
+
+/// Leadership of this instance, as reported by `debug_getStatus`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LeaderStatus {
+    pub instance_id: String,
+    pub is_leader: bool,
+    /// The instance holding the lease, if any
+    pub leader: Option<String>,
+}
+
+/// Elects a single leader among the rollup-boost instances sharing a lease file.
+///
+/// The leader renews the lease three times per TTL. A standby instance takes the lease
+/// over once it expires, so that a failed leader is replaced within one TTL. Updates of
+/// the lease are serialized with an exclusive lock on the file. Any error reading or
+/// writing the lease makes the instance step down, since building blocks with the l2
+/// client only is always safe.
+#[derive(Debug)]
+pub struct LeaderElection {
+    instance_id: String,
+    path: PathBuf,
+    ttl: Duration,
+    is_leader: AtomicBool,
+    leader: Mutex<Option<String>>,
+}
+
+impl LeaderElection {
+    pub fn new(instance_id: String, path: PathBuf, ttl: Duration) -> Self {
+        Self {
+            instance_id,
+            path,
+            ttl,
+            is_leader: AtomicBool::new(false),
+            leader: Mutex::new(None),
+        }
+    }
+
+    pub fn from_args(args: &LeaderElectionArgs) -> Option<Self> {
+        let path = args.ha_lease_path.clone()?;
+        let instance_id = args
+            .ha_instance_id
+            .clone()
+            .unwrap_or_else(default_instance_id);
+        Some(Self::new(
+            instance_id,
+            path,
+            Duration::from_secs(args.ha_lease_ttl),
+        ))
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.is_leader.load(Ordering::Relaxed)
+    }
+
+    pub fn instance_id(&self) -> &str {
+        &self.instance_id
+    }
+
+    /// The instance holding the lease, as of the last lease update.
+    pub fn leader(&self) -> Option<String> {
+        self.leader.lock().clone()
+    }
+
+    pub fn status(&self) -> LeaderStatus {
+        LeaderStatus {
+            instance_id: self.instance_id.clone(),
+            is_leader: self.is_leader(),
+            leader: self.leader(),
+        }
+    }
+
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.ttl / 3);
+            loop {
+                interval.tick().await;
+                let election = self.clone();
+                let result = tokio::task::spawn_blocking(move || election.try_acquire()).await;
+                match result {
+                    Ok(Ok(leader)) => self.set_leader(leader),
+                    Ok(Err(e)) => {
+                        warn!(message = "failed to update the leader lease", path = %self.path.display(), error = %e);
+                        self.set_leader(None);
+                    }
+                    Err(e) => {
+                        warn!(message = "leader election task failed", error = %e);
+                        self.set_leader(None);
+                    }
+                }
+            }
+        })
+    }
+
+    pub(crate) fn set_leader(&self, leader: Option<String>) {
+        let is_leader = leader.as_deref() == Some(self.instance_id.as_str());
+        *self.leader.lock() = leader.clone();
+
+        if self.is_leader.swap(is_leader, Ordering::Relaxed) == is_leader {
+            return;
+        }
+
+        gauge!("ha.leader").set(if is_leader { 1 } else { 0 });
+        counter!("ha.leader_changes").increment(1);
+        if is_leader {
+            info!(message = "acquired the leader lease", instance_id = %self.instance_id);
+        } else {
+            warn!(message = "lost the leader lease, standing by", instance_id = %self.instance_id, leader = ?leader);
+        }
+    }
+
+    /// Renews the lease if this instance holds it, or takes it over if it expired.
+    /// Returns the current holder of the lease.
+    fn try_acquire(&self) -> io::Result<Option<String>> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)?;
+        file.lock()?;
+        let result = self.update_lease(&mut file, now_ms());
+        file.unlock()?;
+        result
+    }
+
+    fn update_lease(&self, file: &mut File, now_ms: u64) -> io::Result<Option<String>> {
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let current: Option<Lease> = serde_json::from_str(&contents).ok();
+
+        if let Some(lease) = &current {
+            if lease.holder != self.instance_id && lease.expires_at_ms > now_ms {
+                return Ok(Some(lease.holder.clone()));
+            }
+        }
+
+        let lease = Lease {
+            holder: self.instance_id.clone(),
+            expires_at_ms: now_ms + self.ttl.as_millis() as u64,
+        };
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&serde_json::to_vec(&lease)?)?;
+        file.sync_all()?;
+
+        Ok(Some(lease.holder))
+    }
+}
+
+fn default_instance_id() -> String {
+    let hostname = std::env::var("HOSTNAME").unwrap_or_else(|_| "rollup-boost".to_string());
+    format!("{hostname}-{}", std::process::id())
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lease_takeover() -> eyre::Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("rollup-boost-lease-{}.json", std::process::id()));
+        let ttl = Duration::from_secs(10);
+        let a = LeaderElection::new("a".to_string(), path.clone(), ttl);
+        let b = LeaderElection::new("b".to_string(), path.clone(), ttl);
+        let open = || {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)
+        };
+
+        // The first instance takes the lease, the second one stands by
+        assert_eq!(a.update_lease(&mut open()?, 1_000)?, Some("a".to_string()));
+        assert_eq!(b.update_lease(&mut open()?, 2_000)?, Some("a".to_string()));
+
+        // The leader renews the lease before it expires
+        assert_eq!(a.update_lease(&mut open()?, 9_000)?, Some("a".to_string()));
+        assert_eq!(b.update_lease(&mut open()?, 12_000)?, Some("a".to_string()));
+
+        // The standby takes over once the lease expired
+        assert_eq!(b.update_lease(&mut open()?, 19_001)?, Some("b".to_string()));
+        assert_eq!(a.update_lease(&mut open()?, 19_002)?, Some("b".to_string()));
+
+        a.set_leader(Some("b".to_string()));
+        assert!(!a.is_leader());
+        b.set_leader(Some("b".to_string()));
+        assert!(b.is_leader());
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -1,7 +1,10 @@
 #![allow(clippy::complexity)]
 
 mod client;
-pub use client::{auth::*, http::*, rpc::*};
+pub use client::{auth::*, http::*, pool::*, rpc::*, tls::*};
+
+mod error;
+pub use error::*;
 
 mod cli;
 pub use cli::*;
@@ -12,9 +15,51 @@ pub use debug_api::*;
 mod metrics;
 pub use metrics::*;
 
+mod runtime;
+pub use runtime::*;
+
+mod profiling;
+pub use profiling::*;
+
 mod proxy;
 pub use proxy::*;
 
+mod rpc_log;
+pub use rpc_log::*;
+
+mod routing;
+pub use routing::*;
+
+mod registry;
+pub use registry::*;
+
+mod quarantine;
+pub use quarantine::*;
+
+mod scoring;
+pub use scoring::*;
+
+mod ingress;
+pub use ingress::*;
+
+mod ratelimit;
+pub use ratelimit::*;
+
+mod ws;
+pub use ws::*;
+
+mod ipc;
+pub use ipc::*;
+
+mod bind;
+pub use bind::*;
+
+mod cors;
+pub use cors::*;
+
+mod forwarded;
+pub use forwarded::*;
+
 mod server;
 pub use server::*;
 
@@ -27,6 +72,30 @@ pub use tracing::*;
 mod probe;
 pub use probe::*;
 
+mod lag;
+pub use lag::*;
+
+mod chain;
+pub use chain::*;
+
+mod multichain;
+pub use multichain::*;
+
+mod audit;
+pub use audit::*;
+
+mod webhook;
+pub use webhook::*;
+
+mod divergence;
+pub use divergence::*;
+
+mod leader;
+pub use leader::*;
+
+mod reorg;
+pub use reorg::*;
+
 mod health;
 pub use health::*;
 
@@ -39,7 +108,83 @@ pub use payload::*;
 mod selection;
 pub use selection::*;
 
+mod shadow;
+pub use shadow::*;
+
+mod validator;
+pub use validator::*;
+
+mod warmup;
+pub use warmup::*;
+
+mod drain;
+pub use drain::*;
+
+mod promotion;
+pub use promotion::*;
+
+mod policy;
+pub use policy::*;
+
 mod consistent_request;
 
+mod correlation;
+pub use correlation::*;
+
+mod dry_run;
+pub use dry_run::*;
+
 mod engine_api;
 pub use engine_api::*;
+
+mod fcu;
+pub use fcu::*;
+
+mod hook;
+pub use hook::*;
+
+mod chaos;
+pub use chaos::*;
+
+mod bench;
+pub use bench::*;
+
+mod stream;
+pub use stream::*;
+
+mod decode;
+pub use decode::*;
+
+mod embed;
+pub use embed::*;
+
+mod relay;
+pub use relay::*;
+
+mod plugin;
+pub use plugin::*;
+
+mod dashboard;
+pub use dashboard::*;
+
+mod clickhouse;
+pub use clickhouse::*;
+
+mod archive;
+pub use archive::*;
+
+mod history;
+pub use history::*;
+
+mod export;
+pub use export::*;
+
+#[cfg(feature = "mock-builder")]
+mod mock_builder;
+#[cfg(feature = "mock-builder")]
+pub use mock_builder::*;
+
+#[cfg(feature = "test-harness")]
+mod test_harness;
+#[cfg(feature = "test-harness")]
+pub use test_harness::*;
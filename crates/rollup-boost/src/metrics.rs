@@ -1,4 +1,9 @@
-use std::net::SocketAddr;
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
 
 use eyre::Result;
 use metrics_exporter_prometheus::PrometheusBuilder;
@@ -6,33 +11,133 @@ use metrics_util::layers::{PrefixLayer, Stack};
 use tokio::net::TcpListener;
 use tracing::{error, info};
 
-use http::StatusCode;
+use http::{Method, StatusCode};
 use hyper::service::service_fn;
 use hyper::{Request, Response, server::conn::http1};
 use hyper_util::rt::TokioIo;
 use jsonrpsee::http_client::HttpBody;
 use metrics_exporter_prometheus::PrometheusHandle;
+use serde::{Deserialize, Serialize};
 
-use crate::cli::Args;
+use crate::{ChainLabelLayer, CorsPolicy, bind_addrs, cli::Args, spawn_runtime_metrics};
 
+/// Interval at which the histograms are drained when the metrics are not scraped.
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+static METRICS_HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// A sample of a metric, as returned by `debug_getMetrics`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MetricSample {
+    /// Labels of the sample, including the `quantile` of summaries
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    pub value: f64,
+}
+
+/// Installs the metrics recorder, and serves the metrics to Prometheus if `--metrics` is
+/// set. The metrics are recorded either way so that they can be read with
+/// `debug_getMetrics`.
 pub fn init_metrics(args: &Args) -> Result<()> {
-    if args.metrics {
-        let recorder = PrometheusBuilder::new().build_recorder();
-        let handle = recorder.handle();
+    let recorder = PrometheusBuilder::new().build_recorder();
+    let handle = recorder.handle();
+
+    Stack::new(recorder)
+        .push(PrefixLayer::new("rollup-boost"))
+        .push(ChainLabelLayer)
+        .install()?;
+    let _ = METRICS_HANDLE.set(handle.clone());
 
-        Stack::new(recorder)
-            .push(PrefixLayer::new("rollup-boost"))
-            .install()?;
+    let upkeep = handle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(UPKEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            upkeep.run_upkeep();
+        }
+    });
+    spawn_runtime_metrics("main", tokio::runtime::Handle::current());
 
-        // Start the metrics server
-        let metrics_addr = format!("{}:{}", args.metrics_host, args.metrics_port);
-        let addr: SocketAddr = metrics_addr.parse()?;
-        tokio::spawn(init_metrics_server(addr, handle)); // Run the metrics server in a separate task
+    if args.metrics {
+        // Start the metrics server on each of the metrics hosts
+        let cors = CorsPolicy::from_args(&args.cors).map(Arc::new);
+        for addr in bind_addrs(&args.metrics_host, args.metrics_port)? {
+            tokio::spawn(init_metrics_server(addr, handle.clone(), cors.clone())); // Run the metrics server in a separate task
+        }
     }
     Ok(())
 }
 
-async fn init_metrics_server(addr: SocketAddr, handle: PrometheusHandle) -> eyre::Result<()> {
+/// Snapshot of the metrics recorded, by name, optionally restricted to the metrics whose
+/// name contains `filter`. `None` if the recorder is not installed.
+pub fn metrics_snapshot(filter: Option<&str>) -> Option<BTreeMap<String, Vec<MetricSample>>> {
+    METRICS_HANDLE
+        .get()
+        .map(|handle| parse_metrics(&handle.render(), filter))
+}
+
+/// Parses metrics in the Prometheus text format.
+fn parse_metrics(text: &str, filter: Option<&str>) -> BTreeMap<String, Vec<MetricSample>> {
+    let mut metrics = BTreeMap::<String, Vec<MetricSample>>::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((series, value)) = line.rsplit_once(' ') else {
+            continue;
+        };
+        let Ok(value) = value.parse::<f64>() else {
+            continue;
+        };
+        let (name, labels) = match series.split_once('{') {
+            Some((name, labels)) => (name, parse_labels(labels.trim_end_matches('}'))),
+            None => (series, BTreeMap::new()),
+        };
+        if filter.is_some_and(|filter| !name.contains(filter)) {
+            continue;
+        }
+        metrics
+            .entry(name.to_string())
+            .or_default()
+            .push(MetricSample { labels, value });
+    }
+    metrics
+}
+
+/// Parses the `key="value",...` labels of a sample, unescaping the values.
+fn parse_labels(labels: &str) -> BTreeMap<String, String> {
+    let mut parsed = BTreeMap::new();
+    let mut chars = labels.chars();
+    loop {
+        let key: String = chars
+            .by_ref()
+            .take_while(|c| *c != '=')
+            .filter(|c| *c != ',')
+            .collect();
+        if key.is_empty() || chars.next() != Some('"') {
+            return parsed;
+        }
+        let mut value = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => break,
+                '\\' => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some(c) => value.push(c),
+                    None => break,
+                },
+                c => value.push(c),
+            }
+        }
+        parsed.insert(key.trim().to_string(), value);
+    }
+}
+
+async fn init_metrics_server(
+    addr: SocketAddr,
+    handle: PrometheusHandle,
+    cors: Option<Arc<CorsPolicy>>,
+) -> eyre::Result<()> {
     let listener = TcpListener::bind(addr).await?;
     info!("Metrics server running on {}", addr);
 
@@ -40,10 +145,14 @@ async fn init_metrics_server(addr: SocketAddr, handle: PrometheusHandle) -> eyre
         match listener.accept().await {
             Ok((stream, _)) => {
                 let handle = handle.clone(); // Clone the handle for each connection
+                let cors = cors.clone();
                 tokio::task::spawn(async move {
-                    let service = service_fn(move |_req: Request<hyper::body::Incoming>| {
-                        let response = match _req.uri().path() {
-                            "/metrics" => Response::builder()
+                    let service = service_fn(move |req: Request<hyper::body::Incoming>| {
+                        let mut response = match (req.method(), req.uri().path(), &cors) {
+                            (&Method::OPTIONS, _, Some(cors)) => {
+                                cors.preflight(req.headers()).map(|()| HttpBody::empty())
+                            }
+                            (_, "/metrics", _) => Response::builder()
                                 .header("content-type", "text/plain")
                                 .body(HttpBody::from(handle.render()))
                                 .expect("Failed to create metrics response"),
@@ -52,6 +161,9 @@ async fn init_metrics_server(addr: SocketAddr, handle: PrometheusHandle) -> eyre
                                 .body(HttpBody::empty())
                                 .expect("Failed to create not found response"),
                         };
+                        if let (Some(cors), false) = (&cors, req.method() == Method::OPTIONS) {
+                            cors.apply(req.headers(), &mut response);
+                        }
 
                         async { Ok::<_, hyper::Error>(response) }
                     });
@@ -69,3 +181,44 @@ async fn init_metrics_server(addr: SocketAddr, handle: PrometheusHandle) -> eyre
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_metrics() {
+        let text = r#"# TYPE rollup_boost_archive_payloads counter
+rollup_boost_archive_payloads 12
+
+# TYPE rollup_boost_rpc_latency summary
+rollup_boost_rpc_latency{method="engine_getPayloadV3",quantile="0.5"} 0.012
+rollup_boost_rpc_latency{method="engine_getPayloadV3",quantile="0.99"} 0.25
+rollup_boost_rpc_latency_count{method="engine_getPayloadV3"} 7
+rollup_boost_build_info{version="a \"quoted\", value"} 1
+"#;
+        let metrics = parse_metrics(text, None);
+        assert_eq!(metrics["rollup_boost_archive_payloads"][0].value, 12.0);
+        assert!(
+            metrics["rollup_boost_archive_payloads"][0]
+                .labels
+                .is_empty()
+        );
+
+        let latency = &metrics["rollup_boost_rpc_latency"];
+        assert_eq!(latency.len(), 2);
+        assert_eq!(latency[1].labels["method"], "engine_getPayloadV3");
+        assert_eq!(latency[1].labels["quantile"], "0.99");
+        assert_eq!(latency[1].value, 0.25);
+        assert_eq!(
+            metrics["rollup_boost_build_info"][0].labels["version"],
+            "a \"quoted\", value"
+        );
+
+        let filtered = parse_metrics(text, Some("latency"));
+        assert_eq!(
+            filtered.keys().collect::<Vec<_>>(),
+            ["rollup_boost_rpc_latency", "rollup_boost_rpc_latency_count"]
+        );
+    }
+}
@@ -0,0 +1,594 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use alloy_consensus::EMPTY_ROOT_HASH;
+use alloy_primitives::{B64, B256, Bytes, U64, U256};
+use alloy_rpc_types_engine::{
+    BlobsBundleV1, ExecutionPayloadBodiesV1, ExecutionPayloadEnvelopeV2, ExecutionPayloadInputV2,
+    ExecutionPayloadV1, ExecutionPayloadV2, ExecutionPayloadV3, ForkchoiceState, ForkchoiceUpdated,
+    PayloadId, PayloadStatus, PayloadStatusEnum,
+};
+use alloy_rpc_types_eth::{Block, BlockNumberOrTag, Header as EthHeader};
+use clap::Parser;
+use jsonrpsee::core::{RpcResult, async_trait};
+use jsonrpsee::server::{Server, ServerHandle};
+use jsonrpsee::types::ErrorObject;
+use jsonrpsee::types::error::INVALID_PARAMS_CODE;
+use op_alloy_rpc_types_engine::{
+    OpExecutionPayloadEnvelopeV3, OpExecutionPayloadEnvelopeV4, OpExecutionPayloadV4,
+    OpPayloadAttributes,
+};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::flashblocks::WebSocketPublisher;
+use crate::{
+    EngineApiServer, ExecutionPayloadBaseV1, ExecutionPayloadFlashblockDeltaV1,
+    FlashblocksPayloadV1, OpExecutionPayloadEnvelope,
+};
+
+/// Gas used by each synthetic transaction.
+const TRANSACTION_GAS: u64 = 21_000;
+
+/// Gas limit of the blocks built for attributes without one.
+const DEFAULT_GAS_LIMIT: u64 = 30_000_000;
+
+#[derive(Parser, Clone, Debug)]
+#[command(about = "Mock block builder streaming synthetic flashblocks to rollup-boost")]
+pub struct MockBuilderArgs {
+    /// Address to serve the Engine API on. The JWT of the requests is not checked
+    #[arg(long, env = "MOCK_BUILDER_RPC_ADDR", default_value = "127.0.0.1:8551")]
+    pub rpc_addr: SocketAddr,
+
+    /// Address to stream the flashblocks on
+    #[arg(
+        long,
+        env = "MOCK_BUILDER_FLASHBLOCKS_ADDR",
+        default_value = "127.0.0.1:1111"
+    )]
+    pub flashblocks_addr: SocketAddr,
+
+    /// Number of flashblocks streamed for each payload
+    #[arg(long, env = "MOCK_BUILDER_FLASHBLOCKS_PER_BLOCK", default_value = "10")]
+    pub flashblocks_per_block: u64,
+
+    /// Interval in milliseconds between two flashblocks of a payload
+    #[arg(long, env = "MOCK_BUILDER_FLASHBLOCKS_INTERVAL", default_value = "200")]
+    pub flashblocks_interval: u64,
+
+    /// Number of synthetic transactions in each flashblock
+    #[arg(long, env = "MOCK_BUILDER_TRANSACTIONS", default_value = "10")]
+    pub transactions_per_flashblock: usize,
+
+    /// Size in bytes of the synthetic transactions
+    #[arg(long, env = "MOCK_BUILDER_TRANSACTION_SIZE", default_value = "128")]
+    pub transaction_size: usize,
+
+    /// Size in bytes of the delta streamed by the `huge-delta` fault
+    #[arg(long, env = "MOCK_BUILDER_HUGE_DELTA_SIZE", default_value = "67108864")]
+    pub huge_delta_size: usize,
+
+    /// Build Isthmus payloads, whose block hash commits to the withdrawals root
+    #[arg(long, env = "MOCK_BUILDER_ISTHMUS")]
+    pub isthmus: bool,
+
+    /// Faults of the successive payloads, e.g. `none,gap,none,wrong-payload-id`. The
+    /// script is repeated once all its faults have been used
+    #[arg(long, env = "MOCK_BUILDER_SCRIPT", value_delimiter = ',')]
+    pub script: Vec<MockBuilderFault>,
+}
+
+impl MockBuilderArgs {
+    pub async fn run(self) -> eyre::Result<()> {
+        let config = MockBuilderConfig::from_args(&self);
+        let builder = MockBuilder::new(config).with_flashblocks(self.flashblocks_addr)?;
+        let (handle, addr) = builder.start(self.rpc_addr).await?;
+        info!(message = "mock builder listening", %addr, flashblocks_addr = %self.flashblocks_addr);
+
+        tokio::select! {
+            _ = handle.clone().stopped() => {}
+            _ = tokio::signal::ctrl_c() => {
+                let _ = handle.stop();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A misbehavior of the mock builder, applied to a whole payload.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Default, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum MockBuilderFault {
+    /// Behave correctly
+    #[default]
+    None,
+    /// Skip the second flashblock of the payload
+    Gap,
+    /// Stream the flashblocks with another payload ID than the one returned by the
+    /// fork choice update
+    WrongPayloadId,
+    /// Stream a second flashblock larger than the default limits of rollup-boost
+    HugeDelta,
+    /// Fail the `engine_getPayload` call
+    NoPayload,
+}
+
+/// Shape of the payloads built by a [`MockBuilder`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockBuilderConfig {
+    pub flashblocks_per_block: u64,
+    pub flashblocks_interval: Duration,
+    pub transactions_per_flashblock: usize,
+    pub transaction_size: usize,
+    pub huge_delta_size: usize,
+    pub isthmus: bool,
+    pub script: Vec<MockBuilderFault>,
+}
+
+impl Default for MockBuilderConfig {
+    fn default() -> Self {
+        Self {
+            flashblocks_per_block: 10,
+            flashblocks_interval: Duration::from_millis(200),
+            transactions_per_flashblock: 10,
+            transaction_size: 128,
+            huge_delta_size: 64 * 1024 * 1024,
+            isthmus: false,
+            script: vec![],
+        }
+    }
+}
+
+impl MockBuilderConfig {
+    pub fn from_args(args: &MockBuilderArgs) -> Self {
+        Self {
+            flashblocks_per_block: args.flashblocks_per_block,
+            flashblocks_interval: Duration::from_millis(args.flashblocks_interval),
+            transactions_per_flashblock: args.transactions_per_flashblock,
+            transaction_size: args.transaction_size,
+            huge_delta_size: args.huge_delta_size,
+            isthmus: args.isthmus,
+            script: args.script.clone(),
+        }
+    }
+
+    /// The fault of the `n`th payload.
+    fn fault(&self, n: u64) -> MockBuilderFault {
+        if self.script.is_empty() {
+            return MockBuilderFault::None;
+        }
+        self.script[n as usize % self.script.len()]
+    }
+}
+
+/// A payload being built by the mock builder, extended with each flashblock.
+#[derive(Debug)]
+struct MockPayload {
+    base: ExecutionPayloadBaseV1,
+    fault: MockBuilderFault,
+    isthmus: bool,
+    transactions: Vec<Bytes>,
+    state_root: B256,
+    receipts_root: B256,
+    block_hash: B256,
+}
+
+impl MockPayload {
+    /// Appends `transactions` and updates the roots and block hash of the payload.
+    fn extend(&mut self, transactions: Vec<Bytes>) -> ExecutionPayloadFlashblockDeltaV1 {
+        self.transactions.extend(transactions.iter().cloned());
+        self.state_root = B256::random();
+        self.receipts_root = B256::random();
+        let envelope = self.envelope();
+        self.block_hash = envelope.header().hash_slow();
+
+        ExecutionPayloadFlashblockDeltaV1 {
+            state_root: self.state_root,
+            receipts_root: self.receipts_root,
+            gas_used: envelope.gas_used(),
+            block_hash: self.block_hash,
+            transactions,
+            withdrawals_root: EMPTY_ROOT_HASH,
+            ..Default::default()
+        }
+    }
+
+    fn envelope(&self) -> OpExecutionPayloadEnvelope {
+        let execution_payload = ExecutionPayloadV3 {
+            blob_gas_used: 0,
+            excess_blob_gas: 0,
+            payload_inner: ExecutionPayloadV2 {
+                withdrawals: vec![],
+                payload_inner: ExecutionPayloadV1 {
+                    parent_hash: self.base.parent_hash,
+                    fee_recipient: self.base.fee_recipient,
+                    state_root: self.state_root,
+                    receipts_root: self.receipts_root,
+                    logs_bloom: Default::default(),
+                    prev_randao: self.base.prev_randao,
+                    block_number: self.base.block_number,
+                    gas_limit: self.base.gas_limit,
+                    gas_used: self.transactions.len() as u64 * TRANSACTION_GAS,
+                    timestamp: self.base.timestamp,
+                    extra_data: self.base.extra_data.clone(),
+                    base_fee_per_gas: self.base.base_fee_per_gas,
+                    block_hash: self.block_hash,
+                    transactions: self.transactions.clone(),
+                },
+            },
+        };
+
+        match self.isthmus {
+            true => OpExecutionPayloadEnvelope::V4(OpExecutionPayloadEnvelopeV4 {
+                parent_beacon_block_root: self.base.parent_beacon_block_root,
+                block_value: U256::ZERO,
+                blobs_bundle: BlobsBundleV1::default(),
+                should_override_builder: false,
+                execution_payload: OpExecutionPayloadV4 {
+                    withdrawals_root: EMPTY_ROOT_HASH,
+                    payload_inner: execution_payload,
+                },
+                execution_requests: vec![],
+            }),
+            false => OpExecutionPayloadEnvelope::V3(OpExecutionPayloadEnvelopeV3 {
+                parent_beacon_block_root: self.base.parent_beacon_block_root,
+                block_value: U256::ZERO,
+                blobs_bundle: BlobsBundleV1::default(),
+                should_override_builder: false,
+                execution_payload,
+            }),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PayloadJob {
+    payload: Arc<Mutex<MockPayload>>,
+    stream: JoinHandle<()>,
+}
+
+/// A block builder speaking the Engine API, which streams synthetic flashblocks for the
+/// payload attributes it is sent, so that rollup-boost can be exercised end to end
+/// without a real builder. It can be scripted to misbehave with [`MockBuilderFault`]s.
+///
+/// The synthetic transactions are random bytes, so the payloads are not executable:
+/// they are only meant to be accepted or rejected by rollup-boost.
+#[derive(Debug, Clone)]
+pub struct MockBuilder {
+    config: Arc<MockBuilderConfig>,
+    publisher: Option<Arc<WebSocketPublisher>>,
+    payloads: Arc<Mutex<HashMap<PayloadId, PayloadJob>>>,
+    payload_count: Arc<AtomicU64>,
+    head_number: Arc<AtomicU64>,
+}
+
+impl MockBuilder {
+    pub fn new(config: MockBuilderConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            publisher: None,
+            payloads: Arc::new(Mutex::new(HashMap::new())),
+            payload_count: Arc::new(AtomicU64::new(0)),
+            head_number: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Streams the flashblocks of the payloads on `addr`. Must be called from a tokio
+    /// runtime.
+    pub fn with_flashblocks(mut self, addr: SocketAddr) -> std::io::Result<Self> {
+        self.publisher = Some(Arc::new(WebSocketPublisher::new(addr, None)?));
+        Ok(self)
+    }
+
+    /// Address the flashblocks are streamed on, if any.
+    pub fn flashblocks_addr(&self) -> Option<SocketAddr> {
+        self.publisher
+            .as_ref()
+            .map(|publisher| publisher.local_addr())
+    }
+
+    /// Serves the Engine API on `addr`. Returns the handle of the server and the address
+    /// it listens on.
+    pub async fn start(self, addr: SocketAddr) -> eyre::Result<(ServerHandle, SocketAddr)> {
+        let server = Server::builder().build(addr).await?;
+        let addr = server.local_addr()?;
+        Ok((server.start(self.into_rpc()), addr))
+    }
+
+    fn start_payload(&self, state: ForkchoiceState, attributes: OpPayloadAttributes) -> PayloadId {
+        let n = self.payload_count.fetch_add(1, Ordering::Relaxed);
+        let payload_id = PayloadId::new(n.to_be_bytes());
+        let fault = self.config.fault(n);
+        let attributes_v2 = attributes.payload_attributes;
+
+        let base = ExecutionPayloadBaseV1 {
+            parent_beacon_block_root: attributes_v2.parent_beacon_block_root.unwrap_or_default(),
+            parent_hash: state.head_block_hash,
+            fee_recipient: attributes_v2.suggested_fee_recipient,
+            prev_randao: attributes_v2.prev_randao,
+            block_number: self.head_number.load(Ordering::Relaxed) + 1,
+            gas_limit: attributes.gas_limit.unwrap_or(DEFAULT_GAS_LIMIT),
+            timestamp: attributes_v2.timestamp,
+            extra_data: attributes
+                .eip_1559_params
+                .map(|params| [&[0u8][..], params.as_slice()].concat().into())
+                .unwrap_or_default(),
+            base_fee_per_gas: U256::from(1),
+            eip_1559_params: attributes.eip_1559_params,
+        };
+        let payload = Arc::new(Mutex::new(MockPayload {
+            base,
+            fault,
+            isthmus: self.config.isthmus,
+            transactions: vec![],
+            state_root: B256::ZERO,
+            receipts_root: B256::ZERO,
+            block_hash: B256::ZERO,
+        }));
+
+        let stream = tokio::spawn(stream_flashblocks(
+            self.config.clone(),
+            self.publisher.clone(),
+            payload_id,
+            attributes.transactions.unwrap_or_default(),
+            payload.clone(),
+        ));
+        let previous = self
+            .payloads
+            .lock()
+            .insert(payload_id, PayloadJob { payload, stream });
+        if let Some(previous) = previous {
+            previous.stream.abort();
+        }
+        payload_id
+    }
+
+    /// Stops streaming the flashblocks of `payload_id` and returns its payload, as built
+    /// so far.
+    fn take_payload(&self, payload_id: PayloadId) -> RpcResult<OpExecutionPayloadEnvelope> {
+        let job = self.payloads.lock().remove(&payload_id).ok_or_else(|| {
+            ErrorObject::owned(INVALID_PARAMS_CODE, "unknown payload", None::<()>)
+        })?;
+        job.stream.abort();
+
+        let payload = job.payload.lock();
+        if payload.fault == MockBuilderFault::NoPayload {
+            return Err(ErrorObject::owned(
+                INVALID_PARAMS_CODE,
+                "payload unavailable",
+                None::<()>,
+            ));
+        }
+        self.head_number
+            .store(payload.base.block_number, Ordering::Relaxed);
+        Ok(payload.envelope())
+    }
+}
+
+/// Streams the flashblocks of a payload, applying its fault, until all have been sent or
+/// the payload is requested.
+async fn stream_flashblocks(
+    config: Arc<MockBuilderConfig>,
+    publisher: Option<Arc<WebSocketPublisher>>,
+    payload_id: PayloadId,
+    mut transactions: Vec<Bytes>,
+    payload: Arc<Mutex<MockPayload>>,
+) {
+    let fault = payload.lock().fault;
+    let streamed_id = match fault {
+        MockBuilderFault::WrongPayloadId => PayloadId::new(B64::random().0),
+        _ => payload_id,
+    };
+
+    let mut interval = tokio::time::interval(config.flashblocks_interval);
+    for index in 0..config.flashblocks_per_block {
+        interval.tick().await;
+
+        transactions.extend(
+            (0..config.transactions_per_flashblock)
+                .map(|_| synthetic_transaction(config.transaction_size)),
+        );
+        let (base, mut diff) = {
+            let mut payload = payload.lock();
+            let diff = payload.extend(std::mem::take(&mut transactions));
+            ((index == 0).then(|| payload.base.clone()), diff)
+        };
+
+        if index == 1 {
+            match fault {
+                MockBuilderFault::Gap => continue,
+                MockBuilderFault::HugeDelta => diff
+                    .transactions
+                    .push(synthetic_transaction(config.huge_delta_size)),
+                _ => {}
+            }
+        }
+
+        let flashblock = FlashblocksPayloadV1 {
+            payload_id: streamed_id,
+            index,
+            base,
+            diff,
+            metadata: serde_json::json!({ "fault": fault }),
+        };
+        if let Some(Err(e)) = publisher.as_ref().map(|p| p.publish(&flashblock)) {
+            warn!(message = "failed to publish flashblock", error = %e);
+        }
+    }
+}
+
+/// Random bytes standing for a transaction of `size` bytes.
+fn synthetic_transaction(size: usize) -> Bytes {
+    std::iter::repeat_with(B256::random)
+        .flat_map(|chunk| chunk.0)
+        .take(size)
+        .collect::<Vec<u8>>()
+        .into()
+}
+
+fn valid() -> PayloadStatus {
+    PayloadStatus::from_status(PayloadStatusEnum::Valid)
+}
+
+fn unsupported(method: &str) -> ErrorObject<'static> {
+    ErrorObject::owned(
+        INVALID_PARAMS_CODE,
+        format!("{method} is not supported by the mock builder"),
+        None::<()>,
+    )
+}
+
+#[async_trait]
+impl EngineApiServer for MockBuilder {
+    async fn fork_choice_updated_v3(
+        &self,
+        fork_choice_state: ForkchoiceState,
+        payload_attributes: Option<OpPayloadAttributes>,
+    ) -> RpcResult<ForkchoiceUpdated> {
+        let payload_id =
+            payload_attributes.map(|attributes| self.start_payload(fork_choice_state, attributes));
+        Ok(ForkchoiceUpdated {
+            payload_status: valid().with_latest_valid_hash(fork_choice_state.head_block_hash),
+            payload_id,
+        })
+    }
+
+    async fn get_payload_v1(&self, _payload_id: PayloadId) -> RpcResult<ExecutionPayloadV1> {
+        Err(unsupported("engine_getPayloadV1"))
+    }
+
+    async fn new_payload_v1(&self, _payload: ExecutionPayloadV1) -> RpcResult<PayloadStatus> {
+        Ok(valid())
+    }
+
+    async fn get_payload_v2(
+        &self,
+        _payload_id: PayloadId,
+    ) -> RpcResult<ExecutionPayloadEnvelopeV2> {
+        Err(unsupported("engine_getPayloadV2"))
+    }
+
+    async fn new_payload_v2(&self, _payload: ExecutionPayloadInputV2) -> RpcResult<PayloadStatus> {
+        Ok(valid())
+    }
+
+    async fn get_payload_v3(
+        &self,
+        payload_id: PayloadId,
+    ) -> RpcResult<OpExecutionPayloadEnvelopeV3> {
+        match self.take_payload(payload_id)? {
+            OpExecutionPayloadEnvelope::V3(v3) => Ok(v3),
+            _ => Err(unsupported("engine_getPayloadV3 with --isthmus")),
+        }
+    }
+
+    async fn new_payload_v3(
+        &self,
+        _payload: ExecutionPayloadV3,
+        _versioned_hashes: Vec<B256>,
+        _parent_beacon_block_root: B256,
+    ) -> RpcResult<PayloadStatus> {
+        Ok(valid())
+    }
+
+    async fn get_payload_v4(
+        &self,
+        payload_id: PayloadId,
+    ) -> RpcResult<OpExecutionPayloadEnvelopeV4> {
+        match self.take_payload(payload_id)? {
+            OpExecutionPayloadEnvelope::V4(v4) => Ok(v4),
+            _ => Err(unsupported("engine_getPayloadV4 without --isthmus")),
+        }
+    }
+
+    async fn new_payload_v4(
+        &self,
+        _payload: OpExecutionPayloadV4,
+        _versioned_hashes: Vec<B256>,
+        _parent_beacon_block_root: B256,
+        _execution_requests: Vec<Bytes>,
+    ) -> RpcResult<PayloadStatus> {
+        Ok(valid())
+    }
+
+    async fn get_payload_bodies_by_hash_v1(
+        &self,
+        block_hashes: Vec<B256>,
+    ) -> RpcResult<ExecutionPayloadBodiesV1> {
+        Ok(vec![None; block_hashes.len()])
+    }
+
+    async fn get_payload_bodies_by_range_v1(
+        &self,
+        _start: U64,
+        _count: U64,
+    ) -> RpcResult<ExecutionPayloadBodiesV1> {
+        Ok(vec![])
+    }
+
+    async fn exchange_capabilities(&self, capabilities: Vec<String>) -> RpcResult<Vec<String>> {
+        Ok(capabilities)
+    }
+
+    async fn get_block_by_number(
+        &self,
+        _number: BlockNumberOrTag,
+        _full: bool,
+    ) -> RpcResult<Block> {
+        // The latest block is always fresh, so that the builder is considered healthy
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Ok(Block {
+            header: EthHeader {
+                inner: alloy_consensus::Header {
+                    number: self.head_number.load(Ordering::Relaxed),
+                    timestamp,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_builder_faults() -> eyre::Result<()> {
+        let builder = MockBuilder::new(MockBuilderConfig {
+            flashblocks_per_block: 3,
+            flashblocks_interval: Duration::from_millis(1),
+            transactions_per_flashblock: 2,
+            script: vec![MockBuilderFault::None, MockBuilderFault::NoPayload],
+            ..Default::default()
+        });
+        let state = ForkchoiceState::default();
+
+        let payload_id = builder.start_payload(state, OpPayloadAttributes::default());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let envelope = builder.take_payload(payload_id)?;
+        assert_eq!(envelope.tx_count(), 6);
+        assert_eq!(envelope.header().hash_slow(), envelope.block_hash());
+        assert_eq!(envelope.gas_used(), 6 * TRANSACTION_GAS);
+
+        // The second payload follows the script
+        let payload_id = builder.start_payload(state, OpPayloadAttributes::default());
+        assert!(builder.take_payload(payload_id).is_err());
+        assert!(builder.take_payload(payload_id).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,358 @@
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    fs,
+    future::Future,
+    path::Path,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use clap::{CommandFactory, FromArgMatches};
+use metrics::{
+    Counter, Gauge, Histogram, Key, KeyName, Label, Metadata, Recorder, SharedString, Unit,
+};
+use metrics_util::layers::Layer;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::runtime::Builder;
+use tracing::{Span, info_span, span::EnteredSpan};
+
+use crate::Args;
+
+thread_local! {
+    /// Chain served by the current thread, in a process serving several chains.
+    static CURRENT_CHAIN: RefCell<Option<Chain>> = const { RefCell::new(None) };
+    /// Span of the chain served by the current thread, entered for the lifetime of the
+    /// thread.
+    static CHAIN_SPAN: RefCell<Option<EnteredSpan>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug, Error)]
+pub enum ChainsConfigError {
+    #[error("no chain is configured")]
+    Empty,
+    #[error("chain {0} is configured more than once")]
+    DuplicateName(String),
+    #[error("invalid arguments for chain {name}: {source}")]
+    Args {
+        name: String,
+        #[source]
+        source: clap::Error,
+    },
+    #[error("chain {0} cannot run a command or load another chains config")]
+    Nested(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// A chain served by a rollup-boost process serving several chains.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ChainConfig {
+    /// Name of the chain, used in the logs
+    pub name: String,
+    /// Command line arguments of the chain, as they would be given to a rollup-boost
+    /// process serving only this chain
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// The chains served by a single rollup-boost process, loaded from `--chains-config`.
+///
+/// Each chain has its own listener ports, builder and L2 clients and flashblocks
+/// service, and is configured with the same arguments as a standalone process.
+/// Arguments that are not given fall back to their defaults, not to the environment of
+/// the process, which is shared by all the chains.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChainsConfig {
+    pub chains: Vec<ChainConfig>,
+}
+
+impl ChainsConfig {
+    pub fn load(path: &Path) -> Result<Self, ChainsConfigError> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    /// Parses the arguments of each chain.
+    pub fn into_args(self) -> Result<Vec<(String, Args)>, ChainsConfigError> {
+        if self.chains.is_empty() {
+            return Err(ChainsConfigError::Empty);
+        }
+
+        let mut names = HashSet::new();
+        let mut chains = Vec::with_capacity(self.chains.len());
+        for chain in self.chains {
+            if !names.insert(chain.name.clone()) {
+                return Err(ChainsConfigError::DuplicateName(chain.name));
+            }
+
+            let args = chain_command()
+                .try_get_matches_from(std::iter::once("rollup-boost".to_string()).chain(chain.args))
+                .and_then(|matches| Args::from_arg_matches(&matches))
+                .map_err(|source| ChainsConfigError::Args {
+                    name: chain.name.clone(),
+                    source,
+                })?;
+            if args.command.is_some() || args.chains_config.is_some() {
+                return Err(ChainsConfigError::Nested(chain.name));
+            }
+
+            chains.push((chain.name, args));
+        }
+        Ok(chains)
+    }
+}
+
+/// The command line of a chain, without the environment variables fallback.
+fn chain_command() -> clap::Command {
+    Args::command().mut_args(|arg| arg.env(None::<&'static str>))
+}
+
+/// A chain of the chains config, which the metrics and logs of its threads are
+/// attributed to.
+#[derive(Clone, Debug)]
+struct Chain {
+    name: Arc<str>,
+    span: Span,
+}
+
+/// Runs `serve` on a runtime of its own for the chain `name`, returning its output. The
+/// threads of the runtime record their metrics with a `chain` label and emit their logs
+/// in a `chain` span.
+pub async fn spawn_chain<F, Fut, T>(
+    name: &str,
+    worker_threads: Option<usize>,
+    serve: F,
+) -> eyre::Result<T>
+where
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = eyre::Result<T>>,
+    T: Send + 'static,
+{
+    let chain = Chain {
+        name: name.into(),
+        span: info_span!(parent: None, "chain", chain = %name),
+    };
+    let mut builder = Builder::new_multi_thread();
+    if let Some(threads) = worker_threads {
+        builder.worker_threads(threads.max(1));
+    }
+    let thread_chain = chain.clone();
+    let runtime = builder
+        .thread_name(format!("chain-{name}"))
+        .on_thread_start(move || enter_chain(thread_chain.clone()))
+        .enable_all()
+        .build()?;
+
+    // The runtime is driven by its own thread for as long as the process runs
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    std::thread::Builder::new()
+        .name(format!("chain-{name}"))
+        .spawn(move || {
+            enter_chain(chain);
+            runtime.block_on(async move {
+                let _ = tx.send(serve().await);
+                std::future::pending::<()>().await
+            })
+        })?;
+    rx.await?
+}
+
+fn enter_chain(chain: Chain) {
+    CHAIN_SPAN.with(|span| *span.borrow_mut() = Some(chain.span.clone().entered()));
+    CURRENT_CHAIN.with(|current| *current.borrow_mut() = Some(chain));
+}
+
+/// Name of the chain served by the current thread, if the process serves several
+/// chains.
+pub fn current_chain() -> Option<Arc<str>> {
+    CURRENT_CHAIN.with(|current| current.borrow().as_ref().map(|chain| chain.name.clone()))
+}
+
+/// Attributes `future` to the chain of the calling thread wherever it is polled, for the
+/// tasks spawned on a runtime shared by the chains.
+pub fn in_current_chain<F: Future>(future: F) -> InChain<F> {
+    InChain {
+        chain: CURRENT_CHAIN.with(|current| current.borrow().clone()),
+        future: Box::pin(future),
+    }
+}
+
+/// Future returned by [`in_current_chain`].
+pub struct InChain<F> {
+    chain: Option<Chain>,
+    future: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for InChain<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Some(chain) = self.chain.clone() else {
+            return self.future.as_mut().poll(cx);
+        };
+        let _enter = chain.span.enter();
+        let previous = CURRENT_CHAIN.with(|current| current.replace(Some(chain)));
+        let poll = self.future.as_mut().poll(cx);
+        CURRENT_CHAIN.with(|current| *current.borrow_mut() = previous);
+        poll
+    }
+}
+
+/// A metrics layer labelling the metrics recorded on the threads of a chain by `chain`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ChainLabelLayer;
+
+impl<R> Layer<R> for ChainLabelLayer {
+    type Output = ChainLabel<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        ChainLabel(inner)
+    }
+}
+
+/// Recorder returned by [`ChainLabelLayer`].
+#[derive(Debug)]
+pub struct ChainLabel<R>(R);
+
+fn chain_key(key: &Key) -> Option<Key> {
+    current_chain().map(|chain| key.with_extra_labels(vec![Label::new("chain", chain.to_string())]))
+}
+
+impl<R: Recorder> Recorder for ChainLabel<R> {
+    fn describe_counter(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.0.describe_counter(key, unit, description)
+    }
+
+    fn describe_gauge(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.0.describe_gauge(key, unit, description)
+    }
+
+    fn describe_histogram(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.0.describe_histogram(key, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        match chain_key(key) {
+            Some(key) => self.0.register_counter(&key, metadata),
+            None => self.0.register_counter(key, metadata),
+        }
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        match chain_key(key) {
+            Some(key) => self.0.register_gauge(&key, metadata),
+            None => self.0.register_gauge(key, metadata),
+        }
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        match chain_key(key) {
+            Some(key) => self.0.register_histogram(&key, metadata),
+            None => self.0.register_histogram(key, metadata),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(name: &str, args: &[&str]) -> ChainConfig {
+        ChainConfig {
+            name: name.to_string(),
+            args: args.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn test_chains_args() -> eyre::Result<()> {
+        let config = ChainsConfig {
+            chains: vec![
+                chain("a", &["--rpc-port", "9001", "--debug-server-port", "9101"]),
+                chain("b", &["--rpc-port", "9002", "--debug-server-port", "9102"]),
+            ],
+        };
+        let chains = config.into_args()?;
+        assert_eq!(chains.len(), 2);
+        assert_eq!(chains[0].0, "a");
+        assert_eq!(chains[0].1.rpc_port, 9001);
+        assert_eq!(chains[1].1.debug_server_port, 9102);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_chains() {
+        assert!(matches!(
+            ChainsConfig::default().into_args(),
+            Err(ChainsConfigError::Empty)
+        ));
+
+        let config = ChainsConfig {
+            chains: vec![chain("a", &[]), chain("a", &[])],
+        };
+        assert!(matches!(
+            config.into_args(),
+            Err(ChainsConfigError::DuplicateName(name)) if name == "a"
+        ));
+
+        let config = ChainsConfig {
+            chains: vec![chain("a", &["--chains-config", "chains.json"])],
+        };
+        assert!(matches!(
+            config.into_args(),
+            Err(ChainsConfigError::Nested(_))
+        ));
+
+        let config = ChainsConfig {
+            chains: vec![chain("a", &["--rpc-port", "not-a-port"])],
+        };
+        assert!(matches!(
+            config.into_args(),
+            Err(ChainsConfigError::Args { .. })
+        ));
+    }
+
+    #[test]
+    fn test_chain_args_ignore_env() {
+        // The environment of the process is shared by all the chains
+        assert!(
+            Args::command()
+                .get_arguments()
+                .any(|arg| arg.get_env().is_some())
+        );
+        assert!(
+            chain_command()
+                .get_arguments()
+                .all(|arg| arg.get_env().is_none())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_spawn_chain() -> eyre::Result<()> {
+        let (chain, key, shared) = spawn_chain("a", Some(1), || async {
+            // Tasks spawned by the chain are attributed to it
+            let key = tokio::spawn(async { chain_key(&Key::from_name("requests")) }).await?;
+            let shared = in_current_chain(async { current_chain() });
+            Ok((current_chain(), key, shared))
+        })
+        .await?;
+        assert_eq!(chain.as_deref(), Some("a"));
+        let key = key.expect("the key is labelled by the chain");
+        assert_eq!(
+            key.labels().collect::<Vec<_>>(),
+            [&Label::new("chain", "a")]
+        );
+
+        assert!(current_chain().is_none());
+        assert!(chain_key(&Key::from_name("requests")).is_none());
+
+        // Futures polled on a runtime shared by the chains keep their chain
+        assert_eq!(tokio::spawn(shared).await?.as_deref(), Some("a"));
+        assert!(current_chain().is_none());
+        Ok(())
+    }
+}
@@ -1,16 +1,27 @@
-use alloy_primitives::{B256, Bytes};
+use alloy_consensus::{EMPTY_OMMER_ROOT_HASH, Header, proofs};
+use alloy_eips::eip7685::EMPTY_REQUESTS_HASH;
+use alloy_primitives::{Address, B64, B256, Bytes, U256, bytes::BufMut};
 use futures::{StreamExt as _, stream};
 use moka::future::Cache;
+use thiserror::Error;
 
-use alloy_rpc_types_engine::{ExecutionPayload, ExecutionPayloadV3, PayloadId};
+use crate::CorrelationId;
+
+use alloy_rpc_types_engine::{
+    ExecutionPayload, ExecutionPayloadEnvelopeV2, ExecutionPayloadFieldV2, ExecutionPayloadInputV2,
+    ExecutionPayloadV1, ExecutionPayloadV3, PayloadId,
+};
 use op_alloy_rpc_types_engine::{
     OpExecutionPayloadEnvelopeV3, OpExecutionPayloadEnvelopeV4, OpExecutionPayloadV4,
+    OpPayloadAttributes,
 };
 
 const CACHE_SIZE: u64 = 100;
 
 #[derive(Debug, Clone)]
 pub enum OpExecutionPayloadEnvelope {
+    V1(ExecutionPayloadV1),
+    V2(ExecutionPayloadEnvelopeV2),
     V3(OpExecutionPayloadEnvelopeV3),
     V4(OpExecutionPayloadEnvelopeV4),
 }
@@ -18,47 +29,148 @@ pub enum OpExecutionPayloadEnvelope {
 impl OpExecutionPayloadEnvelope {
     pub fn version(&self) -> PayloadVersion {
         match self {
+            OpExecutionPayloadEnvelope::V1(_) => PayloadVersion::V1,
+            OpExecutionPayloadEnvelope::V2(_) => PayloadVersion::V2,
             OpExecutionPayloadEnvelope::V3(_) => PayloadVersion::V3,
             OpExecutionPayloadEnvelope::V4(_) => PayloadVersion::V4,
         }
     }
 
     pub fn gas_used(&self) -> u64 {
+        self.payload_v1().gas_used
+    }
+
+    pub fn block_value(&self) -> U256 {
+        match self {
+            // The V1 envelope is the bare payload, without a block value
+            OpExecutionPayloadEnvelope::V1(_) => U256::ZERO,
+            OpExecutionPayloadEnvelope::V2(payload) => payload.block_value,
+            OpExecutionPayloadEnvelope::V3(payload) => payload.block_value,
+            OpExecutionPayloadEnvelope::V4(payload) => payload.block_value,
+        }
+    }
+
+    pub fn state_root(&self) -> B256 {
+        self.payload_v1().state_root
+    }
+
+    pub fn fee_recipient(&self) -> Address {
+        self.payload_v1().fee_recipient
+    }
+
+    pub fn gas_limit(&self) -> u64 {
+        self.payload_v1().gas_limit
+    }
+
+    pub fn extra_data(&self) -> &Bytes {
+        &self.payload_v1().extra_data
+    }
+
+    pub fn block_hash(&self) -> B256 {
+        self.payload_v1().block_hash
+    }
+
+    pub fn block_number(&self) -> u64 {
+        self.payload_v1().block_number
+    }
+
+    fn payload_v1(&self) -> &ExecutionPayloadV1 {
         match self {
+            OpExecutionPayloadEnvelope::V1(payload) => payload,
+            OpExecutionPayloadEnvelope::V2(payload) => match &payload.execution_payload {
+                ExecutionPayloadFieldV2::V1(payload) => payload,
+                ExecutionPayloadFieldV2::V2(payload) => &payload.payload_inner,
+            },
             OpExecutionPayloadEnvelope::V3(payload) => {
-                payload
-                    .execution_payload
-                    .payload_inner
-                    .payload_inner
-                    .gas_used
+                &payload.execution_payload.payload_inner.payload_inner
             }
-
             OpExecutionPayloadEnvelope::V4(payload) => {
-                payload
+                &payload
                     .execution_payload
                     .payload_inner
                     .payload_inner
                     .payload_inner
-                    .gas_used
             }
         }
     }
 
+    /// The header of the block, as hashed by the OP Stack execution engines.
+    pub fn header(&self) -> Header {
+        let (withdrawals_root, blob_gas, parent_beacon_block_root, requests_hash) = match self {
+            OpExecutionPayloadEnvelope::V1(_) => (None, None, None, None),
+            OpExecutionPayloadEnvelope::V2(payload) => match &payload.execution_payload {
+                ExecutionPayloadFieldV2::V1(_) => (None, None, None, None),
+                ExecutionPayloadFieldV2::V2(payload) => (
+                    Some(proofs::calculate_withdrawals_root(&payload.withdrawals)),
+                    None,
+                    None,
+                    None,
+                ),
+            },
+            OpExecutionPayloadEnvelope::V3(payload) => (
+                Some(proofs::calculate_withdrawals_root(
+                    &payload.execution_payload.payload_inner.withdrawals,
+                )),
+                Some((
+                    payload.execution_payload.blob_gas_used,
+                    payload.execution_payload.excess_blob_gas,
+                )),
+                Some(payload.parent_beacon_block_root),
+                None,
+            ),
+            // Isthmus commits to the withdrawals of the L2 to L1 message passer instead
+            // of the (empty) withdrawals of the block, and has no execution requests
+            OpExecutionPayloadEnvelope::V4(payload) => (
+                Some(payload.execution_payload.withdrawals_root),
+                Some((
+                    payload.execution_payload.payload_inner.blob_gas_used,
+                    payload.execution_payload.payload_inner.excess_blob_gas,
+                )),
+                Some(payload.parent_beacon_block_root),
+                Some(EMPTY_REQUESTS_HASH),
+            ),
+        };
+        let payload = self.payload_v1();
+
+        Header {
+            parent_hash: payload.parent_hash,
+            ommers_hash: EMPTY_OMMER_ROOT_HASH,
+            beneficiary: payload.fee_recipient,
+            state_root: payload.state_root,
+            transactions_root: proofs::ordered_trie_root_with_encoder(
+                &payload.transactions,
+                |tx, buf| buf.put_slice(tx),
+            ),
+            receipts_root: payload.receipts_root,
+            withdrawals_root,
+            logs_bloom: payload.logs_bloom,
+            difficulty: U256::ZERO,
+            number: payload.block_number,
+            gas_limit: payload.gas_limit,
+            gas_used: payload.gas_used,
+            timestamp: payload.timestamp,
+            mix_hash: payload.prev_randao,
+            nonce: B64::ZERO,
+            base_fee_per_gas: Some(payload.base_fee_per_gas.saturating_to()),
+            blob_gas_used: blob_gas.map(|(blob_gas_used, _)| blob_gas_used),
+            excess_blob_gas: blob_gas.map(|(_, excess_blob_gas)| excess_blob_gas),
+            parent_beacon_block_root,
+            requests_hash,
+            extra_data: payload.extra_data.clone(),
+        }
+    }
+
     pub fn tx_count(&self) -> usize {
+        self.payload_v1().transactions.len()
+    }
+
+    /// The envelope as returned by `engine_getPayload` of its version.
+    pub fn to_json(&self) -> serde_json::Result<serde_json::Value> {
         match self {
-            OpExecutionPayloadEnvelope::V3(payload) => payload
-                .execution_payload
-                .payload_inner
-                .payload_inner
-                .transactions
-                .len(),
-            OpExecutionPayloadEnvelope::V4(payload) => payload
-                .execution_payload
-                .payload_inner
-                .payload_inner
-                .payload_inner
-                .transactions
-                .len(),
+            OpExecutionPayloadEnvelope::V1(payload) => serde_json::to_value(payload),
+            OpExecutionPayloadEnvelope::V2(payload) => serde_json::to_value(payload),
+            OpExecutionPayloadEnvelope::V3(payload) => serde_json::to_value(payload),
+            OpExecutionPayloadEnvelope::V4(payload) => serde_json::to_value(payload),
         }
     }
 }
@@ -66,6 +178,8 @@ impl OpExecutionPayloadEnvelope {
 impl From<OpExecutionPayloadEnvelope> for ExecutionPayload {
     fn from(envelope: OpExecutionPayloadEnvelope) -> Self {
         match envelope {
+            OpExecutionPayloadEnvelope::V1(v1) => ExecutionPayload::V1(v1),
+            OpExecutionPayloadEnvelope::V2(v2) => v2.execution_payload.into_payload(),
             OpExecutionPayloadEnvelope::V3(v3) => ExecutionPayload::from(v3.execution_payload),
             OpExecutionPayloadEnvelope::V4(v4) => {
                 ExecutionPayload::from(v4.execution_payload.payload_inner)
@@ -91,6 +205,8 @@ pub struct NewPayloadV4 {
 
 #[derive(Debug, Clone)]
 pub enum NewPayload {
+    V1(ExecutionPayloadV1),
+    V2(ExecutionPayloadInputV2),
     V3(NewPayloadV3),
     V4(NewPayloadV4),
 }
@@ -98,6 +214,8 @@ pub enum NewPayload {
 impl NewPayload {
     pub fn version(&self) -> PayloadVersion {
         match self {
+            NewPayload::V1(_) => PayloadVersion::V1,
+            NewPayload::V2(_) => PayloadVersion::V2,
             NewPayload::V3(_) => PayloadVersion::V3,
             NewPayload::V4(_) => PayloadVersion::V4,
         }
@@ -107,6 +225,17 @@ impl NewPayload {
 impl From<OpExecutionPayloadEnvelope> for NewPayload {
     fn from(envelope: OpExecutionPayloadEnvelope) -> Self {
         match envelope {
+            OpExecutionPayloadEnvelope::V1(v1) => NewPayload::V1(v1),
+            OpExecutionPayloadEnvelope::V2(v2) => NewPayload::V2(match v2.execution_payload {
+                ExecutionPayloadFieldV2::V1(payload) => ExecutionPayloadInputV2 {
+                    execution_payload: payload,
+                    withdrawals: None,
+                },
+                ExecutionPayloadFieldV2::V2(payload) => ExecutionPayloadInputV2 {
+                    execution_payload: payload.payload_inner,
+                    withdrawals: Some(payload.withdrawals),
+                },
+            }),
             OpExecutionPayloadEnvelope::V3(v3) => NewPayload::V3(NewPayloadV3 {
                 payload: v3.execution_payload,
                 versioned_hashes: vec![],
@@ -125,6 +254,8 @@ impl From<OpExecutionPayloadEnvelope> for NewPayload {
 impl From<NewPayload> for ExecutionPayload {
     fn from(new_payload: NewPayload) -> Self {
         match new_payload {
+            NewPayload::V1(v1) => ExecutionPayload::V1(v1),
+            NewPayload::V2(v2) => v2.into_payload(),
             NewPayload::V3(v3) => ExecutionPayload::from(v3.payload),
             NewPayload::V4(v4) => ExecutionPayload::from(v4.payload.payload_inner),
         }
@@ -133,13 +264,21 @@ impl From<NewPayload> for ExecutionPayload {
 
 #[derive(Debug, Clone, Copy)]
 pub enum PayloadVersion {
+    /// Bedrock
+    V1,
+    /// Canyon, adding withdrawals
+    V2,
+    /// Ecotone
     V3,
+    /// Isthmus
     V4,
 }
 
 impl PayloadVersion {
     pub fn as_str(&self) -> &'static str {
         match self {
+            PayloadVersion::V1 => "v1",
+            PayloadVersion::V2 => "v2",
             PayloadVersion::V3 => "v3",
             PayloadVersion::V4 => "v4",
         }
@@ -172,10 +311,128 @@ impl PayloadSource {
     }
 }
 
+/// The origin of a block returned from `get_payload`, at a finer granularity than
+/// [`PayloadSource`], used to attribute blocks in metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockSource {
+    /// Assembled from the flashblocks streamed by the builder
+    Flashblocks,
+    /// Returned by the builder's `engine_getPayload`
+    Builder,
+    /// Returned by the fallback l2 client
+    L2,
+}
+
+impl BlockSource {
+    pub const ALL: [BlockSource; 3] = [
+        BlockSource::Flashblocks,
+        BlockSource::Builder,
+        BlockSource::L2,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BlockSource::Flashblocks => "flashblocks",
+            BlockSource::Builder => "builder",
+            BlockSource::L2 => "l2",
+        }
+    }
+}
+
+impl From<&PayloadSource> for BlockSource {
+    fn from(source: &PayloadSource) -> Self {
+        match source {
+            PayloadSource::L2 => BlockSource::L2,
+            PayloadSource::Builder => BlockSource::Builder,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PayloadMismatch {
+    #[error("fee recipient {actual} does not match the expected {expected}")]
+    FeeRecipient { expected: Address, actual: Address },
+    #[error("gas limit {actual} does not match the expected {expected}")]
+    GasLimit { expected: u64, actual: u64 },
+    #[error("extra data {actual} does not match the eip1559 params {expected}")]
+    ExtraData { expected: B64, actual: Bytes },
+}
+
+impl PayloadMismatch {
+    pub fn field(&self) -> &'static str {
+        match self {
+            PayloadMismatch::FeeRecipient { .. } => "fee_recipient",
+            PayloadMismatch::GasLimit { .. } => "gas_limit",
+            PayloadMismatch::ExtraData { .. } => "extra_data",
+        }
+    }
+}
+
+/// Values from the payload attributes that a builder payload must honour before it
+/// can be returned to the proposer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpectedPayload {
+    pub fee_recipient: Address,
+    pub gas_limit: Option<u64>,
+    pub eip_1559_params: Option<B64>,
+}
+
+impl From<&OpPayloadAttributes> for ExpectedPayload {
+    fn from(attrs: &OpPayloadAttributes) -> Self {
+        Self {
+            fee_recipient: attrs.payload_attributes.suggested_fee_recipient,
+            gas_limit: attrs.gas_limit,
+            eip_1559_params: attrs.eip_1559_params,
+        }
+    }
+}
+
+impl ExpectedPayload {
+    pub fn validate(&self, payload: &OpExecutionPayloadEnvelope) -> Result<(), PayloadMismatch> {
+        let actual = payload.fee_recipient();
+        if actual != self.fee_recipient {
+            return Err(PayloadMismatch::FeeRecipient {
+                expected: self.fee_recipient,
+                actual,
+            });
+        }
+
+        let actual = payload.gas_limit();
+        if let Some(expected) = self.gas_limit.filter(|expected| *expected != actual) {
+            return Err(PayloadMismatch::GasLimit { expected, actual });
+        }
+
+        // Holocene encodes the eip1559 params in the extra data after a zero version
+        // byte. Zero params mean the chain defaults are used, which are not known here.
+        if let Some(expected) = self.eip_1559_params.filter(|params| !params.is_zero()) {
+            let actual = payload.extra_data();
+            if actual.len() != 9 || actual[0] != 0 || actual[1..] != expected[..] {
+                return Err(PayloadMismatch::ExtraData {
+                    expected,
+                    actual: actual.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PayloadTrace {
     pub builder_has_payload: bool,
     pub trace_id: Option<tracing::Id>,
+    pub expected_payload: Option<ExpectedPayload>,
+    /// Whether the forkchoice update starting the payload requested a dry run
+    pub dry_run: bool,
+    /// Why the builder was deliberately not sent the payload attributes, if it was not
+    pub bypass_reason: Option<&'static str>,
+    /// Payload ID returned by the builder, which differs from the l2 one when the
+    /// attributes sent to the builder were rewritten
+    pub builder_payload_id: Option<PayloadId>,
+    /// Correlation id of the forkchoice update starting the payload, reused by the
+    /// get payload call of the same payload
+    pub correlation_id: Option<CorrelationId>,
 }
 
 pub struct PayloadTraceContext {
@@ -197,22 +454,8 @@ impl PayloadTraceContext {
         }
     }
 
-    pub async fn store(
-        &self,
-        payload_id: PayloadId,
-        parent_hash: B256,
-        builder_has_payload: bool,
-        trace_id: Option<tracing::Id>,
-    ) {
-        self.payload_id
-            .insert(
-                payload_id,
-                PayloadTrace {
-                    builder_has_payload,
-                    trace_id,
-                },
-            )
-            .await;
+    pub async fn store(&self, payload_id: PayloadId, parent_hash: B256, trace: PayloadTrace) {
+        self.payload_id.insert(payload_id, trace).await;
         self.block_hash_to_payload_ids
             .entry(parent_hash)
             .and_upsert_with(|o| match o {
@@ -258,6 +501,44 @@ impl PayloadTraceContext {
             .unwrap_or_default()
     }
 
+    pub async fn expected_payload(&self, payload_id: &PayloadId) -> Option<ExpectedPayload> {
+        self.payload_id
+            .get(payload_id)
+            .await
+            .and_then(|x| x.expected_payload)
+    }
+
+    pub async fn is_dry_run(&self, payload_id: &PayloadId) -> bool {
+        self.payload_id
+            .get(payload_id)
+            .await
+            .is_some_and(|x| x.dry_run)
+    }
+
+    /// The payload ID to request the payload of the l2 payload `payload_id` from the
+    /// builder with.
+    pub async fn builder_payload_id(&self, payload_id: &PayloadId) -> PayloadId {
+        self.payload_id
+            .get(payload_id)
+            .await
+            .and_then(|x| x.builder_payload_id)
+            .unwrap_or(*payload_id)
+    }
+
+    pub async fn correlation_id(&self, payload_id: &PayloadId) -> Option<CorrelationId> {
+        self.payload_id
+            .get(payload_id)
+            .await
+            .and_then(|x| x.correlation_id)
+    }
+
+    pub async fn bypass_reason(&self, payload_id: &PayloadId) -> Option<&'static str> {
+        self.payload_id
+            .get(payload_id)
+            .await
+            .and_then(|x| x.bypass_reason)
+    }
+
     pub async fn remove_by_parent_hash(&self, block_hash: &B256) {
         if let Some(payload_ids) = self.block_hash_to_payload_ids.remove(block_hash).await {
             for payload_id in payload_ids.iter() {
@@ -266,3 +547,83 @@ impl PayloadTraceContext {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(
+        fee_recipient: Address,
+        gas_limit: u64,
+        extra_data: Bytes,
+    ) -> OpExecutionPayloadEnvelope {
+        let mut payload = OpExecutionPayloadEnvelopeV3 {
+            execution_payload: ExecutionPayloadV3 {
+                payload_inner: alloy_rpc_types_engine::ExecutionPayloadV2 {
+                    payload_inner: ExecutionPayloadV1 {
+                        parent_hash: B256::ZERO,
+                        fee_recipient: Address::ZERO,
+                        state_root: B256::ZERO,
+                        receipts_root: B256::ZERO,
+                        logs_bloom: Default::default(),
+                        prev_randao: B256::ZERO,
+                        block_number: 1,
+                        gas_limit: 0,
+                        gas_used: 0,
+                        timestamp: 0,
+                        extra_data: Bytes::new(),
+                        base_fee_per_gas: U256::ZERO,
+                        block_hash: B256::ZERO,
+                        transactions: vec![],
+                    },
+                    withdrawals: vec![],
+                },
+                blob_gas_used: 0,
+                excess_blob_gas: 0,
+            },
+            block_value: U256::ZERO,
+            blobs_bundle: Default::default(),
+            should_override_builder: false,
+            parent_beacon_block_root: B256::ZERO,
+        };
+        let inner = &mut payload.execution_payload.payload_inner.payload_inner;
+        inner.fee_recipient = fee_recipient;
+        inner.gas_limit = gas_limit;
+        inner.extra_data = extra_data;
+        OpExecutionPayloadEnvelope::V3(payload)
+    }
+
+    #[test]
+    fn test_expected_payload_validation() {
+        let fee_recipient = Address::repeat_byte(1);
+        let expected = ExpectedPayload {
+            fee_recipient,
+            gas_limit: Some(30_000_000),
+            eip_1559_params: Some(B64::from([0, 0, 0, 8, 0, 0, 0, 2])),
+        };
+        let extra_data = Bytes::from_static(&[0, 0, 0, 0, 8, 0, 0, 0, 2]);
+
+        assert_eq!(
+            expected.validate(&payload(fee_recipient, 30_000_000, extra_data.clone())),
+            Ok(())
+        );
+        assert_eq!(
+            expected
+                .validate(&payload(Address::ZERO, 30_000_000, extra_data.clone()))
+                .map_err(|e| e.field()),
+            Err("fee_recipient")
+        );
+        assert_eq!(
+            expected
+                .validate(&payload(fee_recipient, 1, extra_data))
+                .map_err(|e| e.field()),
+            Err("gas_limit")
+        );
+        assert_eq!(
+            expected
+                .validate(&payload(fee_recipient, 30_000_000, Bytes::new()))
+                .map_err(|e| e.field()),
+            Err("extra_data")
+        );
+    }
+}
@@ -0,0 +1,433 @@
+//! Payload policy implemented by a WASM module, so that chains can apply custom policy
+//! without forking rollup-boost.
+//!
+//! The module is sandboxed: it may not import any function, each call runs in a fresh
+//! instance with a bounded amount of fuel and memory, and a call that traps or runs out
+//! of fuel leaves the default behaviour in place, except for the transaction filter which
+//! rejects the payload. The module must export its `memory`
+//! and an `alloc(len: i32) -> i32` function, through which the inputs are passed as
+//! JSON, and any of the hooks:
+//!
+//! - `mutate_attributes(ptr: i32, len: i32) -> i64`: receives the payload attributes
+//!   forwarded to the builder and returns the replacement attributes as
+//!   `ptr << 32 | len`, or 0 to leave them unchanged.
+//! - `filter_transactions(ptr: i32, len: i32) -> i32`: receives the hex encoded
+//!   transactions of a payload assembled from flashblocks and returns the index of the
+//!   first transaction to reject, or -1 to accept all of them. It is called once per
+//!   payload, on the blocking thread pool.
+//! - `select_payload(ptr: i32, len: i32) -> i32`: receives the summaries of the builder
+//!   and L2 client blocks and returns 0 to select the builder block, 1 to select the L2
+//!   client block, or any other value to defer to the block selection policy.
+
+use std::{path::PathBuf, sync::Arc};
+
+use clap::Parser;
+
+use crate::{AttributesHook, PayloadSelector, TransactionFilter};
+
+#[derive(Parser, Clone, Debug)]
+pub struct WasmPluginArgs {
+    /// WASM module implementing payload policy hooks, requires the `wasm-plugins` feature
+    #[arg(long, env)]
+    pub wasm_plugin_path: Option<PathBuf>,
+
+    /// Fuel a single call to the WASM plugin may consume before being aborted
+    #[arg(long, env, default_value = "10000000")]
+    pub wasm_plugin_fuel: u64,
+
+    /// Memory a WASM plugin instance may grow to, in bytes
+    #[arg(long, env, default_value = "67108864")]
+    pub wasm_plugin_max_memory: usize,
+
+    /// Interval in seconds at which the WASM module is reloaded if it changed, 0 disables
+    /// hot reloading
+    #[arg(long, env, default_value = "5")]
+    pub wasm_plugin_reload_interval: u64,
+}
+
+/// The hooks implemented by a policy plugin.
+#[derive(Debug, Clone, Default)]
+pub struct PolicyPlugins {
+    pub attributes_hook: Option<Arc<dyn AttributesHook>>,
+    pub transaction_filter: Option<Arc<dyn TransactionFilter>>,
+    pub payload_selector: Option<Arc<dyn PayloadSelector>>,
+}
+
+impl WasmPluginArgs {
+    /// Loads the WASM plugin, if configured, and starts watching it for changes.
+    pub fn load(&self) -> eyre::Result<PolicyPlugins> {
+        let Some(path) = &self.wasm_plugin_path else {
+            return Ok(PolicyPlugins::default());
+        };
+
+        #[cfg(feature = "wasm-plugins")]
+        {
+            let plugin = Arc::new(wasm::WasmPlugin::new(
+                path.clone(),
+                self.wasm_plugin_fuel,
+                self.wasm_plugin_max_memory,
+            )?);
+            if self.wasm_plugin_reload_interval > 0 {
+                plugin.spawn_reloader(std::time::Duration::from_secs(
+                    self.wasm_plugin_reload_interval,
+                ));
+            }
+            Ok(plugin.policy_plugins())
+        }
+
+        #[cfg(not(feature = "wasm-plugins"))]
+        eyre::bail!(
+            "cannot load {}, rollup-boost was built without the wasm-plugins feature",
+            path.display()
+        )
+    }
+}
+
+#[cfg(feature = "wasm-plugins")]
+pub use wasm::WasmPlugin;
+
+#[cfg(feature = "wasm-plugins")]
+mod wasm {
+    use std::{
+        path::PathBuf,
+        sync::Arc,
+        time::{Duration, SystemTime},
+    };
+
+    use alloy_primitives::{B256, Bytes, U256};
+    use metrics::counter;
+    use op_alloy_rpc_types_engine::OpPayloadAttributes;
+    use parking_lot::RwLock;
+    use serde::Serialize;
+    use tracing::{error, info, warn};
+    use wasmtime::{
+        Config, Engine, Instance, Linker, Module, Store, StoreLimits, StoreLimitsBuilder,
+    };
+
+    use super::PolicyPlugins;
+    use crate::{
+        AttributesHook, OpExecutionPayloadEnvelope, PayloadSelector, PayloadSource,
+        TransactionFilter,
+    };
+
+    const MUTATE_ATTRIBUTES: &str = "mutate_attributes";
+    const FILTER_TRANSACTIONS: &str = "filter_transactions";
+    const SELECT_PAYLOAD: &str = "select_payload";
+
+    /// Summary of a block passed to `select_payload`.
+    #[derive(Debug, Serialize)]
+    struct PayloadSummary {
+        block_hash: B256,
+        gas_used: u64,
+        gas_limit: u64,
+        block_value: U256,
+        tx_count: usize,
+    }
+
+    impl From<&OpExecutionPayloadEnvelope> for PayloadSummary {
+        fn from(payload: &OpExecutionPayloadEnvelope) -> Self {
+            Self {
+                block_hash: payload.block_hash(),
+                gas_used: payload.gas_used(),
+                gas_limit: payload.gas_limit(),
+                block_value: payload.block_value(),
+                tx_count: payload.tx_count(),
+            }
+        }
+    }
+
+    #[derive(Debug, Serialize)]
+    struct SelectionInput {
+        builder: PayloadSummary,
+        l2: PayloadSummary,
+    }
+
+    /// A compiled WASM module and the hooks it exports.
+    #[derive(Clone)]
+    struct LoadedModule {
+        module: Module,
+        modified: Option<SystemTime>,
+    }
+
+    impl LoadedModule {
+        fn exports(&self, name: &str) -> bool {
+            self.module.get_export(name).is_some()
+        }
+    }
+
+    /// A payload policy plugin backed by a WASM module, reloaded when the module
+    /// changes on disk.
+    pub struct WasmPlugin {
+        path: PathBuf,
+        engine: Engine,
+        module: RwLock<LoadedModule>,
+        fuel: u64,
+        max_memory: usize,
+    }
+
+    impl std::fmt::Debug for WasmPlugin {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("WasmPlugin")
+                .field("path", &self.path)
+                .field("fuel", &self.fuel)
+                .field("max_memory", &self.max_memory)
+                .finish()
+        }
+    }
+
+    impl WasmPlugin {
+        pub fn new(path: PathBuf, fuel: u64, max_memory: usize) -> eyre::Result<Self> {
+            let mut config = Config::new();
+            config.consume_fuel(true);
+            let engine = Engine::new(&config)?;
+            let module = load_module(&engine, &path)?;
+            info!(message = "loaded WASM plugin", path = %path.display());
+
+            Ok(Self {
+                path,
+                engine,
+                module: RwLock::new(module),
+                fuel,
+                max_memory,
+            })
+        }
+
+        /// The hooks exported by the module. Hooks exported by a module reloaded later
+        /// are only enabled on restart.
+        pub fn policy_plugins(self: &Arc<Self>) -> PolicyPlugins {
+            let module = self.module.read().clone();
+            PolicyPlugins {
+                attributes_hook: module
+                    .exports(MUTATE_ATTRIBUTES)
+                    .then(|| self.clone() as Arc<dyn AttributesHook>),
+                transaction_filter: module
+                    .exports(FILTER_TRANSACTIONS)
+                    .then(|| self.clone() as Arc<dyn TransactionFilter>),
+                payload_selector: module
+                    .exports(SELECT_PAYLOAD)
+                    .then(|| self.clone() as Arc<dyn PayloadSelector>),
+            }
+        }
+
+        /// Reloads the module if it changed since it was loaded. The current module
+        /// is kept if the new one fails to compile.
+        pub fn reload(&self) -> eyre::Result<bool> {
+            let modified = std::fs::metadata(&self.path)?.modified().ok();
+            if modified == self.module.read().modified {
+                return Ok(false);
+            }
+            let module = load_module(&self.engine, &self.path)?;
+            *self.module.write() = module;
+            counter!("wasm_plugin.reloads").increment(1);
+            info!(message = "reloaded WASM plugin", path = %self.path.display());
+            Ok(true)
+        }
+
+        pub fn spawn_reloader(self: &Arc<Self>, interval: Duration) {
+            let plugin = self.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(interval);
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = plugin.reload() {
+                        counter!("wasm_plugin.reload_failures").increment(1);
+                        warn!(message = "failed to reload WASM plugin", error = %e);
+                    }
+                }
+            });
+        }
+
+        /// Calls `hook` in a fresh instance of the module, with `input` copied into its
+        /// memory.
+        fn call<R: wasmtime::WasmResults>(
+            &self,
+            hook: &'static str,
+            input: &[u8],
+        ) -> eyre::Result<(R, Store<StoreLimits>, Instance)> {
+            let module = self.module.read().module.clone();
+            let limits = StoreLimitsBuilder::new()
+                .memory_size(self.max_memory)
+                .instances(1)
+                .build();
+            let mut store = Store::new(&self.engine, limits);
+            store.limiter(|limits| limits);
+            store.set_fuel(self.fuel)?;
+
+            // No host functions are linked, the module cannot reach outside its sandbox
+            let instance = Linker::new(&self.engine).instantiate(&mut store, &module)?;
+            let memory = instance
+                .get_memory(&mut store, "memory")
+                .ok_or_else(|| eyre::eyre!("the WASM plugin does not export its memory"))?;
+            let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+            let len = i32::try_from(input.len())?;
+            let ptr = alloc.call(&mut store, len)?;
+            memory.write(&mut store, ptr as u32 as usize, input)?;
+
+            let hook = instance.get_typed_func::<(i32, i32), R>(&mut store, hook)?;
+            let result = hook.call(&mut store, (ptr, len))?;
+            Ok((result, store, instance))
+        }
+
+        fn record_failure(&self, hook: &'static str, e: &eyre::Report) {
+            counter!("wasm_plugin.failures", "hook" => hook).increment(1);
+            error!(message = "WASM plugin call failed", hook, error = %e);
+        }
+
+        fn mutate_attributes(
+            &self,
+            attrs: &OpPayloadAttributes,
+        ) -> eyre::Result<Option<OpPayloadAttributes>> {
+            let input = serde_json::to_vec(attrs)?;
+            let (packed, mut store, instance) = self.call::<i64>(MUTATE_ATTRIBUTES, &input)?;
+            if packed == 0 {
+                return Ok(None);
+            }
+            let (ptr, len) = ((packed as u64 >> 32) as usize, packed as u32 as usize);
+            let memory = instance
+                .get_memory(&mut store, "memory")
+                .ok_or_else(|| eyre::eyre!("the WASM plugin does not export its memory"))?;
+            let mut output = vec![0; len];
+            memory.read(&store, ptr, &mut output)?;
+            Ok(Some(serde_json::from_slice(&output)?))
+        }
+    }
+
+    impl AttributesHook for WasmPlugin {
+        fn name(&self) -> &'static str {
+            "wasm_plugin"
+        }
+
+        fn apply(&self, attrs: &mut OpPayloadAttributes) {
+            match self.mutate_attributes(attrs) {
+                Ok(Some(mutated)) => *attrs = mutated,
+                Ok(None) => {}
+                Err(e) => self.record_failure(MUTATE_ATTRIBUTES, &e),
+            }
+        }
+    }
+
+    impl TransactionFilter for WasmPlugin {
+        fn name(&self) -> &'static str {
+            "wasm_plugin"
+        }
+
+        fn first_rejected(&self, transactions: &[Bytes]) -> Option<usize> {
+            let input = serde_json::to_vec(transactions).expect("bytes serialize to JSON");
+            match self.call::<i32>(FILTER_TRANSACTIONS, &input) {
+                Ok((-1, ..)) => None,
+                Ok((index, ..)) => match usize::try_from(index) {
+                    Ok(index) if index < transactions.len() => Some(index),
+                    _ => {
+                        let e = eyre::eyre!("no transaction at index {index}");
+                        self.record_failure(FILTER_TRANSACTIONS, &e);
+                        Some(0)
+                    }
+                },
+                // Fail closed on transactions the plugin could not inspect
+                Err(e) => {
+                    self.record_failure(FILTER_TRANSACTIONS, &e);
+                    Some(0)
+                }
+            }
+        }
+    }
+
+    impl PayloadSelector for WasmPlugin {
+        fn name(&self) -> &'static str {
+            "wasm_plugin"
+        }
+
+        fn select(
+            &self,
+            builder_payload: &OpExecutionPayloadEnvelope,
+            l2_payload: &OpExecutionPayloadEnvelope,
+        ) -> Option<PayloadSource> {
+            let input = SelectionInput {
+                builder: builder_payload.into(),
+                l2: l2_payload.into(),
+            };
+            let input = serde_json::to_vec(&input).expect("summaries serialize to JSON");
+            match self.call::<i32>(SELECT_PAYLOAD, &input) {
+                Ok((0, ..)) => Some(PayloadSource::Builder),
+                Ok((1, ..)) => Some(PayloadSource::L2),
+                Ok(_) => None,
+                Err(e) => {
+                    self.record_failure(SELECT_PAYLOAD, &e);
+                    None
+                }
+            }
+        }
+    }
+
+    fn load_module(engine: &Engine, path: &PathBuf) -> eyre::Result<LoadedModule> {
+        let modified = std::fs::metadata(path)?.modified().ok();
+        let module = Module::from_file(engine, path)?;
+        if module.imports().len() > 0 {
+            eyre::bail!("WASM plugins may not import host functions");
+        }
+        Ok(LoadedModule { module, modified })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const PLUGIN: &str = r#"
+            (module
+              (memory (export "memory") 1)
+              (global $next (mut i32) (i32.const 1024))
+              (func (export "alloc") (param $len i32) (result i32)
+                (local $ptr i32)
+                (local.set $ptr (global.get $next))
+                (global.set $next (i32.add (global.get $next) (local.get $len)))
+                (local.get $ptr))
+              ;; Only accepts a single empty transaction, ["0x"], and rejects the first
+              ;; transaction of any other payload
+              (func (export "filter_transactions") (param $ptr i32) (param $len i32) (result i32)
+                (select (i32.const -1) (i32.const 0) (i32.le_u (local.get $len) (i32.const 6))))
+              (func (export "select_payload") (param $ptr i32) (param $len i32) (result i32)
+                (loop $spin (br $spin))
+                (i32.const 0))
+              ;; Returns its input, the attributes are unchanged
+              (func (export "mutate_attributes") (param $ptr i32) (param $len i32) (result i64)
+                (i64.or
+                  (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+                  (i64.extend_i32_u (local.get $len)))))
+        "#;
+
+        #[test]
+        fn test_wasm_plugin() -> eyre::Result<()> {
+            let dir = std::env::temp_dir().join(format!("wasm-plugin-{}", B256::random()));
+            std::fs::create_dir_all(&dir)?;
+            let path = dir.join("plugin.wat");
+            std::fs::write(&path, PLUGIN)?;
+
+            let plugin = Arc::new(WasmPlugin::new(path.clone(), 100_000, 1 << 20)?);
+            let plugins = plugin.policy_plugins();
+            assert!(plugins.attributes_hook.is_some());
+
+            let filter = plugins.transaction_filter.expect("filter is exported");
+            assert_eq!(filter.first_rejected(&[Bytes::new()]), None);
+            assert_eq!(
+                filter.first_rejected(&[Bytes::new(), Bytes::from_static(&[1, 2, 3])]),
+                Some(0)
+            );
+
+            let mut attrs = OpPayloadAttributes {
+                gas_limit: Some(30_000_000),
+                ..Default::default()
+            };
+            let original = attrs.clone();
+            plugin.apply(&mut attrs);
+            assert_eq!(attrs, original);
+
+            // The spinning selector is aborted once it runs out of fuel
+            assert!(plugins.payload_selector.is_some());
+            assert!(plugin.call::<i32>(SELECT_PAYLOAD, b"{}").is_err());
+
+            assert!(!plugin.reload()?);
+            std::fs::remove_dir_all(dir)?;
+            Ok(())
+        }
+    }
+}
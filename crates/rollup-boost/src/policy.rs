@@ -0,0 +1,308 @@
+use alloy_primitives::{Address, B64};
+use clap::Parser;
+use metrics::counter;
+use op_alloy_rpc_types_engine::OpPayloadAttributes;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::warn;
+
+#[derive(Parser, Clone, Debug, Default)]
+pub struct AttributesPolicyArgs {
+    /// Enforce a policy on the payload attributes forwarded to the builder
+    #[arg(long, env, default_value = "false")]
+    pub attributes_policy: bool,
+
+    /// Action taken when the payload attributes are out of policy
+    #[arg(long, env, default_value = "reject")]
+    pub attributes_policy_action: PolicyAction,
+
+    /// Minimum gas limit allowed in the payload attributes
+    #[arg(long, env)]
+    pub attributes_policy_min_gas_limit: Option<u64>,
+
+    /// Maximum gas limit allowed in the payload attributes
+    #[arg(long, env)]
+    pub attributes_policy_max_gas_limit: Option<u64>,
+
+    /// Fee recipient expected in the payload attributes
+    #[arg(long, env)]
+    pub attributes_policy_fee_recipient: Option<Address>,
+}
+
+/// What to do with payload attributes that violate the policy.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum PolicyAction {
+    /// Do not forward the attributes to the builder, the block is built by the L2 client
+    #[default]
+    Reject,
+    /// Rewrite the out of policy values. Violations that cannot be rewritten are rejected
+    Rewrite,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PolicyViolation {
+    #[error("gas limit {gas_limit} is below the minimum {min}")]
+    GasLimitTooLow { gas_limit: u64, min: u64 },
+    #[error("gas limit {gas_limit} is above the maximum {max}")]
+    GasLimitTooHigh { gas_limit: u64, max: u64 },
+    #[error("fee recipient {actual} does not match the expected {expected}")]
+    FeeRecipientMismatch { expected: Address, actual: Address },
+    #[error("timestamp {timestamp} is lower than the previous timestamp {previous}")]
+    TimestampDecreased { timestamp: u64, previous: u64 },
+    #[error("invalid eip1559 params {0}")]
+    InvalidEip1559Params(B64),
+}
+
+impl PolicyViolation {
+    pub fn rule(&self) -> &'static str {
+        match self {
+            PolicyViolation::GasLimitTooLow { .. } | PolicyViolation::GasLimitTooHigh { .. } => {
+                "gas_limit"
+            }
+            PolicyViolation::FeeRecipientMismatch { .. } => "fee_recipient",
+            PolicyViolation::TimestampDecreased { .. } => "timestamp",
+            PolicyViolation::InvalidEip1559Params(_) => "eip1559_params",
+        }
+    }
+
+    fn is_rewritable(&self) -> bool {
+        matches!(
+            self,
+            PolicyViolation::GasLimitTooLow { .. }
+                | PolicyViolation::GasLimitTooHigh { .. }
+                | PolicyViolation::FeeRecipientMismatch { .. }
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PolicyOutcome {
+    /// The attributes can be forwarded to the builder, possibly after being rewritten
+    Accepted(OpPayloadAttributes),
+    /// The attributes must not be forwarded to the builder
+    Rejected(OpPayloadAttributes),
+}
+
+/// Validates the payload attributes received from op-node before they are forwarded
+/// to the builder.
+///
+/// Only the builder is sent the rewritten attributes, the L2 client is always sent the
+/// attributes of op-node.
+#[derive(Debug, Default)]
+pub struct AttributesPolicy {
+    action: PolicyAction,
+    min_gas_limit: Option<u64>,
+    max_gas_limit: Option<u64>,
+    fee_recipient: Option<Address>,
+    last_timestamp: Mutex<Option<u64>>,
+}
+
+impl AttributesPolicy {
+    pub fn new(action: PolicyAction) -> Self {
+        Self {
+            action,
+            ..Default::default()
+        }
+    }
+
+    pub fn from_args(args: &AttributesPolicyArgs) -> Option<Self> {
+        if !args.attributes_policy {
+            return None;
+        }
+
+        Some(Self {
+            action: args.attributes_policy_action,
+            min_gas_limit: args.attributes_policy_min_gas_limit,
+            max_gas_limit: args.attributes_policy_max_gas_limit,
+            fee_recipient: args.attributes_policy_fee_recipient,
+            last_timestamp: Mutex::new(None),
+        })
+    }
+
+    pub fn with_gas_limit_bounds(mut self, min: Option<u64>, max: Option<u64>) -> Self {
+        self.min_gas_limit = min;
+        self.max_gas_limit = max;
+        self
+    }
+
+    pub fn with_fee_recipient(mut self, fee_recipient: Address) -> Self {
+        self.fee_recipient = Some(fee_recipient);
+        self
+    }
+
+    pub fn apply(&self, mut attrs: OpPayloadAttributes) -> PolicyOutcome {
+        let violations = self.check(&attrs);
+        if violations.is_empty() {
+            return PolicyOutcome::Accepted(attrs);
+        }
+
+        let rewrite =
+            self.action == PolicyAction::Rewrite && violations.iter().all(|v| v.is_rewritable());
+        for violation in &violations {
+            let action = if rewrite { "rewrite" } else { "reject" };
+            counter!("policy.violations", "rule" => violation.rule(), "action" => action)
+                .increment(1);
+            warn!(
+                target: "rollup_boost::security",
+                message = "payload attributes out of policy",
+                rule = violation.rule(),
+                action,
+                %violation,
+            );
+        }
+
+        if !rewrite {
+            return PolicyOutcome::Rejected(attrs);
+        }
+
+        for violation in violations {
+            match violation {
+                PolicyViolation::GasLimitTooLow { min, .. } => attrs.gas_limit = Some(min),
+                PolicyViolation::GasLimitTooHigh { max, .. } => attrs.gas_limit = Some(max),
+                PolicyViolation::FeeRecipientMismatch { expected, .. } => {
+                    attrs.payload_attributes.suggested_fee_recipient = expected
+                }
+                PolicyViolation::TimestampDecreased { .. }
+                | PolicyViolation::InvalidEip1559Params(_) => {}
+            }
+        }
+        PolicyOutcome::Accepted(attrs)
+    }
+
+    fn check(&self, attrs: &OpPayloadAttributes) -> Vec<PolicyViolation> {
+        let mut violations = vec![];
+
+        if let Some(gas_limit) = attrs.gas_limit {
+            if let Some(min) = self.min_gas_limit.filter(|min| gas_limit < *min) {
+                violations.push(PolicyViolation::GasLimitTooLow { gas_limit, min });
+            }
+            if let Some(max) = self.max_gas_limit.filter(|max| gas_limit > *max) {
+                violations.push(PolicyViolation::GasLimitTooHigh { gas_limit, max });
+            }
+        }
+
+        let actual = attrs.payload_attributes.suggested_fee_recipient;
+        if let Some(expected) = self.fee_recipient.filter(|expected| *expected != actual) {
+            violations.push(PolicyViolation::FeeRecipientMismatch { expected, actual });
+        }
+
+        // The timestamp is always recorded so that only the first block after the
+        // timestamp moved backwards (e.g. after a reorg) is flagged
+        let timestamp = attrs.payload_attributes.timestamp;
+        if let Some(previous) = self
+            .last_timestamp
+            .lock()
+            .replace(timestamp)
+            .filter(|previous| timestamp < *previous)
+        {
+            violations.push(PolicyViolation::TimestampDecreased {
+                timestamp,
+                previous,
+            });
+        }
+
+        // A zero denominator is only valid if the elasticity is zero as well
+        if let Some(params) = attrs.eip_1559_params {
+            let (denominator, elasticity) = params.split_at(4);
+            if denominator == [0; 4] && elasticity != [0; 4] {
+                violations.push(PolicyViolation::InvalidEip1559Params(params));
+            }
+        }
+
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_rpc_types_engine::PayloadAttributes;
+
+    fn attrs(timestamp: u64, gas_limit: u64) -> OpPayloadAttributes {
+        OpPayloadAttributes {
+            payload_attributes: PayloadAttributes {
+                timestamp,
+                prev_randao: Default::default(),
+                suggested_fee_recipient: Address::ZERO,
+                withdrawals: Some(vec![]),
+                parent_beacon_block_root: Some(Default::default()),
+            },
+            transactions: None,
+            no_tx_pool: None,
+            gas_limit: Some(gas_limit),
+            eip_1559_params: None,
+        }
+    }
+
+    #[test]
+    fn test_accepts_attributes_in_policy() {
+        let policy = AttributesPolicy::new(PolicyAction::Reject)
+            .with_gas_limit_bounds(Some(100), Some(200))
+            .with_fee_recipient(Address::ZERO);
+
+        let attrs = attrs(1, 150);
+        assert_eq!(policy.apply(attrs.clone()), PolicyOutcome::Accepted(attrs));
+    }
+
+    #[test]
+    fn test_rejects_out_of_policy_gas_limit() {
+        let policy =
+            AttributesPolicy::new(PolicyAction::Reject).with_gas_limit_bounds(None, Some(200));
+
+        let attrs = attrs(1, 300);
+        assert_eq!(policy.apply(attrs.clone()), PolicyOutcome::Rejected(attrs));
+    }
+
+    #[test]
+    fn test_rewrites_gas_limit_and_fee_recipient() {
+        let fee_recipient = Address::repeat_byte(1);
+        let policy = AttributesPolicy::new(PolicyAction::Rewrite)
+            .with_gas_limit_bounds(Some(100), None)
+            .with_fee_recipient(fee_recipient);
+
+        let PolicyOutcome::Accepted(rewritten) = policy.apply(attrs(1, 50)) else {
+            panic!("expected attributes to be rewritten");
+        };
+        assert_eq!(rewritten.gas_limit, Some(100));
+        assert_eq!(
+            rewritten.payload_attributes.suggested_fee_recipient,
+            fee_recipient
+        );
+    }
+
+    #[test]
+    fn test_rejects_decreasing_timestamp_even_when_rewriting() {
+        let policy = AttributesPolicy::new(PolicyAction::Rewrite);
+
+        assert!(matches!(
+            policy.apply(attrs(10, 0)),
+            PolicyOutcome::Accepted(_)
+        ));
+        assert!(matches!(
+            policy.apply(attrs(10, 0)),
+            PolicyOutcome::Accepted(_)
+        ));
+        assert!(matches!(
+            policy.apply(attrs(9, 0)),
+            PolicyOutcome::Rejected(_)
+        ));
+        assert!(matches!(
+            policy.apply(attrs(11, 0)),
+            PolicyOutcome::Accepted(_)
+        ));
+    }
+
+    #[test]
+    fn test_rejects_invalid_eip1559_params() {
+        let policy = AttributesPolicy::new(PolicyAction::Reject);
+
+        let mut valid = attrs(1, 0);
+        valid.eip_1559_params = Some(B64::ZERO);
+        assert!(matches!(policy.apply(valid), PolicyOutcome::Accepted(_)));
+
+        let mut invalid = attrs(2, 0);
+        invalid.eip_1559_params = Some(B64::from([0, 0, 0, 0, 0, 0, 0, 6]));
+        assert!(matches!(policy.apply(invalid), PolicyOutcome::Rejected(_)));
+    }
+}
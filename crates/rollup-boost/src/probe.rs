@@ -13,7 +13,7 @@ use jsonrpsee::{
 use parking_lot::Mutex;
 use tower::{Layer, Service};
 
-use crate::{Request, Response};
+use crate::{FLASHBLOCKS_SCHEMA_PATH, Request, Response, flashblocks_json_schemas};
 
 #[derive(Copy, Clone, Debug, Default)]
 pub enum Health {
@@ -28,6 +28,16 @@ pub enum Health {
     ServiceUnavailable,
 }
 
+impl Health {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Health::Healthy => "healthy",
+            Health::PartialContent => "partial_content",
+            Health::ServiceUnavailable => "service_unavailable",
+        }
+    }
+}
+
 impl From<Health> for Response {
     fn from(health: Health) -> Self {
         match health {
@@ -118,6 +128,8 @@ where
                 "/readyz" => Ok(ok()),
                 // Service is responding, and therefor live
                 "/livez" => Ok(ok()),
+                // JSON Schemas of the flashblocks messages, for the consumers to codegen
+                FLASHBLOCKS_SCHEMA_PATH => Ok(flashblocks_schemas()),
                 // Forward the request to the inner service
                 _ => service.inner.call(request).await.map_err(|e| e.into()),
             }
@@ -133,6 +145,14 @@ fn ok() -> Response {
         .expect("Failed to create OK reponse")
 }
 
+fn flashblocks_schemas() -> Response {
+    HttpResponse::builder()
+        .status(200)
+        .header("content-type", "application/schema+json")
+        .body(HttpBody::from(flashblocks_json_schemas().to_string()))
+        .expect("Failed to create flashblocks schemas response")
+}
+
 fn partial_content() -> Response {
     HttpResponse::builder()
         .status(206)
@@ -0,0 +1,193 @@
+use std::time::Duration;
+
+use crate::cli::Args;
+
+/// Duration of a CPU profile if the `seconds` parameter is not set.
+pub const DEFAULT_PROFILE_DURATION: Duration = Duration::from_secs(30);
+
+/// Longest CPU profile that can be requested.
+pub const MAX_PROFILE_DURATION: Duration = Duration::from_secs(300);
+
+/// Serves pprof profiles on `--profiling-addr`, if set, so that performance regressions
+/// in payload assembly can be diagnosed in production:
+///
+/// - `/debug/pprof/profile?seconds=N` samples the CPU for N seconds
+/// - `/debug/pprof/heap` returns a snapshot of the sampled heap allocations
+///
+/// Both return a gzipped pprof protobuf, readable with `go tool pprof`.
+pub async fn spawn_profiling_server(args: &Args) -> eyre::Result<()> {
+    let Some(addr) = args.profiling_addr else {
+        return Ok(());
+    };
+
+    #[cfg(feature = "profiling")]
+    {
+        server::spawn(addr).await
+    }
+
+    #[cfg(not(feature = "profiling"))]
+    eyre::bail!(
+        "cannot serve profiles on {addr}, rollup-boost was built without the profiling feature"
+    )
+}
+
+/// Duration of the CPU profile requested by the `seconds` parameter of `query`.
+pub fn profile_duration(query: Option<&str>) -> Result<Duration, String> {
+    let seconds = query
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .find_map(|param| param.strip_prefix("seconds="));
+    let Some(seconds) = seconds else {
+        return Ok(DEFAULT_PROFILE_DURATION);
+    };
+    let duration = seconds
+        .parse::<u64>()
+        .map(Duration::from_secs)
+        .map_err(|_| format!("invalid seconds: {seconds}"))?;
+    if duration.is_zero() || duration > MAX_PROFILE_DURATION {
+        return Err(format!(
+            "seconds must be between 1 and {}",
+            MAX_PROFILE_DURATION.as_secs()
+        ));
+    }
+    Ok(duration)
+}
+
+#[cfg(feature = "profiling")]
+mod server {
+    use std::{io::Write, net::SocketAddr, time::Duration};
+
+    use flate2::{Compression, write::GzEncoder};
+    use http::{Method, StatusCode};
+    use hyper::{Request, Response, server::conn::http1, service::service_fn};
+    use hyper_util::rt::TokioIo;
+    use jsonrpsee::http_client::HttpBody;
+    use pprof::protos::Message;
+    use tokio::net::TcpListener;
+    use tracing::{error, info};
+
+    use super::profile_duration;
+
+    /// Frequency at which the CPU is sampled, in Hz.
+    const SAMPLE_FREQUENCY: i32 = 99;
+
+    pub(super) async fn spawn(addr: SocketAddr) -> eyre::Result<()> {
+        let listener = TcpListener::bind(addr).await?;
+        info!("Profiling server running on {}", addr);
+
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        tokio::spawn(async move {
+                            let service =
+                                service_fn(|req: Request<hyper::body::Incoming>| async move {
+                                    Ok::<_, hyper::Error>(handle(req).await)
+                                });
+                            if let Err(err) = http1::Builder::new()
+                                .serve_connection(TokioIo::new(stream), service)
+                                .await
+                            {
+                                error!(message = "Error serving profiling connection", error = %err);
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        error!(message = "Error accepting connection", error = %e);
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    async fn handle(req: Request<hyper::body::Incoming>) -> Response<HttpBody> {
+        let profile = match (req.method(), req.uri().path()) {
+            (&Method::GET, "/debug/pprof/profile") => match profile_duration(req.uri().query()) {
+                Ok(duration) => cpu_profile(duration)
+                    .await
+                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e)),
+                Err(e) => Err((StatusCode::BAD_REQUEST, e)),
+            },
+            (&Method::GET, "/debug/pprof/heap") => heap_profile()
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e)),
+            _ => Err((StatusCode::NOT_FOUND, String::new())),
+        };
+
+        match profile {
+            Ok(profile) => Response::builder()
+                .header("content-type", "application/octet-stream")
+                .header(
+                    "content-disposition",
+                    "attachment; filename=\"profile.pb.gz\"",
+                )
+                .body(HttpBody::from(profile))
+                .expect("Failed to create profile response"),
+            Err((status, message)) => Response::builder()
+                .status(status)
+                .header("content-type", "text/plain")
+                .body(HttpBody::from(message))
+                .expect("Failed to create error response"),
+        }
+    }
+
+    /// Samples the CPU for `duration`, on a blocking thread so that the runtime is not
+    /// held up.
+    async fn cpu_profile(duration: Duration) -> Result<Vec<u8>, String> {
+        info!(
+            message = "Capturing CPU profile",
+            seconds = duration.as_secs()
+        );
+        tokio::task::spawn_blocking(move || {
+            let guard = pprof::ProfilerGuardBuilder::default()
+                .frequency(SAMPLE_FREQUENCY)
+                .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+                .build()
+                .map_err(|e| e.to_string())?;
+            std::thread::sleep(duration);
+            let profile = guard
+                .report()
+                .build()
+                .and_then(|report| report.pprof())
+                .map_err(|e| e.to_string())?;
+
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder
+                .write_all(&profile.encode_to_vec())
+                .and_then(|()| encoder.finish())
+                .map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+
+    /// Dumps the heap allocations sampled by jemalloc, already gzipped.
+    async fn heap_profile() -> Result<Vec<u8>, String> {
+        let prof_ctl = jemalloc_pprof::PROF_CTL
+            .as_ref()
+            .ok_or("heap profiling is not available, jemalloc is not the allocator")?;
+        let mut prof_ctl = prof_ctl.lock().await;
+        if !prof_ctl.activated() {
+            return Err("heap profiling is not activated".to_string());
+        }
+        prof_ctl.dump_pprof().map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_duration() {
+        assert_eq!(profile_duration(None), Ok(DEFAULT_PROFILE_DURATION));
+        assert_eq!(
+            profile_duration(Some("debug=1&seconds=10")),
+            Ok(Duration::from_secs(10))
+        );
+        assert!(profile_duration(Some("seconds=0")).is_err());
+        assert!(profile_duration(Some("seconds=301")).is_err());
+        assert!(profile_duration(Some("seconds=ten")).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::Parser;
+use metrics::gauge;
+use parking_lot::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::{AuditEntry, AuditLog, ExecutionMode, Health, Probes, WebhookEvent, WebhookNotifier};
+
+/// Caller the automatic promotion is recorded with in the audit log.
+pub const PROMOTION_CALLER: &str = "promotion";
+
+#[derive(Parser, Clone, Debug)]
+pub struct PromotionArgs {
+    /// Start in dry run mode, returning the l2 payloads only, and switch to
+    /// `--execution-mode` once the builder passed the health checks for
+    /// `--promotion-healthy-secs`
+    #[arg(long, env, default_value = "false")]
+    pub boot_fallback_only: bool,
+
+    /// Duration in seconds the builder must pass the health checks for in a row before
+    /// it is promoted
+    #[arg(long, env, default_value = "60")]
+    pub promotion_healthy_secs: u64,
+}
+
+/// Starts rollup-boost in dry run mode, so that the builder follows the chain but none
+/// of its payloads is returned, and promotes it to the configured execution mode once
+/// the builder was healthy for long enough, so that deployments do not have to switch
+/// the execution mode by hand. The promotion is abandoned if the execution mode was
+/// changed in the meantime, e.g. through the debug API.
+#[derive(Debug)]
+pub struct BuilderPromotion {
+    target: ExecutionMode,
+    period: Duration,
+    check_interval: Duration,
+    // Start of the current run of successful health checks
+    healthy_since: Mutex<Option<Instant>>,
+}
+
+impl BuilderPromotion {
+    /// Execution mode rollup-boost starts in until the builder is promoted.
+    pub const BOOT_MODE: ExecutionMode = ExecutionMode::DryRun;
+
+    pub fn new(target: ExecutionMode, period: Duration, check_interval: Duration) -> Self {
+        Self {
+            target,
+            period,
+            check_interval,
+            healthy_since: Mutex::new(None),
+        }
+    }
+
+    /// Promotes to `target` if a fallback-only boot is requested, checking the health of
+    /// the builder every `check_interval`.
+    pub fn from_args(
+        args: &PromotionArgs,
+        target: ExecutionMode,
+        check_interval: Duration,
+    ) -> Option<Self> {
+        if !args.boot_fallback_only {
+            return None;
+        }
+        Some(Self::new(
+            target,
+            Duration::from_secs(args.promotion_healthy_secs),
+            check_interval,
+        ))
+    }
+
+    /// Records the outcome of a health check, returning whether the builder has been
+    /// healthy for long enough to be promoted.
+    fn observe(&self, health: Health, now: Instant) -> bool {
+        let mut healthy_since = self.healthy_since.lock();
+        if !matches!(health, Health::Healthy) {
+            *healthy_since = None;
+            return false;
+        }
+        now.duration_since(*healthy_since.get_or_insert(now)) >= self.period
+    }
+
+    /// Checks the health of the builder until it is promoted, or the execution mode was
+    /// changed by other means.
+    pub fn spawn(
+        self: Arc<Self>,
+        execution_mode: Arc<Mutex<ExecutionMode>>,
+        probes: Arc<Probes>,
+        audit_log: Arc<AuditLog>,
+        webhooks: Option<Arc<WebhookNotifier>>,
+    ) -> JoinHandle<()> {
+        gauge!("builder.promotion_pending").set(1);
+        info!(
+            message = "started in fallback-only mode, waiting for the builder to be healthy",
+            target = ?self.target,
+            period_secs = self.period.as_secs(),
+        );
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.check_interval);
+            loop {
+                interval.tick().await;
+                if *execution_mode.lock() != Self::BOOT_MODE {
+                    info!(message = "execution mode changed, builder promotion abandoned");
+                    break;
+                }
+                if self.observe(probes.health(), Instant::now()) {
+                    self.promote(&execution_mode, &audit_log, webhooks.as_deref());
+                    break;
+                }
+            }
+            gauge!("builder.promotion_pending").set(0);
+        })
+    }
+
+    fn promote(
+        &self,
+        execution_mode: &Mutex<ExecutionMode>,
+        audit_log: &AuditLog,
+        webhooks: Option<&WebhookNotifier>,
+    ) {
+        let mut execution_mode = execution_mode.lock();
+        let old_mode = *execution_mode;
+        // Changes are recorded before they take effect, as through the debug API
+        let entry = AuditEntry::new(PROMOTION_CALLER.to_string(), old_mode, self.target);
+        if let Err(e) = audit_log.record(entry) {
+            warn!(message = "failed to record the builder promotion, not promoting", error = %e);
+            return;
+        }
+        *execution_mode = self.target;
+        drop(execution_mode);
+
+        info!(message = "builder healthy, promoted", ?old_mode, new_mode = ?self.target);
+        if let Some(webhooks) = webhooks {
+            webhooks.notify(
+                WebhookEvent::ExecutionModeChanged,
+                format!(
+                    "execution mode changed from {old_mode:?} to {:?} by {PROMOTION_CALLER}",
+                    self.target
+                ),
+                serde_json::json!({
+                    "caller": PROMOTION_CALLER,
+                    "old_mode": old_mode,
+                    "new_mode": self.target,
+                }),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_promotion() {
+        let promotion = BuilderPromotion::new(
+            ExecutionMode::Enabled,
+            Duration::from_secs(10),
+            Duration::from_secs(1),
+        );
+        let start = Instant::now();
+        assert!(!promotion.observe(Health::Healthy, start));
+        assert!(!promotion.observe(Health::Healthy, start + Duration::from_secs(5)));
+
+        // An unhealthy builder starts the period again
+        assert!(!promotion.observe(Health::PartialContent, start + Duration::from_secs(6)));
+        assert!(!promotion.observe(Health::Healthy, start + Duration::from_secs(7)));
+        assert!(!promotion.observe(Health::Healthy, start + Duration::from_secs(16)));
+        assert!(promotion.observe(Health::Healthy, start + Duration::from_secs(17)));
+    }
+
+    #[tokio::test]
+    async fn test_promote() {
+        let execution_mode = Arc::new(Mutex::new(BuilderPromotion::BOOT_MODE));
+        let audit_log = Arc::new(AuditLog::default());
+        let promotion = Arc::new(BuilderPromotion::new(
+            ExecutionMode::Enabled,
+            Duration::ZERO,
+            Duration::from_millis(10),
+        ));
+        promotion
+            .spawn(
+                execution_mode.clone(),
+                Arc::new(Probes::default()),
+                audit_log.clone(),
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(*execution_mode.lock(), ExecutionMode::Enabled);
+        let entries = audit_log.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].caller, PROMOTION_CALLER);
+    }
+}
@@ -1,20 +1,27 @@
 use crate::client::http::HttpClient;
+use crate::client::pool::ConnectionPoolConfig;
 use crate::consistent_request::ConsistentRequest;
 use crate::payload::PayloadSource;
+use crate::server::ENGINE_CAPABILITIES;
 use crate::{
-    ExecutionMode, Probes, Request, Response, from_buffered_request, into_buffered_request,
+    BufferedRequest, ClientAddr, CorrelationId, DryRunOverride, ExecutionMode, IngressDecision,
+    Probes, RawTransaction, RemoteAddr, Request, Response, RpcLogger, RpcRateLimiter, RpcRoute,
+    RpcRouting, SharedJwtSecret, TrustedProxies, TxIngress, from_buffered_request,
+    into_buffered_request,
 };
-use alloy_rpc_types_engine::JwtSecret;
+use alloy_primitives::{Bytes, bytes};
 use http::Uri;
-use http_body_util::BodyExt as _;
+use http_body_util::{BodyExt as _, Full};
 use jsonrpsee::core::BoxError;
 use jsonrpsee::server::HttpBody;
+use jsonrpsee::types::error::{INTERNAL_ERROR_CODE, INVALID_REQUEST_CODE, METHOD_NOT_FOUND_CODE};
 use parking_lot::Mutex;
+use rustls::ClientConfig;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::{future::Future, pin::Pin};
-use tower::{Layer, Service};
-use tracing::info;
+use tower::{Layer, Service, ServiceExt};
+use tracing::{Instrument, info, info_span};
 
 const ENGINE_METHOD: &str = "engine_";
 
@@ -27,52 +34,131 @@ const FORWARD_REQUESTS: [&str; 5] = [
     "miner_setGasLimit",
 ];
 
+/// Requests submitting raw transactions, subject to the transaction ingress checks
+const RAW_TRANSACTION_METHODS: [&str; 2] = [
+    "eth_sendRawTransaction",
+    "eth_sendRawTransactionConditional",
+];
+
+/// JSON-RPC error code for requests over the rate limit (EIP-1474)
+const LIMIT_EXCEEDED_CODE: i32 = -32005;
+
 pub const MINER_SET_MAX_DA_SIZE: &str = "miner_setMaxDASize";
 
 #[derive(Debug, Clone)]
 pub struct ProxyLayer {
     l2_auth_rpc: Uri,
-    l2_auth_secret: JwtSecret,
+    l2_auth_secret: SharedJwtSecret,
     builder_auth_rpc: Uri,
-    builder_auth_secret: JwtSecret,
+    builder_auth_secret: SharedJwtSecret,
     probes: Arc<Probes>,
     execution_mode: Arc<Mutex<ExecutionMode>>,
+    routing: Arc<RpcRouting>,
+    tx_ingress: Arc<TxIngress>,
+    rate_limiter: Option<Arc<RpcRateLimiter>>,
+    builder_tls: Option<ClientConfig>,
+    rpc_logger: Option<Arc<RpcLogger>>,
+    trusted_proxies: Arc<TrustedProxies>,
+    l2_pool: ConnectionPoolConfig,
+    builder_pool: ConnectionPoolConfig,
 }
 
 impl ProxyLayer {
     pub fn new(
         l2_auth_rpc: Uri,
-        l2_auth_secret: JwtSecret,
+        l2_auth_secret: impl Into<SharedJwtSecret>,
         builder_auth_rpc: Uri,
-        builder_auth_secret: JwtSecret,
+        builder_auth_secret: impl Into<SharedJwtSecret>,
         probes: Arc<Probes>,
         execution_mode: Arc<Mutex<ExecutionMode>>,
     ) -> Self {
         ProxyLayer {
             l2_auth_rpc,
-            l2_auth_secret,
+            l2_auth_secret: l2_auth_secret.into(),
             builder_auth_rpc,
-            builder_auth_secret,
+            builder_auth_secret: builder_auth_secret.into(),
             probes,
             execution_mode,
+            routing: Arc::new(RpcRouting::default()),
+            tx_ingress: Arc::new(TxIngress::default()),
+            rate_limiter: None,
+            builder_tls: None,
+            rpc_logger: None,
+            trusted_proxies: Arc::new(TrustedProxies::default()),
+            l2_pool: ConnectionPoolConfig::default(),
+            builder_pool: ConnectionPoolConfig::default(),
         }
     }
+
+    /// Sets the allowlist, denylist and upstream routing of the non-engine methods.
+    pub fn with_routing(mut self, routing: RpcRouting) -> Self {
+        self.routing = Arc::new(routing);
+        self
+    }
+
+    /// Sets the deduplication and rate limiting of raw transactions.
+    pub fn with_tx_ingress(mut self, tx_ingress: TxIngress) -> Self {
+        self.tx_ingress = Arc::new(tx_ingress);
+        self
+    }
+
+    /// Rate limits the non-engine methods per source IP and method. Requests without a
+    /// source IP, such as requests on a unix domain socket, are not limited.
+    pub fn with_rate_limiter(mut self, rate_limiter: Option<RpcRateLimiter>) -> Self {
+        self.rate_limiter = rate_limiter.map(Arc::new);
+        self
+    }
+
+    /// Logs the requests and responses going through the proxy.
+    pub fn with_rpc_logger(mut self, rpc_logger: Option<RpcLogger>) -> Self {
+        self.rpc_logger = rpc_logger.map(Arc::new);
+        self
+    }
+
+    /// Identifies the clients behind the trusted proxies from the forwarding headers of
+    /// their requests, for rate limiting and logging.
+    pub fn with_trusted_proxies(mut self, trusted_proxies: TrustedProxies) -> Self {
+        self.trusted_proxies = Arc::new(trusted_proxies);
+        self
+    }
+
+    /// Sets the connection pool settings of the clients forwarding the calls to the l2
+    /// client and the builder.
+    pub fn with_connection_pools(
+        mut self,
+        l2_pool: ConnectionPoolConfig,
+        builder_pool: ConnectionPoolConfig,
+    ) -> Self {
+        self.l2_pool = l2_pool;
+        self.builder_pool = builder_pool;
+        self
+    }
+
+    /// Sets the TLS configuration of the connections to the builder.
+    pub fn with_builder_tls(mut self, tls: Option<ClientConfig>) -> Self {
+        self.builder_tls = tls;
+        self
+    }
 }
 
 impl<S> Layer<S> for ProxyLayer {
     type Service = ProxyService<S>;
 
     fn layer(&self, inner: S) -> Self::Service {
-        let l2_client = HttpClient::new(
+        let l2_client = HttpClient::new_with_pool(
             self.l2_auth_rpc.clone(),
-            self.l2_auth_secret,
+            self.l2_auth_secret.clone(),
             PayloadSource::L2,
+            None,
+            self.l2_pool.clone(),
         );
 
-        let builder_client = HttpClient::new(
+        let builder_client = HttpClient::new_with_pool(
             self.builder_auth_rpc.clone(),
-            self.builder_auth_secret,
+            self.builder_auth_secret.clone(),
             PayloadSource::Builder,
+            self.builder_tls.clone(),
+            self.builder_pool.clone(),
         );
 
         let set_max_da_size_manager = ConsistentRequest::new(
@@ -88,6 +174,11 @@ impl<S> Layer<S> for ProxyLayer {
             l2_client,
             builder_client,
             set_max_da_size_manager,
+            routing: self.routing.clone(),
+            tx_ingress: self.tx_ingress.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            rpc_logger: self.rpc_logger.clone(),
+            trusted_proxies: self.trusted_proxies.clone(),
         }
     }
 }
@@ -98,6 +189,11 @@ pub struct ProxyService<S> {
     l2_client: HttpClient,
     builder_client: HttpClient,
     set_max_da_size_manager: ConsistentRequest,
+    routing: Arc<RpcRouting>,
+    tx_ingress: Arc<TxIngress>,
+    rate_limiter: Option<Arc<RpcRateLimiter>>,
+    rpc_logger: Option<Arc<RpcLogger>>,
+    trusted_proxies: Arc<TrustedProxies>,
 }
 
 // Consider using `RpcServiceT` when https://github.com/paritytech/jsonrpsee/pull/1521 is merged
@@ -118,66 +214,364 @@ where
     }
 
     fn call(&mut self, req: Request) -> Self::Future {
-        #[derive(serde::Deserialize, Debug)]
-        struct RpcRequest<'a> {
-            #[serde(borrow)]
-            method: &'a str,
-        }
-
         // See https://github.com/tower-rs/tower/blob/abb375d08cf0ba34c1fe76f66f1aba3dc4341013/tower-service/src/lib.rs#L276
         // for an explanation of this pattern
         let mut service = self.clone();
         service.inner = std::mem::replace(&mut self.inner, service.inner);
 
+        // Every request entering the proxy gets a correlation id, either provided by the
+        // caller or freshly generated, which is propagated to the builder and l2 calls.
+        let correlation_id = CorrelationId::from_headers(req.headers()).unwrap_or_default();
+        let dry_run = DryRunOverride::from_headers(req.headers());
+        let span = info_span!(target: "proxy::call", "request", %correlation_id);
+
         let fut = async move {
-            let buffered = into_buffered_request(req).await?;
+            let mut buffered = into_buffered_request(req).await?;
+            let client = buffered
+                .extensions()
+                .get::<RemoteAddr>()
+                .map(|RemoteAddr(addr)| {
+                    service
+                        .trusted_proxies
+                        .client_ip(addr.ip(), buffered.headers())
+                });
+            if let Some(client) = client {
+                buffered.extensions_mut().insert(ClientAddr(client));
+            }
+
             let body_bytes = buffered.clone().collect().await?.to_bytes();
+            let log_entry = service.rpc_logger.as_ref().and_then(|logger| {
+                logger
+                    .start(buffered.headers(), &body_bytes)
+                    .map(|entry| entry.with_client(client))
+            });
+
+            let response = if is_batch(&body_bytes) {
+                service.handle_batch(buffered, &body_bytes).await?
+            } else {
+                service.handle(buffered, &body_bytes).await?
+            };
+            match log_entry {
+                Some(entry) => entry.finish(response).await,
+                None => Ok(response),
+            }
+        };
+
+        Box::pin(
+            correlation_id
+                .scope(DryRunOverride::scope(dry_run, fut))
+                .instrument(span),
+        )
+    }
+}
+
+impl<S> ProxyService<S>
+where
+    S: Service<Request, Response = Response> + Send + Sync + Clone + 'static,
+    S::Response: 'static,
+    S::Error: Into<BoxError> + 'static,
+    S::Future: Send + 'static,
+{
+    /// Routes a single JSON-RPC request to the rollup-boost server or the upstreams.
+    async fn handle(
+        &mut self,
+        buffered: BufferedRequest,
+        body_bytes: &[u8],
+    ) -> Result<Response, BoxError> {
+        #[derive(serde::Deserialize, Debug)]
+        struct RpcRequest<'a> {
+            #[serde(borrow)]
+            method: &'a str,
+            #[serde(default)]
+            id: serde_json::Value,
+        }
 
-            // Deserialize the bytes to find the method
-            let method = serde_json::from_slice::<RpcRequest>(&body_bytes)?
-                .method
-                .to_string();
-
-            // If the request is an Engine API method, call the inner RollupBoostServer
-            if method.starts_with(ENGINE_METHOD) {
-                info!(target: "proxy::call", message = "proxying request to rollup-boost server", ?method);
-                return service
-                    .inner
-                    .call(from_buffered_request(buffered))
+        // Deserialize the bytes to find the method
+        let request = serde_json::from_slice::<RpcRequest>(body_bytes)?;
+        let method = request.method.to_string();
+
+        // If the request is an Engine API method, call the inner RollupBoostServer
+        if method.starts_with(ENGINE_METHOD) {
+            // Engine methods that are not served by rollup-boost are forwarded to
+            // the l2 client instead of failing
+            if !is_served_engine_method(&method) {
+                info!(target: "proxy::call", message = "forwarding unsupported engine method to l2", ?method);
+                return self
+                    .l2_client
+                    .forward(buffered, method)
                     .await
-                    .map_err(|e| e.into());
+                    .map(|res| res.map(HttpBody::new));
             }
 
-            // We need to handle the `miner_setMaxDASize` method carefully,
-            // so we ensure that the responses receive from the L2 and builder
-            // are consistent.
-            if method == MINER_SET_MAX_DA_SIZE {
-                return service.set_max_da_size_manager.send(buffered).await;
+            info!(target: "proxy::call", message = "proxying request to rollup-boost server", ?method);
+            return self
+                .inner
+                .ready()
+                .await
+                .map_err(|e| e.into())?
+                .call(from_buffered_request(buffered))
+                .await
+                .map_err(|e| e.into());
+        }
+
+        let route = self.routing.route(&method);
+        if route == RpcRoute::Denied {
+            info!(target: "proxy::call", message = "rejecting denied method", ?method);
+            return Ok(json_rpc_error(
+                request.id,
+                METHOD_NOT_FOUND_CODE,
+                "Method not allowed",
+            ));
+        }
+
+        if let (Some(rate_limiter), Some(ClientAddr(client))) = (
+            &self.rate_limiter,
+            buffered.extensions().get::<ClientAddr>(),
+        ) {
+            if !rate_limiter.check(*client, &method).await {
+                info!(target: "proxy::call", message = "rejecting rate limited request", ?method, %client);
+                return Ok(json_rpc_error(
+                    request.id,
+                    LIMIT_EXCEEDED_CODE,
+                    "Rate limit exceeded",
+                ));
             }
+        }
 
-            if FORWARD_REQUESTS.contains(&method.as_str()) {
-                // If the request should be forwarded, send to both the
-                // default execution client and the builder
-                let method_clone = method.clone();
-                let buffered_clone = buffered.clone();
-                let mut builder_client = service.builder_client.clone();
+        // Raw transactions go through deduplication and rate limiting before being
+        // forwarded to the builder and the l2 client
+        let mut admitted_tx = None;
+        if route == RpcRoute::L2 && RAW_TRANSACTION_METHODS.contains(&method.as_str()) {
+            if let Some(raw_tx) = raw_transaction(body_bytes) {
+                match self.tx_ingress.admit(&raw_tx).await {
+                    IngressDecision::Forward => admitted_tx = Some(raw_tx),
+                    IngressDecision::Duplicate(tx_hash) => {
+                        return Ok(json_rpc_result(request.id, tx_hash));
+                    }
+                    IngressDecision::RateLimited => {
+                        return Ok(json_rpc_error(
+                            request.id,
+                            LIMIT_EXCEEDED_CODE,
+                            "Rate limit exceeded",
+                        ));
+                    }
+                }
+            }
 
-                // Fire and forget the builder request
-                tokio::spawn(async move {
-                    let _ = builder_client.forward(buffered_clone, method_clone).await;
-                });
+            if self.tx_ingress.builder_only() {
+                let response = self.builder_client.forward(buffered, method).await?;
+                return self.remember_accepted(admitted_tx, response).await;
             }
+        }
 
-            // Return the response from the L2 client
-            service
-                .l2_client
-                .forward(buffered, method)
-                .await
-                .map(|res| res.map(HttpBody::new))
+        // We need to handle the `miner_setMaxDASize` method carefully,
+        // so we ensure that the responses receive from the L2 and builder
+        // are consistent.
+        if method == MINER_SET_MAX_DA_SIZE {
+            return self.set_max_da_size_manager.send(buffered).await;
+        }
+
+        if route == RpcRoute::L2 && FORWARD_REQUESTS.contains(&method.as_str()) {
+            // If the request should be forwarded, send to both the
+            // default execution client and the builder
+            let method_clone = method.clone();
+            let buffered_clone = buffered.clone();
+            let mut builder_client = self.builder_client.clone();
+            let correlation_id = CorrelationId::current().unwrap_or_default();
+
+            // Fire and forget the builder request
+            tokio::spawn(
+                correlation_id
+                    .scope(async move {
+                        let _ = builder_client.forward(buffered_clone, method_clone).await;
+                    })
+                    .in_current_span(),
+            );
+        }
+
+        // Return the response from the upstream the method is routed to
+        let client = match route {
+            RpcRoute::Builder => &mut self.builder_client,
+            _ => &mut self.l2_client,
         };
+        let response = client.forward(buffered, method).await?;
+        self.remember_accepted(admitted_tx, response).await
+    }
 
-        Box::pin(fut)
+    /// Remembers the admitted raw transaction if the upstream accepted it, so that its
+    /// duplicate submissions are dropped while a failed submission can be retried.
+    async fn remember_accepted(
+        &self,
+        admitted_tx: Option<RawTransaction>,
+        response: http::Response<Full<bytes::Bytes>>,
+    ) -> Result<Response, BoxError> {
+        let Some(tx) = admitted_tx else {
+            return Ok(response.map(HttpBody::new));
+        };
+        let (parts, body) = response.into_parts();
+        let body_bytes = body.collect().await?.to_bytes();
+        if parts.status.is_success() && response_error_code(&body_bytes).is_none() {
+            self.tx_ingress.accepted(&tx).await;
+        }
+        Ok(http::Response::from_parts(
+            parts,
+            HttpBody::new(Full::from(body_bytes)),
+        ))
     }
+
+    /// Handles a batch of JSON-RPC requests. Every item is routed on its own, and an
+    /// item failing is answered with an error object without affecting the rest of the
+    /// batch.
+    async fn handle_batch(
+        &mut self,
+        buffered: BufferedRequest,
+        body_bytes: &[u8],
+    ) -> Result<Response, BoxError> {
+        let items = match serde_json::from_slice::<Vec<serde_json::Value>>(body_bytes) {
+            Ok(items) if !items.is_empty() => items,
+            _ => {
+                return Ok(json_rpc_error(
+                    serde_json::Value::Null,
+                    INVALID_REQUEST_CODE,
+                    "Invalid request",
+                ));
+            }
+        };
+
+        let (parts, _) = buffered.into_parts();
+        let mut responses = Vec::with_capacity(items.len());
+
+        // Items are handled in order, since engine calls such as a forkchoice update
+        // followed by a get payload depend on each other
+        for item in items {
+            if !item.get("method").is_some_and(serde_json::Value::is_string) {
+                responses.push(json_rpc_error_object(
+                    serde_json::Value::Null,
+                    INVALID_REQUEST_CODE,
+                    "Invalid request",
+                ));
+                continue;
+            }
+            let id = item.get("id").cloned();
+
+            let item_bytes = bytes::Bytes::from(item.to_string());
+            let mut request = http::Request::new(Full::from(item_bytes.clone()));
+            *request.method_mut() = parts.method.clone();
+            *request.uri_mut() = parts.uri.clone();
+            *request.version_mut() = parts.version;
+            *request.headers_mut() = parts.headers.clone();
+            *request.extensions_mut() = parts.extensions.clone();
+            request.headers_mut().remove(http::header::CONTENT_LENGTH);
+
+            let response = match self.handle(request, &item_bytes).await {
+                Ok(response) => response.into_body().collect().await.map_err(BoxError::from),
+                Err(e) => Err(e),
+            };
+            let response = response.and_then(|body| {
+                serde_json::from_slice::<serde_json::Value>(&body.to_bytes()).map_err(Into::into)
+            });
+
+            // Notifications are not answered
+            let Some(id) = id else {
+                continue;
+            };
+
+            match response {
+                Ok(response) => responses.push(response),
+                Err(e) => {
+                    info!(target: "proxy::call", message = "batch item failed", %e);
+                    responses.push(json_rpc_error_object(
+                        id,
+                        INTERNAL_ERROR_CODE,
+                        &e.to_string(),
+                    ));
+                }
+            }
+        }
+
+        if responses.is_empty() {
+            return Ok(Response::new(HttpBody::new(Full::default())));
+        }
+        Ok(json_response(serde_json::Value::Array(responses)))
+    }
+}
+
+fn json_response(body: serde_json::Value) -> Response {
+    let mut response = Response::new(HttpBody::new(Full::from(body.to_string())));
+    response.headers_mut().insert(
+        http::header::CONTENT_TYPE,
+        http::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+fn json_rpc_result(id: serde_json::Value, result: impl serde::Serialize) -> Response {
+    json_response(serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "result": result,
+    }))
+}
+
+fn json_rpc_error(id: serde_json::Value, code: i32, message: &str) -> Response {
+    json_response(json_rpc_error_object(id, code, message))
+}
+
+fn json_rpc_error_object(id: serde_json::Value, code: i32, message: &str) -> serde_json::Value {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {
+            "code": code,
+            "message": message,
+        },
+    })
+}
+
+/// Whether the request body is a JSON-RPC batch, i.e. a JSON array.
+fn is_batch(body_bytes: &[u8]) -> bool {
+    body_bytes
+        .iter()
+        .find(|b| !b.is_ascii_whitespace())
+        .is_some_and(|b| *b == b'[')
+}
+
+/// Extracts the raw transaction from the params of an `eth_sendRawTransaction*` request.
+/// The raw transaction of an `eth_sendRawTransaction` request, and the conditions of an
+/// `eth_sendRawTransactionConditional` request.
+fn raw_transaction(body_bytes: &[u8]) -> Option<RawTransaction> {
+    #[derive(serde::Deserialize, Debug)]
+    struct RawTransactionRequest {
+        params: Vec<serde_json::Value>,
+    }
+
+    let request = serde_json::from_slice::<RawTransactionRequest>(body_bytes).ok()?;
+    let mut params = request.params.into_iter();
+    let raw_tx: Bytes = serde_json::from_value(params.next()?).ok()?;
+    Some(RawTransaction::new(&raw_tx, params.next().as_ref()))
+}
+
+/// Whether an engine method is served by rollup-boost rather than the l2 client.
+fn is_served_engine_method(method: &str) -> bool {
+    method == "engine_exchangeCapabilities" || ENGINE_CAPABILITIES.contains(&method)
+}
+
+/// The code of the error of a JSON-RPC response, if it failed.
+fn response_error_code(body_bytes: &[u8]) -> Option<i32> {
+    #[derive(serde::Deserialize, Debug)]
+    struct RpcResponse {
+        error: Option<JsonRpcError>,
+    }
+
+    #[derive(serde::Deserialize, Debug)]
+    struct JsonRpcError {
+        code: i32,
+    }
+
+    serde_json::from_slice::<RpcResponse>(body_bytes)
+        .ok()
+        .and_then(|res| res.error)
+        .map(|e| e.code)
 }
 
 #[cfg(test)]
@@ -198,7 +592,7 @@ mod tests {
     use jsonrpsee::types::{ErrorCode, ErrorObject};
     use jsonrpsee::{
         RpcModule,
-        core::{ClientError, client::ClientT},
+        core::{ClientError, client::ClientT, params::BatchRequestBuilder},
         http_client::HttpClient,
         rpc_params,
         server::{ServerBuilder, ServerHandle},
@@ -364,6 +758,12 @@ mod tests {
             let method = request_body["method"].as_str().unwrap_or_default();
 
             let response = match method {
+                // Rejected transaction
+                "eth_sendRawTransaction" if request_body["params"][0] == "0xdead" => json!({
+                    "jsonrpc": "2.0",
+                    "error": { "code": -32000, "message": "nonce too low" },
+                    "id": request_body["id"]
+                }),
                 "eth_sendRawTransaction" | "eth_sendRawTransactionConditional" => json!({
                     "jsonrpc": "2.0",
                     "result": format!("{}", B256::from([1; 32])),
@@ -411,6 +811,7 @@ mod tests {
         proxy_success().await;
         proxy_failure().await;
         does_not_proxy_engine_method().await;
+        proxies_unsupported_engine_method().await;
         health_check().await;
     }
 
@@ -431,11 +832,17 @@ mod tests {
     }
 
     async fn does_not_proxy_engine_method() {
-        let response = send_request("engine_method").await;
+        let response = send_request("engine_exchangeCapabilities").await;
         assert!(response.is_ok());
         assert_eq!(response.unwrap(), "engine response");
     }
 
+    async fn proxies_unsupported_engine_method() {
+        let response = send_request("engine_getClientVersionV1").await;
+        assert!(response.is_ok());
+        assert_eq!(response.unwrap(), "l2 engine response");
+    }
+
     async fn health_check() {
         let execution_mode = Arc::new(Mutex::new(ExecutionMode::Enabled));
         let proxy_server = spawn_proxy_server(execution_mode).await;
@@ -489,6 +896,9 @@ mod tests {
         module
             .register_method("greet_melkor", |_, _, _| "You are the dark lord")
             .unwrap();
+        module
+            .register_method("engine_getClientVersionV1", |_, _, _| "l2 engine response")
+            .unwrap();
 
         server.start(module)
     }
@@ -530,7 +940,7 @@ mod tests {
         // Create a mock rpc module
         let mut module = RpcModule::new(());
         module
-            .register_method("engine_method", |_, _, _| "engine response")
+            .register_method("engine_exchangeCapabilities", |_, _, _| "engine response")
             .unwrap();
         module
             .register_method(
@@ -686,6 +1096,40 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_duplicate_raw_transactions() -> eyre::Result<()> {
+        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        let test_harness = TestHarness::new().await?;
+
+        // The duplicate submission of an accepted transaction is not forwarded
+        let accepted_tx: Bytes = hex!("1234").into();
+        for _ in 0..2 {
+            test_harness
+                .proxy_client
+                .request::<serde_json::Value, _>("eth_sendRawTransaction", (accepted_tx.clone(),))
+                .await?;
+        }
+        assert_eq!(test_harness.l2.requests.lock().await.len(), 1);
+
+        // A rejected transaction is forwarded again when resubmitted
+        let rejected_tx: Bytes = hex!("dead").into();
+        for _ in 0..2 {
+            assert!(
+                test_harness
+                    .proxy_client
+                    .request::<serde_json::Value, _>(
+                        "eth_sendRawTransaction",
+                        (rejected_tx.clone(),)
+                    )
+                    .await
+                    .is_err()
+            );
+        }
+        assert_eq!(test_harness.l2.requests.lock().await.len(), 3);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_forward_eth_send_raw_transaction_conditional() -> eyre::Result<()> {
         tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
@@ -858,4 +1302,95 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_batch_request() -> eyre::Result<()> {
+        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        let test_harness = TestHarness::new().await?;
+
+        let mut batch = BatchRequestBuilder::new();
+        batch.insert("mock_forwardedMethod", rpc_params![])?;
+        batch.insert("non_existent_method", rpc_params![])?;
+        batch.insert("miner_setGasLimit", rpc_params![U64::from(30_000_000)])?;
+
+        let response = test_harness
+            .proxy_client
+            .batch_request::<serde_json::Value>(batch)
+            .await?;
+
+        // The failing item does not affect the rest of the batch
+        assert_eq!(response.num_successful_calls(), 2);
+        assert_eq!(response.num_failed_calls(), 1);
+        let results = response.into_iter().collect::<Vec<_>>();
+        assert_eq!(results[0].as_ref().unwrap(), &json!("forwarded response"));
+        assert!(results[1].is_err());
+        assert_eq!(results[2].as_ref().unwrap(), &json!(true));
+
+        // Every item is forwarded on its own to the l2
+        let l2_requests = test_harness.l2.requests.lock().await;
+        assert_eq!(l2_requests.len(), 3);
+        assert!(l2_requests.iter().all(|req| req.is_object()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_websocket_forward_mock_request() -> eyre::Result<()> {
+        use futures::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::{Message, client::IntoClientRequest};
+
+        let builder = MockHttpServer::serve().await?;
+        let l2 = MockHttpServer::serve().await?;
+        let middleware = tower::ServiceBuilder::new().layer(ProxyLayer::new(
+            format!("http://{}", l2.addr).parse::<Uri>()?,
+            JwtSecret::random(),
+            format!("http://{}", builder.addr).parse::<Uri>()?,
+            JwtSecret::random(),
+            Arc::new(Probes::default()),
+            Arc::new(Mutex::new(ExecutionMode::Enabled)),
+        ));
+        let service_builder = Server::builder()
+            .set_http_middleware(middleware)
+            .to_service_builder();
+
+        let secret = JwtSecret::random();
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let server_handle = crate::spawn_ws_server(listener, secret, |stop_handle| {
+            service_builder.build(RpcModule::new(()), stop_handle)
+        });
+
+        // Connections without the JWT are refused
+        assert!(
+            tokio_tungstenite::connect_async(format!("ws://{addr}"))
+                .await
+                .is_err()
+        );
+
+        let mut request = format!("ws://{addr}").into_client_request()?;
+        request.headers_mut().insert(
+            http::header::AUTHORIZATION,
+            crate::secret_to_bearer_header(&secret),
+        );
+        let (mut ws, _) = tokio_tungstenite::connect_async(request).await?;
+
+        // Methods that are not served by rollup-boost are forwarded to the l2 client
+        let call =
+            json!({"jsonrpc": "2.0", "method": "mock_forwardedMethod", "params": [], "id": 1});
+        ws.send(Message::text(call.to_string())).await?;
+        let Some(Message::Text(text)) = ws.next().await.transpose()? else {
+            panic!("expected a text response");
+        };
+        let response: serde_json::Value = serde_json::from_str(&text)?;
+        assert_eq!(response["result"], "forwarded response");
+        assert_eq!(response["id"], 1);
+
+        let l2_requests = l2.requests.lock().await;
+        assert_eq!(l2_requests.len(), 1);
+        assert_eq!(l2_requests[0]["method"], "mock_forwardedMethod");
+        assert!(builder.requests.lock().await.is_empty());
+
+        server_handle.stop()?;
+        Ok(())
+    }
 }
@@ -0,0 +1,359 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use alloy_primitives::{B256, U64};
+use alloy_rpc_types_engine::{
+    ExecutionPayloadBodiesV1, ForkchoiceState, ForkchoiceUpdated, PayloadId, PayloadStatus,
+};
+use alloy_rpc_types_eth::{Block, BlockNumberOrTag};
+use clap::Parser;
+use jsonrpsee::core::async_trait;
+use metrics::{counter, gauge};
+use op_alloy_rpc_types_engine::OpPayloadAttributes;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    BlockSource, ClientResult, EngineApiExt, NewPayload, OpExecutionPayloadEnvelope,
+    PayloadVersion, ReorgEvent, RpcClientError,
+};
+
+#[derive(Parser, Clone, Debug)]
+pub struct QuarantineArgs {
+    /// Number of invalid payloads within the quarantine window after which a builder is
+    /// quarantined, builders are never quarantined if not set
+    #[arg(long, env)]
+    pub builder_quarantine_threshold: Option<u32>,
+
+    /// Duration in seconds over which the invalid payloads of a builder are counted
+    #[arg(long, env, default_value = "300")]
+    pub builder_quarantine_window: u64,
+
+    /// Duration in seconds a quarantined builder is excluded from block building
+    #[arg(long, env, default_value = "600")]
+    pub builder_quarantine_cooldown: u64,
+}
+
+/// Why a payload from a builder was found to be invalid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationFailure {
+    /// The block hash of the payload does not match its contents
+    BlockHash,
+    /// The builder streamed flashblocks out of order, or without a base
+    IndexSequence,
+    /// The payload was rejected by the `new_payload` call of the L2 client
+    RejectedByL2,
+    /// The payload was rejected when re-executed by the validation client
+    RejectedByValidator,
+    /// The builder streamed more flashblocks, or larger deltas, than allowed
+    LimitExceeded,
+    /// The extra data of the payload does not encode the Holocene EIP-1559 parameters
+    /// of the payload attributes
+    Eip1559Params,
+    /// The gas used decreased between deltas, or exceeded the gas limit of the block
+    GasUsed,
+    /// The builder streamed the same transaction more than once for a payload
+    DuplicateTransaction,
+    /// The builder streamed deltas faster than the minimum interval
+    Cadence,
+    /// The state root changed between deltas without transactions, did not change with
+    /// them, or was missing from the last delta
+    StateRoot,
+    /// The fee recipient, gas limit or EIP-1559 parameters of the payload do not match
+    /// the payload attributes
+    AttributesMismatch,
+}
+
+impl ValidationFailure {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ValidationFailure::BlockHash => "block_hash",
+            ValidationFailure::IndexSequence => "index_sequence",
+            ValidationFailure::RejectedByL2 => "rejected_by_l2",
+            ValidationFailure::RejectedByValidator => "rejected_by_validator",
+            ValidationFailure::LimitExceeded => "limit_exceeded",
+            ValidationFailure::Eip1559Params => "eip1559_params",
+            ValidationFailure::GasUsed => "gas_used",
+            ValidationFailure::DuplicateTransaction => "duplicate_transaction",
+            ValidationFailure::Cadence => "cadence",
+            ValidationFailure::StateRoot => "state_root",
+            ValidationFailure::AttributesMismatch => "attributes_mismatch",
+        }
+    }
+}
+
+/// A builder excluded from block building, as reported by `debug_getStatus`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct QuarantinedBuilder {
+    pub name: String,
+    /// Seconds until the builder is used again
+    pub remaining_secs: u64,
+}
+
+#[derive(Debug, Default)]
+struct BuilderFailures {
+    failures: VecDeque<Instant>,
+    quarantined_until: Option<Instant>,
+}
+
+/// Tracks the invalid payloads of each builder, and quarantines a builder that returns
+/// `threshold` invalid payloads within `window` for `cooldown`.
+#[derive(Debug)]
+pub struct BuilderQuarantine {
+    threshold: u32,
+    window: Duration,
+    cooldown: Duration,
+    builders: Mutex<HashMap<String, BuilderFailures>>,
+}
+
+impl BuilderQuarantine {
+    pub fn new(threshold: u32, window: Duration, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            window,
+            cooldown,
+            builders: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_args(args: &QuarantineArgs) -> Option<Self> {
+        args.builder_quarantine_threshold.map(|threshold| {
+            Self::new(
+                threshold,
+                Duration::from_secs(args.builder_quarantine_window),
+                Duration::from_secs(args.builder_quarantine_cooldown),
+            )
+        })
+    }
+
+    /// Records an invalid payload from `builder`, quarantining it once the threshold is
+    /// reached. Returns whether the builder was quarantined.
+    pub fn record_failure(&self, builder: &str, failure: ValidationFailure) -> bool {
+        self.record_failure_at(builder, failure, Instant::now())
+    }
+
+    fn record_failure_at(&self, builder: &str, failure: ValidationFailure, now: Instant) -> bool {
+        counter!("builder.invalid_payloads", "builder" => builder.to_string(), "reason" => failure.as_str())
+            .increment(1);
+
+        let mut builders = self.builders.lock();
+        let state = builders.entry(builder.to_string()).or_default();
+        if state.quarantined_until.is_some_and(|until| now < until) {
+            return false;
+        }
+
+        state.failures.push_back(now);
+        while state
+            .failures
+            .front()
+            .is_some_and(|at| now.duration_since(*at) > self.window)
+        {
+            state.failures.pop_front();
+        }
+
+        warn!(message = "invalid builder payload", %builder, reason = failure.as_str(), failures = state.failures.len());
+        if state.failures.len() < self.threshold as usize {
+            return false;
+        }
+
+        state.failures.clear();
+        state.quarantined_until = Some(now + self.cooldown);
+
+        counter!("builder.quarantined", "builder" => builder.to_string()).increment(1);
+        gauge!("builder.quarantine_active", "builder" => builder.to_string()).set(1);
+        warn!(target: "rollup_boost::security", message = "builder quarantined", %builder, reason = failure.as_str(), cooldown_secs = self.cooldown.as_secs());
+        true
+    }
+
+    /// Whether `builder` is quarantined. A builder whose cooldown has elapsed is resumed.
+    pub fn is_quarantined(&self, builder: &str) -> bool {
+        self.is_quarantined_at(builder, Instant::now())
+    }
+
+    fn is_quarantined_at(&self, builder: &str, now: Instant) -> bool {
+        let mut builders = self.builders.lock();
+        let Some(state) = builders.get_mut(builder) else {
+            return false;
+        };
+
+        match state.quarantined_until {
+            Some(until) if now < until => true,
+            Some(_) => {
+                state.quarantined_until = None;
+                gauge!("builder.quarantine_active", "builder" => builder.to_string()).set(0);
+                info!(message = "builder quarantine ended", %builder);
+                false
+            }
+            None => false,
+        }
+    }
+
+    pub fn quarantined(&self) -> Vec<QuarantinedBuilder> {
+        let now = Instant::now();
+        self.builders
+            .lock()
+            .iter()
+            .filter_map(|(name, state)| {
+                let until = state.quarantined_until.filter(|until| now < *until)?;
+                Some(QuarantinedBuilder {
+                    name: name.clone(),
+                    remaining_secs: (until - now).as_secs(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// A builder client that refuses to build blocks while its builder is quarantined. It
+/// keeps forwarding fork choice updates without attributes and new payloads, so that
+/// the builder stays in sync and can resume building once the cooldown elapses.
+pub struct QuarantinedClient {
+    name: String,
+    inner: Arc<dyn EngineApiExt>,
+    quarantine: Arc<BuilderQuarantine>,
+}
+
+impl QuarantinedClient {
+    pub fn new(
+        name: String,
+        inner: Arc<dyn EngineApiExt>,
+        quarantine: Arc<BuilderQuarantine>,
+    ) -> Self {
+        Self {
+            name,
+            inner,
+            quarantine,
+        }
+    }
+
+    fn check(&self) -> ClientResult<()> {
+        if self.quarantine.is_quarantined(&self.name) {
+            return Err(RpcClientError::Quarantined(self.name.clone()));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EngineApiExt for QuarantinedClient {
+    async fn fork_choice_updated_v3(
+        &self,
+        fork_choice_state: ForkchoiceState,
+        payload_attributes: Option<OpPayloadAttributes>,
+    ) -> ClientResult<ForkchoiceUpdated> {
+        if payload_attributes.is_some() {
+            self.check()?;
+        }
+        self.inner
+            .fork_choice_updated_v3(fork_choice_state, payload_attributes)
+            .await
+    }
+
+    async fn new_payload(&self, new_payload: NewPayload) -> ClientResult<PayloadStatus> {
+        self.inner.new_payload(new_payload).await
+    }
+
+    async fn get_payload(
+        &self,
+        payload_id: PayloadId,
+        version: PayloadVersion,
+    ) -> ClientResult<OpExecutionPayloadEnvelope> {
+        self.check()?;
+        self.inner.get_payload(payload_id, version).await
+    }
+
+    fn last_payload_source(&self) -> BlockSource {
+        self.inner.last_payload_source()
+    }
+
+    fn last_payload_builder(&self) -> String {
+        self.name.clone()
+    }
+
+    fn last_payload_flashblocks(&self) -> usize {
+        self.inner.last_payload_flashblocks()
+    }
+
+    async fn get_payload_bodies_by_hash_v1(
+        &self,
+        block_hashes: Vec<B256>,
+    ) -> ClientResult<ExecutionPayloadBodiesV1> {
+        self.inner.get_payload_bodies_by_hash_v1(block_hashes).await
+    }
+
+    async fn get_payload_bodies_by_range_v1(
+        &self,
+        start: U64,
+        count: U64,
+    ) -> ClientResult<ExecutionPayloadBodiesV1> {
+        self.inner
+            .get_payload_bodies_by_range_v1(start, count)
+            .await
+    }
+
+    async fn exchange_capabilities(&self, capabilities: Vec<String>) -> ClientResult<Vec<String>> {
+        self.inner.exchange_capabilities(capabilities).await
+    }
+
+    async fn get_block_by_number(
+        &self,
+        number: BlockNumberOrTag,
+        full: bool,
+    ) -> ClientResult<Block> {
+        self.inner.get_block_by_number(number, full).await
+    }
+
+    async fn chain_id(&self) -> ClientResult<u64> {
+        self.inner.chain_id().await
+    }
+
+    async fn on_reorg(&self, event: &ReorgEvent) {
+        self.inner.on_reorg(event).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quarantine_after_threshold() {
+        let quarantine =
+            BuilderQuarantine::new(3, Duration::from_secs(10), Duration::from_secs(60));
+        let start = Instant::now();
+
+        // Failures outside of the window are forgotten
+        assert!(!quarantine.record_failure_at("a", ValidationFailure::BlockHash, start));
+        assert!(!quarantine.record_failure_at(
+            "a",
+            ValidationFailure::RejectedByL2,
+            start + Duration::from_secs(20)
+        ));
+        assert!(!quarantine.record_failure_at(
+            "a",
+            ValidationFailure::IndexSequence,
+            start + Duration::from_secs(21)
+        ));
+        assert!(!quarantine.is_quarantined_at("a", start + Duration::from_secs(21)));
+
+        assert!(quarantine.record_failure_at(
+            "a",
+            ValidationFailure::RejectedByL2,
+            start + Duration::from_secs(22)
+        ));
+        assert!(quarantine.is_quarantined_at("a", start + Duration::from_secs(30)));
+        assert!(!quarantine.is_quarantined_at("b", start + Duration::from_secs(30)));
+
+        // The builder resumes after the cooldown, with a clean slate
+        assert!(!quarantine.is_quarantined_at("a", start + Duration::from_secs(90)));
+        assert!(!quarantine.record_failure_at(
+            "a",
+            ValidationFailure::BlockHash,
+            start + Duration::from_secs(91)
+        ));
+    }
+}
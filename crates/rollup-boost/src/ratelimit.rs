@@ -0,0 +1,255 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use clap::Parser;
+use metrics::counter;
+use moka::future::Cache;
+use parking_lot::Mutex;
+
+/// Maximum number of source IPs whose token buckets are remembered
+const MAX_TRACKED_CLIENTS: u64 = 100_000;
+
+/// Duration after which the token buckets of an idle source IP are forgotten
+const CLIENT_IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Parser, Clone, Debug)]
+pub struct RateLimitArgs {
+    /// Maximum number of requests per second to the proxied and passthrough methods from
+    /// a single source IP, unlimited if not set. Engine API methods are never limited
+    #[arg(long, env)]
+    pub rpc_rate_limit: Option<u32>,
+
+    /// Number of requests a source IP can send at once before being rate limited,
+    /// defaults to one second worth of requests
+    #[arg(long, env)]
+    pub rpc_rate_limit_burst: Option<u32>,
+
+    /// Limits of specific methods per source IP, as `method=rate` or
+    /// `method=rate:burst`, e.g. `eth_call=50:100,eth_estimateGas=10`
+    #[arg(long, env, value_delimiter = ',')]
+    pub rpc_method_rate_limits: Vec<MethodRateLimit>,
+}
+
+/// Rate and burst of a token bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Tokens refilled per second
+    pub rate: u32,
+    /// Maximum number of tokens
+    pub burst: u32,
+}
+
+impl RateLimit {
+    pub fn new(rate: u32, burst: Option<u32>) -> Self {
+        Self {
+            rate,
+            burst: burst.unwrap_or(rate),
+        }
+    }
+}
+
+/// Rate limit of a method, parsed from `method=rate` or `method=rate:burst`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodRateLimit {
+    pub method: String,
+    pub limit: RateLimit,
+}
+
+impl FromStr for MethodRateLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid method rate limit {s}, expected method=rate[:burst]");
+        let (method, limit) = s.split_once('=').ok_or_else(invalid)?;
+        let (rate, burst) = match limit.split_once(':') {
+            Some((rate, burst)) => (rate, Some(burst.parse().map_err(|_| invalid())?)),
+            None => (limit, None),
+        };
+        let rate = rate.parse().map_err(|_| invalid())?;
+        if method.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            method: method.to_string(),
+            limit: RateLimit::new(rate, burst),
+        })
+    }
+}
+
+/// Token buckets of a source IP.
+#[derive(Debug)]
+struct ClientBuckets {
+    all: Option<TokenBucket>,
+    methods: HashMap<String, TokenBucket>,
+}
+
+/// Token bucket rate limiting of the non-engine requests of the proxy, per source IP
+/// and per method and source IP, so that a misbehaving client cannot overload the
+/// sequencer. Requests over the limit are answered with a `-32005` limit exceeded
+/// error.
+#[derive(Debug)]
+pub struct RpcRateLimiter {
+    limit: Option<RateLimit>,
+    method_limits: HashMap<String, RateLimit>,
+    clients: Cache<IpAddr, Arc<Mutex<ClientBuckets>>>,
+}
+
+impl RpcRateLimiter {
+    pub fn new(limit: Option<RateLimit>, method_limits: Vec<MethodRateLimit>) -> Self {
+        Self {
+            limit,
+            method_limits: method_limits
+                .into_iter()
+                .map(|limit| (limit.method, limit.limit))
+                .collect(),
+            clients: Cache::builder()
+                .max_capacity(MAX_TRACKED_CLIENTS)
+                .time_to_idle(CLIENT_IDLE_TIMEOUT)
+                .build(),
+        }
+    }
+
+    pub fn from_args(args: &RateLimitArgs) -> Option<Self> {
+        if args.rpc_rate_limit.is_none() && args.rpc_method_rate_limits.is_empty() {
+            return None;
+        }
+        Some(Self::new(
+            args.rpc_rate_limit
+                .map(|rate| RateLimit::new(rate, args.rpc_rate_limit_burst)),
+            args.rpc_method_rate_limits.clone(),
+        ))
+    }
+
+    /// Takes a token for a request of `method` from `ip`. Returns whether the request is
+    /// allowed.
+    pub async fn check(&self, ip: IpAddr, method: &str) -> bool {
+        let method_limit = self.method_limits.get(method);
+        if self.limit.is_none() && method_limit.is_none() {
+            return true;
+        }
+
+        let buckets = self
+            .clients
+            .get_with(ip, async {
+                Arc::new(Mutex::new(ClientBuckets {
+                    all: self.limit.map(TokenBucket::new),
+                    methods: HashMap::new(),
+                }))
+            })
+            .await;
+        let mut buckets = buckets.lock();
+
+        if let Some(limit) = method_limit {
+            let bucket = buckets
+                .methods
+                .entry(method.to_string())
+                .or_insert_with(|| TokenBucket::new(*limit));
+            if !bucket.try_acquire() {
+                counter!("rpc.rate_limited", "limit" => "method").increment(1);
+                return false;
+            }
+        }
+
+        if let Some(bucket) = &mut buckets.all {
+            if !bucket.try_acquire() {
+                counter!("rpc.rate_limited", "limit" => "ip").increment(1);
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Token bucket refilled continuously at `rate` tokens per second, holding at most
+/// `burst` tokens.
+#[derive(Debug)]
+pub(crate) struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            rate: limit.rate as f64,
+            burst: limit.burst as f64,
+            tokens: limit.burst as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    pub(crate) fn try_acquire(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_method_rate_limit() {
+        assert_eq!(
+            "eth_call=50:100".parse(),
+            Ok(MethodRateLimit {
+                method: "eth_call".to_string(),
+                limit: RateLimit {
+                    rate: 50,
+                    burst: 100
+                },
+            })
+        );
+        assert_eq!(
+            "eth_estimateGas=10"
+                .parse::<MethodRateLimit>()
+                .map(|l| l.limit),
+            Ok(RateLimit {
+                rate: 10,
+                burst: 10
+            })
+        );
+        assert!("eth_call".parse::<MethodRateLimit>().is_err());
+        assert!("eth_call=fast".parse::<MethodRateLimit>().is_err());
+        assert!("=10".parse::<MethodRateLimit>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_per_ip_and_method() {
+        let limiter = RpcRateLimiter::new(
+            Some(RateLimit::new(100, Some(3))),
+            vec!["eth_call=1:1".parse().unwrap()],
+        );
+        let a: IpAddr = "10.0.0.1".parse().unwrap();
+        let b: IpAddr = "10.0.0.2".parse().unwrap();
+
+        // The method limit applies on top of the limit of the source IP
+        assert!(limiter.check(a, "eth_call").await);
+        assert!(!limiter.check(a, "eth_call").await);
+        assert!(limiter.check(a, "eth_chainId").await);
+        assert!(limiter.check(a, "eth_chainId").await);
+        assert!(!limiter.check(a, "eth_chainId").await);
+
+        // Each source IP has its own buckets
+        assert!(limiter.check(b, "eth_call").await);
+        assert!(limiter.check(b, "eth_chainId").await);
+    }
+}
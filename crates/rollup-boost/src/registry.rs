@@ -0,0 +1,972 @@
+use std::{
+    cmp::Reverse,
+    collections::VecDeque,
+    fs,
+    io::{self, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use alloy_primitives::{B256, U64, U256, hex};
+use alloy_rpc_types_engine::{
+    ExecutionPayloadBodiesV1, ForkchoiceState, ForkchoiceUpdated, JwtError, JwtSecret, PayloadId,
+    PayloadStatus,
+};
+use alloy_rpc_types_eth::{Block, BlockNumberOrTag};
+use futures::{StreamExt, future::join_all, stream::FuturesUnordered};
+use http::{Uri, uri::InvalidUri};
+use jsonrpsee::core::async_trait;
+use op_alloy_rpc_types_engine::OpPayloadAttributes;
+use parking_lot::{Mutex, RwLock};
+use rustls::ClientConfig;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+
+use crate::{
+    BlockSource, BuilderQuarantine, ClientResult, EngineApiExt, NewPayload,
+    OpExecutionPayloadEnvelope, PayloadSource, PayloadVersion, QuarantinedClient, ReorgEvent,
+    RpcClient, RpcClientError, SharedJwtSecret,
+};
+
+/// Name of the builder configured on the command line.
+pub const DEFAULT_BUILDER: &str = "default";
+
+/// Number of payloads the payload IDs of the builders are remembered for.
+const MAX_TRACKED_PAYLOADS: usize = 32;
+
+#[derive(Error, Debug)]
+pub enum BuilderRegistryError {
+    #[error("builder {0} is already registered")]
+    AlreadyRegistered(String),
+    #[error("builder {0} is not registered")]
+    NotFound(String),
+    #[error(
+        "the default builder is configured on the command line and cannot be replaced or removed"
+    )]
+    DefaultBuilder,
+    #[error("builder {0} has neither a JWT secret nor a JWT secret file")]
+    MissingJwtSecret(String),
+    #[error("invalid builder url: {0}")]
+    InvalidUrl(#[from] InvalidUri),
+    #[error(transparent)]
+    Jwt(#[from] JwtError),
+    #[error(transparent)]
+    Client(#[from] RpcClientError),
+    #[error("failed to persist the builder registry: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid builder registry file: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// A builder endpoint registered at runtime through the debug API.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BuilderConfig {
+    pub name: String,
+    pub url: String,
+    /// Hex encoded JWT secret of the builder
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jwt_secret: Option<String>,
+    /// File to read the JWT secret of the builder from, if no secret is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jwt_path: Option<PathBuf>,
+    /// Builders with a higher priority are called first, and are the ones queried for
+    /// requests that are not fanned out to every builder
+    #[serde(default)]
+    pub priority: u32,
+    /// Breaks ties between payloads of the same block value, the highest weight wins
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// A registered builder as reported by `debug_listBuilders`, without its JWT secret.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BuilderInfo {
+    pub name: String,
+    pub url: String,
+    pub priority: u32,
+    pub weight: u32,
+    pub enabled: bool,
+    /// Whether the builder was registered at runtime, rather than on the command line
+    pub dynamic: bool,
+}
+
+/// Contents of the registry file, which survives restarts of rollup-boost.
+#[derive(Serialize, Deserialize, Debug)]
+struct RegistryFile {
+    #[serde(default = "default_enabled")]
+    default_enabled: bool,
+    #[serde(default)]
+    builders: Vec<BuilderConfig>,
+}
+
+#[derive(Clone)]
+struct RegisteredBuilder {
+    config: BuilderConfig,
+    client: Arc<dyn EngineApiExt>,
+    dynamic: bool,
+}
+
+/// The set of builders rollup-boost sends Engine API calls to.
+///
+/// The builder configured on the command line is always registered as `default`; more
+/// builders can be registered, disabled or removed at runtime without restarting. Fork
+/// choice updates are fanned out to every enabled builder and `get_payload` returns the
+/// most valuable payload any of them built.
+///
+/// Builders derive the payload ID from the attributes they were sent and may not agree on
+/// it, so the payload of each builder is requested with the payload ID it returned.
+pub struct BuilderRegistry {
+    builders: RwLock<Vec<RegisteredBuilder>>,
+    path: Option<PathBuf>,
+    timeout: u64,
+    tls: Option<ClientConfig>,
+    quarantine: Option<Arc<BuilderQuarantine>>,
+    get_payload_budget: Option<Duration>,
+    /// The payload ID returned by each builder for the last payloads, keyed by the payload
+    /// ID returned to the caller
+    payload_ids: Mutex<VecDeque<(PayloadId, Vec<(String, PayloadId)>)>>,
+    last_payload_source: Mutex<BlockSource>,
+    last_payload_builder: Mutex<String>,
+    last_payload_flashblocks: Mutex<usize>,
+}
+
+impl BuilderRegistry {
+    pub fn new(default_client: Arc<dyn EngineApiExt>, default_url: &Uri, timeout: u64) -> Self {
+        let default = RegisteredBuilder {
+            config: BuilderConfig {
+                name: DEFAULT_BUILDER.to_string(),
+                url: default_url.to_string(),
+                jwt_secret: None,
+                jwt_path: None,
+                priority: 0,
+                weight: default_weight(),
+                enabled: true,
+            },
+            client: default_client,
+            dynamic: false,
+        };
+
+        Self {
+            builders: RwLock::new(vec![default]),
+            path: None,
+            timeout,
+            tls: None,
+            quarantine: None,
+            get_payload_budget: None,
+            payload_ids: Mutex::new(VecDeque::new()),
+            last_payload_source: Mutex::new(BlockSource::Builder),
+            last_payload_builder: Mutex::new(DEFAULT_BUILDER.to_string()),
+            last_payload_flashblocks: Mutex::new(0),
+        }
+    }
+
+    /// Sets the priority and weight of the builder configured on the command line.
+    pub fn with_default_priority(self, priority: u32, weight: u32) -> Self {
+        {
+            let mut builders = self.builders.write();
+            if let Some(default) = builders.iter_mut().find(|b| !b.dynamic) {
+                default.config.priority = priority;
+                default.config.weight = weight;
+            }
+            sort_by_priority(&mut builders);
+        }
+        self
+    }
+
+    /// Sets the TLS configuration of the connections to runtime registered builders.
+    pub fn with_tls(mut self, tls: Option<ClientConfig>) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Quarantines runtime registered builders that return invalid payloads. The default
+    /// builder is expected to be wrapped in a [`QuarantinedClient`] already.
+    pub fn with_quarantine(mut self, quarantine: Option<Arc<BuilderQuarantine>>) -> Self {
+        self.quarantine = quarantine;
+        self
+    }
+
+    /// Stops waiting for the slower builders `budget` after `get_payload` was called, once
+    /// at least one payload was received.
+    pub fn with_get_payload_budget(mut self, budget: Option<Duration>) -> Self {
+        self.get_payload_budget = budget;
+        self
+    }
+
+    /// Persists the registry to `path`, restoring the builders registered by a previous
+    /// run if the file exists.
+    pub fn with_persistence(mut self, path: PathBuf) -> Result<Self, BuilderRegistryError> {
+        match fs::read(&path) {
+            Ok(contents) => {
+                let file: RegistryFile = serde_json::from_slice(&contents)?;
+                let mut builders = self.builders.write();
+                if let Some(default) = builders.iter_mut().find(|b| !b.dynamic) {
+                    default.config.enabled = file.default_enabled;
+                }
+                for config in file.builders {
+                    let builder = self.connect(config)?;
+                    builders.push(builder);
+                }
+                sort_by_priority(&mut builders);
+                info!(message = "restored builder registry", path = %path.display(), builders = builders.len());
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        self.path = Some(path);
+        Ok(self)
+    }
+
+    /// The registered builders, in the order requests are fanned out to them.
+    pub fn builders(&self) -> Vec<BuilderInfo> {
+        self.builders
+            .read()
+            .iter()
+            .map(|builder| BuilderInfo {
+                name: builder.config.name.clone(),
+                url: builder.config.url.clone(),
+                priority: builder.config.priority,
+                weight: builder.config.weight,
+                enabled: builder.config.enabled,
+                dynamic: builder.dynamic,
+            })
+            .collect()
+    }
+
+    pub fn register(&self, config: BuilderConfig) -> Result<(), BuilderRegistryError> {
+        if config.name == DEFAULT_BUILDER {
+            return Err(BuilderRegistryError::DefaultBuilder);
+        }
+
+        let builder = self.connect(config)?;
+        self.update(|builders| {
+            if builders
+                .iter()
+                .any(|b| b.config.name == builder.config.name)
+            {
+                return Err(BuilderRegistryError::AlreadyRegistered(
+                    builder.config.name.clone(),
+                ));
+            }
+            builders.push(builder);
+            Ok(())
+        })
+    }
+
+    pub fn set_enabled(&self, name: &str, enabled: bool) -> Result<(), BuilderRegistryError> {
+        self.update(|builders| {
+            let builder = builders
+                .iter_mut()
+                .find(|b| b.config.name == name)
+                .ok_or_else(|| BuilderRegistryError::NotFound(name.to_string()))?;
+            builder.config.enabled = enabled;
+            Ok(())
+        })
+    }
+
+    pub fn remove(&self, name: &str) -> Result<(), BuilderRegistryError> {
+        if name == DEFAULT_BUILDER {
+            return Err(BuilderRegistryError::DefaultBuilder);
+        }
+
+        self.update(|builders| {
+            let index = builders
+                .iter()
+                .position(|b| b.config.name == name)
+                .ok_or_else(|| BuilderRegistryError::NotFound(name.to_string()))?;
+            builders.remove(index);
+            Ok(())
+        })
+    }
+
+    /// Applies a change to the builders, and persists it before making it visible.
+    fn update(
+        &self,
+        f: impl FnOnce(&mut Vec<RegisteredBuilder>) -> Result<(), BuilderRegistryError>,
+    ) -> Result<(), BuilderRegistryError> {
+        let mut builders = self.builders.write();
+        let mut updated = builders.clone();
+        f(&mut updated)?;
+        sort_by_priority(&mut updated);
+
+        if let Some(path) = &self.path {
+            persist(path, &updated)?;
+        }
+        *builders = updated;
+        Ok(())
+    }
+
+    fn connect(&self, config: BuilderConfig) -> Result<RegisteredBuilder, BuilderRegistryError> {
+        let secret = match (&config.jwt_secret, &config.jwt_path) {
+            (Some(secret), _) => SharedJwtSecret::new(JwtSecret::from_hex(secret)?),
+            (None, Some(path)) => SharedJwtSecret::from_file(path)?,
+            (None, None) => return Err(BuilderRegistryError::MissingJwtSecret(config.name)),
+        };
+
+        let client = RpcClient::new_with_tls(
+            config.url.parse::<Uri>()?,
+            secret,
+            self.timeout,
+            PayloadSource::Builder,
+            self.tls.clone(),
+        )?;
+
+        let client: Arc<dyn EngineApiExt> = match &self.quarantine {
+            Some(quarantine) => Arc::new(QuarantinedClient::new(
+                config.name.clone(),
+                Arc::new(client),
+                quarantine.clone(),
+            )),
+            None => Arc::new(client),
+        };
+
+        Ok(RegisteredBuilder {
+            config,
+            client,
+            dynamic: true,
+        })
+    }
+
+    /// The enabled builders, by decreasing priority.
+    fn enabled(&self) -> Vec<RegisteredBuilder> {
+        self.builders
+            .read()
+            .iter()
+            .filter(|b| b.config.enabled)
+            .cloned()
+            .collect()
+    }
+
+    /// Remembers the payload ID each builder returned for the payload `payload_id`.
+    fn track_payload_ids(&self, payload_id: PayloadId, builder_ids: Vec<(String, PayloadId)>) {
+        let mut payload_ids = self.payload_ids.lock();
+        payload_ids.retain(|(id, _)| *id != payload_id);
+        if payload_ids.len() == MAX_TRACKED_PAYLOADS {
+            payload_ids.pop_front();
+        }
+        payload_ids.push_back((payload_id, builder_ids));
+    }
+
+    /// The payload ID each builder returned for the payload `payload_id`, if known.
+    fn builder_payload_ids(&self, payload_id: PayloadId) -> Option<Vec<(String, PayloadId)>> {
+        self.payload_ids
+            .lock()
+            .iter()
+            .find(|(id, _)| *id == payload_id)
+            .map(|(_, builder_ids)| builder_ids.clone())
+    }
+
+    fn primary(&self) -> ClientResult<Arc<dyn EngineApiExt>> {
+        self.enabled()
+            .into_iter()
+            .next()
+            .map(|b| b.client)
+            .ok_or(RpcClientError::NoBuilderEnabled)
+    }
+}
+
+/// Orders builders by decreasing priority then weight, keeping the registration order of
+/// builders that are otherwise equal.
+fn sort_by_priority(builders: &mut [RegisteredBuilder]) {
+    builders.sort_by_key(|b| Reverse((b.config.priority, b.config.weight)));
+}
+
+/// Picks the payload with the highest block value. Ties are broken by the weight of the
+/// builders, then by their priority.
+fn select_best<T>(payloads: impl IntoIterator<Item = (T, U256, u32)>) -> Option<T> {
+    let mut best: Option<(T, U256, u32)> = None;
+    for (item, value, weight) in payloads {
+        if best.as_ref().is_none_or(|(_, best_value, best_weight)| {
+            (value, weight) > (*best_value, *best_weight)
+        }) {
+            best = Some((item, value, weight));
+        }
+    }
+    best.map(|(item, _, _)| item)
+}
+
+/// Writes the registry file, readable by the owner only since it may hold JWT secrets.
+fn persist(path: &Path, builders: &[RegisteredBuilder]) -> Result<(), BuilderRegistryError> {
+    let file = RegistryFile {
+        default_enabled: builders
+            .iter()
+            .find(|b| !b.dynamic)
+            .is_none_or(|b| b.config.enabled),
+        builders: builders
+            .iter()
+            .filter(|b| b.dynamic)
+            .map(|b| b.config.clone())
+            .collect(),
+    };
+
+    // Write to a temporary file first so that a crash never leaves a truncated registry
+    let tmp = path.with_extension("tmp");
+    let mut out = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp)?;
+    out.write_all(&serde_json::to_vec_pretty(&file)?)?;
+    out.sync_all()?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Returns the first successful response, or the last error if every builder failed.
+fn first_ok<T>(
+    method: &str,
+    builders: &[RegisteredBuilder],
+    results: Vec<ClientResult<T>>,
+) -> ClientResult<T> {
+    let mut last_err = RpcClientError::NoBuilderEnabled;
+    let mut ok = None;
+    for (builder, result) in builders.iter().zip(results) {
+        match result {
+            Ok(res) if ok.is_none() => ok = Some(res),
+            Ok(_) => {}
+            Err(e) => {
+                warn!(message = "builder request failed", method, builder = %builder.config.name, error = %e);
+                last_err = e;
+            }
+        }
+    }
+    ok.ok_or(last_err)
+}
+
+#[async_trait]
+impl EngineApiExt for BuilderRegistry {
+    async fn fork_choice_updated_v3(
+        &self,
+        fork_choice_state: ForkchoiceState,
+        payload_attributes: Option<OpPayloadAttributes>,
+    ) -> ClientResult<ForkchoiceUpdated> {
+        let builders = self.enabled();
+        let results = join_all(builders.iter().map(|b| {
+            b.client
+                .fork_choice_updated_v3(fork_choice_state, payload_attributes.clone())
+        }))
+        .await;
+
+        let builder_ids: Vec<_> = builders
+            .iter()
+            .zip(&results)
+            .filter_map(|(builder, result)| {
+                let payload_id = result.as_ref().ok()?.payload_id?;
+                Some((builder.config.name.clone(), payload_id))
+            })
+            .collect();
+        let response = first_ok("engine_forkchoiceUpdatedV3", &builders, results)?;
+        if let Some(payload_id) = response.payload_id {
+            self.track_payload_ids(payload_id, builder_ids);
+        }
+        Ok(response)
+    }
+
+    async fn new_payload(&self, new_payload: NewPayload) -> ClientResult<PayloadStatus> {
+        let builders = self.enabled();
+        let results = join_all(
+            builders
+                .iter()
+                .map(|b| b.client.new_payload(new_payload.clone())),
+        )
+        .await;
+        first_ok("engine_newPayload", &builders, results)
+    }
+
+    async fn get_payload(
+        &self,
+        payload_id: PayloadId,
+        version: PayloadVersion,
+    ) -> ClientResult<OpExecutionPayloadEnvelope> {
+        let builders = self.enabled();
+        let builder_ids = self.builder_payload_ids(payload_id);
+        let started = Instant::now();
+        let mut pending: FuturesUnordered<_> = builders
+            .iter()
+            .enumerate()
+            .filter_map(|(index, builder)| {
+                // Builders that did not start building the payload have none to return
+                let builder_payload_id = match &builder_ids {
+                    Some(ids) => ids
+                        .iter()
+                        .find(|(name, _)| *name == builder.config.name)
+                        .map(|(_, id)| *id)?,
+                    None => payload_id,
+                };
+                Some(async move {
+                    let result = builder
+                        .client
+                        .get_payload(builder_payload_id, version)
+                        .await;
+                    (index, builder, result)
+                })
+            })
+            .collect();
+
+        let mut payloads = vec![];
+        let mut last_err = RpcClientError::NoBuilderEnabled;
+        loop {
+            // Once the latency budget elapsed, the best payload received so far is returned
+            // without waiting for the slower builders
+            let next = match self.get_payload_budget {
+                Some(budget) if !payloads.is_empty() => {
+                    match tokio::time::timeout_at(started + budget, pending.next()).await {
+                        Ok(next) => next,
+                        Err(_) => {
+                            debug!(
+                                message = "latency budget elapsed, skipping the slower builders",
+                                pending = pending.len()
+                            );
+                            break;
+                        }
+                    }
+                }
+                _ => pending.next().await,
+            };
+            let Some((index, builder, result)) = next else {
+                break;
+            };
+            match result {
+                Ok(payload) => {
+                    let value = payload.block_value();
+                    payloads.push(((index, builder, payload), value, builder.config.weight));
+                }
+                Err(e) => {
+                    warn!(message = "builder request failed", method = "engine_getPayload", builder = %builder.config.name, error = %e);
+                    last_err = e;
+                }
+            }
+        }
+
+        // Ties are broken by priority, whatever the order the payloads were received in
+        payloads.sort_by_key(|((index, _, _), _, _)| *index);
+        let (_, builder, payload) = select_best(payloads).ok_or(last_err)?;
+        debug!(message = "selected builder payload", builder = %builder.config.name, block_value = %payload.block_value());
+        *self.last_payload_source.lock() = builder.client.last_payload_source();
+        *self.last_payload_builder.lock() = builder.config.name.clone();
+        *self.last_payload_flashblocks.lock() = builder.client.last_payload_flashblocks();
+        Ok(payload)
+    }
+
+    fn last_payload_source(&self) -> BlockSource {
+        *self.last_payload_source.lock()
+    }
+
+    fn last_payload_builder(&self) -> String {
+        self.last_payload_builder.lock().clone()
+    }
+
+    fn last_payload_flashblocks(&self) -> usize {
+        *self.last_payload_flashblocks.lock()
+    }
+
+    async fn get_payload_bodies_by_hash_v1(
+        &self,
+        block_hashes: Vec<B256>,
+    ) -> ClientResult<ExecutionPayloadBodiesV1> {
+        self.primary()?
+            .get_payload_bodies_by_hash_v1(block_hashes)
+            .await
+    }
+
+    async fn get_payload_bodies_by_range_v1(
+        &self,
+        start: U64,
+        count: U64,
+    ) -> ClientResult<ExecutionPayloadBodiesV1> {
+        self.primary()?
+            .get_payload_bodies_by_range_v1(start, count)
+            .await
+    }
+
+    async fn exchange_capabilities(&self, capabilities: Vec<String>) -> ClientResult<Vec<String>> {
+        self.primary()?.exchange_capabilities(capabilities).await
+    }
+
+    async fn get_block_by_number(
+        &self,
+        number: BlockNumberOrTag,
+        full: bool,
+    ) -> ClientResult<Block> {
+        self.primary()?.get_block_by_number(number, full).await
+    }
+
+    /// The chain ID of the enabled builders, which must all be on the same chain.
+    async fn chain_id(&self) -> ClientResult<u64> {
+        let builders = self.enabled();
+        let results = join_all(builders.iter().map(|b| b.client.chain_id())).await;
+
+        let mut chain_id = None;
+        let mut last_err = RpcClientError::NoBuilderEnabled;
+        for (builder, result) in builders.iter().zip(results) {
+            match (result, chain_id) {
+                (Ok(actual), Some(expected)) if actual != expected => {
+                    return Err(RpcClientError::ChainIdMismatch {
+                        builder: builder.config.name.clone(),
+                        expected,
+                        actual,
+                    });
+                }
+                (Ok(actual), _) => chain_id = Some(actual),
+                (Err(e), _) => {
+                    warn!(message = "builder request failed", method = "eth_chainId", builder = %builder.config.name, error = %e);
+                    last_err = e;
+                }
+            }
+        }
+        chain_id.ok_or(last_err)
+    }
+
+    async fn on_reorg(&self, event: &ReorgEvent) {
+        let clients: Vec<_> = self
+            .builders
+            .read()
+            .iter()
+            .map(|b| b.client.clone())
+            .collect();
+        for client in clients {
+            client.on_reorg(event).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_rpc_types_engine::{
+        BlobsBundleV1, ExecutionPayloadV1, ExecutionPayloadV2, ExecutionPayloadV3,
+        PayloadStatusEnum,
+    };
+    use op_alloy_rpc_types_engine::OpExecutionPayloadEnvelopeV3;
+
+    /// A builder returning a payload of block value `value` for the payload ID
+    /// `payload_id` only, `delay` after it is asked for it.
+    struct MockBuilder {
+        payload_id: PayloadId,
+        value: u64,
+        delay: Duration,
+        requested: Mutex<Vec<PayloadId>>,
+    }
+
+    impl MockBuilder {
+        fn new(payload_id: u8, value: u64, delay: Duration) -> Arc<Self> {
+            Arc::new(Self {
+                payload_id: PayloadId::new([payload_id; 8]),
+                value,
+                delay,
+                requested: Mutex::new(vec![]),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl EngineApiExt for MockBuilder {
+        async fn fork_choice_updated_v3(
+            &self,
+            _fork_choice_state: ForkchoiceState,
+            _payload_attributes: Option<OpPayloadAttributes>,
+        ) -> ClientResult<ForkchoiceUpdated> {
+            Ok(
+                ForkchoiceUpdated::new(PayloadStatus::from_status(PayloadStatusEnum::Valid))
+                    .with_payload_id(self.payload_id),
+            )
+        }
+
+        async fn new_payload(&self, _new_payload: NewPayload) -> ClientResult<PayloadStatus> {
+            unimplemented!()
+        }
+
+        async fn get_payload(
+            &self,
+            payload_id: PayloadId,
+            _version: PayloadVersion,
+        ) -> ClientResult<OpExecutionPayloadEnvelope> {
+            self.requested.lock().push(payload_id);
+            tokio::time::sleep(self.delay).await;
+            if payload_id != self.payload_id {
+                return Err(RpcClientError::NoBuilderEnabled);
+            }
+            Ok(OpExecutionPayloadEnvelope::V3(
+                OpExecutionPayloadEnvelopeV3 {
+                    execution_payload: ExecutionPayloadV3 {
+                        payload_inner: ExecutionPayloadV2 {
+                            payload_inner: ExecutionPayloadV1 {
+                                parent_hash: B256::ZERO,
+                                fee_recipient: Default::default(),
+                                state_root: B256::ZERO,
+                                receipts_root: B256::ZERO,
+                                logs_bloom: Default::default(),
+                                prev_randao: B256::ZERO,
+                                block_number: 1,
+                                gas_limit: 30_000_000,
+                                gas_used: 0,
+                                timestamp: 1,
+                                extra_data: Default::default(),
+                                base_fee_per_gas: U256::from(1),
+                                block_hash: B256::ZERO,
+                                transactions: vec![],
+                            },
+                            withdrawals: vec![],
+                        },
+                        blob_gas_used: 0,
+                        excess_blob_gas: 0,
+                    },
+                    block_value: U256::from(self.value),
+                    blobs_bundle: BlobsBundleV1 {
+                        commitments: vec![],
+                        proofs: vec![],
+                        blobs: vec![],
+                    },
+                    should_override_builder: false,
+                    parent_beacon_block_root: B256::ZERO,
+                },
+            ))
+        }
+
+        fn last_payload_source(&self) -> BlockSource {
+            BlockSource::Builder
+        }
+
+        async fn get_payload_bodies_by_hash_v1(
+            &self,
+            _block_hashes: Vec<B256>,
+        ) -> ClientResult<ExecutionPayloadBodiesV1> {
+            unimplemented!()
+        }
+
+        async fn get_payload_bodies_by_range_v1(
+            &self,
+            _start: U64,
+            _count: U64,
+        ) -> ClientResult<ExecutionPayloadBodiesV1> {
+            unimplemented!()
+        }
+
+        async fn exchange_capabilities(
+            &self,
+            _capabilities: Vec<String>,
+        ) -> ClientResult<Vec<String>> {
+            unimplemented!()
+        }
+
+        async fn get_block_by_number(
+            &self,
+            _number: BlockNumberOrTag,
+            _full: bool,
+        ) -> ClientResult<Block> {
+            unimplemented!()
+        }
+
+        async fn chain_id(&self) -> ClientResult<u64> {
+            unimplemented!()
+        }
+    }
+
+    /// A registry with the mock builders `builders` only, by decreasing priority.
+    fn mock_registry(builders: &[(&str, Arc<MockBuilder>)]) -> BuilderRegistry {
+        let registry = registry();
+        registry.set_enabled(DEFAULT_BUILDER, false).unwrap();
+        {
+            let mut registered = registry.builders.write();
+            for (index, (name, builder)) in builders.iter().enumerate() {
+                registered.push(RegisteredBuilder {
+                    config: BuilderConfig {
+                        priority: (builders.len() - index) as u32,
+                        ..config(name)
+                    },
+                    client: builder.clone(),
+                    dynamic: true,
+                });
+            }
+            sort_by_priority(&mut registered);
+        }
+        registry
+    }
+
+    fn registry() -> BuilderRegistry {
+        let url = Uri::from_static("http://127.0.0.1:8551");
+        let client = RpcClient::new(
+            url.clone(),
+            JwtSecret::random(),
+            1000,
+            PayloadSource::Builder,
+        )
+        .unwrap();
+        BuilderRegistry::new(Arc::new(client), &url, 1000)
+    }
+
+    fn config(name: &str) -> BuilderConfig {
+        BuilderConfig {
+            name: name.to_string(),
+            url: "http://127.0.0.1:9551".to_string(),
+            jwt_secret: Some(hex::encode(JwtSecret::random().as_bytes())),
+            jwt_path: None,
+            priority: 0,
+            weight: 2,
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_register_and_remove() {
+        let registry = registry();
+        registry.register(config("backup")).unwrap();
+
+        assert!(matches!(
+            registry.register(config("backup")),
+            Err(BuilderRegistryError::AlreadyRegistered(_))
+        ));
+        assert!(matches!(
+            registry.register(config(DEFAULT_BUILDER)),
+            Err(BuilderRegistryError::DefaultBuilder)
+        ));
+        assert!(matches!(
+            registry.register(BuilderConfig {
+                jwt_secret: None,
+                ..config("nojwt")
+            }),
+            Err(BuilderRegistryError::MissingJwtSecret(_))
+        ));
+
+        registry.set_enabled(DEFAULT_BUILDER, false).unwrap();
+        assert_eq!(registry.enabled().len(), 1);
+
+        assert!(matches!(
+            registry.remove(DEFAULT_BUILDER),
+            Err(BuilderRegistryError::DefaultBuilder)
+        ));
+        registry.remove("backup").unwrap();
+        assert!(registry.enabled().is_empty());
+        assert!(matches!(
+            registry.primary(),
+            Err(RpcClientError::NoBuilderEnabled)
+        ));
+    }
+
+    #[test]
+    fn test_priority_order() {
+        let registry = registry().with_default_priority(1, 1);
+        registry.register(config("heavy")).unwrap();
+        registry
+            .register(BuilderConfig {
+                priority: 2,
+                weight: 1,
+                ..config("first")
+            })
+            .unwrap();
+        registry
+            .register(BuilderConfig {
+                weight: 1,
+                ..config("light")
+            })
+            .unwrap();
+
+        let names: Vec<_> = registry.builders().into_iter().map(|b| b.name).collect();
+        assert_eq!(names, ["first", DEFAULT_BUILDER, "heavy", "light"]);
+    }
+
+    #[test]
+    fn test_select_best() {
+        let value = |v: u64| U256::from(v);
+
+        // The highest block value wins regardless of weight
+        assert_eq!(
+            select_best([("a", value(1), 10), ("b", value(2), 1)]),
+            Some("b")
+        );
+        // Ties are broken by weight, then by priority order
+        assert_eq!(
+            select_best([("a", value(2), 1), ("b", value(2), 3)]),
+            Some("b")
+        );
+        assert_eq!(
+            select_best([("a", value(2), 1), ("b", value(2), 1)]),
+            Some("a")
+        );
+        assert_eq!(select_best::<&str>([]), None);
+    }
+
+    #[tokio::test]
+    async fn test_get_payload_with_builder_payload_ids() -> eyre::Result<()> {
+        let first = MockBuilder::new(1, 1, Duration::ZERO);
+        let second = MockBuilder::new(2, 2, Duration::from_millis(50));
+        let registry = mock_registry(&[("first", first.clone()), ("second", second.clone())]);
+
+        // The payload ID of the first builder is returned to the caller
+        let response = registry
+            .fork_choice_updated_v3(ForkchoiceState::default(), None)
+            .await?;
+        assert_eq!(response.payload_id, Some(PayloadId::new([1; 8])));
+
+        // Each builder is asked for its payload with the payload ID it returned
+        let payload = registry
+            .get_payload(PayloadId::new([1; 8]), PayloadVersion::V3)
+            .await?;
+        assert_eq!(payload.block_value(), U256::from(2));
+        assert_eq!(registry.last_payload_builder(), "second");
+        assert_eq!(*first.requested.lock(), [PayloadId::new([1; 8])]);
+        assert_eq!(*second.requested.lock(), [PayloadId::new([2; 8])]);
+
+        // Builders asked for a payload they do not know fail
+        assert!(
+            registry
+                .get_payload(PayloadId::new([9; 8]), PayloadVersion::V3)
+                .await
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_payload_budget() -> eyre::Result<()> {
+        let fast = MockBuilder::new(1, 1, Duration::ZERO);
+        let slow = MockBuilder::new(1, 2, Duration::from_secs(5));
+        let registry = mock_registry(&[("slow", slow), ("fast", fast)])
+            .with_get_payload_budget(Some(Duration::from_millis(100)));
+
+        // The slow builder is not waited for past the budget
+        let started = Instant::now();
+        let payload = registry
+            .get_payload(PayloadId::new([1; 8]), PayloadVersion::V3)
+            .await?;
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(payload.block_value(), U256::from(1));
+        assert_eq!(registry.last_payload_builder(), "fast");
+        Ok(())
+    }
+
+    #[test]
+    fn test_persistence() -> eyre::Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("rollup-boost-builders-{}.json", std::process::id()));
+
+        let registry = registry().with_persistence(path.clone())?;
+        registry.register(config("backup"))?;
+        registry.set_enabled(DEFAULT_BUILDER, false)?;
+
+        let restored = self::registry().with_persistence(path.clone())?;
+        assert_eq!(restored.builders(), registry.builders());
+        assert_eq!(restored.builders()[0].name, "backup");
+        assert_eq!(restored.builders()[0].weight, 2);
+        assert!(!restored.builders()[1].enabled);
+
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,553 @@
+use std::{collections::HashMap, time::Duration};
+
+use alloy_primitives::{Address, B64, B256, Signature, U64, U256, bytes::Bytes, keccak256};
+use alloy_rpc_types_engine::{
+    BlobsBundleV1, ExecutionPayloadBodiesV1, ExecutionPayloadV3, ForkchoiceState,
+    ForkchoiceUpdated, PayloadId, PayloadStatus, PayloadStatusEnum,
+};
+use alloy_rpc_types_eth::{Block, BlockNumberOrTag};
+use clap::Parser;
+use http::{Method, Request, StatusCode, header::CONTENT_TYPE};
+use http_body_util::{BodyExt, Full};
+use hyper_rustls::HttpsConnector;
+use hyper_util::{
+    client::legacy::{Client, connect::HttpConnector},
+    rt::TokioExecutor,
+};
+use jsonrpsee::core::async_trait;
+use jsonrpsee::types::error::METHOD_NOT_FOUND_CODE;
+use metrics::counter;
+use op_alloy_rpc_types_engine::{
+    OpExecutionPayloadEnvelopeV3, OpExecutionPayloadEnvelopeV4, OpExecutionPayloadV4,
+    OpPayloadAttributes,
+};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tracing::{info, warn};
+use url::Url;
+
+use crate::{
+    BlockSource, ClientResult, EngineApiError, EngineApiExt, NewPayload,
+    OpExecutionPayloadEnvelope, PayloadVersion, RpcClientError, SERVER_ERROR_CODE,
+    UNKNOWN_PAYLOAD_CODE,
+};
+
+/// Number of payloads built by the relay that can be fetched at the same time.
+const MAX_PENDING_PAYLOADS: usize = 16;
+
+#[derive(Parser, Clone, Debug)]
+pub struct RelayArgs {
+    /// Source the builder blocks from the builder-API (MEV-Boost style) relay at this URL,
+    /// instead of the Engine API of the builder
+    #[arg(
+        long,
+        env,
+        conflicts_with = "flashblocks",
+        requires = "builder_relay_signer"
+    )]
+    pub builder_relay_url: Option<Url>,
+
+    /// Address of the key signing the bids of the relay. Bids signed by another key are
+    /// rejected before the payload is revealed
+    #[arg(long, env)]
+    pub builder_relay_signer: Option<Address>,
+
+    /// Timeout of the requests to the relay in milliseconds
+    #[arg(long, env, default_value = "1000")]
+    pub builder_relay_timeout: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum RelayError {
+    #[error("relay request failed: {0}")]
+    Http(String),
+    #[error("relay request timed out")]
+    Timeout,
+    #[error("relay returned status {status}: {body}")]
+    Status { status: StatusCode, body: String },
+    #[error("invalid relay response: {0}")]
+    InvalidResponse(String),
+    #[error("relay has no bid for payload {0}")]
+    NoBid(PayloadId),
+    #[error("unknown payload {0}")]
+    UnknownPayload(PayloadId),
+    #[error("invalid bid signature: {0}")]
+    InvalidSignature(String),
+    #[error("relay returned block {actual}, its bid was for block {expected}")]
+    BlockHashMismatch { expected: B256, actual: B256 },
+    #[error("{0} is not supported by relays")]
+    Unsupported(&'static str),
+}
+
+impl EngineApiError for RelayError {
+    fn code(&self) -> i32 {
+        match self {
+            RelayError::UnknownPayload(_) => UNKNOWN_PAYLOAD_CODE,
+            RelayError::Unsupported(_) => METHOD_NOT_FOUND_CODE,
+            _ => SERVER_ERROR_CODE,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            RelayError::Http(_) => "relay_transport",
+            RelayError::Timeout => "relay_timeout",
+            RelayError::Status { .. } => "relay_status",
+            RelayError::InvalidResponse(_) => "relay_invalid_response",
+            RelayError::NoBid(_) => "relay_no_bid",
+            RelayError::InvalidSignature(_) => "relay_invalid_signature",
+            RelayError::UnknownPayload(_) => "unknown_payload",
+            RelayError::BlockHashMismatch { .. } => "relay_block_hash_mismatch",
+            RelayError::Unsupported(_) => "unsupported",
+        }
+    }
+}
+
+/// Attributes sent to the relay on each fork choice update with attributes, so that its
+/// builders can start building.
+#[derive(Serialize, Debug, Clone)]
+struct RelayPayloadAttributes {
+    parent_hash: B256,
+    #[serde(flatten)]
+    attributes: OpPayloadAttributes,
+}
+
+/// A bid of the relay, returned by `getHeader`.
+#[derive(Deserialize, Debug, Clone)]
+struct SignedBid {
+    data: SignedBidData,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct SignedBidData {
+    message: BidMessage,
+    /// Recoverable secp256k1 signature of [`BidMessage::signing_hash`] by the relay
+    signature: alloy_primitives::Bytes,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct BidMessage {
+    header: Value,
+    value: U256,
+}
+
+impl BidMessage {
+    /// Hash signed by the relay for a bid of `value` for block `block_hash`:
+    /// `keccak256(block_hash ++ value)`, the value being 32 bytes big-endian.
+    fn signing_hash(block_hash: B256, value: U256) -> B256 {
+        keccak256([block_hash.as_slice(), &value.to_be_bytes::<32>()].concat())
+    }
+}
+
+/// Payload ID of the block built by the relay on `parent_hash` at `timestamp`. Repeated
+/// fork choice updates for the same block get the same payload ID.
+fn relay_payload_id(parent_hash: B256, timestamp: u64) -> PayloadId {
+    let hash = keccak256([parent_hash.as_slice(), &timestamp.to_be_bytes()].concat());
+    PayloadId(B64::from_slice(&hash[..8]))
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct UnblindedPayload {
+    data: Value,
+}
+
+/// A payload announced to the relay, waiting to be fetched.
+#[derive(Debug, Clone)]
+struct PendingPayload {
+    parent_hash: B256,
+    timestamp: u64,
+    parent_beacon_block_root: B256,
+}
+
+/// A builder client sourcing the blocks from a builder-API relay, translating the Engine
+/// API flow of rollup-boost into the `getHeader` and `getPayload` flow of MEV-Boost:
+///
+/// - `engine_forkchoiceUpdatedV3` with attributes assigns a payload ID and posts the
+///   attributes to `/eth/v1/builder/payload_attributes`.
+/// - `engine_getPayload` requests the best bid for the payload with
+///   `GET /eth/v1/builder/header/{timestamp}/{parent_hash}`, checks the bid is signed by
+///   the relay, then reveals the payload with `POST /eth/v1/builder/blinded_blocks` and
+///   checks it matches the bid.
+///
+/// Rollups have no slots or proposer keys: blocks are identified by their timestamp and
+/// the blinded block is the header of the bid, unsigned.
+pub struct RelayClient {
+    url: Url,
+    signer: Address,
+    timeout: Duration,
+    client: Client<HttpsConnector<HttpConnector>, Full<Bytes>>,
+    pending: Mutex<HashMap<PayloadId, PendingPayload>>,
+}
+
+impl RelayClient {
+    pub fn new(url: Url, signer: Address, timeout: Duration) -> Result<Self, RelayError> {
+        let connector = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .map_err(|e| RelayError::Http(e.to_string()))?
+            .https_or_http()
+            .enable_http1()
+            .build();
+        Ok(Self {
+            url,
+            signer,
+            timeout,
+            client: Client::builder(TokioExecutor::new()).build(connector),
+            pending: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn from_args(args: &RelayArgs) -> Result<Option<Self>, RelayError> {
+        let timeout = Duration::from_millis(args.builder_relay_timeout);
+        args.builder_relay_url
+            .clone()
+            .zip(args.builder_relay_signer)
+            .map(|(url, signer)| Self::new(url, signer, timeout))
+            .transpose()
+    }
+
+    /// Sends a request to the relay, returning the body of a successful response, or
+    /// `None` if the relay answered with no content.
+    async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Vec<u8>>,
+    ) -> Result<Option<Bytes>, RelayError> {
+        let url = self
+            .url
+            .join(path)
+            .map_err(|e| RelayError::Http(e.to_string()))?;
+        let request = Request::builder()
+            .method(method)
+            .uri(url.as_str())
+            .header(CONTENT_TYPE, "application/json")
+            .body(Full::new(Bytes::from(body.unwrap_or_default())))
+            .map_err(|e| RelayError::Http(e.to_string()))?;
+
+        let response = tokio::time::timeout(self.timeout, self.client.request(request))
+            .await
+            .map_err(|_| RelayError::Timeout)?
+            .map_err(|e| RelayError::Http(e.to_string()))?;
+        let status = response.status();
+        let body = tokio::time::timeout(self.timeout, response.into_body().collect())
+            .await
+            .map_err(|_| RelayError::Timeout)?
+            .map_err(|e| RelayError::Http(e.to_string()))?
+            .to_bytes();
+
+        match status {
+            StatusCode::NO_CONTENT => Ok(None),
+            status if status.is_success() => Ok(Some(body)),
+            status => Err(RelayError::Status {
+                status,
+                body: String::from_utf8_lossy(&body).into_owned(),
+            }),
+        }
+    }
+
+    async fn fetch_payload(
+        &self,
+        payload_id: PayloadId,
+        version: PayloadVersion,
+    ) -> Result<OpExecutionPayloadEnvelope, RelayError> {
+        let pending = self
+            .pending
+            .lock()
+            .get(&payload_id)
+            .cloned()
+            .ok_or(RelayError::UnknownPayload(payload_id))?;
+
+        let path = format!(
+            "eth/v1/builder/header/{}/{}",
+            pending.timestamp, pending.parent_hash
+        );
+        let bid = self
+            .request(Method::GET, &path, None)
+            .await?
+            .ok_or(RelayError::NoBid(payload_id))?;
+        let bid: SignedBid =
+            serde_json::from_slice(&bid).map_err(|e| RelayError::InvalidResponse(e.to_string()))?;
+        let signature = bid.data.signature;
+        let bid = bid.data.message;
+        let bid_block_hash = hash_field(&bid.header, "blockHash")?;
+        self.verify_bid(bid_block_hash, bid.value, &signature)?;
+        if hash_field(&bid.header, "parentHash")? != pending.parent_hash {
+            return Err(RelayError::InvalidResponse(format!(
+                "the bid of block {bid_block_hash} is not built on {}",
+                pending.parent_hash
+            )));
+        }
+
+        let blinded_block = serde_json::json!({ "message": { "header": bid.header } });
+        let payload = self
+            .request(
+                Method::POST,
+                "eth/v1/builder/blinded_blocks",
+                Some(blinded_block.to_string().into_bytes()),
+            )
+            .await?
+            .ok_or_else(|| RelayError::InvalidResponse("no payload returned".to_string()))?;
+        let payload: UnblindedPayload = serde_json::from_slice(&payload)
+            .map_err(|e| RelayError::InvalidResponse(e.to_string()))?;
+
+        let envelope = match version {
+            PayloadVersion::V4 => {
+                let execution_payload: OpExecutionPayloadV4 = serde_json::from_value(payload.data)
+                    .map_err(|e| RelayError::InvalidResponse(e.to_string()))?;
+                OpExecutionPayloadEnvelope::V4(OpExecutionPayloadEnvelopeV4 {
+                    execution_payload,
+                    block_value: bid.value,
+                    blobs_bundle: BlobsBundleV1::default(),
+                    should_override_builder: false,
+                    parent_beacon_block_root: pending.parent_beacon_block_root,
+                    execution_requests: vec![],
+                })
+            }
+            _ => {
+                let execution_payload: ExecutionPayloadV3 = serde_json::from_value(payload.data)
+                    .map_err(|e| RelayError::InvalidResponse(e.to_string()))?;
+                OpExecutionPayloadEnvelope::V3(OpExecutionPayloadEnvelopeV3 {
+                    execution_payload,
+                    block_value: bid.value,
+                    blobs_bundle: BlobsBundleV1::default(),
+                    should_override_builder: false,
+                    parent_beacon_block_root: pending.parent_beacon_block_root,
+                })
+            }
+        };
+
+        if envelope.block_hash() != bid_block_hash {
+            return Err(RelayError::BlockHashMismatch {
+                expected: bid_block_hash,
+                actual: envelope.block_hash(),
+            });
+        }
+        Ok(envelope)
+    }
+
+    /// Checks the bid of `value` for block `block_hash` is signed by the relay.
+    fn verify_bid(
+        &self,
+        block_hash: B256,
+        value: U256,
+        signature: &[u8],
+    ) -> Result<(), RelayError> {
+        let signer = Signature::from_raw(signature)
+            .and_then(|signature| {
+                signature.recover_address_from_prehash(&BidMessage::signing_hash(block_hash, value))
+            })
+            .map_err(|e| RelayError::InvalidSignature(e.to_string()))?;
+        if signer != self.signer {
+            return Err(RelayError::InvalidSignature(format!(
+                "the bid of block {block_hash} is signed by {signer}, not by {}",
+                self.signer
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn hash_field(header: &Value, field: &str) -> Result<B256, RelayError> {
+    header
+        .get(field)
+        .and_then(|value| serde_json::from_value(value.clone()).ok())
+        .ok_or_else(|| RelayError::InvalidResponse(format!("the bid header has no {field}")))
+}
+
+#[async_trait]
+impl EngineApiExt for RelayClient {
+    async fn fork_choice_updated_v3(
+        &self,
+        fork_choice_state: ForkchoiceState,
+        payload_attributes: Option<OpPayloadAttributes>,
+    ) -> ClientResult<ForkchoiceUpdated> {
+        let status = PayloadStatus::from_status(PayloadStatusEnum::Valid)
+            .with_latest_valid_hash(fork_choice_state.head_block_hash);
+        let Some(attributes) = payload_attributes else {
+            return Ok(ForkchoiceUpdated::new(status));
+        };
+
+        let payload_id = relay_payload_id(
+            fork_choice_state.head_block_hash,
+            attributes.payload_attributes.timestamp,
+        );
+        let pending = PendingPayload {
+            parent_hash: fork_choice_state.head_block_hash,
+            timestamp: attributes.payload_attributes.timestamp,
+            parent_beacon_block_root: attributes
+                .payload_attributes
+                .parent_beacon_block_root
+                .unwrap_or_default(),
+        };
+        {
+            let mut payloads = self.pending.lock();
+            if payloads.len() >= MAX_PENDING_PAYLOADS {
+                payloads.clear();
+            }
+            payloads.insert(payload_id, pending);
+        }
+
+        let body = serde_json::to_vec(&RelayPayloadAttributes {
+            parent_hash: fork_choice_state.head_block_hash,
+            attributes,
+        })
+        .map_err(|e| RpcClientError::Io(e.into()))?;
+        self.request(
+            Method::POST,
+            "eth/v1/builder/payload_attributes",
+            Some(body),
+        )
+        .await?;
+        info!(message = "Sent payload attributes to the relay", %payload_id);
+
+        Ok(ForkchoiceUpdated::new(status).with_payload_id(payload_id))
+    }
+
+    async fn new_payload(&self, _new_payload: NewPayload) -> ClientResult<PayloadStatus> {
+        // Relays track the chain on their own, the payload is not validated
+        Ok(PayloadStatus::from_status(PayloadStatusEnum::Syncing))
+    }
+
+    async fn get_payload(
+        &self,
+        payload_id: PayloadId,
+        version: PayloadVersion,
+    ) -> ClientResult<OpExecutionPayloadEnvelope> {
+        let result = self.fetch_payload(payload_id, version).await;
+        let status = if result.is_ok() { "ok" } else { "error" };
+        counter!("relay.payloads", "status" => status).increment(1);
+        if let Err(e) = &result {
+            warn!(message = "Failed to get the payload from the relay", %payload_id, error = %e);
+        }
+        result.map_err(Into::into)
+    }
+
+    fn last_payload_source(&self) -> BlockSource {
+        BlockSource::Builder
+    }
+
+    async fn get_payload_bodies_by_hash_v1(
+        &self,
+        _block_hashes: Vec<B256>,
+    ) -> ClientResult<ExecutionPayloadBodiesV1> {
+        Err(RelayError::Unsupported("engine_getPayloadBodiesByHashV1").into())
+    }
+
+    async fn get_payload_bodies_by_range_v1(
+        &self,
+        _start: U64,
+        _count: U64,
+    ) -> ClientResult<ExecutionPayloadBodiesV1> {
+        Err(RelayError::Unsupported("engine_getPayloadBodiesByRangeV1").into())
+    }
+
+    async fn exchange_capabilities(&self, _capabilities: Vec<String>) -> ClientResult<Vec<String>> {
+        Ok(vec![])
+    }
+
+    async fn get_block_by_number(
+        &self,
+        _number: BlockNumberOrTag,
+        _full: bool,
+    ) -> ClientResult<Block> {
+        Err(RelayError::Unsupported("eth_getBlockByNumber").into())
+    }
+
+    async fn chain_id(&self) -> ClientResult<u64> {
+        Err(RelayError::Unsupported("eth_chainId").into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k256::ecdsa::SigningKey;
+
+    fn sign_bid(key: &SigningKey, block_hash: B256, value: U256) -> Vec<u8> {
+        let hash = BidMessage::signing_hash(block_hash, value);
+        let signature = key.sign_prehash_recoverable(hash.as_slice()).unwrap();
+        Signature::from(signature).as_bytes().to_vec()
+    }
+
+    #[test]
+    fn test_verify_bid() -> eyre::Result<()> {
+        let key = SigningKey::from_slice(&[1; 32])?;
+        let relay = RelayClient::new(
+            Url::parse("http://127.0.0.1:1")?,
+            Address::from_public_key(key.verifying_key()),
+            Duration::from_millis(100),
+        )?;
+
+        let block_hash = B256::random();
+        let value = U256::from(15);
+        let signature = sign_bid(&key, block_hash, value);
+        relay.verify_bid(block_hash, value, &signature)?;
+
+        // The signature does not cover another value or block
+        assert!(matches!(
+            relay.verify_bid(block_hash, U256::from(16), &signature),
+            Err(RelayError::InvalidSignature(_))
+        ));
+        assert!(matches!(
+            relay.verify_bid(B256::random(), value, &signature),
+            Err(RelayError::InvalidSignature(_))
+        ));
+
+        // Bids signed by another key or with a malformed signature are rejected
+        let other_key = SigningKey::from_slice(&[2; 32])?;
+        assert!(matches!(
+            relay.verify_bid(block_hash, value, &sign_bid(&other_key, block_hash, value)),
+            Err(RelayError::InvalidSignature(_))
+        ));
+        assert!(matches!(
+            relay.verify_bid(block_hash, value, &signature[..64]),
+            Err(RelayError::InvalidSignature(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_relay_payload_id() {
+        let parent_hash = B256::random();
+        assert_eq!(
+            relay_payload_id(parent_hash, 1),
+            relay_payload_id(parent_hash, 1)
+        );
+        assert_ne!(
+            relay_payload_id(parent_hash, 1),
+            relay_payload_id(parent_hash, 2)
+        );
+        assert_ne!(
+            relay_payload_id(parent_hash, 1),
+            relay_payload_id(B256::random(), 1)
+        );
+    }
+
+    #[test]
+    fn test_hash_field() {
+        let hash = B256::random();
+        let header = serde_json::json!({ "blockHash": hash, "parentHash": "0x01" });
+        assert_eq!(hash_field(&header, "blockHash").unwrap(), hash);
+        assert!(hash_field(&header, "parentHash").is_err());
+        assert!(hash_field(&header, "stateRoot").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_payload() -> eyre::Result<()> {
+        // No relay is listening, the payload ID is checked first
+        let relay = RelayClient::new(
+            Url::parse("http://127.0.0.1:1")?,
+            Address::random(),
+            Duration::from_millis(100),
+        )?;
+
+        let payload_id = PayloadId::new([1; 8]);
+        assert!(matches!(
+            relay.fetch_payload(payload_id, PayloadVersion::V3).await,
+            Err(RelayError::UnknownPayload(id)) if id == payload_id
+        ));
+        Ok(())
+    }
+}
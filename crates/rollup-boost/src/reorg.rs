@@ -0,0 +1,174 @@
+use std::collections::{HashMap, VecDeque};
+
+use alloy_primitives::B256;
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Number of recent blocks kept to resolve the ancestry of new heads.
+const MAX_TRACKED_BLOCKS: usize = 256;
+
+#[derive(Debug, Clone, Copy)]
+struct BlockInfo {
+    number: u64,
+    parent_hash: B256,
+}
+
+/// A change of the unsafe head that is not a descendant of the previous head.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReorgEvent {
+    pub old_head: B256,
+    pub old_number: u64,
+    pub new_head: B256,
+    pub new_number: u64,
+}
+
+impl ReorgEvent {
+    pub fn record(&self) {
+        counter!("reorgs").increment(1);
+        warn!(
+            message = "unsafe head reorg detected",
+            old_head = %self.old_head,
+            old_number = self.old_number,
+            new_head = %self.new_head,
+            new_number = self.new_number,
+        );
+    }
+}
+
+/// Tracks the unsafe head from `ForkchoiceState` updates and detects when it moves
+/// backwards or sideways.
+///
+/// Block ancestry is learned from the payloads seen in `new_payload`. If the ancestry
+/// between the previous and the new head is unknown, no reorg is reported.
+#[derive(Debug, Default)]
+pub struct HeadTracker {
+    head: Option<B256>,
+    blocks: HashMap<B256, BlockInfo>,
+    insertion_order: VecDeque<B256>,
+}
+
+impl HeadTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn head(&self) -> Option<B256> {
+        self.head
+    }
+
+    /// Records a block so its ancestry can be resolved by later head updates.
+    pub fn insert_block(&mut self, hash: B256, number: u64, parent_hash: B256) {
+        if self
+            .blocks
+            .insert(
+                hash,
+                BlockInfo {
+                    number,
+                    parent_hash,
+                },
+            )
+            .is_none()
+        {
+            self.insertion_order.push_back(hash);
+        }
+
+        while self.insertion_order.len() > MAX_TRACKED_BLOCKS {
+            if let Some(oldest) = self.insertion_order.pop_front() {
+                self.blocks.remove(&oldest);
+            }
+        }
+    }
+
+    /// Updates the unsafe head, returning a [`ReorgEvent`] if the new head does not
+    /// descend from the previous one.
+    pub fn update_head(&mut self, new_head: B256) -> Option<ReorgEvent> {
+        let old_head = self.head.replace(new_head).filter(|h| *h != new_head)?;
+        let old_number = self.blocks.get(&old_head)?.number;
+        let new_number = self.blocks.get(&new_head)?.number;
+
+        if self.descends_from(new_head, old_head, old_number)? {
+            return None;
+        }
+
+        Some(ReorgEvent {
+            old_head,
+            old_number,
+            new_head,
+            new_number,
+        })
+    }
+
+    /// Walks back from `hash` until reaching the height of `ancestor`. Returns `None` if
+    /// the ancestry is not known.
+    fn descends_from(&self, mut hash: B256, ancestor: B256, ancestor_number: u64) -> Option<bool> {
+        loop {
+            if hash == ancestor {
+                return Some(true);
+            }
+            let block = self.blocks.get(&hash)?;
+            if block.number <= ancestor_number {
+                return Some(false);
+            }
+            hash = block.parent_hash;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(n: u8) -> B256 {
+        B256::repeat_byte(n)
+    }
+
+    fn tracker_with_chain() -> HeadTracker {
+        // 1 <- 2 <- 3
+        //        \- 13
+        let mut tracker = HeadTracker::new();
+        tracker.insert_block(hash(1), 1, B256::ZERO);
+        tracker.insert_block(hash(2), 2, hash(1));
+        tracker.insert_block(hash(3), 3, hash(2));
+        tracker.insert_block(hash(13), 3, hash(2));
+        tracker
+    }
+
+    #[test]
+    fn test_extension_is_not_a_reorg() {
+        let mut tracker = tracker_with_chain();
+        assert_eq!(tracker.update_head(hash(1)), None);
+        assert_eq!(tracker.update_head(hash(3)), None);
+        assert_eq!(tracker.update_head(hash(3)), None);
+    }
+
+    #[test]
+    fn test_sideways_reorg() {
+        let mut tracker = tracker_with_chain();
+        tracker.update_head(hash(3));
+        assert_eq!(
+            tracker.update_head(hash(13)),
+            Some(ReorgEvent {
+                old_head: hash(3),
+                old_number: 3,
+                new_head: hash(13),
+                new_number: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn test_backwards_reorg() {
+        let mut tracker = tracker_with_chain();
+        tracker.update_head(hash(3));
+        assert!(tracker.update_head(hash(1)).is_some());
+        assert_eq!(tracker.head(), Some(hash(1)));
+    }
+
+    #[test]
+    fn test_unknown_ancestry_is_not_a_reorg() {
+        let mut tracker = tracker_with_chain();
+        tracker.update_head(hash(3));
+        assert_eq!(tracker.update_head(hash(42)), None);
+    }
+}
@@ -0,0 +1,133 @@
+use clap::Parser;
+
+#[derive(Parser, Clone, Debug, Default)]
+pub struct RpcRoutingArgs {
+    /// Non-engine RPC methods that are allowed through the proxy. Entries are either
+    /// method names or namespace wildcards (e.g. `eth_*`). All methods are allowed if empty
+    #[arg(long, env, value_delimiter = ',')]
+    pub rpc_allowlist: Vec<String>,
+
+    /// Non-engine RPC methods that are rejected by the proxy. Takes precedence over the
+    /// allowlist
+    #[arg(long, env, value_delimiter = ',')]
+    pub rpc_denylist: Vec<String>,
+
+    /// Non-engine RPC methods routed to the builder instead of the L2 client
+    #[arg(long, env, value_delimiter = ',')]
+    pub rpc_builder_methods: Vec<String>,
+}
+
+/// Upstream a non-engine RPC request is sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcRoute {
+    L2,
+    Builder,
+    Denied,
+}
+
+/// Method name or namespace wildcard, e.g. `eth_getBlockByNumber` or `debug_*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum MethodPattern {
+    Exact(String),
+    Prefix(String),
+}
+
+impl MethodPattern {
+    fn new(pattern: &str) -> Self {
+        match pattern.strip_suffix('*') {
+            Some(prefix) => MethodPattern::Prefix(prefix.to_string()),
+            None => MethodPattern::Exact(pattern.to_string()),
+        }
+    }
+
+    fn matches(&self, method: &str) -> bool {
+        match self {
+            MethodPattern::Exact(name) => name == method,
+            MethodPattern::Prefix(prefix) => method.starts_with(prefix.as_str()),
+        }
+    }
+}
+
+/// Routing policy for the non-engine RPC methods passing through the proxy.
+#[derive(Debug, Clone, Default)]
+pub struct RpcRouting {
+    allowlist: Vec<MethodPattern>,
+    denylist: Vec<MethodPattern>,
+    builder_methods: Vec<MethodPattern>,
+}
+
+impl RpcRouting {
+    pub fn new(allowlist: &[String], denylist: &[String], builder_methods: &[String]) -> Self {
+        let patterns = |list: &[String]| {
+            list.iter()
+                .map(|pattern| pattern.trim())
+                .filter(|pattern| !pattern.is_empty())
+                .map(MethodPattern::new)
+                .collect()
+        };
+
+        Self {
+            allowlist: patterns(allowlist),
+            denylist: patterns(denylist),
+            builder_methods: patterns(builder_methods),
+        }
+    }
+
+    pub fn from_args(args: &RpcRoutingArgs) -> Self {
+        Self::new(
+            &args.rpc_allowlist,
+            &args.rpc_denylist,
+            &args.rpc_builder_methods,
+        )
+    }
+
+    pub fn route(&self, method: &str) -> RpcRoute {
+        let matches = |patterns: &[MethodPattern]| patterns.iter().any(|p| p.matches(method));
+
+        if matches(&self.denylist) || (!self.allowlist.is_empty() && !matches(&self.allowlist)) {
+            RpcRoute::Denied
+        } else if matches(&self.builder_methods) {
+            RpcRoute::Builder
+        } else {
+            RpcRoute::L2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_default_routes_to_l2() {
+        let routing = RpcRouting::default();
+        assert_eq!(routing.route("eth_chainId"), RpcRoute::L2);
+        assert_eq!(routing.route("admin_addPeer"), RpcRoute::L2);
+    }
+
+    #[test]
+    fn test_allowlist_and_denylist() {
+        let routing = RpcRouting::new(
+            &strings(&["eth_*", "debug_traceTransaction"]),
+            &strings(&["eth_sign"]),
+            &[],
+        );
+        assert_eq!(routing.route("eth_chainId"), RpcRoute::L2);
+        assert_eq!(routing.route("debug_traceTransaction"), RpcRoute::L2);
+        assert_eq!(routing.route("debug_traceBlock"), RpcRoute::Denied);
+        assert_eq!(routing.route("admin_addPeer"), RpcRoute::Denied);
+        assert_eq!(routing.route("eth_sign"), RpcRoute::Denied);
+    }
+
+    #[test]
+    fn test_builder_methods() {
+        let routing = RpcRouting::new(&[], &strings(&["admin_*"]), &strings(&["debug_*"]));
+        assert_eq!(routing.route("debug_traceBlock"), RpcRoute::Builder);
+        assert_eq!(routing.route("eth_call"), RpcRoute::L2);
+        assert_eq!(routing.route("admin_peers"), RpcRoute::Denied);
+    }
+}
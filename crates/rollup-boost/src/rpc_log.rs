@@ -0,0 +1,296 @@
+use std::{
+    collections::HashSet,
+    net::IpAddr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+use clap::Parser;
+use http::{HeaderMap, header::AUTHORIZATION};
+use http_body_util::{BodyExt as _, Full};
+use jsonrpsee::{core::BoxError, server::HttpBody};
+use serde_json::Value;
+use tracing::info;
+
+use crate::Response;
+
+/// Methods whose params are raw transactions.
+const RAW_TRANSACTION_METHODS: [&str; 2] = [
+    "eth_sendRawTransaction",
+    "eth_sendRawTransactionConditional",
+];
+
+#[derive(Parser, Clone, Debug)]
+pub struct RpcLogArgs {
+    /// Log the full JSON-RPC requests and responses of the proxy
+    #[arg(long, env)]
+    pub rpc_log: bool,
+
+    /// Methods whose requests and responses are logged, all methods if not set
+    #[arg(long, env, value_delimiter = ',')]
+    pub rpc_log_methods: Vec<String>,
+
+    /// Fraction of the requests logged, between 0 and 1
+    #[arg(long, env, default_value = "1.0")]
+    pub rpc_log_sample_rate: f64,
+
+    /// Log the HTTP headers of the requests. The JWT of the authorization header is
+    /// redacted unless `--rpc-log-jwt` is set
+    #[arg(long, env)]
+    pub rpc_log_headers: bool,
+
+    /// Log the JWT of the authorization header, requires `--rpc-log-headers`
+    #[arg(long, env, requires = "rpc_log_headers")]
+    pub rpc_log_jwt: bool,
+
+    /// Log the encoded transactions of the requests and responses instead of redacting
+    /// them to their size
+    #[arg(long, env)]
+    pub rpc_log_transactions: bool,
+}
+
+/// Logs the JSON-RPC requests and responses going through the proxy, to diagnose
+/// mismatches between the consensus client, rollup-boost and the builders.
+///
+/// Requests are sampled and filtered by method. The JWTs of the requests and the
+/// transactions of the requests and responses are redacted by default.
+#[derive(Debug)]
+pub struct RpcLogger {
+    methods: HashSet<String>,
+    sample_rate: f64,
+    headers: bool,
+    jwt: bool,
+    transactions: bool,
+    requests: AtomicU64,
+}
+
+impl RpcLogger {
+    pub fn from_args(args: &RpcLogArgs) -> Option<Self> {
+        args.rpc_log.then(|| Self {
+            methods: args.rpc_log_methods.iter().cloned().collect(),
+            sample_rate: args.rpc_log_sample_rate.clamp(0.0, 1.0),
+            headers: args.rpc_log_headers,
+            jwt: args.rpc_log_jwt,
+            transactions: args.rpc_log_transactions,
+            requests: AtomicU64::new(0),
+        })
+    }
+
+    /// Starts logging a request, if its method is logged and it is sampled.
+    pub fn start(&self, headers: &HeaderMap, body: &[u8]) -> Option<RpcLogEntry> {
+        let mut request: Value = serde_json::from_slice(body).ok()?;
+        let method = match &request {
+            Value::Array(_) => "batch".to_string(),
+            request => request.get("method")?.as_str()?.to_string(),
+        };
+        if !self.methods.is_empty() && !self.methods.contains(&method) {
+            return None;
+        }
+        if !self.sampled() {
+            return None;
+        }
+
+        self.redact(&mut request);
+        let headers = self.headers.then(|| self.format_headers(headers));
+        Some(RpcLogEntry {
+            method,
+            request,
+            headers,
+            client: None,
+            transactions: self.transactions,
+            started: Instant::now(),
+        })
+    }
+
+    /// Samples the requests evenly: the n-th request is logged when the number of
+    /// logged requests expected after it increases.
+    fn sampled(&self) -> bool {
+        let n = self.requests.fetch_add(1, Ordering::Relaxed) as f64;
+        (n * self.sample_rate).floor() != ((n + 1.0) * self.sample_rate).floor()
+    }
+
+    fn redact(&self, request: &mut Value) {
+        if self.transactions {
+            return;
+        }
+        match request {
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact(item)),
+            request => {
+                let raw_transaction = request
+                    .get("method")
+                    .and_then(Value::as_str)
+                    .is_some_and(|method| RAW_TRANSACTION_METHODS.contains(&method));
+                if let (true, Some(Value::Array(params))) =
+                    (raw_transaction, request.get_mut("params"))
+                {
+                    params.first_mut().into_iter().for_each(redact_transaction);
+                }
+                redact_transactions(request);
+            }
+        }
+    }
+
+    fn format_headers(&self, headers: &HeaderMap) -> String {
+        let headers = headers
+            .iter()
+            .map(|(name, value)| {
+                let value = if name == AUTHORIZATION && !self.jwt {
+                    "Bearer <redacted>"
+                } else {
+                    value.to_str().unwrap_or("<binary>")
+                };
+                format!("{name}: {value}")
+            })
+            .collect::<Vec<_>>();
+        headers.join(", ")
+    }
+}
+
+/// A request being logged, logged along with its response.
+#[derive(Debug)]
+pub struct RpcLogEntry {
+    method: String,
+    request: Value,
+    headers: Option<String>,
+    client: Option<IpAddr>,
+    transactions: bool,
+    started: Instant,
+}
+
+impl RpcLogEntry {
+    /// Sets the client the request is logged for, resolved behind the trusted proxies.
+    pub fn with_client(mut self, client: Option<IpAddr>) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Logs the request and its response, returning the response.
+    pub async fn finish(self, response: Response) -> Result<Response, BoxError> {
+        let (parts, body) = response.into_parts();
+        let body = body.collect().await?.to_bytes();
+
+        let response = match serde_json::from_slice::<Value>(&body) {
+            Ok(mut response) => {
+                if !self.transactions {
+                    redact_transactions(&mut response);
+                }
+                response.to_string()
+            }
+            Err(_) => String::from_utf8_lossy(&body).into_owned(),
+        };
+        info!(
+            target: "proxy::rpc_log",
+            message = "rpc call",
+            method = %self.method,
+            status = parts.status.as_u16(),
+            duration_ms = self.started.elapsed().as_millis() as u64,
+            client = self.client.map(tracing::field::display),
+            headers = self.headers.as_deref().unwrap_or_default(),
+            request = %self.request,
+            %response,
+        );
+
+        Ok(http::Response::from_parts(
+            parts,
+            HttpBody::new(Full::from(body)),
+        ))
+    }
+}
+
+/// Redacts the encoded transactions of the payloads and flashblocks in `value`.
+fn redact_transactions(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                match value {
+                    Value::Array(transactions) if key == "transactions" => {
+                        transactions.iter_mut().for_each(redact_transaction)
+                    }
+                    value => redact_transactions(value),
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact_transactions),
+        _ => {}
+    }
+}
+
+fn redact_transaction(tx: &mut Value) {
+    if let Value::String(encoded) = tx {
+        let size = encoded.trim_start_matches("0x").len() / 2;
+        *tx = Value::String(format!("<redacted {size} bytes>"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn args() -> RpcLogArgs {
+        RpcLogArgs {
+            rpc_log: true,
+            rpc_log_methods: vec![],
+            rpc_log_sample_rate: 1.0,
+            rpc_log_headers: true,
+            rpc_log_jwt: false,
+            rpc_log_transactions: false,
+        }
+    }
+
+    #[test]
+    fn test_redaction() {
+        let logger = RpcLogger::from_args(&args()).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, "Bearer secret".parse().unwrap());
+
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_sendRawTransaction",
+            "params": ["0x0102"],
+        });
+        let entry = logger
+            .start(&headers, request.to_string().as_bytes())
+            .unwrap();
+        assert_eq!(entry.request["params"], json!(["<redacted 2 bytes>"]));
+        assert_eq!(
+            entry.headers.as_deref(),
+            Some("authorization: Bearer <redacted>")
+        );
+
+        let mut payload = json!({
+            "result": {"executionPayload": {"blockNumber": "0x1", "transactions": ["0x010203"]}}
+        });
+        redact_transactions(&mut payload);
+        assert_eq!(
+            payload["result"]["executionPayload"],
+            json!({"blockNumber": "0x1", "transactions": ["<redacted 3 bytes>"]})
+        );
+    }
+
+    #[test]
+    fn test_sampling_and_methods() {
+        let logger = RpcLogger::from_args(&RpcLogArgs {
+            rpc_log_methods: vec!["engine_getPayloadV3".to_string()],
+            rpc_log_sample_rate: 0.25,
+            ..args()
+        })
+        .unwrap();
+        let request = |method: &str| json!({"id": 1, "method": method}).to_string();
+
+        let logged = (0..8)
+            .filter(|_| {
+                logger
+                    .start(&HeaderMap::new(), request("engine_getPayloadV3").as_bytes())
+                    .is_some()
+            })
+            .count();
+        assert_eq!(logged, 2);
+        assert!((0..8).all(|_| {
+            logger
+                .start(&HeaderMap::new(), request("eth_chainId").as_bytes())
+                .is_none()
+        }));
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    future::Future,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use clap::Parser;
+use metrics::{counter, gauge};
+use tokio::{
+    runtime::{Builder, Handle, Runtime},
+    task::JoinHandle,
+};
+use tokio_metrics::TaskMonitor;
+
+use crate::in_current_chain;
+
+/// Interval at which the runtime and task metrics are sampled.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Runtime the flashblocks tasks are spawned on, if not the runtime of the proxy.
+static FLASHBLOCKS_RUNTIME: OnceLock<Handle> = OnceLock::new();
+
+#[derive(Parser, Clone, Debug)]
+pub struct RuntimeArgs {
+    /// Number of worker threads of the tokio runtime, the number of CPU cores if not set
+    #[arg(long, env)]
+    pub runtime_worker_threads: Option<usize>,
+
+    /// Where the flashblocks are received, assembled and published, so that large payloads
+    /// do not delay the Engine API proxy
+    #[arg(long, env, default_value = "shared")]
+    pub flashblocks_runtime: FlashblocksRuntime,
+
+    /// Number of worker threads of the flashblocks runtime, with `--flashblocks-runtime
+    /// dedicated`
+    #[arg(long, env, default_value = "2")]
+    pub flashblocks_runtime_threads: usize,
+}
+
+/// Runtime the flashblocks receiver, accumulator and publisher tasks run on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, clap::ValueEnum)]
+pub enum FlashblocksRuntime {
+    /// On the runtime of the proxy
+    Shared,
+    /// On a multi-threaded runtime of their own
+    Dedicated,
+    /// On a single thread of their own
+    CurrentThread,
+}
+
+impl RuntimeArgs {
+    /// Builds the runtime rollup-boost runs on.
+    pub fn build_runtime(&self) -> std::io::Result<Runtime> {
+        let mut builder = Builder::new_multi_thread();
+        if let Some(threads) = self.runtime_worker_threads {
+            builder.worker_threads(threads.max(1));
+        }
+        builder.enable_all().build()
+    }
+
+    /// Starts the runtime the flashblocks tasks are spawned on by
+    /// [`spawn_flashblocks_task`], unless they share the runtime of the proxy. Must be
+    /// called before the flashblocks are received.
+    pub fn start_flashblocks_runtime(&self) -> eyre::Result<()> {
+        if FLASHBLOCKS_RUNTIME.get().is_some() {
+            return Ok(());
+        }
+        let Some(runtime) = self.build_flashblocks_runtime()? else {
+            return Ok(());
+        };
+        let handle = runtime.handle().clone();
+        let _ = FLASHBLOCKS_RUNTIME.set(handle.clone());
+
+        // The runtime is driven by its own thread for as long as the process runs
+        std::thread::Builder::new()
+            .name("flashblocks".to_string())
+            .spawn(move || runtime.block_on(std::future::pending::<()>()))?;
+        spawn_runtime_metrics("flashblocks", handle);
+        Ok(())
+    }
+
+    /// Builds the runtime of the flashblocks tasks, if they do not share the runtime of
+    /// the proxy.
+    fn build_flashblocks_runtime(&self) -> std::io::Result<Option<Runtime>> {
+        let runtime = match self.flashblocks_runtime {
+            FlashblocksRuntime::Shared => return Ok(None),
+            FlashblocksRuntime::Dedicated => Builder::new_multi_thread()
+                .worker_threads(self.flashblocks_runtime_threads.max(1))
+                .thread_name("flashblocks")
+                .enable_all()
+                .build()?,
+            FlashblocksRuntime::CurrentThread => {
+                Builder::new_current_thread().enable_all().build()?
+            }
+        };
+        Ok(Some(runtime))
+    }
+}
+
+/// Records the metrics of the runtime of `handle` every second, labelled by `runtime`:
+/// the number of workers and alive tasks, the depth of the global queue and the share of
+/// time the workers were busy. Must be called from a tokio runtime.
+pub fn spawn_runtime_metrics(runtime: &'static str, handle: Handle) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+        let mut last_sample = Instant::now();
+        let mut last_busy: Vec<Duration> = vec![];
+        loop {
+            interval.tick().await;
+            let metrics = handle.metrics();
+            let workers = metrics.num_workers();
+            gauge!("runtime.workers", "runtime" => runtime).set(workers as f64);
+            gauge!("runtime.alive_tasks", "runtime" => runtime)
+                .set(metrics.num_alive_tasks() as f64);
+            gauge!("runtime.global_queue_depth", "runtime" => runtime)
+                .set(metrics.global_queue_depth() as f64);
+
+            let elapsed = last_sample.elapsed().as_secs_f64();
+            last_sample = Instant::now();
+            let busy = (0..workers)
+                .map(|worker| metrics.worker_total_busy_duration(worker))
+                .collect::<Vec<_>>();
+            if last_busy.len() == workers && workers > 0 && elapsed > 0.0 {
+                let utilization = busy
+                    .iter()
+                    .zip(&last_busy)
+                    .map(|(busy, last)| busy.saturating_sub(*last).as_secs_f64() / elapsed)
+                    .collect::<Vec<_>>();
+                gauge!("runtime.worker_utilization", "runtime" => runtime)
+                    .set(utilization.iter().sum::<f64>() / workers as f64);
+                gauge!("runtime.max_worker_utilization", "runtime" => runtime)
+                    .set(utilization.iter().copied().fold(0.0, f64::max));
+            }
+            last_busy = busy;
+        }
+    });
+}
+
+/// Spawns a task receiving, assembling or publishing flashblocks on the runtime selected
+/// by `--flashblocks-runtime`. Its poll and scheduling durations are recorded, labelled
+/// by `task`, to tell whether a latency spike comes from the task itself or from a busy
+/// runtime.
+pub fn spawn_flashblocks_task<F>(task: &'static str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    // The flashblocks runtime is shared by the chains of the process
+    let future = in_current_chain(future);
+    match FLASHBLOCKS_RUNTIME.get() {
+        Some(handle) => spawn_monitored(handle, task, future),
+        None => spawn_monitored(&Handle::current(), task, future),
+    }
+}
+
+/// Spawns `future` on `handle`, recording the metrics of the task until it completes.
+fn spawn_monitored<F>(handle: &Handle, task: &'static str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let monitor = TaskMonitor::new();
+    // Instrumented before the metrics are sampled, so that the task is not seen as done
+    let future = monitor.instrument(future);
+
+    handle.spawn(async move {
+        let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+        let mut intervals = monitor.intervals();
+        loop {
+            interval.tick().await;
+            let Some(metrics) = intervals.next() else {
+                return;
+            };
+            gauge!("runtime.task_mean_poll_duration", "task" => task)
+                .set(metrics.mean_poll_duration().as_secs_f64());
+            gauge!("runtime.task_mean_scheduled_duration", "task" => task)
+                .set(metrics.mean_scheduled_duration().as_secs_f64());
+            counter!("runtime.task_polls", "task" => task).increment(metrics.total_poll_count);
+            counter!("runtime.task_slow_polls", "task" => task)
+                .increment(metrics.total_slow_poll_count);
+
+            let cumulative = monitor.cumulative();
+            if cumulative.dropped_count >= cumulative.instrumented_count {
+                return;
+            }
+        }
+    });
+
+    handle.spawn(future)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runtime_args(flashblocks_runtime: FlashblocksRuntime, threads: usize) -> RuntimeArgs {
+        RuntimeArgs {
+            runtime_worker_threads: None,
+            flashblocks_runtime,
+            flashblocks_runtime_threads: threads,
+        }
+    }
+
+    fn thread_name() -> Option<String> {
+        std::thread::current().name().map(str::to_string)
+    }
+
+    #[test]
+    fn test_flashblocks_runtime() -> eyre::Result<()> {
+        assert!(
+            runtime_args(FlashblocksRuntime::Shared, 2)
+                .build_flashblocks_runtime()?
+                .is_none()
+        );
+
+        // The tasks spawned on a dedicated runtime run on its own threads, of which there
+        // is at least one
+        let runtime = runtime_args(FlashblocksRuntime::Dedicated, 0)
+            .build_flashblocks_runtime()?
+            .expect("a dedicated runtime");
+        assert_eq!(runtime.metrics().num_workers(), 1);
+        let thread = runtime.block_on(spawn_monitored(runtime.handle(), "test", async {
+            thread_name()
+        }))?;
+        assert_eq!(thread.as_deref(), Some("flashblocks"));
+
+        // Without a flashblocks runtime, the tasks run on the runtime of the caller
+        let proxy = Builder::new_current_thread().enable_all().build()?;
+        let thread = proxy
+            .block_on(async { spawn_flashblocks_task("test", async { thread_name() }).await })?;
+        assert_eq!(thread, thread_name());
+        Ok(())
+    }
+}
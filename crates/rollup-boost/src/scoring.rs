@@ -0,0 +1,252 @@
+use std::{collections::HashMap, fs, io, path::PathBuf, sync::Arc, time::Duration};
+
+use clap::Parser;
+use metrics::gauge;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// Interval at which the builder scores are persisted.
+const PERSIST_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Weights of the valid payload rate, the share of blocks worth at least the l2 client
+/// block, and the cadence adherence in the quality score.
+const VALID_WEIGHT: f64 = 0.5;
+const VALUE_WEIGHT: f64 = 0.3;
+const CADENCE_WEIGHT: f64 = 0.2;
+
+#[derive(Parser, Clone, Debug)]
+pub struct ScoringArgs {
+    /// Maintain a rolling quality score for each builder, from its valid payload rate,
+    /// its flashblocks cadence and the value of its blocks against the l2 client blocks
+    #[arg(long, env)]
+    pub builder_scoring: bool,
+
+    /// File the builder scores are persisted to, so that they survive restarts
+    #[arg(long, env)]
+    pub builder_scores_path: Option<PathBuf>,
+
+    /// Weight of each new observation in the rolling builder scores, between 0 and 1
+    #[arg(long, env, default_value = "0.05")]
+    pub builder_score_decay: f64,
+
+    /// Quality score, between 0 and 1, below which the blocks of a builder are not
+    /// selected and the l2 client block is returned instead
+    #[arg(long, env)]
+    pub min_builder_score: Option<f64>,
+}
+
+/// An outcome of a builder that counts towards its quality score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoreObservation {
+    /// Whether a payload of the builder was accepted by the l2 client
+    Valid(bool),
+    /// Whether a block of the builder was worth at least the l2 client block
+    Value(bool),
+    /// Whether a flashblock was received within the configured cadence
+    Cadence(bool),
+}
+
+/// Rolling quality score of a builder, as reported by `debug_getBuilderScores`. Every
+/// rate is an exponentially weighted average between 0 and 1, starting at 1.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BuilderScore {
+    pub name: String,
+    /// Weighted combination of the rates below
+    pub score: f64,
+    /// Share of the payloads of the builder accepted by the l2 client
+    pub valid_rate: f64,
+    /// Share of the blocks of the builder worth at least the l2 client block
+    pub value_rate: f64,
+    /// Share of the flashblocks of the builder received within the configured cadence
+    pub cadence_rate: f64,
+    /// Number of observations the score was computed from
+    pub samples: u64,
+}
+
+impl BuilderScore {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            score: 1.0,
+            valid_rate: 1.0,
+            value_rate: 1.0,
+            cadence_rate: 1.0,
+            samples: 0,
+        }
+    }
+
+    fn observe(&mut self, observation: ScoreObservation, decay: f64) {
+        let (rate, success) = match observation {
+            ScoreObservation::Valid(success) => (&mut self.valid_rate, success),
+            ScoreObservation::Value(success) => (&mut self.value_rate, success),
+            ScoreObservation::Cadence(success) => (&mut self.cadence_rate, success),
+        };
+        let value = if success { 1.0 } else { 0.0 };
+        *rate += decay * (value - *rate);
+        self.samples += 1;
+        self.score = VALID_WEIGHT * self.valid_rate
+            + VALUE_WEIGHT * self.value_rate
+            + CADENCE_WEIGHT * self.cadence_rate;
+    }
+}
+
+/// Maintains a rolling quality score for each builder, which can exclude the blocks of a
+/// builder scoring below a minimum from block selection.
+#[derive(Debug)]
+pub struct BuilderScores {
+    decay: f64,
+    min_score: Option<f64>,
+    path: Option<PathBuf>,
+    builders: Mutex<HashMap<String, BuilderScore>>,
+}
+
+impl BuilderScores {
+    pub fn new(decay: f64) -> Self {
+        Self {
+            decay: decay.clamp(0.0, 1.0),
+            min_score: None,
+            path: None,
+            builders: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn from_args(args: &ScoringArgs) -> eyre::Result<Option<Self>> {
+        if !args.builder_scoring {
+            return Ok(None);
+        }
+        let scores = Self::new(args.builder_score_decay).with_min_score(args.min_builder_score);
+        match &args.builder_scores_path {
+            Some(path) => Ok(Some(scores.with_persistence(path.clone())?)),
+            None => Ok(Some(scores)),
+        }
+    }
+
+    /// Excludes the blocks of builders scoring below `min_score` from block selection.
+    pub fn with_min_score(mut self, min_score: Option<f64>) -> Self {
+        self.min_score = min_score;
+        self
+    }
+
+    /// Persists the scores to `path` with [`Self::spawn_persistence`], restoring the
+    /// scores of a previous run if the file exists.
+    pub fn with_persistence(mut self, path: PathBuf) -> eyre::Result<Self> {
+        match fs::read(&path) {
+            Ok(contents) => {
+                let scores: Vec<BuilderScore> = serde_json::from_slice(&contents)?;
+                info!(message = "restored builder scores", path = %path.display(), builders = scores.len());
+                let mut builders = self.builders.lock();
+                for score in scores {
+                    gauge!("builder.quality_score", "builder" => score.name.clone())
+                        .set(score.score);
+                    builders.insert(score.name.clone(), score);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        self.path = Some(path);
+        Ok(self)
+    }
+
+    /// Updates the score of `builder` with `observation`.
+    pub fn record(&self, builder: &str, observation: ScoreObservation) {
+        let mut builders = self.builders.lock();
+        let score = builders
+            .entry(builder.to_string())
+            .or_insert_with(|| BuilderScore::new(builder.to_string()));
+        score.observe(observation, self.decay);
+        gauge!("builder.quality_score", "builder" => builder.to_string()).set(score.score);
+    }
+
+    /// Quality score of `builder`, 1 for a builder that was never observed.
+    pub fn score(&self, builder: &str) -> f64 {
+        self.builders
+            .lock()
+            .get(builder)
+            .map_or(1.0, |score| score.score)
+    }
+
+    /// Whether the blocks of `builder` can be selected, that is it scores above the
+    /// minimum score.
+    pub fn accepts(&self, builder: &str) -> bool {
+        let Some(min_score) = self.min_score else {
+            return true;
+        };
+        let score = self.score(builder);
+        if score < min_score {
+            warn!(message = "builder score below the minimum", %builder, score, min_score);
+            return false;
+        }
+        true
+    }
+
+    /// The scores of every builder observed, by name.
+    pub fn scores(&self) -> Vec<BuilderScore> {
+        let mut scores = self.builders.lock().values().cloned().collect::<Vec<_>>();
+        scores.sort_by(|a, b| a.name.cmp(&b.name));
+        scores
+    }
+
+    /// Writes the scores to the persistence file, if any.
+    pub fn persist(&self) -> eyre::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        // Write to a temporary file first so that a crash never leaves truncated scores
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(&self.scores())?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Persists the scores every 30 seconds, if a persistence file is set.
+    pub fn spawn_persistence(self: Arc<Self>) {
+        if self.path.is_none() {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PERSIST_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.persist() {
+                    warn!(message = "failed to persist the builder scores", error = %e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_scores() -> eyre::Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("rollup-boost-scores-{}.json", std::process::id()));
+        let scores = BuilderScores::new(0.5)
+            .with_min_score(Some(0.6))
+            .with_persistence(path.clone())?;
+        assert!(scores.accepts("builder"));
+
+        scores.record("builder", ScoreObservation::Valid(false));
+        assert!((scores.score("builder") - 0.75).abs() < 1e-9);
+        assert!(scores.accepts("builder"));
+
+        scores.record("builder", ScoreObservation::Valid(false));
+        scores.record("builder", ScoreObservation::Value(false));
+        assert!(!scores.accepts("builder"));
+        assert!(scores.accepts("other"));
+
+        // The scores survive a restart
+        scores.persist()?;
+        let restored = BuilderScores::new(0.5).with_persistence(path.clone())?;
+        assert_eq!(restored.scores(), scores.scores());
+
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -1,5 +1,8 @@
 use crate::{OpExecutionPayloadEnvelope, PayloadSource};
+use alloy_primitives::U256;
 use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use tracing::info;
 
 /// Defines the strategy for choosing between the builder block and the L2 client block
 /// during block production.
@@ -37,6 +40,60 @@ impl BlockSelectionPolicy {
     }
 }
 
+/// Custom policy choosing between the builder block and the L2 client block, consulted
+/// before the [`BlockSelectionPolicy`].
+pub trait PayloadSelector: Debug + Send + Sync {
+    /// Name of the selector, used in the metrics and logs.
+    fn name(&self) -> &'static str;
+
+    /// Returns the source of the block to select, or `None` to defer to the block
+    /// selection policy.
+    fn select(
+        &self,
+        builder_payload: &OpExecutionPayloadEnvelope,
+        l2_payload: &OpExecutionPayloadEnvelope,
+    ) -> Option<PayloadSource>;
+}
+
+/// Minimum block value a builder block must have to be selected over the L2 client block.
+///
+/// Protects against degenerate builder blocks (e.g. empty blocks) that are valid but
+/// pay less than the block the L2 client built locally.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct BlockValueThreshold {
+    /// Minimum absolute block value of the builder block
+    pub min_value: U256,
+    /// Minimum amount by which the builder block value must exceed the L2 block value
+    pub min_margin: Option<U256>,
+}
+
+impl BlockValueThreshold {
+    /// Returns true if the builder block value is above the threshold.
+    pub fn accepts(
+        &self,
+        builder_payload: &OpExecutionPayloadEnvelope,
+        l2_payload: &OpExecutionPayloadEnvelope,
+    ) -> bool {
+        let builder_value = builder_payload.block_value();
+        let l2_value = l2_payload.block_value();
+
+        let below_min = builder_value < self.min_value;
+        let below_margin = self
+            .min_margin
+            .is_some_and(|margin| builder_value < l2_value.saturating_add(margin));
+
+        if below_min || below_margin {
+            info!(
+                message = "builder block value below threshold",
+                %builder_value,
+                %l2_value,
+            );
+            return false;
+        }
+        true
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -106,4 +163,30 @@ mod tests {
         assert_eq!(selected_payload.1, PayloadSource::Builder);
         Ok(())
     }
+
+    #[test]
+    fn test_block_value_threshold() -> eyre::Result<()> {
+        let execution_payload = r#"{"executionPayload":{"parentHash":"0xe927a1448525fb5d32cb50ee1408461a945ba6c39bd5cf5621407d500ecc8de9","feeRecipient":"0x0000000000000000000000000000000000000000","stateRoot":"0x10f8a0830000e8edef6d00cc727ff833f064b1950afd591ae41357f97e543119","receiptsRoot":"0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421","logsBloom":"0x00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000","prevRandao":"0xe0d8b4521a7da1582a713244ffb6a86aa1726932087386e2dc7973f43fc6cb24","blockNumber":"0x1","gasLimit":"0x2ffbd2","gasUsed":"0x0","timestamp":"0x1235","extraData":"0xd883010d00846765746888676f312e32312e30856c696e7578","baseFeePerGas":"0x342770c0","blockHash":"0x44d0fa5f2f73a938ebb96a2a21679eb8dea3e7b7dd8fd9f35aa756dda8bf0a8a","transactions":[],"withdrawals":[],"blobGasUsed":"0x0","excessBlobGas":"0x0","withdrawalsRoot":"0x123400000000000000000000000000000000000000000000000000000000babe"},"blockValue":"0x0","blobsBundle":{"commitments":[],"proofs":[],"blobs":[]},"shouldOverrideBuilder":false,"parentBeaconBlockRoot":"0xdead00000000000000000000000000000000000000000000000000000000beef","executionRequests":["0xdeadbeef"]}"#;
+        let payload: OpExecutionPayloadEnvelopeV4 = serde_json::from_str(execution_payload)?;
+        let with_value = |value: u64| {
+            let mut payload = payload.clone();
+            payload.block_value = U256::from(value);
+            OpExecutionPayloadEnvelope::V4(payload)
+        };
+
+        let threshold = BlockValueThreshold {
+            min_value: U256::from(100),
+            min_margin: None,
+        };
+        assert!(!threshold.accepts(&with_value(99), &with_value(0)));
+        assert!(threshold.accepts(&with_value(100), &with_value(0)));
+
+        let threshold = BlockValueThreshold {
+            min_value: U256::ZERO,
+            min_margin: Some(U256::from(10)),
+        };
+        assert!(!threshold.accepts(&with_value(109), &with_value(100)));
+        assert!(threshold.accepts(&with_value(110), &with_value(100)));
+        Ok(())
+    }
 }
@@ -1,19 +1,30 @@
 use crate::debug_api::ExecutionMode;
-use crate::{BlockSelectionPolicy, EngineApiExt};
 use crate::{
-    client::rpc::RpcClient,
+    AttributesHook, AttributesPolicy, AuditLog, BlockSelectionPolicy, BlockSource,
+    BlockValueThreshold, BuilderPromotion, BuilderQuarantine, BuilderRegistry, BuilderScores,
+    BuilderWarmup, ChainIdGuard, CorrelationId, DebugAuth, DivergenceDetector, DrainMode,
+    DryRunOverride, EngineApiExt, FaultInjector, ForkchoiceUpdateCache, HeadLagMonitor,
+    HeadTracker, IpcEndpoint, LeaderElection, PayloadArchive, PayloadComparison, PayloadCycle,
+    PayloadHistory, PayloadSelector, PayloadValidator, PolicyOutcome, RecentFlashblocks,
+    Revalidation, ScoreObservation, SharedJwtSecret, SubscriberRegistry, SyntheticFlashblocks,
+    ValidationFailure, WebhookEvent, WebhookNotifier, apply_attributes_hooks,
+};
+use crate::{
+    FlashblocksError,
+    client::rpc::{RpcClient, RpcClientError},
     debug_api::DebugServer,
     health::HealthHandle,
     payload::{
-        NewPayload, NewPayloadV3, NewPayloadV4, OpExecutionPayloadEnvelope, PayloadSource,
-        PayloadTraceContext, PayloadVersion,
+        ExpectedPayload, NewPayload, NewPayloadV3, NewPayloadV4, OpExecutionPayloadEnvelope,
+        PayloadSource, PayloadTrace, PayloadTraceContext, PayloadVersion,
     },
     probe::{Health, Probes},
 };
-use alloy_primitives::{B256, Bytes, bytes};
+use alloy_primitives::{B256, Bytes, U64, bytes};
 use alloy_rpc_types_engine::{
-    ExecutionPayload, ExecutionPayloadV3, ForkchoiceState, ForkchoiceUpdated, PayloadId,
-    PayloadStatus,
+    ExecutionPayload, ExecutionPayloadBodiesV1, ExecutionPayloadEnvelopeV2,
+    ExecutionPayloadInputV2, ExecutionPayloadV1, ExecutionPayloadV3, ForkchoiceState,
+    ForkchoiceUpdated, PayloadId, PayloadStatus, PayloadStatusEnum,
 };
 use alloy_rpc_types_eth::{Block, BlockNumberOrTag};
 use http_body_util::{BodyExt, Full};
@@ -24,32 +35,99 @@ use jsonrpsee::proc_macros::rpc;
 use jsonrpsee::server::HttpBody;
 use jsonrpsee::server::HttpRequest;
 use jsonrpsee::server::HttpResponse;
+use jsonrpsee::server::ServerHandle;
 use jsonrpsee::types::ErrorObject;
-use jsonrpsee::types::error::INVALID_REQUEST_CODE;
-use metrics::counter;
+use jsonrpsee::types::error::{INTERNAL_ERROR_CODE, INVALID_REQUEST_CODE};
+use metrics::{counter, gauge};
 use op_alloy_rpc_types_engine::{
     OpExecutionPayloadEnvelopeV3, OpExecutionPayloadEnvelopeV4, OpExecutionPayloadV4,
     OpPayloadAttributes,
 };
 use opentelemetry::trace::SpanKind;
 use parking_lot::Mutex;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Duration;
-use tracing::{info, instrument};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{Instrument, debug, info, instrument, warn};
+
+/// Engine API methods served by rollup-boost, advertised in `engine_exchangeCapabilities`
+pub(crate) const ENGINE_CAPABILITIES: [&str; 11] = [
+    "engine_forkchoiceUpdatedV3",
+    "engine_getPayloadV1",
+    "engine_newPayloadV1",
+    "engine_getPayloadV2",
+    "engine_newPayloadV2",
+    "engine_getPayloadV3",
+    "engine_newPayloadV3",
+    "engine_getPayloadV4",
+    "engine_newPayloadV4",
+    "engine_getPayloadBodiesByHashV1",
+    "engine_getPayloadBodiesByRangeV1",
+];
 
 pub type Request = HttpRequest;
 pub type Response = HttpResponse;
 pub type BufferedRequest = http::Request<Full<bytes::Bytes>>;
 pub type BufferedResponse = http::Response<Full<bytes::Bytes>>;
 
+/// How the engine API calls keeping the builder synced with the canonical chain are
+/// forwarded to it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum BuilderForwarding {
+    /// Forward the call without waiting for the builder
+    #[default]
+    Async,
+    /// Wait for the builder before responding, and fail the call if the builder
+    /// fails, so that the sequencer retries it until the builder is in sync
+    Sync,
+    /// Do not forward the call to the builder
+    Disabled,
+}
+
 #[derive(Clone)]
 pub struct RollupBoostServer {
     pub l2_client: Arc<RpcClient>,
     pub builder_client: Arc<dyn EngineApiExt>,
     pub payload_trace_context: Arc<PayloadTraceContext>,
     block_selection_policy: Option<BlockSelectionPolicy>,
+    payload_selector: Option<Arc<dyn PayloadSelector>>,
     execution_mode: Arc<Mutex<ExecutionMode>>,
     probes: Arc<Probes>,
+    head_tracker: Arc<Mutex<HeadTracker>>,
+    last_fork_choice_state: Arc<Mutex<Option<ForkchoiceState>>>,
+    // Last forkchoice state the builder acknowledged, head-only FCUs repeating it are
+    // not forwarded to the builder again
+    builder_fork_choice_state: Arc<Mutex<Option<ForkchoiceState>>>,
+    fork_choice_updates: Arc<ForkchoiceUpdateCache>,
+    fcu_forwarding: BuilderForwarding,
+    new_payload_forwarding: BuilderForwarding,
+    import_builder_blocks: bool,
+    get_payload_budget: Option<Duration>,
+    attributes_policy: Option<Arc<AttributesPolicy>>,
+    attributes_hooks: Vec<Arc<dyn AttributesHook>>,
+    block_value_threshold: Option<BlockValueThreshold>,
+    jwt_secrets: Vec<(PayloadSource, SharedJwtSecret)>,
+    builder_registry: Option<Arc<BuilderRegistry>>,
+    builder_quarantine: Option<Arc<BuilderQuarantine>>,
+    builder_scores: Option<Arc<BuilderScores>>,
+    payload_validator: Option<Arc<PayloadValidator>>,
+    warmup: Option<Arc<BuilderWarmup>>,
+    drain: Arc<DrainMode>,
+    head_lag: Arc<HeadLagMonitor>,
+    divergence: Option<Arc<DivergenceDetector>>,
+    leader_election: Option<Arc<LeaderElection>>,
+    chain_id: Option<Arc<ChainIdGuard>>,
+    audit_log: Arc<AuditLog>,
+    debug_auth: Option<Arc<DebugAuth>>,
+    webhooks: Option<Arc<WebhookNotifier>>,
+    fault_injector: Option<Arc<FaultInjector>>,
+    synthetic_flashblocks: Option<SyntheticFlashblocks>,
+    flashblocks_subscribers: Option<Arc<SubscriberRegistry>>,
+    payload_history: Arc<PayloadHistory>,
+    recent_flashblocks: Option<Arc<RecentFlashblocks>>,
+    payload_archive: Option<Arc<PayloadArchive>>,
+    builder_failed_over: Arc<AtomicBool>,
 }
 
 impl RollupBoostServer {
@@ -74,28 +152,474 @@ impl RollupBoostServer {
             l2_client: Arc::new(l2_client),
             builder_client,
             block_selection_policy,
+            payload_selector: None,
             payload_trace_context: Arc::new(PayloadTraceContext::new()),
             execution_mode: initial_execution_mode,
             probes,
+            head_tracker: Arc::new(Mutex::new(HeadTracker::new())),
+            last_fork_choice_state: Arc::new(Mutex::new(None)),
+            builder_fork_choice_state: Arc::new(Mutex::new(None)),
+            fork_choice_updates: Arc::new(ForkchoiceUpdateCache::default()),
+            fcu_forwarding: BuilderForwarding::default(),
+            new_payload_forwarding: BuilderForwarding::default(),
+            import_builder_blocks: false,
+            get_payload_budget: None,
+            attributes_policy: None,
+            attributes_hooks: vec![],
+            block_value_threshold: None,
+            jwt_secrets: vec![],
+            builder_registry: None,
+            builder_quarantine: None,
+            builder_scores: None,
+            payload_validator: None,
+            warmup: None,
+            drain: Arc::new(DrainMode::default()),
+            head_lag: Arc::new(HeadLagMonitor::default()),
+            divergence: None,
+            leader_election: None,
+            chain_id: None,
+            audit_log: Arc::new(AuditLog::default()),
+            debug_auth: None,
+            webhooks: None,
+            fault_injector: None,
+            synthetic_flashblocks: None,
+            flashblocks_subscribers: None,
+            payload_history: Arc::new(PayloadHistory::default()),
+            recent_flashblocks: None,
+            payload_archive: None,
+            builder_failed_over: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Makes selected builder blocks the head of the l2 client as soon as they are
+    /// returned, instead of waiting for the block to be gossiped back.
+    pub fn with_import_builder_blocks(mut self, import_builder_blocks: bool) -> Self {
+        self.import_builder_blocks = import_builder_blocks;
+        self
+    }
+
+    /// Stops waiting for the builder payload `budget` after `get_payload` was called, and
+    /// returns the l2 client payload instead, so that the sequencer gets a block in time.
+    pub fn with_get_payload_budget(mut self, budget: Option<Duration>) -> Self {
+        self.get_payload_budget = budget;
+        self
+    }
+
+    /// Sets how forkchoice updates without payload attributes, which only move the head,
+    /// are forwarded to the builder.
+    pub fn with_fcu_forwarding(mut self, fcu_forwarding: BuilderForwarding) -> Self {
+        self.fcu_forwarding = fcu_forwarding;
+        self
+    }
+
+    /// Sets how `new_payload` calls are forwarded to the builder, so that it imports the
+    /// canonical blocks before building on top of them. In sync mode, the status returned
+    /// by the builder is checked against the one of the L2 client.
+    pub fn with_new_payload_forwarding(
+        mut self,
+        new_payload_forwarding: BuilderForwarding,
+    ) -> Self {
+        self.new_payload_forwarding = new_payload_forwarding;
+        self
+    }
+
+    /// Validates the payload attributes before forwarding them to the builder.
+    pub fn with_attributes_policy(mut self, attributes_policy: Option<AttributesPolicy>) -> Self {
+        self.attributes_policy = attributes_policy.map(Arc::new);
+        self
+    }
+
+    /// Modifies the payload attributes forwarded to the builder with `hooks`, in order.
+    pub fn with_attributes_hooks(mut self, hooks: Vec<Arc<dyn AttributesHook>>) -> Self {
+        self.attributes_hooks = hooks;
+        self
+    }
+
+    /// Consults `selector` before the block selection policy to choose between the
+    /// builder and L2 client blocks.
+    pub fn with_payload_selector(mut self, selector: Option<Arc<dyn PayloadSelector>>) -> Self {
+        self.payload_selector = selector;
+        self
+    }
+
+    /// Only selects builder blocks whose block value is above the threshold.
+    pub fn with_block_value_threshold(
+        mut self,
+        block_value_threshold: Option<BlockValueThreshold>,
+    ) -> Self {
+        self.block_value_threshold = block_value_threshold;
+        self
+    }
+
+    /// Sets the JWT secrets of the upstreams that can be rotated through the debug API.
+    pub fn with_jwt_secrets(mut self, jwt_secrets: Vec<(PayloadSource, SharedJwtSecret)>) -> Self {
+        self.jwt_secrets = jwt_secrets;
+        self
+    }
+
+    /// Exposes the builder registry to the debug API, so that builders can be managed
+    /// at runtime. The registry is expected to be the builder client of the server.
+    pub fn with_builder_registry(mut self, builder_registry: Option<Arc<BuilderRegistry>>) -> Self {
+        self.builder_registry = builder_registry;
+        self
+    }
+
+    /// Counts the builder payloads rejected by the l2 client towards quarantining the
+    /// builder that built them.
+    pub fn with_builder_quarantine(
+        mut self,
+        builder_quarantine: Option<Arc<BuilderQuarantine>>,
+    ) -> Self {
+        self.builder_quarantine = builder_quarantine;
+        self
+    }
+
+    /// Scores the builders on the validity and value of their payloads, and only selects
+    /// the blocks of builders scoring above the minimum score.
+    pub fn with_builder_scores(mut self, builder_scores: Option<Arc<BuilderScores>>) -> Self {
+        self.builder_scores = builder_scores;
+        self
+    }
+
+    /// Re-executes the builder payloads accepted by the l2 client on a dedicated
+    /// validation client, and returns the l2 client payload instead of those it rejects.
+    pub fn with_payload_validator(
+        mut self,
+        payload_validator: Option<Arc<PayloadValidator>>,
+    ) -> Self {
+        self.payload_validator = payload_validator;
+        self
+    }
+
+    /// Compares the builder payloads with the l2 payload as in shadow mode after startup
+    /// and after the builder failed to return a payload, until the warm-up is complete.
+    pub fn with_warmup(mut self, warmup: Option<Arc<BuilderWarmup>>) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// Bypasses the builder while the safe or finalized head lags too far behind.
+    pub fn with_head_lag_monitor(mut self, head_lag: HeadLagMonitor) -> Self {
+        self.head_lag = Arc::new(head_lag);
+        self
+    }
+
+    /// Periodically compares the builder and l2 heads, and bypasses the builder while
+    /// they diverge.
+    pub fn with_divergence_detector(mut self, divergence: Option<DivergenceDetector>) -> Self {
+        self.divergence = divergence.map(|detector| {
+            let detector = Arc::new(detector);
+            detector
+                .clone()
+                .spawn(self.builder_client.clone(), self.l2_client.clone());
+            detector
+        });
+        self
+    }
+
+    /// Only forwards payload attributes to the builder while this instance is the leader
+    /// of an active/standby deployment.
+    pub fn with_leader_election(mut self, leader_election: Option<Arc<LeaderElection>>) -> Self {
+        self.leader_election = leader_election;
+        self
+    }
+
+    /// Periodically checks that the builder and l2 clients are on the configured chain.
+    /// The builder is bypassed while it is on another chain, and engine API calls are
+    /// refused while the l2 client is.
+    pub fn with_chain_id_guard(mut self, chain_id: Option<Arc<ChainIdGuard>>) -> Self {
+        if let Some(guard) = &chain_id {
+            guard
+                .clone()
+                .spawn(self.builder_client.clone(), self.l2_client.clone());
         }
+        self.chain_id = chain_id;
+        self
+    }
+
+    /// Records the execution mode changes made through the debug API in `audit_log`.
+    pub fn with_audit_log(mut self, audit_log: AuditLog) -> Self {
+        self.audit_log = Arc::new(audit_log);
+        self
+    }
+
+    /// Authenticates the callers of the debug API with bearer tokens.
+    pub fn with_debug_auth(mut self, debug_auth: Option<DebugAuth>) -> Self {
+        self.debug_auth = debug_auth.map(Arc::new);
+        self
+    }
+
+    /// Notifies webhooks of builder failovers, payload validation failures and execution
+    /// mode changes.
+    pub fn with_webhooks(mut self, webhooks: Option<Arc<WebhookNotifier>>) -> Self {
+        self.webhooks = webhooks;
+        self
+    }
+
+    /// Promotes the builder from the fallback-only boot mode once it is healthy. Must be
+    /// set after the audit log and webhooks the promotion is recorded in.
+    pub fn with_builder_promotion(self, promotion: Option<BuilderPromotion>) -> Self {
+        if let Some(promotion) = promotion {
+            Arc::new(promotion).spawn(
+                self.execution_mode.clone(),
+                self.probes.clone(),
+                self.audit_log.clone(),
+                self.webhooks.clone(),
+            );
+        }
+        self
+    }
+
+    /// Lets the debug API inject faults in the builder RPCs and flashblocks.
+    pub fn with_fault_injector(mut self, fault_injector: Option<Arc<FaultInjector>>) -> Self {
+        self.fault_injector = fault_injector;
+        self
+    }
+
+    /// Publishes synthetic flashblocks split from the returned payloads.
+    pub fn with_synthetic_flashblocks(
+        mut self,
+        synthetic_flashblocks: Option<SyntheticFlashblocks>,
+    ) -> Self {
+        self.synthetic_flashblocks = synthetic_flashblocks;
+        self
+    }
+
+    /// Lists the subscribers of the flashblocks websocket in the debug API.
+    pub fn with_flashblocks_subscribers(
+        mut self,
+        flashblocks_subscribers: Option<Arc<SubscriberRegistry>>,
+    ) -> Self {
+        self.flashblocks_subscribers = flashblocks_subscribers;
+        self
+    }
+
+    /// Keeps the last `size` payload cycles for `debug_getRecentPayloads`.
+    pub fn with_payload_history_size(mut self, size: usize) -> Self {
+        self.payload_history = Arc::new(PayloadHistory::new(size));
+        self
+    }
+
+    /// Serves the flashblocks received for the last payloads and the archived payloads in
+    /// `debug_getFlashblocks`.
+    pub fn with_flashblocks_stores(
+        mut self,
+        recent_flashblocks: Option<Arc<RecentFlashblocks>>,
+        payload_archive: Option<Arc<PayloadArchive>>,
+    ) -> Self {
+        self.recent_flashblocks = recent_flashblocks;
+        self.payload_archive = payload_archive;
+        self
     }
 
     pub async fn start_debug_server(&self, debug_addr: &str) -> eyre::Result<()> {
-        let server = DebugServer::new(self.execution_mode.clone());
+        let server = self.debug_server();
         server.run(debug_addr).await?;
         Ok(())
     }
 
+    /// Like [`Self::start_debug_server`], returning the handle of the debug server and
+    /// the address it listens on instead of detaching it.
+    pub async fn serve_debug_api(
+        &self,
+        debug_addr: &str,
+    ) -> eyre::Result<(ServerHandle, SocketAddr)> {
+        self.debug_server().start(debug_addr).await
+    }
+
+    pub fn start_debug_ipc_server(&self, endpoint: &IpcEndpoint) -> eyre::Result<()> {
+        self.debug_server().run_ipc(endpoint)
+    }
+
+    fn debug_server(&self) -> DebugServer {
+        DebugServer::new(self.execution_mode.clone())
+            .with_jwt_secrets(self.jwt_secrets.clone())
+            .with_builder_registry(self.builder_registry.clone())
+            .with_builder_quarantine(self.builder_quarantine.clone())
+            .with_builder_scores(self.builder_scores.clone())
+            .with_divergence_detector(self.divergence.clone())
+            .with_leader_election(self.leader_election.clone())
+            .with_drain_mode(self.drain.clone())
+            .with_audit_log(self.audit_log.clone())
+            .with_auth(self.debug_auth.clone())
+            .with_webhooks(self.webhooks.clone())
+            .with_fault_injector(self.fault_injector.clone())
+            .with_flashblocks_subscribers(self.flashblocks_subscribers.clone())
+            .with_payload_history(Some(self.payload_history.clone()))
+            .with_flashblocks_stores(
+                self.recent_flashblocks.clone(),
+                self.payload_archive.clone(),
+            )
+    }
+
     pub fn execution_mode(&self) -> ExecutionMode {
         *self.execution_mode.lock()
     }
 
+    /// Refuses to serve the engine API while the l2 client is on another chain.
+    fn check_chain_id(&self) -> RpcResult<()> {
+        match &self.chain_id {
+            Some(guard) if guard.l2_mismatch() => Err(ErrorObject::owned(
+                INTERNAL_ERROR_CODE,
+                format!("l2 client is not on chain {}", guard.expected()),
+                None::<String>,
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn notify(&self, event: WebhookEvent, message: String, details: serde_json::Value) {
+        if let Some(webhooks) = &self.webhooks {
+            webhooks.notify(event, message, details);
+        }
+    }
+
+    /// Notifies the webhooks when blocks start being built by the l2 client because the
+    /// builder request failed, once per failover.
+    fn record_builder_failover(&self, failed_over: bool, payload_id: PayloadId) {
+        if self
+            .builder_failed_over
+            .swap(failed_over, Ordering::Relaxed)
+            == failed_over
+        {
+            return;
+        }
+        if failed_over {
+            if let Some(warmup) = &self.warmup {
+                warmup.restart();
+            }
+            self.notify(
+                WebhookEvent::BuilderFailover,
+                "builder failed to return a payload, building blocks with the l2 client"
+                    .to_string(),
+                serde_json::json!({ "payload_id": payload_id }),
+            );
+        } else {
+            info!(message = "builder is returning payloads again", %payload_id);
+        }
+    }
+
+    /// Notifies and records an invalid builder payload against the builder that built it,
+    /// before falling back to the l2 client payload.
+    fn record_invalid_builder_payload(
+        &self,
+        payload_id: PayloadId,
+        failure: ValidationFailure,
+        message: String,
+    ) {
+        let builder = self.builder_client.last_payload_builder();
+        self.notify(
+            WebhookEvent::PayloadValidationFailed,
+            message,
+            serde_json::json!({
+                "payload_id": payload_id,
+                "reason": failure.as_str(),
+                "builder": builder,
+            }),
+        );
+        if let Some(quarantine) = &self.builder_quarantine {
+            quarantine.record_failure(&builder, failure);
+        }
+        if let Some(warmup) = &self.warmup {
+            warmup.record(false);
+        }
+        if let Some(scores) = &self.builder_scores {
+            scores.record(&builder, ScoreObservation::Valid(false));
+        }
+    }
+
+    /// Sends a FCU to the l2 client setting the builder block as the new head. The block
+    /// was already imported with `new_payload` when validating the builder payload.
+    fn import_builder_block(&self, block_hash: B256) {
+        let Some(mut fork_choice_state) = *self.last_fork_choice_state.lock() else {
+            return;
+        };
+        fork_choice_state.head_block_hash = block_hash;
+
+        let l2_client = self.l2_client.clone();
+        tokio::spawn(
+            async move {
+                if let Err(e) = l2_client
+                    .fork_choice_updated_v3(fork_choice_state, None)
+                    .await
+                {
+                    warn!(message = "failed to import builder block", %block_hash, error = %e);
+                }
+            }
+            .in_current_span(),
+        );
+    }
+
+    /// Publishes the returned payload as synthetic flashblocks, if enabled.
+    fn publish_synthetic_flashblocks(
+        &self,
+        payload_id: PayloadId,
+        payload: &OpExecutionPayloadEnvelope,
+    ) {
+        if let Some(synthetic_flashblocks) = &self.synthetic_flashblocks {
+            synthetic_flashblocks.publish(payload_id, payload);
+        }
+    }
+
+    /// Records a served `get_payload` call in the payload history.
+    fn record_payload_cycle(
+        &self,
+        payload_id: PayloadId,
+        payload: &OpExecutionPayloadEnvelope,
+        source: BlockSource,
+        fallback_reason: Option<&str>,
+        requested_at: SystemTime,
+        started: Instant,
+        l2_latency: Duration,
+        builder_latency: Option<Duration>,
+    ) {
+        self.payload_history.record(PayloadCycle {
+            payload_id,
+            block_number: payload.block_number(),
+            block_hash: payload.block_hash(),
+            block_value: payload.block_value(),
+            source: source.as_str().to_string(),
+            fallback_reason: fallback_reason.map(str::to_string),
+            flashblocks: match source {
+                BlockSource::Flashblocks => self.builder_client.last_payload_flashblocks(),
+                _ => 0,
+            },
+            requested_at: requested_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            l2_latency_ms: l2_latency.as_millis() as u64,
+            builder_latency_ms: builder_latency.map(|latency| latency.as_millis() as u64),
+            total_latency_ms: started.elapsed().as_millis() as u64,
+        });
+    }
+
+    /// Records which source the returned block came from. The `last_block_source` gauge
+    /// is set to 1 for the source of the latest block and 0 for all others.
+    fn record_block_source(&self, source: BlockSource) {
+        counter!("rpc.block_source", "source" => source.as_str()).increment(1);
+        for s in BlockSource::ALL {
+            gauge!("rpc.last_block_source", "source" => s.as_str()).set(if s == source {
+                1.0
+            } else {
+                0.0
+            });
+        }
+    }
+
     async fn new_payload(&self, new_payload: NewPayload) -> RpcResult<PayloadStatus> {
+        self.check_chain_id()?;
         let execution_payload = ExecutionPayload::from(new_payload.clone());
         let block_hash = execution_payload.block_hash();
         let parent_hash = execution_payload.parent_hash();
         info!(message = "received new_payload", "block_hash" = %block_hash, "version" = new_payload.version().as_str());
 
+        self.head_tracker.lock().insert_block(
+            block_hash,
+            execution_payload.block_number(),
+            parent_hash,
+        );
+
         if let Some(causes) = self
             .payload_trace_context
             .trace_ids_from_parent_hash(&parent_hash)
@@ -110,13 +634,45 @@ impl RollupBoostServer {
             .remove_by_parent_hash(&parent_hash)
             .await;
 
-        // async call to builder to sync the builder node
-        if !self.execution_mode().is_disabled() {
-            let builder = self.builder_client.clone();
-            let new_payload_clone = new_payload.clone();
-            tokio::spawn(async move { builder.new_payload(new_payload_clone).await });
+        if self.execution_mode().is_disabled()
+            || self.new_payload_forwarding == BuilderForwarding::Disabled
+        {
+            return Ok(self.l2_client.new_payload(new_payload).await?);
+        }
+
+        // call the builder to sync the builder node
+        let builder = self.builder_client.clone();
+        let new_payload_clone = new_payload.clone();
+        let builder_fut = async move {
+            let result = builder.new_payload(new_payload_clone).await;
+            if let Err(e) = &result {
+                counter!("rpc.builder_forwarding_errors", "method" => "engine_newPayload")
+                    .increment(1);
+                warn!(message = "builder failed to import the block", %block_hash, error = %e);
+            }
+            result
+        };
+
+        if self.new_payload_forwarding == BuilderForwarding::Async {
+            tokio::spawn(builder_fut.in_current_span());
+            return Ok(self.l2_client.new_payload(new_payload).await?);
+        }
+
+        let (l2_result, builder_result) =
+            tokio::join!(self.l2_client.new_payload(new_payload), builder_fut);
+        let l2_status = l2_result?;
+        let builder_status = builder_result?;
+        if builder_status.status.as_str() != l2_status.status.as_str() {
+            counter!("rpc.builder_new_payload_mismatch", "builder_status" => builder_status.status.as_str())
+                .increment(1);
+            warn!(
+                message = "builder and l2 client disagree on the block",
+                %block_hash,
+                l2_status = %l2_status.status,
+                builder_status = %builder_status.status,
+            );
         }
-        Ok(self.l2_client.new_payload(new_payload).await?)
+        Ok(l2_status)
     }
 
     async fn get_payload(
@@ -124,17 +680,48 @@ impl RollupBoostServer {
         payload_id: PayloadId,
         version: PayloadVersion,
     ) -> RpcResult<OpExecutionPayloadEnvelope> {
-        let l2_fut = self.l2_client.get_payload(payload_id, version);
+        // Serve the call under the correlation id of the forkchoice update that started
+        // the payload, so the logs and upstream calls of the whole flow can be joined
+        let correlation_id = match self.payload_trace_context.correlation_id(&payload_id).await {
+            Some(correlation_id) => correlation_id,
+            None => CorrelationId::current().unwrap_or_default(),
+        };
+        tracing::Span::current().record("correlation_id", correlation_id.as_str());
+        correlation_id
+            .scope(self.serve_get_payload(payload_id, version))
+            .await
+    }
+
+    async fn serve_get_payload(
+        &self,
+        payload_id: PayloadId,
+        version: PayloadVersion,
+    ) -> RpcResult<OpExecutionPayloadEnvelope> {
+        self.check_chain_id()?;
+        self.fork_choice_updates.clear(Some(payload_id));
+        let _cycle = self.drain.serve_cycle(payload_id);
+        // A dry run requested for this cycle, on the forkchoice update or this call
+        let dry_run_override =
+            DryRunOverride::requested() || self.payload_trace_context.is_dry_run(&payload_id).await;
+        // Set if the builder was deliberately not asked to build this payload
+        let bypass_reason = self.payload_trace_context.bypass_reason(&payload_id).await;
+        let requested_at = SystemTime::now();
+        let started = Instant::now();
+        let l2_fut = async {
+            let payload = self.l2_client.get_payload(payload_id, version).await;
+            (payload, started.elapsed())
+        };
 
         // If execution mode is disabled, return the l2 payload without sending
         // the request to the builder
         if self.execution_mode().is_disabled() {
             return match l2_fut.await {
-                Ok(payload) => {
+                (Ok(payload), l2_latency) => {
                     self.probes.set_health(Health::Healthy);
                     let context = PayloadSource::L2;
                     tracing::Span::current().record("payload_source", context.to_string());
                     counter!("rpc.blocks_created", "source" => context.to_string()).increment(1);
+                    self.record_block_source(BlockSource::L2);
 
                     let execution_payload = ExecutionPayload::from(payload.clone());
                     info!(
@@ -144,17 +731,32 @@ impl RollupBoostServer {
                         %context,
                         %payload_id,
                     );
+                    self.publish_synthetic_flashblocks(payload_id, &payload);
+                    self.record_payload_cycle(
+                        payload_id,
+                        &payload,
+                        BlockSource::L2,
+                        Some("disabled"),
+                        requested_at,
+                        started,
+                        l2_latency,
+                        None,
+                    );
 
                     Ok(payload)
                 }
 
-                Err(e) => {
+                (Err(e), _) => {
                     self.probes.set_health(Health::ServiceUnavailable);
                     Err(e.into())
                 }
             };
         }
 
+        // Set when the builder payload carries a denied transaction, which is a policy
+        // decision rather than a builder failure
+        let builder_denied = AtomicBool::new(false);
+
         // Forward the get payload request to the builder
         let builder_fut = async {
             if let Some(cause) = self.payload_trace_context.trace_id(&payload_id).await {
@@ -172,24 +774,129 @@ impl RollupBoostServer {
 
             // Get payload and validate with the local l2 client
             tracing::Span::current().record("builder_has_payload", true);
-            let payload = self.builder_client.get_payload(payload_id, version).await?;
-            let _ = self
+            let builder_payload_id = self
+                .payload_trace_context
+                .builder_payload_id(&payload_id)
+                .await;
+            let payload = match self
+                .builder_client
+                .get_payload(builder_payload_id, version)
+                .await
+            {
+                Ok(payload) => payload,
+                Err(RpcClientError::Flashblocks(FlashblocksError::DeniedTransaction(e))) => {
+                    warn!(message = "builder payload contains a denied transaction", error = %e);
+                    builder_denied.store(true, Ordering::Relaxed);
+                    return RpcResult::Ok(None);
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            // Refuse builder payloads that do not honour the payload attributes
+            if let Some(expected) = self
+                .payload_trace_context
+                .expected_payload(&payload_id)
+                .await
+            {
+                if let Err(e) = expected.validate(&payload) {
+                    counter!("rpc.builder_payload_mismatch", "field" => e.field()).increment(1);
+                    warn!(message = "builder payload does not match the payload attributes", error = %e);
+                    self.record_invalid_builder_payload(
+                        payload_id,
+                        ValidationFailure::AttributesMismatch,
+                        format!("builder payload does not match the payload attributes: {e}"),
+                    );
+                    return RpcResult::Ok(None);
+                }
+            }
+
+            let status = self
                 .l2_client
                 .new_payload(NewPayload::from(payload.clone()))
                 .await?;
 
+            // Refuse builder payloads that the l2 client deems invalid
+            if let PayloadStatusEnum::Invalid { validation_error } = &status.status {
+                let failure = if is_block_hash_error(validation_error) {
+                    ValidationFailure::BlockHash
+                } else {
+                    ValidationFailure::RejectedByL2
+                };
+                warn!(message = "builder payload rejected by the l2 client", reason = failure.as_str(), %validation_error);
+                self.record_invalid_builder_payload(
+                    payload_id,
+                    failure,
+                    format!("builder payload rejected by the l2 client: {validation_error}"),
+                );
+                return RpcResult::Ok(None);
+            }
+
+            // Re-execute the payload on the validation client, trading latency for
+            // safety. The payload is returned if the validation client cannot tell, as
+            // the l2 client accepted it
+            let revalidation = match &self.payload_validator {
+                Some(validator) => validator.validate(&payload).await,
+                None => Revalidation::Valid,
+            };
+            if let Revalidation::Invalid(validation_error) = revalidation {
+                warn!(message = "builder payload rejected by the validation client", %validation_error);
+                self.record_invalid_builder_payload(
+                    payload_id,
+                    ValidationFailure::RejectedByValidator,
+                    format!(
+                        "builder payload rejected by the validation client: {validation_error}"
+                    ),
+                );
+                return RpcResult::Ok(None);
+            }
+
+            if let Some(warmup) = &self.warmup {
+                warmup.record(true);
+            }
+            if let Some(scores) = &self.builder_scores {
+                scores.record(
+                    &self.builder_client.last_payload_builder(),
+                    ScoreObservation::Valid(true),
+                );
+            }
             Ok(Some(payload))
         };
+        let builder_fut = async move {
+            // `None` if the builder did not return a payload within the latency budget
+            let payload = match self.get_payload_budget {
+                Some(budget) => {
+                    let deadline = tokio::time::Instant::from_std(started + budget);
+                    tokio::time::timeout_at(deadline, builder_fut).await.ok()
+                }
+                None => Some(builder_fut.await),
+            };
+            (payload, started.elapsed())
+        };
+
+        let ((l2_payload, l2_latency), (builder_payload, builder_latency)) =
+            tokio::join!(l2_fut, builder_fut);
 
-        let (l2_payload, builder_payload) = tokio::join!(l2_fut, builder_fut);
+        let budget_exceeded = builder_payload.is_none();
+        if budget_exceeded {
+            counter!("rpc.get_payload_budget_exceeded").increment(1);
+            warn!(
+                message = "builder payload not received within the latency budget, falling back to the l2 payload",
+                %payload_id,
+                elapsed_ms = builder_latency.as_millis() as u64,
+            );
+        }
+        let builder_payload = builder_payload.unwrap_or(Ok(None));
 
-        // Evaluate the builder and l2 response and select the final payload
-        let (payload, context) = {
+        // Evaluate the builder and l2 response and select the final payload, along with
+        // the reason for falling back to the l2 payload
+        let (payload, context, fallback_reason) = {
             let l2_payload =
                 l2_payload.inspect_err(|_| self.probes.set_health(Health::ServiceUnavailable))?;
             self.probes.set_health(Health::Healthy);
 
             if let Ok(Some(builder_payload)) = builder_payload {
+                self.record_builder_failover(false, payload_id);
+
                 // Record the delta (gas and txn) between the builder and l2 payload
                 let span = tracing::Span::current();
                 span.record(
@@ -201,22 +908,90 @@ impl RollupBoostServer {
                     (builder_payload.tx_count() - l2_payload.tx_count()).to_string(),
                 );
 
-                // If execution mode is set to DryRun, fallback to the l2_payload,
-                // otherwise prefer the builder payload
-                if self.execution_mode().is_dry_run() {
-                    (l2_payload, PayloadSource::L2)
+                let builder = self.builder_client.last_payload_builder();
+                if let Some(scores) = &self.builder_scores {
+                    scores.record(
+                        &builder,
+                        ScoreObservation::Value(
+                            builder_payload.block_value() >= l2_payload.block_value(),
+                        ),
+                    );
+                }
+
+                // If execution mode is set to Shadow, compare both payloads and fallback
+                // to the l2_payload. If execution mode is set to DryRun, fallback to the
+                // l2_payload, otherwise prefer the builder payload
+                if self.execution_mode().is_shadow() {
+                    PayloadComparison::new(&builder_payload, &l2_payload).record();
+                    (l2_payload, PayloadSource::L2, Some("shadow"))
+                } else if self.execution_mode().is_dry_run() {
+                    (l2_payload, PayloadSource::L2, Some("dry_run"))
+                } else if dry_run_override {
+                    info!(message = "dry run requested, returning the l2 payload", %payload_id);
+                    (l2_payload, PayloadSource::L2, Some("dry_run_override"))
+                } else if self.warmup.as_ref().is_some_and(|w| w.is_warming_up()) {
+                    PayloadComparison::new(&builder_payload, &l2_payload).record();
+                    (l2_payload, PayloadSource::L2, Some("warmup"))
+                } else if self
+                    .builder_scores
+                    .as_ref()
+                    .is_some_and(|scores| !scores.accepts(&builder))
+                {
+                    (l2_payload, PayloadSource::L2, Some("builder_score"))
+                } else if self
+                    .block_value_threshold
+                    .is_some_and(|threshold| !threshold.accepts(&builder_payload, &l2_payload))
+                {
+                    (l2_payload, PayloadSource::L2, Some("block_value_threshold"))
+                } else if let Some((selector, source)) =
+                    self.payload_selector.as_ref().and_then(|selector| {
+                        selector
+                            .select(&builder_payload, &l2_payload)
+                            .map(|source| (selector, source))
+                    })
+                {
+                    counter!(
+                        "rpc.payload_selector_selected",
+                        "selector" => selector.name(),
+                        "source" => source.to_string(),
+                    )
+                    .increment(1);
+                    match source {
+                        PayloadSource::Builder => (builder_payload, source, None),
+                        PayloadSource::L2 => (l2_payload, source, Some("selector")),
+                    }
                 } else if let Some(selection_policy) = &self.block_selection_policy {
-                    selection_policy.select_block(builder_payload, l2_payload)
+                    let (payload, source) =
+                        selection_policy.select_block(builder_payload, l2_payload);
+                    let fallback_reason = (!source.is_builder()).then_some("selection_policy");
+                    (payload, source, fallback_reason)
                 } else {
-                    (builder_payload, PayloadSource::Builder)
+                    (builder_payload, PayloadSource::Builder, None)
                 }
+            } else if let Some(bypass_reason) = bypass_reason {
+                // The builder was not asked for a payload, which is not a builder failure
+                (l2_payload, PayloadSource::L2, Some(bypass_reason))
+            } else if builder_denied.load(Ordering::Relaxed) {
+                // The payload was refused by the denylist, which says nothing about the
+                // health of the builder
+                (l2_payload, PayloadSource::L2, Some("denylist"))
             } else {
                 // Only update the health status if the builder payload fails
-                // and execution mode is not set to DryRun
-                if !self.execution_mode().is_dry_run() {
+                // and execution mode is not set to DryRun or Shadow, or for this cycle
+                let execution_mode = self.execution_mode();
+                if !execution_mode.is_dry_run() && !execution_mode.is_shadow() && !dry_run_override
+                {
                     self.probes.set_health(Health::PartialContent);
                 }
-                (l2_payload, PayloadSource::L2)
+                let fallback_reason = if budget_exceeded {
+                    "latency_budget"
+                } else if builder_payload.is_err() {
+                    self.record_builder_failover(true, payload_id);
+                    "builder_error"
+                } else {
+                    "builder_no_payload"
+                };
+                (l2_payload, PayloadSource::L2, Some(fallback_reason))
             }
         };
 
@@ -224,11 +999,20 @@ impl RollupBoostServer {
         // To maintain backwards compatibility with old metrics, we need to record blocks built
         // This is temporary until we migrate to the new metrics
         counter!("rpc.blocks_created", "source" => context.to_string()).increment(1);
+        let block_source = match context {
+            PayloadSource::L2 => BlockSource::L2,
+            PayloadSource::Builder => self.builder_client.last_payload_source(),
+        };
+        self.record_block_source(block_source);
 
         let inner_payload = ExecutionPayload::from(payload.clone());
         let block_hash = inner_payload.block_hash();
         let block_number = inner_payload.block_number();
 
+        if self.import_builder_blocks && context.is_builder() {
+            self.import_builder_block(block_hash);
+        }
+
         // Note: This log message is used by integration tests to track payload context.
         // While not ideal to rely on log parsing, it provides a reliable way to verify behavior.
         // Happy to consider an alternative approach later on.
@@ -239,10 +1023,28 @@ impl RollupBoostServer {
             %context,
             %payload_id,
         );
+        self.publish_synthetic_flashblocks(payload_id, &payload);
+        self.record_payload_cycle(
+            payload_id,
+            &payload,
+            block_source,
+            fallback_reason,
+            requested_at,
+            started,
+            l2_latency,
+            Some(builder_latency),
+        );
         Ok(payload)
     }
 }
 
+/// Whether the validation error of an invalid payload status reports a block hash that
+/// does not match the payload.
+fn is_block_hash_error(validation_error: &str) -> bool {
+    let validation_error = validation_error.to_lowercase();
+    validation_error.contains("block hash") || validation_error.contains("blockhash")
+}
+
 impl TryInto<RpcModule<()>> for RollupBoostServer {
     type Error = RegisterMethodError;
 
@@ -258,6 +1060,16 @@ impl TryInto<RpcModule<()>> for RollupBoostServer {
     }
 }
 
+/// Error returned when a client returned a payload of another version than the one
+/// requested.
+fn unsupported_version(version: PayloadVersion) -> ErrorObject<'static> {
+    ErrorObject::owned(
+        INVALID_REQUEST_CODE,
+        format!("Payload version {} not supported", version.as_str()),
+        None::<String>,
+    )
+}
+
 #[rpc(server, client)]
 pub trait EngineApi {
     #[method(name = "engine_forkchoiceUpdatedV3")]
@@ -267,6 +1079,18 @@ pub trait EngineApi {
         payload_attributes: Option<OpPayloadAttributes>,
     ) -> RpcResult<ForkchoiceUpdated>;
 
+    #[method(name = "engine_getPayloadV1")]
+    async fn get_payload_v1(&self, payload_id: PayloadId) -> RpcResult<ExecutionPayloadV1>;
+
+    #[method(name = "engine_newPayloadV1")]
+    async fn new_payload_v1(&self, payload: ExecutionPayloadV1) -> RpcResult<PayloadStatus>;
+
+    #[method(name = "engine_getPayloadV2")]
+    async fn get_payload_v2(&self, payload_id: PayloadId) -> RpcResult<ExecutionPayloadEnvelopeV2>;
+
+    #[method(name = "engine_newPayloadV2")]
+    async fn new_payload_v2(&self, payload: ExecutionPayloadInputV2) -> RpcResult<PayloadStatus>;
+
     #[method(name = "engine_getPayloadV3")]
     async fn get_payload_v3(
         &self,
@@ -296,6 +1120,22 @@ pub trait EngineApi {
         execution_requests: Vec<Bytes>,
     ) -> RpcResult<PayloadStatus>;
 
+    #[method(name = "engine_getPayloadBodiesByHashV1")]
+    async fn get_payload_bodies_by_hash_v1(
+        &self,
+        block_hashes: Vec<B256>,
+    ) -> RpcResult<ExecutionPayloadBodiesV1>;
+
+    #[method(name = "engine_getPayloadBodiesByRangeV1")]
+    async fn get_payload_bodies_by_range_v1(
+        &self,
+        start: U64,
+        count: U64,
+    ) -> RpcResult<ExecutionPayloadBodiesV1>;
+
+    #[method(name = "engine_exchangeCapabilities")]
+    async fn exchange_capabilities(&self, capabilities: Vec<String>) -> RpcResult<Vec<String>>;
+
     #[method(name = "eth_getBlockByNumber")]
     async fn get_block_by_number(&self, number: BlockNumberOrTag, full: bool) -> RpcResult<Block>;
 }
@@ -309,7 +1149,8 @@ impl EngineApiServer for RollupBoostServer {
             otel.kind = ?SpanKind::Server,
             head_block_hash = %fork_choice_state.head_block_hash,
             timestamp = ?payload_attributes.as_ref().map(|attrs| attrs.payload_attributes.timestamp),
-            payload_id
+            payload_id,
+            correlation_id,
         )
     )]
     async fn fork_choice_updated_v3(
@@ -317,21 +1158,117 @@ impl EngineApiServer for RollupBoostServer {
         fork_choice_state: ForkchoiceState,
         payload_attributes: Option<OpPayloadAttributes>,
     ) -> RpcResult<ForkchoiceUpdated> {
-        // Send the FCU to the default l2 client
-        let l2_fut = self
-            .l2_client
-            .fork_choice_updated_v3(fork_choice_state, payload_attributes.clone());
+        self.check_chain_id()?;
+        if let Some(correlation_id) = CorrelationId::current() {
+            tracing::Span::current().record("correlation_id", correlation_id.as_str());
+        }
+        let previous_state = self
+            .last_fork_choice_state
+            .lock()
+            .replace(fork_choice_state);
+
+        // Track how far the safe and finalized heads lag behind whenever they move
+        if previous_state != Some(fork_choice_state) {
+            let head_lag = self.head_lag.clone();
+            let l2_client = self.l2_client.clone();
+            tokio::spawn(async move {
+                if let Err(e) = head_lag.refresh(&l2_client).await {
+                    debug!(message = "failed to refresh the head lag", error = %e);
+                }
+            });
+        }
+
+        // Discard any builder state built on top of the previous head if it was reorged
+        let reorg = self
+            .head_tracker
+            .lock()
+            .update_head(fork_choice_state.head_block_hash);
+        if let Some(event) = reorg {
+            event.record();
+            self.builder_client.on_reorg(&event).await;
+        }
 
         // If execution mode is disabled, return the l2 client response immediately
         if self.execution_mode().is_disabled() {
-            return Ok(l2_fut.await?);
+            return Ok(self
+                .l2_client
+                .fork_choice_updated_v3(fork_choice_state, payload_attributes)
+                .await?);
         }
 
+        // Retries of a forkchoice update that started building a payload get the same
+        // response, instead of restarting the payload building
+        let original_attributes = match &payload_attributes {
+            Some(attrs) => {
+                if let Some(response) = self.fork_choice_updates.get(&fork_choice_state, attrs) {
+                    counter!("rpc.fcu_deduplicated", "attributes" => "true").increment(1);
+                    info!(message = "deduplicated forkchoice update", payload_id = ?response.payload_id);
+                    return Ok(response);
+                }
+                Some(attrs.clone())
+            }
+            None => {
+                self.fork_choice_updates.clear(None);
+                None
+            }
+        };
+
+        // Out of policy attributes are either rewritten before being sent to the
+        // builder, or only sent to the default l2 client. The default l2 client always
+        // builds on the attributes it was sent
+        let mut policy_rejected = false;
+        let policy_attributes = match (&payload_attributes, self.attributes_policy.as_ref()) {
+            (Some(attrs), Some(policy)) => match policy.apply(attrs.clone()) {
+                PolicyOutcome::Accepted(attrs) => Some(attrs),
+                PolicyOutcome::Rejected(_) => {
+                    policy_rejected = true;
+                    None
+                }
+            },
+            (attrs, _) => attrs.clone(),
+        };
+
+        // Send the FCU to the default l2 client
+        let l2_fut = self
+            .l2_client
+            .fork_choice_updated_v3(fork_choice_state, payload_attributes.clone());
+
         let span = tracing::Span::current();
-        // If the fcu contains payload attributes and the tx pool is disabled,
-        // only forward the FCU to the default l2 client
+        // If the fcu contains payload attributes and the tx pool is disabled, the
+        // attributes were rejected by the policy, the safe head lags too far behind or
+        // the builder head diverged or is on another chain, or if this instance is on
+        // standby or draining, only forward the FCU to the default l2 client
         if let Some(attrs) = payload_attributes.as_ref() {
-            if attrs.no_tx_pool.unwrap_or_default() {
+            let no_tx_pool = attrs.no_tx_pool.unwrap_or_default();
+            let lag_exceeded = self.head_lag.lag_exceeded();
+            let diverged = self.divergence.as_ref().is_some_and(|d| d.is_diverged());
+            let standby = self
+                .leader_election
+                .as_ref()
+                .is_some_and(|leader| !leader.is_leader())
+                || self.drain.is_draining();
+            let wrong_chain = self.chain_id.as_ref().is_some_and(|g| g.builder_mismatch());
+            let bypass_reason = if no_tx_pool {
+                Some("no_tx_pool")
+            } else if policy_rejected {
+                Some("attributes_policy")
+            } else if lag_exceeded {
+                Some("head_lag")
+            } else if diverged {
+                Some("builder_diverged")
+            } else if wrong_chain {
+                Some("builder_wrong_chain")
+            } else if self.drain.is_draining() {
+                Some("draining")
+            } else if standby {
+                Some("standby")
+            } else {
+                None
+            };
+            if let Some(bypass_reason) = bypass_reason {
+                if no_tx_pool {
+                    counter!("rpc.no_tx_pool_blocks").increment(1);
+                }
                 let l2_response = l2_fut.await?;
                 if let Some(payload_id) = l2_response.payload_id {
                     info!(
@@ -344,56 +1281,129 @@ impl EngineApiServer for RollupBoostServer {
                         .store(
                             payload_id,
                             fork_choice_state.head_block_hash,
-                            false,
-                            span.id(),
+                            PayloadTrace {
+                                builder_has_payload: false,
+                                trace_id: span.id(),
+                                expected_payload: None,
+                                dry_run: DryRunOverride::requested(),
+                                bypass_reason: Some(bypass_reason),
+                                builder_payload_id: None,
+                                correlation_id: CorrelationId::current(),
+                            },
                         )
                         .await;
                 }
 
+                if let Some(attrs) = original_attributes {
+                    self.fork_choice_updates
+                        .insert(fork_choice_state, attrs, &l2_response);
+                }
+
                 // We always return the value from the l2 client
                 return Ok(l2_response);
             } else {
                 // If the tx pool is enabled, forward the fcu
                 // to both the builder and the default l2 client
+                let builder_attributes = apply_attributes_hooks(
+                    &self.attributes_hooks,
+                    policy_attributes.unwrap_or_else(|| attrs.clone()),
+                );
+                let expected_payload = ExpectedPayload::from(&builder_attributes);
                 let builder_fut = self
                     .builder_client
-                    .fork_choice_updated_v3(fork_choice_state, payload_attributes);
+                    .fork_choice_updated_v3(fork_choice_state, Some(builder_attributes));
 
                 let (l2_result, builder_result) = tokio::join!(l2_fut, builder_fut);
+                if builder_result.is_ok() {
+                    *self.builder_fork_choice_state.lock() = Some(fork_choice_state);
+                }
                 let l2_response = l2_result?;
 
                 if let Some(payload_id) = l2_response.payload_id {
+                    if builder_result.is_ok() {
+                        self.drain.start_cycle(payload_id);
+                    }
+                    // The builder still builds a dry run payload, which is not returned
+                    let dry_run = DryRunOverride::requested();
+                    // The builder derives another payload ID if its attributes were
+                    // rewritten, e.g. with another gas limit
+                    let builder_payload_id = builder_result
+                        .as_ref()
+                        .ok()
+                        .and_then(|response| response.payload_id);
                     info!(
                         message = "block building started",
                         "payload_id" = %payload_id,
                         "builder_building" = builder_result.is_ok(),
+                        ?builder_payload_id,
+                        dry_run,
                     );
 
                     self.payload_trace_context
                         .store(
                             payload_id,
                             fork_choice_state.head_block_hash,
-                            builder_result.is_ok(),
-                            span.id(),
+                            PayloadTrace {
+                                builder_has_payload: builder_result.is_ok(),
+                                trace_id: span.id(),
+                                expected_payload: Some(expected_payload),
+                                dry_run,
+                                bypass_reason: None,
+                                builder_payload_id,
+                                correlation_id: CorrelationId::current(),
+                            },
                         )
                         .await;
                 }
 
+                if let Some(attrs) = original_attributes {
+                    self.fork_choice_updates
+                        .insert(fork_choice_state, attrs, &l2_response);
+                }
+
                 return Ok(l2_response);
             }
         } else {
+            // The builder already follows this head if it acknowledged the same FCU. FCUs
+            // that bypassed the builder or that it failed are forwarded again
+            if *self.builder_fork_choice_state.lock() == Some(fork_choice_state) {
+                counter!("rpc.fcu_deduplicated", "attributes" => "false").increment(1);
+                return Ok(l2_fut.await?);
+            }
+
             // If the FCU does not contain payload attributes
-            // forward the fcu to the builder to keep it synced and immediately return the l2
-            // response without awaiting the builder
+            // forward the fcu to the builder to keep it synced
             let builder_client = self.builder_client.clone();
-            tokio::spawn(async move {
-                // It is not critical to wait for the builder response here
-                // During moments of high load, Op-node can send hundreds of FCU requests
-                // and we want to ensure that we don't block the main thread in those scenarios
-                builder_client
+            let builder_fork_choice_state = self.builder_fork_choice_state.clone();
+            let builder_fut = async move {
+                let result = builder_client
                     .fork_choice_updated_v3(fork_choice_state, payload_attributes)
-                    .await
-            });
+                    .await;
+                match &result {
+                    Ok(_) => *builder_fork_choice_state.lock() = Some(fork_choice_state),
+                    Err(e) => {
+                        counter!("rpc.builder_forwarding_errors", "method" => "engine_forkchoiceUpdatedV3")
+                            .increment(1);
+                        warn!(message = "builder failed to follow the head", error = %e);
+                    }
+                }
+                result
+            };
+            match self.fcu_forwarding {
+                BuilderForwarding::Async => {
+                    // It is not critical to wait for the builder response here
+                    // During moments of high load, Op-node can send hundreds of FCU requests
+                    // and we want to ensure that we don't block the main thread in those scenarios
+                    tokio::spawn(builder_fut.in_current_span());
+                }
+                BuilderForwarding::Sync => {
+                    let (l2_result, builder_result) = tokio::join!(l2_fut, builder_fut);
+                    let l2_response = l2_result?;
+                    builder_result?;
+                    return Ok(l2_response);
+                }
+                BuilderForwarding::Disabled => {}
+            }
             return Ok(l2_fut.await?);
         }
     }
@@ -404,25 +1414,92 @@ impl EngineApiServer for RollupBoostServer {
         fields(
             otel.kind = ?SpanKind::Server,
             %payload_id,
+            correlation_id,
             payload_source,
             gas_delta,
             tx_count_delta,
             builder_has_payload,
         )
     )]
-    async fn get_payload_v3(
-        &self,
+    async fn get_payload_v1(&self, payload_id: PayloadId) -> RpcResult<ExecutionPayloadV1> {
+        info!("received get_payload_v1");
+
+        match self.get_payload(payload_id, PayloadVersion::V1).await? {
+            OpExecutionPayloadEnvelope::V1(v1) => Ok(v1),
+            payload => Err(unsupported_version(payload.version())),
+        }
+    }
+
+    #[instrument(
+        skip_all,
+        err,
+        fields(
+            otel.kind = ?SpanKind::Server,
+        )
+    )]
+    async fn new_payload_v1(&self, payload: ExecutionPayloadV1) -> RpcResult<PayloadStatus> {
+        info!("received new_payload_v1");
+
+        self.new_payload(NewPayload::V1(payload)).await
+    }
+
+    #[instrument(
+        skip_all,
+        err,
+        fields(
+            otel.kind = ?SpanKind::Server,
+            %payload_id,
+            correlation_id,
+            payload_source,
+            gas_delta,
+            tx_count_delta,
+            builder_has_payload,
+        )
+    )]
+    async fn get_payload_v2(&self, payload_id: PayloadId) -> RpcResult<ExecutionPayloadEnvelopeV2> {
+        info!("received get_payload_v2");
+
+        match self.get_payload(payload_id, PayloadVersion::V2).await? {
+            OpExecutionPayloadEnvelope::V2(v2) => Ok(v2),
+            payload => Err(unsupported_version(payload.version())),
+        }
+    }
+
+    #[instrument(
+        skip_all,
+        err,
+        fields(
+            otel.kind = ?SpanKind::Server,
+        )
+    )]
+    async fn new_payload_v2(&self, payload: ExecutionPayloadInputV2) -> RpcResult<PayloadStatus> {
+        info!("received new_payload_v2");
+
+        self.new_payload(NewPayload::V2(payload)).await
+    }
+
+    #[instrument(
+        skip_all,
+        err,
+        fields(
+            otel.kind = ?SpanKind::Server,
+            %payload_id,
+            correlation_id,
+            payload_source,
+            gas_delta,
+            tx_count_delta,
+            builder_has_payload,
+        )
+    )]
+    async fn get_payload_v3(
+        &self,
         payload_id: PayloadId,
     ) -> RpcResult<OpExecutionPayloadEnvelopeV3> {
         info!("received get_payload_v3");
 
         match self.get_payload(payload_id, PayloadVersion::V3).await? {
             OpExecutionPayloadEnvelope::V3(v3) => Ok(v3),
-            OpExecutionPayloadEnvelope::V4(_) => Err(ErrorObject::owned(
-                INVALID_REQUEST_CODE,
-                "Payload version 4 not supported",
-                None::<String>,
-            )),
+            payload => Err(unsupported_version(payload.version())),
         }
     }
 
@@ -455,6 +1532,7 @@ impl EngineApiServer for RollupBoostServer {
         fields(
             otel.kind = ?SpanKind::Server,
             %payload_id,
+            correlation_id,
             payload_source,
             gas_delta,
             tx_count_delta,
@@ -468,11 +1546,7 @@ impl EngineApiServer for RollupBoostServer {
 
         match self.get_payload(payload_id, PayloadVersion::V4).await? {
             OpExecutionPayloadEnvelope::V4(v4) => Ok(v4),
-            OpExecutionPayloadEnvelope::V3(_) => Err(ErrorObject::owned(
-                INVALID_REQUEST_CODE,
-                "Payload version 4 not supported",
-                None::<String>,
-            )),
+            payload => Err(unsupported_version(payload.version())),
         }
     }
 
@@ -501,6 +1575,84 @@ impl EngineApiServer for RollupBoostServer {
         .await
     }
 
+    #[instrument(
+        skip_all,
+        err,
+        fields(
+            otel.kind = ?SpanKind::Server,
+        )
+    )]
+    async fn get_payload_bodies_by_hash_v1(
+        &self,
+        block_hashes: Vec<B256>,
+    ) -> RpcResult<ExecutionPayloadBodiesV1> {
+        info!("received get_payload_bodies_by_hash_v1");
+
+        // Payload bodies are only served by the l2 client, which is the canonical chain
+        Ok(self
+            .l2_client
+            .get_payload_bodies_by_hash_v1(block_hashes)
+            .await?)
+    }
+
+    #[instrument(
+        skip_all,
+        err,
+        fields(
+            otel.kind = ?SpanKind::Server,
+        )
+    )]
+    async fn get_payload_bodies_by_range_v1(
+        &self,
+        start: U64,
+        count: U64,
+    ) -> RpcResult<ExecutionPayloadBodiesV1> {
+        info!("received get_payload_bodies_by_range_v1");
+
+        Ok(self
+            .l2_client
+            .get_payload_bodies_by_range_v1(start, count)
+            .await?)
+    }
+
+    #[instrument(
+        skip_all,
+        err,
+        fields(
+            otel.kind = ?SpanKind::Server,
+        )
+    )]
+    async fn exchange_capabilities(&self, capabilities: Vec<String>) -> RpcResult<Vec<String>> {
+        info!("received exchange_capabilities");
+
+        let l2_fut = self.l2_client.exchange_capabilities(capabilities.clone());
+        let l2_capabilities = if self.execution_mode().is_disabled() {
+            l2_fut.await?
+        } else {
+            let builder_fut = self.builder_client.exchange_capabilities(capabilities);
+            let (l2_result, builder_result) = tokio::join!(l2_fut, builder_fut);
+            let l2_capabilities = l2_result?;
+
+            // Fall back to the l2 capabilities if the builder is down
+            match builder_result {
+                Ok(builder_capabilities) => l2_capabilities
+                    .into_iter()
+                    .filter(|capability| builder_capabilities.contains(capability))
+                    .collect(),
+                Err(e) => {
+                    warn!(message = "builder failed to exchange capabilities", error = %e);
+                    l2_capabilities
+                }
+            }
+        };
+
+        // Only advertise the engine methods that rollup-boost serves itself
+        Ok(l2_capabilities
+            .into_iter()
+            .filter(|capability| ENGINE_CAPABILITIES.contains(&capability.as_str()))
+            .collect())
+    }
+
     async fn get_block_by_number(&self, number: BlockNumberOrTag, full: bool) -> RpcResult<Block> {
         Ok(self.l2_client.get_block_by_number(number, full).await?)
     }
@@ -524,7 +1676,7 @@ mod tests {
     use crate::probe::ProbeLayer;
     use crate::proxy::ProxyLayer;
     use alloy_primitives::hex;
-    use alloy_primitives::{FixedBytes, U256};
+    use alloy_primitives::{Address, FixedBytes, U256};
     use alloy_rpc_types_engine::JwtSecret;
     use alloy_rpc_types_engine::{
         BlobsBundleV1, ExecutionPayloadV1, ExecutionPayloadV2, PayloadStatusEnum,
@@ -533,6 +1685,7 @@ mod tests {
     use jsonrpsee::RpcModule;
     use jsonrpsee::http_client::HttpClient;
     use jsonrpsee::server::{Server, ServerBuilder, ServerHandle};
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
     use parking_lot::Mutex;
     use std::net::SocketAddr;
     use std::str::FromStr;
@@ -544,9 +1697,18 @@ mod tests {
         fcu_requests: Arc<Mutex<Vec<(ForkchoiceState, Option<OpPayloadAttributes>)>>>,
         get_payload_requests: Arc<Mutex<Vec<PayloadId>>>,
         new_payload_requests: Arc<Mutex<Vec<(ExecutionPayloadV3, Vec<B256>, B256)>>>,
+        payload_bodies_requests: Arc<Mutex<Vec<(U64, U64)>>>,
         fcu_response: RpcResult<ForkchoiceUpdated>,
         get_payload_response: RpcResult<OpExecutionPayloadEnvelopeV3>,
         new_payload_response: RpcResult<PayloadStatus>,
+        exchange_capabilities_response: RpcResult<Vec<String>>,
+        // Time taken to respond to get_payload calls
+        get_payload_delay: Option<Duration>,
+        // Time taken to respond to fork_choice_updated and new_payload calls
+        sync_delay: Option<Duration>,
+        // Whether the payload ID is derived from the gas limit of the attributes, as
+        // execution clients derive it from the attributes
+        derive_payload_id: bool,
 
         pub override_payload_id: Option<PayloadId>,
     }
@@ -557,6 +1719,7 @@ mod tests {
                 fcu_requests: Arc::new(Mutex::new(vec![])),
                 get_payload_requests: Arc::new(Mutex::new(vec![])),
                 new_payload_requests: Arc::new(Mutex::new(vec![])),
+                payload_bodies_requests: Arc::new(Mutex::new(vec![])),
                 fcu_response: Ok(ForkchoiceUpdated::new(PayloadStatus::from_status(PayloadStatusEnum::Valid))),
                 get_payload_response: Ok(OpExecutionPayloadEnvelopeV3{
                     execution_payload: ExecutionPayloadV3 {
@@ -592,7 +1755,11 @@ mod tests {
                 parent_beacon_block_root: B256::ZERO,
             }),
             override_payload_id: None,
+            get_payload_delay: None,
+            sync_delay: None,
+            derive_payload_id: false,
             new_payload_response: Ok(PayloadStatus::from_status(PayloadStatusEnum::Valid)),
+            exchange_capabilities_response: Ok(ENGINE_CAPABILITIES.map(String::from).to_vec()),
         }
         }
     }
@@ -612,6 +1779,15 @@ mod tests {
         async fn new(
             l2_mock: Option<MockEngineServer>,
             builder_mock: Option<MockEngineServer>,
+        ) -> Self {
+            Self::new_with_server(l2_mock, builder_mock, |server| server).await
+        }
+
+        /// Creates a harness whose rollup-boost server is configured by `configure`.
+        async fn new_with_server(
+            l2_mock: Option<MockEngineServer>,
+            builder_mock: Option<MockEngineServer>,
+            configure: impl FnOnce(RollupBoostServer) -> RollupBoostServer,
         ) -> Self {
             let jwt_secret = JwtSecret::random();
 
@@ -647,6 +1823,7 @@ mod tests {
                 60,
                 5,
             );
+            let rollup_boost = configure(rollup_boost);
 
             let module: RpcModule<()> = rollup_boost.try_into().unwrap();
 
@@ -840,40 +2017,83 @@ mod tests {
 
         let mut module: RpcModule<()> = RpcModule::new(());
 
+        let fcu_mock = mock_engine_server.clone();
+        let new_payload_mock = mock_engine_server.clone();
         module
-            .register_method("engine_forkchoiceUpdatedV3", move |params, _, _| {
-                let params: (ForkchoiceState, Option<OpPayloadAttributes>) = params.parse()?;
-                let mut fcu_requests = mock_engine_server.fcu_requests.lock();
-                fcu_requests.push(params);
-
-                let mut response = mock_engine_server.fcu_response.clone();
-                if let Ok(ref mut fcu_response) = response {
-                    if let Some(override_id) = mock_engine_server.override_payload_id {
-                        fcu_response.payload_id = Some(override_id);
+            .register_async_method("engine_forkchoiceUpdatedV3", move |params, _, _| {
+                let mock_engine_server = fcu_mock.clone();
+                async move {
+                    let params: (ForkchoiceState, Option<OpPayloadAttributes>) = params.parse()?;
+                    let derived_payload_id = params
+                        .1
+                        .as_ref()
+                        .and_then(|attrs| attrs.gas_limit)
+                        .filter(|_| mock_engine_server.derive_payload_id)
+                        .map(|gas_limit| PayloadId::new(gas_limit.to_be_bytes()));
+                    mock_engine_server.fcu_requests.lock().push(params);
+                    if let Some(delay) = mock_engine_server.sync_delay {
+                        sleep(delay).await;
+                    }
+
+                    let mut response = mock_engine_server.fcu_response.clone();
+                    if let Ok(ref mut fcu_response) = response {
+                        if let Some(override_id) = mock_engine_server
+                            .override_payload_id
+                            .or(derived_payload_id)
+                        {
+                            fcu_response.payload_id = Some(override_id);
+                        }
                     }
+
+                    response
                 }
+            })
+            .unwrap();
 
-                response
+        module
+            .register_async_method("engine_getPayloadV3", move |params, _, _| {
+                let get_payload_requests = mock_engine_server.get_payload_requests.clone();
+                let get_payload_response = mock_engine_server.get_payload_response.clone();
+                let get_payload_delay = mock_engine_server.get_payload_delay;
+                async move {
+                    let params: (PayloadId,) = params.parse()?;
+                    get_payload_requests.lock().push(params.0);
+                    if let Some(delay) = get_payload_delay {
+                        sleep(delay).await;
+                    }
+
+                    get_payload_response
+                }
             })
             .unwrap();
 
         module
-            .register_method("engine_getPayloadV3", move |params, _, _| {
-                let params: (PayloadId,) = params.parse()?;
-                let mut get_payload_requests = mock_engine_server.get_payload_requests.lock();
-                get_payload_requests.push(params.0);
+            .register_async_method("engine_newPayloadV3", move |params, _, _| {
+                let mock_engine_server = new_payload_mock.clone();
+                async move {
+                    let params: (ExecutionPayloadV3, Vec<B256>, B256) = params.parse()?;
+                    mock_engine_server.new_payload_requests.lock().push(params);
+                    if let Some(delay) = mock_engine_server.sync_delay {
+                        sleep(delay).await;
+                    }
 
-                mock_engine_server.get_payload_response.clone()
+                    mock_engine_server.new_payload_response.clone()
+                }
             })
             .unwrap();
 
+        let payload_bodies_requests = mock_engine_server.payload_bodies_requests.clone();
         module
-            .register_method("engine_newPayloadV3", move |params, _, _| {
-                let params: (ExecutionPayloadV3, Vec<B256>, B256) = params.parse()?;
-                let mut new_payload_requests = mock_engine_server.new_payload_requests.lock();
-                new_payload_requests.push(params);
+            .register_method("engine_getPayloadBodiesByRangeV1", move |params, _, _| {
+                let params: (U64, U64) = params.parse()?;
+                payload_bodies_requests.lock().push(params);
+                RpcResult::Ok(ExecutionPayloadBodiesV1::new())
+            })
+            .unwrap();
 
-                mock_engine_server.new_payload_response.clone()
+        module
+            .register_method("engine_exchangeCapabilities", move |_, _, _| {
+                mock_engine_server.exchange_capabilities_response.clone()
             })
             .unwrap();
 
@@ -971,10 +2191,14 @@ mod tests {
             safe_block_hash: FixedBytes::random(),
             finalized_block_hash: FixedBytes::random(),
         };
+        // The builder payload must match the fee recipient and gas limit of the attributes
         let mut payload_attributes = OpPayloadAttributes {
-            gas_limit: Some(1000000),
+            gas_limit: Some(0x1c9c380),
             ..Default::default()
         };
+        payload_attributes
+            .payload_attributes
+            .suggested_fee_recipient = hex!("f97e180c050e5ab072211ad2c213eb5aee4df134").into();
         let fcu_response = test_harness
             .rpc_client
             .fork_choice_updated_v3(fcu, Some(payload_attributes.clone()))
@@ -1001,38 +2225,1112 @@ mod tests {
         test_harness.cleanup().await;
     }
 
+    /// The blocks attributed to each source, and the source of the last block.
+    fn block_sources(snapshotter: &Snapshotter) -> (Vec<(String, u64)>, Option<String>) {
+        let mut counts = vec![];
+        let mut last = None;
+        for (key, _, _, value) in snapshotter.snapshot().into_vec() {
+            let source = key
+                .key()
+                .labels()
+                .find(|label| label.key() == "source")
+                .map(|label| label.value().to_string());
+            match (key.key().name(), value) {
+                ("rpc.block_source", DebugValue::Counter(count)) => {
+                    counts.extend(source.map(|source| (source, count)))
+                }
+                ("rpc.last_block_source", DebugValue::Gauge(value))
+                    if value.into_inner() == 1.0 =>
+                {
+                    last = source
+                }
+                _ => {}
+            }
+        }
+        counts.sort();
+        (counts, last)
+    }
+
     #[tokio::test]
-    async fn l2_client_fails_fcu() {
-        // If the canonical l2 client fails the FCU call, it does not matter what the builder returns
-        // the FCU call should fail
+    async fn block_source_metrics() {
+        // The test runtime polls the server on this thread, which records to the recorder
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let _guard = metrics::set_default_local_recorder(&recorder);
+
+        let payload_id: PayloadId = PayloadId::new([0, 0, 0, 0, 0, 0, 0, 42]);
         let mut l2_mock = MockEngineServer::new();
-        l2_mock.fcu_response = Err(ErrorObject::owned(
+        l2_mock.fcu_response = Ok(ForkchoiceUpdated::new(PayloadStatus::from_status(
+            PayloadStatusEnum::Valid,
+        ))
+        .with_payload_id(payload_id));
+        let mut builder_mock = MockEngineServer::new();
+        builder_mock.override_payload_id = Some(payload_id);
+        builder_mock.get_payload_response =
+            builder_mock
+                .get_payload_response
+                .clone()
+                .map(|mut payload| {
+                    payload.block_value = U256::from(15);
+                    payload
+                });
+
+        let fcu = ForkchoiceState {
+            head_block_hash: FixedBytes::random(),
+            safe_block_hash: FixedBytes::random(),
+            finalized_block_hash: FixedBytes::random(),
+        };
+        // The builder payload must match the fee recipient and gas limit of the attributes
+        let mut payload_attributes = OpPayloadAttributes {
+            gas_limit: Some(0x1c9c380),
+            ..Default::default()
+        };
+        payload_attributes
+            .payload_attributes
+            .suggested_fee_recipient = hex!("f97e180c050e5ab072211ad2c213eb5aee4df134").into();
+
+        let test_harness =
+            TestHarness::new(Some(l2_mock.clone()), Some(builder_mock.clone())).await;
+        test_harness
+            .rpc_client
+            .fork_choice_updated_v3(fcu, Some(payload_attributes.clone()))
+            .await
+            .unwrap();
+        test_harness
+            .rpc_client
+            .get_payload_v3(payload_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            block_sources(&snapshotter),
+            (
+                vec![("builder".to_string(), 1)],
+                Some("builder".to_string())
+            )
+        );
+        test_harness.cleanup().await;
+
+        // The l2 block returned when the builder fails is attributed to the l2
+        builder_mock.get_payload_response = Err(ErrorObject::owned(
             INVALID_REQUEST_CODE,
-            "Payload version 4 not supported",
+            "builder down",
             None::<String>,
         ));
+        let test_harness = TestHarness::new(Some(l2_mock), Some(builder_mock)).await;
+        test_harness
+            .rpc_client
+            .fork_choice_updated_v3(fcu, Some(payload_attributes))
+            .await
+            .unwrap();
+        test_harness
+            .rpc_client
+            .get_payload_v3(payload_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            block_sources(&snapshotter),
+            (
+                vec![("builder".to_string(), 1), ("l2".to_string(), 1)],
+                Some("l2".to_string())
+            )
+        );
+        test_harness.cleanup().await;
+    }
 
-        let test_harness = TestHarness::new(Some(l2_mock), None).await;
+    #[tokio::test]
+    async fn import_builder_blocks() {
+        let payload_id: PayloadId = PayloadId::new([0, 0, 0, 0, 0, 0, 0, 42]);
+        let mut l2_mock = MockEngineServer::new();
+        l2_mock.fcu_response = Ok(ForkchoiceUpdated::new(PayloadStatus::from_status(
+            PayloadStatusEnum::Valid,
+        ))
+        .with_payload_id(payload_id));
+        let mut builder_mock = MockEngineServer::new();
+        builder_mock.override_payload_id = Some(payload_id);
+        builder_mock.get_payload_response =
+            builder_mock
+                .get_payload_response
+                .clone()
+                .map(|mut payload| {
+                    payload.block_value = U256::from(15);
+                    payload
+                        .execution_payload
+                        .payload_inner
+                        .payload_inner
+                        .block_hash = B256::with_last_byte(15);
+                    payload
+                });
 
         let fcu = ForkchoiceState {
             head_block_hash: FixedBytes::random(),
             safe_block_hash: FixedBytes::random(),
             finalized_block_hash: FixedBytes::random(),
         };
-        let fcu_response = test_harness
+        // The builder payload must match the fee recipient and gas limit of the attributes
+        let mut payload_attributes = OpPayloadAttributes {
+            gas_limit: Some(0x1c9c380),
+            ..Default::default()
+        };
+        payload_attributes
+            .payload_attributes
+            .suggested_fee_recipient = hex!("f97e180c050e5ab072211ad2c213eb5aee4df134").into();
+
+        let test_harness = TestHarness::new_with_server(
+            Some(l2_mock.clone()),
+            Some(builder_mock.clone()),
+            |server| server.with_import_builder_blocks(true),
+        )
+        .await;
+        test_harness
             .rpc_client
-            .fork_choice_updated_v3(fcu, None)
-            .await;
-        assert!(fcu_response.is_err());
+            .fork_choice_updated_v3(fcu, Some(payload_attributes.clone()))
+            .await
+            .unwrap();
+        let payload = test_harness
+            .rpc_client
+            .get_payload_v3(payload_id)
+            .await
+            .unwrap();
+        assert_eq!(payload.block_value, U256::from(15));
+
+        // wait for the l2 to observe the FCU importing the builder block
+        sleep(Duration::from_millis(100)).await;
+        {
+            let fcu_requests = test_harness.l2_mock.fcu_requests.lock();
+            assert_eq!(fcu_requests.len(), 2);
+            let (state, attributes) = &fcu_requests[1];
+            assert_eq!(state.head_block_hash, B256::with_last_byte(15));
+            assert_eq!(state.safe_block_hash, fcu.safe_block_hash);
+            assert!(attributes.is_none());
+        }
+        test_harness.cleanup().await;
+
+        // The l2 already has the blocks it built, returned when the builder fails
+        builder_mock.get_payload_response = Err(ErrorObject::owned(
+            INVALID_REQUEST_CODE,
+            "builder down",
+            None::<String>,
+        ));
+        let test_harness = TestHarness::new_with_server(
+            Some(MockEngineServer {
+                fcu_requests: Arc::new(Mutex::new(vec![])),
+                ..l2_mock
+            }),
+            Some(builder_mock),
+            |server| server.with_import_builder_blocks(true),
+        )
+        .await;
+        test_harness
+            .rpc_client
+            .fork_choice_updated_v3(fcu, Some(payload_attributes))
+            .await
+            .unwrap();
+        test_harness
+            .rpc_client
+            .get_payload_v3(payload_id)
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(test_harness.l2_mock.fcu_requests.lock().len(), 1);
+        test_harness.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn no_tx_pool_cycle_keeps_health() {
+        let payload_id: PayloadId = PayloadId::new([0, 0, 0, 0, 0, 0, 0, 42]);
+        let mut l2_mock = MockEngineServer::new();
+        l2_mock.fcu_response = Ok(ForkchoiceUpdated::new(PayloadStatus::from_status(
+            PayloadStatusEnum::Valid,
+        ))
+        .with_payload_id(payload_id));
 
+        let test_harness = TestHarness::new(Some(l2_mock), None).await;
+        let fcu = ForkchoiceState {
+            head_block_hash: FixedBytes::random(),
+            safe_block_hash: FixedBytes::random(),
+            finalized_block_hash: FixedBytes::random(),
+        };
         let payload_attributes = OpPayloadAttributes {
-            gas_limit: Some(1000000),
+            no_tx_pool: Some(true),
             ..Default::default()
         };
         let fcu_response = test_harness
             .rpc_client
             .fork_choice_updated_v3(fcu, Some(payload_attributes))
             .await;
-        assert!(fcu_response.is_err());
+        assert!(fcu_response.is_ok());
+
+        let get_payload_response = test_harness.rpc_client.get_payload_v3(payload_id).await;
+        assert!(get_payload_response.is_ok());
+        assert!(
+            test_harness
+                .builder_mock
+                .get_payload_requests
+                .lock()
+                .is_empty()
+        );
+
+        // The builder was not asked for a payload, so the l2 payload is not a builder failure
+        let health = test_harness.get("healthz").await;
+        assert_eq!(health.status(), StatusCode::OK);
+
+        test_harness.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn get_payload_budget_exceeded() {
+        let payload_id: PayloadId = PayloadId::new([0, 0, 0, 0, 0, 0, 0, 42]);
+        let mut l2_mock = MockEngineServer::new();
+        l2_mock.fcu_response = Ok(ForkchoiceUpdated::new(PayloadStatus::from_status(
+            PayloadStatusEnum::Valid,
+        ))
+        .with_payload_id(payload_id));
+        l2_mock.get_payload_response = l2_mock.get_payload_response.clone().map(|mut payload| {
+            payload.block_value = U256::from(10);
+            payload
+        });
+
+        // The builder takes longer than the budget to return its payload
+        let mut builder_mock = MockEngineServer::new();
+        builder_mock.fcu_response = l2_mock.fcu_response.clone();
+        builder_mock.get_payload_delay = Some(Duration::from_secs(1));
+        builder_mock.get_payload_response =
+            builder_mock
+                .get_payload_response
+                .clone()
+                .map(|mut payload| {
+                    payload.block_value = U256::from(15);
+                    payload
+                });
+
+        let budget = Duration::from_millis(200);
+        let test_harness =
+            TestHarness::new_with_server(Some(l2_mock), Some(builder_mock.clone()), |server| {
+                server.with_get_payload_budget(Some(budget))
+            })
+            .await;
+        let fcu = ForkchoiceState {
+            head_block_hash: FixedBytes::random(),
+            safe_block_hash: FixedBytes::random(),
+            finalized_block_hash: FixedBytes::random(),
+        };
+        // The builder payload must match the fee recipient and gas limit of the attributes
+        let mut payload_attributes = OpPayloadAttributes {
+            gas_limit: Some(0x1c9c380),
+            ..Default::default()
+        };
+        payload_attributes
+            .payload_attributes
+            .suggested_fee_recipient = hex!("f97e180c050e5ab072211ad2c213eb5aee4df134").into();
+        let fcu_response = test_harness
+            .rpc_client
+            .fork_choice_updated_v3(fcu, Some(payload_attributes))
+            .await;
+        assert!(fcu_response.is_ok());
+
+        // The l2 payload is returned once the budget is exceeded, without waiting for the
+        // builder
+        let started = Instant::now();
+        let get_payload_response = test_harness.rpc_client.get_payload_v3(payload_id).await;
+        let elapsed = started.elapsed();
+        assert_eq!(get_payload_response.unwrap().block_value, U256::from(10));
+        assert!(elapsed >= budget);
+        assert!(elapsed < Duration::from_millis(800), "took {elapsed:?}");
+        assert_eq!(builder_mock.get_payload_requests.lock().len(), 1);
+
+        test_harness.cleanup().await;
+    }
+
+    /// Builder-API relay serving a single bid for `payload`, signed by `key`.
+    struct MockRelay {
+        url: url::Url,
+        requests: Arc<Mutex<Vec<String>>>,
+        handle: tokio::task::JoinHandle<()>,
+    }
+
+    impl Drop for MockRelay {
+        fn drop(&mut self) {
+            self.handle.abort();
+        }
+    }
+
+    impl MockRelay {
+        async fn spawn(
+            key: &k256::ecdsa::SigningKey,
+            payload: ExecutionPayloadV3,
+            value: U256,
+        ) -> Self {
+            use http_body_util::Full;
+            use hyper::{Response, body::Bytes, service::service_fn};
+            use hyper_util::rt::TokioIo;
+
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url =
+                url::Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+            let block_hash = payload.payload_inner.payload_inner.block_hash;
+            let signing_hash = alloy_primitives::keccak256(
+                [block_hash.as_slice(), &value.to_be_bytes::<32>()].concat(),
+            );
+            let signature = alloy_primitives::Signature::from(
+                key.sign_prehash_recoverable(signing_hash.as_slice())
+                    .unwrap(),
+            );
+            let bid = serde_json::json!({ "data": {
+                "message": { "header": payload, "value": value },
+                "signature": alloy_primitives::Bytes::from(signature.as_bytes()),
+            }});
+            let unblinded = serde_json::json!({ "data": payload });
+
+            let requests = Arc::new(Mutex::new(vec![]));
+            let relay_requests = requests.clone();
+            let handle = tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let (requests, bid, unblinded) =
+                        (relay_requests.clone(), bid.clone(), unblinded.clone());
+                    let service = service_fn(move |req: hyper::Request<hyper::body::Incoming>| {
+                        let path = req.uri().path().to_string();
+                        requests.lock().push(format!("{} {path}", req.method()));
+                        let body = if path.starts_with("/eth/v1/builder/header/") {
+                            bid.to_string()
+                        } else if path == "/eth/v1/builder/blinded_blocks" {
+                            unblinded.to_string()
+                        } else {
+                            String::new()
+                        };
+                        async move {
+                            Ok::<_, std::convert::Infallible>(Response::new(Full::new(
+                                Bytes::from(body),
+                            )))
+                        }
+                    });
+                    tokio::spawn(
+                        hyper::server::conn::http1::Builder::new()
+                            .serve_connection(TokioIo::new(stream), service),
+                    );
+                }
+            });
+
+            Self {
+                url,
+                requests,
+                handle,
+            }
+        }
+    }
+
+    /// Builds a payload with the relay at `relay_url` as the builder, its bids being
+    /// expected to be signed by `relay_signer`.
+    async fn get_relay_payload(
+        relay_url: url::Url,
+        relay_signer: Address,
+        payload: &ExecutionPayloadV3,
+    ) -> OpExecutionPayloadEnvelopeV3 {
+        let jwt_secret = JwtSecret::random();
+        let mut l2_mock = MockEngineServer::new();
+        l2_mock.fcu_response = Ok(ForkchoiceUpdated::new(PayloadStatus::from_status(
+            PayloadStatusEnum::Valid,
+        ))
+        .with_payload_id(PayloadId::new([0, 0, 0, 0, 0, 0, 0, 42])));
+        let (l2_server, l2_server_addr) = spawn_server(l2_mock).await;
+        let l2_client = RpcClient::new(
+            Uri::from_str(&format!("http://{l2_server_addr}")).unwrap(),
+            jwt_secret,
+            2000,
+            PayloadSource::L2,
+        )
+        .unwrap();
+        let relay_client =
+            crate::RelayClient::new(relay_url, relay_signer, Duration::from_millis(1000)).unwrap();
+        let (_, probes) = ProbeLayer::new();
+        let rollup_boost = RollupBoostServer::new(
+            l2_client,
+            Arc::new(relay_client),
+            Arc::new(Mutex::new(ExecutionMode::Enabled)),
+            None,
+            probes,
+            60,
+            5,
+        );
+
+        let fcu = ForkchoiceState {
+            head_block_hash: payload.payload_inner.payload_inner.parent_hash,
+            safe_block_hash: FixedBytes::random(),
+            finalized_block_hash: FixedBytes::random(),
+        };
+        let mut payload_attributes = OpPayloadAttributes {
+            gas_limit: Some(payload.payload_inner.payload_inner.gas_limit),
+            ..Default::default()
+        };
+        payload_attributes.payload_attributes.timestamp =
+            payload.payload_inner.payload_inner.timestamp;
+        payload_attributes
+            .payload_attributes
+            .suggested_fee_recipient = payload.payload_inner.payload_inner.fee_recipient;
+        let payload_id = rollup_boost
+            .fork_choice_updated_v3(fcu, Some(payload_attributes))
+            .await
+            .unwrap()
+            .payload_id
+            .unwrap();
+
+        let envelope = rollup_boost.get_payload_v3(payload_id).await.unwrap();
+        l2_server.stop().unwrap();
+        l2_server.stopped().await;
+        envelope
+    }
+
+    #[tokio::test]
+    async fn relay_payload() {
+        let mut payload = MockEngineServer::new()
+            .get_payload_response
+            .unwrap()
+            .execution_payload;
+        payload.payload_inner.payload_inner.block_hash = B256::random();
+        let key = k256::ecdsa::SigningKey::from_slice(&[1; 32]).unwrap();
+        let relay = MockRelay::spawn(&key, payload.clone(), U256::from(15)).await;
+
+        let envelope = get_relay_payload(
+            relay.url.clone(),
+            Address::from_public_key(key.verifying_key()),
+            &payload,
+        )
+        .await;
+        assert_eq!(envelope.execution_payload, payload);
+        assert_eq!(envelope.block_value, U256::from(15));
+
+        let inner = &payload.payload_inner.payload_inner;
+        assert_eq!(
+            *relay.requests.lock(),
+            vec![
+                "POST /eth/v1/builder/payload_attributes".to_string(),
+                format!(
+                    "GET /eth/v1/builder/header/{}/{}",
+                    inner.timestamp, inner.parent_hash
+                ),
+                "POST /eth/v1/builder/blinded_blocks".to_string(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn relay_bid_of_another_signer() {
+        let mut payload = MockEngineServer::new()
+            .get_payload_response
+            .unwrap()
+            .execution_payload;
+        payload.payload_inner.payload_inner.block_hash = B256::random();
+        let key = k256::ecdsa::SigningKey::from_slice(&[1; 32]).unwrap();
+        let relay = MockRelay::spawn(&key, payload.clone(), U256::from(15)).await;
+
+        // The payload of the bid is never revealed, the l2 payload is returned
+        let envelope = get_relay_payload(relay.url.clone(), Address::random(), &payload).await;
+        assert_ne!(envelope.execution_payload, payload);
+        assert!(
+            !relay
+                .requests
+                .lock()
+                .contains(&"POST /eth/v1/builder/blinded_blocks".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn attributes_policy_rewrites_builder_attributes_only() {
+        let builder_fee_recipient = Address::random();
+        let test_harness = TestHarness::new_with_server(None, None, |server| {
+            server.with_attributes_policy(Some(
+                crate::AttributesPolicy::new(crate::PolicyAction::Rewrite)
+                    .with_fee_recipient(builder_fee_recipient),
+            ))
+        })
+        .await;
+
+        let fcu = ForkchoiceState {
+            head_block_hash: FixedBytes::random(),
+            safe_block_hash: FixedBytes::random(),
+            finalized_block_hash: FixedBytes::random(),
+        };
+        let payload_attributes = OpPayloadAttributes {
+            gas_limit: Some(1000000),
+            ..Default::default()
+        };
+        test_harness
+            .rpc_client
+            .fork_choice_updated_v3(fcu, Some(payload_attributes.clone()))
+            .await
+            .unwrap();
+
+        // The l2 client builds on the attributes of op-node
+        let l2_fcu_requests = test_harness.l2_mock.fcu_requests.lock().clone();
+        assert_eq!(
+            l2_fcu_requests,
+            vec![(fcu, Some(payload_attributes.clone()))]
+        );
+
+        let builder_fcu_requests = test_harness.builder_mock.fcu_requests.lock().clone();
+        let mut builder_attributes = payload_attributes;
+        builder_attributes
+            .payload_attributes
+            .suggested_fee_recipient = builder_fee_recipient;
+        assert_eq!(builder_fcu_requests, vec![(fcu, Some(builder_attributes))]);
+
+        test_harness.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn builder_payload_id_of_rewritten_attributes() {
+        let payload_id: PayloadId = PayloadId::new([0, 0, 0, 0, 0, 0, 0, 42]);
+        let mut l2_mock = MockEngineServer::new();
+        l2_mock.fcu_response = Ok(ForkchoiceUpdated::new(PayloadStatus::from_status(
+            PayloadStatusEnum::Valid,
+        ))
+        .with_payload_id(payload_id));
+        l2_mock.get_payload_response = l2_mock.get_payload_response.clone().map(|mut payload| {
+            payload.block_value = U256::from(10);
+            payload
+        });
+
+        // The builder derives its payload ID from the rewritten gas limit
+        let mut builder_mock = MockEngineServer::new();
+        builder_mock.derive_payload_id = true;
+        builder_mock.get_payload_response =
+            builder_mock
+                .get_payload_response
+                .clone()
+                .map(|mut payload| {
+                    payload.block_value = U256::from(15);
+                    payload
+                });
+
+        let builder_gas_limit = 0x1c9c380;
+        let test_harness =
+            TestHarness::new_with_server(Some(l2_mock), Some(builder_mock.clone()), |server| {
+                server.with_attributes_hooks(vec![Arc::new(crate::GasLimitHook::new(
+                    Some(builder_gas_limit),
+                    None,
+                    None,
+                ))])
+            })
+            .await;
+        let fcu = ForkchoiceState {
+            head_block_hash: FixedBytes::random(),
+            safe_block_hash: FixedBytes::random(),
+            finalized_block_hash: FixedBytes::random(),
+        };
+        let mut payload_attributes = OpPayloadAttributes {
+            gas_limit: Some(60_000_000),
+            ..Default::default()
+        };
+        payload_attributes
+            .payload_attributes
+            .suggested_fee_recipient = hex!("f97e180c050e5ab072211ad2c213eb5aee4df134").into();
+        let fcu_response = test_harness
+            .rpc_client
+            .fork_choice_updated_v3(fcu, Some(payload_attributes))
+            .await
+            .unwrap();
+        assert_eq!(fcu_response.payload_id, Some(payload_id));
+
+        // The builder payload is requested with the payload ID the builder returned
+        let get_payload_response = test_harness.rpc_client.get_payload_v3(payload_id).await;
+        assert_eq!(get_payload_response.unwrap().block_value, U256::from(15));
+        assert_eq!(
+            *builder_mock.get_payload_requests.lock(),
+            vec![PayloadId::new(builder_gas_limit.to_be_bytes())]
+        );
+        assert_eq!(test_harness.get("healthz").await.status(), StatusCode::OK);
+
+        test_harness.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn builder_payload_mismatching_attributes() {
+        let payload_id: PayloadId = PayloadId::new([0, 0, 0, 0, 0, 0, 0, 43]);
+        let mut l2_mock = MockEngineServer::new();
+        l2_mock.fcu_response = Ok(ForkchoiceUpdated::new(PayloadStatus::from_status(
+            PayloadStatusEnum::Valid,
+        ))
+        .with_payload_id(payload_id));
+        l2_mock.get_payload_response = l2_mock.get_payload_response.clone().map(|mut payload| {
+            payload.block_value = U256::from(10);
+            payload
+        });
+
+        let mut builder_mock = MockEngineServer::new();
+        builder_mock.get_payload_response =
+            builder_mock
+                .get_payload_response
+                .clone()
+                .map(|mut payload| {
+                    payload.block_value = U256::from(15);
+                    payload
+                });
+
+        let quarantine = Arc::new(BuilderQuarantine::new(
+            1,
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        ));
+        let scores = Arc::new(BuilderScores::new(0.5));
+        let test_harness = TestHarness::new_with_server(Some(l2_mock), Some(builder_mock), {
+            let quarantine = quarantine.clone();
+            let scores = scores.clone();
+            move |server| {
+                server
+                    .with_builder_quarantine(Some(quarantine))
+                    .with_builder_scores(Some(scores))
+            }
+        })
+        .await;
+
+        // The builder payload pays another fee recipient than the one of the attributes
+        let fcu = ForkchoiceState {
+            head_block_hash: FixedBytes::random(),
+            safe_block_hash: FixedBytes::random(),
+            finalized_block_hash: FixedBytes::random(),
+        };
+        let mut payload_attributes = OpPayloadAttributes::default();
+        payload_attributes
+            .payload_attributes
+            .suggested_fee_recipient = Address::repeat_byte(0x11);
+        test_harness
+            .rpc_client
+            .fork_choice_updated_v3(fcu, Some(payload_attributes))
+            .await
+            .unwrap();
+
+        let get_payload_response = test_harness.rpc_client.get_payload_v3(payload_id).await;
+        assert_eq!(get_payload_response.unwrap().block_value, U256::from(10));
+
+        // The mismatch counts against the builder like any other invalid payload
+        assert!(quarantine.is_quarantined(crate::DEFAULT_BUILDER));
+        assert!(scores.score(crate::DEFAULT_BUILDER) < 1.0);
+
+        test_harness.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn l2_client_fails_fcu() {
+        // If the canonical l2 client fails the FCU call, it does not matter what the builder returns
+        // the FCU call should fail
+        let mut l2_mock = MockEngineServer::new();
+        l2_mock.fcu_response = Err(ErrorObject::owned(
+            INVALID_REQUEST_CODE,
+            "Payload version 4 not supported",
+            None::<String>,
+        ));
+
+        let test_harness = TestHarness::new(Some(l2_mock), None).await;
+
+        let fcu = ForkchoiceState {
+            head_block_hash: FixedBytes::random(),
+            safe_block_hash: FixedBytes::random(),
+            finalized_block_hash: FixedBytes::random(),
+        };
+        let fcu_response = test_harness
+            .rpc_client
+            .fork_choice_updated_v3(fcu, None)
+            .await;
+        assert!(fcu_response.is_err());
+
+        let payload_attributes = OpPayloadAttributes {
+            gas_limit: Some(1000000),
+            ..Default::default()
+        };
+        let fcu_response = test_harness
+            .rpc_client
+            .fork_choice_updated_v3(fcu, Some(payload_attributes))
+            .await;
+        assert!(fcu_response.is_err());
+    }
+
+    #[tokio::test]
+    async fn duplicate_fcu_is_deduplicated() {
+        let payload_id = PayloadId::new([0, 0, 0, 0, 0, 0, 0, 7]);
+        let mut l2_mock = MockEngineServer::new();
+        l2_mock.fcu_response = Ok(ForkchoiceUpdated::new(PayloadStatus::from_status(
+            PayloadStatusEnum::Valid,
+        ))
+        .with_payload_id(payload_id));
+        let builder_mock = MockEngineServer::new();
+
+        let test_harness =
+            TestHarness::new(Some(l2_mock.clone()), Some(builder_mock.clone())).await;
+        let fcu = ForkchoiceState {
+            head_block_hash: FixedBytes::random(),
+            safe_block_hash: FixedBytes::random(),
+            finalized_block_hash: FixedBytes::random(),
+        };
+        let payload_attributes = OpPayloadAttributes {
+            gas_limit: Some(1000000),
+            ..Default::default()
+        };
+
+        // A retried FCU gets the same payload id without restarting the payload building
+        for _ in 0..2 {
+            let fcu_response = test_harness
+                .rpc_client
+                .fork_choice_updated_v3(fcu, Some(payload_attributes.clone()))
+                .await
+                .unwrap();
+            assert_eq!(fcu_response.payload_id, Some(payload_id));
+        }
+        assert_eq!(l2_mock.fcu_requests.lock().len(), 1);
+        assert_eq!(builder_mock.fcu_requests.lock().len(), 1);
+
+        // Once the payload is retrieved, the same FCU is forwarded again
+        test_harness
+            .rpc_client
+            .get_payload_v3(payload_id)
+            .await
+            .ok();
+        test_harness
+            .rpc_client
+            .fork_choice_updated_v3(fcu, Some(payload_attributes))
+            .await
+            .unwrap();
+        assert_eq!(l2_mock.fcu_requests.lock().len(), 2);
+
+        test_harness.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn head_only_fcu_reaches_builder_after_bypass() {
+        let mut l2_mock = MockEngineServer::new();
+        l2_mock.fcu_response = Ok(ForkchoiceUpdated::new(PayloadStatus::from_status(
+            PayloadStatusEnum::Valid,
+        ))
+        .with_payload_id(PayloadId::new([0, 0, 0, 0, 0, 0, 0, 8])));
+        let builder_mock = MockEngineServer::new();
+
+        let test_harness =
+            TestHarness::new(Some(l2_mock.clone()), Some(builder_mock.clone())).await;
+        let fcu = ForkchoiceState {
+            head_block_hash: FixedBytes::random(),
+            safe_block_hash: FixedBytes::random(),
+            finalized_block_hash: FixedBytes::random(),
+        };
+
+        // A deposit-only block bypasses the builder
+        let payload_attributes = OpPayloadAttributes {
+            no_tx_pool: Some(true),
+            ..Default::default()
+        };
+        test_harness
+            .rpc_client
+            .fork_choice_updated_v3(fcu, Some(payload_attributes))
+            .await
+            .unwrap();
+        assert_eq!(builder_mock.fcu_requests.lock().len(), 0);
+
+        // The same head is still forwarded to the builder, once
+        for _ in 0..2 {
+            test_harness
+                .rpc_client
+                .fork_choice_updated_v3(fcu, None)
+                .await
+                .unwrap();
+            sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert_eq!(builder_mock.fcu_requests.lock().len(), 1);
+        assert_eq!(l2_mock.fcu_requests.lock().len(), 3);
+
+        test_harness.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn head_only_fcu_retried_after_builder_failure() {
+        let mut builder_mock = MockEngineServer::new();
+        builder_mock.fcu_response = Err(ErrorObject::owned(
+            INTERNAL_ERROR_CODE,
+            "builder down",
+            None::<String>,
+        ));
+
+        let test_harness = TestHarness::new(None, Some(builder_mock.clone())).await;
+        let fcu = ForkchoiceState {
+            head_block_hash: FixedBytes::random(),
+            safe_block_hash: FixedBytes::random(),
+            finalized_block_hash: FixedBytes::random(),
+        };
+
+        // The builder did not acknowledge the head, so the same FCU is forwarded again
+        for _ in 0..2 {
+            test_harness
+                .rpc_client
+                .fork_choice_updated_v3(fcu, None)
+                .await
+                .unwrap();
+            sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert_eq!(builder_mock.fcu_requests.lock().len(), 2);
+
+        test_harness.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn exchange_capabilities_intersection() {
+        let mut l2_mock = MockEngineServer::new();
+        l2_mock.exchange_capabilities_response = Ok(vec![
+            "engine_forkchoiceUpdatedV3".to_string(),
+            "engine_getPayloadV3".to_string(),
+            "engine_getPayloadV4".to_string(),
+            "engine_getBlobsV1".to_string(),
+        ]);
+        let mut builder_mock = MockEngineServer::new();
+        builder_mock.exchange_capabilities_response = Ok(vec![
+            "engine_forkchoiceUpdatedV3".to_string(),
+            "engine_getPayloadV3".to_string(),
+            "engine_getBlobsV1".to_string(),
+        ]);
+
+        let test_harness = TestHarness::new(Some(l2_mock), Some(builder_mock)).await;
+        let capabilities = test_harness
+            .rpc_client
+            .exchange_capabilities(vec![])
+            .await
+            .unwrap();
+        assert_eq!(
+            capabilities,
+            vec!["engine_forkchoiceUpdatedV3", "engine_getPayloadV3"]
+        );
+        test_harness.cleanup().await;
+
+        // The l2 capabilities are used when the builder is down
+        let mut builder_mock = MockEngineServer::new();
+        builder_mock.exchange_capabilities_response = Err(ErrorObject::owned(
+            INVALID_REQUEST_CODE,
+            "builder down",
+            None::<String>,
+        ));
+
+        let test_harness = TestHarness::new(None, Some(builder_mock)).await;
+        let capabilities = test_harness
+            .rpc_client
+            .exchange_capabilities(vec![])
+            .await
+            .unwrap();
+        assert_eq!(capabilities, ENGINE_CAPABILITIES.to_vec());
+        test_harness.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn payload_bodies_served_by_l2() {
+        let test_harness = TestHarness::new(None, None).await;
+
+        let bodies = test_harness
+            .rpc_client
+            .get_payload_bodies_by_range_v1(U64::from(1), U64::from(2))
+            .await
+            .unwrap();
+        assert!(bodies.is_empty());
+
+        // The bodies of the canonical chain are only requested from the l2
+        assert_eq!(
+            *test_harness.l2_mock.payload_bodies_requests.lock(),
+            vec![(U64::from(1), U64::from(2))]
+        );
+        assert!(
+            test_harness
+                .builder_mock
+                .payload_bodies_requests
+                .lock()
+                .is_empty()
+        );
+
+        test_harness.cleanup().await;
+    }
+
+    /// A builder taking `delay` to respond to the calls keeping it in sync, failing them if
+    /// `fail` is set.
+    fn syncing_builder(delay: Duration, fail: bool) -> MockEngineServer {
+        let mut builder_mock = MockEngineServer::new();
+        builder_mock.sync_delay = Some(delay);
+        if fail {
+            let error = ErrorObject::owned(INVALID_REQUEST_CODE, "builder down", None::<String>);
+            builder_mock.fcu_response = Err(error.clone());
+            builder_mock.new_payload_response = Err(error);
+        }
+        builder_mock
+    }
+
+    #[tokio::test]
+    async fn fcu_forwarding_modes() {
+        let delay = Duration::from_millis(300);
+        for (forwarding, fail, forwarded, waits, fails) in [
+            (BuilderForwarding::Async, false, true, false, false),
+            (BuilderForwarding::Async, true, true, false, false),
+            (BuilderForwarding::Sync, false, true, true, false),
+            (BuilderForwarding::Sync, true, true, true, true),
+            (BuilderForwarding::Disabled, false, false, false, false),
+        ] {
+            let builder_mock = syncing_builder(delay, fail);
+            let test_harness = TestHarness::new_with_server(None, Some(builder_mock), |server| {
+                server.with_fcu_forwarding(forwarding)
+            })
+            .await;
+
+            let fcu = ForkchoiceState {
+                head_block_hash: FixedBytes::random(),
+                safe_block_hash: FixedBytes::random(),
+                finalized_block_hash: FixedBytes::random(),
+            };
+            let started = Instant::now();
+            let response = test_harness
+                .rpc_client
+                .fork_choice_updated_v3(fcu, None)
+                .await;
+            let elapsed = started.elapsed();
+            assert_eq!(response.is_err(), fails, "{forwarding:?}, failing: {fail}");
+            assert_eq!(elapsed >= delay, waits, "{forwarding:?}, failing: {fail}");
+
+            // Wait for the calls forwarded without waiting to reach the builder
+            sleep(Duration::from_millis(100)).await;
+            assert_eq!(
+                test_harness.builder_mock.fcu_requests.lock().len(),
+                usize::from(forwarded),
+                "{forwarding:?}"
+            );
+            assert_eq!(test_harness.l2_mock.fcu_requests.lock().len(), 1);
+            test_harness.cleanup().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn new_payload_forwarding_modes() {
+        let delay = Duration::from_millis(300);
+        for (forwarding, fail, forwarded, waits, fails) in [
+            (BuilderForwarding::Async, false, true, false, false),
+            (BuilderForwarding::Async, true, true, false, false),
+            (BuilderForwarding::Sync, false, true, true, false),
+            (BuilderForwarding::Sync, true, true, true, true),
+            (BuilderForwarding::Disabled, false, false, false, false),
+        ] {
+            let builder_mock = syncing_builder(delay, fail);
+            let test_harness = TestHarness::new_with_server(None, Some(builder_mock), |server| {
+                server.with_new_payload_forwarding(forwarding)
+            })
+            .await;
+
+            let payload = test_harness
+                .l2_mock
+                .get_payload_response
+                .clone()
+                .unwrap()
+                .execution_payload;
+            let started = Instant::now();
+            let response = test_harness
+                .rpc_client
+                .new_payload_v3(payload, vec![], B256::ZERO)
+                .await;
+            let elapsed = started.elapsed();
+            assert_eq!(response.is_err(), fails, "{forwarding:?}, failing: {fail}");
+            assert_eq!(elapsed >= delay, waits, "{forwarding:?}, failing: {fail}");
+
+            // Wait for the calls forwarded without waiting to reach the builder
+            sleep(Duration::from_millis(100)).await;
+            assert_eq!(
+                test_harness.builder_mock.new_payload_requests.lock().len(),
+                usize::from(forwarded),
+                "{forwarding:?}"
+            );
+            assert_eq!(test_harness.l2_mock.new_payload_requests.lock().len(), 1);
+            test_harness.cleanup().await;
+        }
+    }
+
+    /// Collects the correlation ids recorded on the spans, keyed by span name.
+    #[derive(Clone, Default)]
+    struct CorrelationIds(Arc<Mutex<Vec<(&'static str, String)>>>);
+
+    struct CorrelationIdVisitor<'a>(&'a CorrelationIds, &'static str);
+
+    impl tracing::field::Visit for CorrelationIdVisitor<'_> {
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            if field.name() == "correlation_id" {
+                self.0.0.lock().push((self.1, value.to_string()));
+            }
+        }
+
+        fn record_debug(&mut self, _field: &tracing::field::Field, _value: &dyn std::fmt::Debug) {}
+    }
+
+    impl<S> tracing_subscriber::Layer<S> for CorrelationIds
+    where
+        S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        fn on_record(
+            &self,
+            id: &tracing::span::Id,
+            values: &tracing::span::Record<'_>,
+            ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            if let Some(span) = ctx.span(id) {
+                values.record(&mut CorrelationIdVisitor(self, span.name()));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn get_payload_reuses_fcu_correlation_id() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let correlation_ids = CorrelationIds::default();
+        let _guard = tracing::subscriber::set_default(
+            tracing_subscriber::registry().with(correlation_ids.clone()),
+        );
+
+        let payload_id = PayloadId::new([0, 0, 0, 0, 0, 0, 0, 42]);
+        let mut l2_mock = MockEngineServer::new();
+        l2_mock.fcu_response = Ok(ForkchoiceUpdated::new(PayloadStatus::from_status(
+            PayloadStatusEnum::Valid,
+        ))
+        .with_payload_id(payload_id));
+
+        let test_harness = TestHarness::new(Some(l2_mock), None).await;
+        let fcu = ForkchoiceState {
+            head_block_hash: FixedBytes::random(),
+            safe_block_hash: FixedBytes::random(),
+            finalized_block_hash: FixedBytes::random(),
+        };
+        let payload_attributes = OpPayloadAttributes {
+            gas_limit: Some(0x1c9c380),
+            ..Default::default()
+        };
+        test_harness
+            .rpc_client
+            .fork_choice_updated_v3(fcu, Some(payload_attributes))
+            .await
+            .unwrap();
+        // The get payload call arrives as another request, with its own correlation id
+        test_harness
+            .rpc_client
+            .get_payload_v3(payload_id)
+            .await
+            .unwrap();
+        test_harness.cleanup().await;
+
+        // A payload unknown to rollup-boost keeps the id of its own request
+        let test_harness = TestHarness::new(None, None).await;
+        let _ = test_harness
+            .rpc_client
+            .get_payload_v3(PayloadId::new([0, 0, 0, 0, 0, 0, 0, 1]))
+            .await;
+        test_harness.cleanup().await;
+
+        let ids = |span: &str| -> Vec<String> {
+            correlation_ids
+                .0
+                .lock()
+                .iter()
+                .filter(|(name, _)| *name == span)
+                .map(|(_, id)| id.clone())
+                .collect()
+        };
+        let fcu_ids = ids("fork_choice_updated_v3");
+        let get_payload_ids = ids("get_payload_v3");
+        assert_eq!(fcu_ids.len(), 1);
+        assert_eq!(get_payload_ids.len(), 2);
+        assert_eq!(get_payload_ids[0], fcu_ids[0]);
+        assert_ne!(get_payload_ids[1], fcu_ids[0]);
     }
 }
@@ -0,0 +1,101 @@
+use alloy_primitives::U256;
+use metrics::{counter, histogram};
+use serde::Serialize;
+use tracing::info;
+
+use crate::OpExecutionPayloadEnvelope;
+
+/// Differences between a builder payload and the l2 payload built for the same slot.
+///
+/// Used in [`ExecutionMode::Shadow`](crate::ExecutionMode::Shadow) to evaluate a builder
+/// in production without ever returning its blocks to the proposer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PayloadComparison {
+    /// Builder tx count minus l2 tx count
+    pub tx_count_delta: i64,
+    /// Builder gas used minus l2 gas used
+    pub gas_used_delta: i128,
+    /// Builder block value minus l2 block value, in wei, saturating at the i128 bounds
+    pub block_value_delta: i128,
+    /// Whether both payloads share the same state root
+    pub state_root_match: bool,
+}
+
+impl PayloadComparison {
+    pub fn new(
+        builder_payload: &OpExecutionPayloadEnvelope,
+        l2_payload: &OpExecutionPayloadEnvelope,
+    ) -> Self {
+        let to_i128 = |value: U256| i128::try_from(value).unwrap_or(i128::MAX);
+
+        Self {
+            tx_count_delta: builder_payload.tx_count() as i64 - l2_payload.tx_count() as i64,
+            gas_used_delta: builder_payload.gas_used() as i128 - l2_payload.gas_used() as i128,
+            block_value_delta: to_i128(builder_payload.block_value())
+                .saturating_sub(to_i128(l2_payload.block_value())),
+            state_root_match: builder_payload.state_root() == l2_payload.state_root(),
+        }
+    }
+
+    /// Logs the comparison and exports it as metrics.
+    pub fn record(&self) {
+        counter!("shadow.comparisons", "state_root_match" => self.state_root_match.to_string())
+            .increment(1);
+        histogram!("shadow.tx_count_delta").record(self.tx_count_delta as f64);
+        histogram!("shadow.gas_used_delta").record(self.gas_used_delta as f64);
+        histogram!("shadow.block_value_delta").record(self.block_value_delta as f64);
+
+        info!(
+            message = "shadow payload comparison",
+            tx_count_delta = self.tx_count_delta,
+            gas_used_delta = %self.gas_used_delta,
+            block_value_delta = %self.block_value_delta,
+            state_root_match = self.state_root_match,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{B256, Bytes};
+    use op_alloy_rpc_types_engine::OpExecutionPayloadEnvelopeV3;
+
+    const PAYLOAD: &str = r#"{"executionPayload":{"parentHash":"0xe927a1448525fb5d32cb50ee1408461a945ba6c39bd5cf5621407d500ecc8de9","feeRecipient":"0x0000000000000000000000000000000000000000","stateRoot":"0x10f8a0830000e8edef6d00cc727ff833f064b1950afd591ae41357f97e543119","receiptsRoot":"0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421","logsBloom":"0x00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000","prevRandao":"0xe0d8b4521a7da1582a713244ffb6a86aa1726932087386e2dc7973f43fc6cb24","blockNumber":"0x1","gasLimit":"0x2ffbd2","gasUsed":"0x0","timestamp":"0x1235","extraData":"0xd883010d00846765746888676f312e32312e30856c696e7578","baseFeePerGas":"0x342770c0","blockHash":"0x44d0fa5f2f73a938ebb96a2a21679eb8dea3e7b7dd8fd9f35aa756dda8bf0a8a","transactions":[],"withdrawals":[],"blobGasUsed":"0x0","excessBlobGas":"0x0"},"blockValue":"0x0","blobsBundle":{"commitments":[],"proofs":[],"blobs":[]},"shouldOverrideBuilder":false,"parentBeaconBlockRoot":"0xdead00000000000000000000000000000000000000000000000000000000beef"}"#;
+
+    #[test]
+    fn test_payload_comparison() -> eyre::Result<()> {
+        let mut builder_payload: OpExecutionPayloadEnvelopeV3 = serde_json::from_str(PAYLOAD)?;
+        let mut l2_payload = builder_payload.clone();
+
+        let builder_inner = &mut builder_payload
+            .execution_payload
+            .payload_inner
+            .payload_inner;
+        builder_inner.gas_used = 100;
+        builder_inner.transactions = vec![Bytes::new(), Bytes::new()];
+        builder_payload.block_value = U256::from(50);
+
+        let l2_inner = &mut l2_payload.execution_payload.payload_inner.payload_inner;
+        l2_inner.gas_used = 300;
+        l2_inner.transactions = vec![Bytes::new()];
+        l2_inner.state_root = B256::ZERO;
+        l2_payload.block_value = U256::from(20);
+
+        let comparison = PayloadComparison::new(
+            &OpExecutionPayloadEnvelope::V3(builder_payload),
+            &OpExecutionPayloadEnvelope::V3(l2_payload),
+        );
+
+        assert_eq!(
+            comparison,
+            PayloadComparison {
+                tx_count_delta: 1,
+                gas_used_delta: -200,
+                block_value_delta: 30,
+                state_root_match: false,
+            }
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+use std::time::{Duration, Instant};
+
+use alloy_rpc_types_engine::PayloadId;
+use clap::Parser;
+use futures::StreamExt;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use url::Url;
+
+use crate::{FlashblocksControlV1, FlashblocksPayloadV1, LatencyStats};
+
+#[derive(Parser, Clone, Debug)]
+pub struct StreamArgs {
+    /// Flashblocks websocket to connect to, of rollup-boost or a builder
+    #[arg(default_value = "ws://127.0.0.1:1111")]
+    pub url: Url,
+
+    /// Print the flashblocks as JSON lines instead of a summary. Continuity errors and
+    /// latencies are printed to stderr
+    #[arg(long)]
+    pub json: bool,
+
+    /// Exit after this many payloads
+    #[arg(long)]
+    pub payloads: Option<usize>,
+}
+
+/// Checks that the flashblocks of a stream follow each other: every payload starts with
+/// a base at index 0, and its indices increase by one under the same payload ID.
+#[derive(Debug, Default)]
+pub struct ContinuityChecker {
+    current: Option<(PayloadId, u64)>,
+    errors: usize,
+}
+
+impl ContinuityChecker {
+    /// Checks the next flashblock of the stream, returning why it breaks the continuity.
+    pub fn check(&mut self, flashblock: &FlashblocksPayloadV1) -> Option<String> {
+        let error = match (self.current, flashblock.index) {
+            (_, 0) if flashblock.base.is_none() => {
+                Some("the first flashblock of the payload has no base".to_string())
+            }
+            (_, 0) => None,
+            (_, _) if flashblock.base.is_some() => {
+                Some(format!("flashblock {} has a base", flashblock.index))
+            }
+            (None, index) => Some(format!("the stream starts at flashblock {index}")),
+            (Some((payload_id, _)), _) if payload_id != flashblock.payload_id => Some(format!(
+                "flashblock {} of payload {} follows payload {payload_id}",
+                flashblock.index, flashblock.payload_id
+            )),
+            (Some((_, last)), index) if index != last + 1 => {
+                Some(format!("flashblock {index} follows flashblock {last}"))
+            }
+            _ => None,
+        };
+        self.current = Some((flashblock.payload_id, flashblock.index));
+        if error.is_some() {
+            self.errors += 1;
+        }
+        error
+    }
+
+    /// Number of flashblocks that broke the continuity so far.
+    pub fn errors(&self) -> usize {
+        self.errors
+    }
+}
+
+/// Flashblocks received for the payload being streamed.
+struct StreamedPayload {
+    payload_id: PayloadId,
+    started: Instant,
+    flashblocks: usize,
+    transactions: usize,
+    gas_used: u64,
+}
+
+impl StreamedPayload {
+    /// Prints the summary of the payload, whose last flashblock was received at `ended`,
+    /// and returns the time its flashblocks were streamed over.
+    fn finish(self, ended: Instant, quiet: bool) -> Duration {
+        let duration = ended - self.started;
+        if !quiet {
+            println!(
+                "Payload {}: {} flashblocks, {} transactions, {} gas in {duration:?}",
+                self.payload_id, self.flashblocks, self.transactions, self.gas_used
+            );
+        }
+        duration
+    }
+}
+
+impl StreamArgs {
+    pub async fn run(self) -> eyre::Result<()> {
+        let (mut ws_stream, _) = connect_async(self.url.as_str()).await?;
+        eprintln!("Connected to {}", self.url);
+
+        let mut checker = ContinuityChecker::default();
+        let mut intervals = vec![];
+        let mut durations = vec![];
+        let mut payloads = 0;
+        let mut current: Option<StreamedPayload> = None;
+        let mut last_received: Option<Instant> = None;
+
+        loop {
+            let message = tokio::select! {
+                message = ws_stream.next() => message,
+                _ = tokio::signal::ctrl_c() => None,
+            };
+            let Some(message) = message else {
+                break;
+            };
+            let received = Instant::now();
+            let Message::Text(text) = message? else {
+                continue;
+            };
+            // Control messages are not flashblocks. A reset payload is streamed again from
+            // its first flashblock
+            let Ok(flashblock) = serde_json::from_str::<FlashblocksPayloadV1>(&text) else {
+                match serde_json::from_str::<FlashblocksControlV1>(&text) {
+                    Ok(FlashblocksControlV1::Reset(reset))
+                        if current
+                            .as_ref()
+                            .is_some_and(|payload| payload.payload_id == reset.payload_id) =>
+                    {
+                        current = None;
+                    }
+                    _ => {}
+                }
+                continue;
+            };
+
+            if let Some(error) = checker.check(&flashblock) {
+                eprintln!("Continuity error: {error}");
+            }
+            if flashblock.index == 0 {
+                if let Some(payload) = current.take() {
+                    durations.push(payload.finish(last_received.unwrap_or(received), self.json));
+                    payloads += 1;
+                    if self.payloads.is_some_and(|limit| payloads >= limit) {
+                        break;
+                    }
+                }
+                current = Some(StreamedPayload {
+                    payload_id: flashblock.payload_id,
+                    started: received,
+                    flashblocks: 0,
+                    transactions: 0,
+                    gas_used: 0,
+                });
+            } else if let Some(last) = last_received {
+                intervals.push(received - last);
+            }
+            last_received = Some(received);
+
+            if let Some(payload) = current.as_mut() {
+                payload.flashblocks += 1;
+                payload.transactions += flashblock.diff.transactions.len();
+                payload.gas_used = flashblock.diff.gas_used;
+            }
+
+            if self.json {
+                println!("{text}");
+            } else {
+                print_flashblock(&flashblock);
+            }
+        }
+
+        // The payload being streamed when the stream ended
+        if let Some(payload) = current {
+            durations.push(payload.finish(last_received.unwrap_or_else(Instant::now), self.json));
+            payloads += 1;
+        }
+        eprintln!(
+            "{payloads} payloads, {} continuity errors",
+            checker.errors()
+        );
+        print_latencies("flashblock interval", &intervals);
+        print_latencies("payload duration", &durations);
+        Ok(())
+    }
+}
+
+fn print_flashblock(flashblock: &FlashblocksPayloadV1) {
+    match &flashblock.base {
+        Some(base) => println!(
+            "{} #{} block={} parent={} timestamp={} gas_limit={} base_fee={}",
+            flashblock.payload_id,
+            flashblock.index,
+            base.block_number,
+            base.parent_hash,
+            base.timestamp,
+            base.gas_limit,
+            base.base_fee_per_gas
+        ),
+        None => println!(
+            "{} #{} transactions={} gas_used={}",
+            flashblock.payload_id,
+            flashblock.index,
+            flashblock.diff.transactions.len(),
+            flashblock.diff.gas_used
+        ),
+    }
+    if !flashblock.diff.block_hash.is_zero() {
+        println!(
+            "{} #{} block_hash={} state_root={}",
+            flashblock.payload_id,
+            flashblock.index,
+            flashblock.diff.block_hash,
+            flashblock.diff.state_root
+        );
+    }
+}
+
+fn print_latencies(name: &str, samples: &[Duration]) {
+    if samples.is_empty() {
+        return;
+    }
+    let stats = LatencyStats::from_samples(samples);
+    eprintln!(
+        "{name} (ms): mean={:.2} p50={:.2} p90={:.2} p99={:.2} max={:.2}",
+        stats.mean_ms, stats.p50_ms, stats.p90_ms, stats.p99_ms, stats.max_ms
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExecutionPayloadBaseV1;
+
+    fn flashblock(payload_id: u8, index: u64) -> FlashblocksPayloadV1 {
+        FlashblocksPayloadV1 {
+            payload_id: PayloadId::new([payload_id; 8]),
+            index,
+            base: (index == 0).then(ExecutionPayloadBaseV1::default),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_continuity() {
+        let mut checker = ContinuityChecker::default();
+        assert_eq!(checker.check(&flashblock(1, 0)), None);
+        assert_eq!(checker.check(&flashblock(1, 1)), None);
+        assert_eq!(checker.check(&flashblock(1, 2)), None);
+        assert_eq!(checker.errors(), 0);
+
+        // A gap, a payload ID change and a new payload without base
+        assert!(checker.check(&flashblock(1, 4)).is_some());
+        assert!(checker.check(&flashblock(2, 5)).is_some());
+        let mut without_base = flashblock(3, 0);
+        without_base.base = None;
+        assert!(checker.check(&without_base).is_some());
+        assert_eq!(checker.errors(), 3);
+
+        // A payload may start before the previous one completes
+        assert_eq!(checker.check(&flashblock(4, 0)), None);
+        assert_eq!(checker.check(&flashblock(4, 1)), None);
+
+        let mut checker = ContinuityChecker::default();
+        assert!(checker.check(&flashblock(1, 3)).is_some());
+    }
+}
@@ -0,0 +1,432 @@
+use std::{
+    net::SocketAddr,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use alloy_primitives::{Address, B256};
+use alloy_rpc_types_engine::{ForkchoiceState, JwtSecret, PayloadAttributes, PayloadId};
+use eyre::{OptionExt, eyre};
+use futures::StreamExt;
+use http::Uri;
+use jsonrpsee::RpcModule;
+use jsonrpsee::http_client::{HttpClient, HttpClientBuilder};
+use jsonrpsee::server::{Server, ServerHandle};
+use op_alloy_rpc_types_engine::OpPayloadAttributes;
+use parking_lot::Mutex;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use url::Url;
+
+use crate::{
+    EngineApiClient, EngineApiExt, ExecutionMode, Flashblocks, FlashblocksLimits,
+    FlashblocksPayloadV1, FlashblocksService, IngestOverflowPolicy, MockBuilder, MockBuilderConfig,
+    OpExecutionPayloadEnvelope, PayloadSource, PublisherConfig, RollupBoostServer, RpcClient,
+    probe::ProbeLayer,
+};
+
+/// Timeout of the requests sent by rollup-boost to the mock servers, in milliseconds.
+const CLIENT_TIMEOUT: u64 = 2000;
+
+/// Configures and starts a [`TestHarness`].
+#[derive(Debug, Clone)]
+pub struct TestHarnessBuilder {
+    builder: MockBuilderConfig,
+    l2: MockBuilderConfig,
+    flashblocks: bool,
+    limits: FlashblocksLimits,
+    execution_mode: ExecutionMode,
+}
+
+impl Default for TestHarnessBuilder {
+    fn default() -> Self {
+        Self {
+            builder: MockBuilderConfig::default(),
+            l2: MockBuilderConfig::default(),
+            flashblocks: true,
+            limits: FlashblocksLimits::default(),
+            execution_mode: ExecutionMode::Enabled,
+        }
+    }
+}
+
+impl TestHarnessBuilder {
+    /// Shape and faults of the payloads built by the mock builder.
+    pub fn with_builder(mut self, config: MockBuilderConfig) -> Self {
+        self.builder = config;
+        self
+    }
+
+    /// Shape of the payloads built by the mock L2 client.
+    pub fn with_l2(mut self, config: MockBuilderConfig) -> Self {
+        self.l2 = config;
+        self
+    }
+
+    /// Whether rollup-boost builds payloads from the flashblocks of the builder.
+    pub fn with_flashblocks(mut self, flashblocks: bool) -> Self {
+        self.flashblocks = flashblocks;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: FlashblocksLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_execution_mode(mut self, execution_mode: ExecutionMode) -> Self {
+        self.execution_mode = execution_mode;
+        self
+    }
+
+    /// Starts the mock servers and rollup-boost, on ephemeral local ports.
+    pub async fn start(self) -> eyre::Result<TestHarness> {
+        let localhost: SocketAddr = "127.0.0.1:0".parse()?;
+        let jwt_secret = JwtSecret::random();
+
+        let isthmus = self.builder.isthmus;
+        let flashblocks_per_block = self.builder.flashblocks_per_block;
+        let flashblocks_interval = self.builder.flashblocks_interval;
+
+        let builder = MockBuilder::new(self.builder);
+        let builder = match self.flashblocks {
+            true => builder.with_flashblocks(localhost)?,
+            false => builder,
+        };
+        let builder_flashblocks_addr = builder.flashblocks_addr();
+        let (builder_server, builder_addr) = builder.clone().start(localhost).await?;
+        let l2 = MockBuilder::new(self.l2);
+        let (l2_server, l2_addr) = l2.clone().start(localhost).await?;
+
+        let l2_client = RpcClient::new(
+            Uri::from_str(&format!("http://{l2_addr}"))?,
+            jwt_secret,
+            CLIENT_TIMEOUT,
+            PayloadSource::L2,
+        )?;
+        let builder_client = RpcClient::new(
+            Uri::from_str(&format!("http://{builder_addr}"))?,
+            jwt_secret,
+            CLIENT_TIMEOUT,
+            PayloadSource::Builder,
+        )?;
+
+        let (builder_client, flashblocks_addr): (Arc<dyn EngineApiExt>, _) =
+            match builder_flashblocks_addr {
+                Some(addr) => {
+                    let service = FlashblocksService::new(
+                        builder_client,
+                        &[localhost],
+                        PublisherConfig::default(),
+                    )?
+                    .with_limits(self.limits);
+                    let service = Flashblocks::run(
+                        service,
+                        Url::parse(&format!("ws://{addr}"))?,
+                        100,
+                        IngestOverflowPolicy::Block,
+                    );
+                    let outbound_addr = service.outbound_addr();
+                    (Arc::new(service), Some(outbound_addr))
+                }
+                None => (Arc::new(builder_client), None),
+            };
+
+        let (probe_layer, probes) = ProbeLayer::new();
+        let rollup_boost = RollupBoostServer::new(
+            l2_client,
+            builder_client,
+            Arc::new(Mutex::new(self.execution_mode)),
+            None,
+            probes,
+            60,
+            5,
+        );
+        let module: RpcModule<()> = rollup_boost.try_into()?;
+        let server = Server::builder()
+            .set_http_middleware(tower::ServiceBuilder::new().layer(probe_layer))
+            .build(localhost)
+            .await?;
+        let rpc_addr = server.local_addr()?;
+        let server = server.start(module);
+
+        // Subscribe before the first payload, so that no flashblock is missed
+        let (flashblocks_tx, flashblocks) = mpsc::unbounded_channel();
+        let subscriber = match flashblocks_addr {
+            Some(addr) => {
+                let (ws_stream, _) = connect_async(format!("ws://{addr}")).await?;
+                Some(tokio::spawn(subscribe(ws_stream, flashblocks_tx)))
+            }
+            None => None,
+        };
+
+        Ok(TestHarness {
+            builder,
+            l2,
+            servers: vec![builder_server, l2_server, server],
+            rpc_addr,
+            flashblocks_addr,
+            engine: HttpClientBuilder::new().build(format!("http://{rpc_addr}"))?,
+            flashblocks,
+            subscriber,
+            isthmus,
+            block_time: flashblocks_interval * flashblocks_per_block as u32,
+            head: ForkchoiceState::default(),
+            timestamp: 0,
+        })
+    }
+}
+
+/// Forwards the flashblocks republished by rollup-boost to the harness.
+async fn subscribe<S>(mut ws_stream: S, flashblocks: mpsc::UnboundedSender<FlashblocksPayloadV1>)
+where
+    S: futures::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    while let Some(Ok(message)) = ws_stream.next().await {
+        // Control messages, such as reorgs, are not flashblocks
+        let Message::Text(text) = message else {
+            continue;
+        };
+        let Ok(flashblock) = serde_json::from_str(&text) else {
+            continue;
+        };
+        if flashblocks.send(flashblock).is_err() {
+            return;
+        }
+    }
+}
+
+/// rollup-boost running in process between a [`MockBuilder`] and a mock L2 client, so
+/// that integration tests can drive the sidecar like a consensus client would.
+///
+/// ```ignore
+/// let mut harness = TestHarness::builder().start().await?;
+/// let payload = harness.build_block().await?;
+/// let flashblocks = harness.flashblocks();
+/// assert_eq!(flashblocks.last().unwrap().diff.block_hash, payload.block_hash());
+/// harness.stop().await;
+/// ```
+///
+/// The requests are not authenticated, and the payloads are built from synthetic
+/// transactions, so they are only meaningful to rollup-boost.
+#[derive(Debug)]
+pub struct TestHarness {
+    pub builder: MockBuilder,
+    pub l2: MockBuilder,
+    servers: Vec<ServerHandle>,
+    rpc_addr: SocketAddr,
+    flashblocks_addr: Option<SocketAddr>,
+    engine: HttpClient,
+    flashblocks: mpsc::UnboundedReceiver<FlashblocksPayloadV1>,
+    subscriber: Option<JoinHandle<()>>,
+    isthmus: bool,
+    block_time: Duration,
+    head: ForkchoiceState,
+    timestamp: u64,
+}
+
+impl TestHarness {
+    pub fn builder() -> TestHarnessBuilder {
+        TestHarnessBuilder::default()
+    }
+
+    /// Address rollup-boost serves the Engine API on.
+    pub fn rpc_addr(&self) -> SocketAddr {
+        self.rpc_addr
+    }
+
+    /// Address rollup-boost republishes the flashblocks on, if flashblocks are enabled.
+    pub fn flashblocks_addr(&self) -> Option<SocketAddr> {
+        self.flashblocks_addr
+    }
+
+    /// Engine API client of rollup-boost, for the calls the harness does not drive.
+    pub fn engine(&self) -> &HttpClient {
+        &self.engine
+    }
+
+    /// Head of the chain built by the harness so far.
+    pub fn head(&self) -> ForkchoiceState {
+        self.head
+    }
+
+    /// Attributes of the next block, with a fresh timestamp so that its flashblocks are
+    /// never stale.
+    pub fn next_attributes(&mut self) -> OpPayloadAttributes {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.timestamp = now.max(self.timestamp + 1);
+
+        OpPayloadAttributes {
+            payload_attributes: PayloadAttributes {
+                timestamp: self.timestamp,
+                prev_randao: B256::random(),
+                suggested_fee_recipient: Address::random(),
+                withdrawals: Some(vec![]),
+                parent_beacon_block_root: Some(B256::random()),
+            },
+            gas_limit: Some(30_000_000),
+            ..Default::default()
+        }
+    }
+
+    /// Sends a fork choice update building a block with `attributes` on the current head.
+    pub async fn fork_choice_updated(
+        &self,
+        attributes: OpPayloadAttributes,
+    ) -> eyre::Result<PayloadId> {
+        let updated = self
+            .engine
+            .fork_choice_updated_v3(self.head, Some(attributes))
+            .await?;
+        updated
+            .payload_id
+            .ok_or_eyre("the fork choice update returned no payload ID")
+    }
+
+    /// Fetches the payload `payload_id`, with the version matching the hardfork of the
+    /// mock builder.
+    pub async fn get_payload(
+        &self,
+        payload_id: PayloadId,
+    ) -> eyre::Result<OpExecutionPayloadEnvelope> {
+        Ok(match self.isthmus {
+            true => OpExecutionPayloadEnvelope::V4(self.engine.get_payload_v4(payload_id).await?),
+            false => OpExecutionPayloadEnvelope::V3(self.engine.get_payload_v3(payload_id).await?),
+        })
+    }
+
+    /// Runs a whole FCU, getPayload and newPayload cycle, leaving the builder the time
+    /// to stream all the flashblocks of the block, and moves the head to the new block.
+    pub async fn build_block(&mut self) -> eyre::Result<OpExecutionPayloadEnvelope> {
+        let attributes = self.next_attributes();
+        let parent_beacon_block_root = attributes
+            .payload_attributes
+            .parent_beacon_block_root
+            .unwrap_or_default();
+        let payload_id = self.fork_choice_updated(attributes).await?;
+        tokio::time::sleep(self.block_time).await;
+
+        let payload = self.get_payload(payload_id).await?;
+        let status = match &payload {
+            OpExecutionPayloadEnvelope::V3(v3) => {
+                self.engine
+                    .new_payload_v3(
+                        v3.execution_payload.clone(),
+                        vec![],
+                        parent_beacon_block_root,
+                    )
+                    .await?
+            }
+            OpExecutionPayloadEnvelope::V4(v4) => {
+                self.engine
+                    .new_payload_v4(
+                        v4.execution_payload.clone(),
+                        vec![],
+                        parent_beacon_block_root,
+                        vec![],
+                    )
+                    .await?
+            }
+            _ => return Err(eyre!("unexpected payload version {:?}", payload.version())),
+        };
+        if !status.status.is_valid() {
+            return Err(eyre!("the payload was not accepted: {:?}", status.status));
+        }
+
+        let block_hash = payload.block_hash();
+        self.head = ForkchoiceState {
+            head_block_hash: block_hash,
+            safe_block_hash: block_hash,
+            finalized_block_hash: self.head.finalized_block_hash,
+        };
+        Ok(payload)
+    }
+
+    /// Waits up to `timeout` for the next flashblock republished by rollup-boost.
+    pub async fn next_flashblock(
+        &mut self,
+        timeout: Duration,
+    ) -> eyre::Result<FlashblocksPayloadV1> {
+        tokio::time::timeout(timeout, self.flashblocks.recv())
+            .await?
+            .ok_or_eyre("the flashblocks stream of rollup-boost is closed")
+    }
+
+    /// The flashblocks republished by rollup-boost since the last call.
+    pub fn flashblocks(&mut self) -> Vec<FlashblocksPayloadV1> {
+        std::iter::from_fn(|| self.flashblocks.try_recv().ok()).collect()
+    }
+
+    /// Stops rollup-boost and the mock servers.
+    pub async fn stop(self) {
+        if let Some(subscriber) = self.subscriber {
+            subscriber.abort();
+        }
+        for server in self.servers {
+            let _ = server.stop();
+            server.stopped().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockBuilderFault;
+
+    fn config(script: Vec<MockBuilderFault>) -> MockBuilderConfig {
+        MockBuilderConfig {
+            flashblocks_per_block: 4,
+            flashblocks_interval: Duration::from_millis(20),
+            transactions_per_flashblock: 2,
+            script,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_harness_flashblocks() -> eyre::Result<()> {
+        let mut harness = TestHarness::builder()
+            .with_builder(config(vec![]))
+            .start()
+            .await?;
+
+        for _ in 0..2 {
+            let payload = harness.build_block().await?;
+            let flashblocks = harness.flashblocks();
+            assert_eq!(flashblocks.len(), 4);
+            assert_eq!(payload.tx_count(), 8);
+            assert_eq!(
+                flashblocks
+                    .last()
+                    .map(|flashblock| flashblock.diff.block_hash),
+                Some(payload.block_hash())
+            );
+            assert_eq!(harness.head().head_block_hash, payload.block_hash());
+        }
+
+        harness.stop().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_harness_wrong_payload_id() -> eyre::Result<()> {
+        let mut harness = TestHarness::builder()
+            .with_builder(config(vec![MockBuilderFault::WrongPayloadId]))
+            .start()
+            .await?;
+
+        // The flashblocks are dropped, the payload is fetched from the builder
+        let payload = harness.build_block().await?;
+        assert!(harness.flashblocks().is_empty());
+        assert_eq!(payload.tx_count(), 8);
+
+        harness.stop().await;
+        Ok(())
+    }
+}
@@ -99,6 +99,12 @@ impl EngineApi {
         payload_id: PayloadId,
     ) -> eyre::Result<OpExecutionPayloadEnvelope> {
         match version {
+            PayloadVersion::V1 => Ok(OpExecutionPayloadEnvelope::V1(
+                EngineApiClient::get_payload_v1(&self.engine_api_client, payload_id).await?,
+            )),
+            PayloadVersion::V2 => Ok(OpExecutionPayloadEnvelope::V2(
+                EngineApiClient::get_payload_v2(&self.engine_api_client, payload_id).await?,
+            )),
             PayloadVersion::V3 => Ok(OpExecutionPayloadEnvelope::V3(
                 EngineApiClient::get_payload_v3(&self.engine_api_client, payload_id).await?,
             )),
@@ -110,6 +116,12 @@ impl EngineApi {
 
     pub async fn new_payload(&self, payload: NewPayload) -> eyre::Result<PayloadStatus> {
         match payload {
+            NewPayload::V1(payload) => {
+                Ok(EngineApiClient::new_payload_v1(&self.engine_api_client, payload).await?)
+            }
+            NewPayload::V2(payload) => {
+                Ok(EngineApiClient::new_payload_v2(&self.engine_api_client, payload).await?)
+            }
             NewPayload::V3(new_payload) => Ok(EngineApiClient::new_payload_v3(
                 &self.engine_api_client,
                 new_payload.payload,
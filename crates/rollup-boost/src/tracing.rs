@@ -5,12 +5,15 @@ use opentelemetry::{KeyValue, global};
 use opentelemetry_otlp::WithExportConfig;
 use opentelemetry_sdk::trace::SpanProcessor;
 use opentelemetry_sdk::{Resource, propagation::TraceContextPropagator};
+use std::sync::OnceLock;
+use tracing::Subscriber;
 use tracing::level_filters::LevelFilter;
 use tracing_opentelemetry::OpenTelemetryLayer;
-use tracing_subscriber::Layer;
 use tracing_subscriber::filter::Targets;
 use tracing_subscriber::fmt::writer::BoxMakeWriter;
 use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{EnvFilter, Layer, reload};
 
 use crate::cli::{Args, LogFormat};
 
@@ -22,6 +25,55 @@ use crate::cli::{Args, LogFormat};
 pub const SPAN_ATTRIBUTE_LABELS: [&str; 4] =
     ["code", "payload_source", "method", "builder_has_payload"];
 
+type ReloadLogFilter = Box<dyn Fn(EnvFilter) -> Result<(), reload::Error> + Send + Sync>;
+
+/// Reload handle for the log filter, set once the global subscriber is installed.
+static LOG_FILTER_RELOAD: OnceLock<ReloadLogFilter> = OnceLock::new();
+
+/// Replaces the filter applied to the log output with the given `EnvFilter` directives
+/// (e.g. `info,rollup_boost::flashblocks=debug`).
+pub fn set_log_filter(directives: &str) -> eyre::Result<()> {
+    let filter = EnvFilter::try_new(directives).context("Invalid log filter")?;
+    let reload = LOG_FILTER_RELOAD
+        .get()
+        .ok_or_else(|| eyre::eyre!("Log filter reloading is not available"))?;
+    reload(filter).context("Failed to reload log filter")
+}
+
+/// Creates a reloadable log filter and registers its handle for [`set_log_filter`].
+fn reloadable_log_filter<S>(directives: &str) -> eyre::Result<reload::Layer<EnvFilter, S>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a> + 'static,
+{
+    let filter = EnvFilter::try_new(directives).context("Invalid log filter")?;
+    let (layer, handle) = reload::Layer::new(filter);
+    let _ = LOG_FILTER_RELOAD.set(Box::new(move |filter| handle.reload(filter)));
+    Ok(layer)
+}
+
+/// Layer serving the tokio tasks to tokio-console, if `--tokio-console-addr` is set.
+fn console_layer<S>(args: &Args) -> eyre::Result<Option<Box<dyn Layer<S> + Send + Sync>>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a> + 'static,
+{
+    let Some(addr) = args.tokio_console_addr else {
+        return Ok(None);
+    };
+
+    #[cfg(feature = "tokio-console")]
+    {
+        let layer = console_subscriber::ConsoleLayer::builder()
+            .server_addr(addr)
+            .spawn();
+        Ok(Some(layer.boxed()))
+    }
+
+    #[cfg(not(feature = "tokio-console"))]
+    eyre::bail!(
+        "cannot serve tokio-console on {addr}, rollup-boost was built without the tokio-console feature"
+    )
+}
+
 /// Custom span processor that records span durations as histograms
 #[derive(Debug)]
 struct MetricsSpanProcessor;
@@ -103,15 +155,24 @@ pub fn init_tracing(args: &Args) -> eyre::Result<()> {
     // Be cautious with snake_case and kebab-case here
     let filter_name = "rollup_boost".to_string();
 
-    let global_filter = Targets::new()
+    let mut global_filter = Targets::new()
         .with_default(LevelFilter::INFO)
         .with_target(&filter_name, LevelFilter::TRACE);
+    if args.tokio_console_addr.is_some() {
+        // tokio-console is fed the spans and events of the runtime
+        global_filter = global_filter
+            .with_target("tokio", LevelFilter::TRACE)
+            .with_target("runtime", LevelFilter::TRACE);
+    }
 
-    let registry = tracing_subscriber::registry().with(global_filter);
+    let registry = tracing_subscriber::registry()
+        .with(console_layer(args)?)
+        .with(global_filter);
 
-    let log_filter = Targets::new()
-        .with_default(LevelFilter::INFO)
-        .with_target(&filter_name, args.log_level);
+    let log_filter = format!(
+        "info,{filter_name}={}",
+        args.log_level.as_str().to_lowercase()
+    );
 
     let writer = if let Some(path) = &args.log_file {
         let file = std::fs::OpenOptions::new()
@@ -162,7 +223,7 @@ pub fn init_tracing(args: &Args) -> eyre::Result<()> {
                             .json()
                             .with_ansi(false)
                             .with_writer(writer)
-                            .with_filter(log_filter.clone()),
+                            .with_filter(reloadable_log_filter(&log_filter)?),
                     ),
                 )?;
             }
@@ -172,7 +233,7 @@ pub fn init_tracing(args: &Args) -> eyre::Result<()> {
                         tracing_subscriber::fmt::layer()
                             .with_ansi(false)
                             .with_writer(writer)
-                            .with_filter(log_filter.clone()),
+                            .with_filter(reloadable_log_filter(&log_filter)?),
                     ),
                 )?;
             }
@@ -186,7 +247,7 @@ pub fn init_tracing(args: &Args) -> eyre::Result<()> {
                             .json()
                             .with_ansi(false)
                             .with_writer(writer)
-                            .with_filter(log_filter.clone()),
+                            .with_filter(reloadable_log_filter(&log_filter)?),
                     ),
                 )?;
             }
@@ -196,7 +257,7 @@ pub fn init_tracing(args: &Args) -> eyre::Result<()> {
                         tracing_subscriber::fmt::layer()
                             .with_ansi(false)
                             .with_writer(writer)
-                            .with_filter(log_filter.clone()),
+                            .with_filter(reloadable_log_filter(&log_filter)?),
                     ),
                 )?;
             }
@@ -205,3 +266,26 @@ pub fn init_tracing(args: &Args) -> eyre::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::Level;
+    use tracing_subscriber::Registry;
+
+    #[test]
+    fn test_set_log_filter() {
+        let layer = reloadable_log_filter::<Registry>("info").unwrap();
+        let subscriber = Registry::default().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(!tracing::enabled!(Level::DEBUG));
+            set_log_filter("debug").unwrap();
+            assert!(tracing::enabled!(Level::DEBUG));
+
+            // Invalid directives leave the filter in place
+            assert!(set_log_filter("rollup_boost=loud").is_err());
+            assert!(tracing::enabled!(Level::DEBUG));
+        });
+    }
+}
@@ -0,0 +1,212 @@
+use std::{path::PathBuf, time::Instant};
+
+use alloy_rpc_types_engine::{JwtSecret, PayloadStatusEnum};
+use clap::Parser;
+use http::Uri;
+use metrics::{counter, histogram};
+use op_alloy_rpc_types_engine::OpExecutionPayloadEnvelope;
+use tracing::warn;
+
+use crate::{NewPayload, PayloadSource, RpcClient, RpcClientError, SharedJwtSecret};
+
+#[derive(Parser, Clone, Debug)]
+pub struct ValidatorArgs {
+    /// Auth server address of a dedicated execution client re-executing the builder
+    /// payloads before they are returned. Builder payloads are not re-executed if not set
+    #[arg(long, env)]
+    pub validator_url: Option<Uri>,
+
+    /// Hex encoded JWT secret of the validation client, the L2 client secret is used if
+    /// neither this nor the path is set
+    #[arg(long, env, value_name = "HEX")]
+    pub validator_jwt_token: Option<JwtSecret>,
+
+    /// Path to the JWT secret of the validation client
+    #[arg(long, env, value_name = "PATH")]
+    pub validator_jwt_path: Option<PathBuf>,
+
+    /// Timeout for the re-execution of a payload in milliseconds
+    #[arg(long, env, default_value_t = 1000)]
+    pub validator_timeout: u64,
+}
+
+/// Outcome of the re-execution of a builder payload by the validation client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Revalidation {
+    Valid,
+    /// The validation client deems the payload invalid, with its validation error
+    Invalid(String),
+    /// The validation client could not tell, as it is syncing or failed to respond
+    Unverified(&'static str),
+}
+
+impl Revalidation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Revalidation::Valid => "valid",
+            Revalidation::Invalid(_) => "invalid",
+            Revalidation::Unverified(reason) => reason,
+        }
+    }
+}
+
+/// Re-executes the builder payloads with `new_payload` on a dedicated execution client
+/// before they are returned, so that a payload accepted by the L2 client but deemed
+/// invalid by another implementation or version is never proposed. The validation client
+/// must follow the chain on its own, e.g. as a follower node of the sequencer, as only
+/// the payloads are sent to it.
+#[derive(Clone)]
+pub struct PayloadValidator {
+    client: RpcClient,
+}
+
+impl PayloadValidator {
+    pub fn new(client: RpcClient) -> Self {
+        Self { client }
+    }
+
+    /// The validator of the validation client, if any, authenticated with the secret of
+    /// the L2 client unless it has its own.
+    pub fn from_args(args: &ValidatorArgs, l2_jwt: &SharedJwtSecret) -> eyre::Result<Option<Self>> {
+        let Some(url) = args.validator_url.clone() else {
+            return Ok(None);
+        };
+        let secret = if let Some(secret) = args.validator_jwt_token {
+            SharedJwtSecret::new(secret)
+        } else if let Some(path) = args.validator_jwt_path.as_ref() {
+            SharedJwtSecret::from_file(path)?
+        } else {
+            l2_jwt.clone()
+        };
+        let client = RpcClient::new(url, secret, args.validator_timeout, PayloadSource::L2)?;
+        Ok(Some(Self::new(client)))
+    }
+
+    /// Re-executes `payload`, recording the outcome and the time it took.
+    pub async fn validate(&self, payload: &OpExecutionPayloadEnvelope) -> Revalidation {
+        let started = Instant::now();
+        let revalidation = match self
+            .client
+            .new_payload(NewPayload::from(payload.clone()))
+            .await
+        {
+            Ok(status) => match status.status {
+                PayloadStatusEnum::Valid => Revalidation::Valid,
+                PayloadStatusEnum::Invalid { validation_error } => {
+                    Revalidation::Invalid(validation_error)
+                }
+                PayloadStatusEnum::Syncing => Revalidation::Unverified("syncing"),
+                PayloadStatusEnum::Accepted => Revalidation::Unverified("accepted"),
+            },
+            Err(RpcClientError::InvalidPayload(status)) => Revalidation::Invalid(status),
+            Err(e) => {
+                warn!(message = "Failed to re-execute builder payload", error = %e);
+                Revalidation::Unverified("error")
+            }
+        };
+        histogram!("validator.latency").record(started.elapsed());
+        counter!("validator.payloads", "outcome" => revalidation.as_str()).increment(1);
+        revalidation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use alloy_primitives::{B256, U256};
+    use alloy_rpc_types_engine::{
+        BlobsBundleV1, ExecutionPayloadV1, ExecutionPayloadV2, ExecutionPayloadV3, PayloadStatus,
+    };
+    use jsonrpsee::{
+        RpcModule,
+        server::{ServerBuilder, ServerHandle},
+    };
+    use op_alloy_rpc_types_engine::OpExecutionPayloadEnvelopeV3;
+
+    use super::*;
+
+    async fn validation_client(status: PayloadStatusEnum) -> (ServerHandle, SocketAddr) {
+        let server = ServerBuilder::default().build("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let mut module = RpcModule::new(());
+        module
+            .register_method("engine_newPayloadV3", move |_, _, _| {
+                PayloadStatus::from_status(status.clone())
+            })
+            .unwrap();
+        (server.start(module), addr)
+    }
+
+    fn payload() -> OpExecutionPayloadEnvelope {
+        OpExecutionPayloadEnvelope::V3(OpExecutionPayloadEnvelopeV3 {
+            execution_payload: ExecutionPayloadV3 {
+                payload_inner: ExecutionPayloadV2 {
+                    payload_inner: ExecutionPayloadV1 {
+                        parent_hash: B256::ZERO,
+                        fee_recipient: Default::default(),
+                        state_root: B256::ZERO,
+                        receipts_root: B256::ZERO,
+                        logs_bloom: Default::default(),
+                        prev_randao: B256::ZERO,
+                        block_number: 1,
+                        gas_limit: 30_000_000,
+                        gas_used: 0,
+                        timestamp: 1,
+                        extra_data: Default::default(),
+                        base_fee_per_gas: U256::from(1),
+                        block_hash: B256::ZERO,
+                        transactions: vec![],
+                    },
+                    withdrawals: vec![],
+                },
+                blob_gas_used: 0,
+                excess_blob_gas: 0,
+            },
+            block_value: U256::ZERO,
+            blobs_bundle: BlobsBundleV1::default(),
+            should_override_builder: false,
+            parent_beacon_block_root: B256::ZERO,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_payload_validator() -> eyre::Result<()> {
+        let secret = SharedJwtSecret::new(JwtSecret::random());
+        for (status, expected) in [
+            (PayloadStatusEnum::Valid, "valid"),
+            (
+                PayloadStatusEnum::Invalid {
+                    validation_error: "bad state root".to_string(),
+                },
+                "invalid",
+            ),
+            (PayloadStatusEnum::Syncing, "syncing"),
+        ] {
+            let (handle, addr) = validation_client(status).await;
+            let args = ValidatorArgs {
+                validator_url: Some(format!("http://{addr}").parse()?),
+                validator_jwt_token: None,
+                validator_jwt_path: None,
+                validator_timeout: 1000,
+            };
+            let validator = PayloadValidator::from_args(&args, &secret)?.unwrap();
+            assert_eq!(validator.validate(&payload()).await.as_str(), expected);
+            handle.stop()?;
+        }
+
+        // An unreachable validation client cannot tell
+        let args = ValidatorArgs {
+            validator_url: Some("http://127.0.0.1:1".parse()?),
+            validator_jwt_token: None,
+            validator_jwt_path: None,
+            validator_timeout: 1000,
+        };
+        let validator = PayloadValidator::from_args(&args, &secret)?.unwrap();
+        assert_eq!(
+            validator.validate(&payload()).await,
+            Revalidation::Unverified("error")
+        );
+        Ok(())
+    }
+}
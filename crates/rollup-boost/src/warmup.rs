@@ -0,0 +1,142 @@
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use metrics::gauge;
+use parking_lot::Mutex;
+use tracing::info;
+
+#[derive(Parser, Clone, Debug)]
+pub struct WarmupArgs {
+    /// Number of consecutive valid builder payloads required, after startup or after the
+    /// builder failed to return a payload, before builder payloads are returned. Until
+    /// then, builder payloads are compared with the l2 payload as in shadow mode
+    #[arg(long, env)]
+    pub builder_warmup_blocks: Option<u64>,
+
+    /// Minimum duration in seconds of the warm-up, after startup or after the builder
+    /// failed to return a payload
+    #[arg(long, env)]
+    pub builder_warmup_secs: Option<u64>,
+}
+
+#[derive(Debug)]
+struct WarmupState {
+    started_at: Instant,
+    // Builder payloads validated in a row since the warm-up started
+    valid_blocks: u64,
+    complete: bool,
+}
+
+/// Runs rollup-boost in shadow mode after startup, and after the builder failed to
+/// return a payload, until the builder returned enough valid payloads in a row for long
+/// enough, so that a builder that just started or reconnected does not propose blocks
+/// before it proved consistent. An invalid payload restarts the count.
+#[derive(Debug)]
+pub struct BuilderWarmup {
+    blocks: u64,
+    duration: Duration,
+    state: Mutex<WarmupState>,
+}
+
+impl BuilderWarmup {
+    pub fn new(blocks: u64, duration: Duration) -> Self {
+        gauge!("builder.warming_up").set(1);
+        Self {
+            blocks,
+            duration,
+            state: Mutex::new(WarmupState {
+                started_at: Instant::now(),
+                valid_blocks: 0,
+                complete: false,
+            }),
+        }
+    }
+
+    pub fn from_args(args: &WarmupArgs) -> Option<Self> {
+        if args.builder_warmup_blocks.is_none() && args.builder_warmup_secs.is_none() {
+            return None;
+        }
+        Some(Self::new(
+            args.builder_warmup_blocks.unwrap_or_default(),
+            Duration::from_secs(args.builder_warmup_secs.unwrap_or_default()),
+        ))
+    }
+
+    /// Starts the warm-up again, e.g. once the builder failed to return a payload.
+    pub fn restart(&self) {
+        let mut state = self.state.lock();
+        if state.complete {
+            info!(message = "builder warm-up restarted");
+        }
+        *state = WarmupState {
+            started_at: Instant::now(),
+            valid_blocks: 0,
+            complete: false,
+        };
+        gauge!("builder.warming_up").set(1);
+    }
+
+    /// Records whether a builder payload passed validation.
+    pub fn record(&self, valid: bool) {
+        let mut state = self.state.lock();
+        state.valid_blocks = match valid {
+            true => state.valid_blocks + 1,
+            false => 0,
+        };
+    }
+
+    /// Whether builder payloads must not be returned yet.
+    pub fn is_warming_up(&self) -> bool {
+        self.is_warming_up_at(Instant::now())
+    }
+
+    fn is_warming_up_at(&self, now: Instant) -> bool {
+        let mut state = self.state.lock();
+        if state.complete {
+            return false;
+        }
+        if state.valid_blocks < self.blocks || now < state.started_at + self.duration {
+            return true;
+        }
+        state.complete = true;
+        gauge!("builder.warming_up").set(0);
+        info!(
+            message = "builder warm-up complete, returning builder payloads",
+            valid_blocks = state.valid_blocks,
+            elapsed_secs = now.duration_since(state.started_at).as_secs(),
+        );
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warmup_blocks() {
+        let warmup = BuilderWarmup::new(2, Duration::ZERO);
+        assert!(warmup.is_warming_up());
+        warmup.record(true);
+        warmup.record(false);
+        warmup.record(true);
+        assert!(warmup.is_warming_up());
+        warmup.record(true);
+        assert!(!warmup.is_warming_up());
+
+        // Invalid payloads are left to the other safeguards once warmed up
+        warmup.record(false);
+        assert!(!warmup.is_warming_up());
+
+        warmup.restart();
+        assert!(warmup.is_warming_up());
+    }
+
+    #[test]
+    fn test_warmup_duration() {
+        let warmup = BuilderWarmup::new(0, Duration::from_secs(60));
+        let now = Instant::now();
+        assert!(warmup.is_warming_up_at(now));
+        assert!(!warmup.is_warming_up_at(now + Duration::from_secs(61)));
+    }
+}
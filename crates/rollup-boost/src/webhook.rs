@@ -0,0 +1,292 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use alloy_primitives::bytes::Bytes;
+use clap::Parser;
+use http::{Method, Request, header::CONTENT_TYPE};
+use http_body_util::Full;
+use hyper_rustls::HttpsConnector;
+use hyper_util::{
+    client::legacy::{Client, connect::HttpConnector},
+    rt::TokioExecutor,
+};
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+use url::Url;
+
+/// Number of notifications waiting to be delivered before new ones are dropped.
+const QUEUE_SIZE: usize = 256;
+
+#[derive(Parser, Clone, Debug)]
+pub struct WebhookArgs {
+    /// URLs notified with a POST request on critical events, e.g. a Slack incoming
+    /// webhook or the PagerDuty events API
+    #[arg(long, env, value_delimiter = ',')]
+    pub webhook_urls: Vec<Url>,
+
+    /// Events the webhooks are notified of, all of them if not set
+    #[arg(long, env, value_delimiter = ',')]
+    pub webhook_events: Vec<WebhookEvent>,
+
+    /// Path to a JSON template of the body of the notifications. `{{event}}`,
+    /// `{{message}}` and `{{timestamp}}` are replaced in its strings, and a string
+    /// that is exactly `{{details}}` is replaced with the details of the event
+    #[arg(long, env, value_name = "PATH")]
+    pub webhook_template_path: Option<PathBuf>,
+
+    /// Timeout in seconds of a webhook request
+    #[arg(long, env, default_value = "5")]
+    pub webhook_timeout: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum WebhookError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("invalid webhook template: {0}")]
+    Template(#[from] serde_json::Error),
+}
+
+/// Critical events that webhooks are notified of.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// Blocks are built by the L2 client because the builder failed to return a payload
+    BuilderFailover,
+    /// The connection to the flashblocks stream of the builder was lost
+    FlashblocksStreamLost,
+    /// A builder payload was rejected by the payload attributes or the L2 client
+    PayloadValidationFailed,
+    /// The execution mode was changed through the debug API
+    ExecutionModeChanged,
+}
+
+impl WebhookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::BuilderFailover => "builder_failover",
+            WebhookEvent::FlashblocksStreamLost => "flashblocks_stream_lost",
+            WebhookEvent::PayloadValidationFailed => "payload_validation_failed",
+            WebhookEvent::ExecutionModeChanged => "execution_mode_changed",
+        }
+    }
+}
+
+/// A notification of a critical event. Without a template, it is the body of the
+/// webhook requests.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WebhookNotification {
+    pub event: WebhookEvent,
+    pub message: String,
+    /// Unix timestamp of the event, in seconds
+    pub timestamp: u64,
+    pub details: Value,
+}
+
+impl WebhookNotification {
+    pub fn new(event: WebhookEvent, message: String, details: Value) -> Self {
+        Self {
+            event,
+            message,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            details,
+        }
+    }
+
+    /// Renders the body of the webhook requests with `template`, if any.
+    pub fn render(&self, template: Option<&Value>) -> Value {
+        match template {
+            Some(template) => self.render_value(template),
+            None => serde_json::to_value(self).unwrap_or_default(),
+        }
+    }
+
+    fn render_value(&self, template: &Value) -> Value {
+        match template {
+            Value::String(s) if s == "{{details}}" => self.details.clone(),
+            Value::String(s) => Value::String(
+                s.replace("{{event}}", self.event.as_str())
+                    .replace("{{message}}", &self.message)
+                    .replace("{{timestamp}}", &self.timestamp.to_string()),
+            ),
+            Value::Array(values) => {
+                Value::Array(values.iter().map(|v| self.render_value(v)).collect())
+            }
+            Value::Object(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(k, v)| (k.clone(), self.render_value(v)))
+                    .collect(),
+            ),
+            value => value.clone(),
+        }
+    }
+}
+
+/// Notifies webhooks of critical events, so that alerts reach PagerDuty or Slack without
+/// scraping the logs.
+///
+/// Notifications are delivered in the background and never block the engine API. They
+/// are dropped if the webhooks cannot keep up, and failed deliveries are not retried.
+#[derive(Debug)]
+pub struct WebhookNotifier {
+    events: Vec<WebhookEvent>,
+    sender: mpsc::Sender<WebhookNotification>,
+}
+
+impl WebhookNotifier {
+    /// Starts delivering the notifications to `urls`. Must be called from a tokio runtime.
+    pub fn new(
+        urls: Vec<Url>,
+        events: Vec<WebhookEvent>,
+        template: Option<Value>,
+        timeout: Duration,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+        tokio::spawn(deliver(urls, template, timeout, receiver));
+        Self { events, sender }
+    }
+
+    pub fn from_args(args: &WebhookArgs) -> Result<Option<Self>, WebhookError> {
+        if args.webhook_urls.is_empty() {
+            return Ok(None);
+        }
+        let template = args
+            .webhook_template_path
+            .as_deref()
+            .map(load_template)
+            .transpose()?;
+        Ok(Some(Self::new(
+            args.webhook_urls.clone(),
+            args.webhook_events.clone(),
+            template,
+            Duration::from_secs(args.webhook_timeout),
+        )))
+    }
+
+    /// Queues a notification of `event`, unless the webhooks are not notified of it.
+    pub fn notify(&self, event: WebhookEvent, message: impl Into<String>, details: Value) {
+        if !self.events.is_empty() && !self.events.contains(&event) {
+            return;
+        }
+        let notification = WebhookNotification::new(event, message.into(), details);
+        if self.sender.try_send(notification).is_err() {
+            counter!("webhooks.dropped", "event" => event.as_str()).increment(1);
+            warn!(
+                message = "webhook queue is full, dropping notification",
+                event = event.as_str()
+            );
+        }
+    }
+}
+
+fn load_template(path: &Path) -> Result<Value, WebhookError> {
+    Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
+
+async fn deliver(
+    urls: Vec<Url>,
+    template: Option<Value>,
+    timeout: Duration,
+    mut receiver: mpsc::Receiver<WebhookNotification>,
+) {
+    let connector = match hyper_rustls::HttpsConnectorBuilder::new().with_native_roots() {
+        Ok(builder) => builder.https_or_http().enable_http1().build(),
+        Err(e) => {
+            warn!(message = "failed to load the native root certificates, webhooks are disabled", error = %e);
+            return;
+        }
+    };
+    let client: Client<HttpsConnector<HttpConnector>, Full<Bytes>> =
+        Client::builder(TokioExecutor::new()).build(connector);
+
+    while let Some(notification) = receiver.recv().await {
+        let body = Bytes::from(notification.render(template.as_ref()).to_string());
+        let event = notification.event.as_str();
+        for url in &urls {
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri(url.as_str())
+                .header(CONTENT_TYPE, "application/json")
+                .body(Full::new(body.clone()));
+            let request = match request {
+                Ok(request) => request,
+                Err(e) => {
+                    warn!(message = "invalid webhook request", %url, error = %e);
+                    continue;
+                }
+            };
+
+            let status = match tokio::time::timeout(timeout, client.request(request)).await {
+                Ok(Ok(response)) if response.status().is_success() => {
+                    debug!(message = "webhook notified", %url, event);
+                    "ok"
+                }
+                Ok(Ok(response)) => {
+                    warn!(message = "webhook rejected the notification", %url, event, status = %response.status());
+                    "error"
+                }
+                Ok(Err(e)) => {
+                    warn!(message = "failed to notify webhook", %url, event, error = %e);
+                    "error"
+                }
+                Err(_) => {
+                    warn!(message = "webhook request timed out", %url, event);
+                    "error"
+                }
+            };
+            counter!("webhooks.deliveries", "event" => event, "status" => status).increment(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render_template() {
+        let notification = WebhookNotification {
+            event: WebhookEvent::ExecutionModeChanged,
+            message: "execution mode changed to dry_run".to_string(),
+            timestamp: 1_700_000_000,
+            details: json!({"old_mode": "enabled", "new_mode": "dry_run"}),
+        };
+
+        assert_eq!(
+            notification.render(None),
+            json!({
+                "event": "execution_mode_changed",
+                "message": "execution mode changed to dry_run",
+                "timestamp": 1_700_000_000,
+                "details": {"old_mode": "enabled", "new_mode": "dry_run"},
+            })
+        );
+
+        let template = json!({
+            "text": "rollup-boost {{event}} at {{timestamp}}: {{message}}",
+            "payload": {"severity": "critical", "custom_details": "{{details}}"},
+        });
+        assert_eq!(
+            notification.render(Some(&template)),
+            json!({
+                "text": "rollup-boost execution_mode_changed at 1700000000: execution mode changed to dry_run",
+                "payload": {
+                    "severity": "critical",
+                    "custom_details": {"old_mode": "enabled", "new_mode": "dry_run"},
+                },
+            })
+        );
+    }
+}
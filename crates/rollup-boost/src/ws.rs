@@ -0,0 +1,283 @@
+use std::{
+    net::SocketAddr,
+    path::PathBuf,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use alloy_primitives::bytes::Bytes;
+use alloy_rpc_types_engine::{JwtError, JwtSecret};
+use clap::Parser;
+use futures::{FutureExt as _, SinkExt as _, StreamExt as _, TryFutureExt as _};
+use http::{
+    HeaderMap, HeaderValue,
+    header::{
+        AUTHORIZATION, CONNECTION, CONTENT_TYPE, SEC_WEBSOCKET_EXTENSIONS, SEC_WEBSOCKET_KEY,
+        SEC_WEBSOCKET_PROTOCOL, SEC_WEBSOCKET_VERSION, UPGRADE,
+    },
+};
+use http_body_util::{BodyExt as _, Full};
+use hyper::body::Body;
+use jsonrpsee::{
+    core::BoxError,
+    http_client::HttpResponse,
+    server::{HttpBody, ServerHandle, StopHandle, stop_channel},
+};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::{
+    accept_hdr_async,
+    tungstenite::{
+        Message,
+        handshake::server::{
+            ErrorResponse, Request as HandshakeRequest, Response as HandshakeResponse,
+        },
+        http::StatusCode,
+    },
+};
+use tower::{Layer, Service, ServiceExt as _};
+use tracing::{debug, warn};
+
+use crate::{RemoteAddr, Request, Response};
+
+#[derive(Parser, Clone, Debug)]
+pub struct WsArgs {
+    /// Port to serve the Engine API over websocket on, disabled if not set
+    #[arg(long, env)]
+    pub rpc_ws_port: Option<u16>,
+
+    /// Hex encoded JWT secret authenticating websocket connections. Defaults to the L2
+    /// client JWT secret
+    #[arg(long, env, value_name = "HEX")]
+    pub rpc_ws_jwt_token: Option<JwtSecret>,
+
+    /// Path to the JWT secret authenticating websocket connections
+    #[arg(long, env, value_name = "PATH")]
+    pub rpc_ws_jwt_path: Option<PathBuf>,
+}
+
+impl WsArgs {
+    /// JWT secret of the websocket server, falling back to `default` if none is configured.
+    pub fn jwt_secret(&self, default: JwtSecret) -> eyre::Result<JwtSecret> {
+        if let Some(secret) = self.rpc_ws_jwt_token {
+            Ok(secret)
+        } else if let Some(path) = self.rpc_ws_jwt_path.as_ref() {
+            Ok(JwtSecret::from_file(path)?)
+        } else {
+            Ok(default)
+        }
+    }
+}
+
+/// A layer rejecting the requests that do not carry a valid JWT bearer token, as
+/// described in <https://github.com/ethereum/execution-apis/blob/main/src/engine/authentication.md>.
+#[derive(Clone, Debug)]
+pub struct JwtValidationLayer {
+    secret: JwtSecret,
+}
+
+impl JwtValidationLayer {
+    pub const fn new(secret: JwtSecret) -> Self {
+        Self { secret }
+    }
+}
+
+impl<S> Layer<S> for JwtValidationLayer {
+    type Service = JwtValidationService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        JwtValidationService {
+            inner,
+            secret: self.secret,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct JwtValidationService<S> {
+    inner: S,
+    secret: JwtSecret,
+}
+
+impl<S> Service<Request> for JwtValidationService<S>
+where
+    S: Service<Request, Response = Response> + Send + Sync + Clone + 'static,
+    S::Response: 'static,
+    S::Error: Into<BoxError> + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = BoxError;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        if let Err(e) = validate_bearer(&self.secret, request.headers()) {
+            warn!(target: "rollup_boost::security", message = "rejecting unauthenticated request", %e);
+            return futures::future::ready(Ok(unauthorized())).boxed();
+        }
+
+        self.inner.call(request).map_err(Into::into).boxed()
+    }
+}
+
+pub(crate) fn validate_bearer(secret: &JwtSecret, headers: &HeaderMap) -> Result<(), JwtError> {
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(JwtError::MissingOrInvalidAuthorizationHeader)?;
+
+    secret.validate(token)
+}
+
+pub(crate) fn unauthorized() -> Response {
+    HttpResponse::builder()
+        .status(401)
+        .body(HttpBody::from("Unauthorized"))
+        .expect("Failed to create unauthorized response")
+}
+
+/// Serves JSON-RPC over websocket on `listener`, authenticating the connections with JWT.
+///
+/// Every message is passed as a request to the service returned by `make_service`, so
+/// that the websocket clients go through the same HTTP middleware as the http server:
+/// the proxy to the upstreams, the rate limits and the RPC logging.
+pub fn spawn_ws_server<S, B>(
+    listener: TcpListener,
+    secret: JwtSecret,
+    make_service: impl FnOnce(StopHandle) -> S,
+) -> ServerHandle
+where
+    S: Service<Request, Response = http::Response<B>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    B: Body<Data = Bytes> + Send + 'static,
+    B::Error: Into<BoxError>,
+{
+    let (stop_handle, server_handle) = stop_channel();
+    let service = make_service(stop_handle.clone());
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, remote_addr) = tokio::select! {
+                res = listener.accept() => match res {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        warn!(message = "failed to accept websocket connection", %e);
+                        continue;
+                    }
+                },
+                _ = stop_handle.clone().shutdown() => break,
+            };
+
+            let service = service.clone();
+            let stop_handle = stop_handle.clone();
+            tokio::spawn(async move {
+                tokio::select! {
+                    res = serve_ws_connection(stream, remote_addr, secret, service) => {
+                        if let Err(e) = res {
+                            debug!(message = "websocket connection closed", %remote_addr, %e);
+                        }
+                    }
+                    _ = stop_handle.shutdown() => {}
+                }
+            });
+        }
+    });
+
+    server_handle
+}
+
+async fn serve_ws_connection<S, B>(
+    stream: TcpStream,
+    remote_addr: SocketAddr,
+    secret: JwtSecret,
+    mut service: S,
+) -> Result<(), BoxError>
+where
+    S: Service<Request, Response = http::Response<B>>,
+    S::Error: Into<BoxError>,
+    B: Body<Data = Bytes>,
+    B::Error: Into<BoxError>,
+{
+    let mut headers = HeaderMap::new();
+    let handshake = |request: &HandshakeRequest, response: HandshakeResponse| {
+        if let Err(e) = validate_bearer(&secret, request.headers()) {
+            warn!(target: "rollup_boost::security", message = "rejecting unauthenticated websocket connection", %e);
+            let mut rejection = ErrorResponse::new(Some("Unauthorized".to_string()));
+            *rejection.status_mut() = StatusCode::UNAUTHORIZED;
+            return Err(rejection);
+        }
+        headers = request.headers().clone();
+        Ok(response)
+    };
+    let mut ws = accept_hdr_async(stream, handshake).await?;
+
+    // The messages are sent as plain JSON-RPC requests, carrying the headers of the
+    // handshake such as the correlation id or the forwarding proxies
+    for header in [
+        CONNECTION,
+        UPGRADE,
+        SEC_WEBSOCKET_KEY,
+        SEC_WEBSOCKET_VERSION,
+        SEC_WEBSOCKET_EXTENSIONS,
+        SEC_WEBSOCKET_PROTOCOL,
+    ] {
+        headers.remove(header);
+    }
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+    while let Some(message) = ws.next().await {
+        let body = match message? {
+            Message::Text(text) => HttpBody::new(Full::from(text.to_string())),
+            Message::Binary(bytes) => HttpBody::new(Full::new(bytes)),
+            Message::Close(_) => break,
+            Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => continue,
+        };
+        let mut request = http::Request::post("/").body(body)?;
+        *request.headers_mut() = headers.clone();
+        request.extensions_mut().insert(RemoteAddr(remote_addr));
+
+        let response = service
+            .ready()
+            .await
+            .map_err(Into::into)?
+            .call(request)
+            .await
+            .map_err(Into::into)?;
+        let body = response.into_body().collect().await.map_err(Into::into)?;
+        let body = body.to_bytes();
+
+        // Notifications have no response
+        if !body.is_empty() {
+            ws.send(Message::text(String::from_utf8(body.to_vec())?))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secret_to_bearer_header;
+
+    #[test]
+    fn test_validate_bearer() {
+        let secret = JwtSecret::random();
+
+        let mut headers = HeaderMap::new();
+        assert!(validate_bearer(&secret, &headers).is_err());
+
+        headers.insert(AUTHORIZATION, secret_to_bearer_header(&secret));
+        assert!(validate_bearer(&secret, &headers).is_ok());
+
+        headers.insert(AUTHORIZATION, secret_to_bearer_header(&JwtSecret::random()));
+        assert!(validate_bearer(&secret, &headers).is_err());
+    }
+}